@@ -0,0 +1,83 @@
+//! Refresh scan efficiency: sampling the map versus popping the expiry index.
+//!
+//! The map holds 200k entries of which 1% are past their refresh deadline. Each call
+//! looks for one expired entry; entries handed out are made stale again right away so
+//! the expired share stays at 1% for the whole run. Hit ratios over a warm-up of calls
+//! are printed before the timings.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use advcache::config::{self, LifetimeRule, Rule};
+use advcache::db::storage::Map;
+use advcache::model::Entry;
+use advcache::time;
+use criterion::{criterion_group, criterion_main, Criterion};
+use tokio_util::sync::CancellationToken;
+
+const ENTRIES: usize = 200_000;
+const EXPIRED_EVERY: usize = 100;
+const SAMPLE: usize = 32;
+const TTL: Duration = Duration::from_secs(600);
+
+fn stale_at() -> i64 {
+    time::unix_nano() - 2 * TTL.as_nanos() as i64
+}
+
+fn populated_map() -> Map<Entry> {
+    let mut rule = Rule::bare("/api/v1/user");
+    rule.refresh = Some(LifetimeRule {
+        enabled: true,
+        ttl: Some(TTL),
+        beta: Some(1000.0),
+        coefficient: Some(0.5),
+    });
+    let rule = Arc::new(rule);
+
+    let map = Map::new(CancellationToken::new(), config::new_test_config());
+    let now = time::unix_nano();
+    for id in 0..ENTRIES {
+        let queries = vec![(b"id".to_vec(), id.to_string().into_bytes())];
+        let entry = Entry::new(rule.clone(), &queries, &[]);
+        entry.set_refreshed_at_for_tests(if id % EXPIRED_EVERY == 0 { stale_at() } else { now });
+        map.set(entry.key(), entry);
+    }
+    map
+}
+
+fn hit_ratio(mut scan: impl FnMut() -> bool) -> f64 {
+    const CALLS: usize = 10_000;
+    let hits = (0..CALLS).filter(|_| scan()).count();
+    hits as f64 / CALLS as f64
+}
+
+fn bench_expired_scan(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().expect("runtime");
+    let _rt = rt.enter();
+    let _clock = time::start(Duration::from_millis(1));
+
+    let map = populated_map();
+    let sampling = || map.peek_expired(SAMPLE).is_some();
+    let index = || match map.next_due(SAMPLE) {
+        Some(entry) => {
+            entry.set_refreshed_at_for_tests(stale_at());
+            map.reschedule(&entry);
+            true
+        }
+        None => false,
+    };
+
+    eprintln!(
+        "hit ratio at 1% expired: sampling {:.3}, expiry index {:.3}",
+        hit_ratio(sampling),
+        hit_ratio(index)
+    );
+
+    let mut group = c.benchmark_group("expired_scan_1pct");
+    group.bench_function("sampling", |b| b.iter(sampling));
+    group.bench_function("expiry_index", |b| b.iter(index));
+    group.finish();
+}
+
+criterion_group!(benches, bench_expired_scan);
+criterion_main!(benches);
@@ -0,0 +1,99 @@
+//! Request hot path through the cache router: a hit, and a miss carrying headers outside the
+//! key whitelist. Hits are also run with `storage.track_access_time` on, stamping the access
+//! time at the default granularity (`hit_access_coarse`) and on every hit (`hit_access_every`).
+//! Hits count in the counters of entries, kept on through the eviction audit, except in
+//! `hit_no_counter`; `hit_sampled` counts one hit in four with `storage.sample_hits`.
+//!
+//! The router runs over `MockUpstream` (`--features testing`) on a current-thread runtime,
+//! so the timings cover the controller, key building, storage and rendering without any
+//! network; building the request is part of each iteration. Requests carry 16 headers that
+//! are not part of the key, as browsers send.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use advcache::config;
+use advcache::controller::CacheProxyController;
+use advcache::db::DB;
+use advcache::governor::Orchestrator;
+use advcache::http::Controller;
+use advcache::upstream::testing::MockUpstream;
+use axum::body::Body;
+use axum::http::Request;
+use axum::Router;
+use criterion::{criterion_group, criterion_main, Criterion};
+use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
+
+const EXTRA_HEADERS: usize = 16;
+
+fn request(query: &str) -> Request<Body> {
+    let mut request = Request::get(format!("/api/v1/user?{}", query))
+        .header("host", "cache.local")
+        .header("accept-encoding", "gzip");
+    for i in 0..EXTRA_HEADERS {
+        request = request.header(format!("x-client-{}", i), "Mozilla/5.0 (X11; Linux x86_64)");
+    }
+    request.body(Body::empty()).unwrap()
+}
+
+fn router(cfg: config::Config, shutdown: &CancellationToken) -> Router {
+    let upstream = MockUpstream::new();
+    let db = DB::new(shutdown.clone(), cfg.clone(), Arc::new(Orchestrator::new()), upstream.clone())
+        .expect("storage must start");
+    CacheProxyController::new(shutdown.clone(), cfg, db, upstream).add_route(Router::new())
+}
+
+fn bench_hot_path(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("runtime");
+    let _rt = rt.enter();
+    let shutdown = CancellationToken::new();
+    let _clock = advcache::time::start(Duration::from_millis(1));
+
+    // `counted` is `None` for no hit counter, else whether it samples hits.
+    let router_with = |track: bool, granularity: Option<Duration>, counted: Option<bool>| {
+        let mut cfg = config::new_test_config();
+        let storage = cfg.cache.storage.as_mut().unwrap();
+        storage.track_access_time = Some(track);
+        storage.access_time_granularity = granularity;
+        storage.sample_hits = counted;
+        if counted.is_some() {
+            cfg.cache.eviction.as_mut().unwrap().audit = Some(config::EvictionAudit {
+                enabled: true,
+                sample_rate: None,
+                path: None,
+                max_file_size: None,
+            });
+        }
+        router(cfg, &shutdown)
+    };
+    let hit_query = "user[id]=1&domain=example.com&language=en";
+    let mut group = c.benchmark_group("hot_path");
+    for (name, router) in [
+        ("hit", router_with(false, None, Some(false))),
+        ("hit_access_coarse", router_with(true, None, Some(false))),
+        ("hit_access_every", router_with(true, Some(Duration::ZERO), Some(false))),
+        ("hit_sampled", router_with(false, None, Some(true))),
+        ("hit_no_counter", router_with(false, None, None)),
+    ] {
+        rt.block_on(router.clone().oneshot(request(hit_query))).unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| rt.block_on(router.clone().oneshot(request(hit_query))).unwrap())
+        });
+    }
+
+    let router = router(config::new_test_config(), &shutdown);
+    let seq = AtomicU64::new(0);
+    group.bench_function("miss_headers", |b| {
+        b.iter(|| {
+            let query = format!("user[id]={}&domain=example.com", seq.fetch_add(1, Ordering::Relaxed));
+            rt.block_on(router.clone().oneshot(request(&query))).unwrap()
+        })
+    });
+    group.finish();
+    shutdown.cancel();
+}
+
+criterion_group!(benches, bench_hot_path);
+criterion_main!(benches);
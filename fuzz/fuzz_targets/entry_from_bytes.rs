@@ -0,0 +1,26 @@
+//! Feeds arbitrary bytes into the dump entry decoder.
+//!
+//! Decoding must never panic and never allocate more than the input actually holds,
+//! whatever the declared lengths say. Successfully decoded entries are also walked
+//! through the payload decoder since its offsets come from the same untrusted bytes.
+
+#![no_main]
+
+use std::sync::OnceLock;
+
+use advcache::config::{new_test_config, Config};
+use advcache::model::to_bytes::from_bytes;
+use libfuzzer_sys::fuzz_target;
+
+fn cfg() -> &'static Config {
+    static CFG: OnceLock<Config> = OnceLock::new();
+    CFG.get_or_init(new_test_config)
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(entry) = from_bytes(data, cfg()) {
+        let _ = entry.payload();
+        let _ = entry.walk_query(|_, _| true);
+        let _ = entry.to_bytes();
+    }
+});
@@ -1,15 +1,19 @@
 // Main cache application implementation.
 
 use anyhow::Result;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
+use crate::config::overrides::RuntimeOverrides;
+use crate::config::reload::{ReloadReport, Reloader};
 use crate::config::{Config, ConfigTrait};
 use crate::governor;
 use crate::liveness;
 use crate::db;
+use crate::shutdown::{self, GracefulShutdown, ShutdownReport};
 use crate::traces;
 use crate::upstream;
 
@@ -24,6 +28,7 @@ pub struct App {
     probe: Arc<dyn liveness::Prober>,
     cancel_observer: Option<Arc<dyn Fn(CancellationToken) -> Result<()> + Send + Sync>>,
     server: Arc<dyn Http>,
+    reloader: Arc<Reloader>,
 }
 
 impl App {
@@ -38,12 +43,22 @@ impl App {
             shutdown_token.clone(),
             cfg.upstream().and_then(|u| u.backend.as_ref()).cloned(),
         )?;
+        if let Some(ref overrides) = cfg.runtime().overrides {
+            upstream::drain::restore(backend.as_ref(), Path::new(overrides));
+        }
         let adv_cache = db::DB::new(
             shutdown_token.clone(),
             cfg.clone(),
             gov.clone(),
             backend.clone(),
         )?;
+        if let Some(ref overrides) = cfg.runtime().overrides {
+            db::resize::restore(adv_cache.as_ref(), Path::new(overrides));
+        }
+        let reloader = Arc::new(Reloader::new(cfg.clone()).on_reload({
+            let db = adv_cache.clone();
+            move |next| db.on_config_reload(next.clone())
+        }));
         let http_server = Arc::new(HttpServer::new(
             shutdown_token.clone(),
             cfg.clone(),
@@ -51,6 +66,7 @@ impl App {
             backend.clone(),
             gov.clone(),
             probe.clone(),
+            reloader.clone(),
         )?);
         let cancel_observer = traces::apply(shutdown_token.clone(), cfg.traces().cloned());
         let cancel_observer_arc = Arc::new(cancel_observer);
@@ -64,11 +80,12 @@ impl App {
             server: http_server,
             backend,
             cancel_observer: Some(cancel_observer_arc),
+            reloader,
         })
     }
 
     /// Serves the cache server and probes, handles graceful shutdown.
-    pub async fn serve(&self, gsh: Arc<crate::shutdown::GracefulShutdown>) -> Result<()> {
+    pub async fn serve(&self, gsh: Arc<GracefulShutdown>) -> Result<()> {
         // Register liveness target before serving.
         self.probe
             .watch(vec![Arc::new(self.clone()) as Arc<dyn liveness::Service>]);
@@ -80,7 +97,8 @@ impl App {
         let gsh_clone = gsh.clone();
 
         tokio::task::spawn(async move {
-            // Start server
+            // Start server; it returns once shutdown stops it, the rest is closed by the
+            // shutdown phases.
             if let Err(e) = server.listen_and_serve().await {
                 error!(
                     component = "app",
@@ -89,16 +107,8 @@ impl App {
                     error = %e,
                     "server failed to serve"
                 );
-            }
-
-            if let Err(e) = app_for_close.close().await {
-                error!(
-                    component = "app",
-                    scope = "shutdown",
-                    event = "close_failed",
-                    error = %e,
-                    "application close failed"
-                );
+                // Nothing is served anymore: shut the instance down.
+                app_for_close.shutdown_token.cancel();
             }
 
             // Signal graceful shutdown
@@ -128,6 +138,65 @@ impl App {
         true
     }
 
+    /// Stops the application in the shutdown phases, each within its `shutdown` budget, and
+    /// records their report in the runtime overrides file for post-mortems.
+    pub async fn shutdown(&self, gsh: &GracefulShutdown) -> ShutdownReport {
+        let budgets = self.cfg.cache.shutdown.clone().unwrap_or_default();
+
+        gsh.run_phase(shutdown::PHASE_STOP_ACCEPTING, budgets.stop_accepting(), async {
+            self.server.stop_accepting();
+            self.server.closed().await;
+            Ok(())
+        })
+        .await;
+        gsh.run_phase(shutdown::PHASE_DRAIN_HTTP, budgets.drain_http(), async {
+            self.server.drained().await;
+            Ok(())
+        })
+        .await;
+        gsh.run_phase(shutdown::PHASE_STOP_WORKERS, budgets.stop_workers(), async {
+            self.storage.stop_workers();
+            Ok(())
+        })
+        .await;
+        gsh.run_phase(shutdown::PHASE_DUMP, budgets.dump(), self.storage.dump()).await;
+        gsh.run_phase(shutdown::PHASE_FINAL_CANCEL, budgets.final_cancel(), async {
+            self.close().await?;
+            gsh.cancel_and_wait().await;
+            Ok(())
+        })
+        .await;
+
+        let report = gsh.report();
+        if let Some(ref path) = self.cfg.runtime().overrides {
+            let saved = RuntimeOverrides::update(Path::new(path), |o| o.last_shutdown = Some(report.clone()));
+            if let Err(e) = saved {
+                error!(
+                    component = "app",
+                    scope = "shutdown",
+                    event = "report_not_saved",
+                    error = %format!("{:#}", e),
+                    "shutdown report was not written to the runtime overrides"
+                );
+            }
+        }
+        report
+    }
+
+    /// Writes a dump on demand (SIGUSR1), next to the one written on shutdown.
+    pub async fn dump(&self) -> Result<()> {
+        let enabled = self.cfg.data().and_then(|d| d.dump.as_ref()).is_some_and(|d| d.enabled);
+        if !enabled {
+            anyhow::bail!("data.dump is not enabled");
+        }
+        self.storage.dump().await
+    }
+
+    /// Re-reads the config file and applies it (SIGHUP); a failing config leaves the running one.
+    pub fn reload_config(&self) -> Result<ReloadReport> {
+        self.reloader.reload()
+    }
+
     /// Closes application resources.
     pub async fn close(&self) -> Result<()> {
         if let Some(cb) = &self.cancel_observer {
@@ -174,6 +243,7 @@ impl Clone for App {
             probe: self.probe.clone(),
             cancel_observer: self.cancel_observer.clone(),
             server: self.server.clone(),
+            reloader: self.reloader.clone(),
         }
     }
 }
@@ -183,4 +253,9 @@ impl liveness::Service for App {
     fn is_alive(&self, _timeout: Duration) -> bool {
         self.is_alive()
     }
+
+    /// Not ready while a non-critical worker failed to start.
+    fn is_ready(&self, _timeout: Duration) -> bool {
+        self.is_alive() && self.storage.failed_workers().is_empty()
+    }
 }
@@ -0,0 +1,115 @@
+//! Config check of `--dry-run`.
+//!
+//! Loads and validates a config the way a start would, resolves the backend hosts and checks
+//! the dump directories can be written, then prints the startup summary. Nothing is bound or
+//! started, so CI can check a config before it is rolled out.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::config::{Config, ConfigTrait};
+
+use super::summary::Summary;
+
+/// How long a backend host may take to resolve.
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Checks what the config leaves to the environment: backend hosts resolve and dump
+/// directories are writable. Returns the problems found.
+pub async fn check(cfg: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let upstream = cfg.upstream();
+    let backends = upstream
+        .and_then(|u| u.backend.as_ref())
+        .into_iter()
+        .chain(upstream.and_then(|u| u.cluster.as_ref()).and_then(|c| c.backends.as_deref()).unwrap_or_default())
+        .filter(|b| b.enabled);
+    for backend in backends {
+        let id = backend.id.as_deref().unwrap_or_default();
+        let Some(host) = backend.host.as_deref().filter(|h| !h.is_empty()) else {
+            problems.push(format!("backend {:?} has no host", id));
+            continue;
+        };
+        let addr = with_port(host, backend.scheme.as_deref().unwrap_or("http"));
+        let resolved = tokio::time::timeout(RESOLVE_TIMEOUT, tokio::net::lookup_host(addr)).await;
+        match resolved.map(|r| r.map(|mut addrs| addrs.next().is_some())) {
+            Ok(Ok(true)) => {}
+            Ok(Ok(false)) => problems.push(format!("backend {:?} host {} resolves to no address", id, host)),
+            Ok(Err(e)) => problems.push(format!("backend {:?} host {} does not resolve: {}", id, host, e)),
+            Err(_) => problems.push(format!("backend {:?} host {} did not resolve within {:?}", id, host, RESOLVE_TIMEOUT)),
+        }
+    }
+
+    if let Some(dump) = cfg.data().and_then(|d| d.dump.as_ref()).filter(|d| d.enabled) {
+        let dirs = [dump.dir.as_deref().or(Some("public/dump")), dump.dir_fallback.as_deref()];
+        for dir in dirs.into_iter().flatten().filter(|d| !d.is_empty()) {
+            if let Err(e) = check_writable(Path::new(dir)) {
+                problems.push(format!("dump dir {:?} is not writable: {}", dir, e));
+            }
+        }
+    }
+
+    problems
+}
+
+/// Prints the summary of the loaded config to `out` and what is wrong with it to `err`;
+/// returns the exit code, 0 when the config can be started with.
+pub async fn run(cfg: Result<Config>, out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let cfg = match cfg {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            let _ = writeln!(err, "config is invalid: {:#}", e);
+            return 1;
+        }
+    };
+
+    let summary = serde_json::to_string_pretty(&Summary::of(&cfg)).unwrap_or_default();
+    let _ = writeln!(out, "{}", summary);
+
+    let problems = check(&cfg).await;
+    for problem in &problems {
+        let _ = writeln!(err, "{}", problem);
+    }
+    if problems.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+/// Adds the default port of the scheme to a host without one.
+fn with_port(host: &str, scheme: &str) -> String {
+    let has_port = host.rsplit_once(':').is_some_and(|(name, port)| !name.ends_with(':') && port.parse::<u16>().is_ok());
+    if has_port {
+        host.to_string()
+    } else if scheme.eq_ignore_ascii_case("https") {
+        format!("{}:443", host)
+    } else {
+        format!("{}:80", host)
+    }
+}
+
+/// Checks a file can be created in `dir`, or in its nearest existing parent when the dumper
+/// would have to create it.
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    let mut existing: PathBuf = dir.to_path_buf();
+    while !existing.exists() {
+        match existing.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => existing = parent.to_path_buf(),
+            _ => {
+                existing = PathBuf::from(".");
+                break;
+            }
+        }
+    }
+    if !existing.is_dir() {
+        return Err(std::io::Error::other(format!("{:?} is not a directory", existing)));
+    }
+    let probe = existing.join(format!(".advcache-dry-run-{}", std::process::id()));
+    std::fs::File::create(&probe)?;
+    std::fs::remove_file(&probe)
+}
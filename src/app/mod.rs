@@ -1,7 +1,10 @@
 // Main cache application module.
 
 pub mod app;
+pub mod dry_run;
 pub mod server;
+pub mod summary;
 
 // Re-export main types
 pub use app::App;
+pub use summary::Summary;
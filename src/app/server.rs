@@ -1,14 +1,17 @@
 // HTTP server implementation for the cache application.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 
+use crate::audit::AuditLog;
+use crate::config::reload::Reloader;
 use crate::config::{Config, ConfigTrait};
 use crate::governor::Governor;
 use crate::http::{Controller, Middleware, Server as HttpServerTrait};
 use crate::liveness;
+use crate::middleware::audit_middleware::AuditMiddleware;
 use crate::db::Storage;
 use crate::upstream::Upstream;
 
@@ -20,12 +23,25 @@ pub trait Http: Send + Sync {
 
     /// Checks if the server is alive.
     fn is_alive(&self) -> bool;
+
+    /// Stops accepting connections and asks open ones to close once their request is answered.
+    fn stop_accepting(&self);
+
+    /// Resolves once the listeners are closed.
+    async fn closed(&self);
+
+    /// Resolves once the server stopped and every connection is closed.
+    async fn drained(&self);
 }
 
 /// HTTP server implementation that wraps all dependencies.
 pub struct HttpServer {
     server: Arc<dyn HttpServerTrait>,
     is_server_alive: Arc<AtomicBool>,
+    /// Child of the root token: the server stops ahead of the rest of the application.
+    http_token: CancellationToken,
+    /// Cancelled once `listen_and_serve` returned.
+    stopped: CancellationToken,
 }
 
 impl HttpServer {
@@ -38,20 +54,26 @@ impl HttpServer {
         backend: Arc<dyn Upstream>,
         governor: Arc<dyn Governor>,
         probe: Arc<dyn liveness::Prober>,
+        reloader: Arc<Reloader>,
     ) -> Result<Self> {
         // Initialize HTTP server with all controllers and middlewares.
+        let http_token = ctx.child_token();
         let server = Self::make_http_server(
             ctx.clone(),
+            http_token.clone(),
             &cfg,
             db.clone(),
             backend.clone(),
             governor.clone(),
             probe.clone(),
+            reloader,
         )?;
 
         Ok(Self {
             server,
             is_server_alive: Arc::new(AtomicBool::new(false)),
+            http_token,
+            stopped: CancellationToken::new(),
         })
     }
 
@@ -68,18 +90,23 @@ impl HttpServer {
         let result = self.server.listen_and_serve().await;
 
         self.is_server_alive.store(false, Ordering::Relaxed);
+        self.stopped.cancel();
         result
     }
 
     /// Creates the HTTP server instance with controllers and middlewares.
+    #[allow(clippy::too_many_arguments)]
     fn make_http_server(
         ctx: CancellationToken,
+        http_token: CancellationToken,
         cfg: &Config,
         db: Arc<dyn Storage>,
         backend: Arc<dyn Upstream>,
         governor: Arc<dyn Governor>,
         probe: Arc<dyn liveness::Prober>,
+        reloader: Arc<Reloader>,
     ) -> Result<Arc<dyn HttpServerTrait>> {
+        let audit = crate::audit::AuditLog::from_config(cfg).context("failed to open the audit log")?;
         let controllers = Self::controllers(
             ctx.clone(),
             cfg,
@@ -87,60 +114,128 @@ impl HttpServer {
             backend.clone(),
             governor.clone(),
             probe.clone(),
+            audit.clone(),
+            reloader,
         );
-        let middlewares = Self::middlewares(cfg);
+        let audit = audit.map(|log| AuditMiddleware::new(log, &controllers));
+        let middlewares = Self::middlewares(cfg, audit);
 
         // Compose server with controllers and middlewares.
-        let server = crate::http::HttpServer::new(ctx, cfg.clone(), controllers, middlewares)?;
+        let server = crate::http::HttpServer::new(http_token, cfg.clone(), controllers, middlewares)?;
         Ok(Arc::new(server))
     }
 
     /// Returns all HTTP controllers for the server.
-    fn controllers(
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn controllers(
         ctx: CancellationToken,
         cfg: &Config,
         db: Arc<dyn Storage>,
         backend: Arc<dyn Upstream>,
         governor: Arc<dyn Governor>,
         probe: Arc<dyn liveness::Prober>,
+        audit: Option<Arc<AuditLog>>,
+        reloader: Arc<Reloader>,
     ) -> Vec<Box<dyn Controller>> {
         use crate::controller;
 
-        vec![
-            // Healthcheck probe endpoint
-            Box::new(controller::LivenessProbeController::new(probe.clone())),
+        let cache = controller::CacheProxyController::new(ctx, cfg.clone(), db.clone(), backend.clone());
+        let brownout = cache.brownout();
+        let reclaim = cache.idle_reclaim();
+
+        let mut controllers: Vec<Box<dyn Controller>> = vec![
+            // SLO health snapshot for load balancers
+            Box::new(controller::HealthController::new(controller::health::monitor())),
+            // Readiness aggregating dump load, upstream health and worker states
+            Box::new(controller::ReadinessProbeController::new(
+                probe.clone(),
+                db.clone(),
+                backend.clone(),
+                governor.clone(),
+                cfg.k8s().map(|k| &k.probe),
+            )),
+            // Recent errors, sanitized as logged
+            Box::new(controller::ErrorsController::new(crate::dedlog::error_ring())),
             // Metrics endpoint
-            Box::new(controller::PrometheusMetricsController::new()),
+            Box::new(controller::PrometheusMetricsController::new(cfg.clone())),
             // Cache on/off switcher
             Box::new(controller::BypassOnOffController::new(cfg.clone())),
             // Clears cache
             Box::new(controller::ClearController::new(cfg.clone(), db.clone())),
             // Main cache handler
-            Box::new(controller::CacheProxyController::new(ctx, cfg.clone(), db.clone(), backend.clone())),
+            Box::new(cache),
+            // Shows and forces the brownout that sheds misses under saturation
+            Box::new(controller::BrownoutController::new(brownout)),
+            // Hands idle connections and free memory back, on demand or after prolonged low traffic
+            Box::new(controller::IdleReclaimController::new(reclaim)),
             // Searches items by query and mark them as outdated
             Box::new(controller::InvalidateController::new(cfg.clone(), db.clone())),
+            // Removes the entries under a path prefix
+            Box::new(controller::PurgeController::new(db.clone())),
+            // Admin walks over the shards running and queued
+            Box::new(controller::WalksController::new(db.clone())),
+            // Admin jobs over the shards with their progress, and their cancellation
+            Box::new(controller::JobsController::new(db.clone())),
+            // Grows or shrinks the storage memory budget without a restart
+            Box::new(controller::StorageResizeController::new(db.clone(), cfg.runtime().overrides.clone())),
+            // Requests to paths no rule matches, by path prefix
+            Box::new(controller::StatsController::new()),
+            // Entries, bytes and sampled lock waits of the shards
+            Box::new(controller::ShardsController::new(cfg.clone(), db.clone())),
             // Changes await/deny policy to upstream switcher
             Box::new(controller::ChangeBackendPolicyController::new()),
+            // Drains/undrains the upstream backend for origin maintenance
+            Box::new(controller::BackendDrainController::new(backend.clone(), cfg.runtime().overrides.clone())),
+            // Phases of the last shutdown, for post-mortems
+            Box::new(controller::ShutdownReportController::new(cfg.runtime().overrides.clone())),
             // Switches between enable/disable for http compression middleware
             Box::new(controller::HttpCompressionController::new()),
             // Encodes and shows current config as json
             Box::new(controller::ShowConfigController::new(cfg.clone())),
+            // Diffs a candidate config against the current one without applying it
+            Box::new(controller::ConfigDiffController::new(cfg.clone())),
+            // Re-reads the config file and applies its rules, lifetime and eviction settings
+            Box::new(controller::ConfigReloadController::new(reloader)),
             // Provides endpoints for manipulate of Refresher/Remover worker settings
             Box::new(controller::LifetimeManagerController::new(cfg.clone(), governor.clone())),
             // Provides endpoints for manipulate of Evictor worker settings
             Box::new(controller::EvictionController::new(governor.clone())),
             // Provides access to switch off/on admission control
             Box::new(controller::AdmissionController::new(cfg.clone())),
-            // Provides access to enable/disable of open-telemetry traces
-            Box::new(controller::TracesController::new()),
+            // Shows and adjusts rule rollout percents
+            Box::new(controller::RolloutController::new(cfg.clone())),
             // Provides access to single cache item by key
-            Box::new(controller::GetController::new(db.clone())),
-        ]
+            Box::new(controller::GetController::new(cfg.clone(), db.clone())),
+            // Explains rule matching and key building for a hypothetical request
+            Box::new(controller::ExplainController::new(cfg.clone(), db.clone())),
+            // Estimates hit rate and memory under other storage sizes and TTLs
+            Box::new(controller::WhatIfController::new(cfg.clone(), db.clone())),
+            // Most recent admin calls changing the instance state
+            Box::new(controller::AuditController::new(audit)),
+        ];
+
+        // Provides access to enable/disable of open-telemetry traces
+        #[cfg(feature = "tracing-otel")]
+        controllers.push(Box::new(controller::TracesController::new()));
+        // Captures every operation on one key for a while, for debugging
+        #[cfg(feature = "debug-endpoints")]
+        controllers.push(Box::new(controller::DebugCaptureController::new(cfg.clone())));
+
+        // Healthcheck probe endpoints, unless served on a port of their own
+        let probe_cfg = cfg.k8s().map(|k| &k.probe);
+        if probe_cfg.is_none_or(|p| p.port.is_none()) {
+            controllers.insert(0, Box::new(controller::LivenessProbeController::new(probe, probe_cfg)));
+        }
+
+        // Index of the endpoints above, hidden ones included in debug only
+        let index = controller::IndexController::new(&controllers, cfg.is_debug());
+        controllers.push(Box::new(index));
+        controllers
     }
 
-    /// Returns the request middlewares for the server, executed in reverse order.
-    fn middlewares(cfg: &Config) -> Vec<Box<dyn Middleware>> {
-        vec![
+    /// Returns the request middlewares for the server, the first one wrapping the others.
+    fn middlewares(cfg: &Config, audit: Option<AuditMiddleware>) -> Vec<Box<dyn Middleware>> {
+        let mut middlewares: Vec<Box<dyn Middleware>> = vec![
             // Exec first - panic recovery
             Box::new(crate::middleware::recover_middleware::PanicRecoverMiddleware::new()),
             // Exec second - compression
@@ -149,7 +244,12 @@ impl HttpServer {
                     cfg.compression().cloned(),
                 ),
             ),
-        ]
+        ];
+        // Outermost - audit of admin mutations, panicked calls included
+        if let Some(audit) = audit {
+            middlewares.insert(0, Box::new(audit));
+        }
+        middlewares
     }
 }
 
@@ -162,4 +262,16 @@ impl Http for HttpServer {
     fn is_alive(&self) -> bool {
         HttpServer::is_alive(self)
     }
+
+    fn stop_accepting(&self) {
+        self.http_token.cancel();
+    }
+
+    async fn closed(&self) {
+        self.server.closed().await
+    }
+
+    async fn drained(&self) {
+        self.stopped.cancelled().await
+    }
 }
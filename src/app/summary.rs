@@ -0,0 +1,152 @@
+//! Startup summary.
+//!
+//! The effective setup in one structured event, logged once the app is built and printed by
+//! `--dry-run`: listeners, storage limits, rules, backends, workers and the optional parts.
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::config::{Backend, Config, ConfigTrait, Features, MemoryLimits};
+
+/// Listen addresses of the instance.
+#[derive(Debug, Serialize)]
+pub struct Listen {
+    pub api: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe: Option<String>,
+}
+
+/// Storage mode and memory limits.
+#[derive(Debug, Serialize)]
+pub struct StorageSummary {
+    pub mode: String,
+    #[serde(flatten)]
+    pub limits: MemoryLimits,
+}
+
+/// A backend and its policies.
+#[derive(Debug, Serialize)]
+pub struct BackendSummary {
+    pub id: String,
+    pub url: String,
+    pub enabled: bool,
+    pub policy: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<usize>,
+}
+
+impl BackendSummary {
+    fn of(backend: &Backend) -> Self {
+        Self {
+            id: backend.id.clone().unwrap_or_default(),
+            url: format!(
+                "{}://{}",
+                backend.scheme.as_deref().unwrap_or("http"),
+                backend.host.as_deref().unwrap_or_default()
+            ),
+            enabled: backend.enabled,
+            policy: backend.policy.clone().unwrap_or_else(|| "await".to_string()),
+            rate: backend.rate,
+            concurrency: backend.concurrency,
+        }
+    }
+}
+
+/// Replicas of the background workers, `None` when a worker is disabled.
+#[derive(Debug, Serialize)]
+pub struct Workers {
+    pub evictor: Option<usize>,
+    pub lifetimer: Option<usize>,
+}
+
+/// Effective setup of an instance.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub env: String,
+    pub listen: Listen,
+    pub storage: StorageSummary,
+    pub rules: Vec<String>,
+    pub backends: Vec<BackendSummary>,
+    pub workers: Workers,
+    pub dump: bool,
+    pub mock: bool,
+    pub traces: bool,
+    pub metrics: bool,
+    /// Cargo features the binary was built with.
+    pub features: Vec<&'static str>,
+}
+
+impl Summary {
+    /// Summarizes the setup `cfg` starts an instance with.
+    pub fn of(cfg: &Config) -> Self {
+        let address = |port: &str| format!("0.0.0.0:{}", port.trim_start_matches(':'));
+        let api = cfg.api();
+        let listen = Listen {
+            api: address(api.and_then(|a| a.port.as_deref()).unwrap_or("8020")),
+            admin: api.and_then(|a| a.admin_port.as_deref()).map(address),
+            probe: cfg.k8s().and_then(|k| k.probe.port.as_deref()).map(address),
+        };
+
+        let mut rules: Vec<String> = cfg.cache.rules.iter().flat_map(|rules| rules.keys().cloned()).collect();
+        rules.sort();
+
+        let upstream = cfg.upstream();
+        let backends = upstream
+            .and_then(|u| u.backend.as_ref())
+            .into_iter()
+            .chain(upstream.and_then(|u| u.cluster.as_ref()).and_then(|c| c.backends.as_deref()).unwrap_or_default())
+            .map(BackendSummary::of)
+            .collect();
+
+        let replicas = |enabled: bool, replicas: Option<usize>| enabled.then(|| replicas.unwrap_or(32));
+        let data = cfg.data();
+        Self {
+            env: cfg.cache.env.clone(),
+            listen,
+            storage: StorageSummary {
+                mode: cfg.storage().mode.clone().unwrap_or_else(|| "listing".to_string()),
+                limits: cfg.storage().memory_limits(),
+            },
+            rules,
+            backends,
+            workers: Workers {
+                evictor: cfg.eviction().and_then(|e| replicas(e.enabled, e.replicas)),
+                lifetimer: cfg.lifetime().and_then(|l| replicas(l.enabled, l.replicas)),
+            },
+            dump: data.and_then(|d| d.dump.as_ref()).is_some_and(|d| d.enabled),
+            mock: data.and_then(|d| d.mock.as_ref()).is_some_and(|m| m.enabled),
+            traces: cfg.traces().is_some_and(|t| t.enabled),
+            metrics: cfg.cache.metrics.as_ref().is_some_and(|m| m.enabled),
+            features: Features::BUILT.names(),
+        }
+    }
+
+    /// Logs the summary as a single info event.
+    pub fn log(&self) {
+        info!(
+            component = "main",
+            event = "startup_summary",
+            env = %self.env,
+            listen = %json(&self.listen),
+            storage = %json(&self.storage),
+            rules_count = self.rules.len(),
+            rules = ?self.rules,
+            backends = %json(&self.backends),
+            workers = %json(&self.workers),
+            dump = self.dump,
+            mock = self.mock,
+            traces = self.traces,
+            metrics = self.metrics,
+            features = ?self.features,
+            "startup summary"
+        );
+    }
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
@@ -0,0 +1,158 @@
+//! Structured difference between two processed configs.
+//!
+//! Rules are matched by path and backends by id (host when no id is set); limits are the
+//! storage, eviction and admission sections. Items are compared field by field on their
+//! serialized form, so a field is named in the diff the way it is spelled in the YAML.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::Config;
+
+/// One leaf field that differs, addressed by its dotted path within the compared item.
+/// A side on which the field is not set is `null`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: Value,
+    pub to: Value,
+}
+
+/// Named item present in both configs with at least one differing field.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ItemChange {
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Differences between two sets of named items.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SetDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<ItemChange>,
+}
+
+impl SetDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// What applying a candidate config would change compared to the current one.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConfigDiff {
+    pub rules: SetDiff,
+    pub backends: SetDiff,
+    pub limits: Vec<FieldChange>,
+}
+
+impl ConfigDiff {
+    /// Computes the changes from `current` to `candidate`.
+    pub fn between(current: &Config, candidate: &Config) -> Self {
+        let limits = field_changes(&limits(current), &limits(candidate));
+        Self {
+            rules: diff_sets(rules(current), rules(candidate)),
+            backends: diff_sets(backends(current), backends(candidate)),
+            limits,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.backends.is_empty() && self.limits.is_empty()
+    }
+}
+
+fn rules(cfg: &Config) -> BTreeMap<String, Value> {
+    cfg.cache
+        .rules
+        .iter()
+        .flatten()
+        .map(|(path, rule)| (path.clone(), to_value(rule.as_ref())))
+        .collect()
+}
+
+fn backends(cfg: &Config) -> BTreeMap<String, Value> {
+    let Some(upstream) = cfg.cache.upstream.as_ref() else {
+        return BTreeMap::new();
+    };
+    let backends = match upstream.cluster.as_ref() {
+        Some(cluster) => cluster.backends.iter().flatten().collect::<Vec<_>>(),
+        None => upstream.backend.iter().collect(),
+    };
+    backends
+        .into_iter()
+        .enumerate()
+        .map(|(i, backend)| {
+            let name = backend
+                .id
+                .clone()
+                .or_else(|| backend.host.clone())
+                .unwrap_or_else(|| format!("#{}", i));
+            (name, to_value(backend))
+        })
+        .collect()
+}
+
+fn limits(cfg: &Config) -> Value {
+    serde_json::json!({
+        "storage": to_value(&cfg.cache.storage),
+        "eviction": to_value(&cfg.cache.eviction),
+        "admission": to_value(&cfg.cache.admission),
+    })
+}
+
+fn to_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+fn diff_sets(current: BTreeMap<String, Value>, candidate: BTreeMap<String, Value>) -> SetDiff {
+    let mut diff = SetDiff::default();
+    for (name, was) in &current {
+        match candidate.get(name) {
+            None => diff.removed.push(name.clone()),
+            Some(now) => {
+                let changes = field_changes(was, now);
+                if !changes.is_empty() {
+                    diff.changed.push(ItemChange { name: name.clone(), changes });
+                }
+            }
+        }
+    }
+    diff.added = candidate.keys().filter(|name| !current.contains_key(*name)).cloned().collect();
+    diff
+}
+
+fn field_changes(from: &Value, to: &Value) -> Vec<FieldChange> {
+    let (mut was, mut now) = (BTreeMap::new(), BTreeMap::new());
+    flatten(String::new(), from, &mut was);
+    flatten(String::new(), to, &mut now);
+
+    let fields: BTreeSet<&String> = was.keys().chain(now.keys()).collect();
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let from = was.get(field).cloned().unwrap_or(Value::Null);
+            let to = now.get(field).cloned().unwrap_or(Value::Null);
+            (from != to).then(|| FieldChange { field: field.clone(), from, to })
+        })
+        .collect()
+}
+
+/// Collects the leaves of `value` under dotted paths; arrays are leaves, compared as a whole.
+fn flatten(prefix: String, value: &Value, out: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(path, value, out);
+            }
+        }
+        _ => {
+            out.insert(prefix, value.clone());
+        }
+    }
+}
@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::config::diff::{ConfigDiff, FieldChange};
+    use crate::config::Config;
+
+    const CURRENT: &str = r#"
+cache:
+  env: test
+  enabled: true
+  upstream:
+    cluster:
+      backends:
+        - id: main
+          enabled: true
+          scheme: http
+          host: main.local:8080
+          timeout: 10s
+          max_timeout: 1m
+          rate: 1000
+        - id: spare
+          enabled: true
+          scheme: http
+          host: spare.local:8080
+          timeout: 10s
+          max_timeout: 1m
+  storage:
+    mode: listing
+    size: 1073741824
+  rules:
+    /api/v1/user:
+      cache_key:
+        query: ["user[id]"]
+      cache_value:
+        headers: [Content-Type]
+      refresh:
+        enabled: true
+        ttl: 1h
+"#;
+
+    fn config(yaml: &str) -> Config {
+        Config::from_yaml(yaml).expect("test config must load")
+    }
+
+    fn change(field: &str, from: serde_json::Value, to: serde_json::Value) -> FieldChange {
+        FieldChange { field: field.to_string(), from, to }
+    }
+
+    /// Test that a config compared to itself has no changes.
+    #[test]
+    fn test_same_config_has_empty_diff() {
+        assert!(ConfigDiff::between(&config(CURRENT), &config(CURRENT)).is_empty());
+    }
+
+    /// Test that a new rule path is reported as added and nothing else changes.
+    #[test]
+    fn test_added_rule() {
+        let candidate = format!(
+            "{}    /api/v1/buyer:\n      cache_key:\n        query: [\"buyer[id]\"]\n      cache_value:\n        headers: []\n",
+            CURRENT
+        );
+        let diff = ConfigDiff::between(&config(CURRENT), &config(&candidate));
+        assert_eq!(diff.rules.added, vec!["/api/v1/buyer".to_string()]);
+        assert!(diff.rules.removed.is_empty() && diff.rules.changed.is_empty());
+        assert!(diff.backends.is_empty() && diff.limits.is_empty());
+    }
+
+    /// Test that changed TTL, key and value headers come out as field changes of the rule.
+    #[test]
+    fn test_changed_rule_fields() {
+        let candidate = CURRENT
+            .replace("ttl: 1h", "ttl: 30m")
+            .replace(r#"query: ["user[id]"]"#, r#"query: ["user[id]", lang]"#)
+            .replace("headers: [Content-Type]", "headers: [Content-Type, Vary]");
+        let diff = ConfigDiff::between(&config(CURRENT), &config(&candidate));
+
+        assert_eq!(diff.rules.changed.len(), 1);
+        let rule = &diff.rules.changed[0];
+        assert_eq!(rule.name, "/api/v1/user");
+        assert_eq!(
+            rule.changes,
+            vec![
+                change("cache_key.query", json!(["user[id]"]), json!(["user[id]", "lang"])),
+                change("cache_value.headers", json!(["Content-Type"]), json!(["Content-Type", "Vary"])),
+                change("refresh.ttl", json!("1h"), json!("30m")),
+            ]
+        );
+    }
+
+    /// Test that a backend dropped from the cluster is reported as removed by id.
+    #[test]
+    fn test_removed_backend() {
+        let candidate = CURRENT.replace(
+            "        - id: spare\n          enabled: true\n          scheme: http\n          host: spare.local:8080\n          timeout: 10s\n          max_timeout: 1m\n",
+            "",
+        );
+        let diff = ConfigDiff::between(&config(CURRENT), &config(&candidate));
+        assert_eq!(diff.backends.removed, vec!["spare".to_string()]);
+        assert!(diff.backends.added.is_empty() && diff.backends.changed.is_empty());
+        assert!(diff.rules.is_empty());
+    }
+
+    /// Test that backend and limit fields are diffed, unset sides showing as null.
+    #[test]
+    fn test_changed_backend_and_limits() {
+        let candidate = CURRENT
+            .replace("rate: 1000", "rate: 500")
+            .replace("size: 1073741824", "size: 2147483648\n  eviction:\n    enabled: true\n    soft_limit: 0.7\n    check_interval: 1s");
+        let diff = ConfigDiff::between(&config(CURRENT), &config(&candidate));
+
+        assert_eq!(diff.backends.changed.len(), 1);
+        assert_eq!(diff.backends.changed[0].name, "main");
+        assert_eq!(diff.backends.changed[0].changes, vec![change("rate", json!(1000), json!(500))]);
+        assert!(diff.limits.contains(&change("storage.size", json!(1073741824), json!(2147483648u64))));
+        assert!(diff.limits.contains(&change("eviction.soft_limit", json!(null), json!(0.7))));
+    }
+}
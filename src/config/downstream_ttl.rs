@@ -0,0 +1,74 @@
+//! Cache lifetime announced to downstream caches (CDNs) on responses served from the cache.
+
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// `cache_value.downstream_ttl` of a rule: `off`, `remaining` or `fixed:<duration>`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DownstreamTtl {
+    /// No lifetime headers are added; stored ones are served as they are.
+    #[default]
+    Off,
+    /// What is left of the rule TTL at render time, so the CDN expires the copy with ours.
+    Remaining,
+    /// The same lifetime on every response.
+    Fixed(Duration),
+}
+
+impl DownstreamTtl {
+    /// Seconds a downstream cache may keep a response rendered from an entry of `age`, with
+    /// `ttl` the entry's effective TTL. None when nothing is to be announced: the policy is
+    /// `off`, or `remaining` for entries without a TTL. Past the TTL it is 0.
+    pub fn max_age(&self, ttl: Duration, age: Duration) -> Option<u64> {
+        match *self {
+            DownstreamTtl::Off => None,
+            DownstreamTtl::Remaining if ttl.is_zero() => None,
+            DownstreamTtl::Remaining => Some(ttl.saturating_sub(age).as_secs()),
+            DownstreamTtl::Fixed(lifetime) => Some(lifetime.as_secs()),
+        }
+    }
+}
+
+impl fmt::Display for DownstreamTtl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownstreamTtl::Off => f.write_str("off"),
+            DownstreamTtl::Remaining => f.write_str("remaining"),
+            DownstreamTtl::Fixed(lifetime) => write!(f, "fixed:{}", humantime::format_duration(*lifetime)),
+        }
+    }
+}
+
+impl std::str::FromStr for DownstreamTtl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "off" => Ok(DownstreamTtl::Off),
+            "remaining" => Ok(DownstreamTtl::Remaining),
+            other => match other.strip_prefix("fixed:") {
+                Some(lifetime) => super::duration::parse(lifetime, super::duration::is_strict())
+                    .map(DownstreamTtl::Fixed)
+                    .map_err(|e| format!("downstream_ttl: {}", e)),
+                None => Err(format!(
+                    "downstream_ttl must be remaining, fixed:<duration> or off, got {:?}",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl Serialize for DownstreamTtl {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for DownstreamTtl {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::config::DownstreamTtl;
+
+    const TTL: Duration = Duration::from_secs(300);
+
+    /// Test that `remaining` announces the TTL minus the age, in whole seconds, at several ages.
+    #[test]
+    fn test_remaining_at_several_ages() {
+        let policy = DownstreamTtl::Remaining;
+        for (age, expected) in [
+            (Duration::ZERO, 300),
+            (Duration::from_millis(400), 299),
+            (Duration::from_secs(1), 299),
+            (Duration::from_secs(120), 180),
+            (Duration::from_millis(299_999), 0),
+            (Duration::from_secs(300), 0),
+        ] {
+            assert_eq!(policy.max_age(TTL, age), Some(expected), "age {:?}", age);
+        }
+    }
+
+    /// Test that an entry past its TTL announces 0 rather than a negative lifetime.
+    #[test]
+    fn test_remaining_clamps_past_ttl() {
+        let policy = DownstreamTtl::Remaining;
+        assert_eq!(policy.max_age(TTL, Duration::from_secs(301)), Some(0));
+        assert_eq!(policy.max_age(TTL, Duration::from_secs(86_400)), Some(0));
+    }
+
+    /// Test that `remaining` announces nothing for entries without a TTL, `fixed` the same
+    /// lifetime whatever the age, and `off` nothing.
+    #[test]
+    fn test_fixed_off_and_no_ttl() {
+        assert_eq!(DownstreamTtl::Remaining.max_age(Duration::ZERO, Duration::from_secs(5)), None);
+
+        let fixed = DownstreamTtl::Fixed(Duration::from_secs(30));
+        for age in [Duration::ZERO, Duration::from_secs(29), Duration::from_secs(3_600)] {
+            assert_eq!(fixed.max_age(TTL, age), Some(30));
+        }
+
+        assert_eq!(DownstreamTtl::Off.max_age(TTL, Duration::ZERO), None);
+    }
+
+    /// Test the accepted spellings, the default, and that values round-trip through serde.
+    #[test]
+    fn test_parse() {
+        assert_eq!(DownstreamTtl::default(), DownstreamTtl::Off);
+        assert_eq!("off".parse(), Ok(DownstreamTtl::Off));
+        assert_eq!("remaining".parse(), Ok(DownstreamTtl::Remaining));
+        assert_eq!("fixed:1m30s".parse(), Ok(DownstreamTtl::Fixed(Duration::from_secs(90))));
+        assert!("fixed:soon".parse::<DownstreamTtl>().is_err());
+        assert!("always".parse::<DownstreamTtl>().is_err());
+
+        for policy in [DownstreamTtl::Off, DownstreamTtl::Remaining, DownstreamTtl::Fixed(Duration::from_secs(90))] {
+            let yaml = serde_yaml::to_string(&policy).unwrap();
+            assert_eq!(serde_yaml::from_str::<DownstreamTtl>(&yaml).unwrap(), policy);
+        }
+    }
+}
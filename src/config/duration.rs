@@ -0,0 +1,140 @@
+//! Durations of the config, as `humantime` writes them (`500ms`, `30s`, `5m`, `1h 30m`).
+//!
+//! Every duration field goes through [`deserialize`], so a value that does not parse is
+//! reported with its field path and line by `serde_yaml`, the offending text and examples of
+//! accepted forms. With `cache.strict_durations: false`, a few forgiving forms are accepted
+//! too: bare numbers meaning seconds (`30`, `"30"`, `1.5`) and decimal commas (`0,1s`).
+
+use std::cell::Cell;
+use std::fmt;
+use std::time::Duration;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::Serializer;
+
+/// Accepted forms quoted by errors.
+const EXAMPLES: &str = "e.g. \"500ms\", \"30s\", \"5m\", \"1h 30m\" or \"2days\"";
+
+thread_local! {
+    /// Strictness of the config being parsed on this thread.
+    static STRICT: Cell<bool> = const { Cell::new(true) };
+}
+
+/// Runs `parse` with the given strictness for the durations it deserializes.
+pub(crate) fn with_strictness<T>(strict: bool, parse: impl FnOnce() -> T) -> T {
+    let previous = STRICT.with(|s| s.replace(strict));
+    let parsed = parse();
+    STRICT.with(|s| s.set(previous));
+    parsed
+}
+
+/// Strictness of the config being parsed on this thread.
+pub(crate) fn is_strict() -> bool {
+    STRICT.with(Cell::get)
+}
+
+/// Parses a duration, accepting the forgiving forms unless `strict`.
+pub fn parse(value: &str, strict: bool) -> Result<Duration, String> {
+    let trimmed = value.trim();
+    let strict_err = match humantime::parse_duration(trimmed) {
+        Ok(duration) => return Ok(duration),
+        Err(e) => e,
+    };
+    if !strict {
+        let normalized = trimmed.replace(',', ".");
+        if let Ok(secs) = normalized.parse::<f64>() {
+            return seconds(secs)
+                .ok_or_else(|| format!("invalid duration {:?}: not a non-negative number of seconds; expected {}", value, EXAMPLES));
+        }
+        if let Ok(duration) = humantime::parse_duration(&normalized) {
+            return Ok(duration);
+        }
+    } else if lenient_would_accept(trimmed) {
+        return Err(format!(
+            "invalid duration {:?}: {}; expected {} (bare numbers and decimal commas need cache.strict_durations: false)",
+            value, strict_err, EXAMPLES
+        ));
+    }
+    Err(format!("invalid duration {:?}: {}; expected {}", value, strict_err, EXAMPLES))
+}
+
+fn lenient_would_accept(value: &str) -> bool {
+    parse(value, false).is_ok()
+}
+
+/// Seconds given as a bare number.
+fn seconds(secs: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+/// Serializes a duration field the way `humantime` writes it.
+pub fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+    humantime_serde::serialize(duration, serializer)
+}
+
+/// Deserializes a duration field, reporting what is wrong with a malformed one.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    deserializer.deserialize_option(OptionVisitor)
+}
+
+struct OptionVisitor;
+
+impl<'de> Visitor<'de> for OptionVisitor {
+    type Value = Option<Duration>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a duration, {}", EXAMPLES)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(DurationVisitor).map(Some)
+    }
+}
+
+struct DurationVisitor;
+
+impl DurationVisitor {
+    fn number<E: de::Error>(self, secs: f64, text: String) -> Result<Duration, E> {
+        if is_strict() {
+            return Err(E::custom(format!(
+                "invalid duration {}: a bare number has no unit; expected {} (bare numbers mean seconds with cache.strict_durations: false)",
+                text, EXAMPLES
+            )));
+        }
+        seconds(secs).ok_or_else(|| {
+            E::custom(format!("invalid duration {}: not a non-negative number of seconds; expected {}", text, EXAMPLES))
+        })
+    }
+}
+
+impl Visitor<'_> for DurationVisitor {
+    type Value = Duration;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a duration, {}", EXAMPLES)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Duration, E> {
+        parse(value, is_strict()).map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Duration, E> {
+        self.number(value as f64, value.to_string())
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Duration, E> {
+        self.number(value as f64, value.to_string())
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Duration, E> {
+        self.number(value, value.to_string())
+    }
+}
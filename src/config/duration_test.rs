@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::config::duration::parse;
+    use crate::config::Config;
+
+    fn config(strict: Option<bool>, lifetime_ttl: &str, rule_ttl: &str) -> anyhow::Result<Config> {
+        let strict = strict.map(|s| format!("  strict_durations: {}\n", s)).unwrap_or_default();
+        Config::from_yaml(&format!(
+            r#"
+cache:
+  env: test
+  enabled: true
+{strict}  lifetime:
+    enabled: true
+    on_ttl: refresh
+    ttl: {lifetime_ttl}
+  rules:
+    /api/v1/user:
+      cache_key:
+        query: ["user[id]"]
+      cache_value:
+        headers: [Content-Type]
+      refresh:
+        enabled: true
+        ttl: {rule_ttl}
+"#
+        ))
+    }
+
+    fn lifetime_ttl(cfg: &Config) -> Option<Duration> {
+        cfg.cache.lifetime.as_ref().unwrap().ttl
+    }
+
+    /// Test that the humantime forms are accepted in either mode, bare numbers and decimal
+    /// commas only when not strict, and that garbage is refused in both.
+    #[test]
+    fn test_accepted_and_rejected_forms() {
+        let accepted = [
+            ("500ms", Duration::from_millis(500)),
+            ("30s", Duration::from_secs(30)),
+            (" 5m ", Duration::from_secs(300)),
+            ("5 minutes", Duration::from_secs(300)),
+            ("5 mins", Duration::from_secs(300)),
+            ("1h 30m", Duration::from_secs(5400)),
+            ("0.1s", Duration::from_millis(100)),
+        ];
+        for (value, expected) in accepted {
+            assert_eq!(parse(value, true), Ok(expected), "{:?}", value);
+            assert_eq!(parse(value, false), Ok(expected), "{:?}", value);
+        }
+
+        let forgiving = [
+            ("30", Duration::from_secs(30)),
+            ("1.5", Duration::from_millis(1500)),
+            ("0,1s", Duration::from_millis(100)),
+            ("2,5", Duration::from_millis(2500)),
+        ];
+        for (value, expected) in forgiving {
+            let err = parse(value, true).unwrap_err();
+            assert!(err.contains("cache.strict_durations: false"), "{:?}: {}", value, err);
+            assert_eq!(parse(value, false), Ok(expected), "{:?}", value);
+        }
+
+        for value in ["", "soon", "5 fortnights", "-5", "5s ago", "1,5,2s"] {
+            for strict in [true, false] {
+                let err = parse(value, strict).unwrap_err();
+                assert!(err.contains(&format!("{:?}", value)), "{}", err);
+                assert!(err.contains("\"500ms\", \"30s\""), "examples are quoted: {}", err);
+            }
+        }
+    }
+
+    /// Test that YAML numbers are refused as durations by default and taken as seconds when
+    /// not strict.
+    #[test]
+    fn test_bare_numbers_in_config() {
+        let err = config(None, "30", "1h").unwrap_err();
+        assert!(format!("{:#}", err).contains("a bare number has no unit"), "{:#}", err);
+
+        let cfg = config(Some(false), "30", "1.5").unwrap();
+        assert_eq!(lifetime_ttl(&cfg), Some(Duration::from_secs(30)));
+        let rule = cfg.cache.rules.as_ref().unwrap()["/api/v1/user"].clone();
+        assert_eq!(rule.refresh.as_ref().unwrap().ttl, Some(Duration::from_millis(1500)));
+
+        let cfg = config(Some(true), "5 minutes", "0.5s").unwrap();
+        assert_eq!(lifetime_ttl(&cfg), Some(Duration::from_secs(300)));
+    }
+
+    /// Test that a malformed duration is reported with its field path, position and value.
+    #[test]
+    fn test_error_names_the_field() {
+        let err = format!("{:#}", config(None, "1h", "0,1s").unwrap_err());
+        assert!(err.contains("cache.rules./api/v1/user.refresh.ttl"), "{}", err);
+        assert!(err.contains(" at line "), "{}", err);
+        assert!(err.contains("\"0,1s\""), "{}", err);
+
+        let err = format!("{:#}", config(Some(false), "soon", "1h").unwrap_err());
+        assert!(err.contains("cache.lifetime.ttl"), "{}", err);
+        assert!(err.contains("\"soon\""), "{}", err);
+    }
+}
@@ -0,0 +1,58 @@
+//! Cargo features the config depends on.
+//!
+//! Sections backed by a part of the build that may be compiled out (see the `[features]` of
+//! `Cargo.toml`) are refused at load when it was, instead of being silently ignored.
+
+use anyhow::{bail, Result};
+
+use super::CacheBox;
+
+/// Optional parts of the build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Features {
+    pub mocks: bool,
+    pub debug_endpoints: bool,
+    pub dump: bool,
+    pub tracing_otel: bool,
+    pub compression_brotli: bool,
+}
+
+impl Features {
+    /// Features this binary was built with.
+    pub const BUILT: Features = Features {
+        mocks: cfg!(feature = "mocks"),
+        debug_endpoints: cfg!(feature = "debug-endpoints"),
+        dump: cfg!(feature = "dump"),
+        tracing_otel: cfg!(feature = "tracing-otel"),
+        compression_brotli: cfg!(feature = "compression-brotli"),
+    };
+
+    /// Names of the features built in, as in `Cargo.toml`.
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.mocks, "mocks"),
+            (self.debug_endpoints, "debug-endpoints"),
+            (self.dump, "dump"),
+            (self.tracing_otel, "tracing-otel"),
+            (self.compression_brotli, "compression-brotli"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect()
+    }
+
+    /// Fails on the first section enabled whose feature is not built in.
+    pub fn check(&self, cfg: &CacheBox) -> Result<()> {
+        let data = cfg.data.as_ref();
+        if !self.mocks && data.and_then(|d| d.mock.as_ref()).is_some_and(|m| m.enabled) {
+            bail!("data.mock is enabled but advcache was built without the `mocks` feature");
+        }
+        if !self.dump && data.and_then(|d| d.dump.as_ref()).is_some_and(|d| d.enabled) {
+            bail!("data.dump is enabled but advcache was built without the `dump` feature");
+        }
+        if !self.tracing_otel && cfg.traces.as_ref().is_some_and(|t| t.enabled) {
+            bail!("traces is enabled but advcache was built without the `tracing-otel` feature");
+        }
+        Ok(())
+    }
+}
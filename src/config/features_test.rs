@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests {
+    use crate::config::{Cache, Config, Features};
+
+    const ALL: Features =
+        Features { mocks: true, debug_endpoints: true, dump: true, tracing_otel: true, compression_brotli: true };
+    const NONE: Features =
+        Features { mocks: false, debug_endpoints: false, dump: false, tracing_otel: false, compression_brotli: false };
+
+    /// Config with the sections given enabled, in the `data` and `traces` blocks.
+    fn yaml(mock: bool, dump: bool, traces: bool) -> String {
+        format!(
+            r#"
+cache:
+  env: test
+  enabled: true
+  upstream:
+    backend:
+      id: main
+      enabled: true
+      scheme: http
+      host: main.local:8080
+      timeout: 10s
+      max_timeout: 1m
+  storage:
+    mode: listing
+    size: 1073741824
+  data:
+    dump:
+      enabled: {dump}
+      dump_dir: public/dump
+      gzip: false
+      crc32_control_sum: true
+    mock:
+      enabled: {mock}
+  traces:
+    enabled: {traces}
+    service_name: test
+    endpoint: localhost:4317
+    export_batch_timeout: 3s
+"#
+        )
+    }
+
+    fn parsed(mock: bool, dump: bool, traces: bool) -> Cache {
+        serde_yaml::from_str(&yaml(mock, dump, traces)).expect("test config must parse")
+    }
+
+    /// Test that each section is refused without its feature, naming the feature, and passes
+    /// with it.
+    #[test]
+    fn test_check_refuses_sections_of_missing_features() {
+        let sections = [
+            (parsed(true, false, false), Features { mocks: true, ..NONE }, "`mocks`"),
+            (parsed(false, true, false), Features { dump: true, ..NONE }, "`dump`"),
+            (parsed(false, false, true), Features { tracing_otel: true, ..NONE }, "`tracing-otel`"),
+        ];
+        for (cfg, needed, feature) in sections {
+            let err = NONE.check(&cfg.cache).expect_err(feature).to_string();
+            assert!(err.contains(feature), "{}", err);
+            assert!(needed.check(&cfg.cache).is_ok(), "{}", feature);
+        }
+        assert!(NONE.check(&parsed(false, false, false).cache).is_ok(), "disabled sections need no feature");
+        assert!(ALL.check(&parsed(true, true, true).cache).is_ok());
+    }
+
+    /// Test that loading a config enforces the features this binary was built with.
+    #[test]
+    fn test_load_enforces_built_features() {
+        let built = Features::BUILT;
+        assert_eq!(Config::from_yaml(&yaml(true, false, false)).is_ok(), built.mocks);
+        assert_eq!(Config::from_yaml(&yaml(false, true, false)).is_ok(), built.dump);
+        assert_eq!(Config::from_yaml(&yaml(false, false, true)).is_ok(), built.tracing_otel);
+        assert!(Config::from_yaml(&yaml(false, false, false)).is_ok());
+    }
+
+    /// Test that the names of the features built in follow `Cargo.toml`.
+    #[test]
+    fn test_names() {
+        assert!(NONE.names().is_empty());
+        assert_eq!(ALL.names(), ["mocks", "debug-endpoints", "dump", "tracing-otel", "compression-brotli"]);
+    }
+}
@@ -3,6 +3,7 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::net::{IpAddr, ToSocketAddrs};
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -16,6 +17,19 @@ pub const DEBUG: &str = "debug";
 #[allow(dead_code)]
 pub const TEST: &str = "test";
 
+/// Status answered for requests that would be proxied while `upstream.proxy_enabled` is false.
+pub const DEFAULT_PROXY_DISABLED_STATUS: u16 = 404;
+
+/// Status answered for requests refused to protect the origin when `upstream.throttle_status`
+/// is not set.
+pub const DEFAULT_THROTTLE_STATUS: u16 = 429;
+
+/// Largest upstream response body accepted when `backend.max_response_size` is not set (64 MiB).
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 64 << 20;
+
+/// Largest POST body keyed by a rule when its `max_key_body` is not set (64 KiB).
+pub const DEFAULT_MAX_KEY_BODY: usize = 64 << 10;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum SamplingMode {
@@ -52,10 +66,19 @@ impl Clone for Cache {
                 lifetime: self.cache.lifetime.clone(),
                 metrics: self.cache.metrics.clone(),
                 k8s: self.cache.k8s.clone(),
+                health: self.cache.health.clone(),
+                brownout: self.cache.brownout.clone(),
+                shutdown: self.cache.shutdown.clone(),
+                analytics: self.cache.analytics.clone(),
+                audit: self.cache.audit.clone(),
+                strict_durations: self.cache.strict_durations,
                 rules: self.cache.rules.as_ref().map(|rules| {
                     rules.iter().map(|(k, v)| (k.clone(), Arc::clone(v))).collect()
                 }),
                 rules_raw: None, // rules_raw is only used during deserialization
+                source: self.cache.source.clone(),
+                // Shared, so that a reload applied through one clone is seen by all of them.
+                reloaded: self.cache.reloaded.clone(),
             },
         }
     }
@@ -80,10 +103,42 @@ pub struct CacheBox {
     pub lifetime: Option<Lifetime>,
     pub metrics: Option<Metrics>,
     pub k8s: Option<K8S>,
-    #[serde(skip)]
+    #[serde(default)]
+    pub health: Option<Health>,
+    #[serde(default)]
+    pub brownout: Option<Brownout>,
+    #[serde(default)]
+    pub shutdown: Option<Shutdown>,
+    #[serde(default)]
+    pub analytics: Option<Analytics>,
+    #[serde(default)]
+    pub audit: Option<Audit>,
+    /// Whether durations must be written as `humantime` has them (default). False also accepts
+    /// bare numbers of seconds and decimal commas (see [`duration`]).
+    #[serde(default)]
+    pub strict_durations: Option<bool>,
+    /// Processed rules; these are what `/advcache/config` shows, runtime changes included.
+    #[serde(rename = "rules", skip_deserializing, serialize_with = "serialize_rules")]
     pub rules: Option<HashMap<String, Arc<Rule>>>,
-    #[serde(rename = "rules")]
+    #[serde(rename = "rules", skip_serializing)]
     rules_raw: Option<HashMap<String, Rule>>,
+    /// File the config was loaded from, re-read by a reload.
+    #[serde(skip)]
+    source: Option<std::path::PathBuf>,
+    /// Sections swapped in by the last reload, if any (see [`reload`]).
+    #[serde(skip)]
+    reloaded: reload::Cell,
+}
+
+/// Serializes the rules by path, sorted.
+fn serialize_rules<S: serde::Serializer>(
+    rules: &Option<HashMap<String, Arc<Rule>>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let sorted = rules
+        .as_ref()
+        .map(|rules| rules.iter().map(|(path, rule)| (path, &**rule)).collect::<std::collections::BTreeMap<_, _>>());
+    sorted.serialize(serializer)
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -117,7 +172,7 @@ pub struct Traces {
     pub sampling_rate: Option<f64>,
     #[serde(rename = "export_batch_size")]
     pub export_batch_size: Option<usize>,
-    #[serde(rename = "export_batch_timeout", with = "humantime_serde")]
+    #[serde(rename = "export_batch_timeout", with = "duration")]
     pub export_batch_timeout: Option<Duration>,
     #[serde(rename = "export_max_queue")]
     pub export_max_queue: Option<usize>,
@@ -127,6 +182,39 @@ pub struct Traces {
 pub struct Api {
     pub name: Option<String>,
     pub port: Option<String>,
+    /// Default `cache_key.query_ignore` of rules that do not set their own.
+    #[serde(default)]
+    pub query_ignore: Option<Vec<String>>,
+    /// Keep ignored query params on the URL of upstream fills (they are still left out of the key).
+    #[serde(default)]
+    pub forward_ignored_query: Option<bool>,
+    /// Connections the API listener serves at a time; unlimited when unset.
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+    /// Connections accepted beyond `max_connections` that wait for a free slot (none by default).
+    #[serde(default)]
+    pub connections_overflow: Option<usize>,
+    /// With it set, connections beyond the overflow are answered 503 with this `Retry-After`
+    /// instead of being left in the accept backlog.
+    #[serde(default, with = "duration")]
+    pub shed_retry_after: Option<Duration>,
+    /// Port of a separate listener for the admin, probe and metrics endpoints, so an overloaded
+    /// instance can still be inspected.
+    #[serde(default)]
+    pub admin_port: Option<String>,
+    /// `max_connections` of the admin listener; unlimited when unset.
+    #[serde(default)]
+    pub admin_max_connections: Option<usize>,
+    /// How long a `/advcache/clear` token is valid (60s by default).
+    #[serde(default, with = "duration")]
+    pub clear_token_ttl: Option<Duration>,
+    /// Requests per second to a path prefix no rule matches above which a warning is logged,
+    /// at most once a minute per prefix; no warning when unset.
+    #[serde(default)]
+    pub unmatched_warn_rps: Option<u64>,
+    /// Sends the key of the entry a response is about in `X-Cache-Key`, for debugging.
+    #[serde(default)]
+    pub debug_headers: Option<bool>,
 }
 
 impl Clone for Api {
@@ -134,6 +222,16 @@ impl Clone for Api {
         Self {
             name: self.name.clone(),
             port: self.port.clone(),
+            query_ignore: self.query_ignore.clone(),
+            forward_ignored_query: self.forward_ignored_query,
+            max_connections: self.max_connections,
+            connections_overflow: self.connections_overflow,
+            shed_retry_after: self.shed_retry_after,
+            admin_port: self.admin_port.clone(),
+            admin_max_connections: self.admin_max_connections,
+            clear_token_ttl: self.clear_token_ttl,
+            unmatched_warn_rps: self.unmatched_warn_rps,
+            debug_headers: self.debug_headers,
         }
     }
 }
@@ -141,12 +239,70 @@ impl Clone for Api {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Runtime {
     pub num_cpus: usize,
+    /// File keeping state across restarts: changes made through the admin API (e.g. drained
+    /// backends), which last until the process exits when unset, and the last shutdown report.
+    #[serde(default)]
+    pub overrides: Option<String>,
+    #[serde(default)]
+    pub idle_reclaim: Option<IdleReclaim>,
+}
+
+/// Idle reclamation: once the request rate stays below `max_rps` for `idle_for`, idle upstream
+/// connections are closed, per-thread scratch buffers released and free allocator pages purged
+/// (jemalloc builds), so an instance idling overnight returns memory to the system.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct IdleReclaim {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Requests per second (over the 5s metrics tick) under which the instance is idle (1 by default).
+    #[serde(default)]
+    pub max_rps: Option<f64>,
+    /// How long the rate must stay under `max_rps` before reclaiming (10m by default); it is
+    /// reclaimed once per idle period.
+    #[serde(default, with = "duration")]
+    pub idle_for: Option<Duration>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Probe {
-    #[serde(with = "humantime_serde")]
+    #[serde(with = "duration")]
     pub timeout: Option<Duration>,
+    /// Path of the liveness probe, `/k8s/probe` when unset.
+    #[serde(default)]
+    pub liveness_path: Option<String>,
+    /// Path of the readiness probe, `/healthz` when unset.
+    #[serde(default)]
+    pub readiness_path: Option<String>,
+    /// Floor of the probe `timeout`: a shorter one is raised to it. 10ms when unset.
+    #[serde(default, with = "duration")]
+    pub min_timeout: Option<Duration>,
+    /// Time `/advcache/ready` may take to gather its checks before answering 503, `timeout`
+    /// when unset.
+    #[serde(default, with = "duration")]
+    pub readiness_timeout: Option<Duration>,
+    /// Port of a listener serving the probes only, started before the rest of the instance so
+    /// probes are answered while it warms up. The probes move off the API and admin ports.
+    #[serde(default)]
+    pub port: Option<String>,
+}
+
+impl Probe {
+    pub const DEFAULT_LIVENESS_PATH: &'static str = "/k8s/probe";
+    pub const DEFAULT_READINESS_PATH: &'static str = "/healthz";
+    pub const DEFAULT_READINESS_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub fn liveness_path(&self) -> &str {
+        self.liveness_path.as_deref().unwrap_or(Self::DEFAULT_LIVENESS_PATH)
+    }
+
+    pub fn readiness_path(&self) -> &str {
+        self.readiness_path.as_deref().unwrap_or(Self::DEFAULT_READINESS_PATH)
+    }
+
+    /// Time the aggregated readiness checks may take.
+    pub fn readiness_timeout(&self) -> Duration {
+        self.readiness_timeout.or(self.timeout).unwrap_or(Self::DEFAULT_READINESS_TIMEOUT)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -154,14 +310,180 @@ pub struct K8S {
     pub probe: Probe,
 }
 
+/// Conditions under which `/advcache/health` answers 503, telling a load balancer to prefer
+/// another replica. Each condition is on when its threshold is set.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Health {
+    /// Hit rate in percent under which the instance degrades once it lasts `min_hit_rate_for`.
+    #[serde(default)]
+    pub min_hit_rate: Option<f64>,
+    /// How long the hit rate must stay low (0 by default: the first low tick counts).
+    #[serde(default, with = "duration")]
+    pub min_hit_rate_for: Option<Duration>,
+    /// Error rate in percent of all requests above which the instance degrades.
+    #[serde(default)]
+    pub max_error_rate: Option<f64>,
+    /// Degrade while memory usage is above the storage hard limit.
+    #[serde(default)]
+    pub memory_over_hard_limit: Option<bool>,
+    /// Degrade while the upstream backend is marked down.
+    #[serde(default)]
+    pub upstream_down: Option<bool>,
+    /// Number of entries past their refresh deadline above which the instance degrades.
+    #[serde(default)]
+    pub max_refresh_backlog: Option<usize>,
+}
+
+/// Brownout: while the upstream path is saturated, cache misses are answered 503 with
+/// `Retry-After` instead of queueing for the origin; hits keep being served. Each signal is on
+/// when its threshold is set, and any breached signal engages the brownout.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Brownout {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Engage once every upstream connection slot (`backend.concurrency`) stays taken this long.
+    #[serde(default, with = "duration")]
+    pub upstream_saturated_for: Option<Duration>,
+    /// Number of entries past their refresh deadline above which the brownout engages.
+    #[serde(default)]
+    pub max_refresh_backlog: Option<usize>,
+    /// Error rate in percent of all requests above which the brownout engages.
+    #[serde(default)]
+    pub max_error_rate: Option<f64>,
+    /// How long every signal must stay clear before the brownout disengages (30s by default).
+    #[serde(default, with = "duration")]
+    pub recover_after: Option<Duration>,
+    /// `Retry-After` of shed requests (5s by default).
+    #[serde(default, with = "duration")]
+    pub retry_after: Option<Duration>,
+}
+
+/// Sampled cache accesses replayed by `/advcache/whatif` to estimate the hit rate and memory
+/// under other storage sizes and TTLs. Off unless `sample_rate` is set.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Analytics {
+    /// Share of keys (0, 1] whose accesses are recorded; every access of a sampled key is kept.
+    #[serde(default)]
+    pub sample_rate: Option<f64>,
+    /// Sampled accesses kept, the oldest dropped first (1M by default).
+    #[serde(default)]
+    pub max_events: Option<usize>,
+}
+
+/// Append-only log of the admin calls changing the state of the instance, read back by
+/// `/advcache/audit`. Off unless `path` is set.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Audit {
+    /// JSON-lines file records are appended to.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Size in bytes past which the file is rotated (64 MiB by default).
+    #[serde(default)]
+    pub max_size: Option<u64>,
+    /// Rotated files kept, `<path>.1` being the newest (5 by default).
+    #[serde(default)]
+    pub max_files: Option<usize>,
+}
+
+/// Time budget of each shutdown phase, run in this order. A phase over its budget is given up
+/// with a warning and the next one starts.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Shutdown {
+    /// Closing the listeners.
+    #[serde(default, with = "duration")]
+    pub stop_accepting: Option<Duration>,
+    /// Answering the requests of open connections.
+    #[serde(default, with = "duration")]
+    pub drain_http: Option<Duration>,
+    /// Stopping the background workers.
+    #[serde(default, with = "duration")]
+    pub stop_workers: Option<Duration>,
+    /// Writing the dump, when `data.dump` is enabled.
+    #[serde(default, with = "duration")]
+    pub dump: Option<Duration>,
+    /// Cancelling whatever is left and closing storage and exporters.
+    #[serde(default, with = "duration")]
+    pub final_cancel: Option<Duration>,
+}
+
+impl Shutdown {
+    pub const DEFAULT_STOP_ACCEPTING: Duration = Duration::from_secs(1);
+    pub const DEFAULT_DRAIN_HTTP: Duration = Duration::from_secs(20);
+    pub const DEFAULT_STOP_WORKERS: Duration = Duration::from_secs(5);
+    pub const DEFAULT_DUMP: Duration = Duration::from_secs(60);
+    pub const DEFAULT_FINAL_CANCEL: Duration = Duration::from_secs(5);
+
+    pub fn stop_accepting(&self) -> Duration {
+        self.stop_accepting.unwrap_or(Self::DEFAULT_STOP_ACCEPTING)
+    }
+
+    pub fn drain_http(&self) -> Duration {
+        self.drain_http.unwrap_or(Self::DEFAULT_DRAIN_HTTP)
+    }
+
+    pub fn stop_workers(&self) -> Duration {
+        self.stop_workers.unwrap_or(Self::DEFAULT_STOP_WORKERS)
+    }
+
+    pub fn dump(&self) -> Duration {
+        self.dump.unwrap_or(Self::DEFAULT_DUMP)
+    }
+
+    pub fn final_cancel(&self) -> Duration {
+        self.final_cancel.unwrap_or(Self::DEFAULT_FINAL_CANCEL)
+    }
+
+    /// Longest a shutdown may take; the pod's `terminationGracePeriodSeconds` should exceed it.
+    pub fn total(&self) -> Duration {
+        self.stop_accepting() + self.drain_http() + self.stop_workers() + self.dump() + self.final_cancel()
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Metrics {
     pub enabled: bool,
+    /// Basic auth required on `/metrics`; the endpoint is open when unset.
+    #[serde(default)]
+    pub auth: Option<MetricsAuth>,
+    /// Status codes counted under their own label; the others are counted by class (`4xx`, ...).
+    /// Defaults to the common codes, see `metrics::code::DEFAULT_STATUS_CODES`.
+    #[serde(default)]
+    pub status_codes: Option<Vec<u16>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MetricsAuth {
+    pub username: String,
+    /// Environment variable holding the password, so it stays out of the config file.
+    pub password_env: String,
+}
+
+impl MetricsAuth {
+    /// Reads the password from `password_env`.
+    pub fn password(&self) -> Result<String> {
+        match std::env::var(&self.password_env) {
+            Ok(password) if !password.is_empty() => Ok(password),
+            _ => anyhow::bail!("metrics.auth.password_env {:?} is not set or empty", self.password_env),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Logs {
     pub level: Option<String>,
+    pub redact: Option<Redact>,
+    /// Recent errors kept for `/advcache/errors` (default 512, 0 keeps none).
+    pub error_ring: Option<usize>,
+    /// File logs are appended to instead of stdout; SIGUSR2 reopens it, e.g. after logrotate.
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Query parameters and headers whose values are masked wherever a request is logged or traced.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Redact {
+    pub query: Option<Vec<String>>,
+    pub headers: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -169,6 +491,22 @@ pub struct Upstream {
     pub policy: Option<String>,
     pub cluster: Option<Cluster>,
     pub backend: Option<Backend>,
+    /// When false, requests are never proxied to the origin: unmatched paths and every request
+    /// in bypass mode are answered with `proxy_disabled_status`. Rule-matched misses still fill.
+    pub proxy_enabled: Option<bool>,
+    /// Status returned instead of proxying when `proxy_enabled` is false (404 by default).
+    pub proxy_disabled_status: Option<u16>,
+    /// Status of requests refused to protect the origin: throttled by the `deny` policy, shed
+    /// during a brownout or refused a fill slot (429 by default). They carry `Retry-After`.
+    #[serde(default)]
+    pub throttle_status: Option<u16>,
+    /// Other cache instances (`host:port` of their API listener) that invalidations, clears and
+    /// bypass toggles handled here are forwarded to.
+    #[serde(default)]
+    pub peers: Option<Vec<String>>,
+    /// Timeout of a call forwarded to a peer (2s by default).
+    #[serde(default, with = "duration")]
+    pub peer_timeout: Option<Duration>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -191,9 +529,20 @@ pub struct Backend {
     pub host_bytes: Option<Vec<u8>>,
     pub rate: Option<usize>,
     pub concurrency: Option<usize>,
-    #[serde(with = "humantime_serde")]
+    /// Umbrella cap of a request up to the response head (10s by default).
+    #[serde(with = "duration")]
     pub timeout: Option<Duration>,
-    #[serde(rename = "max_timeout", with = "humantime_serde")]
+    /// Cap of establishing a connection, TCP connect and TLS handshake together. Unset keeps
+    /// the 3s TCP connect timeout with no bound on the handshake.
+    #[serde(default, with = "duration")]
+    pub connect_timeout: Option<Duration>,
+    /// Cap of the wait for the response head once the request has a connection.
+    #[serde(default, with = "duration")]
+    pub ttfb_timeout: Option<Duration>,
+    /// Cap of reading the response body once the head arrived; unset leaves it unbounded.
+    #[serde(default, with = "duration")]
+    pub body_timeout: Option<Duration>,
+    #[serde(rename = "max_timeout", with = "duration")]
     pub max_timeout: Option<Duration>,
     #[serde(rename = "use_max_timeout_header")]
     pub use_max_timeout_header: Option<String>,
@@ -205,6 +554,106 @@ pub struct Backend {
     pub addr: Option<String>,
     #[serde(rename = "health_path")]
     pub health_path: Option<String>,
+    /// Notification fired when the health observer marks the backend down or up.
+    #[serde(default)]
+    pub on_health_change: Option<HealthHook>,
+    /// Deep health check: a real ruled request sent periodically besides `healthcheck`, which
+    /// marks the backend down as well when it fails.
+    #[serde(default)]
+    pub health_synthetic: Option<HealthSynthetic>,
+    /// Upper bound of an upstream response body in bytes; larger responses are aborted
+    /// while being read. Defaults to [`DEFAULT_MAX_RESPONSE_SIZE`].
+    #[serde(default)]
+    pub max_response_size: Option<usize>,
+    /// `Accept-Encoding` sent to the origin on cache fills and refreshes instead of the
+    /// client's (e.g. "gzip, br"); proxied requests keep the client's header. Encoded answers
+    /// are stored as described in [`crate::upstream::encoding`].
+    #[serde(default)]
+    pub accept_encoding: Option<String>,
+    /// HMAC signing of fills, refreshes, proxied requests and health checks; see
+    /// [`crate::upstream::signing`].
+    #[serde(default)]
+    pub signing: Option<Signing>,
+}
+
+impl Backend {
+    /// Effective upstream response body limit in bytes.
+    pub fn max_response_size(&self) -> usize {
+        self.max_response_size.unwrap_or(DEFAULT_MAX_RESPONSE_SIZE)
+    }
+}
+
+/// Request signing of a backend. The key is read from the `key_env` environment variable at
+/// startup, never from a config file, so it shows in neither the effective config nor diffs.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Signing {
+    /// Only `hmac-sha256`, the default.
+    #[serde(default)]
+    pub algorithm: Option<String>,
+    /// Environment variable holding the key.
+    pub key_env: String,
+    /// Request headers covered by the signature besides method, path, timestamp and body hash.
+    #[serde(default)]
+    pub headers_to_sign: Vec<String>,
+}
+
+/// Webhook called with a JSON payload on backend health transitions.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HealthHook {
+    pub webhook_url: Option<String>,
+    /// Timeout of a single delivery attempt (5s by default).
+    #[serde(default, with = "duration")]
+    pub timeout: Option<Duration>,
+    /// Minimum time between two deliveries (10s by default). Transitions within it are
+    /// coalesced into one notification carrying the latest state.
+    #[serde(default, with = "duration")]
+    pub min_interval: Option<Duration>,
+}
+
+/// Synthetic request of the deep health check. It is never stored and carries the
+/// `X-AdvCache-Synthetic` header, so the origin can tell it from client traffic.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HealthSynthetic {
+    /// Path of a cache rule.
+    pub path: String,
+    /// Query sent as is, e.g. `user[id]=1`.
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Status a passing answer has (200 by default).
+    #[serde(default)]
+    pub expected_status: Option<u16>,
+    /// Time between two requests (5s by default).
+    #[serde(default, with = "duration")]
+    pub interval: Option<Duration>,
+    /// Failed requests in a row marking the backend down (3 by default).
+    #[serde(default)]
+    pub fail_threshold: Option<u32>,
+    /// Passing requests in a row bringing the backend back up (3 by default).
+    #[serde(default)]
+    pub ok_threshold: Option<u32>,
+}
+
+impl HealthSynthetic {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+    pub const DEFAULT_THRESHOLD: u32 = 3;
+
+    pub fn expected_status(&self) -> u16 {
+        self.expected_status.unwrap_or(200)
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval.unwrap_or(Self::DEFAULT_INTERVAL)
+    }
+
+    pub fn fail_threshold(&self) -> u32 {
+        self.fail_threshold.unwrap_or(Self::DEFAULT_THRESHOLD)
+    }
+
+    pub fn ok_threshold(&self) -> u32 {
+        self.ok_threshold.unwrap_or(Self::DEFAULT_THRESHOLD)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -212,6 +661,9 @@ pub struct Dump {
     pub enabled: bool,
     #[serde(rename = "dump_dir")]
     pub dir: Option<String>,
+    /// Directory taking the rest of a dump when `dump_dir` runs out of space or is not writable.
+    #[serde(rename = "dump_dir_fallback")]
+    pub dir_fallback: Option<String>,
     #[serde(rename = "dump_name")]
     pub name: Option<String>,
     #[serde(rename = "max_versions")]
@@ -219,12 +671,45 @@ pub struct Dump {
     pub gzip: bool,
     #[serde(rename = "crc32_control_sum")]
     pub crc32_control: bool,
+    /// While a dump is restored, a miss on a key still waiting in it loads that entry from disk
+    /// first instead of going to the origin. Needs the key indexes written with each dump.
+    #[serde(default)]
+    pub load_on_demand: bool,
+    /// Moves the shard files of a dump written flat into `dump_dir` (before versioned dirs)
+    /// into `v1/` once they loaded; without it they are loaded read-only on every start.
+    #[serde(default)]
+    pub migrate_legacy: bool,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Mock {
     pub enabled: bool,
+    /// Entries of the default spec (`/api/v1/user`), used when `specs` is unset.
     pub length: Option<usize>,
+    /// Entries generated per second at most; unset generates them as fast as it can.
+    #[serde(default)]
+    pub rate: Option<usize>,
+    /// What to generate, path by path.
+    #[serde(default)]
+    pub specs: Option<Vec<MockSpec>>,
+}
+
+/// Mock entries of one path.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MockSpec {
+    pub path: String,
+    /// Entries generated.
+    pub count: usize,
+    /// Body size in bytes; unset generates a small fixed JSON document.
+    #[serde(default)]
+    pub body_size: Option<usize>,
+    /// Repetitive bodies when true, random ones otherwise.
+    #[serde(default)]
+    pub compressible: bool,
+    /// Distinct values of the synthetic `mock_id` query param, hence distinct keys (default
+    /// `count`). Entries past it overwrite earlier ones.
+    #[serde(default)]
+    pub query_cardinality: Option<usize>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -241,8 +726,25 @@ pub struct Eviction {
     #[serde(rename = "hard_limit")]
     pub hard_limit: Option<f64>,
     pub replicas: Option<usize>,
-    #[serde(rename = "check_interval", with = "humantime_serde")]
+    #[serde(rename = "check_interval", with = "duration")]
     pub check_interval: Option<Duration>,
+    pub audit: Option<EvictionAudit>,
+    /// Whether a failure to start the evictor fails startup (default) or only takes the
+    /// instance out of readiness.
+    #[serde(default)]
+    pub critical: Option<bool>,
+}
+
+/// Sampled stream of evicted entries for offline analysis of admission and eviction.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EvictionAudit {
+    pub enabled: bool,
+    /// Share of evictions (0, 1] recorded, default 1.
+    pub sample_rate: Option<f64>,
+    /// NDJSON file the events are appended to; without it they only feed the per-rule counters.
+    pub path: Option<String>,
+    /// Size in bytes past which the file is rotated to `<path>.1`, default 64 MiB.
+    pub max_file_size: Option<u64>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -257,6 +759,87 @@ pub struct Storage {
     pub hard_memory_limit: i64,
     #[serde(skip)]
     pub admission_memory_limit: i64,
+    /// Share of reads (0, 1] verifying the payload against the checksum taken when it was stored.
+    /// Unset leaves payloads unhashed.
+    pub verify_sample: Option<f64>,
+    /// Admin walks over the shards (invalidations, key schema purges, rule reconciliations) run
+    /// at once, default 1; the others queue. Dumps do not count.
+    pub admin_walks: Option<usize>,
+    /// How long an invalidation waits in the walk queue before giving up with 503, default 30s.
+    #[serde(default, with = "duration")]
+    pub admin_walk_timeout: Option<Duration>,
+    /// Entries an admin job (invalidation, clear by rule, key schema purge) acts on per
+    /// second over all shards. Unset leaves jobs unthrottled.
+    #[serde(default)]
+    pub admin_job_rate: Option<u32>,
+    /// How far ahead of the local clock an entry timestamp (of a stored entry or an
+    /// `_if_refreshed_before`) may be before it is clamped to now, default 5m.
+    #[serde(default, with = "duration")]
+    pub max_clock_skew: Option<Duration>,
+    /// Share [0, 1] of shard lock acquisitions whose wait is sampled, reported by
+    /// `/advcache/shards` and `shard_lock_wait_seconds`. Unset or 0 leaves the locks unprofiled.
+    #[serde(default)]
+    pub lock_profiling: Option<f64>,
+    /// Stamps the access time of an entry on its hits, read by the sampling eviction and the
+    /// eviction audit (`idle_ms`). Off by default: hits write nothing and the access time of an
+    /// entry is when it was last written.
+    #[serde(default)]
+    pub track_access_time: Option<bool>,
+    /// How old the access time of an entry gets before a hit writes it again, default 10s; 0
+    /// writes it on every hit. Hits within it only read the stamp.
+    #[serde(default, with = "duration")]
+    pub access_time_granularity: Option<Duration>,
+    /// Counts one hit in four of an entry, as four, in its hit counter (kept while the sampling
+    /// eviction, prewarm or the eviction audit reads it), sparing hot keys three writes in four.
+    /// Off by default: every hit is counted.
+    #[serde(default)]
+    pub sample_hits: Option<bool>,
+    /// Largest response body stored, in decoded bytes, for rules without a `max_body_size` of
+    /// their own. Unset or 0 is unlimited.
+    #[serde(default)]
+    pub max_body_size: Option<usize>,
+}
+
+impl Storage {
+    /// Sets the size and derives the memory limits from it: soft and hard at the eviction
+    /// thresholds (0.8 and 0.99 by default), admission 100 MiB under soft.
+    #[cfg_attr(not(any(test, feature = "mocks")), allow(dead_code))]
+    pub fn set_size(&mut self, size: i64, eviction: Option<&Eviction>) {
+        let limits = MemoryLimits::derive(size, eviction);
+        self.size = size;
+        self.soft_memory_limit = limits.soft;
+        self.hard_memory_limit = limits.hard;
+        self.admission_memory_limit = limits.admission;
+    }
+
+    /// Memory limits derived from the size.
+    pub fn memory_limits(&self) -> MemoryLimits {
+        MemoryLimits {
+            size: self.size,
+            soft: self.soft_memory_limit,
+            hard: self.hard_memory_limit,
+            admission: self.admission_memory_limit,
+        }
+    }
+}
+
+/// Storage size with the limits eviction and admission enforce, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MemoryLimits {
+    pub size: i64,
+    pub soft: i64,
+    pub hard: i64,
+    pub admission: i64,
+}
+
+impl MemoryLimits {
+    /// Limits of a storage of `size` bytes under the eviction thresholds.
+    pub fn derive(size: i64, eviction: Option<&Eviction>) -> Self {
+        let soft = eviction.and_then(|e| e.soft_limit).unwrap_or(0.8);
+        let hard = eviction.and_then(|e| e.hard_limit).unwrap_or(0.99);
+        let soft = (size as f64 * soft) as i64;
+        Self { size, soft, hard: (size as f64 * hard) as i64, admission: soft - (100 << 20) }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -277,20 +860,51 @@ pub struct Lifetime {
     pub enabled: bool,
     #[serde(rename = "on_ttl")]
     pub on_ttl: Option<TTLMode>,
-    #[serde(rename = "ttl", with = "humantime_serde")]
+    #[serde(rename = "ttl", with = "duration")]
     pub ttl: Option<Duration>,
     pub replicas: Option<usize>,
     pub rate: Option<usize>,
     pub beta: Option<f64>,
     pub coefficient: Option<f64>,
+    /// How long past its TTL a stored entry may still be served when a miss fill
+    /// fails or returns 5xx. Unset disables serving stale on error.
+    #[serde(default, with = "duration")]
+    pub max_stale_on_error: Option<Duration>,
+    /// In remove mode, answer entries past their TTL as misses at read time instead of serving
+    /// them until the lifetime manager gets to remove them. Off by default.
+    #[serde(default)]
+    pub strict_ttl: Option<bool>,
+    /// Refresh mode only: refresh popular entries ahead of their TTL during a quiet window.
+    #[serde(default)]
+    pub prewarm: Option<Prewarm>,
+    /// On a config reload, re-point stored entries at the reloaded rule of their path, so they
+    /// follow its new refresh settings instead of those they were stored under. Off by default.
+    #[serde(default)]
+    pub reconcile_rules: Option<bool>,
+    /// Whether a failure to start the lifetime manager fails startup (default) or only takes
+    /// the instance out of readiness.
+    #[serde(default)]
+    pub critical: Option<bool>,
     #[serde(skip)]
     pub is_remove_on_ttl: Arc<AtomicBool>,
 }
 
+/// Ahead-of-time refresh of hot entries, so their TTLs do not all run out at peak hours.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Prewarm {
+    /// Local time window, "HH:MM-HH:MM"; it may wrap past midnight.
+    pub window: String,
+    /// Prewarm refreshes per second at most; they also count against `lifetime.rate`.
+    pub rate: usize,
+    /// Entries whose refresh is due within this long are prewarmed, default 6h.
+    #[serde(default, with = "duration")]
+    pub ahead: Option<Duration>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LifetimeRule {
     pub enabled: bool,
-    #[serde(rename = "ttl", with = "humantime_serde")]
+    #[serde(rename = "ttl", with = "duration")]
     pub ttl: Option<Duration>,
     pub beta: Option<f64>,
     pub coefficient: Option<f64>,
@@ -307,6 +921,141 @@ pub struct Rule {
     #[serde(rename = "cache_value")]
     pub cache_value: RuleValue,
     pub refresh: Option<LifetimeRule>,
+    /// Set to false to opt the rule out of serving stale entries on upstream errors.
+    pub stale_on_error: Option<bool>,
+    /// Set to false to keep serving misses of the rule during a brownout (critical paths).
+    #[serde(default)]
+    pub shed_on_brownout: Option<bool>,
+    /// Methods served through the cache: `GET`, `HEAD` and `POST`, `GET` being required;
+    /// GET and HEAD when unset. Requests of other methods are proxied. `POST` needs `key_body`.
+    #[serde(default)]
+    pub methods: Option<Vec<String>>,
+    /// Mixes the request body into the key of POST requests, as its xxh3 hash.
+    #[serde(default)]
+    pub key_body: Option<bool>,
+    /// Largest POST body keyed, in bytes (default [`DEFAULT_MAX_KEY_BODY`]); requests with
+    /// larger ones are proxied.
+    #[serde(default)]
+    pub max_key_body: Option<usize>,
+    /// Largest response body stored, in decoded bytes, over `storage.max_body_size`; larger
+    /// ones are answered without being stored. 0 is unlimited.
+    #[serde(default)]
+    pub max_body_size: Option<usize>,
+    /// Whether `refresh` was copied from the global `lifetime` section for want of a rule one.
+    #[serde(skip)]
+    pub refresh_inherited: bool,
+}
+
+impl Rule {
+    /// Creates a rule for the path with empty key/value whitelists and no overrides.
+    #[cfg_attr(not(any(feature = "dump", feature = "mocks")), allow(dead_code))]
+    pub fn bare(path: &str) -> Self {
+        Self {
+            path: Some(path.to_string()),
+            path_bytes: Some(path.as_bytes().to_vec()),
+            cache_key: RuleKey {
+                query: None,
+                query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
+                headers: None,
+                headers_map: None,
+            },
+            cache_value: RuleValue {
+                headers: None,
+                headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
+            },
+            refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
+        }
+    }
+
+    /// Whether requests of `method` are served through the cache, looked up and stored;
+    /// others are proxied.
+    pub fn caches_method(&self, method: &str) -> bool {
+        match self.methods {
+            Some(ref methods) => methods.iter().any(|m| m.eq_ignore_ascii_case(method)),
+            None => method.eq_ignore_ascii_case("GET") || method.eq_ignore_ascii_case("HEAD"),
+        }
+    }
+
+    /// Whether the body of POST requests is part of their key (`key_body`).
+    pub fn keys_body(&self) -> bool {
+        self.key_body == Some(true)
+    }
+
+    /// Largest POST body keyed, in bytes.
+    pub fn max_key_body(&self) -> usize {
+        self.max_key_body.unwrap_or(DEFAULT_MAX_KEY_BODY)
+    }
+
+    /// Largest response body stored, in decoded bytes; `None` when unlimited.
+    pub fn max_body_size(&self, storage: &Storage) -> Option<usize> {
+        self.max_body_size.or(storage.max_body_size).filter(|&limit| limit > 0)
+    }
+
+    /// Fingerprint of the cache key composition: the query and header whitelists, the
+    /// ignored query params and the key transformer. List order and header name case do not matter, as they do not
+    /// change the keys. Never returns 0, which entries use for "schema unknown".
+    pub fn key_schema(&self) -> u64 {
+        use xxhash_rust::xxh3::{xxh3_64, xxh3_64_with_seed, Xxh3};
+
+        let queries = self
+            .cache_key
+            .query
+            .iter()
+            .flatten()
+            .fold(0u64, |acc, q| acc.wrapping_add(xxh3_64_with_seed(q.as_bytes(), 1)));
+        let ignored = self
+            .cache_key
+            .query_ignore
+            .iter()
+            .flatten()
+            .fold(0u64, |acc, q| acc.wrapping_add(xxh3_64_with_seed(q.as_bytes(), 3)));
+
+        let headers = self.cache_key.headers.iter().flatten().fold(0u64, |acc, h| {
+            let mut hasher = Xxh3::with_seed(2);
+            let mut buf = [0u8; 32];
+            for chunk in h.as_bytes().chunks(buf.len()) {
+                for (dst, src) in buf.iter_mut().zip(chunk) {
+                    *dst = src.to_ascii_lowercase();
+                }
+                hasher.update(&buf[..chunk.len()]);
+            }
+            acc.wrapping_add(hasher.digest())
+        });
+
+        let mut buf = [0u8; 24];
+        buf[..8].copy_from_slice(&queries.to_le_bytes());
+        buf[8..16].copy_from_slice(&headers.to_le_bytes());
+        buf[16..].copy_from_slice(&ignored.to_le_bytes());
+        let schema = xxh3_64(&buf);
+        match self.cache_key.transformer {
+            Some(ref transformer) => xxh3_64_with_seed(transformer.as_bytes(), schema).max(1),
+            None => schema.max(1),
+        }
+    }
+
+    /// Whether the rule's entries go into dumps (`cache_value.persist`, true by default).
+    #[cfg_attr(not(feature = "dump"), allow(dead_code))]
+    pub fn is_persisted(&self) -> bool {
+        self.cache_value.persist != Some(false)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -314,16 +1063,76 @@ pub struct RuleKey {
     pub query: Option<Vec<String>>,
     #[serde(skip)]
     pub query_bytes: Option<Vec<Vec<u8>>>,
+    /// Query params dropped before whitelisting, by name or glob (`utm_*`).
+    #[serde(default)]
+    pub query_ignore: Option<Vec<String>>,
+    /// What to do with a whitelisted query param given more than once (default: last).
+    #[serde(default)]
+    pub duplicate_query: Option<DuplicateQuery>,
+    /// Registered key transformer adding bytes derived from the request to the key, e.g.
+    /// `jwt_sub` (see [`crate::plugin`]).
+    #[serde(default)]
+    pub transformer: Option<String>,
     pub headers: Option<Vec<String>>,
     #[serde(skip)]
     pub headers_map: Option<HashMap<String, Vec<u8>>>,
 }
 
+/// Value kept in the key for a whitelisted query param given more than once with different
+/// values. Repeats of the same value always collapse into one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateQuery {
+    /// The first value in request order.
+    First,
+    /// The last value in request order.
+    #[default]
+    Last,
+    /// The request is answered 400.
+    Reject,
+    /// All distinct values, sorted and joined with a comma.
+    Join,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RuleValue {
     pub headers: Option<Vec<String>>,
     #[serde(skip)]
     pub headers_map: Option<std::collections::HashSet<String>>,
+    /// Set to false to keep the rule's entries in memory only: dumps skip them and loading
+    /// a dump that still holds some drops them.
+    #[serde(default)]
+    pub persist: Option<bool>,
+    /// Share of the rule's keys served through the cache (default 100); the others are proxied
+    /// without being stored. Adjustable at runtime through `/advcache/rollout`.
+    #[serde(default)]
+    pub rollout_percent: RolloutPercent,
+    /// Upstream fills of the rule's misses run at once; misses past it wait for a slot up to
+    /// `max_fill_wait` and are refused with `upstream.throttle_status` after. Unset leaves
+    /// fills uncapped.
+    #[serde(default)]
+    pub max_concurrent_fills: Option<usize>,
+    /// How long a miss waits for a fill slot under `max_concurrent_fills`, default 1s.
+    #[serde(default, with = "duration")]
+    pub max_fill_wait: Option<Duration>,
+    /// Registered admission hook deciding whether a fetched response is stored (see
+    /// [`crate::plugin`]).
+    #[serde(default)]
+    pub admission_hook: Option<String>,
+    /// Lifetime announced to downstream caches in `Cache-Control: s-maxage` and
+    /// `Surrogate-Control` on responses served from the cache (default off).
+    #[serde(default)]
+    pub downstream_ttl: DownstreamTtl,
+    /// Follows the origin's `Cache-Control` and `Expires` on fills and refreshes: `no-store`
+    /// and `private` answers are not stored, the others are kept for the lifetime they give in
+    /// place of the rule's TTL (default off).
+    #[serde(default)]
+    pub respect_cache_control: Option<bool>,
+    /// Stores 404 and 5xx answers of the rule's misses for this long, so the requests after
+    /// one are answered from memory rather than from the origin. Such entries are removed
+    /// once it is over instead of refreshed (default off: only 200s are stored).
+    #[serde(default, with = "duration")]
+    pub negative_ttl: Option<Duration>,
 }
 
 // Config trait
@@ -388,7 +1197,7 @@ impl ConfigTrait for Config {
         self.cache
             .runtime
             .as_ref()
-            .unwrap_or(&Runtime { num_cpus: 0 })
+            .unwrap_or(&Runtime { num_cpus: 0, overrides: None, idle_reclaim: None })
     }
 
     fn api(&self) -> Option<&Api> {
@@ -404,11 +1213,17 @@ impl ConfigTrait for Config {
     }
 
     fn lifetime(&self) -> Option<&Lifetime> {
-        self.cache.lifetime.as_ref()
+        match self.reloaded() {
+            Some(reloaded) => reloaded.lifetime.as_ref(),
+            None => self.cache.lifetime.as_ref(),
+        }
     }
 
     fn eviction(&self) -> Option<&Eviction> {
-        self.cache.eviction.as_ref()
+        match self.reloaded() {
+            Some(reloaded) => reloaded.eviction.as_ref(),
+            None => self.cache.eviction.as_ref(),
+        }
     }
 
     fn admission(&self) -> Option<&Admission> {
@@ -435,7 +1250,7 @@ impl ConfigTrait for Config {
     }
 
     fn rule(&self, path: &str) -> Option<Arc<Rule>> {
-        self.cache.rules.as_ref()?.get(path).map(Arc::clone)
+        self.rules()?.get(path).map(Arc::clone)
     }
 }
 
@@ -453,9 +1268,30 @@ impl Config {
         let data = std::fs::read_to_string(&abs_path)
             .with_context(|| format!("read config yaml file {:?}", abs_path))?;
 
-        // Parse YAML
-        let mut cfg: Cache = serde_yaml::from_str(&data)
-            .with_context(|| format!("unmarshal yaml from {:?}", abs_path))?;
+        let mut cfg = Self::from_yaml(&data).with_context(|| format!("load config from {:?}", abs_path))?;
+        cfg.cache.source = Some(abs_path);
+
+        if let Some(host) = cfg.backend_host_pointing_to_self() {
+            tracing::warn!(
+                component = "config",
+                event = "upstream_is_self",
+                host = %host,
+                "upstream backend host resolves to this cache's own listen address: every miss would loop back into the cache"
+            );
+        }
+
+        Ok(cfg)
+    }
+
+    /// Parses and processes a YAML document exactly like [`Config::load`], without touching the filesystem.
+    pub fn from_yaml(data: &str) -> Result<Self> {
+        // Parse YAML, with the duration strictness the document asks for
+        let strict = serde_yaml::from_str::<serde_yaml::Value>(data)
+            .ok()
+            .and_then(|doc| doc.get("cache")?.get("strict_durations")?.as_bool())
+            .unwrap_or(true);
+        let mut cfg: Cache =
+            duration::with_strictness(strict, || serde_yaml::from_str(data)).context("unmarshal yaml")?;
 
         // Initialize atomic fields
         cfg.cache.atomic_enabled = Arc::new(AtomicBool::new(cfg.cache.enabled));
@@ -464,14 +1300,97 @@ impl Config {
             admission.is_enabled = Arc::new(AtomicBool::new(admission.enabled));
         }
 
+        if let Some(ref api) = cfg.cache.api {
+            if api.max_connections == Some(0) || api.admin_max_connections == Some(0) {
+                anyhow::bail!("api.max_connections and api.admin_max_connections must be positive when set");
+            }
+            if api.admin_port.is_some() && api.admin_port == api.port {
+                anyhow::bail!("api.admin_port must differ from api.port");
+            }
+            if api.clear_token_ttl == Some(Duration::ZERO) {
+                anyhow::bail!("api.clear_token_ttl must be positive when set");
+            }
+            if api.unmatched_warn_rps == Some(0) {
+                anyhow::bail!("api.unmatched_warn_rps must be positive when set");
+            }
+        }
+
+        if let Some(ref k8s) = cfg.cache.k8s {
+            let probe = &k8s.probe;
+            for path in [probe.liveness_path(), probe.readiness_path()] {
+                if !path.starts_with('/') {
+                    anyhow::bail!("k8s.probe paths must start with '/', got {:?}", path);
+                }
+            }
+            if probe.liveness_path() == probe.readiness_path() {
+                anyhow::bail!("k8s.probe.liveness_path and k8s.probe.readiness_path must differ");
+            }
+            if let (Some(port), Some(api)) = (probe.port.as_ref(), cfg.cache.api.as_ref()) {
+                if api.port.as_ref() == Some(port) || api.admin_port.as_ref() == Some(port) {
+                    anyhow::bail!("k8s.probe.port must differ from api.port and api.admin_port");
+                }
+            }
+        }
+
+        if let Some(auth) = cfg.cache.metrics.as_ref().and_then(|m| m.auth.as_ref()) {
+            if auth.username.is_empty() || auth.username.contains(':') {
+                anyhow::bail!("metrics.auth.username must be non-empty and must not contain ':'");
+            }
+            auth.password()?;
+        }
+        if let Some(codes) = cfg.cache.metrics.as_ref().and_then(|m| m.status_codes.as_ref()) {
+            if let Some(code) = codes.iter().find(|code| !(100..=599).contains(*code)) {
+                anyhow::bail!("metrics.status_codes: {} is not a status code (100..=599)", code);
+            }
+        }
+
+        if let Some(reclaim) = cfg.cache.runtime.as_ref().and_then(|r| r.idle_reclaim.as_ref()) {
+            if let Some(rps) = reclaim.max_rps {
+                if !(rps.is_finite() && rps >= 0.0) {
+                    anyhow::bail!("runtime.idle_reclaim.max_rps must be a non-negative number, got {}", rps);
+                }
+            }
+            if reclaim.idle_for == Some(Duration::ZERO) {
+                anyhow::bail!("runtime.idle_reclaim.idle_for must be positive");
+            }
+        }
+
+        if let Some(brownout) = cfg.cache.brownout.as_ref().filter(|b| b.enabled) {
+            if brownout.upstream_saturated_for.is_none()
+                && brownout.max_refresh_backlog.is_none()
+                && brownout.max_error_rate.is_none()
+            {
+                anyhow::bail!(
+                    "brownout.enabled requires at least one of upstream_saturated_for, max_refresh_backlog, max_error_rate"
+                );
+            }
+        }
+
         // Process storage mode
         const LISTING_MODE: &str = "listing";
         if let Some(ref mut storage) = cfg.cache.storage {
             storage.is_listing = storage.mode.as_deref() == Some(LISTING_MODE);
+            if let Some(sample) = storage.verify_sample {
+                if !(sample > 0.0 && sample <= 1.0) {
+                    anyhow::bail!("storage.verify_sample must be in (0, 1], got {}", sample);
+                }
+            }
+            if storage.admin_walks == Some(0) {
+                anyhow::bail!("storage.admin_walks must be at least 1");
+            }
+            if storage.admin_job_rate == Some(0) {
+                anyhow::bail!("storage.admin_job_rate must be at least 1 when set");
+            }
+            if let Some(rate) = storage.lock_profiling {
+                if !(0.0..=1.0).contains(&rate) {
+                    anyhow::bail!("storage.lock_profiling must be in [0, 1], got {}", rate);
+                }
+            }
         }
 
         if let Some(ref mut rules_raw) = cfg.cache.rules_raw {
             let default_lifetime = cfg.cache.lifetime.as_ref().cloned();
+            let default_query_ignore = cfg.cache.api.as_ref().and_then(|a| a.query_ignore.clone());
             let mut processed_rules = HashMap::new();
             for (rule_path, mut rule) in rules_raw.drain() {
                 rule.path = Some(rule_path.clone());
@@ -485,6 +1404,7 @@ impl Config {
                             beta: lifetime.beta,
                             coefficient: lifetime.coefficient,
                         });
+                        rule.refresh_inherited = true;
                     }
                 }
 
@@ -493,6 +1413,10 @@ impl Config {
                         Some(queries.iter().map(|q| q.as_bytes().to_vec()).collect());
                 }
 
+                if rule.cache_key.query_ignore.is_none() {
+                    rule.cache_key.query_ignore = default_query_ignore.clone();
+                }
+
                 if let Some(ref headers) = rule.cache_key.headers {
                     let mut headers_map = HashMap::new();
                     for header in headers {
@@ -505,6 +1429,38 @@ impl Config {
                 if let Some(ref headers) = rule.cache_value.headers {
                     rule.cache_value.headers_map = Some(headers.iter().cloned().collect());
                 }
+
+                if let Some(ref name) = rule.cache_key.transformer {
+                    crate::plugin::key_transformer(name)
+                        .with_context(|| format!("rule {:?}: cache_key.transformer", rule_path))?;
+                }
+                if let Some(ref name) = rule.cache_value.admission_hook {
+                    crate::plugin::admission_hook(name)
+                        .with_context(|| format!("rule {:?}: cache_value.admission_hook", rule_path))?;
+                }
+                if rule.cache_value.max_concurrent_fills == Some(0) {
+                    anyhow::bail!("rule {:?}: cache_value.max_concurrent_fills must be at least 1", rule_path);
+                }
+                if rule.cache_value.negative_ttl.is_some_and(|ttl| ttl.is_zero()) {
+                    anyhow::bail!("rule {:?}: cache_value.negative_ttl must be above zero", rule_path);
+                }
+                if let Some(ref mut methods) = rule.methods {
+                    for method in methods.iter_mut() {
+                        *method = method.trim().to_ascii_uppercase();
+                        if !matches!(method.as_str(), "GET" | "HEAD" | "POST") {
+                            anyhow::bail!("rule {:?}: methods may list GET, HEAD and POST only, got {:?}", rule_path, method);
+                        }
+                    }
+                    if !methods.iter().any(|m| m == "GET") {
+                        anyhow::bail!("rule {:?}: methods must list GET", rule_path);
+                    }
+                }
+                if rule.caches_method("POST") != rule.keys_body() {
+                    anyhow::bail!("rule {:?}: POST in methods and key_body go together", rule_path);
+                }
+                if rule.max_key_body == Some(0) {
+                    anyhow::bail!("rule {:?}: max_key_body must be at least 1", rule_path);
+                }
                 
                 // Wrap in Arc and store
                 processed_rules.insert(rule_path, Arc::new(rule));
@@ -518,30 +1474,97 @@ impl Config {
             if let Some(ref mut cluster) = upstream.cluster {
                 if let Some(ref mut backends) = cluster.backends {
                     for backend in backends.iter_mut() {
-                        Self::process_backend(backend);
+                        Self::process_backend(backend)?;
                     }
                 }
             } else if let Some(ref mut backend) = upstream.backend {
-                Self::process_backend(backend);
+                Self::process_backend(backend)?;
             } else {
                 anyhow::bail!("no backend configured");
             }
-        }
 
-        let (soft_limit, hard_limit, size) = if let Some(eviction) = cfg.eviction() {
-            let soft = eviction.soft_limit.unwrap_or(0.8);
-            let hard = eviction.hard_limit.unwrap_or(0.99);
-            let storage_size = cfg.cache.storage.as_ref().map(|s| s.size).unwrap_or(0);
-            (soft, hard, storage_size)
-        } else {
-            (0.8, 0.99, 0)
-        };
+            if let Some(status) = upstream.proxy_disabled_status {
+                if !(100..=599).contains(&status) {
+                    anyhow::bail!("invalid upstream.proxy_disabled_status {} configured", status);
+                }
+            }
+
+            if let Some(status) = upstream.throttle_status {
+                if !(400..=599).contains(&status) {
+                    anyhow::bail!("invalid upstream.throttle_status {} configured, must be a 4xx or 5xx", status);
+                }
+            }
+
+            let backends = upstream.cluster.as_ref().and_then(|c| c.backends.as_ref());
+            for backend in backends.into_iter().flatten().chain(upstream.backend.as_ref()) {
+                if let Some(ref synthetic) = backend.health_synthetic {
+                    if !cfg.cache.rules.as_ref().is_some_and(|rules| rules.contains_key(&synthetic.path)) {
+                        anyhow::bail!("backend.health_synthetic.path {:?} is not the path of a cache rule", synthetic.path);
+                    }
+                }
+            }
+
+            for peer in upstream.peers.iter().flatten() {
+                if peer.parse::<hyper::http::uri::Authority>().is_err() || peer.contains('@') {
+                    anyhow::bail!("invalid upstream.peers entry {:?}, expected host:port", peer);
+                }
+            }
+        }
 
+        // Without an eviction section the limits are derived from a zero size.
+        let eviction = cfg.cache.eviction.clone();
         if let Some(ref mut storage) = cfg.cache.storage {
-            storage.soft_memory_limit = (size as f64 * soft_limit) as i64;
-            storage.hard_memory_limit = (size as f64 * hard_limit) as i64;
-            storage.admission_memory_limit = storage.soft_memory_limit - (100 << 20);
-            // soft - 100mb
+            let limits = MemoryLimits::derive(if eviction.is_some() { storage.size } else { 0 }, eviction.as_ref());
+            storage.soft_memory_limit = limits.soft;
+            storage.hard_memory_limit = limits.hard;
+            storage.admission_memory_limit = limits.admission;
+        }
+
+        if let Some(audit) = cfg.cache.eviction.as_ref().and_then(|e| e.audit.as_ref()) {
+            if let Some(rate) = audit.sample_rate {
+                if !(rate > 0.0 && rate <= 1.0) {
+                    anyhow::bail!("eviction.audit.sample_rate must be in (0, 1], got {}", rate);
+                }
+            }
+        }
+
+        Features::BUILT.check(&cfg.cache)?;
+
+        if let Some(mock) = cfg.cache.data.as_ref().and_then(|d| d.mock.as_ref()) {
+            if mock.rate == Some(0) {
+                anyhow::bail!("data.mock.rate must be positive when set");
+            }
+            for spec in mock.specs.iter().flatten() {
+                if !spec.path.starts_with('/') {
+                    anyhow::bail!("data.mock.specs paths must start with '/', got {:?}", spec.path);
+                }
+                if spec.query_cardinality == Some(0) {
+                    anyhow::bail!("data.mock.specs {:?}: query_cardinality must be positive when set", spec.path);
+                }
+            }
+        }
+
+        if let Some(analytics) = cfg.cache.analytics.as_ref() {
+            if let Some(rate) = analytics.sample_rate {
+                if !(rate > 0.0 && rate <= 1.0) {
+                    anyhow::bail!("analytics.sample_rate must be in (0, 1], got {}", rate);
+                }
+            }
+            if analytics.max_events == Some(0) {
+                anyhow::bail!("analytics.max_events must be at least 1");
+            }
+        }
+
+        if let Some(audit) = cfg.cache.audit.as_ref() {
+            if audit.path.as_deref() == Some("") {
+                anyhow::bail!("audit.path must not be empty when set");
+            }
+            if audit.max_size == Some(0) {
+                anyhow::bail!("audit.max_size must be positive when set");
+            }
+            if audit.max_files == Some(0) {
+                anyhow::bail!("audit.max_files must be at least 1");
+            }
         }
 
         // Process lifetime TTL mode
@@ -551,12 +1574,140 @@ impl Config {
             } else {
                 anyhow::bail!("invalid lifetime.OnTTLMode configured");
             }
+            if let Some(ref prewarm) = lifetime.prewarm {
+                if lifetime.on_ttl == Some(TTLMode::Remove) {
+                    anyhow::bail!("lifetime.prewarm requires lifetime.on_ttl: refresh");
+                }
+                if prewarm.rate == 0 {
+                    anyhow::bail!("lifetime.prewarm.rate must be positive");
+                }
+                crate::workers::lifetimer::prewarm::Window::parse(&prewarm.window)
+                    .with_context(|| format!("invalid lifetime.prewarm.window {:?}", prewarm.window))?;
+            }
         }
 
         Ok(cfg)
     }
 
-    fn process_backend(backend: &mut Backend) {
+    /// Returns the host of a configured backend that resolves to a local address on the API port.
+    /// Such a backend makes the cache proxy to itself; requests would only be stopped by loop detection.
+    pub fn backend_host_pointing_to_self(&self) -> Option<String> {
+        let port: u16 = self.cache.api.as_ref()?.port.as_deref()?.trim().parse().ok()?;
+        let upstream = self.cache.upstream.as_ref()?;
+        let backends = upstream
+            .cluster
+            .as_ref()
+            .and_then(|c| c.backends.as_ref())
+            .map(|b| b.iter().collect::<Vec<_>>())
+            .unwrap_or_else(|| upstream.backend.iter().collect());
+
+        let local: Vec<IpAddr> = crate::upstream::loop_guard::system_hostname()
+            .and_then(|name| (name.as_str(), 0).to_socket_addrs().ok())
+            .map(|addrs| addrs.map(|addr| addr.ip()).collect())
+            .unwrap_or_default();
+
+        backends.into_iter().filter_map(|b| b.host.as_deref()).find_map(|host| {
+            let target = if host.rsplit_once(':').is_some_and(|(_, p)| p.parse::<u16>().is_ok()) {
+                host.to_string()
+            } else {
+                format!("{}:80", host)
+            };
+            let is_self = target.to_socket_addrs().ok()?.any(|addr| {
+                addr.port() == port
+                    && (addr.ip().is_loopback() || addr.ip().is_unspecified() || local.contains(&addr.ip()))
+            });
+            is_self.then(|| host.to_string())
+        })
+    }
+
+    /// Reports whether requests that are not served from the cache may be proxied to the origin.
+    pub fn is_proxy_enabled(&self) -> bool {
+        self.cache
+            .upstream
+            .as_ref()
+            .and_then(|u| u.proxy_enabled)
+            .unwrap_or(true)
+    }
+
+    /// Reports whether ignored query params are kept on the URL of upstream fills.
+    pub fn is_ignored_query_forwarded(&self) -> bool {
+        self.cache
+            .api
+            .as_ref()
+            .and_then(|a| a.forward_ignored_query)
+            .unwrap_or(false)
+    }
+
+    /// Reports whether responses carry the `X-Cache-Key` debug header (`api.debug_headers`).
+    pub fn is_debug_headers(&self) -> bool {
+        self.cache.api.as_ref().and_then(|a| a.debug_headers).unwrap_or(false)
+    }
+
+    /// Status answered in place of proxying when proxying is disabled.
+    pub fn proxy_disabled_status(&self) -> u16 {
+        self.cache
+            .upstream
+            .as_ref()
+            .and_then(|u| u.proxy_disabled_status)
+            .unwrap_or(DEFAULT_PROXY_DISABLED_STATUS)
+    }
+
+    /// Status answered for requests refused to protect the origin.
+    pub fn throttle_status(&self) -> u16 {
+        self.cache
+            .upstream
+            .as_ref()
+            .and_then(|u| u.throttle_status)
+            .unwrap_or(DEFAULT_THROTTLE_STATUS)
+    }
+
+    /// Creates a config with nothing but an empty rule set, for tools that only decode
+    /// stored entries (rules are added with [`Config::ensure_rule`]).
+    #[cfg_attr(not(feature = "dump"), allow(dead_code))]
+    pub fn decode_only() -> Self {
+        Self {
+            cache: CacheBox {
+                env: DEV.to_string(),
+                enabled: true,
+                atomic_enabled: Arc::new(AtomicBool::new(true)),
+                logs: None,
+                runtime: None,
+                api: None,
+                upstream: None,
+                data: None,
+                storage: None,
+                compression: None,
+                eviction: None,
+                admission: None,
+                traces: None,
+                lifetime: None,
+                metrics: None,
+                k8s: None,
+                health: None,
+                brownout: None,
+                shutdown: None,
+                analytics: None,
+                audit: None,
+                strict_durations: None,
+                rules: Some(HashMap::new()),
+                rules_raw: None,
+                source: None,
+                reloaded: Default::default(),
+            },
+        }
+    }
+
+    /// Registers a bare rule for the path unless one is already configured.
+    #[cfg_attr(not(feature = "dump"), allow(dead_code))]
+    pub fn ensure_rule(&mut self, path: &str) {
+        self.cache
+            .rules
+            .get_or_insert_with(HashMap::new)
+            .entry(path.to_string())
+            .or_insert_with(|| Arc::new(Rule::bare(path)));
+    }
+
+    fn process_backend(backend: &mut Backend) -> Result<()> {
         if let Some(ref id) = backend.id {
             backend.id_bytes = Some(id.as_bytes().to_vec());
         }
@@ -572,9 +1723,66 @@ impl Config {
         if let Some(ref healthcheck) = backend.healthcheck {
             backend.healthcheck_bytes = Some(healthcheck.as_bytes().to_vec());
         }
+        for (name, timeout) in [
+            ("connect_timeout", backend.connect_timeout),
+            ("ttfb_timeout", backend.ttfb_timeout),
+            ("body_timeout", backend.body_timeout),
+        ] {
+            if timeout.is_some_and(|t| t.is_zero()) {
+                anyhow::bail!("backend.{} must be positive when set", name);
+            }
+        }
+        // An empty value means the client's Accept-Encoding is forwarded as before.
+        backend.accept_encoding = backend.accept_encoding.take().filter(|v| !v.trim().is_empty());
+        if let Some(ref accept_encoding) = backend.accept_encoding {
+            crate::upstream::encoding::validate(accept_encoding)?;
+        }
+        if let Some(ref signing) = backend.signing {
+            crate::upstream::signing::Signer::from_config(signing)?;
+        }
+        if let Some(ref synthetic) = backend.health_synthetic {
+            if !synthetic.path.starts_with('/') {
+                anyhow::bail!("backend.health_synthetic.path must start with '/', got {:?}", synthetic.path);
+            }
+            if !(100..=599).contains(&synthetic.expected_status()) {
+                anyhow::bail!("invalid backend.health_synthetic.expected_status {}", synthetic.expected_status());
+            }
+            if synthetic.interval().is_zero() {
+                anyhow::bail!("backend.health_synthetic.interval must be positive");
+            }
+            if synthetic.fail_threshold() == 0 || synthetic.ok_threshold() == 0 {
+                anyhow::bail!("backend.health_synthetic thresholds must be at least 1");
+            }
+        }
+        Ok(())
     }
 }
 
-// Test config is always available for integration tests
+pub mod diff;
+#[cfg(test)]
+mod diff_test;
+pub mod duration;
+#[cfg(test)]
+mod duration_test;
+pub mod features;
+pub use features::Features;
+#[cfg(test)]
+mod features_test;
+pub mod downstream_ttl;
+pub use downstream_ttl::DownstreamTtl;
+#[cfg(test)]
+mod downstream_ttl_test;
+pub mod overrides;
+pub mod reload;
+#[cfg(test)]
+mod reload_test;
+pub mod rollout;
+pub use rollout::RolloutPercent;
+#[cfg(test)]
+mod rollout_test;
+
+// Test config is available to tests, and to benchmarks through the `mocks` feature
+#[cfg(any(test, feature = "mocks"))]
 mod test_config;
+#[cfg(any(test, feature = "mocks"))]
 pub use test_config::new_test_config;
@@ -0,0 +1,63 @@
+//! Runtime overrides: state changed through the admin API that outlives the process, and the
+//! report of the last shutdown.
+//!
+//! The file (`runtime.overrides`) is YAML and is rewritten whole on every change, through a
+//! temporary file and a rename, so a crash leaves either the old or the new state. A missing
+//! file means no overrides.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::shutdown::ShutdownReport;
+
+/// Serializes read-modify-write cycles of the file within the process.
+static UPDATE: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RuntimeOverrides {
+    /// Ids of the backends drained through `/advcache/upstream/{id}/drain`.
+    #[serde(default)]
+    pub drained_backends: BTreeSet<String>,
+    /// Storage size in bytes set through `/advcache/storage/resize`, over `storage.size`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_size: Option<i64>,
+    /// Phases of the last shutdown, served by `/advcache/shutdown/last`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_shutdown: Option<ShutdownReport>,
+}
+
+impl RuntimeOverrides {
+    /// Reads the overrides at `path`; empty when the file does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = match std::fs::read_to_string(path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("read runtime overrides {:?}", path)),
+        };
+        if raw.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_yaml::from_str(&raw).with_context(|| format!("parse runtime overrides {:?}", path))
+    }
+
+    /// Applies `change` to the overrides at `path` and writes them back.
+    pub fn update(path: &Path, change: impl FnOnce(&mut Self)) -> Result<Self> {
+        let _guard = UPDATE.lock();
+        let mut overrides = Self::load(path)?;
+        change(&mut overrides);
+        overrides.save(path)?;
+        Ok(overrides)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let yaml = serde_yaml::to_string(self).context("encode runtime overrides")?;
+        std::fs::write(&tmp_path, yaml).with_context(|| format!("write {:?}", tmp_path))?;
+        std::fs::rename(&tmp_path, path).with_context(|| format!("rename {:?} to {:?}", tmp_path, path))
+    }
+}
@@ -0,0 +1,148 @@
+//! Hot reload of the rules, lifetime and eviction sections.
+//!
+//! Every clone of a [`Config`] shares one reload cell, so a config applied through any of them
+//! is seen by all the controllers and workers holding one on their next read: requests match
+//! the reloaded rules, refreshes follow the reloaded lifetime. Entries stored under a rule the
+//! reload removed are no longer looked up; their requests are proxied as rule-not-found.
+//! Backends and the other sections keep what they were started with until a restart.
+//!
+//! A reload is run by SIGHUP or `/advcache/config/reload`, re-reading the file the config was
+//! loaded from. A file that fails to load or validate leaves the running config as it was.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use arc_swap::ArcSwapOption;
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::info;
+
+use super::diff::ConfigDiff;
+use super::{Config, Eviction, Lifetime, Rule};
+
+/// Sections swapped in by a reload.
+#[derive(Debug)]
+pub struct Reloaded {
+    pub rules: Option<HashMap<String, Arc<Rule>>>,
+    pub lifetime: Option<Lifetime>,
+    pub eviction: Option<Eviction>,
+}
+
+/// Reload cell shared by the clones of a config. Config accessors hand out plain references
+/// into the sections, so each reloaded generation is kept for the life of the process;
+/// reloads are operator driven and few.
+pub type Cell = Arc<ArcSwapOption<&'static Reloaded>>;
+
+impl Config {
+    /// Sections of the last reload, if the config was reloaded.
+    pub(super) fn reloaded(&self) -> Option<&'static Reloaded> {
+        self.cache.reloaded.load().as_deref().copied()
+    }
+
+    /// Rules in effect by path: the reloaded ones once the config was reloaded.
+    pub fn rules(&self) -> Option<&HashMap<String, Arc<Rule>>> {
+        match self.reloaded() {
+            Some(reloaded) => reloaded.rules.as_ref(),
+            None => self.cache.rules.as_ref(),
+        }
+    }
+
+    /// File the config was loaded from; `None` for configs parsed in memory.
+    pub fn source(&self) -> Option<&Path> {
+        self.cache.source.as_deref()
+    }
+
+    /// Swaps in the rules, lifetime and eviction sections of `next` for every clone of this config.
+    pub fn apply_reload(&self, next: &Config) {
+        let reloaded: &'static Reloaded = Box::leak(Box::new(Reloaded {
+            rules: next.cache.rules.clone(),
+            lifetime: next.cache.lifetime.clone(),
+            eviction: next.cache.eviction.clone(),
+        }));
+        self.cache.reloaded.store(Some(Arc::new(reloaded)));
+    }
+
+    /// The config as in effect, the reloaded sections in place of the loaded ones.
+    pub fn effective(&self) -> Config {
+        let mut cfg = self.clone();
+        if let Some(reloaded) = self.reloaded() {
+            cfg.cache.rules = reloaded.rules.clone();
+            cfg.cache.lifetime = reloaded.lifetime.clone();
+            cfg.cache.eviction = reloaded.eviction.clone();
+        }
+        cfg
+    }
+}
+
+/// Outcome of an applied reload.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadReport {
+    pub path: String,
+    pub diff: ConfigDiff,
+    /// Changed backends and limits that only apply after a restart.
+    pub not_applied: Vec<String>,
+}
+
+/// Called with the reloaded config once it is applied.
+type Hook = Box<dyn Fn(&Config) + Send + Sync>;
+
+/// Reloads a running config from its file, for SIGHUP and the admin endpoint.
+pub struct Reloader {
+    cfg: Config,
+    hooks: Vec<Hook>,
+    /// Reloads run one at a time.
+    running: Mutex<()>,
+}
+
+impl Reloader {
+    /// Creates a reloader of the running config.
+    pub fn new(cfg: Config) -> Self {
+        Self { cfg, hooks: Vec::new(), running: Mutex::new(()) }
+    }
+
+    /// Adds a hook run with the reloaded config after it is applied.
+    pub fn on_reload(mut self, hook: impl Fn(&Config) + Send + Sync + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Re-reads the config file and applies its rules, lifetime and eviction sections.
+    pub fn reload(&self) -> Result<ReloadReport> {
+        let _running = self.running.lock();
+        let path = self.cfg.source().context("config was not loaded from a file")?;
+        let next = Config::load(path)?;
+
+        let diff = ConfigDiff::between(&self.cfg.effective(), &next);
+        self.cfg.apply_reload(&next);
+        for hook in &self.hooks {
+            hook(&next);
+        }
+
+        let report = ReloadReport { path: path.display().to_string(), not_applied: not_applied(&diff), diff };
+        info!(
+            component = "config",
+            event = "config_reloaded",
+            path = %report.path,
+            rules_added = report.diff.rules.added.len(),
+            rules_removed = report.diff.rules.removed.len(),
+            rules_changed = report.diff.rules.changed.len(),
+            not_applied = ?report.not_applied,
+            "config reloaded"
+        );
+        Ok(report)
+    }
+}
+
+/// Changes of the diff a reload does not apply: backends, and limits other than the storage
+/// size and the eviction settings (the eviction audit is opened once).
+fn not_applied(diff: &ConfigDiff) -> Vec<String> {
+    let backends = &diff.backends;
+    let backends = backends.added.iter().chain(&backends.removed).chain(backends.changed.iter().map(|c| &c.name));
+    let limits = diff.limits.iter().map(|change| &change.field).filter(|field| {
+        let applied = field.as_str() == "storage.size" || (field.starts_with("eviction.") && !field.starts_with("eviction.audit."));
+        !applied
+    });
+    backends.map(|name| format!("backends.{}", name)).chain(limits.cloned()).collect()
+}
@@ -0,0 +1,125 @@
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::config::reload::Reloader;
+    use crate::config::{Config, ConfigTrait};
+
+    const RULES: &str = r#"
+cache:
+  env: test
+  enabled: true
+  upstream:
+    backend:
+      id: main
+      enabled: true
+      scheme: http
+      host: main.local:8080
+      timeout: 10s
+      max_timeout: 1m
+  storage:
+    mode: listing
+    size: 1073741824
+  eviction:
+    enabled: true
+    soft_limit: 0.8
+    hard_limit: 0.9
+    check_interval: 1s
+  lifetime:
+    enabled: true
+    on_ttl: refresh
+    ttl: 1h
+  rules:
+    /api/v1/user:
+      cache_key:
+        query: ["user[id]"]
+      cache_value:
+        headers: [Content-Type]
+"#;
+
+    const BUYER_RULE: &str = "    /api/v1/buyer:\n      cache_key:\n        query: [\"buyer[id]\"]\n      cache_value:\n        headers: []\n";
+
+    fn config_file(name: &str, yaml: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("advcache-reload-{}-{}.yaml", name, std::process::id()));
+        std::fs::write(&path, yaml).unwrap();
+        path
+    }
+
+    /// Test that a reload swaps the rules and lifetime of every clone of the running config,
+    /// and reports the rules it added and removed.
+    #[test]
+    fn test_reload_applies_to_every_clone() {
+        let path = config_file("apply", &format!("{}{}", RULES, BUYER_RULE));
+        let cfg = Config::load(&path).unwrap();
+        let held = cfg.clone();
+        let buyer = held.rule("/api/v1/buyer").expect("buyer rule is loaded");
+
+        let next = RULES.replace("ttl: 1h", "ttl: 2h").replace("soft_limit: 0.8", "soft_limit: 0.5");
+        let next = format!("{}    /api/v1/client:\n      cache_key:\n        query: []\n      cache_value:\n        headers: []\n", next);
+        std::fs::write(&path, next).unwrap();
+        let reloads = Arc::new(AtomicUsize::new(0));
+        let reloader = Reloader::new(cfg).on_reload({
+            let reloads = reloads.clone();
+            move |next| {
+                assert_eq!(next.storage().soft_memory_limit, 1073741824 / 2);
+                reloads.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let report = reloader.reload().unwrap();
+
+        assert_eq!(reloads.load(Ordering::SeqCst), 1);
+        assert_eq!(report.diff.rules.added, vec!["/api/v1/client".to_string()]);
+        assert_eq!(report.diff.rules.removed, vec!["/api/v1/buyer".to_string()]);
+        assert!(report.not_applied.is_empty(), "{:?}", report.not_applied);
+
+        assert!(held.rule("/api/v1/buyer").is_none(), "removed rules are not found anymore");
+        assert!(held.rule("/api/v1/client").is_some());
+        assert!(held.rule("/api/v1/user").is_some());
+        assert_eq!(held.lifetime().and_then(|l| l.ttl), Some(Duration::from_secs(2 * 3600)));
+        assert_eq!(held.eviction().and_then(|e| e.soft_limit), Some(0.5));
+        assert!(held.effective().cache.rules.unwrap().contains_key("/api/v1/client"));
+        // Entries hold on to the rule they were stored under.
+        assert_eq!(buyer.path.as_deref(), Some("/api/v1/buyer"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Test that a config failing to load is reported and leaves the running one in place.
+    #[test]
+    fn test_failed_reload_keeps_config() {
+        let path = config_file("failed", RULES);
+        let cfg = Config::load(&path).unwrap();
+        let reloads = Arc::new(AtomicUsize::new(0));
+        let reloader = Reloader::new(cfg.clone()).on_reload({
+            let reloads = reloads.clone();
+            move |_| {
+                reloads.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        std::fs::write(&path, RULES.replace("ttl: 1h", "ttl: [")).unwrap();
+        let err = reloader.reload().unwrap_err();
+        assert!(format!("{:#}", err).contains("unmarshal yaml"), "{:#}", err);
+        assert_eq!(reloads.load(Ordering::SeqCst), 0);
+        assert_eq!(cfg.lifetime().and_then(|l| l.ttl), Some(Duration::from_secs(3600)));
+        assert!(cfg.rule("/api/v1/user").is_some());
+
+        // Configs parsed in memory have no file to reload from.
+        assert!(Reloader::new(Config::from_yaml(RULES).unwrap()).reload().is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Test that changed backends are reported as needing a restart.
+    #[test]
+    fn test_backend_changes_not_applied() {
+        let path = config_file("backends", RULES);
+        let reloader = Reloader::new(Config::load(&path).unwrap());
+        std::fs::write(&path, RULES.replace("main.local:8080", "other.local:8080")).unwrap();
+
+        let report = reloader.reload().unwrap();
+        assert_eq!(report.not_applied, vec!["backends.main".to_string()]);
+        let _ = std::fs::remove_file(&path);
+    }
+}
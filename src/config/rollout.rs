@@ -0,0 +1,64 @@
+//! Rule rollout: the share of a rule's keys served through the cache while it is ramped up.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+/// Rollout percent of a rule. Clones share the value, so a change made at runtime is seen by
+/// every holder of the config; it is serialized as its current value.
+#[derive(Debug, Clone)]
+pub struct RolloutPercent(Arc<AtomicU8>);
+
+impl RolloutPercent {
+    pub const FULL: u8 = 100;
+
+    pub fn new(percent: u8) -> Self {
+        Self(Arc::new(AtomicU8::new(percent.min(Self::FULL))))
+    }
+
+    pub fn get(&self) -> u8 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Sets the percent, capped at 100.
+    pub fn set(&self, percent: u8) {
+        self.0.store(percent.min(Self::FULL), Ordering::Relaxed);
+    }
+
+    /// Whether the rule is ramping up, i.e. not every key is cached.
+    pub fn is_ramped(&self) -> bool {
+        self.get() < Self::FULL
+    }
+
+    /// Whether a key falls within the rollout: `key % 100 < percent`, so a given key is
+    /// consistently cached or not for a given percent.
+    pub fn includes(&self, key: u64) -> bool {
+        key % 100 < self.get() as u64
+    }
+}
+
+impl Default for RolloutPercent {
+    fn default() -> Self {
+        Self::new(Self::FULL)
+    }
+}
+
+impl Serialize for RolloutPercent {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.get())
+    }
+}
+
+impl<'de> Deserialize<'de> for RolloutPercent {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let percent = u8::deserialize(deserializer)?;
+        if percent > Self::FULL {
+            return Err(serde::de::Error::custom(format!(
+                "rollout_percent must be in 0..=100, got {}",
+                percent
+            )));
+        }
+        Ok(Self::new(percent))
+    }
+}
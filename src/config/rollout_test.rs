@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+    use crate::config::RolloutPercent;
+
+    /// Test that a key is consistently in or out for a given percent, and that raising the
+    /// percent only adds keys: keys cached earlier in a ramp stay cached.
+    #[test]
+    fn test_rollout_bucketing_is_key_stable() {
+        let keys: Vec<u64> = (0..10_000u64).map(|i| xxhash_rust::xxh3::xxh3_64(&i.to_le_bytes())).collect();
+        let rollout = RolloutPercent::new(25);
+
+        let first: Vec<bool> = keys.iter().map(|&k| rollout.includes(k)).collect();
+        let again: Vec<bool> = keys.iter().map(|&k| rollout.includes(k)).collect();
+        assert_eq!(first, again);
+
+        let share = first.iter().filter(|&&included| included).count();
+        assert!((2_000..3_000).contains(&share), "about a quarter of keys expected, got {}", share);
+
+        rollout.set(60);
+        for (&key, &was_included) in keys.iter().zip(&first) {
+            assert!(!was_included || rollout.includes(key), "key {} left the rollout on ramp-up", key);
+        }
+    }
+
+    /// Test the bounds: 0 caches nothing, 100 everything, and values over 100 are capped.
+    #[test]
+    fn test_rollout_bounds() {
+        let none = RolloutPercent::new(0);
+        let all = RolloutPercent::default();
+        for key in [0, 1, 99, 100, u64::MAX] {
+            assert!(!none.includes(key));
+            assert!(all.includes(key));
+        }
+        assert!(none.is_ramped());
+        assert!(!all.is_ramped());
+
+        none.set(250);
+        assert_eq!(none.get(), 100);
+    }
+
+    /// Test that clones share the value, as every holder of the config must see a change.
+    #[test]
+    fn test_rollout_clones_share_value() {
+        let rollout = RolloutPercent::new(10);
+        let clone = rollout.clone();
+
+        clone.set(40);
+
+        assert_eq!(rollout.get(), 40);
+    }
+
+    /// Test that the percent reads from and writes to YAML as a plain number, bounded by 100.
+    #[test]
+    fn test_rollout_serde() {
+        let rollout: RolloutPercent = serde_yaml::from_str("30").unwrap();
+        assert_eq!(rollout.get(), 30);
+        assert_eq!(serde_json::to_string(&rollout).unwrap(), "30");
+
+        let err = serde_yaml::from_str::<RolloutPercent>("101").unwrap_err();
+        assert!(err.to_string().contains("0..=100"), "{}", err);
+    }
+}
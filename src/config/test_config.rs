@@ -15,11 +15,27 @@ pub fn new_test_config() -> Config {
             atomic_enabled: Arc::new(AtomicBool::new(true)),
             logs: Some(super::Logs {
                 level: Some("debug".to_string()),
+                redact: Some(super::Redact {
+                    query: Some(vec!["token".to_string(), "email".to_string()]),
+                    headers: Some(vec!["authorization".to_string(), "cookie".to_string()]),
+                }),
+                error_ring: None,
+                path: None,
             }),
-            runtime: Some(super::Runtime { num_cpus: 12 }),
+            runtime: Some(super::Runtime { num_cpus: 12, overrides: None, idle_reclaim: None }),
             api: Some(super::Api {
                 name: Some("adv_cache_test:8091".to_string()),
                 port: Some("8091".to_string()),
+                query_ignore: None,
+                forward_ignored_query: None,
+                max_connections: None,
+                connections_overflow: None,
+                shed_retry_after: None,
+                admin_port: None,
+                admin_max_connections: None,
+                clear_token_ttl: None,
+                unmatched_warn_rps: None,
+                debug_headers: None,
             }),
             upstream: Some(super::Upstream {
                 policy: Some("deny".to_string()),
@@ -36,6 +52,9 @@ pub fn new_test_config() -> Config {
                     rate: Some(2_000_000),
                     concurrency: Some(500_000),
                     timeout: Some(Duration::from_secs(5)),
+                    connect_timeout: None,
+                    ttfb_timeout: None,
+                    body_timeout: None,
                     max_timeout: Some(Duration::from_secs(60)),
                     use_max_timeout_header: None,
                     use_max_timeout_header_bytes: None,
@@ -43,20 +62,35 @@ pub fn new_test_config() -> Config {
                     healthcheck_bytes: None,
                     addr: None,
                     health_path: None,
+                    on_health_change: None,
+                    health_synthetic: None,
+                    max_response_size: None,
+                    accept_encoding: None,
+                    signing: None,
                 }),
+                proxy_enabled: None,
+                proxy_disabled_status: None,
+                throttle_status: None,
+                peers: None,
+                peer_timeout: None,
             }),
             data: Some(super::Data {
                 dump: Some(super::Dump {
                     enabled: false,
                     dir: Some("public/dump".to_string()),
+                    dir_fallback: None,
                     name: Some("cache.dump".to_string()),
                     max_versions: Some(3),
                     gzip: false,
                     crc32_control: true,
+                    load_on_demand: false,
+                    migrate_legacy: false,
                 }),
                 mock: Some(super::Mock {
                     enabled: false,
                     length: Some(100000),
+                    rate: None,
+                    specs: None,
                 }),
             }),
             admission: Some(super::Admission {
@@ -79,6 +113,16 @@ pub fn new_test_config() -> Config {
                 soft_memory_limit: 0,
                 hard_memory_limit: 0,
                 admission_memory_limit: 0,
+                verify_sample: None,
+                admin_walks: None,
+                admin_walk_timeout: None,
+                admin_job_rate: None,
+                max_clock_skew: None,
+                lock_profiling: None,
+                track_access_time: None,
+                access_time_granularity: None,
+                sample_hits: None,
+                max_body_size: None,
             }),
             eviction: Some(super::Eviction {
                 enabled: true,
@@ -86,6 +130,8 @@ pub fn new_test_config() -> Config {
                 replicas: Some(4),
                 hard_limit: Some(0.85),
                 check_interval: Some(Duration::from_millis(100)),
+                audit: None,
+                critical: None,
             }),
             lifetime: Some(super::Lifetime {
                 enabled: false,
@@ -95,6 +141,11 @@ pub fn new_test_config() -> Config {
                 rate: Some(50),
                 beta: Some(0.4),
                 coefficient: Some(0.5),
+                max_stale_on_error: Some(Duration::from_secs(600)),
+                strict_ttl: None,
+                reconcile_rules: None,
+                prewarm: None,
+                critical: None,
                 is_remove_on_ttl: Arc::new(AtomicBool::new(false)),
             }),
             traces: Some(super::Traces {
@@ -110,14 +161,27 @@ pub fn new_test_config() -> Config {
                 export_batch_timeout: None,
                 export_max_queue: None,
             }),
-            metrics: Some(super::Metrics { enabled: true }),
+            metrics: Some(super::Metrics { enabled: true, auth: None, status_codes: None }),
             k8s: Some(super::K8S {
                 probe: super::Probe {
                     timeout: Some(Duration::from_secs(5)),
+                    liveness_path: None,
+                    readiness_path: None,
+                    min_timeout: None,
+                    readiness_timeout: None,
+                    port: None,
                 },
             }),
+            health: None,
+            brownout: None,
+            shutdown: None,
+            analytics: None,
+            audit: None,
+            strict_durations: None,
             rules: None,
             rules_raw: Some(HashMap::new()),
+            source: None,
+            reloaded: Default::default(),
         },
     };
 
@@ -157,12 +221,23 @@ pub fn new_test_config() -> Config {
             cache_key: super::RuleKey {
                 query: Some(key_query.clone()),
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: Some(key_headers.clone()),
                 headers_map: None,
             },
             cache_value: super::RuleValue {
                 headers: Some(value_headers_pd.clone()),
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: Some(super::LifetimeRule {
                 enabled: true,
@@ -170,6 +245,13 @@ pub fn new_test_config() -> Config {
                 beta: Some(0.4),
                 coefficient: Some(0.5),
             }),
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         },
     );
 
@@ -182,14 +264,32 @@ pub fn new_test_config() -> Config {
             cache_key: super::RuleKey {
                 query: Some(key_query.clone()),
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: Some(key_headers.clone()),
                 headers_map: None,
             },
             cache_value: super::RuleValue {
                 headers: Some(value_headers_with_len.clone()),
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         },
     );
 
@@ -202,14 +302,32 @@ pub fn new_test_config() -> Config {
             cache_key: super::RuleKey {
                 query: Some(key_query.clone()),
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: Some(key_headers.clone()),
                 headers_map: None,
             },
             cache_value: super::RuleValue {
                 headers: Some(value_headers_with_len.clone()),
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         },
     );
 
@@ -222,14 +340,111 @@ pub fn new_test_config() -> Config {
             cache_key: super::RuleKey {
                 query: Some(key_query.clone()),
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
+                headers: Some(key_headers.clone()),
+                headers_map: None,
+            },
+            cache_value: super::RuleValue {
+                headers: Some(value_headers_with_len.clone()),
+                headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
+            },
+            refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
+        },
+    );
+
+    // /api/v1/flaky and /api/v1/flaky_strict: upstream alternates 503/200 per request,
+    // the strict one opts out of serving stale entries on errors.
+    for (path, stale_on_error) in [("/api/v1/flaky", None), ("/api/v1/flaky_strict", Some(false))] {
+        rules.insert(
+            path.to_string(),
+            super::Rule {
+                path: Some(path.to_string()),
+                path_bytes: Some(path.as_bytes().to_vec()),
+                cache_key: super::RuleKey {
+                    query: Some(key_query.clone()),
+                    query_bytes: None,
+                    query_ignore: None,
+                    duplicate_query: None,
+                    transformer: None,
+                    headers: Some(key_headers.clone()),
+                    headers_map: None,
+                },
+                cache_value: super::RuleValue {
+                    headers: Some(value_headers_with_len.clone()),
+                    headers_map: None,
+                    persist: None,
+                    rollout_percent: Default::default(),
+                    max_concurrent_fills: None,
+                    max_fill_wait: None,
+                    admission_hook: None,
+                    downstream_ttl: Default::default(),
+                    respect_cache_control: None,
+                    negative_ttl: None,
+                },
+                refresh: None,
+                stale_on_error,
+                shed_on_brownout: None,
+                methods: None,
+                key_body: None,
+                max_key_body: None,
+                max_body_size: None,
+                refresh_inherited: false,
+            },
+        );
+    }
+
+    // /api/v1/search: GET-like POSTs, keyed by their body.
+    rules.insert(
+        "/api/v1/search".to_string(),
+        super::Rule {
+            path: Some("/api/v1/search".to_string()),
+            path_bytes: Some(b"/api/v1/search".to_vec()),
+            cache_key: super::RuleKey {
+                query: Some(key_query.clone()),
+                query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: Some(key_headers.clone()),
                 headers_map: None,
             },
             cache_value: super::RuleValue {
                 headers: Some(value_headers_with_len.clone()),
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: Some(vec!["GET".to_string(), "HEAD".to_string(), "POST".to_string()]),
+            key_body: Some(true),
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         },
     );
 
@@ -261,6 +476,7 @@ pub fn new_test_config() -> Config {
                         beta: lifetime.beta,
                         coefficient: lifetime.coefficient,
                     });
+                    rule.refresh_inherited = true;
                 }
             }
 
@@ -313,12 +529,7 @@ pub fn new_test_config() -> Config {
 
     // Compute memory limits from eviction + storage
     if let (Some(ev), Some(storage)) = (cfg.cache.eviction.as_ref(), cfg.cache.storage.as_mut()) {
-        let soft = ev.soft_limit.unwrap_or(0.8);
-        let hard = ev.hard_limit.unwrap_or(0.99);
-        let size = storage.size;
-        storage.soft_memory_limit = (size as f64 * soft) as i64;
-        storage.hard_memory_limit = (size as f64 * hard) as i64;
-        storage.admission_memory_limit = storage.soft_memory_limit - (100 << 20);
+        storage.set_size(storage.size, Some(ev));
     }
 
     // Lifetime on_ttl flag
@@ -1,8 +1,8 @@
 //! Admission control controller.
 
 use crate::config::{Config, ConfigTrait};
-use crate::http::Controller;
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
+use crate::http::{Controller, Route};
+use axum::{http::StatusCode, response::IntoResponse};
 use serde::Serialize;
 use std::sync::Arc;
 
@@ -61,22 +61,16 @@ impl AdmissionController {
 }
 
 impl Controller for AdmissionController {
-    fn add_route(&self, router: Router) -> Router {
+    fn describe(&self) -> Vec<Route> {
         let cfg1 = self.cfg.clone();
         let cfg2 = self.cfg.clone();
         let cfg3 = self.cfg.clone();
-        router
-            .route(
-                "/advcache/admission",
-                get(move || async move { Self::get(cfg1).await }),
-            )
-            .route(
-                "/advcache/admission/on",
-                get(move || async move { Self::on(cfg2).await }),
-            )
-            .route(
-                "/advcache/admission/off",
-                get(move || async move { Self::off(cfg3).await }),
-            )
+        vec![
+            Route::get("/advcache/admission", "Shows whether admission control is enabled", move || async move {
+                Self::get(cfg1).await
+            }),
+            Route::get("/advcache/admission/on", "Enables admission control", move || async move { Self::on(cfg2).await }).mutating(),
+            Route::get("/advcache/admission/off", "Disables admission control", move || async move { Self::off(cfg3).await }).mutating(),
+        ]
     }
 }
@@ -0,0 +1,94 @@
+//! Audit log controller.
+//!
+//! `/advcache/audit` serves the tail of the audit log (see [`crate::audit`]): the most recent
+//! admin calls that changed the state of the instance, oldest first.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::audit::log::is_chained;
+use crate::audit::{AuditLog, AuditRecord};
+use crate::http::{Controller, Route};
+
+/// Records returned when `limit` is not given.
+const DEFAULT_LIMIT: usize = 100;
+
+/// Most records returned at once.
+const MAX_LIMIT: usize = 10_000;
+
+/// Query parameters for the audit endpoint.
+#[derive(Deserialize)]
+struct AuditQuery {
+    limit: Option<String>,
+}
+
+/// Audit response body.
+#[derive(Debug, Serialize)]
+struct AuditResponse {
+    count: usize,
+    /// Whether every record returned follows the one before it in the chain.
+    chained: bool,
+    records: Vec<AuditRecord>,
+}
+
+/// AuditController serves the tail of the audit log; 404 while it is off.
+pub struct AuditController {
+    log: Option<Arc<AuditLog>>,
+}
+
+impl AuditController {
+    /// Creates an audit controller reading the given log.
+    pub fn new(log: Option<Arc<AuditLog>>) -> Self {
+        Self { log }
+    }
+
+    /// Lists up to `limit` of the most recent records, those queued before the call included.
+    async fn get(log: Option<Arc<AuditLog>>, Query(params): Query<AuditQuery>) -> Response {
+        let Some(log) = log else {
+            return error(StatusCode::NOT_FOUND, "audit log is disabled, set audit.path");
+        };
+        let limit = match params.limit.as_deref().map(str::parse::<usize>) {
+            None => DEFAULT_LIMIT,
+            Some(Ok(n)) if n <= MAX_LIMIT => n,
+            Some(_) => return error(StatusCode::BAD_REQUEST, "invalid 'limit' parameter, at most 10000"),
+        };
+
+        log.flush().await;
+        let records = match tokio::task::spawn_blocking(move || log.tail(limit)).await {
+            Ok(Ok(records)) => records,
+            Ok(Err(e)) => return error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+            Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+        };
+        let resp = AuditResponse { count: records.len(), chained: is_chained(&records), records };
+        (
+            StatusCode::OK,
+            [("content-type", "application/json; charset=utf-8")],
+            serde_json::to_string(&resp).unwrap_or_default(),
+        )
+            .into_response()
+    }
+}
+
+fn error(status: StatusCode, msg: &str) -> Response {
+    (
+        status,
+        [("content-type", "application/json")],
+        serde_json::json!({ "error": msg }).to_string(),
+    )
+        .into_response()
+}
+
+impl Controller for AuditController {
+    fn describe(&self) -> Vec<Route> {
+        let log = self.log.clone();
+        vec![Route::get("/advcache/audit", "Most recent admin calls changing the instance state", move |query: Query<AuditQuery>| {
+            Self::get(log.clone(), query)
+        })]
+    }
+}
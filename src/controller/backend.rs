@@ -1,9 +1,9 @@
 //! Backend policy controller.
 
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
+use axum::{http::StatusCode, response::IntoResponse};
 use serde::Serialize;
 
-use crate::http::Controller;
+use crate::http::{Controller, Route};
 use crate::upstream::{actual_policy, change_policy, Policy};
 
 /// Backend policy response structure.
@@ -72,16 +72,11 @@ impl Default for ChangeBackendPolicyController {
 }
 
 impl Controller for ChangeBackendPolicyController {
-    fn add_route(&self, router: Router) -> Router {
-        router
-            .route(
-                "/advcache/upstream/policy/await",
-                get(Self::turn_on_await_policy),
-            )
-            .route(
-                "/advcache/upstream/policy/deny",
-                get(Self::turn_on_deny_policy),
-            )
-            .route("/advcache/upstream/policy", get(Self::show_policy))
+    fn describe(&self) -> Vec<Route> {
+        vec![
+            Route::get("/advcache/upstream/policy/await", "Queues upstream requests over the rate limit", Self::turn_on_await_policy).mutating(),
+            Route::get("/advcache/upstream/policy/deny", "Rejects upstream requests over the rate limit", Self::turn_on_deny_policy).mutating(),
+            Route::get("/advcache/upstream/policy", "Shows the upstream rate limit policy", Self::show_policy),
+        ]
     }
 }
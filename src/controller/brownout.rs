@@ -0,0 +1,292 @@
+//! Brownout controller: sheds cache misses while the upstream path is saturated.
+//!
+//! The signals of `cache.brownout` are evaluated on the metrics tick of the cache controller,
+//! like the health conditions. Once any of them is breached, misses of rules that do not opt
+//! out with `shed_on_brownout: false` are answered `upstream.throttle_status` with
+//! `Retry-After` instead of queueing for the origin, while hits keep being served. The brownout
+//! disengages once every signal has stayed clear for `recover_after`.
+//! `/advcache/brownout/{on,off,auto}` forces it for drills.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{http::StatusCode, response::IntoResponse};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::config::{self, Rule};
+use crate::controller::cache_metrics::Snapshot;
+use crate::controller::health::Breach;
+use crate::controller::metrics;
+use crate::db::Storage;
+use crate::http::{Controller, Route};
+use crate::upstream::Upstream;
+
+pub const SIGNAL_UPSTREAM_SATURATED: &str = "upstream_saturated";
+pub const SIGNAL_REFRESH_BACKLOG: &str = "refresh_backlog";
+pub const SIGNAL_ERROR_RATE: &str = "error_rate";
+
+const DEFAULT_RECOVER_AFTER: Duration = Duration::from_secs(30);
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Who decides whether misses are shed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// The signals of `cache.brownout`.
+    #[default]
+    Auto,
+    /// Forced on by an operator.
+    On,
+    /// Forced off by an operator.
+    Off,
+}
+
+/// Figures a tick is evaluated on.
+#[derive(Debug, Clone, Default)]
+pub struct Signals {
+    pub upstream_saturated: bool,
+    pub refresh_backlog: usize,
+    pub total: i64,
+    pub errored: i64,
+}
+
+impl Signals {
+    /// Collects the signals from the tick's counters, the storage and the upstream. The refresh
+    /// backlog is only counted when its signal is enabled, as it walks the expiry indexes.
+    pub fn collect(
+        cfg: Option<&config::Brownout>,
+        snapshot: &Snapshot,
+        cache: &dyn Storage,
+        upstream: &dyn Upstream,
+    ) -> Self {
+        let refresh_backlog = match cfg.and_then(|b| b.max_refresh_backlog) {
+            Some(_) => cache.refresh_backlog(),
+            None => 0,
+        };
+        Self {
+            upstream_saturated: upstream.is_saturated(),
+            refresh_backlog,
+            total: snapshot.total,
+            errored: snapshot.errored,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    mode: Mode,
+    /// Decision of the signals, whatever the mode.
+    engaged: bool,
+    /// Start of the current run of ticks with the upstream saturated.
+    saturated_since: Option<Instant>,
+    /// Start of the current run of clear ticks while engaged.
+    clear_since: Option<Instant>,
+    breached: Vec<Breach>,
+}
+
+/// Brownout state shared by the cache controller, which feeds and reads it, and the admin
+/// endpoint.
+pub struct Brownout {
+    cfg: Option<config::Brownout>,
+    active: AtomicBool,
+    state: Mutex<State>,
+}
+
+impl Brownout {
+    /// Creates a brownout evaluating `cache.brownout`; without it (or disabled) misses are only
+    /// shed when forced on.
+    pub fn new(cfg: Option<&config::Brownout>) -> Self {
+        Self {
+            cfg: cfg.filter(|b| b.enabled).cloned(),
+            active: AtomicBool::new(false),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn config(&self) -> Option<&config::Brownout> {
+        self.cfg.as_ref()
+    }
+
+    /// Whether misses are shed right now.
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Whether a miss of the rule is to be shed right now.
+    pub fn sheds(&self, rule: &Rule) -> bool {
+        self.is_active() && rule.shed_on_brownout != Some(false)
+    }
+
+    /// `Retry-After` of shed requests.
+    pub fn retry_after(&self) -> Duration {
+        self.cfg.as_ref().and_then(|b| b.retry_after).unwrap_or(DEFAULT_RETRY_AFTER)
+    }
+
+    /// Evaluates the signals sampled at `now` and returns whether misses are shed.
+    ///
+    /// Any breached signal engages the brownout; it disengages once no signal has been breached
+    /// for `recover_after`, so a path recovering on and off does not flap.
+    pub fn observe(&self, signals: &Signals, now: Instant) -> bool {
+        let mut state = self.state.lock();
+        let Some(cfg) = self.cfg.as_ref() else {
+            return self.apply(&state);
+        };
+
+        let mut breached = Vec::new();
+        match cfg.upstream_saturated_for {
+            Some(min) if signals.upstream_saturated => {
+                let since = *state.saturated_since.get_or_insert(now);
+                let saturated_for = now.duration_since(since);
+                if saturated_for >= min {
+                    breached.push(Breach {
+                        condition: SIGNAL_UPSTREAM_SATURATED,
+                        value: saturated_for.as_secs_f64(),
+                        threshold: min.as_secs_f64(),
+                    });
+                }
+            }
+            _ => state.saturated_since = None,
+        }
+
+        if let Some(max) = cfg.max_refresh_backlog {
+            if signals.refresh_backlog > max {
+                breached.push(Breach {
+                    condition: SIGNAL_REFRESH_BACKLOG,
+                    value: signals.refresh_backlog as f64,
+                    threshold: max as f64,
+                });
+            }
+        }
+
+        if let Some(max) = cfg.max_error_rate {
+            let value = signals.errored as f64 / signals.total.max(1) as f64 * 100.0;
+            if signals.total > 0 && value > max {
+                breached.push(Breach { condition: SIGNAL_ERROR_RATE, value, threshold: max });
+            }
+        }
+
+        if !breached.is_empty() {
+            state.clear_since = None;
+            if !state.engaged {
+                state.engaged = true;
+                warn!(
+                    component = "brownout",
+                    event = "brownout_engaged",
+                    signals = ?breached.iter().map(|b| b.condition).collect::<Vec<_>>(),
+                    mode = ?state.mode,
+                    "upstream path saturated: shedding cache misses with 503"
+                );
+            }
+        } else if state.engaged {
+            let since = *state.clear_since.get_or_insert(now);
+            if now.duration_since(since) >= cfg.recover_after.unwrap_or(DEFAULT_RECOVER_AFTER) {
+                state.engaged = false;
+                state.clear_since = None;
+                info!(
+                    component = "brownout",
+                    event = "brownout_disengaged",
+                    mode = ?state.mode,
+                    "upstream path recovered: serving cache misses again"
+                );
+            }
+        }
+        state.breached = breached;
+
+        self.apply(&state)
+    }
+
+    /// Forces the brownout on or off, or hands it back to the signals.
+    pub fn set_mode(&self, mode: Mode) -> bool {
+        let mut state = self.state.lock();
+        if state.mode != mode {
+            warn!(component = "brownout", event = "brownout_mode", mode = ?mode, "brownout mode changed");
+        }
+        state.mode = mode;
+        self.apply(&state)
+    }
+
+    /// Current mode, decision and breached signals.
+    pub fn status(&self) -> Status {
+        let state = self.state.lock();
+        Status {
+            mode: state.mode,
+            active: self.is_active(),
+            engaged: state.engaged,
+            breached: state.breached.clone(),
+        }
+    }
+
+    fn apply(&self, state: &State) -> bool {
+        let active = match state.mode {
+            Mode::Auto => state.engaged,
+            Mode::On => true,
+            Mode::Off => false,
+        };
+        if self.active.swap(active, Ordering::Relaxed) != active {
+            metrics::set_brownout_active(active);
+        }
+        active
+    }
+}
+
+/// Brownout response body.
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    pub mode: Mode,
+    /// Whether misses are shed.
+    pub active: bool,
+    /// Whether the signals call for shedding, which only applies in `auto` mode.
+    pub engaged: bool,
+    /// Signals breached on the last tick.
+    pub breached: Vec<Breach>,
+}
+
+/// BrownoutController shows the brownout state and forces it for drills.
+pub struct BrownoutController {
+    brownout: Arc<Brownout>,
+}
+
+impl BrownoutController {
+    /// Creates a brownout controller over the state of the cache controller.
+    pub fn new(brownout: Arc<Brownout>) -> Self {
+        Self { brownout }
+    }
+
+    /// Sets the mode (when given) and returns the resulting state as JSON.
+    async fn handle(brownout: Arc<Brownout>, mode: Option<Mode>) -> impl IntoResponse {
+        if let Some(mode) = mode {
+            brownout.set_mode(mode);
+        }
+        (
+            StatusCode::OK,
+            [("content-type", "application/json; charset=utf-8")],
+            serde_json::to_string(&brownout.status()).unwrap_or_default(),
+        )
+    }
+}
+
+impl Controller for BrownoutController {
+    fn describe(&self) -> Vec<Route> {
+        let route = |path: &str, description: &'static str, mode: Option<Mode>| {
+            let brownout = self.brownout.clone();
+            let route = Route::get(path, description, move || {
+                let brownout = brownout.clone();
+                async move { Self::handle(brownout, mode).await }
+            });
+            if mode.is_some() {
+                route.mutating()
+            } else {
+                route
+            }
+        };
+        vec![
+            route("/advcache/brownout", "Shows the brownout state", None),
+            route("/advcache/brownout/on", "Forces the brownout on: misses are shed", Some(Mode::On)),
+            route("/advcache/brownout/off", "Forces the brownout off", Some(Mode::Off)),
+            route("/advcache/brownout/auto", "Returns the brownout to automatic detection", Some(Mode::Auto)),
+        ]
+    }
+}
@@ -1,11 +1,16 @@
 //! Cache bypass (on/off) controller.
 
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
-use serde::Serialize;
+use axum::{extract::Query, http::HeaderMap, http::StatusCode, response::IntoResponse};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tracing::warn;
 
 use crate::config::{Config, ConfigTrait};
-use crate::http::Controller;
+use crate::controller::metrics;
+use crate::http::{Controller, Route};
+use crate::upstream::peers::{PeerResult, Peers};
+
+const MSG_BYPASS_REFUSED: &str = "bypass refused: proxying to the origin is disabled (upstream.proxy_enabled: false)";
 
 /// Status response for bypass operations.
 #[derive(Debug, Serialize)]
@@ -13,25 +18,50 @@ struct StatusResponse {
     enabled: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     message: Option<String>,
+    /// Outcome of the toggle forwarded to each of `upstream.peers`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    peers: Vec<PeerResult>,
+}
+
+/// Query parameters of the toggles.
+#[derive(Deserialize)]
+struct ToggleQuery {
+    #[serde(rename = "_propagate")]
+    propagate: Option<String>,
+}
+
+/// Forwards a toggle to the peers unless it came from one or opted out.
+async fn propagate(peers: &Option<Arc<Peers>>, path: &str, query: &ToggleQuery, headers: &HeaderMap) -> Vec<PeerResult> {
+    match peers {
+        Some(peers) if Peers::should_propagate(headers, query.propagate.as_deref()) => peers.forward(path, headers).await,
+        _ => Vec::new(),
+    }
 }
 
 /// OnOffController provides endpoints to switch the advanced cache on and off.
 pub struct BypassOnOffController {
     cfg: Arc<Config>,
+    peers: Option<Arc<Peers>>,
 }
 
 impl BypassOnOffController {
     /// Creates a new OnOffController instance.
     pub fn new(cfg: Config) -> Self {
-        Self { cfg: Arc::new(cfg) }
+        metrics::set_cache_bypass(!cfg.is_enabled());
+        Self {
+            peers: Peers::from_config(&cfg).map(Arc::new),
+            cfg: Arc::new(cfg),
+        }
     }
 
     /// Handles POST /adv-cache/on and enables the advanced cache, returning JSON.
-    async fn on(cfg: Arc<Config>) -> impl IntoResponse {
+    async fn on(cfg: Arc<Config>, peers: Option<Arc<Peers>>, query: ToggleQuery, headers: HeaderMap) -> impl IntoResponse {
         cfg.set_enabled(true);
+        metrics::set_cache_bypass(false);
         let resp = StatusResponse {
             enabled: !cfg.is_enabled(),
             message: Some("bypass".to_string()),
+            peers: propagate(&peers, "/advcache/bypass/off", &query, &headers).await,
         };
         (
             StatusCode::OK,
@@ -41,11 +71,32 @@ impl BypassOnOffController {
     }
 
     /// Handles POST /adv-cache/off and disables the advanced cache, returning JSON.
-    async fn off(cfg: Arc<Config>) -> impl IntoResponse {
+    /// Refused with 409 when proxying is disabled, since bypass would then serve nothing.
+    async fn off(cfg: Arc<Config>, peers: Option<Arc<Peers>>, query: ToggleQuery, headers: HeaderMap) -> impl IntoResponse {
+        if !cfg.is_proxy_enabled() {
+            warn!(
+                component = "bypass",
+                event = "bypass_refused",
+                "refused to enable bypass: upstream.proxy_enabled is false, every request would be rejected"
+            );
+            let resp = StatusResponse {
+                enabled: !cfg.is_enabled(),
+                message: Some(MSG_BYPASS_REFUSED.to_string()),
+                peers: Vec::new(),
+            };
+            return (
+                StatusCode::CONFLICT,
+                [("content-type", "application/json; charset=utf-8")],
+                serde_json::to_string(&resp).unwrap_or_default(),
+            );
+        }
+
         cfg.set_enabled(false);
+        metrics::set_cache_bypass(true);
         let resp = StatusResponse {
             enabled: !cfg.is_enabled(),
             message: Some("bypass".to_string()),
+            peers: propagate(&peers, "/advcache/bypass/on", &query, &headers).await,
         };
         (
             StatusCode::OK,
@@ -59,6 +110,7 @@ impl BypassOnOffController {
         let resp = StatusResponse {
             enabled: !cfg.is_enabled(),
             message: Some("bypass".to_string()),
+            peers: Vec::new(),
         };
         (
             StatusCode::OK,
@@ -69,63 +121,40 @@ impl BypassOnOffController {
 }
 
 impl Controller for BypassOnOffController {
-    fn add_route(&self, router: Router) -> Router {
-        let cfg1 = self.cfg.clone();
-        let cfg2 = self.cfg.clone();
-        let cfg3 = self.cfg.clone();
-        let router = router
-            .route(
-                "/advcache/bypass/on",
-                get(move || {
-                    let cfg = cfg1.clone();
-                    async move { Self::off(cfg).await }
-                }),
-            )
-            .route(
-                "/advcache/bypass/off",
-                get(move || {
-                    let cfg = cfg2.clone();
-                    async move { Self::on(cfg).await }
-                }),
-            )
-            .route(
-                "/advcache/bypass",
-                get(move || {
-                    let cfg = cfg3.clone();
-                    async move { Self::bypass_is(cfg).await }
-                }),
-            )
-            .route(
-                "/cache/bypass/on",
-                get({
-                    let cfg = self.cfg.clone();
-                    move || {
-                        let cfg = cfg.clone();
-                        async move { Self::off(cfg).await }
-                    }
-                }),
-            )
-            .route(
-                "/cache/bypass/off",
-                get({
-                    let cfg = self.cfg.clone();
-                    move || {
-                        let cfg = cfg.clone();
-                        async move { Self::on(cfg).await }
-                    }
-                }),
-            )
-            .route(
-                "/cache/bypass",
-                get({
-                    let cfg = self.cfg.clone();
-                    move || {
-                        let cfg = cfg.clone();
-                        async move { Self::bypass_is(cfg).await }
-                    }
-                }),
-            );
+    fn describe(&self) -> Vec<Route> {
+        // Paths are named after bypass: `/on` turns the cache off.
+        let on = |path: &str| {
+            let (cfg, peers) = (self.cfg.clone(), self.peers.clone());
+            Route::get(path, "Turns bypass on: requests skip the cache", move |Query(query): Query<ToggleQuery>, headers: HeaderMap| {
+                let (cfg, peers) = (cfg.clone(), peers.clone());
+                async move { Self::off(cfg, peers, query, headers).await }
+            })
+            .mutating()
+        };
+        let off = |path: &str| {
+            let (cfg, peers) = (self.cfg.clone(), self.peers.clone());
+            Route::get(path, "Turns bypass off: requests go through the cache", move |Query(query): Query<ToggleQuery>, headers: HeaderMap| {
+                let (cfg, peers) = (cfg.clone(), peers.clone());
+                async move { Self::on(cfg, peers, query, headers).await }
+            })
+            .mutating()
+        };
+        let status = |path: &str| {
+            let cfg = self.cfg.clone();
+            Route::get(path, "Shows whether bypass is on", move || {
+                let cfg = cfg.clone();
+                async move { Self::bypass_is(cfg).await }
+            })
+        };
 
-        router
+        vec![
+            on("/advcache/bypass/on"),
+            off("/advcache/bypass/off"),
+            status("/advcache/bypass"),
+            // Legacy aliases
+            on("/cache/bypass/on").hidden(),
+            off("/cache/bypass/off").hidden(),
+            status("/cache/bypass").hidden(),
+        ]
     }
 }
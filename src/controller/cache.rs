@@ -2,36 +2,54 @@
 
 use axum::{
     extract::State,
-    http::{HeaderMap, HeaderValue, StatusCode},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, Uri, Version},
     response::Response,
-    routing::get,
-    Router,
 };
-use std::sync::atomic::{AtomicI64, Ordering};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::time::interval;
 use tokio_util::sync::CancellationToken;
-use tracing::info;
+use tracing::{info, warn};
+use xxhash_rust::xxh3::xxh3_128;
 
-use crate::config::{Config, ConfigTrait};
+use crate::config::{Config, ConfigTrait, DownstreamTtl, Rule};
 use crate::dedlog;
 use crate::http::header::filter_and_sort_request as filter_and_sort_headers;
 use crate::http::query::filter_and_sort_request as filter_and_sort_queries;
+use crate::http::query::{ignored_queries, DuplicateQueryError};
 use crate::http::render::renderer;
-use crate::http::Controller;
+use crate::http::utils::cache_status::{self, Diagnostics, XCache};
+use crate::http::{Controller, Route};
 use crate::http::is_compression_enabled;
-use crate::controller::metrics;
+use crate::controller::brownout::{self, Brownout};
+use crate::controller::cache_metrics::ControllerMetrics;
+use crate::controller::collapse::{Collapser, Join, Outcome, SharedError};
+use crate::controller::fill_limit::FillLimits;
+use crate::controller::health;
+use crate::controller::metrics::{self, RolloutResult};
+use crate::controller::purge::{self, PurgeResponse};
+use crate::controller::reclaim::IdleReclaim;
+use crate::controller::unmatched;
 use crate::metrics as prom_metrics;
 use crate::metrics::policy::Policy as LifetimePolicy;
+use crate::plugin::{self, KeyInput, ResponseView};
 use crate::model::{
-    is_cache_rule_not_found_err, match_cache_rule, Response as ModelResponse,
+    freshness, is_cache_rule_not_found_err, match_cache_rule, vary, Entry, RefreshParams, Response as ModelResponse,
 };
+use crate::db::capture::{self, Op};
 use crate::db::Storage;
 use crate::time;
 use crate::traces;
 use crate::upstream::actual_policy;
+use crate::upstream::backend_hyper_impl::{is_response_too_large, throttled_retry_after};
+use crate::upstream::encoding;
+use crate::upstream::loop_guard;
+use crate::upstream::misconfig::{self, Misconfig};
 use crate::upstream::Upstream;
+use crate::upstream::Response as UpstreamResponse;
 
 // Error constants
 const ERR_MSG_INTERNAL_ERROR: &str = "internal error";
@@ -40,6 +58,17 @@ const ERR_MSG_UPSTREAM_ERROR_WHILE_PROXYING: &str = "fetch upstream error while
 const ERR_MSG_UPSTREAM_ERROR_WHILE_CACHE_PROXYING: &str =
     "fetch upstream error while cache-proxying";
 const ERR_MSG_WRITE_ENTRY_TO_RESPONSE: &str = "write entry into response failed";
+const ERR_MSG_PROXY_DISABLED: &str = "request is not served by the cache and proxying to the origin is disabled";
+const ERR_MSG_UPSTREAM_MISCONFIGURED: &str = "upstream backend is misconfigured";
+
+/// `error_code` of the problem answered to requests failing for the backend config.
+pub const ERROR_CODE_UPSTREAM_MISCONFIGURED: &str = "upstream_misconfigured";
+
+/// Header whose value tags the errors a request reports in `/advcache/errors`.
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Largest request body read, to key a POST or to proxy it; larger ones are answered 413.
+const MAX_REQUEST_BODY: usize = 2 << 20;
 
 // Error types
 #[derive(Debug, thiserror::Error)]
@@ -48,20 +77,268 @@ pub enum CacheError {
     // when we need to retry a request from proxy.
     #[error("need retry through proxy")]
     NeedRetryThroughProxy,
+    /// The request repeats a query param with different values under `duplicate_query: reject`.
+    #[error(transparent)]
+    DuplicateQuery(#[from] DuplicateQueryError),
+    /// The key is outside the rule's `rollout_percent`: the request is proxied without storing.
+    #[error("key is outside the rule rollout")]
+    OutOfRollout(Arc<Rule>),
+    /// A miss shed during a brownout: answered `upstream.throttle_status` with `Retry-After`,
+    /// without reaching the origin.
+    #[error("miss shed during a brownout")]
+    Shed(Duration),
+    /// A miss that waited `max_fill_wait` for a fill slot of its rule in vain: answered
+    /// `upstream.throttle_status` with `Retry-After`, without reaching the origin.
+    #[error("no fill slot of the rule freed up in time")]
+    FillCapped(Duration),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
-// Metrics counters
-static TOTAL: AtomicI64 = AtomicI64::new(0);
-static HITS: AtomicI64 = AtomicI64::new(0);
-static MISSES: AtomicI64 = AtomicI64::new(0);
-static PROXIED: AtomicI64 = AtomicI64::new(0);
-static ERRORED: AtomicI64 = AtomicI64::new(0);
-static DURATION: AtomicI64 = AtomicI64::new(0);
-static CACHE_DURATION: AtomicI64 = AtomicI64::new(0);
-static PROXY_DURATION: AtomicI64 = AtomicI64::new(0);
-static ERROR_DURATION: AtomicI64 = AtomicI64::new(0);
+impl CacheError {
+    /// Status answered to the client: 400 for a rejected duplicate query param, 502 when the
+    /// origin sent a response the proxy refuses to relay (body over `backend.max_response_size`),
+    /// 503 otherwise.
+    fn status_code(&self) -> StatusCode {
+        match self {
+            CacheError::DuplicateQuery(_) => StatusCode::BAD_REQUEST,
+            CacheError::Other(e) if is_response_too_large(e) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Misconfiguration of the backend the request failed for, if that is why.
+    fn misconfig(&self) -> Option<&Misconfig> {
+        match self {
+            CacheError::Other(e) => misconfig::misconfig_of(e),
+            _ => None,
+        }
+    }
+
+    /// Why and for how long the request was refused to protect the origin, if it was.
+    fn throttle(&self) -> Option<(ThrottleReason, Duration)> {
+        match self {
+            CacheError::Shed(retry_after) => Some((ThrottleReason::Brownout, *retry_after)),
+            CacheError::FillCapped(retry_after) => Some((ThrottleReason::FillCap, *retry_after)),
+            CacheError::Other(e) => throttled_retry_after(e).map(|after| (ThrottleReason::UpstreamProtection, after)),
+            _ => None,
+        }
+    }
+}
+
+/// Why a request was refused to protect the origin, answered with `upstream.throttle_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleReason {
+    /// The `deny` policy limiter of the backend had no token left.
+    UpstreamProtection = 0,
+    /// A miss shed during a brownout.
+    Brownout = 1,
+    /// A miss that waited `max_fill_wait` for a fill slot of its rule in vain.
+    FillCap = 2,
+}
+
+impl ThrottleReason {
+    pub const ALL: [ThrottleReason; 3] = [ThrottleReason::UpstreamProtection, ThrottleReason::Brownout, ThrottleReason::FillCap];
+
+    /// Label of the `requests_throttled` metric and value of `X-Error-Reason`.
+    pub fn label(self) -> &'static str {
+        match self {
+            ThrottleReason::UpstreamProtection => "upstream_protection",
+            ThrottleReason::Brownout => "brownout",
+            ThrottleReason::FillCap => "fill_cap",
+        }
+    }
+
+    /// Value of `X-RateLimit-Reason`.
+    fn header(self) -> &'static str {
+        match self {
+            ThrottleReason::UpstreamProtection => "upstream-protection",
+            ThrottleReason::Brownout => "brownout",
+            ThrottleReason::FillCap => "fill-cap",
+        }
+    }
+}
+
+/// Cache rule and normalized key of a request, as used for the storage lookup.
+pub(crate) struct CacheRequest {
+    pub rule: Arc<Rule>,
+    /// Whitelisted and sorted query params that make up the key.
+    pub queries: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Whitelisted and sorted headers that make up the key.
+    pub headers: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Body of a POST keyed by it, sent to the origin and stored with the entry.
+    pub body: Option<Vec<u8>>,
+    /// Payload-less entry carrying the computed key and fingerprint.
+    pub entry: Entry,
+}
+
+/// Matches the cache rule for the path and builds the request key from the rule's
+/// query and header whitelists, and from the body of a POST whose rule keys it. Returns
+/// `NeedRetryThroughProxy` when no rule matches, the rule does not cache the method or the
+/// body is over its `max_key_body`, and `DuplicateQuery` when the rule rejects the request's
+/// repeated query params.
+pub(crate) fn resolve_cache_request(
+    cfg: &Config,
+    path_bytes: &[u8],
+    query_str: &str,
+    request_headers: &[(&str, &str)],
+    method: &str,
+    body: Option<&[u8]>,
+) -> Result<CacheRequest, CacheError> {
+    let rule = match match_cache_rule(cfg, path_bytes) {
+        Ok(r) => r,
+        Err(e) => {
+            if is_cache_rule_not_found_err(&*e) {
+                return Err(CacheError::NeedRetryThroughProxy);
+            }
+            return Err(CacheError::Other(anyhow::anyhow!("{}", e)));
+        }
+    };
+    if !rule.caches_method(method) {
+        return Err(CacheError::NeedRetryThroughProxy);
+    }
+    resolve_ruled_request(rule, path_bytes, query_str, request_headers, method, body)
+}
+
+/// Builds the request key as `resolve_cache_request` does, for a rule already matched and
+/// caching the method.
+fn resolve_ruled_request(
+    rule: Arc<Rule>,
+    path_bytes: &[u8],
+    query_str: &str,
+    request_headers: &[(&str, &str)],
+    method: &str,
+    body: Option<&[u8]>,
+) -> Result<CacheRequest, CacheError> {
+    let body = body.filter(|_| method.eq_ignore_ascii_case("POST") && rule.keys_body());
+    if body.is_some_and(|body| body.len() > rule.max_key_body()) {
+        return Err(CacheError::NeedRetryThroughProxy);
+    }
+
+    let headers = filter_and_sort_headers(Some(&rule), request_headers);
+    let queries = filter_and_sort_queries(Some(&rule), query_str)?;
+    let extra = rule.cache_key.transformer.as_deref().and_then(|name| {
+        let input = KeyInput {
+            path: path_bytes,
+            queries: &queries,
+            headers: &headers,
+            request_headers,
+        };
+        plugin::key_transformer(name).ok()?.key_bytes(&input)
+    });
+    // The method keeps a POST apart from a GET of the same params.
+    let extra = match body {
+        Some(body) => {
+            let mut extra = extra.unwrap_or_default();
+            extra.push(0);
+            extra.extend_from_slice(b"POST");
+            extra.extend_from_slice(&xxh3_128(body).to_le_bytes());
+            Some(extra)
+        }
+        None => extra,
+    };
+    let entry = Entry::with_key_extra(rule.clone(), &queries, &headers, extra.as_deref());
+
+    Ok(CacheRequest {
+        rule,
+        queries,
+        headers,
+        body: body.map(<[u8]>::to_vec),
+        entry,
+    })
+}
+
+/// Asks the rule's admission hook, if any, whether the fetched response may be stored.
+fn is_admitted_by_hook(rule: &Rule, request: &KeyInput<'_>, resp: &ModelResponse) -> bool {
+    let Some(hook) = rule.cache_value.admission_hook.as_deref().and_then(|name| plugin::admission_hook(name).ok()) else {
+        return true;
+    };
+    let response = ResponseView {
+        status: resp.status,
+        headers: &resp.headers,
+        body: &resp.body,
+    };
+    let admitted = hook.admit(request, &response);
+    if !admitted {
+        tracing::debug!(
+            component = "cache-controller",
+            event = "admission_hook_refused",
+            rule = rule.path.as_deref().unwrap_or_default(),
+            "admission hook refused to store the response"
+        );
+    }
+    admitted
+}
+
+/// Moves an upstream response into the model one, without copying headers or body.
+fn into_model_response(resp: UpstreamResponse) -> ModelResponse {
+    ModelResponse {
+        status: resp.status,
+        headers: resp.headers,
+        body: resp.body,
+    }
+}
+
+/// Whether the decoded body of an origin answer is over the largest one the rule stores.
+fn exceeds_max_body_size(rule: &Rule, storage: &crate::config::Storage, resp: &ModelResponse) -> bool {
+    let Some(limit) = rule.max_body_size(storage) else {
+        return false;
+    };
+    let coding = resp.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case("content-encoding")).map(|(_, v)| v.as_str());
+    encoding::decoded_len(coding, &resp.body, limit) > limit
+}
+
+/// Headers of the request an origin answer varies on that were not sent with it.
+fn unsent_vary_headers(
+    rsp_headers: &[(String, String)],
+    sent: &[(Vec<u8>, Vec<u8>)],
+    request_headers: &[(&str, &str)],
+) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let Some(vary::Varies::Headers(names)) = vary::parse(rsp_headers) else {
+        return Vec::new();
+    };
+    let unsent = names
+        .iter()
+        .map(String::as_str)
+        .filter(|name| !sent.iter().any(|(k, _)| k.eq_ignore_ascii_case(name.as_bytes())));
+    vary::request_headers(unsent, request_headers)
+}
+
+/// Counts a request of a ramped rule for the rollout comparison.
+fn record_rollout(rule: &Rule, in_rollout: bool, result: RolloutResult) {
+    metrics::inc_rollout_requests(rule.path.as_deref().unwrap_or_default(), in_rollout, result);
+}
+
+/// Borrows the request headers with valid string values as name/value pairs.
+pub(crate) fn collect_request_headers(headers: &HeaderMap) -> Vec<(&str, &str)> {
+    let mut request_headers = Vec::with_capacity(headers.len());
+    for (k, v) in headers {
+        if let Ok(v) = v.to_str() {
+            request_headers.push((k.as_str(), v));
+        }
+    }
+    request_headers
+}
+
+/// Copies borrowed request headers into the owned pairs the upstream proxies with.
+fn to_owned_headers(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+    headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+/// Request line (`GET /path?query HTTP/1.1`) for logs and traces. Formatting it takes an
+/// allocation, so it is only done when something is actually written.
+#[derive(Clone, Copy)]
+struct RequestLine<'a> {
+    method: &'a Method,
+    uri: &'a Uri,
+    version: Version,
+}
+
+impl fmt::Display for RequestLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {:?}", self.method, self.uri, self.version)
+    }
+}
 
 /// Handles cache API requests with read/write-through, error reporting, and metrics.
 pub struct CacheProxyController {
@@ -69,6 +346,11 @@ pub struct CacheProxyController {
     shutdown_token: CancellationToken,
     cache: Arc<dyn Storage>,
     upstream: Arc<dyn Upstream>,
+    counters: Arc<ControllerMetrics>,
+    brownout: Arc<Brownout>,
+    reclaim: Arc<IdleReclaim>,
+    fill_limits: Arc<FillLimits>,
+    collapser: Arc<Collapser>,
 }
 
 impl CacheProxyController {
@@ -79,56 +361,96 @@ impl CacheProxyController {
         cache: Arc<dyn Storage>,
         backend: Arc<dyn Upstream>,
     ) -> Self {
+        let reclaim = Arc::new(IdleReclaim::new(cfg.runtime().idle_reclaim.as_ref(), backend.clone()));
         let controller = Self {
-            cfg: Arc::new(cfg),
             shutdown_token,
             cache,
             upstream: backend,
+            counters: Arc::new(ControllerMetrics::new()),
+            brownout: Arc::new(Brownout::new(cfg.cache.brownout.as_ref())),
+            reclaim,
+            fill_limits: Arc::new(FillLimits::new()),
+            collapser: Arc::new(Collapser::new(&cfg)),
+            cfg: Arc::new(cfg),
         };
 
+        if !controller.cfg.is_enabled() && !controller.cfg.is_proxy_enabled() {
+            warn!(
+                component = "cache-controller",
+                event = "cache_and_proxy_disabled",
+                status = controller.cfg.proxy_disabled_status(),
+                "cache is disabled and proxying is disabled: every request will be rejected"
+            );
+        }
+
         // Start metrics logger (runs every 5 seconds)
         controller.run_logger_metrics_writer();
 
         controller
     }
 
+    /// Brownout state of the controller, for the admin endpoint that forces it.
+    pub fn brownout(&self) -> Arc<Brownout> {
+        self.brownout.clone()
+    }
+
+    /// Idle reclamation of the controller, for the admin endpoint that runs it on demand.
+    pub fn idle_reclaim(&self) -> Arc<IdleReclaim> {
+        self.reclaim.clone()
+    }
+
     /// Main HTTP handler for cache requests.
     async fn index(
         State(controller): State<Arc<Self>>,
         request: axum::extract::Request,
     ) -> Response {
         let start = Instant::now();
-        TOTAL.fetch_add(1, Ordering::Relaxed);
+        controller.counters.inc_total();
         // Update metrics in real-time
         metrics::inc_total(1);
 
         // Extract request information
-        let uri = request.uri();
+        let (parts, body) = request.into_parts();
+        let uri = &parts.uri;
         let path = uri.path();
         let path_bytes = path.as_bytes();
 
         // Extract headers
-        // Note: HeaderName::to_string() returns lowercase, but we preserve original case via as_str()
-        let mut request_headers = Vec::new();
-        for (k, v) in request.headers() {
-            if let Ok(v_str) = v.to_str() {
-                // Use as_str() to get the original header name (axum normalizes to lowercase)
-                // But for comparison, we use eq_ignore_ascii_case anyway
-                let header_name = k.as_str().to_string();
-                request_headers.push((header_name, v_str.to_string()));
-            }
-        }
+        let request_headers = collect_request_headers(&parts.headers);
 
         // Extract query string
         let query_str = uri.query().unwrap_or("");
 
-        // Build request string representation for tracing
-        let request_str = format!("{} {} {:?}", request.method(), uri, request.version());
+        let request_line = RequestLine {
+            method: &parts.method,
+            uri,
+            version: parts.version,
+        };
+
+        // A request that already went through this instance would only come back again.
+        if let Some(chain) = parts.headers.get(loop_guard::LOOP_HEADER).and_then(|v| v.to_str().ok()) {
+            if loop_guard::is_loop(chain) {
+                return controller.respond_loop_detected(chain, request_line);
+            }
+        }
+
+        // The body of other methods than GET and HEAD is read whole: a POST rule may key it,
+        // else it is proxied along.
+        let body = if parts.method != Method::GET && parts.method != Method::HEAD {
+            match axum::body::to_bytes(body, MAX_REQUEST_BODY).await {
+                Ok(body) => Some(body),
+                Err(err) => return controller.respond_unreadable_body(&err),
+            }
+        } else {
+            None
+        };
+        let method = parts.method.as_str();
 
         let tracing_enabled = traces::is_active_tracing();
 
+        #[cfg(feature = "tracing-otel")]
         if tracing_enabled {
-            let trace_ctx = traces::extract(request.headers());
+            let trace_ctx = traces::extract(&parts.headers);
             // Attach context in synchronous block before any await
             // The guard will be dropped at end of block, but tracing-opentelemetry
             // will propagate the context through async boundaries automatically
@@ -144,9 +466,9 @@ impl CacheProxyController {
             Some(tracing::span!(
                 tracing::Level::INFO,
                 "ingress",
-                http.method = %request.method(),
+                http.method = %parts.method,
                 http.path = path,
-                http.request = %request_str,
+                http.request = %dedlog::redacted(&request_line.to_string()),
             ))
         } else {
             None
@@ -167,34 +489,55 @@ impl CacheProxyController {
                     path_bytes,
                     query_str,
                     &request_headers,
-                    request.method().as_str(),
-                    &request_str,
+                    method,
+                    body.as_deref(),
+                    request_line,
                 )
                 .await
             {
                 Ok(ok) => Ok(ok),
                 Err(CacheError::NeedRetryThroughProxy) => {
                     path_kind = PathKind::Proxy;
-                    PROXIED.fetch_add(1, Ordering::Relaxed);
-                    metrics::inc_proxied(1);
                     controller
-                        .handle_through_proxy(
+                        .proxy_or_refuse(
                             path,
                             query_str,
                             &request_headers,
-                            request.method().as_str(),
-                            &request_str,
+                            method,
+                            body.as_deref(),
+                            request_line,
                         )
                         .await
                 }
+                Err(CacheError::OutOfRollout(rule)) => {
+                    path_kind = PathKind::Proxy;
+                    let result = controller
+                        .proxy_or_refuse(
+                            path,
+                            query_str,
+                            &request_headers,
+                            method,
+                            body.as_deref(),
+                            request_line,
+                        )
+                        .await;
+                    let failed = match &result {
+                        Ok((resp, ..)) => resp.status().is_server_error(),
+                        Err(_) => true,
+                    };
+                    record_rollout(
+                        &rule,
+                        false,
+                        if failed { RolloutResult::Error } else { RolloutResult::Proxied },
+                    );
+                    result
+                }
                 Err(err) => Err(err),
             }
         } else {
             path_kind = PathKind::Proxy;
-            PROXIED.fetch_add(1, Ordering::Relaxed);
-            metrics::inc_proxied(1);
             controller
-                .handle_through_proxy(path, query_str, &request_headers, request.method().as_str(), &request_str)
+                .proxy_or_refuse(path, query_str, &request_headers, method, body.as_deref(), request_line)
                 .await
         };
 
@@ -202,12 +545,27 @@ impl CacheProxyController {
 
         let (response, cache_hit, cache_key_attr) = match result {
             Ok((resp, hit, _is_error, key)) => (resp, hit, key),
+            Err(CacheError::DuplicateQuery(err)) => {
+                if let Some(ref s) = span {
+                    s.record(traces::ATTR_HTTP_STATUS_CODE_KEY, StatusCode::BAD_REQUEST.as_u16());
+                    s.record(traces::ATTR_CACHE_HIT, false);
+                }
+                return controller.respond_duplicate_query(&err);
+            }
             Err(err) => {
-                DURATION.fetch_add(elapsed, Ordering::Relaxed);
-                ERROR_DURATION.fetch_add(elapsed, Ordering::Relaxed);
-                ERRORED.fetch_add(1, Ordering::Relaxed);
+                if let Some((reason, retry_after)) = err.throttle() {
+                    if let Some(ref s) = span {
+                        s.record(traces::ATTR_HTTP_STATUS_CODE_KEY, controller.cfg.throttle_status());
+                        s.record(traces::ATTR_CACHE_HIT, false);
+                    }
+                    return controller.respond_throttled(reason, retry_after);
+                }
+
+                controller.counters.add_error_duration(elapsed);
+                controller.counters.inc_errored();
                 metrics::inc_errors(1);
-                let status_code = StatusCode::SERVICE_UNAVAILABLE.as_u16();
+                let status = err.status_code();
+                let status_code = status.as_u16();
                 metrics::inc_status_code(status_code);
 
                 // Set tracing attributes for error case
@@ -218,7 +576,10 @@ impl CacheProxyController {
                     s.record(traces::ATTR_CACHE_IS_ERR, true);
                 }
 
-                return controller.respond_service_unavailable(&err, &request_str);
+                if let Some(misconfig) = err.misconfig() {
+                    return controller.respond_misconfigured(misconfig, &err, request_line);
+                }
+                return controller.respond_error(status, &err, request_line);
             }
         };
 
@@ -236,11 +597,10 @@ impl CacheProxyController {
         metrics::inc_status_code(status_code);
 
         // Update duration metrics
-        DURATION.fetch_add(elapsed, Ordering::Relaxed);
         match path_kind {
-            PathKind::Cache => CACHE_DURATION.fetch_add(elapsed, Ordering::Relaxed),
-            PathKind::Proxy => PROXY_DURATION.fetch_add(elapsed, Ordering::Relaxed),
-        };
+            PathKind::Cache => controller.counters.add_cache_duration(elapsed, cache_hit),
+            PathKind::Proxy => controller.counters.add_proxy_duration(elapsed),
+        }
 
         // Set tracing span attributes after handling
         if let Some(ref s) = span {
@@ -262,102 +622,340 @@ impl CacheProxyController {
         &self,
         path_bytes: &[u8],
         query_str: &str,
-        request_headers: &[(String, String)],
-        _method: &str,
-        request_str: &str,
+        request_headers: &[(&str, &str)],
+        method: &str,
+        body: Option<&[u8]>,
+        request_line: RequestLine<'_>,
     ) -> Result<(Response, bool, bool, u64), CacheError> {
+        // PURGE removes what the same request would be served with GET.
+        if method == "PURGE" {
+            return self.purge(path_bytes, query_str, request_headers);
+        }
+
         // Attempts to find cache rule in config. Otherwise just proxy it.
         let rule = match match_cache_rule(&self.cfg, path_bytes) {
-            Ok(r) => r, // Already Arc<Rule>, just clone the Arc
-            Err(e) => {
-                if is_cache_rule_not_found_err(&*e) {
-                    return Err(CacheError::NeedRetryThroughProxy);
-                }
-                return Err(CacheError::Other(anyhow::anyhow!("{}", e)));
+            Ok(rule) => rule,
+            Err(e) if is_cache_rule_not_found_err(&*e) => {
+                self.count_unmatched(path_bytes);
+                return Err(CacheError::NeedRetryThroughProxy);
+            }
+            Err(e) => return Err(CacheError::Other(anyhow::anyhow!("{}", e))),
+        };
+
+        // Only the methods a rule caches reach the storage: the others are proxied on ruled
+        // paths too, without looking up or storing anything.
+        if !rule.caches_method(method) {
+            metrics::inc_ruled_non_get_requests(method);
+            return Err(CacheError::NeedRetryThroughProxy);
+        }
+
+        let CacheRequest {
+            rule,
+            queries: queries_bytes,
+            headers: mut headers_bytes,
+            body: keyed_body,
+            entry: request_entry,
+        } = resolve_ruled_request(rule, path_bytes, query_str, request_headers, method, body)?;
+
+        let ramped = rule.cache_value.rollout_percent.is_ramped();
+        if ramped && !rule.cache_value.rollout_percent.includes(request_entry.key()) {
+            return Err(CacheError::OutOfRollout(rule));
+        }
+        let rollout = |result| {
+            if ramped {
+                record_rollout(&rule, true, result);
             }
         };
 
         // Extract forwarded_host from original headers BEFORE filtering.
         // This ensures X-Forwarded-Host and Host are available even if not in cache key whitelist.
-        let headers_bytes_for_forwarded: Vec<(Vec<u8>, Vec<u8>)> = request_headers
-            .iter()
-            .map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec()))
-            .collect();
-        let forwarded_host = crate::upstream::proxy::forwarded_host_value_bytes(&headers_bytes_for_forwarded);
-        
-        let headers_bytes = filter_and_sort_headers(Some(&rule), request_headers);
-        let queries_bytes = filter_and_sort_queries(Some(&rule), query_str);
-
-        let request_entry = crate::model::Entry::new(rule.clone(), queries_bytes.as_ref(), headers_bytes.as_ref());
+        let forwarded_host = crate::upstream::proxy::forwarded_host_value_bytes(request_headers);
 
-        let (cache_entry_opt, hit) = self.cache.get(&request_entry);
+        // A key removed with a tombstone is fetched from the origin but not stored until it expires.
+        let tombstoned = self.cache.is_tombstoned(request_entry.key());
+        let (cache_entry_opt, hit) = if tombstoned {
+            (None, false)
+        } else {
+            self.cache.get_variant(&request_entry, request_headers)
+        };
 
         if hit {
             if let Some(cache_entry) = cache_entry_opt {
-                HITS.fetch_add(1, Ordering::Relaxed);
+                self.counters.inc_hits();
                 metrics::inc_cache_hits(1);
+                if cache_entry.is_negative() {
+                    metrics::inc_cache_negative_hits();
+                }
+                rollout(RolloutResult::Hit);
 
                 let cache_key = cache_entry.key();
-                return match renderer::write_from_entry(&cache_entry) {
-                    Ok(response) => Ok((response, true, false, cache_key)),
+                // Past its TTL, the entry is served while its refresh is queued.
+                let served = if cache_entry.is_expired(&self.cfg) { XCache::Stale } else { XCache::Hit };
+                return match renderer::write_from_entry(&cache_entry, request_headers, self.diagnostics(served, cache_key)) {
+                    Ok(mut response) => {
+                        self.set_downstream_ttl(&mut response, &rule, &cache_entry, false);
+                        Ok((response, true, false, cache_key))
+                    }
                     Err(e) => {
-                dedlog::err(Some(e.as_ref()), Some(request_str), ERR_MSG_WRITE_ENTRY_TO_RESPONSE);
+                dedlog::err("cache-controller", Some(e.as_ref()), Some(&request_line.to_string()), ERR_MSG_WRITE_ENTRY_TO_RESPONSE);
                         Err(CacheError::NeedRetryThroughProxy)
                     }
                 };
             }
         }
 
-        MISSES.fetch_add(1, Ordering::Relaxed);
+        self.counters.inc_misses();
         metrics::inc_cache_misses(1);
 
+        if self.brownout.sheds(&rule) {
+            return Err(CacheError::Shed(self.brownout.retry_after()));
+        }
+
         let cache_key = request_entry.key();
+
+        // Concurrent misses of the key share one fill: the first leads it, the others are
+        // answered by its outcome.
+        let flight = match self.collapser.join(cache_key).await {
+            Join::Leader(flight) => Some(flight),
+            Join::Follower(outcome) => {
+                return self.respond_collapsed(&outcome, &rule, &request_entry, request_headers, rollout);
+            }
+            Join::TimedOut => None,
+        };
         
+        // Headers the stored variants of the key vary on go to the origin, so that it answers
+        // for this request's variant, and are stored with the payload for refreshes to send.
+        if let Some(variant) = self.cache.get_by_key(cache_key).0.and_then(|stored| stored.variant()) {
+            let names: Vec<&str> = variant
+                .names()
+                .filter(|name| !headers_bytes.iter().any(|(k, _)| k.eq_ignore_ascii_case(name.as_bytes())))
+                .collect();
+            let varied = vary::request_headers(names, request_headers);
+            headers_bytes.extend(varied);
+        }
+
         // Add forwarded_host to headers_bytes so it's available in request().
         // This ensures Host header is passed to upstream even if not in cache key whitelist.
-        // Note: We clone headers_bytes because it was moved into request_entry above.
-        let mut headers_bytes_with_host = headers_bytes.clone();
+        // The additions are truncated away once the upstream answered: the payload only stores
+        // the whitelisted headers and those the response varies on.
+        let mut whitelisted_headers = headers_bytes.len();
         if let Some(host_bytes) = forwarded_host {
-            headers_bytes_with_host.push((b"host".to_vec(), host_bytes.to_vec()));
+            headers_bytes.push((b"host".to_vec(), host_bytes.to_vec()));
+        }
+        // Keep the inbound loop chain so the upstream sees every instance the request went through.
+        if let Some((k, v)) = request_headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(loop_guard::LOOP_HEADER)) {
+            headers_bytes.push((k.as_bytes().to_vec(), v.as_bytes().to_vec()));
         }
         
-        let upstream_resp = match self
+        // Ignored query params are not part of the key, so they only reach the origin on request.
+        let mut upstream_queries = Cow::Borrowed(queries_bytes.as_slice());
+        if self.cfg.is_ignored_query_forwarded() {
+            let ignored = ignored_queries(&rule, query_str);
+            if !ignored.is_empty() {
+                upstream_queries.to_mut().extend(ignored);
+            }
+        }
+
+        // Held until the origin answered, so the rule's fills in flight stay under its cap.
+        let fill_permit = self.fill_limits.acquire(&rule).await.map_err(CacheError::FillCapped)?;
+        let fill_method = if keyed_body.is_some() { "POST" } else { "GET" };
+        let mut upstream_result = self
             .upstream
-            .request(&rule, queries_bytes.as_ref(), &headers_bytes_with_host)
-            .await
-        {
+            .request(fill_method, &rule, &upstream_queries, &headers_bytes, keyed_body.as_deref())
+            .await;
+        // An answer varying on request headers the origin was not sent is asked for again with
+        // them, once: this request's variant is the one stored.
+        let unsent = match &upstream_result {
+            Ok(resp) => unsent_vary_headers(&resp.headers, &headers_bytes[..whitelisted_headers], request_headers),
+            Err(_) => Vec::new(),
+        };
+        if !unsent.is_empty() {
+            let added = unsent.len();
+            headers_bytes.splice(whitelisted_headers..whitelisted_headers, unsent);
+            whitelisted_headers += added;
+            upstream_result = self
+                .upstream
+                .request(fill_method, &rule, &upstream_queries, &headers_bytes, keyed_body.as_deref())
+                .await;
+        }
+        let upstream_resp = match upstream_result {
             Ok(resp) => resp,
             Err(e) => {
-            dedlog::err(Some(e.as_ref()), Some(request_str), ERR_MSG_UPSTREAM_ERROR_WHILE_CACHE_PROXYING);
+            dedlog::err("cache-controller", Some(e.as_ref()), Some(&request_line.to_string()), ERR_MSG_UPSTREAM_ERROR_WHILE_CACHE_PROXYING);
+                let e = match flight {
+                    Some(flight) => {
+                        let shared = Arc::new(e);
+                        flight.publish(Outcome::Failed(shared.clone()));
+                        anyhow::Error::new(SharedError(shared))
+                    }
+                    None => e,
+                };
+                if let Some(stale) = self.serve_stale_on_error(&rule, &request_entry, request_headers) {
+                    rollout(RolloutResult::Hit);
+                    return Ok((stale, true, false, cache_key));
+                }
+                rollout(RolloutResult::Error);
                 return Err(CacheError::Other(e));
             }
         };
 
+        drop(fill_permit);
+        headers_bytes.truncate(whitelisted_headers);
+
+        let model_resp = Arc::new(into_model_response(upstream_resp));
         let mut refreshed_at = 0i64;
-        if upstream_resp.status == 200 {
-            let model_response = ModelResponse {
-                status: upstream_resp.status,
-                headers: upstream_resp.headers.clone(),
-                body: upstream_resp.body.clone(),
+        let mut filled = None;
+        if model_resp.status == 200 {
+            filled = Some(request_entry.clone());
+            rollout(RolloutResult::Miss);
+        } else {
+            self.log_on_err_status_code(model_resp.status, request_line);
+            if model_resp.status >= 500 {
+                if let Some(stale) = self.serve_stale_on_error(&rule, &request_entry, request_headers) {
+                    if let Some(flight) = flight {
+                        flight.publish(Outcome::Answered { response: model_resp, stored: false });
+                    }
+                    rollout(RolloutResult::Hit);
+                    return Ok((stale, true, false, cache_key));
+                }
+                rollout(RolloutResult::Error);
+            } else {
+                rollout(RolloutResult::Miss);
+            }
+        }
+
+        // 404 and 5xx answers are stored too for a rule with `negative_ttl`, for that long.
+        let negative = rule.cache_value.negative_ttl.is_some() && freshness::is_negative_status(model_resp.status);
+        let oversize = (model_resp.status == 200 || negative) && exceeds_max_body_size(&rule, self.cfg.storage(), &model_resp);
+        if oversize {
+            metrics::inc_rejected_oversize();
+            capture::record(cache_key, Op::Set, "oversize: not stored");
+        } else if model_resp.status == 200 || negative {
+            request_entry.set_payload_with_body(&queries_bytes, &headers_bytes, keyed_body.as_deref(), &model_resp);
+            let input = KeyInput {
+                path: path_bytes,
+                queries: &queries_bytes,
+                headers: &headers_bytes,
+                request_headers,
             };
+            if !tombstoned && is_admitted_by_hook(&rule, &input, &model_resp) && self.cache.set(request_entry) {
+                refreshed_at = time::unix_nano();
+            }
+        }
+        if let Some(flight) = flight {
+            flight.publish(Outcome::Answered { response: model_resp.clone(), stored: refreshed_at != 0 });
+        }
 
-            request_entry.set_payload(&queries_bytes, &headers_bytes, &model_response);
+        let mut response = renderer::write_from_response(&model_resp, refreshed_at, self.diagnostics(XCache::Miss, cache_key));
+        // The ETag a later hit answers with, so the client can revalidate with it.
+        if let Some(filled) = filled {
+            renderer::set_etag(&mut response, &filled);
+        }
 
-            if self.cache.set(request_entry) {
-                refreshed_at = time::unix_nano();
+        Ok((response, false, false, cache_key))
+    }
+
+    /// Answers a miss that waited for the fill of a concurrent miss of its key: from the entry
+    /// the fill stored, from the origin's answer when it was not stored, and as the fill failed
+    /// otherwise. A stale entry is served on failures as it would be to the leader.
+    fn respond_collapsed(
+        &self,
+        outcome: &Outcome,
+        rule: &Rule,
+        request_entry: &Entry,
+        request_headers: &[(&str, &str)],
+        rollout: impl Fn(RolloutResult),
+    ) -> Result<(Response, bool, bool, u64), CacheError> {
+        let cache_key = request_entry.key();
+        let response = match outcome {
+            Outcome::Answered { response, .. } => response,
+            Outcome::Failed(err) => {
+                if let Some(stale) = self.serve_stale_on_error(rule, request_entry, request_headers) {
+                    rollout(RolloutResult::Hit);
+                    return Ok((stale, true, false, cache_key));
+                }
+                rollout(RolloutResult::Error);
+                return Err(CacheError::Other(anyhow::Error::new(SharedError(err.clone()))));
+            }
+        };
+        if matches!(outcome, Outcome::Answered { stored: true, .. }) {
+            if let (Some(entry), true) = self.cache.get_variant(request_entry, request_headers) {
+                let diagnostics = self.diagnostics(XCache::Miss, cache_key);
+                if let Ok(mut rendered) = renderer::write_from_entry(&entry, request_headers, diagnostics) {
+                    self.set_downstream_ttl(&mut rendered, rule, &entry, false);
+                    rollout(RolloutResult::Miss);
+                    return Ok((rendered, false, false, cache_key));
+                }
             }
+        }
+        // An answer varying on request headers is for the leader's variant, which may not be
+        // this request's.
+        if vary::parse(&response.headers).is_some() {
+            return Err(CacheError::NeedRetryThroughProxy);
+        }
+        if response.status >= 500 {
+            if let Some(stale) = self.serve_stale_on_error(rule, request_entry, request_headers) {
+                rollout(RolloutResult::Hit);
+                return Ok((stale, true, false, cache_key));
+            }
+            rollout(RolloutResult::Error);
         } else {
-            self.log_on_err_status_code(upstream_resp.status, request_str);
+            rollout(RolloutResult::Miss);
         }
+        Ok((renderer::write_from_response(response, 0, self.diagnostics(XCache::Miss, cache_key)), false, false, cache_key))
+    }
+
+    /// Counts a request to a path no rule matches (see `controller::unmatched`).
+    fn count_unmatched(&self, path_bytes: &[u8]) {
+        let warn_rps = self.cfg.api().and_then(|api| api.unmatched_warn_rps);
+        unmatched::unmatched().record(&String::from_utf8_lossy(path_bytes), warn_rps);
+    }
 
-        let model_resp = ModelResponse {
-            status: upstream_resp.status,
-            headers: upstream_resp.headers.clone(),
-            body: upstream_resp.body.clone(),
+    /// Removes the entry a GET of the request would be served: that of its key or, for a
+    /// response varying on request headers, of the request's variant. Answers 404 when there
+    /// is none and proxies PURGE on paths without a rule.
+    fn purge(
+        &self,
+        path_bytes: &[u8],
+        query_str: &str,
+        request_headers: &[(&str, &str)],
+    ) -> Result<(Response, bool, bool, u64), CacheError> {
+        let CacheRequest { entry, .. } = resolve_cache_request(&self.cfg, path_bytes, query_str, request_headers, "GET", None)?;
+        let stored = |entry: &Entry| self.cache.get_by_key(entry.key()).0.filter(|stored| stored.is_the_same_fingerprint(entry));
+        let target = match stored(&entry).and_then(|stored| stored.variant()) {
+            Some(variant) if !variant.matches(request_headers) => entry.variant_of_key(&variant.of_request(request_headers)),
+            _ => entry,
         };
-        let response = renderer::write_from_response(&model_resp, refreshed_at);
+        let freed = stored(&target).and_then(|stored| purge::purge_entry(self.cache.as_ref(), &stored));
+        let resp = PurgeResponse {
+            success: freed.is_some(),
+            removed: freed.map_or(0, |_| 1),
+            freed_bytes: freed.unwrap_or_default(),
+            ..Default::default()
+        };
+        let status = if freed.is_some() { StatusCode::OK } else { StatusCode::NOT_FOUND };
+        Ok((resp.into_response(status), false, false, target.key()))
+    }
 
-        Ok((response, false, false, cache_key))
+    /// Proxies a request that is not served from the cache, or answers it with the configured
+    /// status without touching the origin when `upstream.proxy_enabled` is false.
+    async fn proxy_or_refuse(
+        &self,
+        path: &str,
+        query_str: &str,
+        request_headers: &[(&str, &str)],
+        method: &str,
+        body: Option<&[u8]>,
+        request_line: RequestLine<'_>,
+    ) -> Result<(Response, bool, bool, u64), CacheError> {
+        if !self.cfg.is_proxy_enabled() {
+            return Ok((self.respond_proxy_disabled(), false, false, 0));
+        }
+
+        self.counters.inc_proxied();
+        metrics::inc_proxied(1);
+        self.handle_through_proxy(path, query_str, request_headers, method, body, request_line)
+            .await
     }
 
     /// Handles request through proxy (proxy mode).
@@ -365,9 +963,10 @@ impl CacheProxyController {
         &self,
         path: &str,
         query_str: &str,
-        request_headers: &[(String, String)],
+        request_headers: &[(&str, &str)],
         method: &str,
-        request_str: &str,
+        body: Option<&[u8]>,
+        request_line: RequestLine<'_>,
     ) -> Result<(Response, bool, bool, u64), CacheError> {
         let upstream_resp = match self
             .upstream
@@ -375,43 +974,102 @@ impl CacheProxyController {
                 method,
                 path,
                 query_str,
-                request_headers,
-                None,
+                &to_owned_headers(request_headers),
+                body,
             )
             .await
         {
             Ok(resp) => resp,
             Err(e) => {
                 // Use dedlog for error logging
-                dedlog::err(Some(e.as_ref()), Some(request_str), ERR_MSG_UPSTREAM_ERROR_WHILE_PROXYING);
+                dedlog::err("cache-controller", Some(e.as_ref()), Some(&request_line.to_string()), ERR_MSG_UPSTREAM_ERROR_WHILE_PROXYING);
                 return Err(CacheError::Other(e));
             }
         };
 
-        self.log_on_err_status_code(upstream_resp.status, request_str);
+        self.log_on_err_status_code(upstream_resp.status, request_line);
 
-        let model_resp = ModelResponse {
-            status: upstream_resp.status,
-            headers: upstream_resp.headers.clone(),
-            body: upstream_resp.body.clone(),
-        };
-        let response = renderer::write_from_response(&model_resp, 0);
+        let model_resp = into_model_response(upstream_resp);
+        let response = renderer::write_from_response(&model_resp, 0, Diagnostics { cache: XCache::Bypass, key: None });
         Ok((response, false, false, 0))
     }
 
+    /// Serves a stored entry for the request when the miss fill failed, as long as the rule
+    /// does not opt out and the entry is not older than `lifetime.max_stale_on_error` past its TTL.
+    /// Looking the entry up through storage also queues it for refresh when it is expired.
+    fn serve_stale_on_error(&self, rule: &Rule, request_entry: &Entry, request_headers: &[(&str, &str)]) -> Option<Response> {
+        if rule.stale_on_error == Some(false) {
+            return None;
+        }
+        let max_stale = self.cfg.lifetime().and_then(|l| l.max_stale_on_error)?;
+
+        let (stored, hit) = self.cache.get_variant(request_entry, request_headers);
+        let stored = stored.filter(|stored| hit && !stored.is_negative())?;
+        if stored.stale_for(&self.cfg) > max_stale {
+            return None;
+        }
+
+        let mut response = renderer::write_from_entry(&stored, &[], self.diagnostics(XCache::Stale, stored.key())).ok()?;
+        self.set_downstream_ttl(&mut response, rule, &stored, true);
+        response.headers_mut().insert(
+            cache_status::CACHE_STATUS_KEY,
+            HeaderValue::from_static(cache_status::STALE_ERROR),
+        );
+        Some(response)
+    }
+
+    /// Diagnostic headers of a response about the entry of `key`, which is only told with
+    /// `api.debug_headers`.
+    fn diagnostics(&self, cache: XCache, key: u64) -> Diagnostics {
+        Diagnostics { cache, key: self.cfg.is_debug_headers().then_some(key) }
+    }
+
+    /// Announces the rule's `downstream_ttl` on a response rendered from `entry`.
+    fn set_downstream_ttl(&self, response: &mut Response, rule: &Rule, entry: &Entry, stale: bool) {
+        let policy = rule.cache_value.downstream_ttl;
+        // A no-store response goes out with the origin's own Cache-Control.
+        if policy == DownstreamTtl::Off || entry.is_no_store() {
+            return;
+        }
+        let ttl = entry.origin_ttl().unwrap_or_else(|| RefreshParams::resolve(&self.cfg, rule).ttl);
+        renderer::set_downstream_ttl(response, policy, ttl, entry.age(), stale);
+    }
+
     /// Logs error on non-OK status codes.
-    fn log_on_err_status_code(&self, code: u16, request_str: &str) {
+    fn log_on_err_status_code(&self, code: u16, request_line: RequestLine<'_>) {
         if code >= 500 {
-            dedlog::err(None, Some(request_str), ERR_MSG_UPSTREAM_INTERNAL_ERROR);
-            ERRORED.fetch_add(1, Ordering::Relaxed);
+            dedlog::err("cache-controller", None, Some(&request_line.to_string()), ERR_MSG_UPSTREAM_INTERNAL_ERROR);
+            self.counters.inc_errored();
             metrics::inc_errors(1);
         }
     }
 
-    /// Returns 503 Service Unavailable response and logs the error.
-    fn respond_service_unavailable(&self, err: &dyn std::error::Error, request_str: &str) -> Response {
+    /// Builds the response for a request refused because proxying is disabled.
+    fn respond_proxy_disabled(&self) -> Response {
+        let status = StatusCode::from_u16(self.cfg.proxy_disabled_status()).unwrap_or(StatusCode::NOT_FOUND);
+        let body = serde_json::json!({
+            "status": status.as_u16(),
+            "error": status.canonical_reason().unwrap_or_default(),
+            "message": ERR_MSG_PROXY_DISABLED,
+        })
+        .to_string();
+
+        Response::builder()
+            .status(status)
+            .header(axum::http::header::CONTENT_TYPE, "application/json")
+            .header("content-length", body.len())
+            .body(body.into())
+            .unwrap_or_else(|_| {
+                let mut resp = Response::new(Vec::new().into());
+                *resp.status_mut() = status;
+                resp
+            })
+    }
+
+    /// Answers with the error status (503, or 502 for oversized upstream bodies) and logs the error.
+    fn respond_error(&self, status: StatusCode, err: &dyn std::error::Error, request_line: RequestLine<'_>) -> Response {
         // Use dedlog for error logging
-        dedlog::err(Some(err), Some(request_str), ERR_MSG_INTERNAL_ERROR);
+        dedlog::err("cache-controller", Some(err), Some(&request_line.to_string()), ERR_MSG_INTERNAL_ERROR);
 
         let mut headers = HeaderMap::new();
         if let (Ok(name), Ok(value)) = (
@@ -425,10 +1083,13 @@ impl CacheProxyController {
             HeaderValue::from_static("application/json"),
         );
 
-        let body = crate::http::render::templates::UNAVAILABLE_RESPONSE_BODY;
+        let body = match status {
+            StatusCode::BAD_GATEWAY => crate::http::render::templates::BAD_GATEWAY_RESPONSE_BODY,
+            _ => crate::http::render::templates::UNAVAILABLE_RESPONSE_BODY,
+        };
 
         Response::builder()
-            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .status(status)
             .header("content-length", body.len())
             .body(body.to_vec().into())
             .map(|mut resp| {
@@ -436,7 +1097,7 @@ impl CacheProxyController {
                 resp
             })
             .unwrap_or_else(|e| {
-                dedlog::err(Some(&e), Some(request_str), "attempt to write service unavailable response failed");
+                dedlog::err("cache-controller", Some(&e), Some(&request_line.to_string()), "attempt to write error response failed");
                 Response::builder()
                     .status(StatusCode::INTERNAL_SERVER_ERROR)
                     .body(Vec::new().into())
@@ -444,11 +1105,138 @@ impl CacheProxyController {
             })
     }
 
+    /// Refuses a request repeating a query param with different values with 400 and an
+    /// RFC 9457 problem document.
+    fn respond_duplicate_query(&self, err: &DuplicateQueryError) -> Response {
+        let status = StatusCode::BAD_REQUEST;
+        metrics::inc_status_code(status.as_u16());
+
+        let body = serde_json::json!({
+            "type": "about:blank",
+            "title": status.canonical_reason().unwrap_or_default(),
+            "status": status.as_u16(),
+            "detail": err.to_string(),
+        })
+        .to_string();
+
+        Response::builder()
+            .status(status)
+            .header(axum::http::header::CONTENT_TYPE, "application/problem+json")
+            .header("content-length", body.len())
+            .body(body.into())
+            .unwrap()
+    }
+
+    /// Answers a POST whose body could not be read: 413 when it is over [`MAX_REQUEST_BODY`],
+    /// 400 otherwise.
+    fn respond_unreadable_body(&self, err: &axum::Error) -> Response {
+        let too_large = std::error::Error::source(err)
+            .is_some_and(|source| source.is::<http_body_util::LengthLimitError>());
+        let status = if too_large { StatusCode::PAYLOAD_TOO_LARGE } else { StatusCode::BAD_REQUEST };
+        metrics::inc_status_code(status.as_u16());
+
+        let body = serde_json::json!({
+            "type": "about:blank",
+            "title": status.canonical_reason().unwrap_or_default(),
+            "status": status.as_u16(),
+            "detail": if too_large {
+                format!("request body is over {} bytes", MAX_REQUEST_BODY)
+            } else {
+                format!("request body could not be read: {}", err)
+            },
+        })
+        .to_string();
+
+        Response::builder()
+            .status(status)
+            .header(axum::http::header::CONTENT_TYPE, "application/problem+json")
+            .header("content-length", body.len())
+            .body(body.into())
+            .unwrap()
+    }
+
+    /// Answers a request failing because the backend config is broken with 503 and an RFC 9457
+    /// problem document whose `error_code` tells it from transient upstream failures.
+    fn respond_misconfigured(&self, misconfig: &Misconfig, err: &CacheError, request_line: RequestLine<'_>) -> Response {
+        dedlog::err("cache-controller", Some(err), Some(&request_line.to_string()), ERR_MSG_UPSTREAM_MISCONFIGURED);
+
+        let status = StatusCode::SERVICE_UNAVAILABLE;
+        let body = serde_json::json!({
+            "type": "about:blank",
+            "title": status.canonical_reason().unwrap_or_default(),
+            "status": status.as_u16(),
+            "detail": format!("the upstream backend is misconfigured ({})", misconfig.kind.label()),
+            "error_code": ERROR_CODE_UPSTREAM_MISCONFIGURED,
+        })
+        .to_string();
+
+        Response::builder()
+            .status(status)
+            .header(axum::http::header::CONTENT_TYPE, "application/problem+json")
+            .header("x-error-reason", ERROR_CODE_UPSTREAM_MISCONFIGURED)
+            .header("content-length", body.len())
+            .body(body.into())
+            .unwrap()
+    }
+
+    /// Builds the response for a request refused to protect the origin: throttled by the `deny`
+    /// policy, shed during a brownout or refused for want of a fill slot. Answered with
+    /// `upstream.throttle_status` and `Retry-After`, and not counted as an error, so that
+    /// shedding does not feed the error rate a brownout may be engaged on.
+    fn respond_throttled(&self, reason: ThrottleReason, retry_after: Duration) -> Response {
+        if reason == ThrottleReason::Brownout {
+            metrics::inc_brownout_shed();
+        }
+        metrics::inc_requests_throttled(reason);
+        let status = StatusCode::from_u16(self.cfg.throttle_status()).unwrap_or(StatusCode::TOO_MANY_REQUESTS);
+        metrics::inc_status_code(status.as_u16());
+
+        let body = crate::http::render::templates::UNAVAILABLE_RESPONSE_BODY;
+        Response::builder()
+            .status(status)
+            .header(axum::http::header::CONTENT_TYPE, "application/json")
+            // Rounded up: a client retrying at once would only be refused again.
+            .header(axum::http::header::RETRY_AFTER, retry_after.as_secs_f64().ceil().max(1.0) as u64)
+            .header("x-ratelimit-reason", reason.header())
+            .header("x-error-reason", reason.label())
+            .header("content-length", body.len())
+            .body(body.to_vec().into())
+            .unwrap()
+    }
+
+    /// Refuses a request that looped back into this instance with 508 Loop Detected.
+    fn respond_loop_detected(&self, chain: &str, request_line: RequestLine<'_>) -> Response {
+        tracing::error!(
+            component = "cache-controller",
+            event = "loop_detected",
+            instance = loop_guard::instance_id(),
+            chain = %chain,
+            request = %dedlog::redacted(&request_line.to_string()),
+            "request looped back into the cache, check that upstream backends do not point at the cache itself"
+        );
+        self.counters.inc_errored();
+        metrics::inc_errors(1);
+        metrics::inc_status_code(StatusCode::LOOP_DETECTED.as_u16());
+
+        let body = crate::http::render::templates::LOOP_DETECTED_RESPONSE_BODY;
+        Response::builder()
+            .status(StatusCode::LOOP_DETECTED)
+            .header(axum::http::header::CONTENT_TYPE, "application/json")
+            .header("content-length", body.len())
+            .body(body.to_vec().into())
+            .unwrap()
+    }
+
     /// Runs metrics logger that periodically writes metrics to Prometheus.
     fn run_logger_metrics_writer(&self) {
         let cfg = self.cfg.clone();
         let cache = self.cache.clone();
         let shutdown_token = self.shutdown_token.clone();
+        let counters = self.counters.clone();
+        let upstream = self.upstream.clone();
+        let monitor = health::monitor();
+        let brownout = self.brownout.clone();
+        let reclaim = self.reclaim.clone();
 
         tokio::task::spawn(async move {
             let mut interval = interval(Duration::from_secs(5));
@@ -461,41 +1249,11 @@ impl CacheProxyController {
                         return;
                     }
                     _ = interval.tick() => {
-                        // Swap counters to get values and reset them
-                        let total_num = TOTAL.swap(0, Ordering::Relaxed);
-                        let hits_num = HITS.swap(0, Ordering::Relaxed);
-                        let misses_num = MISSES.swap(0, Ordering::Relaxed);
-                        let proxied_num = PROXIED.swap(0, Ordering::Relaxed);
-                        let errors_num = ERRORED.swap(0, Ordering::Relaxed);
-                        let total_duration_num = DURATION.swap(0, Ordering::Relaxed);
-                        let cache_duration_num = CACHE_DURATION.swap(0, Ordering::Relaxed);
-                        let proxy_duration_num = PROXY_DURATION.swap(0, Ordering::Relaxed);
-                        let error_duration_num = ERROR_DURATION.swap(0, Ordering::Relaxed);
-
-                        // Calculate averages
-                        let avg_duration = if total_num > 0 {
-                            total_duration_num as f64 / total_num as f64
-                        } else {
-                            0.0
-                        };
-
-                        let cache_avg_duration = if hits_num + misses_num > 0 {
-                            cache_duration_num as f64 / (hits_num + misses_num) as f64
-                        } else {
-                            0.0
-                        };
-
-                        let proxy_avg_duration = if proxied_num > 0 {
-                            proxy_duration_num as f64 / proxied_num as f64
-                        } else {
-                            0.0
-                        };
-
-                        let errors_avg_duration = if errors_num > 0 {
-                            error_duration_num as f64 / errors_num as f64
-                        } else {
-                            0.0
-                        };
+                        // Drain counters to get values and reset them
+                        let snapshot = counters.take_snapshot();
+                        let latencies = counters.take_latencies();
+                        let total_num = snapshot.total;
+                        let avg_duration = snapshot.avg_duration();
 
                         let now = time::now();
                         let elapsed = now.duration_since(prev).unwrap_or(Duration::from_secs(0));
@@ -510,6 +1268,14 @@ impl CacheProxyController {
                         // Get cache statistics
                         let (mem_usage, length) = cache.stat();
 
+                        let sample = health::Sample::collect(&cfg, &snapshot, cache.as_ref(), upstream.as_ref());
+                        monitor.observe(cfg.cache.health.as_ref(), &sample, Instant::now());
+                        let signals = brownout::Signals::collect(brownout.config(), &snapshot, cache.as_ref(), upstream.as_ref());
+                        brownout.observe(&signals, Instant::now());
+                        if reclaim.observe(rps, Instant::now()) {
+                            reclaim.reclaim("idle");
+                        }
+
                         // Set metrics
                         prom_metrics::set_backend_policy(actual_policy());
                         let lifetime_policy = LifetimePolicy::new_lifetime_policy(
@@ -525,21 +1291,18 @@ impl CacheProxyController {
                         // Update gauges (absolute values that change over time)
                         metrics::set_cache_length(length as u64);
                         metrics::set_cache_memory(mem_usage as u64);
-                        metrics::set_avg_response_time(avg_duration, cache_avg_duration, proxy_avg_duration, errors_avg_duration);
+                        metrics::set_avg_response_time(
+                            avg_duration,
+                            snapshot.cache_avg_duration(),
+                            snapshot.proxy_avg_duration(),
+                            snapshot.errors_avg_duration(),
+                        );
                         metrics::set_rps(rps);
                         
               
                         if cfg.is_enabled() {
-                            let hit_rate = if hits_num + misses_num > 0 {
-                                (hits_num as f64 / (hits_num + misses_num) as f64) * 100.0
-                            } else {
-                                0.0
-                            };
-                            let err_rate = if total_num > 0 {
-                                (errors_num as f64 / total_num as f64) * 100.0
-                            } else {
-                                0.0
-                            };
+                            let hit_rate = snapshot.hit_rate();
+                            let err_rate = snapshot.err_rate();
 
                             info!(
                                 target = "cache-controller",
@@ -550,17 +1313,22 @@ impl CacheProxyController {
                                 avg_duration = ?Duration::from_nanos(avg_duration as u64),
                                 hit_rate = hit_rate,
                                 err_rate = err_rate as i32,
-                                hits = hits_num,
-                                misses = misses_num,
-                                errored = errors_num,
+                                hits = snapshot.hits,
+                                misses = snapshot.misses,
+                                errored = snapshot.errored,
+                                hit_p50 = ?latencies.hit.p50,
+                                hit_p95 = ?latencies.hit.p95,
+                                hit_p99 = ?latencies.hit.p99,
+                                miss_p50 = ?latencies.miss.p50,
+                                miss_p95 = ?latencies.miss.p95,
+                                miss_p99 = ?latencies.miss.p99,
+                                proxy_p50 = ?latencies.proxy.p50,
+                                proxy_p95 = ?latencies.proxy.p95,
+                                proxy_p99 = ?latencies.proxy.p99,
                                 "ingress"
                             );
                         } else {
-                            let err_rate = if total_num > 0 {
-                                (errors_num as f64 / total_num as f64) * 100.0
-                            } else {
-                                0.0
-                            };
+                            let err_rate = snapshot.err_rate();
 
                             info!(
                                 target = "proxy-controller",
@@ -571,8 +1339,11 @@ impl CacheProxyController {
                                 avg_duration = ?Duration::from_nanos(avg_duration as u64),
                                 err_rate = err_rate as i32,
                                 total = total_num,
-                                proxied = proxied_num,
-                                errored = errors_num,
+                                proxied = snapshot.proxied,
+                                errored = snapshot.errored,
+                                proxy_p50 = ?latencies.proxy.p50,
+                                proxy_p95 = ?latencies.proxy.p95,
+                                proxy_p99 = ?latencies.proxy.p99,
                                 "ingress"
                             );
                         }
@@ -584,18 +1355,27 @@ impl CacheProxyController {
 }
 
 impl Controller for CacheProxyController {
-    fn add_route(&self, router: Router) -> Router {
+    fn describe(&self) -> Vec<Route> {
+        // The cache API itself rather than an admin endpoint: indexed in debug only.
         let controller = Arc::new(self.clone());
-        router.route(
+        let handler = move |request: axum::extract::Request| {
+            let controller = controller.clone();
+            let request_id = request
+                .headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            async move { dedlog::with_request_id(request_id, Self::index(State(controller), request)).await }
+        };
+        // Every method is taken: those the matching rule does not cache are proxied.
+        let mut route = Route::get(
             "/*path",
-            get({
-                let controller = controller.clone();
-                move |request: axum::extract::Request| {
-                    let controller = controller.clone();
-                    async move { Self::index(State(controller), request).await }
-                }
-            }),
+            "Serves requests through the cache, or proxies them when no rule matches",
+            handler.clone(),
         )
+        .hidden();
+        route.handler = route.handler.fallback(handler);
+        vec![route]
     }
 }
 
@@ -606,6 +1386,11 @@ impl Clone for CacheProxyController {
             shutdown_token: self.shutdown_token.clone(),
             cache: self.cache.clone(),
             upstream: self.upstream.clone(),
+            counters: self.counters.clone(),
+            brownout: self.brownout.clone(),
+            reclaim: self.reclaim.clone(),
+            fill_limits: self.fill_limits.clone(),
+            collapser: self.collapser.clone(),
         }
     }
 }
@@ -0,0 +1,164 @@
+//! Per-controller request counters for the cache proxy controller.
+//!
+//! Counters accumulate between two ticks of the 5s metrics writer, which drains
+//! them via [`ControllerMetrics::take_snapshot`]. Prometheus counters live in
+//! `controller::metrics` and are updated separately in real time.
+//!
+//! Latencies of hits, misses and proxied requests are recorded once, into their
+//! histograms: the duration sums of a snapshot and the quantiles of
+//! [`ControllerMetrics::take_latencies`] are read from the same samples.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use crate::controller::latency::{Histogram, Quantiles};
+
+/// Request counters and accumulated durations (nanoseconds) of one controller instance.
+#[derive(Debug, Default)]
+pub struct ControllerMetrics {
+    total: AtomicI64,
+    hits: AtomicI64,
+    misses: AtomicI64,
+    proxied: AtomicI64,
+    errored: AtomicI64,
+    error_duration: AtomicI64,
+    hit_latency: Histogram,
+    miss_latency: Histogram,
+    proxy_latency: Histogram,
+}
+
+/// Values drained from [`ControllerMetrics`] for one writer interval.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    pub total: i64,
+    pub hits: i64,
+    pub misses: i64,
+    pub proxied: i64,
+    pub errored: i64,
+    pub duration: i64,
+    pub cache_duration: i64,
+    pub proxy_duration: i64,
+    pub error_duration: i64,
+}
+
+impl ControllerMetrics {
+    /// Creates zeroed counters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn inc_total(&self) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_hits(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_misses(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_proxied(&self) {
+        self.proxied.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_errored(&self) {
+        self.errored.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Accounts a request served through the cache path, as a hit or a miss.
+    pub fn add_cache_duration(&self, elapsed: i64, hit: bool) {
+        if hit {
+            self.hit_latency.record(elapsed);
+        } else {
+            self.miss_latency.record(elapsed);
+        }
+    }
+
+    /// Accounts a request served through the proxy path.
+    pub fn add_proxy_duration(&self, elapsed: i64) {
+        self.proxy_latency.record(elapsed);
+    }
+
+    /// Accounts a request that ended with an error response.
+    pub fn add_error_duration(&self, elapsed: i64) {
+        self.error_duration.fetch_add(elapsed, Ordering::Relaxed);
+    }
+
+    /// Returns the accumulated values and resets every counter to zero.
+    /// Each counter is swapped individually, so increments racing with the call
+    /// land either in this snapshot or in the next one, never in neither.
+    pub fn take_snapshot(&self) -> Snapshot {
+        let cache_duration = self.hit_latency.take_sum() + self.miss_latency.take_sum();
+        let proxy_duration = self.proxy_latency.take_sum();
+        let error_duration = self.error_duration.swap(0, Ordering::Relaxed);
+        Snapshot {
+            total: self.total.swap(0, Ordering::Relaxed),
+            hits: self.hits.swap(0, Ordering::Relaxed),
+            misses: self.misses.swap(0, Ordering::Relaxed),
+            proxied: self.proxied.swap(0, Ordering::Relaxed),
+            errored: self.errored.swap(0, Ordering::Relaxed),
+            duration: cache_duration + proxy_duration + error_duration,
+            cache_duration,
+            proxy_duration,
+            error_duration,
+        }
+    }
+
+    /// Returns the latency quantiles of hits, misses and proxied requests and resets them.
+    pub fn take_latencies(&self) -> Latencies {
+        Latencies {
+            hit: self.hit_latency.take_quantiles(),
+            miss: self.miss_latency.take_quantiles(),
+            proxy: self.proxy_latency.take_quantiles(),
+        }
+    }
+}
+
+/// Latency quantiles per outcome for one writer interval.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Latencies {
+    pub hit: Quantiles,
+    pub miss: Quantiles,
+    pub proxy: Quantiles,
+}
+
+impl Snapshot {
+    /// Average duration over all requests, ns.
+    pub fn avg_duration(&self) -> f64 {
+        avg(self.duration, self.total)
+    }
+
+    /// Average duration of cache hits and misses, ns.
+    pub fn cache_avg_duration(&self) -> f64 {
+        avg(self.cache_duration, self.hits + self.misses)
+    }
+
+    /// Average duration of proxied requests, ns.
+    pub fn proxy_avg_duration(&self) -> f64 {
+        avg(self.proxy_duration, self.proxied)
+    }
+
+    /// Average duration of errored requests, ns.
+    pub fn errors_avg_duration(&self) -> f64 {
+        avg(self.error_duration, self.errored)
+    }
+
+    /// Hit rate in percent of cache lookups.
+    pub fn hit_rate(&self) -> f64 {
+        avg(self.hits, self.hits + self.misses) * 100.0
+    }
+
+    /// Error rate in percent of all requests.
+    pub fn err_rate(&self) -> f64 {
+        avg(self.errored, self.total) * 100.0
+    }
+}
+
+fn avg(sum: i64, count: i64) -> f64 {
+    if count > 0 {
+        sum as f64 / count as f64
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::controller::cache_metrics::{ControllerMetrics, Snapshot};
+
+    /// Test that a snapshot returns accumulated values and resets every counter.
+    #[test]
+    fn test_snapshot_drains_counters() {
+        let m = ControllerMetrics::new();
+        for _ in 0..3 {
+            m.inc_total();
+        }
+        m.inc_hits();
+        m.inc_misses();
+        m.inc_proxied();
+        m.inc_errored();
+        m.add_cache_duration(100, true);
+        m.add_proxy_duration(40);
+        m.add_error_duration(10);
+
+        assert_eq!(
+            m.take_snapshot(),
+            Snapshot {
+                total: 3,
+                hits: 1,
+                misses: 1,
+                proxied: 1,
+                errored: 1,
+                duration: 150,
+                cache_duration: 100,
+                proxy_duration: 40,
+                error_duration: 10,
+            }
+        );
+        assert_eq!(m.take_snapshot(), Snapshot::default());
+    }
+
+    /// Test that two instances do not share state.
+    #[test]
+    fn test_instances_are_isolated() {
+        let a = ControllerMetrics::new();
+        let b = ControllerMetrics::new();
+        a.inc_total();
+        a.inc_hits();
+
+        assert_eq!(b.take_snapshot(), Snapshot::default());
+        assert_eq!(a.take_snapshot().hits, 1);
+    }
+
+    /// Test that averages and rates are derived from the snapshot and are zero without samples.
+    #[test]
+    fn test_snapshot_averages() {
+        assert_eq!(Snapshot::default().avg_duration(), 0.0);
+        assert_eq!(Snapshot::default().hit_rate(), 0.0);
+        assert_eq!(Snapshot::default().err_rate(), 0.0);
+
+        let s = Snapshot {
+            total: 4,
+            hits: 3,
+            misses: 1,
+            proxied: 2,
+            errored: 1,
+            duration: 400,
+            cache_duration: 200,
+            proxy_duration: 100,
+            error_duration: 30,
+        };
+        assert_eq!(s.avg_duration(), 100.0);
+        assert_eq!(s.cache_avg_duration(), 50.0);
+        assert_eq!(s.proxy_avg_duration(), 50.0);
+        assert_eq!(s.errors_avg_duration(), 30.0);
+        assert_eq!(s.hit_rate(), 75.0);
+        assert_eq!(s.err_rate(), 25.0);
+    }
+
+    /// Test that no increment is lost when snapshots are taken concurrently with writers.
+    #[test]
+    fn test_concurrent_increments_are_not_lost() {
+        const THREADS: usize = 4;
+        const PER_THREAD: i64 = 10_000;
+
+        let m = Arc::new(ControllerMetrics::new());
+        let writers: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let m = m.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..PER_THREAD {
+                        m.inc_total();
+                    }
+                })
+            })
+            .collect();
+
+        let mut seen = 0;
+        while writers.iter().any(|w| !w.is_finished()) {
+            seen += m.take_snapshot().total;
+        }
+        for w in writers {
+            w.join().unwrap();
+        }
+        seen += m.take_snapshot().total;
+
+        assert_eq!(seen, THREADS as i64 * PER_THREAD);
+    }
+}
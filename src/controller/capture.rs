@@ -0,0 +1,139 @@
+//! Debug capture controller.
+//!
+//! `POST /advcache/debug/capture` watches one key for a while, given as `{"key": <u64>}` or
+//! as the request it is built from, `{"path": "/api/v1/user", "params": {"user[id]": "1"},
+//! "headers": {...}}`, with an optional `"duration"` (`"60s"` by default, at most `10m`).
+//! `GET /advcache/debug/capture/{id}` returns the operations captured on it so far.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::config::{duration, Config};
+use crate::controller::cache::{resolve_cache_request, CacheError};
+use crate::db::capture::{self, WatchError};
+use crate::http::{Controller, Route};
+
+const DEFAULT_DURATION: Duration = Duration::from_secs(60);
+
+/// Capture request body: a key, or the path and params of a request for it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CaptureRequest {
+    key: Option<u64>,
+    path: Option<String>,
+    #[serde(default)]
+    params: BTreeMap<String, String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    duration: Option<String>,
+}
+
+/// DebugCaptureController starts and shows time-boxed captures of single keys.
+pub struct DebugCaptureController {
+    cfg: Arc<Config>,
+}
+
+impl DebugCaptureController {
+    /// Creates a new debug capture controller.
+    pub fn new(cfg: Config) -> Self {
+        Self { cfg: Arc::new(cfg) }
+    }
+
+    fn json(status: StatusCode, body: String) -> Response {
+        (status, [("content-type", "application/json; charset=utf-8")], body).into_response()
+    }
+
+    fn error(status: StatusCode, error: impl std::fmt::Display) -> Response {
+        Self::json(status, serde_json::json!({ "error": error.to_string() }).to_string())
+    }
+
+    /// Starts a capture.
+    async fn start(cfg: Arc<Config>, body: String) -> Response {
+        let req: CaptureRequest = match serde_json::from_str(&body) {
+            Ok(req) => req,
+            Err(e) => return Self::error(StatusCode::BAD_REQUEST, format_args!("invalid capture request: {}", e)),
+        };
+        let window = match req.duration.as_deref().map(|d| duration::parse(d, true)).transpose() {
+            Ok(window) => window.unwrap_or(DEFAULT_DURATION),
+            Err(e) => return Self::error(StatusCode::BAD_REQUEST, e),
+        };
+        if window.is_zero() || window > capture::MAX_DURATION {
+            return Self::error(
+                StatusCode::BAD_REQUEST,
+                format_args!("duration must be above zero and at most {}", humantime::format_duration(capture::MAX_DURATION)),
+            );
+        }
+
+        let (key, path) = match (req.key, req.path) {
+            (Some(key), None) => (key, None),
+            (None, Some(path)) => match Self::resolve_key(&cfg, &path, &req.params, &req.headers) {
+                Ok(key) => (key, Some(path)),
+                Err(CacheError::NeedRetryThroughProxy) => {
+                    return Self::error(StatusCode::NOT_FOUND, format_args!("no rule configured for path {:?}", path))
+                }
+                Err(e) => return Self::error(StatusCode::BAD_REQUEST, e),
+            },
+            _ => return Self::error(StatusCode::BAD_REQUEST, "either key or path is required"),
+        };
+
+        match capture::watchlist().watch(key, path, window) {
+            Ok(capture) => Self::json(StatusCode::OK, serde_json::to_string(&capture.report()).unwrap_or_default()),
+            Err(e @ WatchError::AlreadyWatched { .. }) => Self::error(StatusCode::CONFLICT, e),
+            Err(e @ WatchError::Full) => Self::error(StatusCode::TOO_MANY_REQUESTS, e),
+        }
+    }
+
+    /// Builds the key of a request for `path`, as the cache controller does.
+    fn resolve_key(
+        cfg: &Config,
+        path: &str,
+        params: &BTreeMap<String, String>,
+        headers: &BTreeMap<String, String>,
+    ) -> Result<u64, CacheError> {
+        let query = params
+            .iter()
+            .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        resolve_cache_request(cfg, path.as_bytes(), &query, &headers, "GET", None).map(|resolved| resolved.entry.key())
+    }
+
+    /// Shows a running or recent capture.
+    async fn show(id: u64) -> Response {
+        match capture::watchlist().capture(id) {
+            Some(capture) => Self::json(StatusCode::OK, serde_json::to_string(&capture.report()).unwrap_or_default()),
+            None => Self::error(StatusCode::NOT_FOUND, format_args!("unknown capture {}", id)),
+        }
+    }
+}
+
+impl Controller for DebugCaptureController {
+    fn describe(&self) -> Vec<Route> {
+        let cfg = self.cfg.clone();
+        vec![
+            Route::post(
+                "/advcache/debug/capture",
+                "Captures every operation on one key for a while",
+                move |body: String| {
+                    let cfg = cfg.clone();
+                    async move { Self::start(cfg, body).await }
+                },
+            )
+            .mutating(),
+            Route::get(
+                "/advcache/debug/capture/:id",
+                "Operations captured on a watched key",
+                |Path(id): Path<u64>| Self::show(id),
+            ),
+        ]
+    }
+}
@@ -1,27 +1,39 @@
 //! Cache clear controller.
 
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::get,
-    Router,
+    extract::{ConnectInfo, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
 };
+use parking_lot::Mutex;
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::sync::Mutex;
+use xxhash_rust::xxh3::xxh3_64;
 
 use crate::config::{Config, ConfigTrait};
-use crate::http::Controller;
+use crate::http::{Controller, Route};
+use crate::db::jobs::{Outcome, ShardJob};
+use crate::db::walks::OP_CLEAR_RULE;
 use crate::db::Storage;
+use crate::middleware::audit_middleware::Affected;
+use crate::model::Entry;
 use crate::time;
+use crate::upstream::peers::{PeerResult, Peers};
 
 /// Query parameters for clear endpoint.
 #[derive(Deserialize)]
 struct ClearQuery {
     token: Option<String>,
+    /// Path of the rule whose entries alone are cleared.
+    rule: Option<String>,
+    #[serde(rename = "_propagate")]
+    propagate: Option<String>,
 }
 
 /// Token response structure.
@@ -37,151 +49,351 @@ struct TokenResponse {
 struct ClearStatusResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     cleared: Option<bool>,
+    /// Number of cleared entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    items: Option<i64>,
+    /// Weight of the cleared entries; released in the background, see `/advcache/clear/status`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<i64>,
+    /// Id of the job clearing a rule, see `/advcache/jobs/{id}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
+    /// Outcome of the clear forwarded to each of `upstream.peers`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    peers: Vec<PeerResult>,
+}
+
+/// Release progress of cleared entries.
+#[derive(Debug, Serialize)]
+struct ReleaseStatusResponse {
+    /// Bytes of cleared entries still waiting to be freed.
+    #[serde(rename = "pendingBytes")]
+    pending_bytes: i64,
+}
+
+/// Clear tokens kept at a time; issuing one more drops the one expiring first.
+const MAX_CLEAR_TOKENS: usize = 64;
+
+/// How long a clear token is valid when `api.clear_token_ttl` is unset.
+const DEFAULT_CLEAR_TOKEN_TTL: Duration = Duration::from_secs(60);
+
+/// Who asked for a clear token: a token is only accepted from the one it was issued to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Principal {
+    /// Hash of the `Authorization` header, when one is sent.
+    Credentials(u64),
+    /// Address of the client otherwise.
+    Addr(IpAddr),
+    /// Neither is known, e.g. for a router served without connection info.
+    Anonymous,
+}
+
+impl Principal {
+    pub(crate) fn of(headers: &HeaderMap, addr: Option<SocketAddr>) -> Self {
+        if let Some(credentials) = headers.get(header::AUTHORIZATION) {
+            return Principal::Credentials(xxh3_64(credentials.as_bytes()));
+        }
+        match addr {
+            Some(addr) => Principal::Addr(addr.ip()),
+            None => Principal::Anonymous,
+        }
+    }
+}
+
+impl fmt::Display for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Principal::Credentials(hash) => write!(f, "credentials:{:016x}", hash),
+            Principal::Addr(ip) => write!(f, "addr:{}", ip),
+            Principal::Anonymous => f.write_str("anonymous"),
+        }
+    }
+}
+
+/// Why a clear token was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub(crate) enum TokenError {
+    #[error("unknown or already used token")]
+    Unknown,
+    #[error("expired token")]
+    Expired,
+    #[error("token was issued to another client")]
+    OtherPrincipal,
+}
+
+struct IssuedToken {
+    token: String,
+    principal: Principal,
+    expires: SystemTime,
+}
+
+/// Clear tokens issued and not used yet, at most [`MAX_CLEAR_TOKENS`].
+pub(crate) struct TokenStore {
+    ttl: Duration,
+    tokens: Vec<IssuedToken>,
+}
+
+impl TokenStore {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self { ttl, tokens: Vec::new() }
+    }
+
+    /// Issues a new token to the principal, valid for the TTL from `now`.
+    pub(crate) fn issue(&mut self, principal: Principal, now: SystemTime) -> (String, SystemTime) {
+        self.tokens.retain(|t| now < t.expires);
+        if self.tokens.len() >= MAX_CLEAR_TOKENS {
+            if let Some(first) = self.tokens.iter().enumerate().min_by_key(|(_, t)| t.expires).map(|(i, _)| i) {
+                self.tokens.swap_remove(first);
+            }
+        }
+
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = hex::encode(bytes);
+        let expires = now + self.ttl;
+        self.tokens.push(IssuedToken { token: token.clone(), principal, expires });
+        (token, expires)
+    }
+
+    /// Takes the token out of the store, so it is used at most once whatever the outcome.
+    pub(crate) fn consume(&mut self, token: &str, principal: Principal, now: SystemTime) -> Result<(), TokenError> {
+        let pos = self.tokens.iter().position(|t| t.token == token).ok_or(TokenError::Unknown)?;
+        let issued = self.tokens.swap_remove(pos);
+        if now >= issued.expires {
+            return Err(TokenError::Expired);
+        }
+        if issued.principal != principal {
+            return Err(TokenError::OtherPrincipal);
+        }
+        Ok(())
+    }
+
+    /// Number of tokens kept.
+    #[allow(dead_code)]
+    pub(crate) fn len(&self) -> usize {
+        self.tokens.len()
+    }
+}
+
+/// Marks a clear in progress until dropped.
+pub(crate) struct Clearing<'a>(&'a AtomicBool);
+
+impl<'a> Clearing<'a> {
+    /// Starts a clear, unless one is already in progress.
+    pub(crate) fn start(flag: &'a AtomicBool) -> Option<Self> {
+        flag.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).ok()?;
+        Some(Self(flag))
+    }
+}
+
+impl Drop for Clearing<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
 }
 
 /// ClearController handles cache clearing with token-based security.
+///
+/// A token is valid for `api.clear_token_ttl` (60s by default), is used at most once and only
+/// from the client it was issued to: the same `Authorization` header, or the same address.
+/// A clear asked for while another one runs, peers included, is answered 409 and leaves the
+/// token usable for a retry. With `rule`, only the entries of that rule are removed, by an
+/// admin job whose progress `/advcache/jobs/{id}` reports.
 pub struct ClearController {
     db: Arc<dyn Storage>,
     cfg: Arc<Config>,
-    token_state: Arc<Mutex<TokenState>>,
-}
-
-struct TokenState {
-    token: Option<String>,
-    expires: SystemTime,
+    tokens: Arc<Mutex<TokenStore>>,
+    clearing: Arc<AtomicBool>,
+    peers: Option<Arc<Peers>>,
 }
 
 impl ClearController {
     /// Creates a new clear controller.
     pub fn new(cfg: Config, db: Arc<dyn Storage>) -> Self {
+        let ttl = cfg.api().and_then(|api| api.clear_token_ttl).unwrap_or(DEFAULT_CLEAR_TOKEN_TTL);
         Self {
             db,
+            peers: Peers::from_config(&cfg).map(Arc::new),
             cfg: Arc::new(cfg),
-            token_state: Arc::new(Mutex::new(TokenState {
-                token: None,
-                expires: SystemTime::UNIX_EPOCH,
-            })),
+            tokens: Arc::new(Mutex::new(TokenStore::new(ttl))),
+            clearing: Arc::new(AtomicBool::new(false)),
         }
     }
 
     /// Handles the clear request.
     async fn handle_clear(
         Query(params): Query<ClearQuery>,
+        headers: HeaderMap,
+        addr: Option<ConnectInfo<SocketAddr>>,
         State(controller): State<Arc<Self>>,
-    ) -> impl IntoResponse {
+    ) -> Response {
         let now = time::now();
-        let raw_token = params.token;
-
-        if let Some(token) = raw_token {
-            // Validate provided token
-            let mut state = controller.token_state.lock().await;
-
-            let valid =
-                state.token.as_ref().map(|t| t == &token).unwrap_or(false) && now < state.expires;
-
-            // Clear token after use
-            state.token = None;
-            state.expires = SystemTime::UNIX_EPOCH;
-
-            if !valid {
-                let resp = ClearStatusResponse {
-                    cleared: None,
-                    error: Some("invalid or expired token".to_string()),
-                };
-
-                return (
-                    StatusCode::FORBIDDEN,
-                    [("content-type", "application/json")],
-                    serde_json::to_string(&resp).unwrap_or_default(),
-                );
-            }
-
-            // Clear storage
-            controller.db.clear();
-
-            // Log the clear operation
-            if controller.cfg.is_prod() {
-                tracing::info!(
-                    component = "clear",
-                    token = %token,
-                    "storage cleared"
-                );
-            } else {
-                tracing::info!(component = "clear", "storage cleared");
-            }
+        let principal = Principal::of(&headers, addr.map(|ConnectInfo(addr)| addr));
 
-            let resp = ClearStatusResponse {
-                cleared: Some(true),
-                error: None,
+        let Some(token) = params.token else {
+            let (token, expires) = controller.tokens.lock().issue(principal, now);
+            let resp = TokenResponse {
+                token,
+                expires_at: expires.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64,
             };
-
-            (
+            return (
                 StatusCode::OK,
                 [("content-type", "application/json")],
                 serde_json::to_string(&resp).unwrap_or_default(),
             )
-        } else {
-            // Return or reuse token
-            let mut state = controller.token_state.lock().await;
-
-            if let Some(ref token) = state.token {
-                if now < state.expires {
-                    let expires_millis = state
-                        .expires
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis() as i64;
-
-                    let resp = TokenResponse {
-                        token: token.clone(),
-                        expires_at: expires_millis,
-                    };
-
-                    return (
-                        StatusCode::OK,
-                        [("content-type", "application/json")],
-                        serde_json::to_string(&resp).unwrap_or_default(),
-                    );
-                }
-            }
+                .into_response();
+        };
 
-            // Generate new token
-            let mut bytes = [0u8; 16];
-            rand::thread_rng().fill_bytes(&mut bytes);
-            let token = hex::encode(bytes);
-            let expires = now + Duration::from_secs(5 * 60);
+        if let Some(rule) = params.rule {
+            return controller.clear_rule(rule, &token, principal, now);
+        }
 
-            state.token = Some(token.clone());
-            state.expires = expires;
+        // Checked before the token is consumed, so the caller can retry with it
+        let Some(_clearing) = Clearing::start(&controller.clearing) else {
+            return error_response(StatusCode::CONFLICT, "clear already in progress".to_string()).into_response();
+        };
 
-            let expires_millis = expires.duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+        let consumed = controller.tokens.lock().consume(&token, principal, now);
+        if let Err(e) = consumed {
+            tracing::warn!(
+                component = "clear",
+                event = "clear_token_refused",
+                principal = %principal,
+                reason = %e,
+                "clear token refused"
+            );
+            return error_response(StatusCode::FORBIDDEN, e.to_string()).into_response();
+        }
 
-            let resp = TokenResponse {
-                token,
-                expires_at: expires_millis,
-            };
+        // Clear storage; memory is released in the background
+        let (bytes, items) = controller.db.clear();
 
-            (
-                StatusCode::OK,
-                [("content-type", "application/json")],
-                serde_json::to_string(&resp).unwrap_or_default(),
-            )
+        // Log the clear operation
+        if controller.cfg.is_prod() {
+            tracing::info!(
+                component = "clear",
+                token = %token,
+                principal = %principal,
+                items = items,
+                bytes = bytes,
+                "storage cleared"
+            );
+        } else {
+            tracing::info!(component = "clear", principal = %principal, items = items, bytes = bytes, "storage cleared");
         }
+
+        // Peers are cleared with tokens of their own, once this instance is.
+        let peers = match &controller.peers {
+            Some(peers) if Peers::should_propagate(&headers, params.propagate.as_deref()) => peers.clear(&headers).await,
+            _ => Vec::new(),
+        };
+
+        let resp = ClearStatusResponse {
+            cleared: Some(true),
+            items: Some(items),
+            bytes: Some(bytes),
+            job: None,
+            error: None,
+            peers,
+        };
+
+        (
+            StatusCode::OK,
+            Extension(Affected(items as u64)),
+            [("content-type", "application/json")],
+            serde_json::to_string(&resp).unwrap_or_default(),
+        )
+            .into_response()
+    }
+
+    /// Removes the entries of one rule by a job walking the shards, answered 202 with its id
+    /// before it runs. Not forwarded to peers.
+    fn clear_rule(&self, rule: String, token: &str, principal: Principal, now: SystemTime) -> Response {
+        // Checked before the token is consumed, so the caller can retry with it
+        if self.cfg.rule(&rule).is_none() {
+            return error_response(StatusCode::NOT_FOUND, format!("no rule for path {}", rule)).into_response();
+        }
+        let Some(jobs) = self.db.jobs() else {
+            return error_response(StatusCode::SERVICE_UNAVAILABLE, "storage does not run jobs".to_string()).into_response();
+        };
+        if let Err(e) = self.tokens.lock().consume(token, principal, now) {
+            tracing::warn!(
+                component = "clear",
+                event = "clear_token_refused",
+                principal = %principal,
+                reason = %e,
+                "clear token refused"
+            );
+            return error_response(StatusCode::FORBIDDEN, e.to_string()).into_response();
+        }
+
+        let path = rule.clone();
+        let of_rule = move |entry: &Entry| entry.rule().path.as_deref() == Some(path.as_str());
+        let remove = |db: &dyn Storage, entry: &Entry| match db.remove(entry) {
+            (_, true) => Outcome::Acted,
+            (_, false) => Outcome::Skipped,
+        };
+        let job = jobs.submit(self.db.clone(), ShardJob::new(OP_CLEAR_RULE, rule.clone(), of_rule, remove));
+        tracing::info!(component = "clear", principal = %principal, rule = %rule, job = job.id(), "rule clear started");
+
+        let resp = ClearStatusResponse {
+            cleared: None,
+            items: None,
+            bytes: None,
+            job: Some(job.id()),
+            error: None,
+            peers: Vec::new(),
+        };
+        (
+            StatusCode::ACCEPTED,
+            [("content-type", "application/json")],
+            serde_json::to_string(&resp).unwrap_or_default(),
+        )
+            .into_response()
+    }
+
+    /// Reports how much of the last clear is still being released.
+    async fn handle_status(State(controller): State<Arc<Self>>) -> impl IntoResponse {
+        let resp = ReleaseStatusResponse {
+            pending_bytes: controller.db.clear_pending_bytes(),
+        };
+        (
+            StatusCode::OK,
+            [("content-type", "application/json")],
+            serde_json::to_string(&resp).unwrap_or_default(),
+        )
     }
 }
 
+fn error_response(status: StatusCode, error: String) -> (StatusCode, [(&'static str, &'static str); 1], String) {
+    let resp = ClearStatusResponse {
+        cleared: None,
+        items: None,
+        bytes: None,
+        job: None,
+        error: Some(error),
+        peers: Vec::new(),
+    };
+    (status, [("content-type", "application/json")], serde_json::to_string(&resp).unwrap_or_default())
+}
+
 impl Controller for ClearController {
-    fn add_route(&self, router: Router) -> Router {
+    fn describe(&self) -> Vec<Route> {
         let controller = Arc::new(self.clone());
-        router.route(
-            "/advcache/clear",
-            get(move |query: Query<ClearQuery>| {
+        let status_controller = controller.clone();
+        vec![
+            Route::get("/advcache/clear", "Clears the cache, or the entries of one rule", move |query: Query<ClearQuery>, headers: HeaderMap, addr: Option<ConnectInfo<SocketAddr>>| {
                 let controller = controller.clone();
-                async move { Self::handle_clear(query, State(controller)).await }
+                async move { Self::handle_clear(query, headers, addr, State(controller)).await }
+            })
+            .destructive(),
+            Route::get("/advcache/clear/status", "Progress of the last clear", move || {
+                let controller = status_controller.clone();
+                async move { Self::handle_status(State(controller)).await }
             }),
-        )
+        ]
     }
 }
 
@@ -190,7 +402,9 @@ impl Clone for ClearController {
         Self {
             db: self.db.clone(),
             cfg: self.cfg.clone(),
-            token_state: self.token_state.clone(),
+            tokens: self.tokens.clone(),
+            clearing: self.clearing.clone(),
+            peers: self.peers.clone(),
         }
     }
 }
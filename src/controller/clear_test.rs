@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::atomic::AtomicBool;
+    use std::time::{Duration, SystemTime};
+
+    use axum::http::{header, HeaderMap, HeaderValue};
+
+    use crate::controller::clear::{Clearing, Principal, TokenError, TokenStore};
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + secs)
+    }
+
+    fn addr(ip: &str) -> Principal {
+        Principal::of(&HeaderMap::new(), Some(SocketAddr::new(ip.parse().unwrap(), 4000)))
+    }
+
+    /// Test that a token is accepted once, and refused when used again.
+    #[test]
+    fn test_token_is_single_use() {
+        let mut store = TokenStore::new(Duration::from_secs(60));
+        let client = addr("10.0.0.1");
+        let (token, expires) = store.issue(client, at(0));
+        assert_eq!(expires, at(60));
+
+        assert_eq!(store.consume(&token, client, at(1)), Ok(()));
+        assert_eq!(store.consume(&token, client, at(2)), Err(TokenError::Unknown));
+        assert_eq!(store.len(), 0);
+    }
+
+    /// Test that a token is refused once its TTL passed.
+    #[test]
+    fn test_token_expires() {
+        let mut store = TokenStore::new(Duration::from_secs(60));
+        let client = addr("10.0.0.1");
+        let (token, _) = store.issue(client, at(0));
+
+        assert_eq!(store.consume(&token, client, at(60)), Err(TokenError::Expired));
+        assert_eq!(store.consume(&token, client, at(1)), Err(TokenError::Unknown), "a refused token is used up");
+    }
+
+    /// Test that a token is only accepted from the principal it was issued to, and that the
+    /// `Authorization` header takes precedence over the address.
+    #[test]
+    fn test_token_bound_to_principal() {
+        let mut store = TokenStore::new(Duration::from_secs(60));
+        let (token, _) = store.issue(addr("10.0.0.1"), at(0));
+        assert_eq!(store.consume(&token, addr("10.0.0.2"), at(1)), Err(TokenError::OtherPrincipal));
+
+        let with = |credentials: &'static str, ip: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, HeaderValue::from_static(credentials));
+            Principal::of(&headers, Some(SocketAddr::new(ip.parse().unwrap(), 4000)))
+        };
+        let (token, _) = store.issue(with("Bearer admin", "10.0.0.1"), at(0));
+        assert_eq!(store.consume(&token, with("Bearer admin", "10.0.0.3"), at(1)), Ok(()));
+        let (token, _) = store.issue(with("Bearer admin", "10.0.0.1"), at(0));
+        assert_eq!(store.consume(&token, with("Bearer other", "10.0.0.1"), at(1)), Err(TokenError::OtherPrincipal));
+
+        assert_eq!(Principal::of(&HeaderMap::new(), None), Principal::Anonymous);
+    }
+
+    /// Test that the store keeps at most 64 tokens, dropping the ones expiring first.
+    #[test]
+    fn test_store_is_bounded() {
+        let mut store = TokenStore::new(Duration::from_secs(60));
+        let client = addr("10.0.0.1");
+        let tokens: Vec<String> = (0..100).map(|i| store.issue(client, at(i / 10)).0).collect();
+
+        assert_eq!(store.len(), 64);
+        assert_eq!(store.consume(&tokens[0], client, at(10)), Err(TokenError::Unknown));
+        assert_eq!(store.consume(&tokens[99], client, at(10)), Ok(()));
+
+        // Expired tokens are dropped on the next issue
+        store.issue(client, at(100));
+        assert_eq!(store.len(), 1);
+    }
+
+    /// Test that a second clear cannot start until the first one is done.
+    #[test]
+    fn test_one_clear_at_a_time() {
+        let flag = AtomicBool::new(false);
+        let first = Clearing::start(&flag).expect("no clear in progress");
+        assert!(Clearing::start(&flag).is_none());
+        drop(first);
+        assert!(Clearing::start(&flag).is_some());
+    }
+}
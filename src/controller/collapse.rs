@@ -0,0 +1,142 @@
+//! Request collapsing (singleflight) of concurrent misses on one key.
+//!
+//! The first miss of a key fetches it from the origin as the leader of a flight; misses of the
+//! same key arriving meanwhile join the flight and wait for its outcome instead of sending
+//! requests of their own. Once the leader stored the answer they are rendered from the fresh
+//! entry, otherwise from the answer itself; a failed fill is shared the same way.
+//!
+//! Followers wait as long as an upstream request may take (`backend.timeout`, plus
+//! `backend.body_timeout` when set) and fetch on their own after that, so a stuck leader does
+//! not hold them forever. A leader cancelled before it had an outcome (its client went away)
+//! wakes the followers, one of which leads a new flight.
+
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::sync::watch;
+
+use crate::config::{Config, ConfigTrait};
+use crate::controller::metrics;
+use crate::model::Response as ModelResponse;
+
+/// Upstream timeout when `backend.timeout` is unset, as the backend applies it.
+const DEFAULT_UPSTREAM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How the fill of a flight ended.
+pub enum Outcome {
+    /// The origin answered; `stored` tells whether the answer is in the cache now.
+    Answered { response: Arc<ModelResponse>, stored: bool },
+    /// The fill failed.
+    Failed(Arc<anyhow::Error>),
+}
+
+type Slot = Option<Arc<Outcome>>;
+
+/// Flights in progress, by key.
+pub struct Collapser {
+    flights: Mutex<HashMap<u64, (u64, watch::Receiver<Slot>)>>,
+    next_id: AtomicU64,
+    wait: Duration,
+}
+
+/// Role of a miss in the flight of its key.
+pub enum Join<'a> {
+    /// The miss fills the key and publishes how it went.
+    Leader(Flight<'a>),
+    /// The fill of the leader ended so.
+    Follower(Arc<Outcome>),
+    /// The leader did not finish within the wait: the miss fills on its own.
+    TimedOut,
+}
+
+/// The flight a leader fills. Dropped without [`Flight::publish`], it wakes the followers to
+/// retry.
+pub struct Flight<'a> {
+    collapser: &'a Collapser,
+    key: u64,
+    id: u64,
+    tx: watch::Sender<Slot>,
+}
+
+impl Flight<'_> {
+    /// Hands the outcome to the followers.
+    pub fn publish(self, outcome: Outcome) {
+        self.tx.send_replace(Some(Arc::new(outcome)));
+    }
+}
+
+impl Drop for Flight<'_> {
+    fn drop(&mut self) {
+        let mut flights = self.collapser.flights.lock();
+        if flights.get(&self.key).is_some_and(|(id, _)| *id == self.id) {
+            flights.remove(&self.key);
+        }
+    }
+}
+
+impl Collapser {
+    /// Creates a collapser whose followers wait as long as an upstream request of the config
+    /// may take.
+    pub fn new(cfg: &Config) -> Self {
+        let backend = cfg.upstream().and_then(|u| u.backend.as_ref());
+        let total = backend.and_then(|b| b.timeout).unwrap_or(DEFAULT_UPSTREAM_TIMEOUT);
+        let body = backend.and_then(|b| b.body_timeout).unwrap_or_default();
+        Self::with_wait(total + body)
+    }
+
+    pub fn with_wait(wait: Duration) -> Self {
+        Self { flights: Mutex::new(HashMap::new()), next_id: AtomicU64::new(0), wait }
+    }
+
+    /// Joins the flight of `key`, leading a new one when none is in progress.
+    pub async fn join(&self, key: u64) -> Join<'_> {
+        let deadline = tokio::time::Instant::now() + self.wait;
+        loop {
+            let mut rx = {
+                let mut flights = self.flights.lock();
+                match flights.get(&key) {
+                    Some((_, rx)) => rx.clone(),
+                    None => {
+                        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                        let (tx, rx) = watch::channel(None);
+                        flights.insert(key, (id, rx));
+                        return Join::Leader(Flight { collapser: self, key, id, tx });
+                    }
+                }
+            };
+            let outcome = match tokio::time::timeout_at(deadline, rx.wait_for(Option::is_some)).await {
+                Ok(Ok(slot)) => (*slot).clone(),
+                // The leader went away without an outcome: one of its followers leads next.
+                Ok(Err(_)) => continue,
+                Err(_) => return Join::TimedOut,
+            };
+            if let Some(outcome) = outcome {
+                metrics::inc_collapsed_requests();
+                return Join::Follower(outcome);
+            }
+        }
+    }
+}
+
+/// The error of a failed flight as a follower reports it: reads as the leader's, whose chain
+/// it carries for the checks looking into it (oversized answers, misconfiguration).
+#[derive(Debug)]
+pub struct SharedError(pub Arc<anyhow::Error>);
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl StdError for SharedError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        let leader: &(dyn StdError + 'static) = (*self.0).as_ref();
+        Some(leader)
+    }
+}
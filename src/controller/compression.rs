@@ -1,9 +1,9 @@
 //! HTTP compression controller.
 
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
+use axum::{http::StatusCode, response::IntoResponse};
 use serde::Serialize;
 
-use crate::http::Controller;
+use crate::http::{Controller, Route};
 use crate::http::{disable_compression, enable_compression, is_compression_enabled};
 
 /// Status response structure.
@@ -71,10 +71,11 @@ impl Default for HttpCompressionController {
 }
 
 impl Controller for HttpCompressionController {
-    fn add_route(&self, router: Router) -> Router {
-        router
-            .route("/advcache/http/compression", get(Self::get))
-            .route("/advcache/http/compression/on", get(Self::on))
-            .route("/advcache/http/compression/off", get(Self::off))
+    fn describe(&self) -> Vec<Route> {
+        vec![
+            Route::get("/advcache/http/compression", "Shows whether response compression is enabled", Self::get),
+            Route::get("/advcache/http/compression/on", "Enables response compression", Self::on).mutating(),
+            Route::get("/advcache/http/compression/off", "Disables response compression", Self::off).mutating(),
+        ]
     }
 }
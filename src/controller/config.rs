@@ -1,10 +1,16 @@
-//! Config display controller.
+//! Config display, diff and reload controllers.
 
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
 use std::sync::Arc;
 
+use crate::config::diff::ConfigDiff;
+use crate::config::reload::Reloader;
 use crate::config::Config;
-use crate::http::Controller;
+use crate::http::render::negotiate::negotiated;
+use crate::http::{Controller, Route};
 
 /// ShowConfigController displays the current configuration.
 pub struct ShowConfigController {
@@ -17,28 +23,107 @@ impl ShowConfigController {
         Self { cfg: Arc::new(cfg) }
     }
 
-    /// Handles the show config request.
-    async fn show_config(cfg: Arc<Config>) -> impl IntoResponse {
-        let json = serde_json::to_string(&*cfg)
-            .unwrap_or_else(|_| r#"{"error": "failed to serialize config"}"#.to_string());
-
-        (
-            StatusCode::OK,
-            [("content-type", "application/json; charset=utf-8")],
-            json,
-        )
+    /// Handles the show config request, as JSON or YAML by the `Accept` header.
+    async fn show_config(cfg: Arc<Config>, headers: HeaderMap) -> impl IntoResponse {
+        negotiated(&headers, StatusCode::OK, &cfg.effective())
     }
 }
 
 impl Controller for ShowConfigController {
-    fn add_route(&self, router: Router) -> Router {
+    fn describe(&self) -> Vec<Route> {
+        let cfg = self.cfg.clone();
+        vec![Route::get("/advcache/config", "Current config as JSON or YAML", move |headers: HeaderMap| {
+            let cfg = cfg.clone();
+            async move { Self::show_config(cfg, headers).await }
+        })]
+    }
+}
+
+/// ConfigDiffController compares a candidate YAML config against the current one.
+/// The candidate is parsed and processed in memory only; nothing is applied.
+pub struct ConfigDiffController {
+    cfg: Arc<Config>,
+}
+
+impl ConfigDiffController {
+    /// Creates a new config diff controller.
+    pub fn new(cfg: Config) -> Self {
+        Self { cfg: Arc::new(cfg) }
+    }
+
+    /// Handles the config diff request.
+    async fn diff(cfg: Arc<Config>, body: String) -> impl IntoResponse {
+        let (status, json) = match Config::from_yaml(&body) {
+            Ok(candidate) => (
+                StatusCode::OK,
+                serde_json::to_string(&ConfigDiff::between(&cfg.effective(), &candidate))
+                    .unwrap_or_else(|_| r#"{"error": "failed to serialize diff"}"#.to_string()),
+            ),
+            Err(err) => (
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({ "error": format!("{:#}", err) }).to_string(),
+            ),
+        };
+
+        (status, [("content-type", "application/json; charset=utf-8")], json)
+    }
+}
+
+impl Controller for ConfigDiffController {
+    fn describe(&self) -> Vec<Route> {
         let cfg = self.cfg.clone();
-        router.route(
-            "/advcache/config",
-            get(move || {
+        vec![Route::post(
+            "/advcache/config/diff",
+            "Diffs a candidate config against the current one without applying it",
+            move |body: String| {
                 let cfg = cfg.clone();
-                async move { Self::show_config(cfg).await }
-            }),
+                async move { Self::diff(cfg, body).await }
+            },
+        )]
+    }
+}
+
+/// ConfigReloadController re-reads the config file and applies its rules, lifetime and
+/// eviction sections, like SIGHUP does.
+pub struct ConfigReloadController {
+    reloader: Arc<Reloader>,
+}
+
+impl ConfigReloadController {
+    /// Creates a new config reload controller.
+    pub fn new(reloader: Arc<Reloader>) -> Self {
+        Self { reloader }
+    }
+
+    /// Handles the reload request; a config that fails to load is reported and not applied.
+    async fn reload(reloader: Arc<Reloader>) -> impl IntoResponse {
+        let (status, json) = match reloader.reload() {
+            Ok(report) => (
+                StatusCode::OK,
+                serde_json::to_string(&report)
+                    .unwrap_or_else(|_| r#"{"error": "failed to serialize reload report"}"#.to_string()),
+            ),
+            Err(err) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                serde_json::json!({ "error": format!("{:#}", err) }).to_string(),
+            ),
+        };
+
+        (status, [("content-type", "application/json; charset=utf-8")], json)
+    }
+}
+
+impl Controller for ConfigReloadController {
+    fn describe(&self) -> Vec<Route> {
+        let reloader = self.reloader.clone();
+        vec![Route::post(
+            "/advcache/config/reload",
+            "Re-reads the config file and applies its rules, lifetime and eviction settings",
+            move || {
+                let reloader = reloader.clone();
+                async move { Self::reload(reloader).await }
+            },
         )
+        .mutating()]
     }
 }
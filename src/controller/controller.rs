@@ -1,17 +1,102 @@
 // HTTP controller trait for route registration.
 
+use axum::handler::Handler;
+use axum::routing::{self, MethodRouter};
 use axum::Router;
 
+/// A route served by a controller: its handler and what the admin index
+/// (`GET /advcache/`) tells about it.
+pub struct Route {
+    pub path: String,
+    pub method: &'static str,
+    pub description: &'static str,
+    /// Answered only with credentials.
+    pub auth: bool,
+    /// Drops or rewrites cached data.
+    pub destructive: bool,
+    /// Changes the state of the instance; calls are recorded in the audit log.
+    pub mutating: bool,
+    /// Left out of the index unless debug endpoints are enabled (`env: debug`).
+    pub hidden: bool,
+    pub handler: MethodRouter,
+}
+
+impl Route {
+    /// GET route; `get` answers HEAD too.
+    pub fn get<H, T>(path: impl Into<String>, description: &'static str, handler: H) -> Self
+    where
+        H: Handler<T, ()>,
+        T: 'static,
+    {
+        Self::new(path.into(), "GET", description, routing::get(handler))
+    }
+
+    /// POST route.
+    pub fn post<H, T>(path: impl Into<String>, description: &'static str, handler: H) -> Self
+    where
+        H: Handler<T, ()>,
+        T: 'static,
+    {
+        Self::new(path.into(), "POST", description, routing::post(handler))
+    }
+
+    /// DELETE route.
+    pub fn delete<H, T>(path: impl Into<String>, description: &'static str, handler: H) -> Self
+    where
+        H: Handler<T, ()>,
+        T: 'static,
+    {
+        Self::new(path.into(), "DELETE", description, routing::delete(handler))
+    }
+
+    fn new(path: String, method: &'static str, description: &'static str, handler: MethodRouter) -> Self {
+        Self { path, method, description, auth: false, destructive: false, mutating: false, hidden: false, handler }
+    }
+
+    /// Marks the route as answered only with credentials.
+    pub fn auth(mut self, auth: bool) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Marks the route as dropping or rewriting cached data, which makes it mutating too.
+    pub fn destructive(mut self) -> Self {
+        self.destructive = true;
+        self.mutating = true;
+        self
+    }
+
+    /// Marks the route as changing the state of the instance.
+    pub fn mutating(mut self) -> Self {
+        self.mutating = true;
+        self
+    }
+
+    /// Leaves the route out of the index unless debug endpoints are enabled.
+    pub fn hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+}
+
 /// Trait for adding routes to the HTTP server.
 pub trait Controller: Send + Sync {
-    /// Adds routes to the router.
+    /// Routes served by the controller, registered by `add_route` and listed
+    /// by the admin index from the same description.
     ///
     /// Commonly may be represented as:
     /// ```rust
-    /// # use axum::{Router, routing::get};
+    /// # use advcache::http::Route;
     /// # async fn handler() -> &'static str { "ok" }
-    /// let router: Router<()> = Router::new().route("/path", get(handler));
-    /// # let _ = router;
+    /// let routes = vec![Route::get("/path", "Answers ok", handler)];
+    /// # let _ = routes;
     /// ```
-    fn add_route(&self, router: Router) -> Router;
+    fn describe(&self) -> Vec<Route>;
+
+    /// Adds routes to the router.
+    fn add_route(&self, router: Router) -> Router {
+        self.describe()
+            .into_iter()
+            .fold(router, |router, route| router.route(&route.path, route.handler))
+    }
 }
@@ -0,0 +1,123 @@
+//! Backend drain controller.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Serialize;
+
+use crate::http::{Controller, Route};
+use crate::upstream::health::HealthChecks;
+use crate::upstream::{drain, Upstream};
+
+/// State of a backend as shown by the admin API.
+#[derive(Debug, Serialize)]
+struct BackendStatus {
+    id: String,
+    alive: bool,
+    drained: bool,
+    /// What is wrong with the backend config, while requests fail for it.
+    config_error: Option<String>,
+    /// Results of the shallow (`healthcheck`) and deep (`health_synthetic`) checks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    health: Option<HealthChecks>,
+}
+
+impl BackendStatus {
+    fn of(backend: &dyn Upstream) -> Self {
+        Self {
+            id: backend.backend_id().to_string(),
+            alive: backend.is_alive(),
+            drained: backend.is_drained(),
+            config_error: backend.config_error().map(|misconfig| misconfig.to_string()),
+            health: backend.health_checks(),
+        }
+    }
+}
+
+/// BackendDrainController drains and undrains backends and shows their state.
+pub struct BackendDrainController {
+    backend: Arc<dyn Upstream>,
+    overrides: Option<PathBuf>,
+}
+
+impl BackendDrainController {
+    /// Creates a new drain controller; the state is persisted to `overrides` when set.
+    pub fn new(backend: Arc<dyn Upstream>, overrides: Option<String>) -> Self {
+        Self {
+            backend,
+            overrides: overrides.map(PathBuf::from),
+        }
+    }
+
+    fn json(status: StatusCode, body: String) -> axum::response::Response {
+        (status, [("content-type", "application/json; charset=utf-8")], body).into_response()
+    }
+
+    /// Shows the configured backends.
+    async fn list(controller: Arc<Self>) -> impl IntoResponse {
+        let backends = vec![BackendStatus::of(controller.backend.as_ref())];
+        Self::json(
+            StatusCode::OK,
+            serde_json::json!({ "backends": backends }).to_string(),
+        )
+    }
+
+    /// Drains or undrains the backend with the id.
+    async fn set(controller: Arc<Self>, id: String, drained: bool) -> impl IntoResponse {
+        let backend = controller.backend.as_ref();
+        if id != backend.backend_id() {
+            return Self::json(
+                StatusCode::NOT_FOUND,
+                serde_json::json!({ "error": format!("unknown backend {:?}", id) }).to_string(),
+            );
+        }
+        if let Err(e) = drain::set_drained(backend, drained, controller.overrides.as_deref()) {
+            return Self::json(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                serde_json::json!({ "error": format!("{:#}", e) }).to_string(),
+            );
+        }
+        Self::json(
+            StatusCode::OK,
+            serde_json::to_string(&BackendStatus::of(backend)).unwrap_or_default(),
+        )
+    }
+}
+
+impl Controller for BackendDrainController {
+    fn describe(&self) -> Vec<Route> {
+        let controller = Arc::new(self.clone());
+        let (list, drain, undrain) = (controller.clone(), controller.clone(), controller);
+        vec![
+            Route::get("/advcache/upstream/backends", "Lists upstream backends with their drain and health state", move || {
+                Self::list(list.clone())
+            }),
+            Route::post(
+                "/advcache/upstream/:backend_id/drain",
+                "Drains a backend for origin maintenance",
+                move |Path(id): Path<String>| Self::set(drain.clone(), id, true),
+            )
+            .mutating(),
+            Route::post(
+                "/advcache/upstream/:backend_id/undrain",
+                "Puts a drained backend back in rotation",
+                move |Path(id): Path<String>| Self::set(undrain.clone(), id, false),
+            )
+            .mutating(),
+        ]
+    }
+}
+
+impl Clone for BackendDrainController {
+    fn clone(&self) -> Self {
+        Self {
+            backend: self.backend.clone(),
+            overrides: self.overrides.clone(),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Recent errors controller.
+//!
+//! `/advcache/errors` serves the ring of recent errors (see [`crate::dedlog::ring`]) so an
+//! operator can see what is failing without access to the logs. Records carry the same
+//! sanitized and redacted text as the log lines.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::dedlog::ring::{ErrorRecord, ErrorRing};
+use crate::http::{Controller, Route};
+
+/// Records returned when `limit` is not given.
+const DEFAULT_LIMIT: usize = 50;
+
+/// Query parameters for errors endpoint.
+#[derive(Deserialize)]
+struct ErrorsQuery {
+    limit: Option<String>,
+    component: Option<String>,
+    /// Unix milliseconds.
+    since: Option<String>,
+}
+
+/// Errors response body.
+#[derive(Debug, Serialize)]
+struct ErrorsResponse {
+    count: usize,
+    errors: Vec<ErrorRecord>,
+}
+
+/// ErrorsController serves the most recent errors, newest first.
+pub struct ErrorsController {
+    ring: Arc<ErrorRing>,
+}
+
+impl ErrorsController {
+    /// Creates an errors controller reading the given ring.
+    pub fn new(ring: Arc<ErrorRing>) -> Self {
+        Self { ring }
+    }
+
+    /// Lists up to `limit` records, of `component` and seen since `since` when given.
+    async fn get(ring: Arc<ErrorRing>, Query(params): Query<ErrorsQuery>) -> Response {
+        let limit = match params.limit.as_deref().map(str::parse::<usize>) {
+            None => DEFAULT_LIMIT,
+            Some(Ok(n)) => n,
+            Some(Err(_)) => return bad_request("invalid 'limit' parameter"),
+        };
+        let since = match params.since.as_deref().map(str::parse::<i64>) {
+            None => None,
+            Some(Ok(ms)) => Some(ms),
+            Some(Err(_)) => return bad_request("invalid 'since' parameter, unix milliseconds expected"),
+        };
+
+        let errors = ring.recent(limit, params.component.as_deref(), since);
+        let resp = ErrorsResponse { count: errors.len(), errors };
+        (
+            StatusCode::OK,
+            [("content-type", "application/json; charset=utf-8")],
+            serde_json::to_string(&resp).unwrap_or_default(),
+        )
+            .into_response()
+    }
+}
+
+fn bad_request(msg: &'static str) -> Response {
+    (StatusCode::BAD_REQUEST, [("content-type", "text/plain")], msg).into_response()
+}
+
+impl Controller for ErrorsController {
+    fn describe(&self) -> Vec<Route> {
+        let ring = self.ring.clone();
+        vec![Route::get("/advcache/errors", "Recent errors, sanitized as logged", move |query: Query<ErrorsQuery>| {
+            Self::get(ring.clone(), query)
+        })]
+    }
+}
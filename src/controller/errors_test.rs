@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use tower::ServiceExt;
+
+    use crate::controller::errors::ErrorsController;
+    use crate::dedlog::ring::{ErrorRecord, ErrorRing};
+    use crate::http::Controller;
+
+    async fn get(router: &Router, uri: &str) -> (StatusCode, Vec<u8>) {
+        let req = Request::get(uri).body(Body::empty()).unwrap();
+        let resp = router.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    /// Test that the endpoint lists records newest first, honouring limit, component and since.
+    #[tokio::test]
+    async fn test_lists_recent_errors() {
+        let ring = Arc::new(ErrorRing::new(16));
+        for i in 0..60 {
+            ring.push(ErrorRecord {
+                timestamp: i * 10_000,
+                component: if i % 2 == 0 { "upstream" } else { "dump" }.to_string(),
+                class: format!("e{}", i),
+                message: None,
+                request: None,
+                request_id: (i == 59).then(|| "req-59".to_string()),
+                count: 1,
+            });
+        }
+        let router = ErrorsController::new(ring).add_route(Router::new());
+
+        let (status, body) = get(&router, "/advcache/errors").await;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["count"], 16, "bounded by the ring, under the default limit");
+        assert_eq!(body["errors"][0]["class"], "e59");
+        assert_eq!(body["errors"][0]["requestId"], "req-59");
+        assert_eq!(body["errors"][0]["count"], 1);
+        assert_eq!(body["errors"][15]["class"], "e44");
+
+        let (_, body) = get(&router, "/advcache/errors?limit=2&component=upstream&since=540000").await;
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["count"], 2);
+        assert_eq!(body["errors"][0]["class"], "e58");
+        assert_eq!(body["errors"][1]["class"], "e56");
+
+        let (status, _) = get(&router, "/advcache/errors?limit=many").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get(&router, "/advcache/errors?since=yesterday").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
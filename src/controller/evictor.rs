@@ -4,14 +4,12 @@ use axum::{
     extract::{Query, State},
     http::StatusCode,
     response::{IntoResponse, Response},
-    routing::get,
-    Router,
 };
 use serde::Deserialize;
 use std::sync::Arc;
 
 use crate::governor::Governor;
-use crate::http::Controller;
+use crate::http::{Controller, Route};
 use crate::db::SVC_EVICTOR;
 
 /// Query parameters for scale endpoint.
@@ -31,14 +29,17 @@ impl EvictionController {
         Self { orchestrator }
     }
 
-    /// Gets the current eviction configuration.
+    /// Gets the current eviction state and configuration.
     async fn get(State(controller): State<Arc<Self>>) -> impl IntoResponse {
-        match controller.orchestrator.cfg(SVC_EVICTOR) {
-            Ok(cfg) => {
-                let freq = cfg.get_freq();
+        match controller.orchestrator.status(SVC_EVICTOR) {
+            Ok(status) => {
+                let (cfg, freq) = (&status.cfg, status.cfg.get_freq());
                 let json = serde_json::json!({
+                    "name": status.name,
+                    "state": status.state.label(),
                     "enabled": cfg.is_enabled(),
                     "replicas": cfg.get_replicas(),
+                    "active_replicas": status.active_replicas,
                     "frequency": {
                         "limit_per_sec": freq.get_rate_limit(),
                         "interval_ns": freq.get_tick_freq().as_nanos(),
@@ -134,40 +135,32 @@ impl EvictionController {
 }
 
 impl Controller for EvictionController {
-    fn add_route(&self, router: Router) -> Router {
+    fn describe(&self) -> Vec<Route> {
         let controller1 = Arc::new(self.clone());
         let controller2 = Arc::new(self.clone());
         let controller3 = Arc::new(self.clone());
         let controller4 = Arc::new(self.clone());
-        router
-            .route(
-                "/advcache/eviction",
-                get(move || {
-                    let controller = controller1.clone();
-                    async move { Self::get(State(controller)).await }
-                }),
-            )
-            .route(
-                "/advcache/eviction/on",
-                get(move || {
-                    let controller = controller2.clone();
-                    async move { Self::on(State(controller)).await }
-                }),
-            )
-            .route(
-                "/advcache/eviction/off",
-                get(move || {
-                    let controller = controller3.clone();
-                    async move { Self::off(State(controller)).await }
-                }),
-            )
-            .route(
-                "/advcache/eviction/scale",
-                get(move |query: Query<ScaleQuery>| {
-                    let controller = controller4.clone();
-                    async move { Self::scale(query, State(controller)).await }
-                }),
-            )
+        vec![
+            Route::get("/advcache/eviction", "Shows the evictor state", move || {
+                let controller = controller1.clone();
+                async move { Self::get(State(controller)).await }
+            }),
+            Route::get("/advcache/eviction/on", "Enables the evictor", move || {
+                let controller = controller2.clone();
+                async move { Self::on(State(controller)).await }
+            })
+            .mutating(),
+            Route::get("/advcache/eviction/off", "Disables the evictor", move || {
+                let controller = controller3.clone();
+                async move { Self::off(State(controller)).await }
+            })
+            .mutating(),
+            Route::get("/advcache/eviction/scale", "Scales the evictor replicas", move |query: Query<ScaleQuery>| {
+                let controller = controller4.clone();
+                async move { Self::scale(query, State(controller)).await }
+            })
+            .mutating(),
+        ]
     }
 }
 
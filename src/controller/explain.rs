@@ -0,0 +1,337 @@
+//! Rule resolution explain controller.
+//!
+//! Answers "what would the cache do with this request" without calling upstream
+//! or touching storage: `GET /advcache/explain?method=GET&path=/api/v1/user&user[id]=1`.
+//! All query params except `method` and `path` form the hypothetical request's query,
+//! headers of the explain request itself are used as its headers.
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::config::{Config, ConfigTrait};
+use crate::controller::cache::{collect_request_headers, resolve_cache_request, CacheError};
+use crate::db::Storage;
+use crate::http::{Controller, Route};
+use crate::model::RefreshParams;
+use crate::upstream::{actual_policy, Policy};
+
+const METHOD_PARAM: &str = "method";
+const PATH_PARAM: &str = "path";
+
+const REASON_MATCHED: &str = "exact match on rule path";
+const REASON_NO_RULE: &str = "no rule configured for path, request is proxied";
+const REASON_METHOD: &str = "only GET requests are served through the cache";
+const REASON_BYPASS: &str = "cache is disabled (bypass), request is proxied";
+const REASON_NO_RULE_REFUSED: &str = "no rule configured for path, proxying is disabled so request is refused";
+const REASON_BYPASS_REFUSED: &str = "cache is disabled (bypass), proxying is disabled so request is refused";
+const REASON_OUT_OF_ROLLOUT: &str = "key is outside the rule's rollout_percent, request is proxied without storing";
+const REASON_DUPLICATE_QUERY: &str = "query param repeated with different values, request is rejected with 400 (duplicate_query: reject)";
+
+/// Explain response structure.
+#[derive(Debug, Serialize)]
+struct ExplainResponse {
+    request: ExplainRequest,
+    rule: ExplainRule,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<ExplainKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh: Option<ExplainRefresh>,
+    admission: ExplainAdmission,
+    bypass: ExplainBypass,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backend: Option<ExplainBackend>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExplainRequest {
+    method: String,
+    path: String,
+    query: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ExplainRule {
+    matched: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    reason: &'static str,
+}
+
+/// Normalized key components and the resulting hashes.
+#[derive(Debug, Serialize)]
+struct ExplainKey {
+    queries: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    key: u64,
+    fingerprint: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ExplainRefresh {
+    enabled: bool,
+    ttl: String,
+    beta: f64,
+    coefficient: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_stale_on_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExplainAdmission {
+    enabled: bool,
+    /// Admission (and its doorkeeper) only filters new keys once the admission memory limit is exceeded.
+    would_apply: bool,
+    doorkeeper: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ExplainBypass {
+    /// Cache disabled globally: every request goes straight to upstream.
+    global: bool,
+    /// Rule-level opt-out from serving stale entries on upstream errors.
+    stale_on_error_opt_out: bool,
+    /// Requests not served from the cache may be proxied to the origin.
+    proxy_enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ExplainBackend {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    url: String,
+    policy: &'static str,
+}
+
+/// ExplainController reports how a hypothetical request would be resolved.
+pub struct ExplainController {
+    cfg: Arc<Config>,
+    db: Arc<dyn Storage>,
+}
+
+impl ExplainController {
+    /// Creates a new explain controller.
+    pub fn new(cfg: Config, db: Arc<dyn Storage>) -> Self {
+        Self {
+            cfg: Arc::new(cfg),
+            db,
+        }
+    }
+
+    /// Handles the explain request.
+    async fn explain(
+        State(controller): State<Arc<Self>>,
+        request: axum::extract::Request,
+    ) -> Response {
+        let (method, path, query) = split_explain_query(request.uri().query().unwrap_or(""));
+        let Some(path) = path else {
+            return (StatusCode::BAD_REQUEST, "path query param is required").into_response();
+        };
+        let headers = collect_request_headers(request.headers());
+
+        let resp = match controller.build(method, path, query, &headers) {
+            Ok(resp) => resp,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        };
+        (
+            StatusCode::OK,
+            [("content-type", "application/json")],
+            serde_json::to_string(&resp).unwrap_or_default(),
+        )
+            .into_response()
+    }
+
+    /// Resolves the request through the cache controller code paths.
+    fn build(
+        &self,
+        method: String,
+        path: String,
+        query: String,
+        headers: &[(&str, &str)],
+    ) -> anyhow::Result<ExplainResponse> {
+        let cfg = &self.cfg;
+        let bypass = !cfg.is_enabled();
+        let proxy_enabled = cfg.is_proxy_enabled();
+
+        let admission_enabled = cfg
+            .admission()
+            .map(|a| a.is_enabled.load(Ordering::Relaxed))
+            .unwrap_or(false);
+        let would_apply = self.db.is_admission_active();
+        let admission = ExplainAdmission {
+            enabled: admission_enabled,
+            would_apply,
+            doorkeeper: would_apply,
+        };
+
+        let backend = cfg.upstream().and_then(|u| u.backend.as_ref()).map(|b| ExplainBackend {
+            id: b.id.clone(),
+            url: format!(
+                "{}://{}",
+                b.scheme.as_deref().unwrap_or("http"),
+                b.host.as_deref().unwrap_or_default()
+            ),
+            policy: match actual_policy() {
+                Policy::Await => "await",
+                Policy::Deny => "deny",
+            },
+        });
+
+        let mut resp = ExplainResponse {
+            request: ExplainRequest {
+                method,
+                path,
+                query,
+            },
+            rule: ExplainRule {
+                matched: false,
+                path: None,
+                reason: if proxy_enabled { REASON_NO_RULE } else { REASON_NO_RULE_REFUSED },
+            },
+            key: None,
+            refresh: None,
+            admission,
+            bypass: ExplainBypass {
+                global: bypass,
+                stale_on_error_opt_out: false,
+                proxy_enabled,
+            },
+            backend,
+        };
+
+        if !resp.request.method.eq_ignore_ascii_case("GET") {
+            resp.rule.reason = REASON_METHOD;
+            return Ok(resp);
+        }
+
+        let resolved = match resolve_cache_request(
+            cfg,
+            resp.request.path.as_bytes(),
+            &resp.request.query,
+            headers,
+            "GET",
+            None,
+        ) {
+            Ok(resolved) => resolved,
+            Err(CacheError::NeedRetryThroughProxy)
+            | Err(CacheError::OutOfRollout(_))
+            | Err(CacheError::Shed(_))
+            | Err(CacheError::FillCapped(_)) => return Ok(resp),
+            Err(CacheError::DuplicateQuery(_)) => {
+                // Rules match on the exact path.
+                resp.rule = ExplainRule {
+                    matched: true,
+                    path: Some(resp.request.path.clone()),
+                    reason: match (bypass, proxy_enabled) {
+                        (false, _) => REASON_DUPLICATE_QUERY,
+                        (true, true) => REASON_BYPASS,
+                        (true, false) => REASON_BYPASS_REFUSED,
+                    },
+                };
+                return Ok(resp);
+            }
+            Err(CacheError::Other(e)) => return Err(e),
+        };
+
+        let rule = &resolved.rule;
+        let in_rollout = rule.cache_value.rollout_percent.includes(resolved.entry.key());
+        resp.rule = ExplainRule {
+            matched: true,
+            path: rule.path.clone(),
+            reason: match (bypass, proxy_enabled) {
+                (false, _) if !in_rollout => REASON_OUT_OF_ROLLOUT,
+                (false, _) => REASON_MATCHED,
+                (true, true) => REASON_BYPASS,
+                (true, false) => REASON_BYPASS_REFUSED,
+            },
+        };
+
+        let entry = &resolved.entry;
+        resp.key = Some(ExplainKey {
+            queries: to_string_pairs(&resolved.queries),
+            headers: to_string_pairs(&resolved.headers),
+            key: entry.key(),
+            fingerprint: format!("{:016x}{:016x}", entry.fingerprint_hi(), entry.fingerprint_lo()),
+        });
+
+        let params = RefreshParams::resolve(cfg, rule);
+        let max_stale_on_error = cfg
+            .lifetime()
+            .and_then(|l| l.max_stale_on_error)
+            .filter(|_| rule.stale_on_error != Some(false));
+        resp.refresh = Some(ExplainRefresh {
+            enabled: params.enabled,
+            ttl: humantime::format_duration(params.ttl).to_string(),
+            beta: params.beta,
+            coefficient: params.coefficient,
+            max_stale_on_error: max_stale_on_error.map(|d| humantime::format_duration(d).to_string()),
+        });
+        resp.bypass.stale_on_error_opt_out = rule.stale_on_error == Some(false);
+
+        Ok(resp)
+    }
+}
+
+/// Splits the explain query into method (GET by default), path and the remaining raw query.
+fn split_explain_query(raw: &str) -> (String, Option<String>, String) {
+    let mut method = "GET".to_string();
+    let mut path = None;
+    let mut rest = Vec::new();
+
+    for pair in raw.split('&').filter(|p| !p.is_empty()) {
+        let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+        match k {
+            METHOD_PARAM => method = v.to_string(),
+            PATH_PARAM => {
+                path = Some(
+                    urlencoding::decode(v)
+                        .map(|p| p.into_owned())
+                        .unwrap_or_else(|_| v.to_string()),
+                )
+            }
+            _ => rest.push(pair),
+        }
+    }
+
+    (method, path, rest.join("&"))
+}
+
+fn to_string_pairs(pairs: &[(Vec<u8>, Vec<u8>)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(k, v)| {
+            (
+                String::from_utf8_lossy(k).into_owned(),
+                String::from_utf8_lossy(v).into_owned(),
+            )
+        })
+        .collect()
+}
+
+impl Controller for ExplainController {
+    fn describe(&self) -> Vec<Route> {
+        let controller = Arc::new(self.clone());
+        vec![Route::get(
+            "/advcache/explain",
+            "Explains rule matching and key building for a hypothetical request",
+            move |request: axum::extract::Request| {
+                let controller = controller.clone();
+                async move { Self::explain(State(controller), request).await }
+            },
+        )]
+    }
+}
+
+impl Clone for ExplainController {
+    fn clone(&self) -> Self {
+        Self {
+            cfg: self.cfg.clone(),
+            db: self.db.clone(),
+        }
+    }
+}
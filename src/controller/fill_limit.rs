@@ -0,0 +1,84 @@
+//! Per-rule cap on concurrent upstream fills (`cache_value.max_concurrent_fills`).
+//!
+//! Singleflight only merges misses of one key, so a burst of distinct keys of a rule (every
+//! page of a search, say) still reaches the origin at once. With a cap, misses past it wait up
+//! to `cache_value.max_fill_wait` for a fill of the same rule to finish and are refused with
+//! 503 after. Each rule has a semaphore of its own, so a capped rule never holds up the others.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::Rule;
+use crate::controller::metrics;
+
+/// Wait for a fill slot when `cache_value.max_fill_wait` is unset.
+pub const DEFAULT_MAX_FILL_WAIT: Duration = Duration::from_secs(1);
+
+struct Limit {
+    cap: usize,
+    semaphore: Arc<Semaphore>,
+}
+
+/// Fill semaphores of the capped rules, by rule path.
+#[derive(Default)]
+pub struct FillLimits {
+    limits: Mutex<HashMap<String, Arc<Limit>>>,
+}
+
+/// A fill slot of a capped rule, given back on drop.
+pub struct FillPermit {
+    rule: String,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for FillPermit {
+    fn drop(&mut self) {
+        metrics::add_rule_fills_inflight(&self.rule, -1);
+    }
+}
+
+impl FillLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits for a fill slot of the rule, none being needed for a rule without a cap.
+    /// Returns the wait that ran out when no slot freed up in time.
+    pub async fn acquire(&self, rule: &Rule) -> Result<Option<FillPermit>, Duration> {
+        let Some(cap) = rule.cache_value.max_concurrent_fills else {
+            return Ok(None);
+        };
+        let wait = rule.cache_value.max_fill_wait.unwrap_or(DEFAULT_MAX_FILL_WAIT);
+        let name = rule.path.as_deref().unwrap_or_default();
+
+        let semaphore = self.limit(name, cap).semaphore.clone();
+        match tokio::time::timeout(wait, semaphore.acquire_owned()).await {
+            Ok(Ok(permit)) => {
+                metrics::add_rule_fills_inflight(name, 1);
+                Ok(Some(FillPermit { rule: name.to_string(), _permit: permit }))
+            }
+            _ => {
+                metrics::inc_rule_fills_rejected(name);
+                Err(wait)
+            }
+        }
+    }
+
+    /// Semaphore of the rule. A reload changing the cap gets a new one; fills holding a slot of
+    /// the previous one finish on it.
+    fn limit(&self, rule: &str, cap: usize) -> Arc<Limit> {
+        let mut limits = self.limits.lock();
+        match limits.get(rule) {
+            Some(limit) if limit.cap == cap => limit.clone(),
+            _ => {
+                let limit = Arc::new(Limit { cap, semaphore: Arc::new(Semaphore::new(cap)) });
+                limits.insert(rule.to_string(), limit.clone());
+                limit
+            }
+        }
+    }
+}
@@ -4,14 +4,15 @@ use axum::{
     extract::{Query, State},
     http::StatusCode,
     response::IntoResponse,
-    routing::get,
-    Json, Router,
+    Json,
 };
 use serde::Deserialize;
 use std::sync::Arc;
 
-use crate::http::Controller;
+use crate::config::Config;
+use crate::http::{Controller, Route};
 use crate::db::Storage;
+use crate::model::Entry;
 
 /// Query parameters for get endpoint.
 #[derive(Deserialize)]
@@ -21,13 +22,37 @@ struct GetQuery {
 
 /// GetController handles cache entry retrieval by key.
 pub struct GetController {
+    cfg: Arc<Config>,
     db: Arc<dyn Storage>,
 }
 
 impl GetController {
     /// Creates a new get controller.
-    pub fn new(db: Arc<dyn Storage>) -> Self {
-        Self { db }
+    pub fn new(cfg: Config, db: Arc<dyn Storage>) -> Self {
+        Self { cfg: Arc::new(cfg), db }
+    }
+
+    /// Entry as shown by the endpoint, with the refresh settings in effect for it. Hits are left
+    /// out when the storage does not count them.
+    fn entry_json(&self, entry: &Entry) -> serde_json::Value {
+        let mut json = entry.to_map();
+        let (params, source) = entry.refresh_settings(&self.cfg);
+        if let Some(map) = json.as_object_mut() {
+            if !self.db.counts_hits() {
+                map.remove("hits");
+            }
+            map.insert(
+                "refresh".to_string(),
+                serde_json::json!({
+                    "enabled": params.enabled,
+                    "ttl": humantime::format_duration(params.ttl).to_string(),
+                    "beta": params.beta,
+                    "coefficient": params.coefficient,
+                    "source": source.label(),
+                }),
+            );
+        }
+        json
     }
 
     /// Handles the get request.
@@ -56,8 +81,7 @@ impl GetController {
         }
 
         if let Some(entry) = entry {
-            let json = entry.to_map();
-            (StatusCode::OK, Json(json)).into_response()
+            (StatusCode::OK, Json(controller.entry_json(&entry))).into_response()
         } else {
             (StatusCode::NOT_FOUND, "").into_response()
         }
@@ -65,21 +89,19 @@ impl GetController {
 }
 
 impl Controller for GetController {
-    fn add_route(&self, router: Router) -> Router {
+    fn describe(&self) -> Vec<Route> {
         let controller = Arc::new(self.clone());
-        router.route(
-            "/advcache/entry",
-            get(move |query: Query<GetQuery>| {
-                let controller = controller.clone();
-                async move { Self::get(query, State(controller)).await }
-            }),
-        )
+        vec![Route::get("/advcache/entry", "Shows a single cache entry by key", move |query: Query<GetQuery>| {
+            let controller = controller.clone();
+            async move { Self::get(query, State(controller)).await }
+        })]
     }
 }
 
 impl Clone for GetController {
     fn clone(&self) -> Self {
         Self {
+            cfg: self.cfg.clone(),
             db: self.db.clone(),
         }
     }
@@ -0,0 +1,214 @@
+//! Health (SLO) controller: tells a load balancer whether to prefer another replica.
+//!
+//! Unlike the liveness probe, which only says the process is up, `/advcache/health` answers
+//! 503 while any condition of `cache.health` is breached. Conditions are evaluated on the
+//! metrics tick of the cache controller; the endpoint serves the result of the last tick.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{http::StatusCode, response::IntoResponse};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::config::{Config, Health};
+use crate::controller::cache_metrics::Snapshot;
+use crate::db::Storage;
+use crate::http::{Controller, Route};
+use crate::upstream::Upstream;
+
+pub const CONDITION_HIT_RATE: &str = "hit_rate";
+pub const CONDITION_ERROR_RATE: &str = "error_rate";
+pub const CONDITION_MEMORY: &str = "memory_over_hard_limit";
+pub const CONDITION_UPSTREAM: &str = "upstream_down";
+pub const CONDITION_REFRESH_BACKLOG: &str = "refresh_backlog";
+
+static MONITOR: Lazy<Arc<HealthMonitor>> = Lazy::new(|| Arc::new(HealthMonitor::new()));
+
+/// Monitor fed by the cache controller and read by [`HealthController`].
+pub fn monitor() -> Arc<HealthMonitor> {
+    MONITOR.clone()
+}
+
+/// A condition breached on the last tick, with the observed value and the configured threshold.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Breach {
+    pub condition: &'static str,
+    pub value: f64,
+    pub threshold: f64,
+}
+
+/// Figures a tick is evaluated on.
+#[derive(Debug, Clone, Default)]
+pub struct Sample {
+    pub hits: i64,
+    pub misses: i64,
+    pub total: i64,
+    pub errored: i64,
+    pub mem: i64,
+    /// Storage hard memory limit in bytes, 0 when eviction is not configured.
+    pub hard_limit: i64,
+    pub upstream_alive: bool,
+    pub refresh_backlog: usize,
+}
+
+impl Sample {
+    /// Collects a sample from the tick's counters, the storage and the upstream. The refresh
+    /// backlog is only counted when its condition is enabled, as it walks the expiry indexes.
+    pub fn collect(
+        cfg: &Config,
+        snapshot: &Snapshot,
+        cache: &dyn Storage,
+        upstream: &dyn Upstream,
+    ) -> Self {
+        let health = cfg.cache.health.as_ref();
+        let refresh_backlog = match health.and_then(|h| h.max_refresh_backlog) {
+            Some(_) => cache.refresh_backlog(),
+            None => 0,
+        };
+        Self {
+            hits: snapshot.hits,
+            misses: snapshot.misses,
+            total: snapshot.total,
+            errored: snapshot.errored,
+            mem: cache.stat().0,
+            hard_limit: cache.memory_limits().hard,
+            upstream_alive: upstream.is_alive(),
+            refresh_backlog,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Start of the current run of ticks with a hit rate under the threshold.
+    low_hit_since: Option<Instant>,
+    breached: Vec<Breach>,
+}
+
+/// Evaluates `cache.health` tick after tick and keeps the conditions breached on the last one.
+#[derive(Default)]
+pub struct HealthMonitor {
+    state: Mutex<State>,
+}
+
+impl HealthMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Evaluates the conditions on a sample taken at `now` and returns the breached ones.
+    ///
+    /// The hit rate only counts on ticks with cache lookups: a tick without traffic ends a
+    /// low hit rate run, so an idle instance does not report a low hit rate.
+    pub fn observe(&self, cfg: Option<&Health>, sample: &Sample, now: Instant) -> Vec<Breach> {
+        let mut state = self.state.lock();
+        let mut breached = Vec::new();
+        let Some(cfg) = cfg else {
+            state.low_hit_since = None;
+            state.breached = breached;
+            return Vec::new();
+        };
+
+        let lookups = sample.hits + sample.misses;
+        match cfg.min_hit_rate {
+            Some(min) if lookups > 0 && rate(sample.hits, lookups) < min => {
+                let since = *state.low_hit_since.get_or_insert(now);
+                if now.duration_since(since) >= cfg.min_hit_rate_for.unwrap_or(Duration::ZERO) {
+                    breached.push(Breach {
+                        condition: CONDITION_HIT_RATE,
+                        value: rate(sample.hits, lookups),
+                        threshold: min,
+                    });
+                }
+            }
+            _ => state.low_hit_since = None,
+        }
+
+        if let Some(max) = cfg.max_error_rate {
+            let value = rate(sample.errored, sample.total);
+            if sample.total > 0 && value > max {
+                breached.push(Breach { condition: CONDITION_ERROR_RATE, value, threshold: max });
+            }
+        }
+
+        if cfg.memory_over_hard_limit.unwrap_or(false) && sample.hard_limit > 0 && sample.mem > sample.hard_limit {
+            breached.push(Breach {
+                condition: CONDITION_MEMORY,
+                value: sample.mem as f64,
+                threshold: sample.hard_limit as f64,
+            });
+        }
+
+        if cfg.upstream_down.unwrap_or(false) && !sample.upstream_alive {
+            breached.push(Breach { condition: CONDITION_UPSTREAM, value: 0.0, threshold: 1.0 });
+        }
+
+        if let Some(max) = cfg.max_refresh_backlog {
+            if sample.refresh_backlog > max {
+                breached.push(Breach {
+                    condition: CONDITION_REFRESH_BACKLOG,
+                    value: sample.refresh_backlog as f64,
+                    threshold: max as f64,
+                });
+            }
+        }
+
+        state.breached = breached.clone();
+        breached
+    }
+
+    /// Conditions breached on the last tick.
+    pub fn breached(&self) -> Vec<Breach> {
+        self.state.lock().breached.clone()
+    }
+}
+
+fn rate(part: i64, whole: i64) -> f64 {
+    part as f64 / whole as f64 * 100.0
+}
+
+/// Health response body.
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    breached: Vec<Breach>,
+}
+
+/// HealthController serves the SLO snapshot of the instance.
+pub struct HealthController {
+    monitor: Arc<HealthMonitor>,
+}
+
+impl HealthController {
+    /// Creates a health controller reading the given monitor.
+    pub fn new(monitor: Arc<HealthMonitor>) -> Self {
+        Self { monitor }
+    }
+
+    /// 200 while no condition is breached, 503 listing the breached ones otherwise.
+    async fn get(monitor: Arc<HealthMonitor>) -> impl IntoResponse {
+        let breached = monitor.breached();
+        let (status, label) = if breached.is_empty() {
+            (StatusCode::OK, "ok")
+        } else {
+            (StatusCode::SERVICE_UNAVAILABLE, "degraded")
+        };
+        let resp = HealthResponse { status: label, breached };
+        (
+            status,
+            [("content-type", "application/json; charset=utf-8")],
+            serde_json::to_string(&resp).unwrap_or_default(),
+        )
+    }
+}
+
+impl Controller for HealthController {
+    fn describe(&self) -> Vec<Route> {
+        let monitor = self.monitor.clone();
+        vec![Route::get("/advcache/health", "SLO health snapshot for load balancers", move || {
+            Self::get(monitor.clone())
+        })]
+    }
+}
@@ -0,0 +1,199 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use tokio_util::sync::CancellationToken;
+    use tower::ServiceExt;
+
+    use crate::config::{self, Config, ConfigTrait, Health};
+    use crate::controller::cache_metrics::Snapshot;
+    use crate::controller::health::{
+        Breach, HealthController, HealthMonitor, Sample, CONDITION_ERROR_RATE, CONDITION_HIT_RATE,
+        CONDITION_MEMORY, CONDITION_REFRESH_BACKLOG, CONDITION_UPSTREAM,
+    };
+    use crate::db::storage::{Map, Storage};
+    use crate::http::Controller;
+    use crate::model::{Entry, Response};
+    use crate::time;
+    use crate::upstream::testing::MockUpstream;
+    use crate::upstream::Upstream;
+
+    fn conditions(breached: &[Breach]) -> Vec<&'static str> {
+        breached.iter().map(|b| b.condition).collect()
+    }
+
+    fn traffic(hits: i64, misses: i64, errored: i64) -> Sample {
+        Sample { hits, misses, total: hits + misses + errored, errored, upstream_alive: true, ..Default::default() }
+    }
+
+    fn storage(cfg: &Config, upstream: Arc<MockUpstream>) -> (Arc<Storage>, CancellationToken) {
+        let token = CancellationToken::new();
+        let map = Arc::new(Map::new(token.clone(), cfg.clone()));
+        let storage = Storage::new(token.clone(), cfg.clone(), upstream as Arc<dyn Upstream>, map)
+            .expect("Failed to create storage");
+        (storage, token)
+    }
+
+    fn fill(cfg: &Config, storage: &Storage, n: usize) -> Vec<Entry> {
+        let rule = cfg.rule("/api/v1/user").unwrap();
+        (0..n)
+            .map(|id| {
+                let queries = vec![(b"user[id]".to_vec(), id.to_string().into_bytes())];
+                let entry = Entry::new(rule.clone(), &queries, &[]);
+                entry.set_payload(&queries, &[], &Response { status: 200, headers: vec![], body: b"{}".to_vec() });
+                assert!(storage.set(entry.clone()));
+                entry
+            })
+            .collect()
+    }
+
+    /// Test that nothing is breached without a health section, whatever the figures.
+    #[test]
+    fn test_unconfigured_is_healthy() {
+        let monitor = HealthMonitor::new();
+        let sample = Sample { mem: 10, hard_limit: 1, ..traffic(0, 100, 100) };
+        assert!(monitor.observe(None, &sample, Instant::now()).is_empty());
+    }
+
+    /// Test that a low hit rate breaches only once it has lasted `min_hit_rate_for`.
+    #[test]
+    fn test_low_hit_rate_for_duration() {
+        let monitor = HealthMonitor::new();
+        let cfg = Health {
+            min_hit_rate: Some(50.0),
+            min_hit_rate_for: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let t0 = Instant::now();
+        let low = traffic(1, 9, 0);
+
+        assert!(monitor.observe(Some(&cfg), &low, t0).is_empty());
+        assert!(monitor.observe(Some(&cfg), &low, t0 + Duration::from_secs(30)).is_empty());
+        let breached = monitor.observe(Some(&cfg), &low, t0 + Duration::from_secs(60));
+        assert_eq!(breached, vec![Breach { condition: CONDITION_HIT_RATE, value: 10.0, threshold: 50.0 }]);
+
+        assert!(monitor.observe(Some(&cfg), &traffic(9, 1, 0), t0 + Duration::from_secs(65)).is_empty());
+        assert!(
+            monitor.observe(Some(&cfg), &low, t0 + Duration::from_secs(70)).is_empty(),
+            "a good tick starts the low hit rate run over"
+        );
+    }
+
+    /// Test that ticks without lookups neither breach nor keep a low hit rate run going.
+    #[test]
+    fn test_idle_ticks_do_not_count_as_low_hit_rate() {
+        let monitor = HealthMonitor::new();
+        let cfg = Health { min_hit_rate: Some(50.0), min_hit_rate_for: Some(Duration::from_secs(10)), ..Default::default() };
+        let t0 = Instant::now();
+
+        assert!(monitor.observe(Some(&cfg), &traffic(0, 5, 0), t0).is_empty());
+        assert!(monitor.observe(Some(&cfg), &traffic(0, 0, 0), t0 + Duration::from_secs(5)).is_empty());
+        assert!(monitor.observe(Some(&cfg), &traffic(0, 5, 0), t0 + Duration::from_secs(10)).is_empty());
+    }
+
+    /// Test that the error rate is a share of all requests.
+    #[test]
+    fn test_error_rate() {
+        let monitor = HealthMonitor::new();
+        let cfg = Health { max_error_rate: Some(5.0), ..Default::default() };
+
+        assert!(monitor.observe(Some(&cfg), &traffic(90, 5, 5), Instant::now()).is_empty());
+        let breached = monitor.observe(Some(&cfg), &traffic(80, 10, 10), Instant::now());
+        assert_eq!(breached, vec![Breach { condition: CONDITION_ERROR_RATE, value: 10.0, threshold: 5.0 }]);
+    }
+
+    /// Test that a storage grown past its hard limit is reported.
+    #[tokio::test]
+    async fn test_memory_over_hard_limit() {
+        let mut cfg = config::new_test_config();
+        cfg.cache.storage.as_mut().unwrap().hard_memory_limit = 1;
+        cfg.cache.health = Some(Health { memory_over_hard_limit: Some(true), ..Default::default() });
+        let (storage, token) = storage(&cfg, MockUpstream::new());
+        let monitor = HealthMonitor::new();
+
+        let sample = Sample::collect(&cfg, &Snapshot::default(), storage.as_ref(), MockUpstream::new().as_ref());
+        assert!(monitor.observe(cfg.cache.health.as_ref(), &sample, Instant::now()).is_empty());
+
+        fill(&cfg, &storage, 1);
+        let sample = Sample::collect(&cfg, &Snapshot::default(), storage.as_ref(), MockUpstream::new().as_ref());
+        let breached = monitor.observe(cfg.cache.health.as_ref(), &sample, Instant::now());
+        token.cancel();
+        assert_eq!(conditions(&breached), vec![CONDITION_MEMORY]);
+        assert_eq!(breached[0].threshold, 1.0);
+    }
+
+    /// Test that a backend marked down breaches until it is back up.
+    #[tokio::test]
+    async fn test_upstream_down() {
+        let cfg = config::new_test_config();
+        let health = Health { upstream_down: Some(true), ..Default::default() };
+        let upstream = MockUpstream::new();
+        let (storage, token) = storage(&cfg, upstream.clone());
+        let monitor = HealthMonitor::new();
+
+        upstream.set_healthy(false);
+        let sample = Sample::collect(&cfg, &Snapshot::default(), storage.as_ref(), upstream.as_ref());
+        assert_eq!(conditions(&monitor.observe(Some(&health), &sample, Instant::now())), vec![CONDITION_UPSTREAM]);
+
+        upstream.set_healthy(true);
+        let sample = Sample::collect(&cfg, &Snapshot::default(), storage.as_ref(), upstream.as_ref());
+        token.cancel();
+        assert!(monitor.observe(Some(&health), &sample, Instant::now()).is_empty());
+    }
+
+    /// Test that entries past their refresh deadline count towards the backlog.
+    #[tokio::test]
+    async fn test_refresh_backlog() {
+        let _clock = time::start(Duration::from_millis(1));
+        let mut cfg = config::new_test_config();
+        cfg.cache.health = Some(Health { max_refresh_backlog: Some(1), ..Default::default() });
+        let upstream = MockUpstream::new();
+        let (storage, token) = storage(&cfg, upstream.clone());
+        let monitor = HealthMonitor::new();
+
+        let entries = fill(&cfg, &storage, 3);
+        storage.mark_outdated(&entries[0]);
+        let sample = Sample::collect(&cfg, &Snapshot::default(), storage.as_ref(), upstream.as_ref());
+        assert_eq!(sample.refresh_backlog, 1);
+        assert!(monitor.observe(cfg.cache.health.as_ref(), &sample, Instant::now()).is_empty());
+
+        storage.mark_outdated(&entries[1]);
+        let sample = Sample::collect(&cfg, &Snapshot::default(), storage.as_ref(), upstream.as_ref());
+        token.cancel();
+        assert_eq!(
+            monitor.observe(cfg.cache.health.as_ref(), &sample, Instant::now()),
+            vec![Breach { condition: CONDITION_REFRESH_BACKLOG, value: 2.0, threshold: 1.0 }]
+        );
+    }
+
+    /// Test that the endpoint answers 200 while healthy and 503 listing the breached conditions.
+    #[tokio::test]
+    async fn test_endpoint_reports_last_tick() {
+        let monitor = Arc::new(HealthMonitor::new());
+        let router = HealthController::new(monitor.clone()).add_route(Router::new());
+        let get = || async {
+            let req = Request::get("/advcache/health").body(Body::empty()).unwrap();
+            let resp = router.clone().oneshot(req).await.unwrap();
+            let status = resp.status();
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+        };
+
+        let (status, body) = get().await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!({"status": "ok", "breached": []}));
+
+        let cfg = Health { max_error_rate: Some(1.0), upstream_down: Some(true), ..Default::default() };
+        let sample = Sample { upstream_alive: false, ..traffic(0, 1, 1) };
+        monitor.observe(Some(&cfg), &sample, Instant::now());
+        let (status, body) = get().await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "degraded");
+        assert_eq!(body["breached"][0]["condition"], CONDITION_ERROR_RATE);
+        assert_eq!(body["breached"][1]["condition"], CONDITION_UPSTREAM);
+    }
+}
@@ -0,0 +1,87 @@
+//! Admin index controller.
+
+use std::sync::Arc;
+
+use axum::{http::StatusCode, response::IntoResponse};
+use serde::Serialize;
+
+use crate::http::{Controller, Route};
+
+/// Path of the index.
+pub const INDEX_PATH: &str = "/advcache/";
+
+const INDEX_DESCRIPTION: &str = "Index of the admin endpoints";
+
+/// An indexed endpoint, as described by the controller serving it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Endpoint {
+    pub path: String,
+    pub method: &'static str,
+    pub description: &'static str,
+    pub auth: bool,
+    pub destructive: bool,
+    pub mutating: bool,
+}
+
+impl From<&Route> for Endpoint {
+    fn from(route: &Route) -> Self {
+        Self {
+            path: route.path.clone(),
+            method: route.method,
+            description: route.description,
+            auth: route.auth,
+            destructive: route.destructive,
+            mutating: route.mutating,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct IndexResponse<'a> {
+    endpoints: &'a [Endpoint],
+}
+
+/// IndexController lists the endpoints of the other controllers, built from the
+/// same descriptions their routes are registered from. Hidden routes are listed
+/// only with debug endpoints enabled.
+pub struct IndexController {
+    endpoints: Arc<Vec<Endpoint>>,
+}
+
+impl IndexController {
+    /// Creates an index of the given controllers and of itself.
+    pub fn new(controllers: &[Box<dyn Controller>], debug: bool) -> Self {
+        let mut endpoints: Vec<Endpoint> = controllers
+            .iter()
+            .flat_map(|controller| controller.describe())
+            .filter(|route| debug || !route.hidden)
+            .map(|route| Endpoint::from(&route))
+            .collect();
+        endpoints.push(Endpoint {
+            path: INDEX_PATH.to_string(),
+            method: "GET",
+            description: INDEX_DESCRIPTION,
+            auth: false,
+            destructive: false,
+            mutating: false,
+        });
+        endpoints.sort_by(|a, b| (&a.path, a.method).cmp(&(&b.path, b.method)));
+        Self { endpoints: Arc::new(endpoints) }
+    }
+
+    /// Gets the index.
+    async fn get(endpoints: Arc<Vec<Endpoint>>) -> impl IntoResponse {
+        (
+            StatusCode::OK,
+            [("content-type", "application/json")],
+            serde_json::to_string(&IndexResponse { endpoints: &endpoints }).unwrap_or_default(),
+        )
+    }
+}
+
+impl Controller for IndexController {
+    fn describe(&self) -> Vec<Route> {
+        let endpoints = self.endpoints.clone();
+        vec![Route::get(INDEX_PATH, INDEX_DESCRIPTION, move || Self::get(endpoints.clone()))]
+    }
+}
@@ -1,38 +1,58 @@
 //! Cache invalidation controller.
 
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::get,
-    Router,
+    extract::{Query, RawQuery, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
 };
 use serde::Serialize;
 use std::collections::HashMap;
-use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tokio_util::sync::CancellationToken;
 
 use crate::config::Config;
+use crate::db::capture::{self, Op};
+use crate::db::jobs::{JobState, Outcome, ShardJob};
 use crate::http::query::filter_and_sort_request;
-use crate::http::Controller;
-use crate::model::match_cache_rule;
+use crate::http::{Controller, Route};
+use crate::middleware::audit_middleware::Affected;
+use crate::model::surrogate::tag_hash;
+use crate::model::{match_cache_rule, Entry};
+use crate::db::walks::OP_INVALIDATE;
+use crate::model::timestamps::{self, DEFAULT_MAX_CLOCK_SKEW};
 use crate::db::Storage;
+use crate::upstream::peers::{PeerResult, Peers, PROPAGATE_PARAM};
 
 const PATH_SPECIAL: &str = "_path";
+const TAG_PARAM: &str = "key";
 const REMOVE_SPECIAL: &str = "_remove";
+const TOMBSTONE_SPECIAL: &str = "_tombstone";
+const IF_REFRESHED_BEFORE_SPECIAL: &str = "_if_refreshed_before";
 
 /// Marked response structure.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Default)]
 struct MarkedResponse {
     success: bool,
     affected: i64,
+    /// Matching entries left alone because they were refreshed at or after `_if_refreshed_before`.
+    skipped_newer: i64,
+    /// Id of the job run, see `/advcache/jobs/{id}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job: Option<u64>,
+    /// Outcome of the invalidation forwarded to each of `upstream.peers`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    peers: Vec<PeerResult>,
+    /// Why the invalidation did not run or stopped, e.g. it gave up waiting for its turn to
+    /// walk or was cancelled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
 }
 
 /// InvalidateController handles cache invalidation and marking.
 pub struct InvalidateController {
     db: Arc<dyn Storage>,
     cfg: Arc<Config>,
+    peers: Option<Arc<Peers>>,
 }
 
 impl InvalidateController {
@@ -40,28 +60,41 @@ impl InvalidateController {
     pub fn new(cfg: Config, db: Arc<dyn Storage>) -> Self {
         Self {
             db,
+            peers: Peers::from_config(&cfg).map(Arc::new),
             cfg: Arc::new(cfg),
         }
     }
 
+    /// Clamps an `_if_refreshed_before` of a client whose clock runs more than
+    /// `storage.max_clock_skew` ahead to now.
+    fn guard_clock_skew(&self, ts: i64) -> i64 {
+        let max_skew = self.cfg.cache.storage.as_ref().and_then(|s| s.max_clock_skew).unwrap_or(DEFAULT_MAX_CLOCK_SKEW);
+        let now = crate::time::unix_nano();
+        if !timestamps::is_past_clock_skew(ts, now, max_skew) {
+            return ts;
+        }
+        timestamps::report_clamped("invalidate", ts, now);
+        now
+    }
+
     /// Invalidates cache entries based on query parameters and path.
     async fn invalidate(
         Query(params): Query<HashMap<String, String>>,
+        RawQuery(raw_query): RawQuery,
+        headers: HeaderMap,
         State(controller): State<Arc<Self>>,
-    ) -> impl IntoResponse {
+    ) -> Response {
         // Extract path from parameters
         let path_str = match params.get(PATH_SPECIAL) {
             Some(p) => p.clone(),
             None => {
-                let resp = MarkedResponse {
-                    success: false,
-                    affected: 0,
-                };
+                let resp = MarkedResponse::default();
                 return (
                     StatusCode::BAD_REQUEST,
                     [("content-type", "application/json")],
                     serde_json::to_string(&resp).unwrap_or_default(),
-                );
+                )
+                    .into_response();
             }
         };
 
@@ -70,163 +103,285 @@ impl InvalidateController {
         let rule = match match_cache_rule(&controller.cfg, path_bytes) {
             Ok(r) => r, // Already Arc<Rule>, just clone the Arc
             Err(_) => {
-                let resp = MarkedResponse {
-                    success: false,
-                    affected: 0,
-                };
+                let resp = MarkedResponse::default();
                 return (
                     StatusCode::NOT_FOUND,
                     [("content-type", "application/json")],
                     serde_json::to_string(&resp).unwrap_or_default(),
-                );
+                )
+                    .into_response();
             }
         };
 
+        // Built from the raw query, in request order and with repeated params kept, so the
+        // rule's `duplicate_query` resolves them exactly as for the requests that were cached.
         use url::form_urlencoded;
-        let mut serializer = form_urlencoded::Serializer::new(String::new());
-        for (key, value) in &params {
-            if key != PATH_SPECIAL && key != REMOVE_SPECIAL {
-                serializer.append_pair(key, value);
+        let raw_query = raw_query.unwrap_or_default();
+        let query_str = {
+            let mut serializer = form_urlencoded::Serializer::new(String::new());
+            for (key, value) in form_urlencoded::parse(raw_query.as_bytes()) {
+                if key != PATH_SPECIAL
+                    && key != REMOVE_SPECIAL
+                    && key != TOMBSTONE_SPECIAL
+                    && key != IF_REFRESHED_BEFORE_SPECIAL
+                    && key != PROPAGATE_PARAM
+                {
+                    serializer.append_pair(&key, &value);
+                }
             }
-        }
-        let query_str = serializer.finish();
+            serializer.finish()
+        };
 
-        let filtered_queries = filter_and_sort_request(Some(&*rule), &query_str);
+        let filtered_queries = match filter_and_sort_request(Some(&*rule), &query_str) {
+            Ok(queries) => queries,
+            Err(_) => {
+                let resp = MarkedResponse::default();
+                return (
+                    StatusCode::BAD_REQUEST,
+                    [("content-type", "application/json")],
+                    serde_json::to_string(&resp).unwrap_or_default(),
+                )
+                    .into_response();
+            }
+        };
 
         // Determine if we should remove entries (check for _remove query param)
         let should_remove = params.contains_key(REMOVE_SPECIAL);
 
-        // Walk through all shards and invalidate matching entries
-        let affected = Arc::new(std::sync::atomic::AtomicI64::new(0));
-        let keys_to_remove = Arc::new(std::sync::Mutex::new(Vec::new()));
-        let affected_clone = affected.clone();
-        let keys_to_remove_clone = keys_to_remove.clone();
-        let db_clone = controller.db.clone();
-        let rule_clone = rule.clone();
-        let filtered_queries_clone = filtered_queries.clone();
-        let path_bytes_clone = path_bytes.to_vec();
-
-        let ctx = CancellationToken::new();
-        db_clone.walk_shards(ctx.clone(), Box::new(move |_shard_id, shard| {
-            let affected = affected_clone.clone();
-            let keys_to_remove = keys_to_remove_clone.clone();
-            let rule = rule_clone.clone();
-            let filtered_queries = filtered_queries_clone.clone();
-            let path_bytes = path_bytes_clone.clone();
-            let should_remove = should_remove;
-
-            shard.walk_r(&ctx, |key, entry| {
-                // Check if path matches
-                let entry_path = entry.rule().path_bytes.as_deref().unwrap_or(&[]);
-                if entry_path != path_bytes {
-                    return true; // Continue to next entry
+        // Removed keys may be tombstoned (`_tombstone=5s`): until it expires they are
+        // served from the origin without being stored, giving its own caches time to converge.
+        let tombstone_ttl = match params.get(TOMBSTONE_SPECIAL) {
+            None => None,
+            Some(raw) => match humantime::parse_duration(raw) {
+                Ok(ttl) if should_remove => Some(ttl),
+                _ => {
+                    let resp = MarkedResponse::default();
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        [("content-type", "application/json")],
+                        serde_json::to_string(&resp).unwrap_or_default(),
+                    )
+                        .into_response();
                 }
+            },
+        };
 
-                // Check if rule path matches
-                if entry.rule().path.as_deref() != rule.path.as_deref() {
-                    return true; // Continue to next entry
+        // Entries refreshed at or after `_if_refreshed_before` (unix ms) are skipped, so an
+        // invalidation carrying the source change time never undoes a refresh that already
+        // picked the change up, and can be retried safely.
+        let refreshed_before_nanos = match params.get(IF_REFRESHED_BEFORE_SPECIAL) {
+            None => None,
+            Some(raw) => match raw.parse::<i64>() {
+                Ok(ms) => Some(controller.guard_clock_skew(ms.saturating_mul(1_000_000))),
+                Err(_) => {
+                    let resp = MarkedResponse::default();
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        [("content-type", "application/json")],
+                        serde_json::to_string(&resp).unwrap_or_default(),
+                    )
+                        .into_response();
                 }
+            },
+        };
 
-                // Check if queries match using walk_query
-                // If no filtered queries provided, match all entries for the path
-                let matches = if filtered_queries.is_empty() {
-                    true // No query filter - match all entries for this path
-                } else {
-                    // All filtered queries must be present in entry with exact match
-                    // Use walk_query to iterate over queries directly from encoded payload
-                    let mut all_match = true;
-                    for (filter_key, filter_value) in &filtered_queries {
-                        let mut found = false;
-                        if let Err(e) = entry.walk_query(|entry_key, entry_value| {
-                            // Use is_bytes_are_equals for comparison
-                            if crate::bytes::is_bytes_are_equals(filter_key, entry_key)
-                                && crate::bytes::is_bytes_are_equals(filter_value, entry_value) {
-                                found = true;
-                                false // Stop iteration
-                            } else {
-                                true // Continue iteration
-                            }
-                        }) {
-                            // Error walking queries - probably malformed payload
-                            tracing::error!(error = %e, "failed to mark/remove entries (probably malformed payload)");
-                            all_match = false;
-                            break;
-                        }
-                        if !found {
-                            all_match = false;
-                            break;
-                        }
-                    }
-                    all_match
-                };
-
-                if !matches {
-                    return true; // Continue to next entry
-                }
+        let Some(jobs) = controller.db.jobs() else {
+            let resp = MarkedResponse { error: Some("storage does not run jobs".to_string()), ..Default::default() };
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [("content-type", "application/json")],
+                serde_json::to_string(&resp).unwrap_or_default(),
+            )
+                .into_response();
+        };
 
-                // Match found - invalidate or remove
-                if should_remove {
-                    // Collect key for removal (we'll remove after walk completes)
-                    keys_to_remove.lock().unwrap().push(key);
-                    affected.fetch_add(1, Ordering::Relaxed);
-                } else {
-                    // Mark entry as outdated for background refresh
-                    // Collect key to handle after walk completes
-                    keys_to_remove.lock().unwrap().push(key);
-                    affected.fetch_add(1, Ordering::Relaxed);
+        // Entries of the path whose queries hold every filtered pair.
+        let path_bytes = path_bytes.to_vec();
+        let matches = move |entry: &Entry| {
+            let entry_rule = entry.rule();
+            if entry_rule.path_bytes.as_deref().unwrap_or(&[]) != path_bytes.as_slice() || entry_rule.path.as_deref() != rule.path.as_deref() {
+                return false;
+            }
+            filtered_queries.iter().all(|(filter_key, filter_value)| {
+                let mut found = false;
+                let walked = entry.walk_query(|entry_key, entry_value| {
+                    found = crate::bytes::is_bytes_equal(filter_key, entry_key)
+                        && crate::bytes::is_bytes_equal(filter_value, entry_value);
+                    !found
+                });
+                if let Err(e) = walked {
+                    tracing::error!(error = %e, "failed to mark/remove entries (probably malformed payload)");
+                    return false;
                 }
-
-                true // Continue to next entry
-            });
-        }));
-
-        // Handle collected entries: mark as outdated or remove
-        let keys = keys_to_remove.lock().unwrap().clone();
-        for key in keys {
-            if let (Some(entry), _) = controller.db.get_by_key(key) {
-                if should_remove {
-                    // Remove entry
-                    controller.db.remove(&entry);
-                } else {
-                    // Mark entry as outdated for background refresh
-                    entry.untouch_refreshed_at();
+                found
+            })
+        };
+        let act = move |db: &dyn Storage, entry: &Entry| {
+            if refreshed_before_nanos.is_some_and(|before| entry.fresh_at() >= before) {
+                return Outcome::Skipped;
+            }
+            if should_remove {
+                db.remove(entry);
+                if let Some(ttl) = tombstone_ttl {
+                    db.tombstone(entry.key(), ttl);
                 }
+                capture::record(entry.key(), Op::Invalidate, "removed");
+            } else {
+                // Marked outdated for background refresh
+                db.mark_outdated(entry);
+                capture::record(entry.key(), Op::Invalidate, "marked outdated");
             }
-        }
+            Outcome::Acted
+        };
 
-        let affected_count = affected.load(Ordering::Relaxed);
+        // Jobs queue behind other admin walks, giving up after `storage.admin_walk_timeout`.
+        let job = jobs.submit(controller.db.clone(), ShardJob::new(OP_INVALIDATE, path_str.clone(), matches, act));
+        let record = match job.wait().await {
+            Ok(record) => record,
+            Err(e) => {
+                tracing::warn!(component = "invalidate", path = %path_str, error = %e, "invalidation not run");
+                let resp = MarkedResponse { job: Some(job.id()), error: Some(e.to_string()), ..Default::default() };
+                return (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [("content-type", "application/json")],
+                    serde_json::to_string(&resp).unwrap_or_default(),
+                )
+                    .into_response();
+            }
+        };
+        let affected_count = record.acted as i64;
+        let skipped_newer = record.skipped as i64;
+        let cancelled = record.state == JobState::Cancelled;
+
+        // Peers get the same call, special params included, once it went through here.
+        let peers = match &controller.peers {
+            Some(peers) if Peers::should_propagate(&headers, params.get(PROPAGATE_PARAM).map(String::as_str)) => {
+                peers.forward(&format!("/advcache/invalidate?{}", raw_query), &headers).await
+            }
+            _ => Vec::new(),
+        };
 
         let resp = MarkedResponse {
-            success: true,
+            success: !cancelled,
             affected: affected_count,
+            skipped_newer,
+            job: Some(record.id),
+            peers,
+            error: cancelled.then(|| "cancelled".to_string()),
         };
 
         tracing::info!(
             component = "invalidate",
             path = %path_str,
             affected = affected_count,
+            skipped_newer,
             removed = should_remove,
+            tombstone = ?tombstone_ttl,
             "cache entries marked as outdated"
         );
 
         (
             StatusCode::OK,
+            Extension(Affected(affected_count as u64)),
             [("content-type", "application/json")],
             serde_json::to_string(&resp).unwrap_or_default(),
         )
+            .into_response()
     }
 }
 
+impl InvalidateController {
+    /// Marks outdated or removes the entries whose response carries the `Surrogate-Key` tag.
+    /// They are looked up in the tag index of the shards, without walking them.
+    async fn invalidate_tag(
+        Query(params): Query<HashMap<String, String>>,
+        RawQuery(raw_query): RawQuery,
+        headers: HeaderMap,
+        State(controller): State<Arc<Self>>,
+    ) -> Response {
+        let Some(tag) = params.get(TAG_PARAM).filter(|tag| !tag.trim().is_empty()).map(|tag| tag.trim().to_string())
+        else {
+            return json(StatusCode::BAD_REQUEST, &MarkedResponse::default());
+        };
+        let should_remove = params.contains_key(REMOVE_SPECIAL);
+        let refreshed_before_nanos = match params.get(IF_REFRESHED_BEFORE_SPECIAL).map(|raw| raw.parse::<i64>()) {
+            None => None,
+            Some(Ok(ms)) => Some(controller.guard_clock_skew(ms.saturating_mul(1_000_000))),
+            Some(Err(_)) => return json(StatusCode::BAD_REQUEST, &MarkedResponse::default()),
+        };
+
+        let (mut affected, mut skipped_newer) = (0, 0);
+        for entry in controller.db.tagged(tag_hash(&tag)) {
+            if refreshed_before_nanos.is_some_and(|before| entry.fresh_at() >= before) {
+                skipped_newer += 1;
+                continue;
+            }
+            if should_remove {
+                controller.db.remove(&entry);
+                capture::record(entry.key(), Op::Invalidate, "removed by tag");
+            } else {
+                controller.db.mark_outdated(&entry);
+                capture::record(entry.key(), Op::Invalidate, "marked outdated by tag");
+            }
+            affected += 1;
+        }
+
+        let peers = match &controller.peers {
+            Some(peers) if Peers::should_propagate(&headers, params.get(PROPAGATE_PARAM).map(String::as_str)) => {
+                let raw_query = raw_query.unwrap_or_default();
+                peers.forward(&format!("/advcache/invalidate/tag?{}", raw_query), &headers).await
+            }
+            _ => Vec::new(),
+        };
+
+        tracing::info!(
+            component = "invalidate",
+            tag = %tag,
+            affected,
+            skipped_newer,
+            removed = should_remove,
+            "tagged cache entries invalidated"
+        );
+
+        let resp = MarkedResponse { success: true, affected, skipped_newer, peers, ..Default::default() };
+        (
+            StatusCode::OK,
+            Extension(Affected(affected as u64)),
+            [("content-type", "application/json")],
+            serde_json::to_string(&resp).unwrap_or_default(),
+        )
+            .into_response()
+    }
+}
+
+fn json(status: StatusCode, resp: &MarkedResponse) -> Response {
+    (status, [("content-type", "application/json")], serde_json::to_string(resp).unwrap_or_default()).into_response()
+}
+
 impl Controller for InvalidateController {
-    fn add_route(&self, router: Router) -> Router {
+    fn describe(&self) -> Vec<Route> {
         let controller = Arc::new(self.clone());
-        router.route(
-            "/advcache/invalidate",
-            get(move |query: Query<HashMap<String, String>>| {
-                let controller = controller.clone();
-                async move { Self::invalidate(query, State(controller)).await }
-            }),
-        )
+        let tag_controller = controller.clone();
+        vec![
+            Route::get(
+                "/advcache/invalidate",
+                "Marks outdated or removes the entries matching a path and query",
+                move |query: Query<HashMap<String, String>>, raw_query: RawQuery, headers: HeaderMap| {
+                    let controller = controller.clone();
+                    async move { Self::invalidate(query, raw_query, headers, State(controller)).await }
+                },
+            )
+            .destructive(),
+            Route::get(
+                "/advcache/invalidate/tag",
+                "Marks outdated or removes the entries whose response carries a Surrogate-Key tag",
+                move |query: Query<HashMap<String, String>>, raw_query: RawQuery, headers: HeaderMap| {
+                    let controller = tag_controller.clone();
+                    async move { Self::invalidate_tag(query, raw_query, headers, State(controller)).await }
+                },
+            )
+            .destructive(),
+        ]
     }
 }
 
@@ -235,6 +390,7 @@ impl Clone for InvalidateController {
         Self {
             db: self.db.clone(),
             cfg: self.cfg.clone(),
+            peers: self.peers.clone(),
         }
     }
 }
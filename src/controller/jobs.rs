@@ -0,0 +1,86 @@
+//! Admin jobs controller.
+
+use std::sync::Arc;
+
+use axum::{extract::Path, http::{HeaderMap, StatusCode}, response::IntoResponse};
+
+use crate::db::Storage;
+use crate::http::render::negotiate::{negotiated, negotiated_list};
+use crate::http::{Controller, Route};
+
+/// JobsController shows the admin jobs over the shards (invalidations, clears by rule, key
+/// schema purges) with their progress, and cancels them.
+pub struct JobsController {
+    db: Arc<dyn Storage>,
+}
+
+impl JobsController {
+    /// Creates a new jobs controller.
+    pub fn new(db: Arc<dyn Storage>) -> Self {
+        Self { db }
+    }
+
+    fn json(status: StatusCode, body: String) -> axum::response::Response {
+        (status, [("content-type", "application/json; charset=utf-8")], body).into_response()
+    }
+
+    fn no_jobs() -> axum::response::Response {
+        Self::json(StatusCode::NOT_FOUND, r#"{"error":"storage does not run jobs"}"#.to_string())
+    }
+
+    fn unknown(id: u64) -> axum::response::Response {
+        Self::json(StatusCode::NOT_FOUND, serde_json::json!({ "error": format!("no job {}", id) }).to_string())
+    }
+
+    /// Lists the jobs running and queued and the last finished, oldest first; `text/plain`
+    /// lists them as a table.
+    async fn list(db: Arc<dyn Storage>, headers: HeaderMap) -> axum::response::Response {
+        let Some(jobs) = db.jobs() else {
+            return Self::no_jobs();
+        };
+        let list = jobs.list();
+        negotiated_list(&headers, StatusCode::OK, &serde_json::json!({ "jobs": list }), &list)
+    }
+
+    /// Shows the progress of a job.
+    async fn get(db: Arc<dyn Storage>, id: u64, headers: HeaderMap) -> axum::response::Response {
+        let Some(jobs) = db.jobs() else {
+            return Self::no_jobs();
+        };
+        match jobs.get(id) {
+            Some(job) => negotiated(&headers, StatusCode::OK, &job.record()),
+            None => Self::unknown(id),
+        }
+    }
+
+    /// Cancels a job; what it did so far stays done. Cancelling a finished job changes nothing.
+    async fn cancel(db: Arc<dyn Storage>, id: u64) -> axum::response::Response {
+        let Some(jobs) = db.jobs() else {
+            return Self::no_jobs();
+        };
+        let Some(job) = jobs.get(id) else {
+            return Self::unknown(id);
+        };
+        job.cancel();
+        tracing::info!(component = "jobs", id, "job cancellation requested");
+        Self::json(StatusCode::OK, serde_json::to_string(&job.record()).unwrap_or_default())
+    }
+}
+
+impl Controller for JobsController {
+    fn describe(&self) -> Vec<Route> {
+        let (list, get, cancel) = (self.db.clone(), self.db.clone(), self.db.clone());
+        vec![
+            Route::get("/advcache/jobs", "Admin jobs over the shards running and last finished, with their progress", move |headers: HeaderMap| {
+                Self::list(list.clone(), headers)
+            }),
+            Route::get("/advcache/jobs/:id", "Progress of an admin job", move |Path(id): Path<u64>, headers: HeaderMap| {
+                Self::get(get.clone(), id, headers)
+            }),
+            Route::delete("/advcache/jobs/:id", "Cancels an admin job", move |Path(id): Path<u64>| {
+                Self::cancel(cancel.clone(), id)
+            })
+            .mutating(),
+        ]
+    }
+}
@@ -0,0 +1,113 @@
+//! Fixed-bucket latency histogram behind the percentiles of the `ingress` log and the
+//! average durations exported as metrics.
+//
+// Buckets are log-linear: every power of two of nanoseconds is split into 16 equal buckets, so a
+// bucket spans at most 1/16 of its lower bound and a quantile, reported at the bucket midpoint, is
+// off by at most 1/32 of the true value. Durations past 2^40 ns (~18 min) land in the last bucket.
+// The histogram is a fixed array of atomics plus the exact sum of the recorded durations:
+// recording is two fetch_adds and memory does not grow with traffic.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+const SUB_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BITS;
+const MAX_EXP: u32 = 40;
+const BUCKETS: usize = (MAX_EXP - SUB_BITS + 2) as usize * SUB_BUCKETS;
+
+/// Bucket of a duration in nanoseconds.
+fn bucket_of(nanos: u64) -> usize {
+    if nanos < SUB_BUCKETS as u64 {
+        return nanos as usize;
+    }
+    let exp = (63 - nanos.leading_zeros()).min(MAX_EXP);
+    if exp == MAX_EXP && nanos >> MAX_EXP > 1 {
+        return BUCKETS - 1;
+    }
+    let sub = (nanos >> (exp - SUB_BITS)) as usize & (SUB_BUCKETS - 1);
+    (exp - SUB_BITS + 1) as usize * SUB_BUCKETS + sub
+}
+
+/// Lower bound and width of a bucket, ns.
+fn bucket_bounds(bucket: usize) -> (u64, u64) {
+    if bucket < SUB_BUCKETS {
+        return (bucket as u64, 1);
+    }
+    let exp = (bucket / SUB_BUCKETS) as u32 + SUB_BITS - 1;
+    let sub = (bucket % SUB_BUCKETS) as u64;
+    let width = 1u64 << (exp - SUB_BITS);
+    ((1u64 << exp) + sub * width, width)
+}
+
+/// p50, p95 and p99 of one window; zero without samples.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Quantiles {
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+/// Durations recorded since the histogram was last drained.
+pub struct Histogram {
+    buckets: Box<[AtomicU64]>,
+    sum: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+        }
+    }
+}
+
+impl std::fmt::Debug for Histogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Histogram").field("buckets", &BUCKETS).finish()
+    }
+}
+
+impl Histogram {
+    /// Records a duration in nanoseconds; negative values count as zero.
+    pub fn record(&self, nanos: i64) {
+        let nanos = nanos.max(0) as u64;
+        self.buckets[bucket_of(nanos)].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    /// Returns the exact sum of the recorded durations, ns, and resets it. Drained apart from
+    /// the buckets: a sample racing with the two drains may land in different windows of each.
+    pub fn take_sum(&self) -> i64 {
+        self.sum.swap(0, Ordering::Relaxed) as i64
+    }
+
+    /// Returns the quantiles of the recorded durations and resets the histogram. Buckets are
+    /// swapped one by one, so a racing sample lands in this window or the next one.
+    pub fn take_quantiles(&self) -> Quantiles {
+        let counts: Vec<u64> = self.buckets.iter().map(|b| b.swap(0, Ordering::Relaxed)).collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return Quantiles::default();
+        }
+        Quantiles {
+            p50: quantile(&counts, total, 0.50),
+            p95: quantile(&counts, total, 0.95),
+            p99: quantile(&counts, total, 0.99),
+        }
+    }
+}
+
+/// Midpoint of the bucket holding the q-th sample.
+fn quantile(counts: &[u64], total: u64, q: f64) -> Duration {
+    let rank = ((total as f64 * q).ceil() as u64).max(1);
+    let mut seen = 0;
+    for (bucket, &count) in counts.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            let (lower, width) = bucket_bounds(bucket);
+            return Duration::from_nanos(lower + width / 2);
+        }
+    }
+    Duration::ZERO
+}
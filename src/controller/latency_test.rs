@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::controller::cache_metrics::ControllerMetrics;
+    use crate::controller::latency::{Histogram, Quantiles};
+
+    /// Asserts that a reported quantile is within the bucket error (1/32) of the exact one.
+    fn assert_close(name: &str, got: Duration, exact: u64) {
+        let diff = (got.as_nanos() as i128 - exact as i128).unsigned_abs();
+        assert!(diff * 32 <= exact as u128, "{}: got {:?}, exact {}ns", name, got, exact);
+    }
+
+    /// Exact quantile of sorted samples, by the same nearest-rank definition.
+    fn exact(sorted: &[u64], q: f64) -> u64 {
+        let rank = ((sorted.len() as f64 * q).ceil() as usize).max(1);
+        sorted[rank - 1]
+    }
+
+    /// Test that quantiles of synthetic durations spread over several orders of magnitude are
+    /// within bucket error of the exact ones.
+    #[test]
+    fn test_quantiles_within_bucket_error() {
+        let h = Histogram::default();
+        // Deterministic spread from 100us to ~1s, skewed towards the fast end.
+        let mut samples: Vec<u64> = (0..10_000u64)
+            .map(|i| 100_000 + (i * i * 7919) % 1_000_000_000 / (1 + i % 5))
+            .collect();
+        for &s in &samples {
+            h.record(s as i64);
+        }
+        samples.sort_unstable();
+
+        let q = h.take_quantiles();
+        assert_close("p50", q.p50, exact(&samples, 0.50));
+        assert_close("p95", q.p95, exact(&samples, 0.95));
+        assert_close("p99", q.p99, exact(&samples, 0.99));
+        assert!(q.p50 <= q.p95 && q.p95 <= q.p99);
+    }
+
+    /// Test that small, huge and negative durations are bucketed without panicking.
+    #[test]
+    fn test_extremes() {
+        let h = Histogram::default();
+        for nanos in [-5, 0, 1, 15, 16, 17] {
+            h.record(nanos);
+        }
+        assert_eq!(h.take_quantiles().p50, Duration::from_nanos(1));
+
+        h.record(i64::MAX);
+        let q = h.take_quantiles();
+        assert!(q.p99 >= Duration::from_secs(1024), "{:?}", q.p99);
+
+        h.record(7);
+        assert_eq!(h.take_quantiles().p99, Duration::from_nanos(7));
+    }
+
+    /// Test that a window is drained once taken.
+    #[test]
+    fn test_take_resets() {
+        let h = Histogram::default();
+        h.record(1_000_000);
+        assert_close("p50", h.take_quantiles().p50, 1_000_000);
+        assert_eq!(h.take_quantiles(), Quantiles::default());
+    }
+
+    /// Test that the controller keeps hits, misses and proxied requests apart.
+    #[test]
+    fn test_latencies_per_outcome() {
+        let m = ControllerMetrics::new();
+        for _ in 0..100 {
+            m.add_cache_duration(50_000, true);
+            m.add_cache_duration(20_000_000, false);
+            m.add_proxy_duration(80_000_000);
+        }
+
+        let l = m.take_latencies();
+        assert_close("hit", l.hit.p99, 50_000);
+        assert_close("miss", l.miss.p50, 20_000_000);
+        assert_close("proxy", l.proxy.p95, 80_000_000);
+        assert_eq!(m.take_latencies().hit, Quantiles::default());
+        assert_eq!(m.take_snapshot().cache_duration, 100 * 20_050_000);
+    }
+
+    /// Test that the average durations of a snapshot and the quantiles are read from the same
+    /// samples: a single latency shows up in both, and draining one does not drop the other.
+    #[test]
+    fn test_snapshot_durations_come_from_histograms() {
+        let m = ControllerMetrics::new();
+        m.add_proxy_duration(3_000_000);
+        m.add_proxy_duration(5_000_000);
+        m.add_cache_duration(-7, true);
+
+        let s = m.take_snapshot();
+        assert_eq!(s.proxy_duration, 8_000_000);
+        assert_eq!(s.cache_duration, 0, "a negative duration counts as zero in both");
+        assert_eq!(s.duration, 8_000_000);
+
+        let l = m.take_latencies();
+        assert_close("proxy p99", l.proxy.p99, 5_000_000);
+        assert_eq!(m.take_snapshot().duration, 0);
+    }
+}
@@ -4,15 +4,13 @@ use axum::{
     extract::{Query, State},
     http::StatusCode,
     response::{IntoResponse, Response},
-    routing::get,
-    Router,
 };
 use serde::Deserialize;
 use std::sync::Arc;
 
 use crate::config::{Config, ConfigTrait};
 use crate::governor::Governor;
-use crate::http::Controller;
+use crate::http::{Controller, Route};
 use crate::db::SVC_LIFETIME_MANAGER;
 
 /// Query parameters for scale endpoint.
@@ -42,14 +40,17 @@ impl LifetimeManagerController {
         }
     }
 
-    /// Gets the current lifetime manager configuration.
+    /// Gets the current lifetime manager state and configuration.
     async fn get(State(controller): State<Arc<Self>>) -> impl IntoResponse {
-        match controller.orchestrator.cfg(SVC_LIFETIME_MANAGER) {
-            Ok(cfg) => {
-                let freq = cfg.get_freq();
+        match controller.orchestrator.status(SVC_LIFETIME_MANAGER) {
+            Ok(status) => {
+                let (cfg, freq) = (&status.cfg, status.cfg.get_freq());
                 let json = serde_json::json!({
+                    "name": status.name,
+                    "state": status.state.label(),
                     "enabled": cfg.is_enabled(),
                     "replicas": cfg.get_replicas(),
+                    "active_replicas": status.active_replicas,
                     "frequency": {
                         "limit_per_sec": freq.get_rate_limit(),
                         "interval_ns": freq.get_tick_freq().as_nanos(),
@@ -253,7 +254,7 @@ impl LifetimeManagerController {
 }
 
 impl Controller for LifetimeManagerController {
-    fn add_route(&self, router: Router) -> Router {
+    fn describe(&self) -> Vec<Route> {
         let controller1 = Arc::new(self.clone());
         let controller2 = Arc::new(self.clone());
         let controller3 = Arc::new(self.clone());
@@ -262,63 +263,58 @@ impl Controller for LifetimeManagerController {
         let controller6 = Arc::new(self.clone());
         let controller7 = Arc::new(self.clone());
         let controller8 = Arc::new(self.clone());
-        router
-            .route(
-                "/advcache/lifetime-manager",
-                get(move || {
-                    let controller = controller1.clone();
-                    async move { Self::get(State(controller)).await }
-                }),
-            )
-            .route(
-                "/advcache/lifetime-manager/on",
-                get(move || {
-                    let controller = controller2.clone();
-                    async move { Self::on(State(controller)).await }
-                }),
-            )
-            .route(
-                "/advcache/lifetime-manager/off",
-                get(move || {
-                    let controller = controller3.clone();
-                    async move { Self::off(State(controller)).await }
-                }),
-            )
-            .route(
+        vec![
+            Route::get("/advcache/lifetime-manager", "Shows the lifetime manager state", move || {
+                let controller = controller1.clone();
+                async move { Self::get(State(controller)).await }
+            }),
+            Route::get("/advcache/lifetime-manager/on", "Enables the lifetime manager", move || {
+                let controller = controller2.clone();
+                async move { Self::on(State(controller)).await }
+            })
+            .mutating(),
+            Route::get("/advcache/lifetime-manager/off", "Disables the lifetime manager", move || {
+                let controller = controller3.clone();
+                async move { Self::off(State(controller)).await }
+            })
+            .mutating(),
+            Route::get(
                 "/advcache/lifetime-manager/scale",
-                get(move |query: Query<ScaleQuery>| {
+                "Scales the lifetime manager replicas",
+                move |query: Query<ScaleQuery>| {
                     let controller = controller4.clone();
                     async move { Self::scale(query, State(controller)).await }
-                }),
+                },
             )
-            .route(
+            .mutating(),
+            Route::get(
                 "/advcache/lifetime-manager/rate",
-                get(move |query: Query<RateQuery>| {
+                "Sets the lifetime manager rate limit",
+                move |query: Query<RateQuery>| {
                     let controller = controller5.clone();
                     async move { Self::rate(query, State(controller)).await }
-                }),
-            )
-            .route(
-                "/advcache/lifetime-manager/policy",
-                get(move || {
-                    let controller = controller6.clone();
-                    async move { Self::policy(State(controller)).await }
-                }),
+                },
             )
-            .route(
+            .mutating(),
+            Route::get("/advcache/lifetime-manager/policy", "Shows the expired entries policy", move || {
+                let controller = controller6.clone();
+                async move { Self::policy(State(controller)).await }
+            }),
+            Route::get(
                 "/advcache/lifetime-manager/policy/remove",
-                get(move || {
+                "Removes expired entries instead of refreshing them",
+                move || {
                     let controller = controller7.clone();
                     async move { Self::to_remove_policy(State(controller)).await }
-                }),
-            )
-            .route(
-                "/advcache/lifetime-manager/policy/refresh",
-                get(move || {
-                    let controller = controller8.clone();
-                    async move { Self::to_refresh_policy(State(controller)).await }
-                }),
+                },
             )
+            .destructive(),
+            Route::get("/advcache/lifetime-manager/policy/refresh", "Refreshes expired entries", move || {
+                let controller = controller8.clone();
+                async move { Self::to_refresh_policy(State(controller)).await }
+            })
+            .mutating(),
+        ]
     }
 }
 
@@ -10,16 +10,36 @@
 //! - Cache logical bytes: cache_memory_usage
 //! - Overhead: process_resident_memory_bytes - cache_memory_usage
 
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::OnceLock;
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::prelude::*;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-use crate::http::Controller;
+use parking_lot::Mutex;
+
+use crate::config;
+use crate::controller::unmatched::{self, MAX_PREFIXES, OVERFLOW_PREFIX};
+use crate::db::storage::audit::EvictionReason;
+use crate::db::storage::contention::LockMode;
+use crate::controller::cache::ThrottleReason;
+use crate::db::storage::storage::RefreshDiscard;
+use crate::http::server::limit::Listener;
+use crate::shutdown::signals::Signal;
+use crate::upstream::backend_hyper_impl::TimeoutPhase;
+use crate::http::{Controller, Route};
 
 pub const PROMETHEUS_METRICS_PATH: &str = "/metrics";
 
 static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_NEGATIVE_HITS: AtomicU64 = AtomicU64::new(0);
 static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+static COLLAPSED_REQUESTS: AtomicU64 = AtomicU64::new(0);
+static REJECTED_OVERSIZE: AtomicU64 = AtomicU64::new(0);
 static TOTAL_REQUESTS: AtomicU64 = AtomicU64::new(0);
 static ERRORED_REQUESTS: AtomicU64 = AtomicU64::new(0);
 static PROXIED_REQUESTS: AtomicU64 = AtomicU64::new(0);
@@ -47,13 +67,96 @@ static REFRESH_ERRORS: AtomicU64 = AtomicU64::new(0);
 static REFRESH_SCANS: AtomicU64 = AtomicU64::new(0);
 static REFRESH_HITS: AtomicU64 = AtomicU64::new(0);
 static REFRESH_MISS: AtomicU64 = AtomicU64::new(0);
+static REFRESH_PREWARMED: AtomicU64 = AtomicU64::new(0);
+static REFRESH_APPLIED: AtomicU64 = AtomicU64::new(0);
+static REFRESH_UNCHANGED: AtomicU64 = AtomicU64::new(0);
+static REFRESH_NOT_MODIFIED: AtomicU64 = AtomicU64::new(0);
+static REFRESH_DISCARDED: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+/// Requests refused to protect the origin, by [`ThrottleReason`].
+static REQUESTS_THROTTLED: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+
+static UPSTREAM_RESPONSE_TOO_LARGE: AtomicU64 = AtomicU64::new(0);
+static UPSTREAM_TIMEOUTS: [AtomicU64; 4] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+static CACHE_ENTRIES_CORRUPTED: AtomicU64 = AtomicU64::new(0);
+static ENTRY_TIMESTAMPS_CLAMPED: AtomicU64 = AtomicU64::new(0);
+static DUMP_RESTORED_ON_DEMAND: AtomicU64 = AtomicU64::new(0);
+static RULE_ENTRIES_RECONCILED: AtomicU64 = AtomicU64::new(0);
+static METRICS_AUTH_FAILURES: AtomicU64 = AtomicU64::new(0);
+static METRICS_AUTH_THROTTLED: AtomicU64 = AtomicU64::new(0);
+static BROWNOUT_ACTIVE: AtomicU64 = AtomicU64::new(0);
+static CACHE_BYPASS: AtomicU64 = AtomicU64::new(0);
+static BROWNOUT_SHED: AtomicU64 = AtomicU64::new(0);
+static AUDIT_RECORDS: AtomicU64 = AtomicU64::new(0);
+static AUDIT_WRITE_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+// Indexed by `Listener`.
+static HTTP_CONNECTIONS: [AtomicI64; 2] = [AtomicI64::new(0), AtomicI64::new(0)];
+static HTTP_CONNECTIONS_SHED: [AtomicU64; 2] = [AtomicU64::new(0), AtomicU64::new(0)];
+
+// Sampled shard lock waits, indexed by `LockMode`: count per upper bound of
+// SHARD_LOCK_WAIT_BOUNDS (not cumulative, the last slot is +Inf) and the sum in nanoseconds.
+const SHARD_LOCK_WAIT_BOUNDS: [Duration; 7] = [
+    Duration::from_micros(1),
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+];
+static SHARD_LOCK_WAIT_BUCKETS: [[AtomicU64; 8]; 2] = [const { [const { AtomicU64::new(0) }; 8] }; 2];
+static SHARD_LOCK_WAIT_SUM_NANOS: [AtomicU64; 2] = [AtomicU64::new(0), AtomicU64::new(0)];
+static SHARD_LOCK_SLOW_WRITES: AtomicU64 = AtomicU64::new(0);
+
+// Indexed by `Signal`.
+static PROCESS_SIGNALS: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+
+// Requests of methods other than GET on paths matching a rule, by method
+const NON_GET_METHODS: [&str; 7] = ["HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS", "OTHER"];
+static RULED_NON_GET_REQUESTS: [AtomicU64; 7] = [const { AtomicU64::new(0) }; 7];
+
+// Eviction audit events by rule path and reason
+static EVICTIONS_AUDITED: OnceLock<Mutex<HashMap<(String, EvictionReason), u64>>> = OnceLock::new();
+
+// Drain state by backend id
+static BACKENDS_DRAINED: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
+static BACKENDS_CONFIG_ERROR: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
 
-static STATUS_CODE_COUNTERS: OnceLock<Vec<AtomicU64>> = OnceLock::new();
+// Requests of ramped rules by rule path, rollout side (in = true) and result
+type RolloutKey = (String, bool, RolloutResult);
+static ROLLOUT_REQUESTS: OnceLock<Mutex<HashMap<RolloutKey, u64>>> = OnceLock::new();
+// Fills in flight and misses refused by rules with cache_value.max_concurrent_fills, by rule path
+static RULE_FILLS: OnceLock<Mutex<HashMap<String, RuleFills>>> = OnceLock::new();
 
-fn get_status_code_counters() -> &'static Vec<AtomicU64> {
-    STATUS_CODE_COUNTERS.get_or_init(|| {
-        (0..600).map(|_| AtomicU64::new(0)).collect()
-    })
+#[derive(Debug, Default, Clone, Copy)]
+struct RuleFills {
+    inflight: i64,
+    rejected: u64,
+}
+
+// Panics caught by the recover middleware by handler (matched route)
+static HANDLER_PANICS: OnceLock<Mutex<HashMap<String, u64>>> = OnceLock::new();
+
+/// How a request of a rule being ramped up (`cache_value.rollout_percent` under 100) was answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RolloutResult {
+    Hit,
+    Miss,
+    /// Outside the rollout: proxied without being stored.
+    Proxied,
+    /// A failed request or a 5xx response.
+    Error,
+}
+
+impl RolloutResult {
+    pub fn label(self) -> &'static str {
+        match self {
+            RolloutResult::Hit => "hit",
+            RolloutResult::Miss => "miss",
+            RolloutResult::Proxied => "proxied",
+            RolloutResult::Error => "error",
+        }
+    }
 }
 
 /// Increments cache hits counter.
@@ -61,6 +164,46 @@ pub fn inc_cache_hits(value: u64) {
     CACHE_HITS.fetch_add(value, Ordering::Relaxed);
 }
 
+/// Number of requests served from the cache.
+#[allow(dead_code)]
+pub fn cache_hits() -> u64 {
+    CACHE_HITS.load(Ordering::Relaxed)
+}
+
+/// Increments the hits answered with a negative entry (a stored 404 or 5xx), counted in the
+/// cache hits too.
+pub fn inc_cache_negative_hits() {
+    CACHE_NEGATIVE_HITS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of requests answered with a negative entry.
+#[allow(dead_code)]
+pub fn cache_negative_hits() -> u64 {
+    CACHE_NEGATIVE_HITS.load(Ordering::Relaxed)
+}
+
+/// Increments the counter of misses answered by the fill of another request for their key.
+pub fn inc_collapsed_requests() {
+    COLLAPSED_REQUESTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of misses answered by the fill of another request for their key.
+#[allow(dead_code)]
+pub fn collapsed_requests() -> u64 {
+    COLLAPSED_REQUESTS.load(Ordering::Relaxed)
+}
+
+/// Increments the counter of origin answers not stored for being over `max_body_size`.
+pub fn inc_rejected_oversize() {
+    REJECTED_OVERSIZE.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of origin answers not stored for being over `max_body_size`.
+#[allow(dead_code)]
+pub fn rejected_oversize() -> u64 {
+    REJECTED_OVERSIZE.load(Ordering::Relaxed)
+}
+
 /// Increments cache misses counter.
 pub fn inc_cache_misses(value: u64) {
     CACHE_MISSES.fetch_add(value, Ordering::Relaxed);
@@ -133,16 +276,322 @@ pub fn add_lifetime_stats(updated: u64, errors: u64, scans: u64, miss: u64, hits
     REFRESH_MISS.fetch_add(miss, Ordering::Relaxed);
 }
 
-/// Increments status code counter.
-pub fn inc_status_code(code: u16) {
-    if code < 600 {
-        let counters = get_status_code_counters();
-        if let Some(counter) = counters.get(code as usize) {
-            counter.fetch_add(1, Ordering::Relaxed);
+/// Adds to the counter of entries handed out for refresh ahead of their TTL.
+pub fn add_refresh_prewarmed(value: u64) {
+    REFRESH_PREWARMED.fetch_add(value, Ordering::Relaxed);
+}
+
+/// Number of entries handed out for refresh ahead of their TTL.
+#[allow(dead_code)]
+pub fn refresh_prewarmed() -> u64 {
+    REFRESH_PREWARMED.load(Ordering::Relaxed)
+}
+
+/// Increments the counter of refreshed payloads swapped into their entry.
+pub fn inc_refresh_applied() {
+    REFRESH_APPLIED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of refreshed payloads swapped into their entry.
+#[allow(dead_code)]
+pub fn refresh_applied() -> u64 {
+    REFRESH_APPLIED.load(Ordering::Relaxed)
+}
+
+/// Increments the counter of refreshes that brought the stored response again.
+pub fn inc_refresh_unchanged() {
+    REFRESH_UNCHANGED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of refreshes that brought the stored response again.
+#[allow(dead_code)]
+pub fn refresh_unchanged() -> u64 {
+    REFRESH_UNCHANGED.load(Ordering::Relaxed)
+}
+
+/// Increments the counter of refreshes the origin answered with 304.
+pub fn inc_refresh_not_modified() {
+    REFRESH_NOT_MODIFIED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of refreshes the origin answered with 304.
+#[allow(dead_code)]
+pub fn refresh_not_modified() -> u64 {
+    REFRESH_NOT_MODIFIED.load(Ordering::Relaxed)
+}
+
+/// Increments the counter of refreshed payloads thrown away for `reason`.
+pub fn inc_refresh_discarded(reason: RefreshDiscard) {
+    REFRESH_DISCARDED[reason as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of refreshed payloads thrown away for `reason`.
+#[allow(dead_code)]
+pub fn refresh_discarded(reason: RefreshDiscard) -> u64 {
+    REFRESH_DISCARDED[reason as usize].load(Ordering::Relaxed)
+}
+
+/// Increments the counter of upstream responses aborted for exceeding the body size limit.
+pub fn inc_upstream_response_too_large(value: u64) {
+    UPSTREAM_RESPONSE_TOO_LARGE.fetch_add(value, Ordering::Relaxed);
+}
+
+/// Increments the counter of upstream requests that timed out in `phase`.
+pub fn inc_upstream_timeouts(phase: TimeoutPhase) {
+    UPSTREAM_TIMEOUTS[phase as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of upstream requests that timed out in `phase`.
+#[allow(dead_code)]
+pub fn upstream_timeouts(phase: TimeoutPhase) -> u64 {
+    UPSTREAM_TIMEOUTS[phase as usize].load(Ordering::Relaxed)
+}
+
+/// Increments the counter of audit records written.
+pub fn inc_audit_records() {
+    AUDIT_RECORDS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Increments the counter of audit records dropped or not written.
+pub fn inc_audit_write_failures() {
+    AUDIT_WRITE_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of audit records dropped or not written.
+#[allow(dead_code)]
+pub fn audit_write_failures() -> u64 {
+    AUDIT_WRITE_FAILURES.load(Ordering::Relaxed)
+}
+
+/// Increments the counter of cached entries dropped for failing checksum verification.
+pub fn inc_cache_entries_corrupted() {
+    CACHE_ENTRIES_CORRUPTED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of cached entries dropped for failing checksum verification.
+#[allow(dead_code)]
+pub fn cache_entries_corrupted() -> u64 {
+    CACHE_ENTRIES_CORRUPTED.load(Ordering::Relaxed)
+}
+
+/// Increments the counter of entry timestamps clamped for lying too far ahead of the local clock.
+pub fn inc_entry_timestamps_clamped() {
+    ENTRY_TIMESTAMPS_CLAMPED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of entry timestamps clamped for lying too far ahead of the local clock.
+#[allow(dead_code)]
+pub fn entry_timestamps_clamped() -> u64 {
+    ENTRY_TIMESTAMPS_CLAMPED.load(Ordering::Relaxed)
+}
+
+/// Increments the counter of entries loaded from the dump on a miss while it was restored.
+#[cfg_attr(not(feature = "dump"), allow(dead_code))]
+pub fn inc_dump_restored_on_demand() {
+    DUMP_RESTORED_ON_DEMAND.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of entries loaded from the dump on a miss while it was restored.
+#[allow(dead_code)]
+pub fn dump_restored_on_demand() -> u64 {
+    DUMP_RESTORED_ON_DEMAND.load(Ordering::Relaxed)
+}
+
+/// Adds to the number of connections a listener is serving.
+pub fn add_http_connections(listener: Listener, delta: i64) {
+    HTTP_CONNECTIONS[listener as usize].fetch_add(delta, Ordering::Relaxed);
+}
+
+/// Increments the counter of connections a listener shed with 503.
+pub fn inc_http_connections_shed(listener: Listener) {
+    HTTP_CONNECTIONS_SHED[listener as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of connections a listener is serving.
+#[allow(dead_code)]
+pub fn http_connections(listener: Listener) -> i64 {
+    HTTP_CONNECTIONS[listener as usize].load(Ordering::Relaxed)
+}
+
+/// Records a sampled wait for a shard lock.
+pub fn observe_shard_lock_wait(mode: LockMode, waited: Duration) {
+    let bucket = SHARD_LOCK_WAIT_BOUNDS.iter().position(|bound| waited <= *bound).unwrap_or(SHARD_LOCK_WAIT_BOUNDS.len());
+    SHARD_LOCK_WAIT_BUCKETS[mode as usize][bucket].fetch_add(1, Ordering::Relaxed);
+    SHARD_LOCK_WAIT_SUM_NANOS[mode as usize].fetch_add(waited.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// Increments the counter of shard write locks waited on for longer than 1ms.
+pub fn inc_shard_lock_slow_writes() {
+    SHARD_LOCK_SLOW_WRITES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Increments the counter of OS signals received.
+pub fn inc_process_signals(signal: Signal) {
+    PROCESS_SIGNALS[signal as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of times the signal was received.
+#[allow(dead_code)]
+pub fn process_signals(signal: Signal) -> u64 {
+    PROCESS_SIGNALS[signal as usize].load(Ordering::Relaxed)
+}
+
+/// Increments the counter of requests on a path matching a rule of a method the rule does not
+/// cache, proxied as they are; methods outside the usual ones are counted as `OTHER`.
+pub fn inc_ruled_non_get_requests(method: &str) {
+    let slot = NON_GET_METHODS.iter().position(|m| m.eq_ignore_ascii_case(method)).unwrap_or(NON_GET_METHODS.len() - 1);
+    RULED_NON_GET_REQUESTS[slot].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of requests of the method proxied on paths matching a rule.
+#[allow(dead_code)]
+pub fn ruled_non_get_requests(method: &str) -> u64 {
+    NON_GET_METHODS
+        .iter()
+        .position(|m| *m == method)
+        .map_or(0, |slot| RULED_NON_GET_REQUESTS[slot].load(Ordering::Relaxed))
+}
+
+/// Increments the counter of audited evictions of a rule.
+pub fn inc_evictions_audited(rule: &str, reason: EvictionReason) {
+    let mut counters = EVICTIONS_AUDITED.get_or_init(Default::default).lock();
+    match counters.get_mut(&(rule.to_string(), reason)) {
+        Some(count) => *count += 1,
+        None => {
+            counters.insert((rule.to_string(), reason), 1);
+        }
+    }
+}
+
+/// Number of audited evictions of a rule.
+#[allow(dead_code)]
+pub fn evictions_audited(rule: &str, reason: EvictionReason) -> u64 {
+    EVICTIONS_AUDITED
+        .get()
+        .and_then(|c| c.lock().get(&(rule.to_string(), reason)).copied())
+        .unwrap_or(0)
+}
+
+/// Sets whether a backend is drained.
+pub fn set_backend_drained(backend: &str, drained: bool) {
+    BACKENDS_DRAINED.get_or_init(Default::default).lock().insert(backend.to_string(), drained);
+}
+
+/// Sets whether a backend fails for its config (see `upstream::misconfig`).
+pub fn set_upstream_config_error(backend: &str, misconfigured: bool) {
+    BACKENDS_CONFIG_ERROR.get_or_init(Default::default).lock().insert(backend.to_string(), misconfigured);
+}
+
+/// Adds `delta` to the upstream fills in flight of a rule with a fill cap.
+pub fn add_rule_fills_inflight(rule: &str, delta: i64) {
+    RULE_FILLS.get_or_init(Default::default).lock().entry(rule.to_string()).or_default().inflight += delta;
+}
+
+/// Increments the counter of misses of a rule refused after waiting for a fill slot.
+pub fn inc_rule_fills_rejected(rule: &str) {
+    RULE_FILLS.get_or_init(Default::default).lock().entry(rule.to_string()).or_default().rejected += 1;
+}
+
+/// Upstream fills in flight of a rule with a fill cap.
+#[allow(dead_code)]
+pub fn rule_fills_inflight(rule: &str) -> i64 {
+    RULE_FILLS.get().and_then(|f| f.lock().get(rule).map(|f| f.inflight)).unwrap_or(0)
+}
+
+/// Misses of a rule refused after waiting for a fill slot.
+#[allow(dead_code)]
+pub fn rule_fills_rejected(rule: &str) -> u64 {
+    RULE_FILLS.get().and_then(|f| f.lock().get(rule).map(|f| f.rejected)).unwrap_or(0)
+}
+
+/// Drain state of a backend as exported; `None` before it was first set.
+#[allow(dead_code)]
+pub fn backend_drained(backend: &str) -> Option<bool> {
+    BACKENDS_DRAINED.get().and_then(|b| b.lock().get(backend).copied())
+}
+
+/// Config error state of a backend as exported; `None` before it was first set.
+#[allow(dead_code)]
+pub fn upstream_config_error(backend: &str) -> Option<bool> {
+    BACKENDS_CONFIG_ERROR.get().and_then(|b| b.lock().get(backend).copied())
+}
+
+/// Increments the counter of requests of a ramped rule, inside or outside its rollout.
+pub fn inc_rollout_requests(rule: &str, in_rollout: bool, result: RolloutResult) {
+    let mut counters = ROLLOUT_REQUESTS.get_or_init(Default::default).lock();
+    match counters.get_mut(&(rule.to_string(), in_rollout, result)) {
+        Some(count) => *count += 1,
+        None => {
+            counters.insert((rule.to_string(), in_rollout, result), 1);
+        }
+    }
+}
+
+/// Number of requests of a ramped rule counted with the rollout side and result.
+#[allow(dead_code)]
+pub fn rollout_requests(rule: &str, in_rollout: bool, result: RolloutResult) -> u64 {
+    ROLLOUT_REQUESTS
+        .get()
+        .and_then(|c| c.lock().get(&(rule.to_string(), in_rollout, result)).copied())
+        .unwrap_or(0)
+}
+
+/// Adds entries re-pointed at their reloaded rule by lifetime.reconcile_rules.
+pub fn inc_rule_entries_reconciled(value: u64) {
+    RULE_ENTRIES_RECONCILED.fetch_add(value, Ordering::Relaxed);
+}
+
+/// Sets whether a brownout sheds cache misses.
+pub fn set_brownout_active(active: bool) {
+    BROWNOUT_ACTIVE.store(active as u64, Ordering::Relaxed);
+}
+
+/// Sets whether requests skip the cache (`/advcache/bypass/on|off`).
+pub fn set_cache_bypass(on: bool) {
+    CACHE_BYPASS.store(on as u64, Ordering::Relaxed);
+}
+
+/// Increments the counter of cache misses shed during a brownout.
+pub fn inc_brownout_shed() {
+    BROWNOUT_SHED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Increments the counter of requests refused to protect the origin.
+pub fn inc_requests_throttled(reason: ThrottleReason) {
+    REQUESTS_THROTTLED[reason as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Requests refused to protect the origin for the reason so far.
+#[allow(dead_code)]
+pub fn requests_throttled(reason: ThrottleReason) -> u64 {
+    REQUESTS_THROTTLED[reason as usize].load(Ordering::Relaxed)
+}
+
+/// Increments the counter of panics caught in a handler.
+pub fn inc_handler_panics(handler: &str) {
+    let mut counters = HANDLER_PANICS.get_or_init(Default::default).lock();
+    match counters.get_mut(handler) {
+        Some(count) => *count += 1,
+        None => {
+            counters.insert(handler.to_string(), 1);
         }
     }
 }
 
+/// Number of panics caught in the handler.
+#[allow(dead_code)]
+pub fn handler_panics(handler: &str) -> u64 {
+    HANDLER_PANICS
+        .get()
+        .and_then(|c| c.lock().get(handler).copied())
+        .unwrap_or(0)
+}
+
+/// Increments status code counter, under its class unless the code is in `metrics.status_codes`.
+pub fn inc_status_code(code: u16) {
+    crate::metrics::code::inc_status_code(code);
+}
+
 /// Renders manual metrics from atomic counters/gauges.
 fn render_manual_metrics() -> String {
     let mut output = String::new();
@@ -150,10 +599,22 @@ fn render_manual_metrics() -> String {
     output.push_str(&format!("# HELP cache_hits Total number of cache hits\n"));
     output.push_str(&format!("# TYPE cache_hits counter\n"));
     output.push_str(&format!("cache_hits {}\n", CACHE_HITS.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP cache_negative_hits Cache hits answered with a stored 404 or 5xx (cache_value.negative_ttl), counted in cache_hits too\n");
+    output.push_str("# TYPE cache_negative_hits counter\n");
+    output.push_str(&format!("cache_negative_hits {}\n", CACHE_NEGATIVE_HITS.load(Ordering::Relaxed)));
     
     output.push_str(&format!("# HELP cache_misses Total number of cache misses\n"));
     output.push_str(&format!("# TYPE cache_misses counter\n"));
     output.push_str(&format!("cache_misses {}\n", CACHE_MISSES.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP collapsed_requests_total Cache misses that waited for the fill of a concurrent miss of their key instead of reaching the origin, counted in cache_misses too\n");
+    output.push_str("# TYPE collapsed_requests_total counter\n");
+    output.push_str(&format!("collapsed_requests_total {}\n", COLLAPSED_REQUESTS.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP rejected_oversize_total Origin answers served but not stored because their decoded body is over the rule's max_body_size\n");
+    output.push_str("# TYPE rejected_oversize_total counter\n");
+    output.push_str(&format!("rejected_oversize_total {}\n", REJECTED_OVERSIZE.load(Ordering::Relaxed)));
     
     output.push_str(&format!("# HELP total Total number of requests\n"));
     output.push_str(&format!("# TYPE total counter\n"));
@@ -246,16 +707,244 @@ fn render_manual_metrics() -> String {
     output.push_str(&format!("# HELP refresh_miss Total refresh misses\n"));
     output.push_str(&format!("# TYPE refresh_miss counter\n"));
     output.push_str(&format!("refresh_miss {}\n", REFRESH_MISS.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP refresh_prewarmed Total entries refreshed ahead of their TTL within lifetime.prewarm.window\n");
+    output.push_str("# TYPE refresh_prewarmed counter\n");
+    output.push_str(&format!("refresh_prewarmed {}\n", REFRESH_PREWARMED.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP refresh_applied Total refreshed payloads that changed and were swapped into their entry\n");
+    output.push_str("# TYPE refresh_applied counter\n");
+    output.push_str(&format!("refresh_applied {}\n", REFRESH_APPLIED.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP refresh_unchanged Total refreshes that brought the stored response again, left in place\n");
+    output.push_str("# TYPE refresh_unchanged counter\n");
+    output.push_str(&format!("refresh_unchanged {}\n", REFRESH_UNCHANGED.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP refresh_not_modified Total refreshes the origin answered with 304 to the stored validators, left in place\n");
+    output.push_str("# TYPE refresh_not_modified counter\n");
+    output.push_str(&format!("refresh_not_modified {}\n", REFRESH_NOT_MODIFIED.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP refresh_discarded Refreshed payloads thrown away by reason (gone, memory, no_store)\n");
+    output.push_str("# TYPE refresh_discarded counter\n");
+    for reason in RefreshDiscard::ALL {
+        output.push_str(&format!(
+            "refresh_discarded{{reason=\"{}\"}} {}\n",
+            reason.label(),
+            REFRESH_DISCARDED[reason as usize].load(Ordering::Relaxed)
+        ));
+    }
     
-    output.push_str(&format!("# HELP resp_status_total Total number of HTTP responses by status code\n"));
-    output.push_str(&format!("# TYPE resp_status_total counter\n"));
-    let counters = get_status_code_counters();
-    for (code, counter) in counters.iter().enumerate() {
-        let count = counter.load(Ordering::Relaxed);
-        if count > 0 {
-            output.push_str(&format!("resp_status_total{{code=\"{}\"}} {}\n", code, count));
+    output.push_str("# HELP upstream_response_too_large Total upstream responses aborted for exceeding backend.max_response_size\n");
+    output.push_str("# TYPE upstream_response_too_large counter\n");
+    output.push_str(&format!("upstream_response_too_large {}\n", UPSTREAM_RESPONSE_TOO_LARGE.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP upstream_timeouts Upstream requests that timed out by phase (connect, ttfb, body, total)\n");
+    output.push_str("# TYPE upstream_timeouts counter\n");
+    for phase in TimeoutPhase::ALL {
+        output.push_str(&format!(
+            "upstream_timeouts{{phase=\"{}\"}} {}\n",
+            phase.label(),
+            UPSTREAM_TIMEOUTS[phase as usize].load(Ordering::Relaxed)
+        ));
+    }
+    
+    output.push_str("# HELP cache_entries_corrupted Total cached entries dropped for failing checksum verification (storage.verify_sample)\n");
+    output.push_str("# TYPE cache_entries_corrupted counter\n");
+    output.push_str(&format!("cache_entries_corrupted {}\n", CACHE_ENTRIES_CORRUPTED.load(Ordering::Relaxed)));
+    
+    output.push_str("# HELP entry_timestamps_clamped Total entry timestamps clamped to now for lying more than storage.max_clock_skew ahead\n");
+    output.push_str("# TYPE entry_timestamps_clamped counter\n");
+    output.push_str(&format!("entry_timestamps_clamped {}\n", ENTRY_TIMESTAMPS_CLAMPED.load(Ordering::Relaxed)));
+    
+    output.push_str("# HELP dump_restored_on_demand Total entries loaded from the dump on a miss during restore, sparing an origin request (data.dump.load_on_demand)\n");
+    output.push_str("# TYPE dump_restored_on_demand counter\n");
+    output.push_str(&format!("dump_restored_on_demand {}\n", DUMP_RESTORED_ON_DEMAND.load(Ordering::Relaxed)));
+    
+    output.push_str("# HELP cache_rule_entries_reconciled Total entries re-pointed at their reloaded rule (lifetime.reconcile_rules)\n");
+    output.push_str("# TYPE cache_rule_entries_reconciled counter\n");
+    output.push_str(&format!("cache_rule_entries_reconciled {}\n", RULE_ENTRIES_RECONCILED.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP metrics_auth_failures Total scrapes of /metrics refused for missing or wrong credentials (metrics.auth)\n");
+    output.push_str("# TYPE metrics_auth_failures counter\n");
+    output.push_str(&format!("metrics_auth_failures {}\n", METRICS_AUTH_FAILURES.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP metrics_auth_throttled Total scrapes of /metrics answered 429 while locked out after repeated auth failures\n");
+    output.push_str("# TYPE metrics_auth_throttled counter\n");
+    output.push_str(&format!("metrics_auth_throttled {}\n", METRICS_AUTH_THROTTLED.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP brownout_active Whether cache misses are shed by a brownout (cache.brownout), 0 or 1\n");
+    output.push_str("# TYPE brownout_active gauge\n");
+    output.push_str(&format!("brownout_active {}\n", BROWNOUT_ACTIVE.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP cache_bypass Whether requests skip the cache (/advcache/bypass/on), 0 or 1\n");
+    output.push_str("# TYPE cache_bypass gauge\n");
+    output.push_str(&format!("cache_bypass {}\n", CACHE_BYPASS.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP brownout_shed Total cache misses shed during a brownout\n");
+    output.push_str("# TYPE brownout_shed counter\n");
+    output.push_str(&format!("brownout_shed {}\n", BROWNOUT_SHED.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP requests_throttled Requests refused with upstream.throttle_status to protect the origin, by reason (upstream_protection, brownout, fill_cap); not counted as errors\n");
+    output.push_str("# TYPE requests_throttled counter\n");
+    for reason in ThrottleReason::ALL {
+        output.push_str(&format!(
+            "requests_throttled{{reason=\"{}\"}} {}\n",
+            reason.label(),
+            REQUESTS_THROTTLED[reason as usize].load(Ordering::Relaxed)
+        ));
+    }
+
+    output.push_str("# HELP audit_records Total admin mutations written to the audit log (audit.path)\n");
+    output.push_str("# TYPE audit_records counter\n");
+    output.push_str(&format!("audit_records {}\n", AUDIT_RECORDS.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP audit_write_failures Total audit records dropped on a full queue or failing to be written\n");
+    output.push_str("# TYPE audit_write_failures counter\n");
+    output.push_str(&format!("audit_write_failures {}\n", AUDIT_WRITE_FAILURES.load(Ordering::Relaxed)));
+
+    if let Some(counters) = EVICTIONS_AUDITED.get() {
+        let mut counters: Vec<_> = counters.lock().iter().map(|((rule, reason), n)| (rule.clone(), *reason, *n)).collect();
+        counters.sort_by(|a, b| (&a.0, a.1.label()).cmp(&(&b.0, b.1.label())));
+        output.push_str("# HELP cache_evictions_audited Evictions sampled by the eviction audit, by rule and reason\n");
+        output.push_str("# TYPE cache_evictions_audited counter\n");
+        for (rule, reason, n) in counters {
+            output.push_str(&format!("cache_evictions_audited{{rule=\"{}\",reason=\"{}\"}} {}\n", rule, reason.label(), n));
+        }
+    }
+
+    if let Some(counters) = ROLLOUT_REQUESTS.get() {
+        let mut counters: Vec<_> = counters
+            .lock()
+            .iter()
+            .map(|((rule, in_rollout, result), n)| (rule.clone(), *in_rollout, *result, *n))
+            .collect();
+        counters.sort_by(|a, b| (&a.0, !a.1, a.2.label()).cmp(&(&b.0, !b.1, b.2.label())));
+        output.push_str("# HELP cache_rollout_requests Requests of rules ramped up by cache_value.rollout_percent, by rule, rollout side and result\n");
+        output.push_str("# TYPE cache_rollout_requests counter\n");
+        for (rule, in_rollout, result, n) in counters {
+            output.push_str(&format!(
+                "cache_rollout_requests{{rule=\"{}\",rollout=\"{}\",result=\"{}\"}} {}\n",
+                rule,
+                if in_rollout { "in" } else { "out" },
+                result.label(),
+                n
+            ));
+        }
+    }
+
+    if let Some(counters) = HANDLER_PANICS.get() {
+        let mut counters: Vec<_> = counters.lock().iter().map(|(h, n)| (h.clone(), *n)).collect();
+        counters.sort();
+        output.push_str("# HELP http_handler_panics Panics caught by the recover middleware, by handler\n");
+        output.push_str("# TYPE http_handler_panics counter\n");
+        for (handler, n) in counters {
+            output.push_str(&format!("http_handler_panics{{handler=\"{}\"}} {}\n", handler, n));
+        }
+    }
+
+    if let Some(fills) = RULE_FILLS.get() {
+        let mut fills: Vec<_> = fills.lock().iter().map(|(rule, f)| (rule.clone(), *f)).collect();
+        fills.sort_by(|a, b| a.0.cmp(&b.0));
+        output.push_str("# HELP cache_rule_fills_inflight Upstream fills in flight of rules capped by cache_value.max_concurrent_fills, by rule\n");
+        output.push_str("# TYPE cache_rule_fills_inflight gauge\n");
+        for (rule, f) in &fills {
+            output.push_str(&format!("cache_rule_fills_inflight{{rule=\"{}\"}} {}\n", rule, f.inflight));
+        }
+        output.push_str("# HELP cache_rule_fills_rejected Misses refused after waiting cache_value.max_fill_wait for a fill slot, by rule\n");
+        output.push_str("# TYPE cache_rule_fills_rejected counter\n");
+        for (rule, f) in &fills {
+            output.push_str(&format!("cache_rule_fills_rejected{{rule=\"{}\"}} {}\n", rule, f.rejected));
+        }
+    }
+
+    if let Some(backends) = BACKENDS_DRAINED.get() {
+        let mut backends: Vec<_> = backends.lock().iter().map(|(id, drained)| (id.clone(), *drained)).collect();
+        backends.sort();
+        output.push_str("# HELP upstream_backend_drained Whether the backend is drained through /advcache/upstream/{id}/drain\n");
+        output.push_str("# TYPE upstream_backend_drained gauge\n");
+        for (id, drained) in backends {
+            output.push_str(&format!("upstream_backend_drained{{backend=\"{}\"}} {}\n", id, drained as u8));
+        }
+    }
+
+    if let Some(backends) = BACKENDS_CONFIG_ERROR.get() {
+        let mut backends: Vec<_> = backends.lock().iter().map(|(id, failing)| (id.clone(), *failing)).collect();
+        backends.sort();
+        output.push_str("# HELP upstream_config_error Whether the backend fails for its config (invalid URL, unresolvable host, TLS handshake)\n");
+        output.push_str("# TYPE upstream_config_error gauge\n");
+        for (id, failing) in backends {
+            output.push_str(&format!("upstream_config_error{{backend=\"{}\"}} {}\n", id, failing as u8));
+        }
+    }
+
+    output.push_str("# HELP http_connections Connections being served by listener\n");
+    output.push_str("# TYPE http_connections gauge\n");
+    for listener in Listener::ALL {
+        output.push_str(&format!(
+            "http_connections{{listener=\"{}\"}} {}\n",
+            listener.label(),
+            HTTP_CONNECTIONS[listener as usize].load(Ordering::Relaxed)
+        ));
+    }
+
+    output.push_str("# HELP http_connections_shed Connections answered 503 for exceeding the listener's connection limit\n");
+    output.push_str("# TYPE http_connections_shed counter\n");
+    for listener in Listener::ALL {
+        output.push_str(&format!(
+            "http_connections_shed{{listener=\"{}\"}} {}\n",
+            listener.label(),
+            HTTP_CONNECTIONS_SHED[listener as usize].load(Ordering::Relaxed)
+        ));
+    }
+
+    output.push_str("# HELP shard_lock_wait_seconds Sampled waits for a shard lock by mode (storage.lock_profiling)\n");
+    output.push_str("# TYPE shard_lock_wait_seconds histogram\n");
+    for mode in LockMode::ALL {
+        let buckets = &SHARD_LOCK_WAIT_BUCKETS[mode as usize];
+        let mut count = 0;
+        for (i, bucket) in buckets.iter().enumerate() {
+            count += bucket.load(Ordering::Relaxed);
+            let le = SHARD_LOCK_WAIT_BOUNDS.get(i).map_or("+Inf".to_string(), |bound| bound.as_secs_f64().to_string());
+            output.push_str(&format!("shard_lock_wait_seconds_bucket{{mode=\"{}\",le=\"{}\"}} {}\n", mode.label(), le, count));
         }
+        let sum = SHARD_LOCK_WAIT_SUM_NANOS[mode as usize].load(Ordering::Relaxed) as f64 / 1e9;
+        output.push_str(&format!("shard_lock_wait_seconds_sum{{mode=\"{}\"}} {}\n", mode.label(), sum));
+        output.push_str(&format!("shard_lock_wait_seconds_count{{mode=\"{}\"}} {}\n", mode.label(), count));
+    }
+
+    output.push_str("# HELP shard_lock_slow_writes Shard write locks waited on for longer than 1ms (storage.lock_profiling)\n");
+    output.push_str("# TYPE shard_lock_slow_writes counter\n");
+    output.push_str(&format!("shard_lock_slow_writes {}\n", SHARD_LOCK_SLOW_WRITES.load(Ordering::Relaxed)));
+
+    output.push_str("# HELP process_signals OS signals received by signal\n");
+    output.push_str("# TYPE process_signals counter\n");
+    for signal in Signal::ALL {
+        output.push_str(&format!(
+            "process_signals{{signal=\"{}\"}} {}\n",
+            signal.label(),
+            PROCESS_SIGNALS[signal as usize].load(Ordering::Relaxed)
+        ));
+    }
+
+    output.push_str("# HELP ruled_non_get_requests_total Requests proxied on a path matching a rule for a method it does not list in methods, by method\n");
+    output.push_str("# TYPE ruled_non_get_requests_total counter\n");
+    for (method, counter) in NON_GET_METHODS.iter().zip(&RULED_NON_GET_REQUESTS) {
+        output.push_str(&format!("ruled_non_get_requests_total{{method=\"{}\"}} {}\n", method, counter.load(Ordering::Relaxed)));
+    }
+
+    let report = unmatched::unmatched().report(MAX_PREFIXES);
+    output.push_str("# HELP unmatched_requests_total Requests to paths no rule matches, by the first two segments of the path; \"other\" past the tracked prefixes\n");
+    output.push_str("# TYPE unmatched_requests_total counter\n");
+    for stat in report.top {
+        // Client paths may carry characters a label value must escape.
+        let prefix = stat.prefix.replace('\\', "\\\\").replace('"', "\\\"");
+        output.push_str(&format!("unmatched_requests_total{{prefix=\"{}\"}} {}\n", prefix, stat.requests));
     }
+    if report.overflow > 0 {
+        output.push_str(&format!("unmatched_requests_total{{prefix=\"{}\"}} {}\n", OVERFLOW_PREFIX, report.overflow));
+    }
+
+    crate::metrics::code::status_codes().render(&mut output);
     
     let footprint = get_process_footprint_bytes().unwrap_or(0);
     output.push_str(&format!("# HELP process_footprint_bytes Process memory footprint in bytes (cross-platform)\n"));
@@ -272,8 +961,9 @@ fn render_manual_metrics() -> String {
 /// - Linux: Reads /proc/self/status and sums VmRSS + VmSwap (both in KB, converted to bytes)
 /// 
 /// Returns None on error or unsupported platform.
-/// All reads happen only in /metrics handler (scrape path), not in hot path.
-fn get_process_footprint_bytes() -> Option<u64> {
+/// All reads happen only in /metrics handler (scrape path) and around idle reclamation, not in
+/// hot path.
+pub(crate) fn get_process_footprint_bytes() -> Option<u64> {
     #[cfg(target_os = "macos")]
     {
         use std::mem::zeroed;
@@ -429,35 +1119,181 @@ pub fn metrics_text() -> String {
     out
 }
 
+/// Formats the settings of the rules in effect as gauges labeled by rule path: TTL, refresh
+/// and beta as reloaded last, rollout percent as adjusted at runtime. One series per rule and
+/// gauge, so the cardinality is the rule count.
+pub fn rule_settings_text(cfg: &config::Config) -> String {
+    let mut rules: Vec<_> = cfg.rules().into_iter().flat_map(HashMap::values).collect();
+    rules.sort_by(|a, b| a.path.cmp(&b.path));
+    let path = |rule: &config::Rule| rule.path.clone().unwrap_or_default();
+
+    let mut output = String::new();
+    output.push_str("# HELP cache_rule_ttl_seconds TTL of the rule's entries (refresh.ttl, or lifetime.ttl it inherits), by rule\n");
+    output.push_str("# TYPE cache_rule_ttl_seconds gauge\n");
+    for rule in &rules {
+        if let Some(ttl) = rule.refresh.as_ref().and_then(|r| r.ttl) {
+            output.push_str(&format!("cache_rule_ttl_seconds{{rule=\"{}\"}} {}\n", path(rule), ttl.as_secs_f64()));
+        }
+    }
+    output.push_str("# HELP cache_rule_refresh_enabled Whether the rule's entries are refreshed in the background, 0 or 1, by rule\n");
+    output.push_str("# TYPE cache_rule_refresh_enabled gauge\n");
+    for rule in &rules {
+        let enabled = rule.refresh.as_ref().is_some_and(|r| r.enabled);
+        output.push_str(&format!("cache_rule_refresh_enabled{{rule=\"{}\"}} {}\n", path(rule), enabled as u8));
+    }
+    output.push_str("# HELP cache_rule_beta Beta of the rule's probabilistic early refresh, by rule\n");
+    output.push_str("# TYPE cache_rule_beta gauge\n");
+    for rule in &rules {
+        if let Some(beta) = rule.refresh.as_ref().and_then(|r| r.beta) {
+            output.push_str(&format!("cache_rule_beta{{rule=\"{}\"}} {}\n", path(rule), beta));
+        }
+    }
+    output.push_str("# HELP cache_rule_rollout_percent Share of the rule's keys served through the cache (cache_value.rollout_percent), by rule\n");
+    output.push_str("# TYPE cache_rule_rollout_percent gauge\n");
+    for rule in &rules {
+        let percent = rule.cache_value.rollout_percent.get();
+        output.push_str(&format!("cache_rule_rollout_percent{{rule=\"{}\"}} {}\n", path(rule), percent));
+    }
+    output
+}
+
+/// Wrong credentials accepted per window before `/metrics` locks out.
+const AUTH_FAILURES_PER_WINDOW: u32 = 10;
+/// Window the auth failures are counted over, and how long a lockout lasts.
+const AUTH_FAILURE_WINDOW: Duration = Duration::from_secs(60);
+const AUTH_CHALLENGE: &str = r#"Basic realm="metrics", charset="UTF-8""#;
+
+/// Basic auth of `/metrics` (`metrics.auth`).
+struct BasicAuth {
+    username: Vec<u8>,
+    /// None when `password_env` could not be read: nobody is let in then.
+    password: Option<Vec<u8>>,
+    /// Start of the current window and the failures counted in it.
+    failures: Mutex<(Instant, u32)>,
+}
+
+impl BasicAuth {
+    fn new(cfg: &config::MetricsAuth) -> Self {
+        let password = match cfg.password() {
+            Ok(password) => Some(password.into_bytes()),
+            Err(e) => {
+                tracing::error!(component = "metrics", error = %e, "metrics endpoint refuses every scrape");
+                None
+            }
+        };
+        Self {
+            username: cfg.username.as_bytes().to_vec(),
+            password,
+            failures: Mutex::new((Instant::now(), 0)),
+        }
+    }
+
+    /// Seconds left of the lockout once the window's failures are used up.
+    fn locked_for(&self) -> Option<u64> {
+        let (started, failures) = *self.failures.lock();
+        let elapsed = started.elapsed();
+        if failures < AUTH_FAILURES_PER_WINDOW || elapsed >= AUTH_FAILURE_WINDOW {
+            return None;
+        }
+        Some((AUTH_FAILURE_WINDOW - elapsed).as_secs().max(1))
+    }
+
+    fn record_failure(&self) {
+        METRICS_AUTH_FAILURES.fetch_add(1, Ordering::Relaxed);
+        let mut failures = self.failures.lock();
+        if failures.0.elapsed() >= AUTH_FAILURE_WINDOW {
+            *failures = (Instant::now(), 0);
+        }
+        failures.1 += 1;
+    }
+
+    /// Whether the `Authorization` header carries the configured credentials.
+    fn is_authorized(&self, header: Option<&HeaderValue>) -> bool {
+        let Some(password) = self.password.as_deref() else {
+            return false;
+        };
+        let Some(decoded) = header
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Basic ").or_else(|| v.strip_prefix("basic ")))
+            .and_then(|v| BASE64_STANDARD.decode(v.trim()).ok())
+        else {
+            return false;
+        };
+        let Some(colon) = decoded.iter().position(|&b| b == b':') else {
+            return false;
+        };
+        // Both halves are always compared, so timing tells nothing about which one is wrong.
+        let username_ok = constant_time_eq(&decoded[..colon], &self.username);
+        let password_ok = constant_time_eq(&decoded[colon + 1..], password);
+        username_ok & password_ok
+    }
+
+    /// Answers the request when it may not see the metrics.
+    fn check(&self, headers: &HeaderMap) -> Option<Response> {
+        if let Some(retry_after) = self.locked_for() {
+            METRICS_AUTH_THROTTLED.fetch_add(1, Ordering::Relaxed);
+            return Some(
+                (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, retry_after.to_string())]).into_response(),
+            );
+        }
+        if self.is_authorized(headers.get(header::AUTHORIZATION)) {
+            return None;
+        }
+        self.record_failure();
+        Some((StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, AUTH_CHALLENGE)]).into_response())
+    }
+}
+
+/// Compares in time that depends on the length of `given` only, not on where it differs.
+fn constant_time_eq(given: &[u8], expected: &[u8]) -> bool {
+    let mut diff = given.len() ^ expected.len();
+    for (i, &b) in given.iter().enumerate() {
+        diff |= (b ^ expected.get(i).copied().unwrap_or(0)) as usize;
+    }
+    diff == 0
+}
+
 /// PrometheusMetricsController handles Prometheus metrics endpoint.
-pub struct PrometheusMetricsController;
+#[derive(Clone)]
+pub struct PrometheusMetricsController {
+    auth: Option<Arc<BasicAuth>>,
+    /// Rules reported by [`rule_settings_text`]; clones of a config share its reloads.
+    cfg: Arc<config::Config>,
+}
 
 impl PrometheusMetricsController {
-    /// Creates a new Prometheus metrics controller.
-    pub fn new() -> Self {
-        Self
+    /// Creates a new Prometheus metrics controller, requiring basic auth with `metrics.auth`.
+    pub fn new(cfg: config::Config) -> Self {
+        Self {
+            auth: cfg.cache.metrics.as_ref().and_then(|m| m.auth.as_ref()).map(|auth| Arc::new(BasicAuth::new(auth))),
+            cfg: Arc::new(cfg),
+        }
     }
 
     /// Handles the metrics request.
-    async fn get_metrics() -> impl IntoResponse {
-        let metrics_text = metrics_text();
-        
+    async fn get_metrics(self, headers: HeaderMap) -> Response {
+        if let Some(refused) = self.auth.as_ref().and_then(|auth| auth.check(&headers)) {
+            return refused;
+        }
+
+        let mut text = metrics_text();
+        text.push_str(&rule_settings_text(&self.cfg));
         (
             StatusCode::OK,
             [("content-type", "text/plain; charset=utf-8")],
-            metrics_text,
+            text,
         )
-    }
-}
-
-impl Default for PrometheusMetricsController {
-    fn default() -> Self {
-        Self::new()
+            .into_response()
     }
 }
 
 impl Controller for PrometheusMetricsController {
-    fn add_route(&self, router: Router) -> Router {
-        router.route(PROMETHEUS_METRICS_PATH, get(Self::get_metrics))
+    fn describe(&self) -> Vec<Route> {
+        // `get` answers HEAD too, without the body: some probes preflight with it.
+        let controller = self.clone();
+        vec![Route::get(PROMETHEUS_METRICS_PATH, "Prometheus metrics", move |headers: HeaderMap| {
+            controller.clone().get_metrics(headers)
+        })
+        .auth(self.auth.is_some())]
     }
 }
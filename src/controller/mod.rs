@@ -1,33 +1,93 @@
 // HTTP API controllers for cache management endpoints.
 
 pub mod admission;
+pub mod audit;
 pub mod backend;
+pub mod brownout;
 pub mod bypass;
 pub mod cache;
+pub mod cache_metrics;
+#[cfg(feature = "debug-endpoints")]
+pub mod capture;
 pub mod clear;
+pub mod collapse;
 pub mod compression;
 pub mod config;
 pub mod controller;
+pub mod drain;
+pub mod errors;
 pub mod evictor;
+pub mod explain;
+pub mod fill_limit;
 pub mod get;
+pub mod health;
+pub mod index;
 pub mod invalidator;
+pub mod jobs;
+pub mod latency;
 pub mod lifetimer;
 pub mod metrics;
 pub mod probe;
+pub mod purge;
+pub mod reclaim;
+pub mod resize;
+pub mod rollout;
+pub mod shards;
+pub mod shutdown;
+pub mod stats;
+#[cfg(feature = "tracing-otel")]
 pub mod traces;
+pub mod unmatched;
+pub mod walks;
+pub mod whatif;
+
+#[cfg(test)]
+mod cache_metrics_test;
+#[cfg(test)]
+mod clear_test;
+#[cfg(test)]
+mod errors_test;
+#[cfg(test)]
+mod health_test;
+#[cfg(test)]
+mod latency_test;
+#[cfg(test)]
+mod reclaim_test;
+#[cfg(test)]
+mod unmatched_test;
 
 // Re-export controller types for convenience
 pub use admission::AdmissionController;
+pub use audit::AuditController;
 pub use backend::ChangeBackendPolicyController;
+pub use brownout::BrownoutController;
 pub use bypass::BypassOnOffController;
 pub use cache::CacheProxyController;
+#[cfg(feature = "debug-endpoints")]
+pub use capture::DebugCaptureController;
 pub use clear::ClearController;
 pub use compression::HttpCompressionController;
-pub use config::ShowConfigController;
+pub use config::{ConfigDiffController, ConfigReloadController, ShowConfigController};
+pub use drain::BackendDrainController;
+pub use errors::ErrorsController;
 pub use evictor::EvictionController;
+pub use explain::ExplainController;
 pub use get::GetController;
+pub use health::HealthController;
+pub use index::IndexController;
 pub use invalidator::InvalidateController;
+pub use jobs::JobsController;
 pub use lifetimer::LifetimeManagerController;
 pub use metrics::PrometheusMetricsController;
-pub use probe::LivenessProbeController;
+pub use probe::{LivenessProbeController, ReadinessProbeController};
+pub use purge::PurgeController;
+pub use reclaim::IdleReclaimController;
+pub use resize::StorageResizeController;
+pub use rollout::RolloutController;
+pub use shards::ShardsController;
+pub use shutdown::ShutdownReportController;
+pub use stats::StatsController;
+#[cfg(feature = "tracing-otel")]
 pub use traces::TracesController;
+pub use walks::WalksController;
+pub use whatif::WhatIfController;
@@ -1,15 +1,34 @@
-//! Liveness probe controller.
+//! Liveness and readiness probe controllers.
+//!
+//! `/advcache/ready` aggregates what the instance needs to take traffic, next to the readiness
+//! of the registered services: the dump restored on start is loaded, the upstream is up and no
+//! worker failed or stopped. The upstream state is the one of its health checks, which flips
+//! only after their thresholds of failed or passed probes in a row.
 
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
-    routing::get,
-    Router,
 };
+use serde::Serialize;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::http::Controller;
+use crate::config;
+use crate::db::{Storage, SVC_EVICTOR, SVC_LIFETIME_MANAGER};
+use crate::governor::api::ServiceState;
+use crate::governor::Governor;
+use crate::http::{Controller, Route};
 use crate::liveness;
+use crate::upstream::Upstream;
+
+pub const CHECK_APP: &str = "app_not_ready";
+pub const CHECK_DUMP: &str = "dump_loading";
+pub const CHECK_UPSTREAM: &str = "upstream_down";
+pub const CHECK_WORKER: &str = "worker_down";
+pub const CHECK_TIMEOUT: &str = "timeout";
+
+/// Workers readiness watches, when registered.
+const WATCHED_WORKERS: [&str; 2] = [SVC_EVICTOR, SVC_LIFETIME_MANAGER];
 
 const SUCCESS_RESPONSE: &str = r#"{
   "status": 200,
@@ -21,51 +40,55 @@ const FAILED_RESPONSE: &str = r#"{
   "message": "I'm tired :("
 }"#;
 
-/// LivenessProbeController handles Kubernetes liveness probes.
+/// LivenessProbeController handles Kubernetes liveness and readiness probes.
 pub struct LivenessProbeController {
     probe: Arc<dyn liveness::Prober>,
+    liveness_path: String,
+    readiness_path: String,
 }
 
 impl LivenessProbeController {
-    /// Creates a new liveness probe controller.
-    pub fn new(probe: Arc<dyn liveness::Prober>) -> Self {
-        Self { probe }
+    /// Creates a new probe controller serving the paths of `k8s.probe`, or the default ones.
+    pub fn new(probe: Arc<dyn liveness::Prober>, cfg: Option<&config::Probe>) -> Self {
+        Self {
+            probe,
+            liveness_path: cfg.map_or(config::Probe::DEFAULT_LIVENESS_PATH, |c| c.liveness_path()).to_string(),
+            readiness_path: cfg.map_or(config::Probe::DEFAULT_READINESS_PATH, |c| c.readiness_path()).to_string(),
+        }
     }
 
-    /// Handles the probe request.
+    /// Handles the liveness probe request.
     async fn probe(&self) -> Response {
-        if self.probe.is_alive() {
-            (StatusCode::OK, SUCCESS_RESPONSE).into_response()
-        } else {
-            (StatusCode::SERVICE_UNAVAILABLE, FAILED_RESPONSE).into_response()
-        }
+        respond(self.probe.is_alive())
+    }
+
+    /// Handles the readiness probe request.
+    async fn ready(&self) -> Response {
+        respond(self.probe.is_ready())
+    }
+}
+
+fn respond(ok: bool) -> Response {
+    if ok {
+        (StatusCode::OK, SUCCESS_RESPONSE).into_response()
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, FAILED_RESPONSE).into_response()
     }
 }
 
 impl Controller for LivenessProbeController {
-    fn add_route(&self, router: Router) -> Router {
-        let probe_controller = self.clone();
-        router
-            .route(
-                "/k8s/probe",
-                get({
-                    let controller = probe_controller.clone();
-                    move || {
-                        let controller = controller.clone();
-                        async move { controller.probe().await }
-                    }
-                }),
-            )
-            .route(
-                "/healthz",
-                get({
-                    let controller = probe_controller.clone();
-                    move || {
-                        let controller = controller.clone();
-                        async move { controller.probe().await }
-                    }
-                }),
-            )
+    fn describe(&self) -> Vec<Route> {
+        let (liveness, readiness) = (self.clone(), self.clone());
+        vec![
+            Route::get(self.liveness_path.clone(), "Liveness probe", move || {
+                let controller = liveness.clone();
+                async move { controller.probe().await }
+            }),
+            Route::get(self.readiness_path.clone(), "Readiness probe", move || {
+                let controller = readiness.clone();
+                async move { controller.ready().await }
+            }),
+        ]
     }
 }
 
@@ -73,6 +96,127 @@ impl Clone for LivenessProbeController {
     fn clone(&self) -> Self {
         Self {
             probe: self.probe.clone(),
+            liveness_path: self.liveness_path.clone(),
+            readiness_path: self.readiness_path.clone(),
+        }
+    }
+}
+
+/// A readiness check that fails, with the worker it is about.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Failing {
+    pub check: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker: Option<String>,
+}
+
+impl Failing {
+    fn new(check: &'static str) -> Self {
+        Self { check, worker: None }
+    }
+}
+
+/// Readiness response body.
+#[derive(Debug, Serialize)]
+struct ReadinessResponse {
+    status: &'static str,
+    failing: Vec<Failing>,
+}
+
+/// ReadinessProbeController tells whether the instance should be put into rotation: 503 until
+/// the dump restored on start is loaded, then while the upstream is down, a worker failed or
+/// stopped, or a registered service is not ready.
+pub struct ReadinessProbeController {
+    probe: Arc<dyn liveness::Prober>,
+    db: Arc<dyn Storage>,
+    backend: Arc<dyn Upstream>,
+    governor: Arc<dyn Governor>,
+    timeout: Duration,
+}
+
+impl ReadinessProbeController {
+    /// Creates a readiness controller bounded by `k8s.probe.readiness_timeout`.
+    pub fn new(
+        probe: Arc<dyn liveness::Prober>,
+        db: Arc<dyn Storage>,
+        backend: Arc<dyn Upstream>,
+        governor: Arc<dyn Governor>,
+        cfg: Option<&config::Probe>,
+    ) -> Self {
+        Self {
+            probe,
+            db,
+            backend,
+            governor,
+            timeout: cfg.map_or(config::Probe::DEFAULT_READINESS_TIMEOUT, |c| c.readiness_timeout()),
+        }
+    }
+
+    /// Checks failing right now. While the dump is loading nothing else is looked at.
+    pub fn failing(&self) -> Vec<Failing> {
+        if !self.db.is_dump_loaded() {
+            return vec![Failing::new(CHECK_DUMP)];
+        }
+        let mut failing = Vec::new();
+        if !self.probe.is_ready() {
+            failing.push(Failing::new(CHECK_APP));
+        }
+        if !self.backend.is_alive() {
+            failing.push(Failing::new(CHECK_UPSTREAM));
+        }
+        for name in WATCHED_WORKERS {
+            let Ok(status) = self.governor.status(name) else {
+                continue;
+            };
+            if matches!(status.state, ServiceState::Failed | ServiceState::Stopped) {
+                failing.push(Failing { check: CHECK_WORKER, worker: Some(name.to_string()) });
+            }
+        }
+        failing
+    }
+
+    /// 200 while every check passes, 503 listing the failing ones otherwise, or when they are
+    /// not gathered within the timeout.
+    async fn ready(&self) -> Response {
+        let controller = self.clone();
+        let checks = tokio::task::spawn_blocking(move || controller.failing());
+        let failing = match tokio::time::timeout(self.timeout, checks).await {
+            Ok(Ok(failing)) => failing,
+            _ => vec![Failing::new(CHECK_TIMEOUT)],
+        };
+        let (status, label) = if failing.is_empty() {
+            (StatusCode::OK, "ready")
+        } else {
+            (StatusCode::SERVICE_UNAVAILABLE, "not_ready")
+        };
+        let resp = ReadinessResponse { status: label, failing };
+        (
+            status,
+            [("content-type", "application/json; charset=utf-8")],
+            serde_json::to_string(&resp).unwrap_or_default(),
+        )
+            .into_response()
+    }
+}
+
+impl Controller for ReadinessProbeController {
+    fn describe(&self) -> Vec<Route> {
+        let controller = self.clone();
+        vec![Route::get("/advcache/ready", "Readiness: dump loaded, upstream up, workers running", move || {
+            let controller = controller.clone();
+            async move { controller.ready().await }
+        })]
+    }
+}
+
+impl Clone for ReadinessProbeController {
+    fn clone(&self) -> Self {
+        Self {
+            probe: self.probe.clone(),
+            db: self.db.clone(),
+            backend: self.backend.clone(),
+            governor: self.governor.clone(),
+            timeout: self.timeout,
         }
     }
 }
@@ -0,0 +1,151 @@
+//! Cache purge controller.
+//!
+//! `POST /advcache/purge?prefix=/api/v1/items` removes every entry whose path starts with the
+//! prefix, unlike `/advcache/invalidate` which matches one path and its query params. Rules
+//! match request paths exactly, so the rule path of an entry is its request path too. A
+//! `PURGE` request on the main port removes the single entry a GET of it would be served (see
+//! `CacheProxyController`).
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::capture::{self, Op};
+use crate::db::jobs::{JobState, Outcome, ShardJob};
+use crate::db::walks::OP_PURGE;
+use crate::db::Storage;
+use crate::http::{Controller, Route};
+use crate::middleware::audit_middleware::Affected;
+use crate::model::Entry;
+
+/// Query parameters for purging by prefix.
+#[derive(Deserialize)]
+struct PurgeQuery {
+    prefix: Option<String>,
+}
+
+/// Outcome of a purge.
+#[derive(Debug, Serialize, Default)]
+pub(crate) struct PurgeResponse {
+    pub success: bool,
+    /// Entries removed by this purge; those another one removed first are not counted.
+    pub removed: i64,
+    /// Weight of the removed entries.
+    pub freed_bytes: i64,
+    /// Id of the job run, see `/advcache/jobs/{id}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl PurgeResponse {
+    pub(crate) fn into_response(self, status: StatusCode) -> Response {
+        let removed = self.removed.max(0) as u64;
+        (
+            status,
+            Extension(Affected(removed)),
+            [("content-type", "application/json")],
+            serde_json::to_string(&self).unwrap_or_default(),
+        )
+            .into_response()
+    }
+
+    fn error(status: StatusCode, error: String) -> Response {
+        Self { error: Some(error), ..Default::default() }.into_response(status)
+    }
+}
+
+/// Removes the entry and reports what it freed, `None` when it was already gone: the shard
+/// removal is what decides, so overlapping purges count each entry once.
+pub(crate) fn purge_entry(db: &dyn Storage, entry: &Entry) -> Option<i64> {
+    match db.remove(entry) {
+        (freed_bytes, true) => {
+            capture::record(entry.key(), Op::Invalidate, "purged");
+            Some(freed_bytes)
+        }
+        (_, false) => None,
+    }
+}
+
+/// PurgeController removes the entries under a path prefix.
+#[derive(Clone)]
+pub struct PurgeController {
+    db: Arc<dyn Storage>,
+}
+
+impl PurgeController {
+    /// Creates a new purge controller.
+    pub fn new(db: Arc<dyn Storage>) -> Self {
+        Self { db }
+    }
+
+    /// Purges the entries under the `prefix` query param.
+    async fn purge(controller: Arc<Self>, Query(params): Query<PurgeQuery>) -> Response {
+        let Some(prefix) = params.prefix.filter(|p| p.starts_with('/')) else {
+            return PurgeResponse::error(StatusCode::BAD_REQUEST, "'prefix' query param starting with '/' is required".to_string());
+        };
+        let Some(jobs) = controller.db.jobs() else {
+            return PurgeResponse::error(StatusCode::SERVICE_UNAVAILABLE, "storage does not run jobs".to_string());
+        };
+
+        let matched_prefix = prefix.clone();
+        let matches = move |entry: &Entry| entry.rule().path.as_deref().is_some_and(|path| path.starts_with(&matched_prefix));
+        let freed = Arc::new(AtomicI64::new(0));
+        let freed_by_job = freed.clone();
+        let remove = move |db: &dyn Storage, entry: &Entry| match purge_entry(db, entry) {
+            Some(freed_bytes) => {
+                freed_by_job.fetch_add(freed_bytes, Ordering::Relaxed);
+                Outcome::Acted
+            }
+            None => Outcome::Skipped,
+        };
+
+        // Jobs queue behind other admin walks, giving up after `storage.admin_walk_timeout`.
+        let job = jobs.submit(controller.db.clone(), ShardJob::new(OP_PURGE, prefix.clone(), matches, remove));
+        let record = match job.wait().await {
+            Ok(record) => record,
+            Err(e) => {
+                tracing::warn!(component = "purge", prefix = %prefix, error = %e, "purge not run");
+                let resp = PurgeResponse { job: Some(job.id()), error: Some(e.to_string()), ..Default::default() };
+                return resp.into_response(StatusCode::SERVICE_UNAVAILABLE);
+            }
+        };
+        let cancelled = record.state == JobState::Cancelled;
+        let resp = PurgeResponse {
+            success: !cancelled,
+            removed: record.acted as i64,
+            freed_bytes: freed.load(Ordering::Relaxed),
+            job: Some(record.id),
+            error: cancelled.then(|| "cancelled".to_string()),
+        };
+
+        tracing::info!(
+            component = "purge",
+            prefix = %prefix,
+            removed = resp.removed,
+            freed_bytes = resp.freed_bytes,
+            "cache entries purged"
+        );
+        resp.into_response(StatusCode::OK)
+    }
+}
+
+impl Controller for PurgeController {
+    fn describe(&self) -> Vec<Route> {
+        let controller = Arc::new(self.clone());
+        vec![Route::post(
+            "/advcache/purge",
+            "Removes the entries whose path starts with a prefix",
+            move |query: Query<PurgeQuery>| Self::purge(controller.clone(), query),
+        )
+        .destructive()]
+    }
+}
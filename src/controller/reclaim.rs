@@ -0,0 +1,214 @@
+//! Idle reclamation: hands memory and connections back after prolonged low traffic.
+//!
+//! The request rate of the cache controller's metrics tick is fed to [`IdleReclaim::observe`].
+//! Once it has stayed below `runtime.idle_reclaim.max_rps` for `idle_for`, the idle upstream
+//! connections are closed, the per-thread key scratch buffers released and, in `jemalloc`
+//! builds, the free pages of the allocator arenas purged. This happens once per idle period;
+//! traffic above the threshold re-arms it. `/advcache/reclaim/run` reclaims on demand.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{http::StatusCode, response::IntoResponse};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::info;
+
+use crate::config;
+use crate::controller::metrics;
+use crate::http::{Controller, Route};
+use crate::model;
+use crate::upstream::Upstream;
+
+const DEFAULT_MAX_RPS: f64 = 1.0;
+const DEFAULT_IDLE_FOR: Duration = Duration::from_secs(10 * 60);
+
+/// What a reclamation did.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// `idle` for the tick, `manual` for the endpoint.
+    pub trigger: &'static str,
+    pub footprint_before: u64,
+    pub footprint_after: u64,
+    /// Estimate of the memory handed back: the drop of the process footprint, 0 when it grew.
+    pub reclaimed_bytes: u64,
+    /// Whether the allocator arenas were purged (`jemalloc` builds only).
+    pub allocator_purged: bool,
+}
+
+#[derive(Default)]
+struct State {
+    /// Start of the current run of ticks below the threshold.
+    idle_since: Option<Instant>,
+    /// Whether the current idle period was reclaimed already.
+    reclaimed: bool,
+    last: Option<Report>,
+}
+
+/// Idle detection shared by the cache controller, which feeds it, and the admin endpoint.
+pub struct IdleReclaim {
+    cfg: Option<config::IdleReclaim>,
+    upstream: Arc<dyn Upstream>,
+    state: Mutex<State>,
+}
+
+impl IdleReclaim {
+    /// Creates an idle reclamation over `runtime.idle_reclaim`; without it (or disabled) it only
+    /// runs on demand.
+    pub fn new(cfg: Option<&config::IdleReclaim>, upstream: Arc<dyn Upstream>) -> Self {
+        Self {
+            cfg: cfg.filter(|r| r.enabled).cloned(),
+            upstream,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Evaluates the request rate sampled at `now` and returns whether to reclaim.
+    pub fn observe(&self, rps: f64, now: Instant) -> bool {
+        let Some(cfg) = self.cfg.as_ref() else {
+            return false;
+        };
+        let mut state = self.state.lock();
+        if rps >= cfg.max_rps.unwrap_or(DEFAULT_MAX_RPS) {
+            state.idle_since = None;
+            state.reclaimed = false;
+            return false;
+        }
+
+        let since = *state.idle_since.get_or_insert(now);
+        if state.reclaimed || now.duration_since(since) < cfg.idle_for.unwrap_or(DEFAULT_IDLE_FOR) {
+            return false;
+        }
+        state.reclaimed = true;
+        true
+    }
+
+    /// Closes the idle upstream connections, releases the scratch buffers and purges the
+    /// allocator, then logs and returns the estimate of the memory handed back.
+    pub fn reclaim(&self, trigger: &'static str) -> Report {
+        let footprint_before = metrics::get_process_footprint_bytes().unwrap_or(0);
+
+        self.upstream.drop_idle_connections();
+        model::entry::release_key_scratch();
+        let allocator_purged = purge_allocator();
+
+        let footprint_after = metrics::get_process_footprint_bytes().unwrap_or(0);
+        let report = Report {
+            trigger,
+            footprint_before,
+            footprint_after,
+            reclaimed_bytes: footprint_before.saturating_sub(footprint_after),
+            allocator_purged,
+        };
+        info!(
+            component = "idle-reclaim",
+            event = "idle_reclaimed",
+            trigger = trigger,
+            reclaimed_bytes = report.reclaimed_bytes,
+            footprint_bytes = footprint_after,
+            allocator_purged = allocator_purged,
+            "reclaimed idle connections and memory"
+        );
+        self.state.lock().last = Some(report.clone());
+        report
+    }
+
+    /// Configuration in effect, whether the current idle period was reclaimed and the last
+    /// reclamation.
+    pub fn status(&self) -> Status {
+        let state = self.state.lock();
+        Status {
+            enabled: self.cfg.is_some(),
+            max_rps: self.cfg.as_ref().map(|r| r.max_rps.unwrap_or(DEFAULT_MAX_RPS)),
+            idle_for_secs: self.cfg.as_ref().map(|r| r.idle_for.unwrap_or(DEFAULT_IDLE_FOR).as_secs()),
+            idle: state.idle_since.is_some(),
+            reclaimed: state.reclaimed,
+            last: state.last.clone(),
+        }
+    }
+}
+
+/// Purges the free pages of every jemalloc arena.
+#[cfg(all(feature = "jemalloc", not(target_env = "msvc")))]
+fn purge_allocator() -> bool {
+    // `arena.<MALLCTL_ARENAS_ALL>.purge`: 4096 addresses every arena; it takes and returns nothing.
+    let name = b"arena.4096.purge\0";
+    // SAFETY: the name is NUL terminated and the control reads and writes no value.
+    let rc = unsafe {
+        tikv_jemalloc_sys::mallctl(
+            name.as_ptr() as *const _,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    rc == 0
+}
+
+#[cfg(not(all(feature = "jemalloc", not(target_env = "msvc"))))]
+fn purge_allocator() -> bool {
+    false
+}
+
+/// Idle reclamation response body.
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    pub enabled: bool,
+    pub max_rps: Option<f64>,
+    pub idle_for_secs: Option<u64>,
+    /// Whether the rate is below the threshold.
+    pub idle: bool,
+    /// Whether the current idle period was reclaimed already.
+    pub reclaimed: bool,
+    pub last: Option<Report>,
+}
+
+/// IdleReclaimController shows the idle reclamation and runs it on demand.
+pub struct IdleReclaimController {
+    reclaim: Arc<IdleReclaim>,
+}
+
+impl IdleReclaimController {
+    /// Creates an idle reclamation controller over the state of the cache controller.
+    pub fn new(reclaim: Arc<IdleReclaim>) -> Self {
+        Self { reclaim }
+    }
+
+    /// Reclaims (when asked to) and returns the resulting state as JSON.
+    async fn handle(reclaim: Arc<IdleReclaim>, run: bool) -> impl IntoResponse {
+        if run {
+            reclaim.reclaim("manual");
+        }
+        (
+            StatusCode::OK,
+            [("content-type", "application/json; charset=utf-8")],
+            serde_json::to_string(&reclaim.status()).unwrap_or_default(),
+        )
+    }
+}
+
+impl Controller for IdleReclaimController {
+    fn describe(&self) -> Vec<Route> {
+        let route = |path: &str, description: &'static str, run: bool| {
+            let reclaim = self.reclaim.clone();
+            let route = Route::get(path, description, move || {
+                let reclaim = reclaim.clone();
+                async move { Self::handle(reclaim, run).await }
+            });
+            if run {
+                route.mutating()
+            } else {
+                route
+            }
+        };
+        vec![
+            route("/advcache/reclaim", "Shows the idle reclamation state", false),
+            route(
+                "/advcache/reclaim/run",
+                "Closes idle upstream connections and hands free memory back now",
+                true,
+            ),
+        ]
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use tower::ServiceExt;
+
+    use crate::config;
+    use crate::controller::reclaim::{IdleReclaim, IdleReclaimController};
+    use crate::http::Controller;
+    use crate::upstream::testing::MockUpstream;
+
+    const IDLE_FOR: Duration = Duration::from_secs(60);
+
+    fn reclaim(enabled: bool) -> (IdleReclaim, Arc<MockUpstream>) {
+        let cfg = config::IdleReclaim { enabled, max_rps: Some(2.0), idle_for: Some(IDLE_FOR) };
+        let upstream = MockUpstream::new();
+        (IdleReclaim::new(Some(&cfg), upstream.clone()), upstream)
+    }
+
+    /// Test that reclamation is due once the rate has stayed below `max_rps` for `idle_for`,
+    /// and only once per idle period.
+    #[test]
+    fn test_fires_once_after_idle_for() {
+        let (reclaim, _) = reclaim(true);
+        let start = Instant::now();
+        assert!(!reclaim.observe(0.5, start));
+        assert!(!reclaim.observe(1.9, start + IDLE_FOR / 2));
+        assert!(reclaim.observe(0.0, start + IDLE_FOR));
+        assert!(!reclaim.observe(0.0, start + IDLE_FOR * 3));
+        assert!(reclaim.status().reclaimed);
+    }
+
+    /// Test that traffic at the threshold restarts the idle period and re-arms reclamation.
+    #[test]
+    fn test_traffic_rearms() {
+        let (reclaim, _) = reclaim(true);
+        let start = Instant::now();
+        assert!(!reclaim.observe(0.0, start));
+        assert!(!reclaim.observe(2.0, start + IDLE_FOR / 2));
+        assert!(!reclaim.observe(0.0, start + IDLE_FOR));
+        assert!(reclaim.observe(0.0, start + IDLE_FOR * 2));
+
+        assert!(!reclaim.observe(10.0, start + IDLE_FOR * 3));
+        assert!(!reclaim.status().idle);
+        assert!(!reclaim.observe(0.0, start + IDLE_FOR * 4));
+        assert!(reclaim.observe(0.0, start + IDLE_FOR * 5));
+    }
+
+    /// Test that a disabled reclamation never fires on its own.
+    #[test]
+    fn test_disabled_never_fires() {
+        let (reclaim, _) = reclaim(false);
+        let start = Instant::now();
+        assert!(!reclaim.observe(0.0, start));
+        assert!(!reclaim.observe(0.0, start + IDLE_FOR * 10));
+        assert!(!reclaim.status().enabled);
+    }
+
+    /// Test that the run endpoint reclaims on demand, disabled or not, and reports it.
+    #[tokio::test]
+    async fn test_run_endpoint() {
+        let (reclaim, upstream) = reclaim(false);
+        let router = IdleReclaimController::new(Arc::new(reclaim)).add_route(Router::new());
+        let get = |path: &'static str| {
+            let router = router.clone();
+            async move {
+                let resp = router.oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap();
+                let status = resp.status();
+                let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+                (status, serde_json::from_slice::<serde_json::Value>(&body).unwrap())
+            }
+        };
+
+        let (status, body) = get("/advcache/reclaim").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["last"].is_null());
+        assert_eq!(upstream.idle_drops(), 0);
+
+        let (status, body) = get("/advcache/reclaim/run").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["last"]["trigger"], "manual");
+        assert_eq!(upstream.idle_drops(), 1);
+    }
+}
@@ -0,0 +1,117 @@
+//! Storage resize controller.
+//!
+//! `POST /advcache/storage/resize?size=24GB` grows or shrinks the memory budget without a
+//! restart, `DELETE` goes back to `storage.size`. Shrinking below the bytes stored answers at
+//! once; eviction brings them under the new limits in the background.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::bytes::parse_mem;
+use crate::config::MemoryLimits;
+use crate::db::{resize, Storage};
+use crate::http::{Controller, Route};
+
+/// Query parameters for resizing the storage.
+#[derive(Deserialize)]
+struct ResizeQuery {
+    size: Option<String>,
+}
+
+/// Storage size as shown by the admin API.
+#[derive(Debug, Serialize)]
+struct StorageSize {
+    #[serde(flatten)]
+    limits: MemoryLimits,
+    /// Bytes stored, above the limits for a while after a shrink.
+    stored: i64,
+    /// Whether the size was set at runtime rather than by `storage.size`.
+    resized: bool,
+}
+
+impl StorageSize {
+    fn of(db: &dyn Storage) -> Self {
+        Self {
+            limits: db.memory_limits(),
+            stored: db.stat().0,
+            resized: db.resized().is_some(),
+        }
+    }
+}
+
+/// StorageResizeController shows and changes the storage size.
+#[derive(Clone)]
+pub struct StorageResizeController {
+    db: Arc<dyn Storage>,
+    overrides: Option<PathBuf>,
+}
+
+impl StorageResizeController {
+    /// Creates a new resize controller; the size is persisted to `overrides` when set.
+    pub fn new(db: Arc<dyn Storage>, overrides: Option<String>) -> Self {
+        Self {
+            db,
+            overrides: overrides.map(PathBuf::from),
+        }
+    }
+
+    /// Shows the storage size, its limits and the bytes stored.
+    async fn show(controller: Arc<Self>) -> Response {
+        json(StatusCode::OK, serde_json::to_string(&StorageSize::of(controller.db.as_ref())).unwrap_or_default())
+    }
+
+    /// Sets the storage size, `None` going back to the configured one.
+    async fn set(controller: Arc<Self>, size: Option<i64>) -> Response {
+        let db = controller.db.as_ref();
+        if let Err(e) = resize::resize(db, size, controller.overrides.as_deref()) {
+            return error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e));
+        }
+        json(StatusCode::OK, serde_json::to_string(&StorageSize::of(db)).unwrap_or_default())
+    }
+
+    /// Resizes the storage to the `size` query param.
+    async fn resize(controller: Arc<Self>, Query(params): Query<ResizeQuery>) -> Response {
+        let Some(raw) = params.size else {
+            return error(StatusCode::BAD_REQUEST, "'size' query param is required".to_string());
+        };
+        match parse_mem(&raw) {
+            Some(size) if size > 0 => Self::set(controller, Some(size)).await,
+            _ => error(StatusCode::BAD_REQUEST, format!("invalid size {:?}, expected e.g. 512MB or 20GB", raw)),
+        }
+    }
+}
+
+fn json(status: StatusCode, body: String) -> Response {
+    (status, [("content-type", "application/json; charset=utf-8")], body).into_response()
+}
+
+fn error(status: StatusCode, msg: String) -> Response {
+    json(status, serde_json::json!({ "error": msg }).to_string())
+}
+
+impl Controller for StorageResizeController {
+    fn describe(&self) -> Vec<Route> {
+        let controller = Arc::new(self.clone());
+        let (show, resize, reset) = (controller.clone(), controller.clone(), controller);
+        vec![
+            Route::get("/advcache/storage/size", "Shows the storage size and its memory limits", move || {
+                Self::show(show.clone())
+            }),
+            Route::post("/advcache/storage/resize", "Resizes the storage without a restart", move |query: Query<ResizeQuery>| {
+                Self::resize(resize.clone(), query)
+            })
+            .mutating(),
+            Route::delete("/advcache/storage/resize", "Puts the storage back to its configured size", move || {
+                Self::set(reset.clone(), None)
+            })
+            .mutating(),
+        ]
+    }
+}
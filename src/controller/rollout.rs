@@ -0,0 +1,113 @@
+//! Rule rollout controller.
+//!
+//! `/advcache/rollout` shows and adjusts `cache_value.rollout_percent` of the rules while a
+//! new endpoint is being ramped up. Changes apply at once to every request and last until the
+//! process exits; the config file keeps the value the next start uses.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::config::{Config, ConfigTrait, Rule};
+use crate::http::{Controller, Route};
+
+/// Query parameters for changing a rollout.
+#[derive(Deserialize)]
+struct RolloutQuery {
+    path: Option<String>,
+    percent: Option<String>,
+}
+
+/// Rollout of a rule as shown by the admin API.
+#[derive(Debug, Serialize)]
+struct RuleRollout {
+    path: String,
+    percent: u8,
+}
+
+impl RuleRollout {
+    fn of(rule: &Rule) -> Self {
+        Self {
+            path: rule.path.clone().unwrap_or_default(),
+            percent: rule.cache_value.rollout_percent.get(),
+        }
+    }
+}
+
+/// RolloutController shows and changes the rollout percent of the rules.
+pub struct RolloutController {
+    cfg: Arc<Config>,
+}
+
+impl RolloutController {
+    /// Creates a rollout controller over the rules of the config.
+    pub fn new(cfg: Config) -> Self {
+        Self { cfg: Arc::new(cfg) }
+    }
+
+    /// Lists the rules with their rollout percent, by path.
+    async fn list(cfg: Arc<Config>) -> Response {
+        let mut rules: Vec<_> = cfg
+            .rules()
+            .into_iter()
+            .flat_map(HashMap::values)
+            .map(|rule| RuleRollout::of(rule))
+            .collect();
+        rules.sort_by(|a, b| a.path.cmp(&b.path));
+        json(StatusCode::OK, serde_json::json!({ "rules": rules }).to_string())
+    }
+
+    /// Sets the rollout percent of the rule with the path.
+    async fn set(cfg: Arc<Config>, Query(params): Query<RolloutQuery>) -> Response {
+        let Some(path) = params.path else {
+            return error(StatusCode::BAD_REQUEST, "'path' query param is required".to_string());
+        };
+        let percent = match params.percent.as_deref().map(str::parse::<u8>) {
+            Some(Ok(percent)) if percent <= 100 => percent,
+            _ => return error(StatusCode::BAD_REQUEST, "'percent' must be an integer in 0..=100".to_string()),
+        };
+        let Some(rule) = cfg.rule(&path) else {
+            return error(StatusCode::NOT_FOUND, format!("no rule for path {:?}", path));
+        };
+
+        let previous = rule.cache_value.rollout_percent.get();
+        rule.cache_value.rollout_percent.set(percent);
+        info!(
+            component = "rollout",
+            event = "rollout_changed",
+            rule = %path,
+            from = previous,
+            to = percent,
+            "rule rollout changed"
+        );
+        json(StatusCode::OK, serde_json::to_string(&RuleRollout::of(&rule)).unwrap_or_default())
+    }
+}
+
+fn json(status: StatusCode, body: String) -> Response {
+    (status, [("content-type", "application/json; charset=utf-8")], body).into_response()
+}
+
+fn error(status: StatusCode, msg: String) -> Response {
+    json(status, serde_json::json!({ "error": msg }).to_string())
+}
+
+impl Controller for RolloutController {
+    fn describe(&self) -> Vec<Route> {
+        let (list, set) = (self.cfg.clone(), self.cfg.clone());
+        vec![
+            Route::get("/advcache/rollout", "Shows rule rollout percents", move || Self::list(list.clone())),
+            Route::post("/advcache/rollout", "Adjusts the rollout percent of a rule", move |query: Query<RolloutQuery>| {
+                Self::set(set.clone(), query)
+            })
+            .mutating(),
+        ]
+    }
+}
@@ -0,0 +1,112 @@
+//! Shards controller.
+
+use std::sync::Arc;
+
+use axum::{extract::Query, http::{HeaderMap, StatusCode}, response::IntoResponse};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{Config, ConfigTrait};
+use crate::db::storage::contention::{LockStatsSnapshot, ModeStats};
+use crate::db::Storage;
+use crate::http::render::negotiate::negotiated_list;
+use crate::http::{Controller, Route};
+
+/// Shards listed when `top` is not given.
+const DEFAULT_TOP: usize = 16;
+
+#[derive(Deserialize)]
+struct ShardsQuery {
+    /// Shards listed, those that waited longest for their lock first; 0 lists all.
+    top: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct ShardInfo {
+    id: u64,
+    len: i64,
+    bytes: i64,
+    lock: LockStatsSnapshot,
+}
+
+#[derive(Debug, Serialize)]
+struct ShardsResponse {
+    /// `storage.lock_profiling`, null when the locks are not profiled.
+    lock_profiling: Option<f64>,
+    shards: usize,
+    /// Lock waits of all shards.
+    lock: LockStatsSnapshot,
+    top: Vec<ShardInfo>,
+}
+
+/// ShardsController shows the entries, bytes and sampled lock waits of the shards.
+pub struct ShardsController {
+    cfg: Config,
+    db: Arc<dyn Storage>,
+}
+
+impl ShardsController {
+    /// Creates a new shards controller.
+    pub fn new(cfg: Config, db: Arc<dyn Storage>) -> Self {
+        Self { cfg, db }
+    }
+
+    /// Lists the shards as JSON, YAML or, for `text/plain`, a table of the top shards.
+    async fn get(cfg: Config, db: Arc<dyn Storage>, Query(params): Query<ShardsQuery>, headers: HeaderMap) -> impl IntoResponse {
+        // The walk reads counters only, the shard locks are not taken.
+        let shards = Arc::new(Mutex::new(Vec::new()));
+        let collected = shards.clone();
+        db.walk_shards(
+            CancellationToken::new(),
+            Box::new(move |id, shard| {
+                collected.lock().push(ShardInfo {
+                    id,
+                    len: shard.len(),
+                    bytes: shard.weight(),
+                    lock: shard.lock_stats().snapshot(),
+                })
+            }),
+        );
+        let mut shards = std::mem::take(&mut *shards.lock());
+
+        let mut total = LockStatsSnapshot::default();
+        for shard in &shards {
+            add(&mut total.read, &shard.lock.read);
+            add(&mut total.write, &shard.lock.write);
+            total.slow_writes += shard.lock.slow_writes;
+        }
+
+        let count = shards.len();
+        shards.sort_by_key(|s| std::cmp::Reverse(s.lock.read.wait_nanos + s.lock.write.wait_nanos));
+        match params.top.unwrap_or(DEFAULT_TOP) {
+            0 => {}
+            top => shards.truncate(top),
+        }
+
+        let resp = ShardsResponse {
+            lock_profiling: cfg.storage().lock_profiling.filter(|r| *r > 0.0),
+            shards: count,
+            lock: total,
+            top: shards,
+        };
+        negotiated_list(&headers, StatusCode::OK, &resp, &resp.top)
+    }
+}
+
+fn add(total: &mut ModeStats, shard: &ModeStats) {
+    total.samples += shard.samples;
+    total.wait_nanos += shard.wait_nanos;
+    total.max_wait_nanos = total.max_wait_nanos.max(shard.max_wait_nanos);
+}
+
+impl Controller for ShardsController {
+    fn describe(&self) -> Vec<Route> {
+        let (cfg, db) = (self.cfg.clone(), self.db.clone());
+        vec![Route::get(
+            "/advcache/shards",
+            "Entries, bytes and sampled lock waits of the shards",
+            move |query: Query<ShardsQuery>, headers: HeaderMap| Self::get(cfg.clone(), db.clone(), query, headers),
+        )]
+    }
+}
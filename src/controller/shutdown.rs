@@ -0,0 +1,64 @@
+//! Last shutdown report controller.
+//!
+//! `/advcache/shutdown/last` serves the phases of the previous shutdown, as written to the
+//! runtime overrides file on the way out, for post-mortems of slow or stuck terminations.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+use crate::config::overrides::RuntimeOverrides;
+use crate::http::{Controller, Route};
+
+/// ShutdownReportController serves the report of the last shutdown.
+pub struct ShutdownReportController {
+    overrides: Option<Arc<PathBuf>>,
+}
+
+impl ShutdownReportController {
+    /// Creates a controller reading the report from the `overrides` file, when configured.
+    pub fn new(overrides: Option<String>) -> Self {
+        Self {
+            overrides: overrides.map(|path| Arc::new(PathBuf::from(path))),
+        }
+    }
+
+    /// The file is read per request: it is only written while shutting down.
+    async fn get(overrides: Option<Arc<PathBuf>>) -> Response {
+        let Some(path) = overrides else {
+            return json(StatusCode::NOT_FOUND, "runtime.overrides is not configured, no shutdown is recorded");
+        };
+        match RuntimeOverrides::load(&path) {
+            Ok(RuntimeOverrides { last_shutdown: Some(report), .. }) => (
+                StatusCode::OK,
+                [("content-type", "application/json; charset=utf-8")],
+                serde_json::to_string(&report).unwrap_or_default(),
+            )
+                .into_response(),
+            Ok(_) => json(StatusCode::NOT_FOUND, "no shutdown is recorded yet"),
+            Err(e) => json(StatusCode::INTERNAL_SERVER_ERROR, &format!("{:#}", e)),
+        }
+    }
+}
+
+fn json(status: StatusCode, error: &str) -> Response {
+    (
+        status,
+        [("content-type", "application/json; charset=utf-8")],
+        serde_json::json!({ "error": error }).to_string(),
+    )
+        .into_response()
+}
+
+impl Controller for ShutdownReportController {
+    fn describe(&self) -> Vec<Route> {
+        let overrides = self.overrides.clone();
+        vec![Route::get("/advcache/shutdown/last", "Phases of the last shutdown, for post-mortems", move || {
+            Self::get(overrides.clone())
+        })]
+    }
+}
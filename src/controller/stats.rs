@@ -0,0 +1,62 @@
+//! Request stats controller.
+//!
+//! `GET /advcache/stats?top=10` reports the requests to paths no rule matches, by path prefix,
+//! the most requested first: a prefix high in the list is usually a rule worth adding.
+
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::controller::unmatched::{self, Report, MAX_PREFIXES};
+use crate::http::{Controller, Route};
+
+/// Prefixes reported when `top` is not given.
+const DEFAULT_TOP: usize = 10;
+
+/// Query parameters for the stats.
+#[derive(Deserialize)]
+struct StatsQuery {
+    top: Option<usize>,
+}
+
+/// Stats response structure.
+#[derive(Debug, Serialize)]
+struct StatsResponse {
+    unmatched: Report,
+}
+
+/// StatsController shows request stats of the instance.
+#[derive(Clone, Default)]
+pub struct StatsController;
+
+impl StatsController {
+    /// Creates a new stats controller.
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Shows the `top` most requested unmatched prefixes, at most [`MAX_PREFIXES`].
+    async fn stats(Query(params): Query<StatsQuery>) -> Response {
+        let top = params.top.unwrap_or(DEFAULT_TOP).min(MAX_PREFIXES);
+        let resp = StatsResponse { unmatched: unmatched::unmatched().report(top) };
+        (
+            StatusCode::OK,
+            [("content-type", "application/json")],
+            serde_json::to_string(&resp).unwrap_or_default(),
+        )
+            .into_response()
+    }
+}
+
+impl Controller for StatsController {
+    fn describe(&self) -> Vec<Route> {
+        vec![Route::get(
+            "/advcache/stats",
+            "Requests to paths no rule matches, by path prefix",
+            Self::stats,
+        )]
+    }
+}
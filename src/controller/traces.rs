@@ -1,9 +1,9 @@
 //! Traces controller.
 
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
+use axum::{http::StatusCode, response::IntoResponse};
 use serde::Serialize;
 
-use crate::http::Controller;
+use crate::http::{Controller, Route};
 use crate::traces;
 
 /// Traces response structure.
@@ -53,10 +53,11 @@ impl Default for TracesController {
 }
 
 impl Controller for TracesController {
-    fn add_route(&self, router: Router) -> Router {
-        router
-            .route("/advcache/traces", get(Self::get))
-            .route("/advcache/traces/on", get(Self::on))
-            .route("/advcache/traces/off", get(Self::off))
+    fn describe(&self) -> Vec<Route> {
+        vec![
+            Route::get("/advcache/traces", "Shows whether open-telemetry traces are enabled", Self::get),
+            Route::get("/advcache/traces/on", "Enables open-telemetry traces", Self::on).mutating(),
+            Route::get("/advcache/traces/off", "Disables open-telemetry traces", Self::off).mutating(),
+        ]
     }
 }
@@ -0,0 +1,158 @@
+//! Requests to paths no rule matches, by path prefix.
+//
+// A client hammering a path without a rule costs an origin round trip per request and only
+// shows up as proxied traffic. Such requests are counted under the first two segments of their
+// path (`/api/v1/items/5` counts for `/api/v1`), so the offenders and the rule they miss can be
+// told from `/advcache/stats` and `unmatched_requests_total{prefix}`. At most `MAX_PREFIXES`
+// prefixes are tracked, later ones count for `OVERFLOW_PREFIX`. Recording takes a read lock and
+// a map lookup, on the unmatched path only; a prefix is inserted under the write lock once.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use parking_lot::RwLock;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::time;
+
+/// Prefixes tracked; requests of further ones count for [`OVERFLOW_PREFIX`].
+pub const MAX_PREFIXES: usize = 256;
+
+/// Prefix the requests of untracked prefixes are counted under.
+pub const OVERFLOW_PREFIX: &str = "other";
+
+/// Least time between two warnings about the same prefix, seconds.
+const WARN_INTERVAL_SECS: u64 = 60;
+
+/// First two segments of the path: `/api/v1` for `/api/v1/items/5`.
+pub fn prefix_of(path: &str) -> &str {
+    let mut slashes = path.match_indices('/').skip(2);
+    match (path.starts_with('/'), slashes.next()) {
+        (true, Some((at, _))) => &path[..at],
+        _ => path,
+    }
+}
+
+/// Requests of one prefix, in all and in the current second.
+#[derive(Default)]
+struct PrefixCount {
+    total: AtomicU64,
+    second: AtomicU64,
+    in_second: AtomicU64,
+    warned_at: AtomicU64,
+}
+
+impl PrefixCount {
+    /// Counts a request at `now` (unix seconds); returns the requests of the current second
+    /// when they are over `warn_rps` and the prefix was not warned about lately.
+    fn hit(&self, now: u64, warn_rps: Option<u64>) -> Option<u64> {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        let warn_rps = warn_rps?;
+        let second = self.second.load(Ordering::Relaxed);
+        if second != now && self.second.compare_exchange(second, now, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            self.in_second.store(0, Ordering::Relaxed);
+        }
+        let in_second = self.in_second.fetch_add(1, Ordering::Relaxed) + 1;
+        if in_second <= warn_rps {
+            return None;
+        }
+        let warned_at = self.warned_at.load(Ordering::Relaxed);
+        let due = warned_at == 0 || now >= warned_at + WARN_INTERVAL_SECS;
+        (due && self.warned_at.compare_exchange(warned_at, now, Ordering::Relaxed, Ordering::Relaxed).is_ok())
+            .then_some(in_second)
+    }
+}
+
+/// Requests of a prefix, as reported.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct PrefixStat {
+    pub prefix: String,
+    pub requests: u64,
+}
+
+/// Unmatched requests with the prefixes most requested.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// Unmatched requests in all, the overflow included.
+    pub requests: u64,
+    /// Prefixes tracked, out of [`MAX_PREFIXES`].
+    pub tracked: usize,
+    /// Requests of prefixes past the tracked ones.
+    pub overflow: u64,
+    /// Most requested prefixes first.
+    pub top: Vec<PrefixStat>,
+}
+
+/// Counts of unmatched requests by prefix.
+#[derive(Default)]
+pub struct Unmatched {
+    prefixes: RwLock<HashMap<Box<str>, PrefixCount>>,
+    overflow: PrefixCount,
+}
+
+impl Unmatched {
+    /// Counts a request to a path no rule matches, logging a warning when its prefix gets
+    /// more than `warn_rps` requests a second.
+    pub fn record(&self, path: &str, warn_rps: Option<u64>) {
+        let prefix = prefix_of(path);
+        let now = (time::unix_nano().max(0) / 1_000_000_000) as u64;
+        let tracked = self.prefixes.read().get(prefix).map(|count| count.hit(now, warn_rps));
+        let over = match tracked {
+            Some(over) => over,
+            None => self.insert(prefix, now, warn_rps),
+        };
+        if let Some(rps) = over {
+            warn!(
+                component = "cache-controller",
+                event = "unmatched_path_rate",
+                prefix = prefix,
+                path = path,
+                rps = rps,
+                "requests to a path no rule matches are over api.unmatched_warn_rps, a rule may be missing"
+            );
+        }
+    }
+
+    fn insert(&self, prefix: &str, now: u64, warn_rps: Option<u64>) -> Option<u64> {
+        let mut prefixes = self.prefixes.write();
+        if prefixes.len() >= MAX_PREFIXES && !prefixes.contains_key(prefix) {
+            return self.overflow.hit(now, None);
+        }
+        prefixes.entry(prefix.into()).or_default().hit(now, warn_rps)
+    }
+
+    /// Requests of the prefix so far; those of untracked prefixes under [`OVERFLOW_PREFIX`].
+    #[cfg(test)]
+    pub fn requests(&self, prefix: &str) -> u64 {
+        if prefix == OVERFLOW_PREFIX {
+            return self.overflow.total.load(Ordering::Relaxed);
+        }
+        self.prefixes.read().get(prefix).map_or(0, |count| count.total.load(Ordering::Relaxed))
+    }
+
+    /// Reports the `top` most requested prefixes.
+    pub fn report(&self, top: usize) -> Report {
+        let overflow = self.overflow.total.load(Ordering::Relaxed);
+        let prefixes = self.prefixes.read();
+        let mut stats: Vec<PrefixStat> = prefixes
+            .iter()
+            .map(|(prefix, count)| PrefixStat { prefix: prefix.to_string(), requests: count.total.load(Ordering::Relaxed) })
+            .collect();
+        stats.sort_unstable_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.prefix.cmp(&b.prefix)));
+        stats.truncate(top);
+        Report {
+            requests: overflow + prefixes.values().map(|count| count.total.load(Ordering::Relaxed)).sum::<u64>(),
+            tracked: prefixes.len(),
+            overflow,
+            top: stats,
+        }
+    }
+}
+
+/// Counts of the instance.
+pub fn unmatched() -> &'static Unmatched {
+    static UNMATCHED: OnceLock<Unmatched> = OnceLock::new();
+    UNMATCHED.get_or_init(Unmatched::default)
+}
@@ -0,0 +1,44 @@
+#[cfg(test)]
+mod tests {
+    use crate::controller::unmatched::{prefix_of, PrefixStat, Unmatched, MAX_PREFIXES, OVERFLOW_PREFIX};
+
+    /// Test that the prefix of a path is its first two segments.
+    #[test]
+    fn test_prefix_of() {
+        assert_eq!(prefix_of("/api/v1/items/5"), "/api/v1");
+        assert_eq!(prefix_of("/api/v1/"), "/api/v1");
+        assert_eq!(prefix_of("/api/v1"), "/api/v1");
+        assert_eq!(prefix_of("/favicon.ico"), "/favicon.ico");
+        assert_eq!(prefix_of("/"), "/");
+        assert_eq!(prefix_of("*"), "*");
+    }
+
+    /// Test that requests are counted by prefix, reported most requested first, and that
+    /// prefixes past the tracked ones count for the overflow.
+    #[test]
+    fn test_counts_by_prefix_with_overflow() {
+        let unmatched = Unmatched::default();
+        for i in 0..3 {
+            unmatched.record(&format!("/api/v2/items/{}", i), None);
+        }
+        unmatched.record("/api/v3", None);
+        for i in 0..MAX_PREFIXES {
+            unmatched.record(&format!("/p{}/x/y", i), None);
+        }
+        assert_eq!(unmatched.requests("/api/v2"), 3);
+        assert_eq!(unmatched.requests("/api/v3"), 1);
+        assert_eq!(unmatched.requests(OVERFLOW_PREFIX), 2, "the last two prefixes are not tracked");
+
+        unmatched.record("/api/v3/again", None);
+        let report = unmatched.report(2);
+        assert_eq!(report.requests, 4 + MAX_PREFIXES as u64 + 1);
+        assert_eq!((report.tracked, report.overflow), (MAX_PREFIXES, 2));
+        assert_eq!(
+            report.top,
+            vec![
+                PrefixStat { prefix: "/api/v2".to_string(), requests: 3 },
+                PrefixStat { prefix: "/api/v3".to_string(), requests: 2 },
+            ]
+        );
+    }
+}
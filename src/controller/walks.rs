@@ -0,0 +1,46 @@
+//! Admin walks controller.
+
+use std::sync::Arc;
+
+use axum::{http::StatusCode, response::IntoResponse};
+
+use crate::db::Storage;
+use crate::http::{Controller, Route};
+
+/// WalksController shows the admin walks over the shards running and queued, with the
+/// position of each queued one.
+pub struct WalksController {
+    db: Arc<dyn Storage>,
+}
+
+impl WalksController {
+    /// Creates a new walks controller.
+    pub fn new(db: Arc<dyn Storage>) -> Self {
+        Self { db }
+    }
+
+    /// Gets the running and queued walks.
+    async fn get(db: Arc<dyn Storage>) -> impl IntoResponse {
+        match db.walks() {
+            Some(walks) => (
+                StatusCode::OK,
+                [("content-type", "application/json")],
+                serde_json::to_string(&walks.status()).unwrap_or_default(),
+            ),
+            None => (
+                StatusCode::NOT_FOUND,
+                [("content-type", "application/json")],
+                r#"{"error":"storage does not coordinate walks"}"#.to_string(),
+            ),
+        }
+    }
+}
+
+impl Controller for WalksController {
+    fn describe(&self) -> Vec<Route> {
+        let db = self.db.clone();
+        vec![Route::get("/advcache/walks", "Admin walks over the shards running and queued", move || {
+            Self::get(db.clone())
+        })]
+    }
+}
@@ -0,0 +1,137 @@
+//! What-if analysis controller.
+//!
+//! Replays the accesses sampled by `analytics` under the current storage size and TTL and
+//! under the ones asked for: `GET /advcache/whatif?size=20GB&ttl=10m`. Either param may be
+//! left out to keep the current value; `ttl=off` leaves entries until they are evicted.
+
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{Config, ConfigTrait};
+use crate::db::analytics::Trace;
+use crate::db::simulator::{simulate, Estimate, Scenario};
+use crate::db::Storage;
+use crate::http::{Controller, Route};
+use crate::bytes::parse_mem;
+
+/// Query parameters for the what-if endpoint.
+#[derive(Deserialize)]
+struct WhatIfQuery {
+    size: Option<String>,
+    ttl: Option<String>,
+}
+
+#[derive(Serialize)]
+struct WhatIfResponse {
+    sample: Sample,
+    current: Estimate,
+    whatif: Estimate,
+}
+
+#[derive(Serialize)]
+struct Sample {
+    rate: f64,
+    accesses: usize,
+    keys: usize,
+    #[serde(with = "humantime_serde")]
+    window: Duration,
+}
+
+/// WhatIfController estimates the hit rate and memory of other storage sizes and TTLs.
+pub struct WhatIfController {
+    cfg: Config,
+    db: Arc<dyn Storage>,
+}
+
+impl WhatIfController {
+    /// Creates a new what-if controller.
+    pub fn new(cfg: Config, db: Arc<dyn Storage>) -> Self {
+        Self { cfg, db }
+    }
+
+    /// Storage size in effect, set at runtime or configured, and TTL as configured.
+    fn current(cfg: &Config, db: &dyn Storage) -> Scenario {
+        let ttl = cfg.lifetime().filter(|lifetime| lifetime.enabled).and_then(|lifetime| lifetime.ttl);
+        Scenario { size: db.memory_limits().size, ttl }
+    }
+
+    fn scenario(current: Scenario, params: &WhatIfQuery) -> Result<Scenario, String> {
+        let size = match params.size.as_deref() {
+            None => current.size,
+            Some(raw) => match parse_mem(raw) {
+                Some(size) if size > 0 => size,
+                _ => return Err(format!("invalid size {:?}, expected e.g. 512MB or 20GB", raw)),
+            },
+        };
+        let ttl = match params.ttl.as_deref() {
+            None => current.ttl,
+            Some("off") => None,
+            Some(raw) => match humantime::parse_duration(raw) {
+                Ok(ttl) if !ttl.is_zero() => Some(ttl),
+                _ => return Err(format!("invalid ttl {:?}, expected e.g. 10m or off", raw)),
+            },
+        };
+        Ok(Scenario { size, ttl })
+    }
+
+    async fn get(cfg: Config, db: Arc<dyn Storage>, Query(params): Query<WhatIfQuery>) -> Response {
+        let Some(sampler) = db.access_sampler() else {
+            return error(StatusCode::NOT_FOUND, "analytics is disabled, set analytics.sample_rate");
+        };
+        let current = Self::current(&cfg, db.as_ref());
+        let whatif = match Self::scenario(current, &params) {
+            Ok(whatif) => whatif,
+            Err(err) => return error(StatusCode::BAD_REQUEST, &err),
+        };
+        let trace = sampler.trace();
+        if trace.accesses.is_empty() {
+            return error(StatusCode::NOT_FOUND, "no accesses sampled yet");
+        }
+
+        // A full window replays in about a second; off the runtime threads.
+        let resp = tokio::task::spawn_blocking(move || replay(&trace, current, whatif)).await;
+        match resp {
+            Ok(resp) => (
+                StatusCode::OK,
+                [("content-type", "application/json")],
+                serde_json::to_string(&resp).unwrap_or_default(),
+            )
+                .into_response(),
+            Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, &err.to_string()),
+        }
+    }
+}
+
+fn replay(trace: &Trace, current: Scenario, whatif: Scenario) -> WhatIfResponse {
+    WhatIfResponse {
+        sample: Sample { rate: trace.rate, accesses: trace.accesses.len(), keys: trace.keys(), window: trace.window() },
+        current: simulate(trace, current),
+        whatif: simulate(trace, whatif),
+    }
+}
+
+fn error(status: StatusCode, msg: &str) -> Response {
+    (
+        status,
+        [("content-type", "application/json")],
+        serde_json::json!({ "error": msg }).to_string(),
+    )
+        .into_response()
+}
+
+impl Controller for WhatIfController {
+    fn describe(&self) -> Vec<Route> {
+        let (cfg, db) = (self.cfg.clone(), self.db.clone());
+        vec![Route::get(
+            "/advcache/whatif",
+            "Estimates hit rate and memory under another storage size and TTL from sampled accesses",
+            move |query: Query<WhatIfQuery>| Self::get(cfg.clone(), db.clone(), query),
+        )]
+    }
+}
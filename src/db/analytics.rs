@@ -0,0 +1,106 @@
+//! Access sampling for the what-if analysis (`analytics`).
+//
+// Keys are sampled rather than accesses: a sampled key has every access recorded, so the
+// replay sees its real reuse, and the share of keys scales storage sizes and memory between
+// the sample and the whole cache. Recording is a multiply and a compare for keys left out and
+// a short lock for sampled ones; the window is bounded by `analytics.max_events`.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::config::Config;
+use crate::time;
+
+/// Sampled accesses kept when `analytics.max_events` is not set.
+pub const DEFAULT_MAX_EVENTS: usize = 1 << 20;
+
+/// One access to a sampled key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    pub key: u64,
+    /// Unix nanoseconds of the access.
+    pub at: i64,
+    /// Weight of the entry in bytes.
+    pub size: i64,
+}
+
+/// Sampled accesses, oldest first, with the share of keys they were sampled at.
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub rate: f64,
+    pub accesses: Vec<Access>,
+}
+
+impl Trace {
+    /// Distinct keys sampled.
+    pub fn keys(&self) -> usize {
+        self.accesses.iter().map(|a| a.key).collect::<HashSet<_>>().len()
+    }
+
+    /// Time between the oldest and the latest access.
+    pub fn window(&self) -> Duration {
+        match (self.accesses.first(), self.accesses.last()) {
+            (Some(first), Some(last)) => Duration::from_nanos(last.at.saturating_sub(first.at).max(0) as u64),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// Records the accesses of a share of keys in a bounded window.
+pub struct AccessSampler {
+    rate: f64,
+    /// Keys whose mixed hash is at most this are sampled.
+    threshold: u64,
+    max_events: usize,
+    window: Mutex<VecDeque<Access>>,
+}
+
+impl AccessSampler {
+    /// Creates a sampler if `analytics.sample_rate` is set.
+    pub fn from_config(cfg: &Config) -> Option<Arc<Self>> {
+        let analytics = cfg.cache.analytics.as_ref()?;
+        let rate = analytics.sample_rate?;
+        Some(Arc::new(Self::new(rate, analytics.max_events.unwrap_or(DEFAULT_MAX_EVENTS))))
+    }
+
+    /// Creates a sampler recording the accesses of `rate` of keys, keeping the last `max_events`.
+    pub fn new(rate: f64, max_events: usize) -> Self {
+        Self {
+            rate,
+            threshold: if rate >= 1.0 { u64::MAX } else { (rate * u64::MAX as f64) as u64 },
+            max_events: max_events.max(1),
+            window: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Whether accesses of the key are recorded. Keys are hashes already; they are mixed once
+    /// more so that the sample does not follow the shard a key lands in.
+    #[inline]
+    pub fn is_sampled(&self, key: u64) -> bool {
+        key.wrapping_mul(0x9e37_79b9_7f4a_7c15) <= self.threshold
+    }
+
+    /// Records an access of `size` bytes to the key, if the key is sampled.
+    #[inline]
+    pub fn record(&self, key: u64, size: i64) {
+        if self.is_sampled(key) {
+            self.push(Access { key, at: time::unix_nano(), size });
+        }
+    }
+
+    fn push(&self, access: Access) {
+        let mut window = self.window.lock();
+        if window.len() == self.max_events {
+            window.pop_front();
+        }
+        window.push_back(access);
+    }
+
+    /// Copy of the sampled accesses.
+    pub fn trace(&self) -> Trace {
+        Trace { rate: self.rate, accesses: self.window.lock().iter().copied().collect() }
+    }
+}
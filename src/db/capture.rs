@@ -0,0 +1,259 @@
+//! Time-boxed debug capture of single cache keys.
+//!
+//! `POST /advcache/debug/capture` puts a key on the watch-list for a while. Every operation
+//! touching it (get, set, refresh pick and result, eviction, invalidation) is then logged at
+//! debug level under the [`TARGET`] target, which the logger lets through whatever its level,
+//! inside the span of the request or worker, and kept in the capture that
+//! `GET /advcache/debug/capture/{id}` returns. Key-bearing code paths pay one atomic load
+//! while nothing is watched and one map lookup otherwise.
+
+// Without the `debug-endpoints` feature nothing starts a capture; the recording stays.
+#![cfg_attr(not(feature = "debug-endpoints"), allow(dead_code))]
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+
+use crate::time;
+
+/// Log target of captured operations, enabled at debug level by the logger.
+pub const TARGET: &str = "advcache::capture";
+
+/// Keys watched at once.
+pub const MAX_WATCHED: usize = 4;
+
+/// Events kept per capture; later ones are counted as dropped.
+pub const MAX_EVENTS: usize = 1024;
+
+/// Longest capture window.
+pub const MAX_DURATION: Duration = Duration::from_secs(600);
+
+/// Finished captures kept for reading, the oldest going first.
+const KEPT_CAPTURES: usize = 16;
+
+static WATCHLIST: Lazy<Watchlist> = Lazy::new(Watchlist::new);
+
+/// The process-wide watch-list the storage and admin API share.
+pub fn watchlist() -> &'static Watchlist {
+    &WATCHLIST
+}
+
+/// Records an operation on `key` if it is watched.
+#[inline]
+pub fn record(key: u64, op: Op, detail: impl fmt::Display) {
+    WATCHLIST.record(key, op, detail)
+}
+
+/// Operation on a watched key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Get,
+    Set,
+    RefreshPick,
+    RefreshResult,
+    Evict,
+    Invalidate,
+}
+
+impl Op {
+    pub fn label(self) -> &'static str {
+        match self {
+            Op::Get => "get",
+            Op::Set => "set",
+            Op::RefreshPick => "refresh_pick",
+            Op::RefreshResult => "refresh_result",
+            Op::Evict => "evict",
+            Op::Invalidate => "invalidate",
+        }
+    }
+}
+
+/// Why a key cannot be watched.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WatchError {
+    #[error("key {key} is already watched by capture {id}")]
+    AlreadyWatched { key: u64, id: u64 },
+    #[error("{MAX_WATCHED} keys are watched already")]
+    Full,
+}
+
+/// One captured operation.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureEvent {
+    /// Unix milliseconds of the operation.
+    pub at: i64,
+    pub op: &'static str,
+    pub detail: String,
+}
+
+/// Operations captured on one key during its window.
+pub struct Capture {
+    id: u64,
+    key: u64,
+    path: Option<String>,
+    started_at: i64,
+    until: Instant,
+    events: Mutex<Vec<CaptureEvent>>,
+    dropped: AtomicU64,
+}
+
+/// A capture as the admin API shows it.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureReport {
+    pub id: u64,
+    pub key: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub started_at: i64,
+    pub active: bool,
+    pub remaining_ms: u64,
+    pub events: Vec<CaptureEvent>,
+    pub dropped: u64,
+}
+
+impl Capture {
+    fn is_over(&self, now: Instant) -> bool {
+        now >= self.until
+    }
+
+    fn push(&self, op: Op, detail: String) {
+        let mut events = self.events.lock();
+        if events.len() >= MAX_EVENTS {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        events.push(CaptureEvent {
+            at: time::unix_nano() / 1_000_000,
+            op: op.label(),
+            detail,
+        });
+    }
+
+    pub fn report(&self) -> CaptureReport {
+        let remaining = self.until.saturating_duration_since(Instant::now());
+        CaptureReport {
+            id: self.id,
+            key: self.key,
+            path: self.path.clone(),
+            started_at: self.started_at,
+            active: !remaining.is_zero(),
+            remaining_ms: remaining.as_millis() as u64,
+            events: self.events.lock().clone(),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Watched keys and the captures of the recent ones.
+pub struct Watchlist {
+    watched: AtomicUsize,
+    keys: RwLock<HashMap<u64, Arc<Capture>>>,
+    recent: Mutex<VecDeque<Arc<Capture>>>,
+    next_id: AtomicU64,
+}
+
+impl Watchlist {
+    pub fn new() -> Self {
+        Self {
+            watched: AtomicUsize::new(0),
+            keys: RwLock::new(HashMap::new()),
+            recent: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Watches `key` for `duration`, capped to [`MAX_DURATION`]; `path` names the request the
+    /// key was resolved from, if any.
+    pub fn watch(&self, key: u64, path: Option<String>, duration: Duration) -> Result<Arc<Capture>, WatchError> {
+        let now = Instant::now();
+        let mut keys = self.keys.write();
+        keys.retain(|_, capture| !capture.is_over(now));
+        self.watched.store(keys.len(), Ordering::Release);
+        if let Some(capture) = keys.get(&key) {
+            return Err(WatchError::AlreadyWatched { key, id: capture.id });
+        }
+        if keys.len() >= MAX_WATCHED {
+            return Err(WatchError::Full);
+        }
+
+        let capture = Arc::new(Capture {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            key,
+            path,
+            started_at: time::unix_nano() / 1_000_000,
+            until: now + duration.min(MAX_DURATION),
+            events: Mutex::new(Vec::new()),
+            dropped: AtomicU64::new(0),
+        });
+        keys.insert(key, capture.clone());
+        self.watched.store(keys.len(), Ordering::Release);
+        drop(keys);
+
+        let mut recent = self.recent.lock();
+        if recent.len() >= KEPT_CAPTURES {
+            recent.pop_front();
+        }
+        recent.push_back(capture.clone());
+        tracing::info!(target: TARGET, capture = capture.id, key, "debug capture started");
+        Ok(capture)
+    }
+
+    /// A running or recent capture by id.
+    pub fn capture(&self, id: u64) -> Option<Arc<Capture>> {
+        self.recent.lock().iter().find(|capture| capture.id == id).cloned()
+    }
+
+    /// Number of keys watched, captures past their window not yet dropped included.
+    pub fn len(&self) -> usize {
+        self.watched.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Records an operation on `key` if it is watched; a capture past its window is dropped
+    /// from the watch-list instead.
+    #[inline]
+    pub fn record(&self, key: u64, op: Op, detail: impl fmt::Display) {
+        if self.watched.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        self.record_watched(key, op, &detail)
+    }
+
+    #[cold]
+    fn record_watched(&self, key: u64, op: Op, detail: &dyn fmt::Display) {
+        let Some(capture) = self.keys.read().get(&key).cloned() else {
+            return;
+        };
+        if capture.is_over(Instant::now()) {
+            self.unwatch(&capture);
+            return;
+        }
+        let detail = detail.to_string();
+        tracing::debug!(target: TARGET, capture = capture.id, key, op = op.label(), detail = %detail, "captured");
+        capture.push(op, detail);
+    }
+
+    fn unwatch(&self, capture: &Arc<Capture>) {
+        let mut keys = self.keys.write();
+        if keys.get(&capture.key).is_some_and(|watched| Arc::ptr_eq(watched, capture)) {
+            keys.remove(&capture.key);
+            tracing::info!(target: TARGET, capture = capture.id, key = capture.key, "debug capture finished");
+        }
+        self.watched.store(keys.len(), Ordering::Release);
+    }
+}
+
+impl Default for Watchlist {
+    fn default() -> Self {
+        Self::new()
+    }
+}
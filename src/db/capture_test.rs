@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::db::capture::{Op, WatchError, Watchlist, MAX_EVENTS, MAX_WATCHED};
+
+    /// Test that only watched keys are captured, in order, and that a key is watched once.
+    #[test]
+    fn test_records_watched_keys_only() {
+        let watchlist = Watchlist::new();
+        watchlist.record(1, Op::Get, "miss");
+        assert!(watchlist.is_empty());
+
+        let capture = watchlist.watch(1, None, Duration::from_secs(60)).unwrap();
+        watchlist.record(1, Op::Get, "miss");
+        watchlist.record(2, Op::Get, "miss");
+        watchlist.record(1, Op::Set, "stored");
+
+        let report = capture.report();
+        let ops: Vec<_> = report.events.iter().map(|e| (e.op, e.detail.as_str())).collect();
+        assert_eq!(ops, vec![("get", "miss"), ("set", "stored")]);
+        assert!(report.active);
+        assert_eq!(
+            watchlist.watch(1, None, Duration::from_secs(60)).err(),
+            Some(WatchError::AlreadyWatched { key: 1, id: report.id })
+        );
+        assert_eq!(watchlist.capture(report.id).unwrap().report().events.len(), 2);
+    }
+
+    /// Test that at most `MAX_WATCHED` keys are watched and that a finished capture frees its
+    /// slot while staying readable.
+    #[test]
+    fn test_watch_limit_and_expiry() {
+        let watchlist = Watchlist::new();
+        let short = watchlist.watch(0, None, Duration::from_millis(30)).unwrap();
+        for key in 1..MAX_WATCHED as u64 {
+            watchlist.watch(key, None, Duration::from_secs(60)).unwrap();
+        }
+        assert_eq!(watchlist.watch(100, None, Duration::from_secs(60)).err(), Some(WatchError::Full));
+
+        std::thread::sleep(Duration::from_millis(50));
+        watchlist.record(0, Op::Get, "hit");
+        assert_eq!(watchlist.len(), MAX_WATCHED - 1);
+        let report = watchlist.capture(short.report().id).unwrap().report();
+        assert!(!report.active);
+        assert!(report.events.is_empty(), "nothing is captured past the window");
+        assert!(watchlist.watch(100, None, Duration::from_secs(60)).is_ok());
+    }
+
+    /// Test that events past `MAX_EVENTS` are counted as dropped.
+    #[test]
+    fn test_events_are_bounded() {
+        let watchlist = Watchlist::new();
+        let capture = watchlist.watch(1, None, Duration::from_secs(60)).unwrap();
+        for _ in 0..MAX_EVENTS + 5 {
+            watchlist.record(1, Op::Get, "hit");
+        }
+        let report = capture.report();
+        assert_eq!(report.events.len(), MAX_EVENTS);
+        assert_eq!(report.dropped, 5);
+    }
+}
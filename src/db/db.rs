@@ -3,12 +3,20 @@
 use anyhow::Result;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::timeout;
+use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info};
-
-use crate::config::{Config, ConfigTrait};
-use crate::governor::Governor;
+use tracing::{error, info, warn};
+
+use crate::config::{Config, ConfigTrait, MemoryLimits};
+use crate::db::analytics::AccessSampler;
+use crate::db::jobs::Jobs;
+use crate::db::key_schema::{purge_stale_key_schemas, PurgeReport};
+#[cfg(feature = "mocks")]
+use crate::db::mock;
+use crate::db::rule_reconcile::{reconcile_rules, ReconcileReport};
+use crate::db::tombstones::Tombstones;
+use crate::db::walks::{WalkCoordinator, OP_RULE_RECONCILE};
+use crate::governor::{Governor, GovernorError};
 use crate::model::Entry;
 use crate::upstream::Upstream;
 
@@ -18,6 +26,8 @@ const COMP_STORAGE: &str = "storage";
 const COMP_DUMP: &str = "dump";
 pub const SVC_EVICTOR: &str = "soft-eviction";
 pub const SVC_LIFETIME_MANAGER: &str = "wrk-lifetime-manager";
+/// Spins of the eviction pass a shrinking resize starts, as many as an evictor pass.
+const RESIZE_EVICTION_BACKOFF: i64 = 8196;
 
 /// Trait for cache storage backends.
 #[async_trait::async_trait]
@@ -25,6 +35,12 @@ pub trait Storage: Send + Sync {
     /// Retrieves an entry from storage, returning the entry and a hit flag.
     fn get(&self, entry: &Entry) -> (Option<Entry>, bool);
 
+    /// Retrieves the entry of the variant the request headers ask for, when the stored
+    /// response varies on request headers (see `model::vary`).
+    fn get_variant(&self, entry: &Entry, _request_headers: &[(&str, &str)]) -> (Option<Entry>, bool) {
+        self.get(entry)
+    }
+
     /// Retrieves an entry by its numeric key.
     fn get_by_key(&self, key: u64) -> (Option<Entry>, bool);
 
@@ -34,14 +50,103 @@ pub trait Storage: Send + Sync {
     /// Walks through all shards, calling the provided function for each shard.
     fn walk_shards(&self,ctx: CancellationToken,f: Box<dyn FnMut(u64, &crate::db::storage::Shard<Entry>) + Send + Sync>);
 
+    /// Number of shards, walked one at a time by [`Storage::walk_shard`].
+    fn shards_count(&self) -> usize;
+
+    /// Calls `f` with the shard `shard_id`, unless the walk is cancelled.
+    fn walk_shard(&self, ctx: &CancellationToken, shard_id: usize, f: &mut dyn FnMut(&crate::db::storage::Shard<Entry>));
+
     /// Removes an entry from storage, returning freed bytes and a hit flag.
     fn remove(&self, entry: &Entry) -> (i64, bool);
 
+    /// Marks an entry as outdated so background refresh picks it up soon.
+    fn mark_outdated(&self, entry: &Entry);
+
     /// Returns storage statistics: (bytes, entry_count).
     fn stat(&self) -> (i64, i64);
 
-    /// Clears all entries from storage.
-    fn clear(&self);
+    /// Clears all entries from storage, returning the cleared (bytes, entry_count).
+    /// Memory of the cleared entries may be released after this returns.
+    fn clear(&self) -> (i64, i64);
+
+    /// Bytes of cleared entries not released yet (0 once the last clear has been freed).
+    fn clear_pending_bytes(&self) -> i64;
+
+    /// Number of entries past their refresh deadline not handed out for refresh yet.
+    fn refresh_backlog(&self) -> usize {
+        0
+    }
+
+    /// Entries whose response carries the tag, by its hash (see `model::surrogate`).
+    fn tagged(&self, _tag: u64) -> Vec<Entry> {
+        Vec::new()
+    }
+
+    /// Reports whether new keys currently have to pass admission
+    /// (admission is enabled and the admission memory limit is exceeded).
+    fn is_admission_active(&self) -> bool;
+
+    /// Memory limits in effect.
+    fn memory_limits(&self) -> MemoryLimits {
+        MemoryLimits::default()
+    }
+
+    /// Whether entries count their hits (see `Entry::hits`); otherwise the counts stay at zero.
+    fn counts_hits(&self) -> bool {
+        false
+    }
+
+    /// Sets the storage size at runtime, `None` going back to the configured one, and returns
+    /// the limits taken. Entries above them are evicted in the background.
+    fn resize(&self, _size: Option<i64>) -> MemoryLimits {
+        self.memory_limits()
+    }
+
+    /// Storage size set at runtime, if any.
+    fn resized(&self) -> Option<i64> {
+        None
+    }
+
+    /// Keeps the key from being stored again for `ttl` (see [`Tombstones`]).
+    fn tombstone(&self, _key: u64, _ttl: Duration) {}
+
+    /// Whether the key is tombstoned, i.e. must be served from the origin without being stored.
+    fn is_tombstoned(&self, _key: u64) -> bool {
+        false
+    }
+
+    /// Coordinator admin walks take a permit of before walking the shards, if any.
+    fn walks(&self) -> Option<Arc<WalkCoordinator>> {
+        None
+    }
+
+    /// Runner of the admin jobs over the shards, if any.
+    fn jobs(&self) -> Option<Arc<Jobs>> {
+        None
+    }
+
+    /// Sampler of client accesses for the what-if analysis, if `analytics` is enabled.
+    fn access_sampler(&self) -> Option<Arc<AccessSampler>> {
+        None
+    }
+
+    /// Background workers that failed to start without failing startup (not `critical`).
+    fn failed_workers(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Whether the dump restored on start is loaded, or none is restored at all.
+    fn is_dump_loaded(&self) -> bool {
+        true
+    }
+
+    /// Stops the background workers.
+    fn stop_workers(&self) {}
+
+    /// Writes the dump, if dumping is enabled.
+    async fn dump(&self) -> Result<()> {
+        Ok(())
+    }
 
     /// Gracefully closes storage.
     async fn close(&self) -> Result<()> {
@@ -56,6 +161,12 @@ pub struct DB {
     shutdown_token: CancellationToken,
     governor: Arc<dyn Governor>,
     persistence: Arc<dyn Dumper>,
+    tombstones: Tombstones,
+    walks: Arc<WalkCoordinator>,
+    jobs: Arc<Jobs>,
+    analytics: Option<Arc<AccessSampler>>,
+    /// Non-critical workers that failed to start.
+    failed_workers: Vec<String>,
 }
 
 /// Trait for persistence operations.
@@ -82,44 +193,23 @@ impl DB {
 
         // Force groups with initial config (interval/enable).
         // Eviction worker
-        let eviction_freq_cfg = crate::workers::CallFreq::new(
-            0,
-            cfg.eviction()
-                .and_then(|e| e.check_interval)
-                .unwrap_or(Duration::from_millis(100)),
-        );
-        let eviction_cfg = crate::workers::WorkerConfig::new(
-            cfg.eviction().map(|e| e.enabled).unwrap_or(false),
-            Arc::new(eviction_freq_cfg) as Arc<dyn crate::governor::Freq>,
-            cfg.eviction().and_then(|e| e.replicas).unwrap_or(32),
-        );
         let eviction = crate::workers::evictor::Evictor::new(
             ctx.clone(),
             SVC_EVICTOR.to_string(),
-            Arc::new(eviction_cfg) as Arc<dyn crate::governor::Config>,
+            eviction_worker_config(&cfg),
             storage.clone(),
         )?;
 
         // Lifetime manager worker
-        let refresh_freq_cfg = crate::workers::CallFreq::new(
-            cfg.lifetime().and_then(|l| l.rate).unwrap_or(1000) as usize,
-            Duration::ZERO,
-        );
-        let refresh_cfg = crate::workers::WorkerConfig::new(
-            cfg.lifetime().map(|l| l.enabled).unwrap_or(false),
-            Arc::new(refresh_freq_cfg) as Arc<dyn crate::governor::Freq>,
-            cfg.lifetime().and_then(|l| l.replicas).unwrap_or(32),
-        );
         let refresh = crate::workers::lifetimer::LifetimeManager::new(
             ctx.clone(),
             SVC_LIFETIME_MANAGER.to_string(),
-            Arc::new(refresh_cfg) as Arc<dyn crate::governor::Config>,
+            lifetime_worker_config(&cfg),
             cfg.clone(),
             storage.clone(),
         )
         .map_err(|e| anyhow::anyhow!("{}", e))?;
 
-        // Register services before starting governor to prevent race conditions.
         struct ServiceWrapper<T: 'static>(Arc<T>)
         where
             Arc<T>: crate::governor::Service;
@@ -157,34 +247,48 @@ impl DB {
             Arc::new(ServiceWrapper(s))
         }
 
-        gov.register(SVC_EVICTOR.to_string(), to_dyn_service(eviction.clone()));
-        gov.register(
-            SVC_LIFETIME_MANAGER.to_string(),
-            to_dyn_service(refresh.clone()),
-        );
-        // Starting workers
-        let _ = gov.start(SVC_EVICTOR);
-        let _ = gov.start(SVC_LIFETIME_MANAGER);
-
-        // Enabled/disable workers
-        if cfg.eviction().map(|e| e.enabled).unwrap_or(false) {
-            let _ = gov.on(SVC_EVICTOR);
-        } else {
-            info!(name = SVC_EVICTOR, event = "on/off", "disabled");
-        }
-        if cfg.lifetime().map(|l| l.enabled).unwrap_or(false) {
-            let _ = gov.on(SVC_LIFETIME_MANAGER);
-        } else {
-            info!(name = SVC_LIFETIME_MANAGER, event = "on/off", "disabled");
+        // A worker failing to start fails startup, unless it is not critical: the instance
+        // then starts without it and stays out of readiness.
+        let workers = [
+            (
+                SVC_EVICTOR,
+                to_dyn_service(eviction.clone()),
+                cfg.eviction().map(|e| e.enabled).unwrap_or(false),
+                cfg.eviction().and_then(|e| e.critical).unwrap_or(true),
+            ),
+            (
+                SVC_LIFETIME_MANAGER,
+                to_dyn_service(refresh.clone()),
+                cfg.lifetime().map(|l| l.enabled).unwrap_or(false),
+                cfg.lifetime().and_then(|l| l.critical).unwrap_or(true),
+            ),
+        ];
+        let mut failed_workers = Vec::new();
+        for (name, service, enabled, critical) in workers {
+            if let Err(e) = start_worker(gov.as_ref(), name, service, enabled) {
+                if critical {
+                    error!(component = COMP_STORAGE, name, error = %e, "worker failed to start");
+                    gov.stop();
+                    return Err(e.into());
+                }
+                warn!(component = COMP_STORAGE, name, error = %e, "worker failed to start, instance is not ready");
+                failed_workers.push(name.to_string());
+            }
         }
 
         // Init. of the storage itself
+        let walks = Arc::new(WalkCoordinator::new(&cfg));
         let db = Arc::new(Self {
+            jobs: Arc::new(Jobs::new(&cfg, walks.clone(), ctx.clone())),
             shutdown_token: ctx,
             cfg: cfg.clone(),
             governor: gov,
             storage: storage.clone(),
+            walks,
+            analytics: AccessSampler::from_config(&cfg),
             persistence: new_dump(cfg, storage.clone())?,
+            tombstones: Tombstones::default(),
+            failed_workers,
         });
 
         Ok(db.run())
@@ -200,11 +304,10 @@ impl DB {
                 .map(|d| d.enabled)
                 .unwrap_or(false)
             {
-                // Load dump asynchronously
-                let persistence = self.persistence.clone();
-                let token = self.shutdown_token.clone();
+                // Load dump asynchronously, then drop entries keyed by an outdated rule schema
+                let db = self.clone();
                 tokio::task::spawn(async move {
-                    if let Err(e) = persistence.load(token).await {
+                    if let Err(e) = db.persistence.load(db.shutdown_token.clone()).await {
                         error!(
                             component = COMP_DUMP,
                             event = "load_failed",
@@ -212,6 +315,7 @@ impl DB {
                             "error loading cache dump"
                         );
                     }
+                    db.schedule_key_schema_purge(db.cfg.clone());
                 });
             } else if self
                 .cfg
@@ -220,37 +324,103 @@ impl DB {
                 .map(|m| m.enabled)
                 .unwrap_or(false)
             {
-                let length = self
-                    .cfg
-                    .data()
-                    .and_then(|d| d.mock.as_ref())
-                    .and_then(|m| m.length)
-                    .unwrap_or(1000000);
-                load_mocks(
-                    self.shutdown_token.clone(),
-                    self.cfg.clone(),
-                    self.clone(),
-                    length,
-                );
+                self.load_mocks();
             }
         }
         self
     }
+
+    /// Prefills the storage with mock entries (`data.mock`) in the background.
+    #[cfg(feature = "mocks")]
+    fn load_mocks(self: &Arc<Self>) {
+        let rate = self.cfg.data().and_then(|d| d.mock.as_ref()).and_then(|m| m.rate);
+        mock::load_mocks(self.shutdown_token.clone(), self.cfg.clone(), self.clone(), mock::specs(&self.cfg), rate);
+    }
+
+    /// Without the `mocks` feature a config enabling `data.mock` is refused at load.
+    #[cfg(not(feature = "mocks"))]
+    fn load_mocks(self: &Arc<Self>) {}
+
+    /// Stores the entry of a key still waiting in the dump being restored, if it is one.
+    fn load_pending(&self, key: u64) -> bool {
+        match self.persistence.load_key(key) {
+            Some(entry) => self.storage.set(entry),
+            None => false,
+        }
+    }
+
+    /// Purges, in the background, entries keyed with a schema that differs from their rule in `cfg`.
+    /// Called once a config (and a dump restored against it) is loaded.
+    pub fn schedule_key_schema_purge(self: &Arc<Self>, cfg: Config) -> JoinHandle<PurgeReport> {
+        let db = self.clone();
+        tokio::task::spawn(async move {
+            let jobs = db.jobs.clone();
+            purge_stale_key_schemas(&jobs, db, &cfg).await
+        })
+    }
+
+    /// Re-points, in the background, entries holding a previous rule at the rule of their path in `cfg`.
+    pub fn schedule_rule_reconciliation(self: &Arc<Self>, cfg: Config) -> JoinHandle<ReconcileReport> {
+        let db = self.clone();
+        tokio::task::spawn(async move {
+            let _permit = db.walks.acquire_background(OP_RULE_RECONCILE).await;
+            let walker = db.clone();
+            tokio::task::spawn_blocking(move || reconcile_rules(&walker.shutdown_token, walker.as_ref(), &cfg))
+                .await
+                .expect("rule reconciliation panicked")
+        })
+    }
+
+    /// Brings storage and workers in line with a reloaded config: the memory limits and worker
+    /// settings are taken over, entries keyed by an outdated schema are purged and, with
+    /// `lifetime.reconcile_rules`, the rest follow their reloaded rule.
+    pub fn on_config_reload(self: &Arc<Self>, cfg: Config) {
+        self.storage.set_memory_limits(cfg.storage());
+        let workers = [(SVC_EVICTOR, eviction_worker_config(&cfg)), (SVC_LIFETIME_MANAGER, lifetime_worker_config(&cfg))];
+        for (name, worker_cfg) in workers {
+            if let Err(e) = self.governor.reload(name, worker_cfg) {
+                warn!(component = COMP_STORAGE, name, error = %e, "worker did not take the reloaded config");
+            }
+        }
+
+        if cfg.lifetime().and_then(|l| l.reconcile_rules).unwrap_or(false) {
+            self.schedule_rule_reconciliation(cfg.clone());
+        }
+        self.schedule_key_schema_purge(cfg);
+    }
 }
 
 #[async_trait::async_trait]
 impl Storage for DB {
     fn get(&self, entry: &Entry) -> (Option<Entry>, bool) {
-        self.storage.get(entry)
+        self.get_variant(entry, &[])
+    }
+
+    fn get_variant(&self, entry: &Entry, request_headers: &[(&str, &str)]) -> (Option<Entry>, bool) {
+        let mut found = self.storage.get_variant(entry, request_headers);
+        if !found.1 && self.load_pending(entry.key()) {
+            found = self.storage.get_variant(entry, request_headers);
+        }
+        // Misses are sampled by the fill that follows them in set.
+        if let (Some(sampler), (Some(stored), true)) = (&self.analytics, &found) {
+            sampler.record(stored.key(), stored.weight());
+        }
+        found
     }
 
     fn get_by_key(&self, key: u64) -> (Option<Entry>, bool) {
-        let entry = self.storage.get_by_key(key);
+        let mut entry = self.storage.get_by_key(key);
+        if entry.is_none() && self.load_pending(key) {
+            entry = self.storage.get_by_key(key);
+        }
         let hit = entry.is_some();
         (entry, hit)
     }
 
     fn set(&self, entry: Entry) -> bool {
+        if let Some(sampler) = &self.analytics {
+            sampler.record(entry.key(), entry.weight());
+        }
         self.storage.set(entry)
     }
 
@@ -262,55 +432,126 @@ impl Storage for DB {
         self.storage.walk_shards(ctx, f);
     }
 
+    fn shards_count(&self) -> usize {
+        crate::db::Storage::shards_count(self.storage.as_ref())
+    }
+
+    fn walk_shard(&self, ctx: &CancellationToken, shard_id: usize, f: &mut dyn FnMut(&crate::db::storage::Shard<Entry>)) {
+        crate::db::Storage::walk_shard(self.storage.as_ref(), ctx, shard_id, f);
+    }
+
     fn remove(&self, entry: &Entry) -> (i64, bool) {
         self.storage.remove(entry)
     }
 
+    fn mark_outdated(&self, entry: &Entry) {
+        self.storage.mark_outdated(entry)
+    }
+
     fn stat(&self) -> (i64, i64) {
         self.storage.stat()
     }
 
-    fn clear(&self) {
-        self.storage.clear();
+    fn clear(&self) -> (i64, i64) {
+        self.storage.clear()
     }
 
-    async fn close(&self) -> Result<()> {
-        let stop_ctx = CancellationToken::new();
+    fn clear_pending_bytes(&self) -> i64 {
+        self.storage.clear_pending_bytes()
+    }
 
-        if self.cfg.is_enabled()
-            && self
-                .cfg
-                .data()
-                .and_then(|d| d.dump.as_ref())
-                .map(|d| d.enabled)
-                .unwrap_or(false)
-        {
-            match timeout(
-                Duration::from_secs(60),
-                self.persistence.dump(stop_ctx.clone()),
-            )
-            .await
-            {
-                Ok(result) => {
-                    if let Err(e) = result {
-                        error!(
-                            component = COMP_DUMP,
-                            event = "store_failed",
-                            error = %e,
-                            "failed to store cache dump"
-                        );
-                    }
-                }
-                Err(_) => {
-                    error!(
-                        component = COMP_DUMP,
-                        event = "store_timeout",
-                        "dump operation timed out"
-                    );
-                }
-            }
+    fn is_admission_active(&self) -> bool {
+        self.storage.is_admission_active()
+    }
+
+    fn refresh_backlog(&self) -> usize {
+        self.storage.refresh_backlog()
+    }
+
+    fn tagged(&self, tag: u64) -> Vec<Entry> {
+        self.storage.tagged(tag)
+    }
+
+    fn memory_limits(&self) -> MemoryLimits {
+        self.storage.memory_limits()
+    }
+
+    fn counts_hits(&self) -> bool {
+        self.storage.counts_hits()
+    }
+
+    /// Over the new soft limit, a pass of soft eviction starts at once rather than on the
+    /// evictor's next tick; it frees a bounded share per call and the evictor goes on from there.
+    fn resize(&self, size: Option<i64>) -> MemoryLimits {
+        let limits = self.storage.resize(size);
+        if self.storage.soft_memory_limit_overcome() {
+            let storage = self.storage.clone();
+            tokio::task::spawn_blocking(move || {
+                let (freed_bytes, items) = storage.soft_evict_until_within_limit(RESIZE_EVICTION_BACKOFF);
+                crate::metrics::add_soft_eviction_stat_counters(freed_bytes, items, 0);
+            });
         }
+        limits
+    }
+
+    fn resized(&self) -> Option<i64> {
+        self.storage.resized()
+    }
+
+    fn tombstone(&self, key: u64, ttl: Duration) {
+        self.tombstones.add(key, ttl);
+    }
 
+    fn is_tombstoned(&self, key: u64) -> bool {
+        self.tombstones.contains(key)
+    }
+
+    fn walks(&self) -> Option<Arc<WalkCoordinator>> {
+        Some(self.walks.clone())
+    }
+
+    fn jobs(&self) -> Option<Arc<Jobs>> {
+        Some(self.jobs.clone())
+    }
+
+    fn access_sampler(&self) -> Option<Arc<AccessSampler>> {
+        self.analytics.clone()
+    }
+
+    fn failed_workers(&self) -> Vec<String> {
+        self.failed_workers.clone()
+    }
+
+    fn is_dump_loaded(&self) -> bool {
+        !self.cfg.is_enabled()
+            || !self.cfg.data().and_then(|d| d.dump.as_ref()).is_some_and(|d| d.enabled)
+            || self.persistence.is_loaded()
+    }
+
+    fn stop_workers(&self) {
+        self.governor.stop();
+    }
+
+    async fn dump(&self) -> Result<()> {
+        let enabled = self.cfg.is_enabled()
+            && self.cfg.data().and_then(|d| d.dump.as_ref()).map(|d| d.enabled).unwrap_or(false);
+        if !enabled {
+            return Ok(());
+        }
+        // Abandoning the dump (the shutdown phase running out of time) stops it as well.
+        let stop_ctx = CancellationToken::new();
+        let _stop = stop_ctx.clone().drop_guard();
+        self.persistence.dump(stop_ctx).await.inspect_err(|e| {
+            error!(
+                component = COMP_DUMP,
+                event = "store_failed",
+                error = %e,
+                "failed to store cache dump"
+            );
+        })
+    }
+
+    async fn close(&self) -> Result<()> {
         if let Err(e) = self.storage.close().await {
             error!(
                 component = COMP_STORAGE,
@@ -333,7 +574,53 @@ impl Drop for DB {
     }
 }
 
+/// Registers the worker with the governor, starts it and turns it on when enabled.
+/// Worker settings of the evictor (`eviction`).
+fn eviction_worker_config(cfg: &Config) -> Arc<dyn crate::governor::Config> {
+    let freq = crate::workers::CallFreq::new(
+        0,
+        cfg.eviction()
+            .and_then(|e| e.check_interval)
+            .unwrap_or(Duration::from_millis(100)),
+    );
+    Arc::new(crate::workers::WorkerConfig::new(
+        cfg.eviction().map(|e| e.enabled).unwrap_or(false),
+        Arc::new(freq) as Arc<dyn crate::governor::Freq>,
+        cfg.eviction().and_then(|e| e.replicas).unwrap_or(32),
+    ))
+}
+
+/// Worker settings of the lifetime manager (`lifetime`).
+fn lifetime_worker_config(cfg: &Config) -> Arc<dyn crate::governor::Config> {
+    let freq = crate::workers::CallFreq::new(
+        cfg.lifetime().and_then(|l| l.rate).unwrap_or(1000) as usize,
+        Duration::ZERO,
+    );
+    Arc::new(crate::workers::WorkerConfig::new(
+        cfg.lifetime().map(|l| l.enabled).unwrap_or(false),
+        Arc::new(freq) as Arc<dyn crate::governor::Freq>,
+        cfg.lifetime().and_then(|l| l.replicas).unwrap_or(32),
+    ))
+}
+
+fn start_worker(
+    gov: &dyn Governor,
+    name: &str,
+    service: Arc<dyn crate::governor::Service>,
+    enabled: bool,
+) -> std::result::Result<(), GovernorError> {
+    gov.register(name.to_string(), service)?;
+    gov.start(name)?;
+    if enabled {
+        gov.on(name)?;
+    } else {
+        info!(name, event = "on/off", "disabled");
+    }
+    Ok(())
+}
+
 /// Creates a new dumper instance.
+#[cfg(feature = "dump")]
 fn new_dump(
     cfg: Config,
     storage: Arc<crate::db::storage::Storage>,
@@ -344,149 +631,11 @@ fn new_dump(
     )?))
 }
 
-/// Loads mock data into storage.
-pub fn load_mocks(ctx: CancellationToken, cfg: Config, storage: Arc<dyn Storage>, num: usize) {
-    load_mocks_with(ctx, cfg, storage, num, false);
-}
-
-/// Loads mock data with optional brotli compression.
-fn load_mocks_with(
-    ctx: CancellationToken,
-    cfg: Config,
-    storage: Arc<dyn Storage>,
-    num: usize,
-    brotli: bool,
-) {
-    tokio::task::spawn(async move {
-        info!(
-            component = "mocks",
-            event = "loading_start",
-            "start loading mock data"
-        );
-
-        let path = b"/api/v1/user";
-        for i in 0..num {
-            if ctx.is_cancelled() {
-                break;
-            }
-            let entry = get_single_mock_with(i, path, cfg.clone(), brotli);
-            storage.set(entry);
-        }
-
-        info!(
-            component = "mocks",
-            event = "loading_finish",
-            "finished loading mock data"
-        );
-    });
-}
-
-/// Gets a single mock entry.
-fn get_single_mock_with(i: usize, path: &[u8], cfg: Config, brotli: bool) -> Entry {
-    use crate::model::{match_cache_rule, Response};
-    use std::sync::Arc;
-
-    // Try to match a rule for the path, or create a default rule
-    let rule = match match_cache_rule(&cfg, path) {
-        Ok(r) => r, // Already Arc<Rule>, just use it
-        Err(_) => {
-            // Create a default rule if no match found
-            Arc::new(crate::config::Rule {
-                path: Some(String::from_utf8_lossy(path).to_string()),
-                path_bytes: Some(path.to_vec()),
-                cache_key: crate::config::RuleKey {
-                    query: None,
-                    query_bytes: None,
-                    headers: None,
-                    headers_map: None,
-                },
-                cache_value: crate::config::RuleValue {
-                    headers: None,
-                    headers_map: None,
-                },
-                refresh: None,
-            })
-        }
-    };
-
-    // Create empty queries and headers for mock
-    let queries: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
-    let headers: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
-
-    // Create entry
-    let entry = Entry::new(rule, &queries, &headers);
-
-    // Create mock JSON response body
-    let mock_json = format!(
-        r#"{{
-      "response": {{
-        "status": "ok",
-        "payload": {{
-          "id": "item-10",
-          "context": {{
-            "label": "Mock Label [{}]",
-            "tags": [
-              "example",
-              "tag-placeholder"
-            ]
-          }},
-          "content": {{
-            "header": "Header for item [{}]",
-            "summary": "This is a mock summary for. Placeholder inserted.",
-            "details": {{
-              "info": "Extra information with repeated placeholder.",
-              "active": true,
-              "score": 10,
-            }},
-            "assets": {{
-              "images": [],
-              "videos": null
-            }}
-          }}
-        }},
-        "meta": {{
-          "generatedAt": "2025-01-01T00:00:00Z",
-          "mockSource": "advCache-Mock-v2"
-        }}
-      }}
-    }}"#,
-        i, i
-    );
-
-    let body = if brotli {
-        // Compress with brotli if requested
-        use brotli::enc::BrotliEncoderParams;
-        let mut compressed = Vec::new();
-        if brotli::BrotliCompress(
-            &mut mock_json.as_bytes(),
-            &mut compressed,
-            &BrotliEncoderParams::default(),
-        )
-        .is_err()
-        {
-            // Fallback to uncompressed if compression fails
-            compressed = mock_json.as_bytes().to_vec();
-        }
-        compressed
-    } else {
-        mock_json.as_bytes().to_vec()
-    };
-
-    // Create mock response
-    let response = Response {
-        status: 200,
-        headers: vec![("content-type".to_string(), "application/json".to_string())],
-        body,
-    };
-
-    // Set payload
-    entry.set_payload(&queries, &headers, &response);
-
-    // Set updated timestamp
-        entry.updated_at_ref().store(
-        crate::time::unix_nano(),
-        std::sync::atomic::Ordering::Relaxed,
-    );
-
-    entry
+/// Creates the dumper of builds without the `dump` feature.
+#[cfg(not(feature = "dump"))]
+fn new_dump(
+    _cfg: Config,
+    _storage: Arc<crate::db::storage::Storage>,
+) -> Result<Arc<dyn Dumper>> {
+    Ok(Arc::new(crate::db::persistance::NoDumper))
 }
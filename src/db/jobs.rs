@@ -0,0 +1,398 @@
+//! Admin jobs walking the storage shards: match entries, then act on the matches.
+//!
+//! Invalidations, purges by prefix, clears by rule and key schema purges share one shape,
+//! described by a [`ShardJob`]: a matcher and an action, run within the
+//! `storage.admin_job_rate` budget.
+//! A submitted job takes a walk permit (see [`super::walks`]) and fans out one blocking task
+//! per shard, [`MAX_PARALLEL_SHARDS`] at a time. Each task collects the keys matching under
+//! the shard read lock and releases it before acting, on the entries found again by key that
+//! still match, so no action runs under a shard lock. Progress is kept in a [`JobRecord`],
+//! listed by `/advcache/jobs` along with the last finished jobs; a job cancelled by
+//! `DELETE /advcache/jobs/{id}` stops between two entries and keeps what it did so far.
+
+use std::collections::VecDeque;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use governor::clock::{Clock, DefaultClock};
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::db::walks::{WalkCoordinator, WalkQueueTimeout};
+use crate::db::Storage;
+use crate::model::Entry;
+
+const COMP_JOBS: &str = "jobs";
+
+/// Finished jobs kept for `/advcache/jobs`; older ones are dropped.
+const MAX_FINISHED_JOBS: usize = 64;
+
+/// Shards a job walks at once.
+pub const MAX_PARALLEL_SHARDS: usize = 8;
+
+/// Longest sleep waiting for the rate budget, so a cancellation is seen soon.
+const MAX_BUDGET_WAIT: Duration = Duration::from_millis(50);
+
+/// What an action did with a matched entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Acted,
+    /// Left alone, e.g. refreshed after an `_if_refreshed_before`.
+    Skipped,
+}
+
+type Matcher = Box<dyn Fn(&Entry) -> bool + Send + Sync>;
+type Action = Box<dyn Fn(&dyn Storage, &Entry) -> Outcome + Send + Sync>;
+
+/// A walk over the shards acting on the entries it matches.
+pub struct ShardJob {
+    op: &'static str,
+    target: String,
+    matcher: Matcher,
+    action: Action,
+    background: bool,
+}
+
+impl ShardJob {
+    /// Job of `op` over `target` (the invalidated path, the cleared rule...). The matcher runs
+    /// under the shard read lock and again on the entry about to be acted on.
+    pub fn new(
+        op: &'static str,
+        target: impl Into<String>,
+        matcher: impl Fn(&Entry) -> bool + Send + Sync + 'static,
+        action: impl Fn(&dyn Storage, &Entry) -> Outcome + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            op,
+            target: target.into(),
+            matcher: Box::new(matcher),
+            action: Box::new(action),
+            background: false,
+        }
+    }
+
+    /// Waits for a walk permit as long as it takes, instead of failing after
+    /// `storage.admin_walk_timeout`. For jobs run in the background, which must not be skipped.
+    pub fn background(mut self) -> Self {
+        self.background = true;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Waiting for a walk permit.
+    Queued,
+    Running,
+    Done,
+    Cancelled,
+    /// Gave up waiting for a walk permit.
+    Failed,
+}
+
+/// Progress of a job.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobRecord {
+    pub id: u64,
+    pub op: &'static str,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub target: String,
+    pub state: JobState,
+    pub shards: usize,
+    pub shards_done: usize,
+    /// Entries walked.
+    pub scanned: u64,
+    /// Entries matched under the shard locks.
+    pub matched: u64,
+    pub acted: u64,
+    /// Matched entries the action left alone.
+    pub skipped: u64,
+    /// Shards whose task failed.
+    pub errors: u64,
+    /// Unix ms.
+    pub started_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<i64>,
+    /// Why a failed job did not run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+struct Status {
+    state: JobState,
+    finished_at: Option<i64>,
+    error: Option<WalkQueueTimeout>,
+}
+
+/// A submitted job, shared by its runner and whoever watches it.
+pub struct Job {
+    id: u64,
+    op: &'static str,
+    target: String,
+    started_at: i64,
+    token: CancellationToken,
+    /// Cancelled once the job is finished, whatever its outcome.
+    finished: CancellationToken,
+    shards: AtomicUsize,
+    shards_done: AtomicUsize,
+    scanned: AtomicU64,
+    matched: AtomicU64,
+    acted: AtomicU64,
+    skipped: AtomicU64,
+    errors: AtomicU64,
+    status: Mutex<Status>,
+}
+
+impl Job {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Stops the job between two entries; a finished job is left as it is.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.is_cancelled()
+    }
+
+    /// Waits for the job to finish, with the walk queue timeout it gave up on if it failed.
+    pub async fn wait(&self) -> Result<JobRecord, WalkQueueTimeout> {
+        self.finished.cancelled().await;
+        let error = self.status.lock().error.clone();
+        match error {
+            Some(e) => Err(e),
+            None => Ok(self.record()),
+        }
+    }
+
+    pub fn record(&self) -> JobRecord {
+        let status = self.status.lock();
+        JobRecord {
+            id: self.id,
+            op: self.op,
+            target: self.target.clone(),
+            state: status.state,
+            shards: self.shards.load(Ordering::Relaxed),
+            shards_done: self.shards_done.load(Ordering::Relaxed),
+            scanned: self.scanned.load(Ordering::Relaxed),
+            matched: self.matched.load(Ordering::Relaxed),
+            acted: self.acted.load(Ordering::Relaxed),
+            skipped: self.skipped.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            started_at: self.started_at,
+            finished_at: status.finished_at,
+            error: status.error.as_ref().map(|e| e.to_string()),
+        }
+    }
+
+    fn set_state(&self, state: JobState) {
+        self.status.lock().state = state;
+    }
+
+    fn finish(&self, state: JobState, error: Option<WalkQueueTimeout>) {
+        {
+            let mut status = self.status.lock();
+            status.state = state;
+            status.finished_at = Some(unix_millis());
+            status.error = error;
+        }
+        self.finished.cancel();
+    }
+}
+
+/// Runs the submitted jobs and keeps their records.
+pub struct Jobs {
+    walks: Arc<WalkCoordinator>,
+    shutdown: CancellationToken,
+    /// `storage.admin_job_rate`.
+    rate: Option<u32>,
+    next_id: AtomicU64,
+    /// Jobs queued or running and the last finished ones, in submission order.
+    jobs: Mutex<VecDeque<Arc<Job>>>,
+}
+
+impl Jobs {
+    /// Jobs taking their walk permits of `walks`, cancelled on shutdown.
+    pub fn new(cfg: &Config, walks: Arc<WalkCoordinator>, shutdown: CancellationToken) -> Self {
+        Self {
+            walks,
+            shutdown,
+            rate: cfg.cache.storage.as_ref().and_then(|s| s.admin_job_rate),
+            next_id: AtomicU64::new(1),
+            jobs: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Starts the job over the shards of `storage`.
+    pub fn submit(&self, storage: Arc<dyn Storage>, spec: ShardJob) -> Arc<Job> {
+        let job = Arc::new(Job {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            op: spec.op,
+            target: spec.target.clone(),
+            started_at: unix_millis(),
+            token: self.shutdown.child_token(),
+            finished: CancellationToken::new(),
+            shards: AtomicUsize::new(0),
+            shards_done: AtomicUsize::new(0),
+            scanned: AtomicU64::new(0),
+            matched: AtomicU64::new(0),
+            acted: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            status: Mutex::new(Status { state: JobState::Queued, finished_at: None, error: None }),
+        });
+
+        {
+            let mut jobs = self.jobs.lock();
+            jobs.push_back(job.clone());
+            let mut finished = jobs.iter().filter(|j| j.is_finished()).count();
+            while finished > MAX_FINISHED_JOBS {
+                let Some(oldest) = jobs.iter().position(|j| j.is_finished()) else {
+                    break;
+                };
+                jobs.remove(oldest);
+                finished -= 1;
+            }
+        }
+
+        tokio::spawn(run(storage, self.walks.clone(), spec, self.rate, job.clone()));
+        job
+    }
+
+    /// Job of the id, running or among the last finished.
+    pub fn get(&self, id: u64) -> Option<Arc<Job>> {
+        self.jobs.lock().iter().find(|j| j.id == id).cloned()
+    }
+
+    /// Records of the jobs running and the last finished, oldest first.
+    pub fn list(&self) -> Vec<JobRecord> {
+        self.jobs.lock().iter().map(|j| j.record()).collect()
+    }
+}
+
+async fn run(storage: Arc<dyn Storage>, walks: Arc<WalkCoordinator>, spec: ShardJob, rate: Option<u32>, job: Arc<Job>) {
+    let acquired = async {
+        if spec.background {
+            Ok(walks.acquire_background(spec.op).await)
+        } else {
+            walks.acquire(spec.op).await
+        }
+    };
+    let permit = tokio::select! {
+        permit = acquired => permit,
+        _ = job.token.cancelled() => return job.finish(JobState::Cancelled, None),
+    };
+    let _permit = match permit {
+        Ok(permit) => permit,
+        Err(e) => {
+            warn!(component = COMP_JOBS, id = job.id, op = job.op, target = %job.target, error = %e, "job not run");
+            return job.finish(JobState::Failed, Some(e));
+        }
+    };
+
+    job.set_state(JobState::Running);
+    let shards = storage.shards_count();
+    job.shards.store(shards, Ordering::Relaxed);
+    let limiter = rate.and_then(NonZeroU32::new).map(|rate| Arc::new(RateLimiter::direct(Quota::per_second(rate))));
+    let spec = Arc::new(spec);
+    let slots = Arc::new(Semaphore::new(MAX_PARALLEL_SHARDS));
+    let mut tasks = JoinSet::new();
+    for shard_id in 0..shards {
+        if job.token.is_cancelled() {
+            break;
+        }
+        let slot = slots.clone().acquire_owned().await.expect("job slots are never closed");
+        let (storage, spec, job, limiter) = (storage.clone(), spec.clone(), job.clone(), limiter.clone());
+        tasks.spawn_blocking(move || {
+            let _slot = slot;
+            run_shard(storage.as_ref(), &spec, &job, limiter.as_deref(), shard_id);
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        if let Err(e) = joined {
+            job.errors.fetch_add(1, Ordering::Relaxed);
+            warn!(component = COMP_JOBS, id = job.id, op = job.op, error = %e, "job shard task failed");
+        }
+    }
+
+    let state = if job.token.is_cancelled() { JobState::Cancelled } else { JobState::Done };
+    job.finish(state, None);
+    let record = job.record();
+    info!(
+        component = COMP_JOBS,
+        event = "job_finished",
+        id = record.id,
+        op = record.op,
+        target = %record.target,
+        state = ?record.state,
+        scanned = record.scanned,
+        matched = record.matched,
+        acted = record.acted,
+        skipped = record.skipped,
+        errors = record.errors,
+        "admin job finished"
+    );
+}
+
+/// Matches the entries of one shard under its read lock, then acts on them with it released.
+fn run_shard(storage: &dyn Storage, spec: &ShardJob, job: &Job, limiter: Option<&DefaultDirectRateLimiter>, shard_id: usize) {
+    let mut matched = Vec::new();
+    let mut scanned = 0u64;
+    storage.walk_shard(&job.token, shard_id, &mut |shard| {
+        shard.walk_r(&job.token, |key, entry| {
+            scanned += 1;
+            if (spec.matcher)(entry) {
+                matched.push(key);
+            }
+            true
+        });
+    });
+    job.scanned.fetch_add(scanned, Ordering::Relaxed);
+    job.matched.fetch_add(matched.len() as u64, Ordering::Relaxed);
+
+    for key in matched {
+        if let Some(limiter) = limiter {
+            wait_for_budget(limiter, &job.token);
+        }
+        if job.token.is_cancelled() {
+            return;
+        }
+        // Removed or refilled since the walk.
+        let (Some(entry), _) = storage.get_by_key(key) else {
+            continue;
+        };
+        if !(spec.matcher)(&entry) {
+            continue;
+        }
+        let counter = match (spec.action)(storage, &entry) {
+            Outcome::Acted => &job.acted,
+            Outcome::Skipped => &job.skipped,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+    job.shards_done.fetch_add(1, Ordering::Relaxed);
+}
+
+fn wait_for_budget(limiter: &DefaultDirectRateLimiter, token: &CancellationToken) {
+    while let Err(not_until) = limiter.check() {
+        if token.is_cancelled() {
+            return;
+        }
+        std::thread::sleep(not_until.wait_time_from(DefaultClock::default().now()).min(MAX_BUDGET_WAIT));
+    }
+}
+
+fn unix_millis() -> i64 {
+    crate::time::unix_nano() / 1_000_000
+}
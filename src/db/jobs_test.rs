@@ -0,0 +1,155 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::config::{self, Config, ConfigTrait};
+    use crate::db::jobs::{JobState, Jobs, Outcome, ShardJob};
+    use crate::db::{Storage, DB};
+    use crate::governor::Orchestrator;
+    use crate::model::{Entry, Response};
+    use crate::upstream::testing::MockUpstream;
+
+    const USER: &str = "/api/v1/user";
+    const BUYER: &str = "/api/v1/buyer";
+
+    fn stored_entry(cfg: &Config, path: &str, id: usize) -> Entry {
+        let rule = cfg.rule(path).unwrap();
+        let queries = vec![(b"user[id]".to_vec(), id.to_string().into_bytes())];
+        let entry = Entry::new(rule, &queries, &[]);
+        entry.set_payload(
+            &queries,
+            &[],
+            &Response {
+                status: 200,
+                headers: vec![],
+                body: format!("{{\"id\":{}}}", id).into_bytes(),
+            },
+        );
+        entry
+    }
+
+    /// DB holding `users` entries of the user rule and `buyers` of the buyer rule.
+    fn new_db(cfg: &Config, users: usize, buyers: usize) -> (Arc<DB>, Arc<Jobs>, CancellationToken) {
+        let shutdown = CancellationToken::new();
+        let db = DB::new(shutdown.clone(), cfg.clone(), Arc::new(Orchestrator::new()), MockUpstream::new())
+            .expect("storage must start");
+        for id in 0..users {
+            assert!(db.set(stored_entry(cfg, USER, id)));
+        }
+        for id in 0..buyers {
+            assert!(db.set(stored_entry(cfg, BUYER, id)));
+        }
+        let jobs = db.jobs().expect("the db runs jobs");
+        (db, jobs, shutdown)
+    }
+
+    fn of_rule(path: &'static str) -> impl Fn(&Entry) -> bool + Send + Sync + 'static {
+        move |entry: &Entry| entry.rule().path.as_deref() == Some(path)
+    }
+
+    fn remove(db: &dyn Storage, entry: &Entry) -> Outcome {
+        match db.remove(entry) {
+            (_, true) => Outcome::Acted,
+            (_, false) => Outcome::Skipped,
+        }
+    }
+
+    /// Test that a job walks every shard and counts the entries it scanned, matched, acted on
+    /// and skipped.
+    #[tokio::test]
+    async fn test_job_counts_are_accurate() {
+        let cfg = config::new_test_config();
+        let (db, jobs, shutdown) = new_db(&cfg, 10, 5);
+
+        // Removes the users of even id, skips the others.
+        let even: HashSet<u64> = (0..10).step_by(2).map(|id| stored_entry(&cfg, USER, id).key()).collect();
+        let act = move |db: &dyn Storage, entry: &Entry| match even.contains(&entry.key()) {
+            true => remove(db, entry),
+            false => Outcome::Skipped,
+        };
+        let job = jobs.submit(db.clone(), ShardJob::new("test", USER, of_rule(USER), act));
+        let record = job.wait().await.unwrap();
+
+        assert_eq!(record.state, JobState::Done);
+        assert_eq!(record.target, USER);
+        assert_eq!((record.shards, record.shards_done), (db.shards_count(), db.shards_count()));
+        assert_eq!((record.scanned, record.matched), (15, 10));
+        assert_eq!((record.acted, record.skipped, record.errors), (5, 5, 0));
+        assert!(record.finished_at.is_some_and(|at| at >= record.started_at));
+        assert_eq!(db.stat().1, 10);
+        assert_eq!(jobs.get(record.id).unwrap().record().acted, 5);
+        shutdown.cancel();
+    }
+
+    /// Test that jobs submitted together run side by side with walk permits to spare, and that
+    /// an entry matched by both is acted on once.
+    #[tokio::test]
+    async fn test_concurrent_jobs() {
+        let mut cfg = config::new_test_config();
+        cfg.cache.storage.as_mut().unwrap().admin_walks = Some(3);
+        let (db, jobs, shutdown) = new_db(&cfg, 40, 20);
+
+        let users = jobs.submit(db.clone(), ShardJob::new("users", USER, of_rule(USER), remove));
+        let users_again = jobs.submit(db.clone(), ShardJob::new("users", USER, of_rule(USER), remove));
+        let buyers = jobs.submit(db.clone(), ShardJob::new("buyers", BUYER, of_rule(BUYER), remove));
+        let (users, users_again, buyers) = (users.wait().await.unwrap(), users_again.wait().await.unwrap(), buyers.wait().await.unwrap());
+
+        assert_eq!(buyers.acted, 20);
+        assert_eq!(users.acted + users_again.acted, 40, "each user entry is removed once");
+        assert_eq!(db.stat().1, 0);
+        let listed: Vec<_> = jobs.list().iter().map(|r| (r.id, r.state)).collect();
+        assert_eq!(listed, [(users.id, JobState::Done), (users_again.id, JobState::Done), (buyers.id, JobState::Done)]);
+        shutdown.cancel();
+    }
+
+    /// Test that a job cancelled mid-run, slowed down by `storage.admin_job_rate`, stops and
+    /// keeps what it did so far.
+    #[tokio::test]
+    async fn test_cancel_mid_run() {
+        let mut cfg = config::new_test_config();
+        cfg.cache.storage.as_mut().unwrap().admin_job_rate = Some(20);
+        let (db, jobs, shutdown) = new_db(&cfg, 60, 0);
+
+        let job = jobs.submit(db.clone(), ShardJob::new("test", USER, of_rule(USER), remove));
+        while job.record().acted < 3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        job.cancel();
+        let record = job.wait().await.unwrap();
+
+        assert_eq!(record.state, JobState::Cancelled);
+        assert!(record.acted >= 3 && record.acted < 60, "{:?}", record);
+        assert_eq!(db.stat().1, 60 - record.acted as i64);
+        shutdown.cancel();
+    }
+
+    /// Test that a queued job is cancelled before it walks, and that one giving up on its walk
+    /// permit fails with the queue timeout.
+    #[tokio::test]
+    async fn test_queued_jobs_cancel_and_fail() {
+        let mut cfg = config::new_test_config();
+        cfg.cache.storage.as_mut().unwrap().admin_walk_timeout = Some(Duration::from_millis(50));
+        let (db, jobs, shutdown) = new_db(&cfg, 5, 0);
+        let walking = db.walks().unwrap().acquire("test").await.unwrap();
+
+        let queued = jobs.submit(db.clone(), ShardJob::new("test", USER, of_rule(USER), remove).background());
+        assert_eq!(queued.record().state, JobState::Queued);
+        queued.cancel();
+        let record = queued.wait().await.unwrap();
+        assert_eq!((record.state, record.shards, record.acted), (JobState::Cancelled, 0, 0));
+
+        let timed_out = jobs.submit(db.clone(), ShardJob::new("test", USER, of_rule(USER), remove));
+        let err = timed_out.wait().await.unwrap_err();
+        assert_eq!(err.ahead, 1);
+        assert_eq!(timed_out.record().state, JobState::Failed);
+        assert!(timed_out.record().error.is_some());
+
+        drop(walking);
+        assert_eq!(db.stat().1, 5);
+        shutdown.cancel();
+    }
+}
@@ -0,0 +1,92 @@
+//! Purge of entries keyed with an outdated key schema.
+//!
+//! Changing the `cache_key` whitelists of a rule changes how its keys are built, so entries
+//! stored under the previous composition are never looked up again and would only leave
+//! with eviction. Every entry remembers the schema it was keyed with (`Rule::key_schema`);
+//! after a config is loaded, entries whose schema differs from their rule's are removed by an
+//! admin job (see [`super::jobs`]).
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use tracing::info;
+
+use crate::config::Config;
+use crate::db::jobs::{Jobs, Outcome, ShardJob};
+use crate::db::walks::OP_KEY_SCHEMA_PURGE;
+use crate::db::Storage;
+use crate::model::Entry;
+
+const COMP_KEY_SCHEMA: &str = "key_schema";
+
+/// Outcome of a key schema purge.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PurgeReport {
+    pub scanned: u64,
+    pub purged: u64,
+    pub freed_bytes: i64,
+    /// Purged entries per rule path.
+    pub by_rule: BTreeMap<String, u64>,
+}
+
+/// Removes entries whose key schema differs from the current one of their rule in `cfg`,
+/// as a background job of `jobs`. Entries of unknown schema (restored from dumps written
+/// before schemas were stored) and entries of rules absent in `cfg` are left to eviction.
+pub async fn purge_stale_key_schemas(jobs: &Jobs, storage: Arc<dyn Storage>, cfg: &Config) -> PurgeReport {
+    let schemas: HashMap<Vec<u8>, u64> = cfg
+        .rules()
+        .into_iter()
+        .flatten()
+        .map(|(path, rule)| (path.as_bytes().to_vec(), rule.key_schema()))
+        .collect();
+
+    let purged = Arc::new(Mutex::new(PurgeReport::default()));
+    let job = {
+        let purged = purged.clone();
+        // Checked again before the removal: the key may have been refilled under the current schema.
+        let is_stale = move |entry: &Entry| {
+            let schema = entry.key_schema();
+            let rule = entry.rule();
+            let path = rule.path_bytes.as_deref().unwrap_or(&[]);
+            schema != 0 && schemas.get(path).is_some_and(|current| *current != schema)
+        };
+        let remove = move |storage: &dyn Storage, entry: &Entry| {
+            let (freed, hit) = storage.remove(entry);
+            if !hit {
+                return Outcome::Skipped;
+            }
+            let mut report = purged.lock().unwrap();
+            report.purged += 1;
+            report.freed_bytes += freed;
+            let path = entry.rule().path.clone().unwrap_or_default();
+            *report.by_rule.entry(path).or_default() += 1;
+            Outcome::Acted
+        };
+        jobs.submit(storage, ShardJob::new(OP_KEY_SCHEMA_PURGE, "", is_stale, remove).background())
+    };
+    let record = job.wait().await.expect("background jobs wait for their walk permit");
+
+    let mut report = std::mem::take(&mut *purged.lock().unwrap());
+    report.scanned = record.scanned;
+
+    for (rule, purged) in &report.by_rule {
+        info!(
+            component = COMP_KEY_SCHEMA,
+            event = "purged",
+            rule = %rule,
+            purged = purged,
+            "purged entries keyed with an outdated cache_key schema"
+        );
+    }
+    info!(
+        component = COMP_KEY_SCHEMA,
+        event = "purge_done",
+        job = record.id,
+        scanned = report.scanned,
+        purged = report.purged,
+        freed_bytes = report.freed_bytes,
+        "key schema purge finished"
+    );
+
+    report
+}
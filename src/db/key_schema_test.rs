@@ -0,0 +1,134 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::config::{self, Config, ConfigTrait, Rule};
+    use crate::db::{Storage, DB};
+    use crate::governor::Orchestrator;
+    use crate::model::to_bytes::from_bytes;
+    use crate::model::{Entry, Response};
+    use crate::upstream::testing::MockUpstream;
+
+    fn rule_with(path: &str, query: &[&str], headers: &[&str]) -> Rule {
+        let mut rule = Rule::bare(path);
+        rule.cache_key.query = Some(query.iter().map(|q| q.to_string()).collect());
+        rule.cache_key.headers = Some(headers.iter().map(|h| h.to_string()).collect());
+        rule
+    }
+
+    /// Same config with the query whitelist of `path` extended by one parameter,
+    /// as a config reload after editing `cache_key` would produce.
+    fn with_extra_key_query(cfg: &Config, path: &str, query: &str) -> Config {
+        let mut cfg = cfg.clone();
+        let rules = cfg.cache.rules.as_mut().unwrap();
+        let mut rule = (*rules[path]).clone();
+        rule.cache_key.query.get_or_insert_with(Vec::new).push(query.to_string());
+        rules.insert(path.to_string(), Arc::new(rule));
+        cfg
+    }
+
+    fn stored_entry(cfg: &Config, path: &str, id: usize) -> Entry {
+        let rule = cfg.rule(path).unwrap();
+        let queries = vec![(b"user[id]".to_vec(), id.to_string().into_bytes())];
+        let entry = Entry::new(rule, &queries, &[]);
+        entry.set_payload(
+            &queries,
+            &[],
+            &Response {
+                status: 200,
+                headers: vec![],
+                body: format!("{{\"id\":{}}}", id).into_bytes(),
+            },
+        );
+        entry
+    }
+
+    fn new_db(cfg: &Config) -> (Arc<DB>, CancellationToken) {
+        let shutdown = CancellationToken::new();
+        let db = DB::new(shutdown.clone(), cfg.clone(), Arc::new(Orchestrator::new()), MockUpstream::new())
+            .expect("storage must start");
+        (db, shutdown)
+    }
+
+    /// Test that the schema ignores whitelist order and header case but follows whitelist changes.
+    #[test]
+    fn test_key_schema_follows_whitelists() {
+        let base = rule_with("/p", &["a", "b"], &["Accept-Encoding"]);
+
+        assert_ne!(Rule::bare("/p").key_schema(), 0);
+        assert_eq!(base.key_schema(), rule_with("/other", &["b", "a"], &["accept-encoding"]).key_schema());
+        assert_ne!(base.key_schema(), rule_with("/p", &["a", "b", "c"], &["Accept-Encoding"]).key_schema());
+        assert_ne!(base.key_schema(), rule_with("/p", &["a", "b"], &[]).key_schema());
+        assert_ne!(base.key_schema(), rule_with("/p", &["a"], &["b", "Accept-Encoding"]).key_schema());
+    }
+
+    /// Test that the key schema survives the dump wire format and that old records decode as unknown.
+    #[test]
+    fn test_key_schema_round_trips_through_dump_format() {
+        let cfg = config::new_test_config();
+        let entry = stored_entry(&cfg, "/api/v1/user", 1);
+        assert_eq!(entry.key_schema(), cfg.rule("/api/v1/user").unwrap().key_schema());
+
+        let restored = from_bytes(&entry.to_bytes(), &cfg).unwrap();
+        assert_eq!(restored.key_schema(), entry.key_schema());
+
+        let legacy = from_bytes(include_bytes!("../model/testdata/entry_v1.golden"), &cfg).unwrap();
+        assert_eq!(legacy.key_schema(), 0);
+    }
+
+    /// Test that after a reload changing one rule's whitelist only that rule's entries are purged.
+    #[tokio::test]
+    async fn test_reload_with_changed_whitelist_purges_orphans() {
+        let cfg = config::new_test_config();
+        let (db, shutdown) = new_db(&cfg);
+
+        for id in 0..8 {
+            assert!(db.set(stored_entry(&cfg, "/api/v1/user", id)));
+            assert!(db.set(stored_entry(&cfg, "/api/v1/buyer", id)));
+        }
+
+        let report = db.schedule_key_schema_purge(cfg.clone()).await.unwrap();
+        assert_eq!((report.scanned, report.purged), (16, 0));
+
+        let reloaded = with_extra_key_query(&cfg, "/api/v1/user", "user[locale]");
+        let report = db.schedule_key_schema_purge(reloaded.clone()).await.unwrap();
+        assert_eq!(report.purged, 8);
+        assert!(report.freed_bytes > 0);
+        assert_eq!(report.by_rule.get("/api/v1/user"), Some(&8));
+        assert_eq!(report.by_rule.len(), 1);
+
+        for id in 0..8 {
+            assert!(db.get(&stored_entry(&cfg, "/api/v1/user", id)).0.is_none());
+            assert!(db.get(&stored_entry(&cfg, "/api/v1/buyer", id)).0.is_some());
+        }
+        assert_eq!(db.stat().1, 8);
+
+        // Entries filled under the reloaded rule are current and stay.
+        assert!(db.set(stored_entry(&reloaded, "/api/v1/user", 100)));
+        let report = db.schedule_key_schema_purge(reloaded).await.unwrap();
+        assert_eq!(report.purged, 0);
+        shutdown.cancel();
+    }
+
+    /// Test that dump-restored entries carry their schema into the purge, while legacy ones are kept.
+    #[tokio::test]
+    async fn test_dump_restored_entries_are_purged_after_whitelist_change() {
+        let cfg = config::new_test_config();
+        let dumped: Vec<Vec<u8>> = (0..4).map(|id| stored_entry(&cfg, "/api/v1/user", id).to_bytes()).collect();
+
+        let reloaded = with_extra_key_query(&cfg, "/api/v1/user", "user[locale]");
+        let (db, shutdown) = new_db(&reloaded);
+        for record in &dumped {
+            assert!(db.set(from_bytes(record, &reloaded).unwrap()));
+        }
+        assert!(db.set(from_bytes(include_bytes!("../model/testdata/entry_v1.golden"), &reloaded).unwrap()));
+
+        let report = db.schedule_key_schema_purge(reloaded).await.unwrap();
+        assert_eq!(report.scanned, 5);
+        assert_eq!(report.purged, 4);
+        assert_eq!(db.stat().1, 1);
+        shutdown.cancel();
+    }
+}
@@ -7,7 +7,7 @@ use tokio::time::interval;
 use tokio_util::sync::CancellationToken;
 
 use crate::bytes;
-use crate::config::{Config, ConfigTrait};
+use crate::config::{Config, ConfigTrait, MemoryLimits};
 use crate::metrics;
 
 
@@ -23,17 +23,13 @@ lazy_static::lazy_static! {
 pub async fn logger(
     shutdown_token: CancellationToken,
     cfg: Arc<tokio::sync::RwLock<Config>>,
-    soft_memory_limit: i64,
-    hard_memory_limit: i64,
+    limits: Arc<dyn Fn() -> MemoryLimits + Send + Sync>,
     mem: Arc<dyn Fn() -> i64 + Send + Sync>,
     len: Arc<dyn Fn() -> i64 + Send + Sync>,
 ) {
     let mut each_sec = interval(Duration::from_secs(1));
     let mut each_5sec = interval(Duration::from_secs(5));
 
-    let soft_limit = bytes::fmt_mem(soft_memory_limit);
-    let hard_limit = bytes::fmt_mem(hard_memory_limit);
-
     let mut adm_allowed_5s = 0i64;
     let mut adm_not_allowed_5s = 0i64;
     let mut hard_evicted_5s = 0i64;
@@ -78,6 +74,10 @@ pub async fn logger(
                 adm_allowed_5s = 0;
                 adm_not_allowed_5s = 0;
 
+                let limits = limits();
+                let soft_limit = bytes::fmt_mem(limits.soft);
+                let hard_limit = bytes::fmt_mem(limits.hard);
+
                 let freed_bytes_str = bytes::fmt_mem(hard_evicted_bytes_5s);
                 tracing::info!(
                     freed_bytes = %freed_bytes_str,
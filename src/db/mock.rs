@@ -0,0 +1,164 @@
+//! Mock data prefilled into storage (`data.mock`) for local testing and benchmarks.
+
+use std::sync::Arc;
+
+use rand::distributions::{Alphanumeric, DistString};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::config::{Config, MockSpec, Rule};
+use crate::db::Storage;
+use crate::model::{match_cache_rule, Entry, Response};
+use crate::rate;
+
+/// Query param varied to give mock entries of one path distinct keys.
+pub const MOCK_QUERY: &[u8] = b"mock_id";
+
+const DEFAULT_PATH: &str = "/api/v1/user";
+const DEFAULT_LENGTH: usize = 1_000_000;
+
+/// Entries generated between two cancellation checks when no rate is set.
+const YIELD_EVERY: usize = 1024;
+
+/// Phrase repeated in compressible bodies.
+const FILLER: &[u8] = b"lorem ipsum dolor sit amet ";
+
+/// The configured specs, or `length` entries of `/api/v1/user` when there are none.
+pub fn specs(cfg: &Config) -> Vec<MockSpec> {
+    use crate::config::ConfigTrait;
+
+    let mock = cfg.data().and_then(|d| d.mock.as_ref());
+    if let Some(specs) = mock.and_then(|m| m.specs.clone()) {
+        return specs;
+    }
+    vec![MockSpec {
+        path: DEFAULT_PATH.to_string(),
+        count: mock.and_then(|m| m.length).unwrap_or(DEFAULT_LENGTH),
+        body_size: None,
+        compressible: true,
+        query_cardinality: None,
+    }]
+}
+
+/// Loads the mock entries of `specs` into storage in the background, at most `rate` a second.
+pub fn load_mocks(ctx: CancellationToken, cfg: Config, storage: Arc<dyn Storage>, specs: Vec<MockSpec>, rate: Option<usize>) {
+    tokio::task::spawn(async move {
+        info!(component = "mocks", event = "loading_start", "start loading mock data");
+
+        let loaded = generate(ctx, &cfg, &specs, rate, |entry| {
+            storage.set(entry);
+        })
+        .await;
+
+        info!(component = "mocks", event = "loading_finish", entries = loaded, "finished loading mock data");
+    });
+}
+
+/// Generates the entries of `specs` in order, handing each to `set`, until done or `ctx` is
+/// cancelled. Returns the number of entries generated.
+pub async fn generate(
+    ctx: CancellationToken,
+    cfg: &Config,
+    specs: &[MockSpec],
+    rate: Option<usize>,
+    mut set: impl FnMut(Entry),
+) -> usize {
+    // The limiter's ticker stops with generation.
+    let ticks = ctx.child_token();
+    let _stop_ticks = ticks.clone().drop_guard();
+    let mut limiter = rate.map(|rate| rate::Limiter::new(ticks, rate));
+
+    let mut generated = 0;
+    for spec in specs {
+        let rule = match match_cache_rule(cfg, spec.path.as_bytes()) {
+            Ok(rule) => rule,
+            Err(_) => Arc::new(Rule::bare(&spec.path)),
+        };
+        for i in 0..spec.count {
+            match limiter.as_mut() {
+                Some(limiter) => limiter.take().await,
+                None if generated % YIELD_EVERY == 0 => tokio::task::yield_now().await,
+                None => {}
+            }
+            if ctx.is_cancelled() {
+                return generated;
+            }
+            set(mock_entry(rule.clone(), spec, i));
+            generated += 1;
+        }
+    }
+    generated
+}
+
+/// The `i`th mock entry of `spec`.
+pub fn mock_entry(rule: Arc<Rule>, spec: &MockSpec, i: usize) -> Entry {
+    let id = i % spec.query_cardinality.unwrap_or(spec.count).max(1);
+    let queries = vec![(MOCK_QUERY.to_vec(), id.to_string().into_bytes())];
+    let headers: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+
+    let entry = Entry::new(rule, &queries, &headers);
+    let response = Response {
+        status: 200,
+        headers: vec![("content-type".to_string(), "application/json".to_string())],
+        body: mock_body(spec, id),
+    };
+    entry.set_payload(&queries, &headers, &response);
+    entry.touch_refreshed_at();
+    entry
+}
+
+/// JSON body of `body_size` bytes (the default document when unset), padded with a repeated
+/// phrase or random characters depending on `compressible`.
+fn mock_body(spec: &MockSpec, id: usize) -> Vec<u8> {
+    let Some(size) = spec.body_size else {
+        return default_body(id).into_bytes();
+    };
+
+    let mut body = format!(r#"{{"id":{},"data":""#, id).into_bytes();
+    let pad = size.saturating_sub(body.len() + 2);
+    if spec.compressible {
+        body.extend(FILLER.iter().cycle().take(pad));
+    } else {
+        body.extend(Alphanumeric.sample_string(&mut rand::thread_rng(), pad).into_bytes());
+    }
+    body.extend_from_slice(br#""}"#);
+    body
+}
+
+fn default_body(id: usize) -> String {
+    format!(
+        r#"{{
+      "response": {{
+        "status": "ok",
+        "payload": {{
+          "id": "item-10",
+          "context": {{
+            "label": "Mock Label [{}]",
+            "tags": [
+              "example",
+              "tag-placeholder"
+            ]
+          }},
+          "content": {{
+            "header": "Header for item [{}]",
+            "summary": "This is a mock summary for. Placeholder inserted.",
+            "details": {{
+              "info": "Extra information with repeated placeholder.",
+              "active": true,
+              "score": 10,
+            }},
+            "assets": {{
+              "images": [],
+              "videos": null
+            }}
+          }}
+        }},
+        "meta": {{
+          "generatedAt": "2025-01-01T00:00:00Z",
+          "mockSource": "advCache-Mock-v2"
+        }}
+      }}
+    }}"#,
+        id, id
+    )
+}
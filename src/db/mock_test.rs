@@ -0,0 +1,112 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::time::{Duration, Instant};
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::config::{self, MockSpec};
+    use crate::db::mock::{generate, specs};
+    use crate::model::Entry;
+
+    fn spec(path: &str, count: usize, body_size: Option<usize>, compressible: bool) -> MockSpec {
+        MockSpec { path: path.to_string(), count, body_size, compressible, query_cardinality: None }
+    }
+
+    async fn collect(specs: &[MockSpec], rate: Option<usize>) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        let generated = generate(CancellationToken::new(), &config::new_test_config(), specs, rate, |e| entries.push(e)).await;
+        assert_eq!(generated, entries.len());
+        entries
+    }
+
+    fn body_len(entry: &Entry) -> usize {
+        entry.response_payload().unwrap().body.len()
+    }
+
+    fn brotli_len(body: &[u8]) -> usize {
+        let mut compressed = Vec::new();
+        brotli::BrotliCompress(&mut &body[..], &mut compressed, &Default::default()).unwrap();
+        compressed.len()
+    }
+
+    /// Test that every spec gets its count of entries on its own path, all with distinct keys.
+    #[tokio::test]
+    async fn test_counts_and_distinct_keys() {
+        let specs = [spec("/api/v1/user", 300, None, true), spec("/api/v2/search", 200, Some(512), false)];
+        let entries = collect(&specs, None).await;
+        assert_eq!(entries.len(), 500);
+
+        let users = entries.iter().filter(|e| e.rule().path.as_deref() == Some("/api/v1/user")).count();
+        assert_eq!(users, 300);
+
+        let keys: HashSet<u64> = entries.iter().map(|e| e.key()).collect();
+        assert_eq!(keys.len(), 500, "mock entries must not share keys");
+    }
+
+    /// Test that the query cardinality caps the distinct keys of a spec.
+    #[tokio::test]
+    async fn test_query_cardinality() {
+        let mut search = spec("/api/v2/search", 100, Some(256), true);
+        search.query_cardinality = Some(10);
+        let entries = collect(&[search], None).await;
+        assert_eq!(entries.len(), 100);
+
+        let keys: HashSet<u64> = entries.iter().map(|e| e.key()).collect();
+        assert_eq!(keys.len(), 10);
+    }
+
+    /// Test that bodies have the requested size, and that only compressible ones compress well.
+    #[tokio::test]
+    async fn test_body_sizes_and_compressibility() {
+        for size in [64, 4096, 64 * 1024] {
+            let entries = collect(&[spec("/a", 3, Some(size), true), spec("/b", 3, Some(size), false)], None).await;
+            for entry in &entries {
+                let len = body_len(entry);
+                assert!(len.abs_diff(size) <= 8, "body of {} bytes asked for {}", len, size);
+                serde_json::from_slice::<serde_json::Value>(&entry.response_payload().unwrap().body).unwrap();
+            }
+        }
+
+        let entries = collect(&[spec("/a", 1, Some(64 * 1024), true), spec("/b", 1, Some(64 * 1024), false)], None).await;
+        let repetitive = brotli_len(&entries[0].response_payload().unwrap().body);
+        let random = brotli_len(&entries[1].response_payload().unwrap().body);
+        assert!(repetitive * 20 < random, "repetitive {} bytes, random {} bytes compressed", repetitive, random);
+    }
+
+    /// Test that without specs, `length` entries of /api/v1/user are generated.
+    #[test]
+    fn test_default_spec() {
+        let mut cfg = config::new_test_config();
+        let specs = specs(&cfg);
+        assert_eq!(specs.len(), 1);
+        assert_eq!(specs[0].path, "/api/v1/user");
+        assert_eq!(specs[0].count, 100000);
+
+        let mock = cfg.cache.data.as_mut().unwrap().mock.as_mut().unwrap();
+        mock.specs = Some(vec![spec("/x", 5, None, true)]);
+        assert_eq!(self::specs(&cfg)[0].path, "/x");
+    }
+
+    /// Test that generation is held to the rate and stops once cancelled.
+    #[tokio::test]
+    async fn test_rate_and_cancellation() {
+        let started = Instant::now();
+        let entries = collect(&[spec("/a", 100, None, true)], Some(400)).await;
+        assert_eq!(entries.len(), 100);
+        assert!(started.elapsed() >= Duration::from_millis(200), "100 entries at 400/s took {:?}", started.elapsed());
+
+        let ctx = CancellationToken::new();
+        let mut generated = 0;
+        let cfg = config::new_test_config();
+        let stop = ctx.clone();
+        let count = generate(ctx, &cfg, &[spec("/a", 1000, None, true)], Some(1000), |_| {
+            generated += 1;
+            if generated == 10 {
+                stop.cancel();
+            }
+        })
+        .await;
+        assert_eq!(count, 10);
+    }
+}
@@ -1,10 +1,42 @@
 //! Cache storage functionality and database implementation.
 
 pub mod admission;
+pub mod analytics;
+pub mod capture;
 pub mod storage;
 pub mod db;
+pub mod jobs;
+pub mod key_schema;
 pub mod log;
+#[cfg(feature = "mocks")]
+pub mod mock;
 pub mod persistance;
+pub mod resize;
+pub mod rule_reconcile;
+pub mod simulator;
+pub mod tombstones;
+pub mod walks;
+
+#[cfg(test)]
+mod capture_test;
+
+#[cfg(test)]
+mod jobs_test;
+
+#[cfg(test)]
+mod key_schema_test;
+
+#[cfg(all(test, feature = "mocks"))]
+mod mock_test;
+
+#[cfg(test)]
+mod rule_reconcile_test;
+
+#[cfg(test)]
+mod simulator_test;
+
+#[cfg(test)]
+mod tombstones_test;
 
 // Re-export main types
 pub use db::{Storage, DB, SVC_EVICTOR, SVC_LIFETIME_MANAGER};
@@ -1,4 +1,7 @@
 use anyhow::{Context, Result};
+use arc_swap::ArcSwapOption;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -6,38 +9,471 @@ use tokio::fs;
 use tokio::sync::oneshot;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
-use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicI64, AtomicU64, Ordering};
 use flate2::Compression;
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 
+use super::key_index::{self, Claim, RestoreIndex};
+use super::Dumper;
 use crate::config::{Config, ConfigTrait};
+use crate::db::storage::map::NUM_OF_SHARDS;
 use crate::db::Storage;
+use crate::model::Entry;
 use crate::time;
 use crate::dedlog;
 
+/// Upper bound for a single serialized entry in a dump file.
+/// Record sizes are read from disk, so anything above this is treated as corruption
+/// instead of being trusted as an allocation size.
+pub const MAX_ENTRY_SIZE: usize = 256 * 1024 * 1024;
+
+/// Initial buffer capacity used while reading a record.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Size of the per-record meta prefix: u32 length + u32 CRC32 (both little-endian).
+const RECORD_META_SIZE: usize = 8;
+
+/// File written into a version directory once every shard file of the dump is on disk.
+pub const MANIFEST_NAME: &str = "manifest.json";
+
 #[derive(Debug, thiserror::Error)]
 #[error("persistence mode is not enabled")]
 pub struct DumpNotEnabledError;
 
-/// Dumper interface for cache persistence.
-#[async_trait::async_trait]
-pub trait Dumper: Send + Sync {
-    /// Dumps cache to disk.
-    async fn dump(&self, ctx: CancellationToken) -> Result<()>;
+/// Summary of a finished dump. A version is complete when its manifest is present and all
+/// the shard files it counts are found, in the primary and fallback directories together.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Timestamp shared by the shard file names.
+    pub timestamp: String,
+    /// Number of shard files.
+    pub files: usize,
+    pub entries: i64,
+    /// Total size of the shard files.
+    pub bytes: u64,
+    /// Entries left out because their rule sets `cache_value.persist: false`.
+    #[serde(default)]
+    pub excluded: i64,
+    /// Shard count of the storage that wrote the dump, one file per shard named by its id.
+    /// Absent in dumps written before it was recorded.
+    #[serde(default)]
+    pub shards: Option<usize>,
+    /// Fingerprint of the key schemas of the rules the dump was written with.
+    #[serde(default)]
+    pub key_schema: Option<u64>,
+}
+
+/// How the shard files of a dump map onto the current storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardLayout {
+    /// Same shard count, every file id within it: a file holds the entries of one shard.
+    Matching,
+    /// Another shard count, or file ids outside the current one: entries of a file spread
+    /// over any shard and are re-hashed on set.
+    Resharding { from: Option<usize> },
+}
+
+/// Works out the layout of dump `files` for a storage of `shards` shards. The shard count comes
+/// from the manifest or, for older dumps, from the highest file id. The layout never drops a
+/// file: ids are only compared, so a dump of any shard count loads entirely.
+pub fn shard_layout(files: &[PathBuf], manifest: Option<&Manifest>, shards: usize) -> ShardLayout {
+    let ids: Vec<_> = files.iter().map(|f| dump_file_shard(f)).collect();
+    let from = manifest
+        .and_then(|m| m.shards)
+        .or_else(|| ids.iter().flatten().max().map(|id| *id as usize + 1));
+    let in_range = ids.iter().all(|id| id.is_some_and(|id| (id as usize) < shards));
+    if from == Some(shards) && in_range {
+        ShardLayout::Matching
+    } else {
+        ShardLayout::Resharding { from }
+    }
+}
+
+/// Fingerprint of the key schemas of all rules of `cfg`, recorded in the manifest to tell
+/// whether a dump was keyed like the config loading it.
+pub fn rules_key_schema(cfg: &Config) -> u64 {
+    use xxhash_rust::xxh3::Xxh3;
+
+    let mut rules: Vec<_> = cfg.rules().into_iter().flatten().collect();
+    rules.sort_by(|a, b| a.0.cmp(b.0));
+    let mut hasher = Xxh3::new();
+    for (path, rule) in rules {
+        hasher.update(path.as_bytes());
+        hasher.update(&rule.key_schema().to_le_bytes());
+    }
+    hasher.digest()
+}
+
+/// Opens the file a dump file is written to. Tests swap it to inject IO errors.
+pub type FileOpener = Arc<dyn Fn(&Path) -> std::io::Result<Box<dyn Write + Send>> + Send + Sync>;
+
+fn create_file(path: &Path) -> std::io::Result<Box<dyn Write + Send>> {
+    Ok(Box::new(std::fs::File::create(path)?))
+}
+
+/// Reports whether a write failed because the directory cannot take the dump (disk full,
+/// quota exceeded, read-only or not permitted), so the rest goes to `dump_dir_fallback`.
+pub fn is_failover_error(e: &std::io::Error) -> bool {
+    e.kind() == std::io::ErrorKind::PermissionDenied
+        || matches!(
+            e.raw_os_error(),
+            Some(libc::ENOSPC | libc::EDQUOT | libc::EROFS | libc::EACCES | libc::EPERM)
+        )
+}
+
+/// Version directory a dump is written to: the primary one until a write there fails with a
+/// failover error, the fallback one from then on.
+struct DumpTarget {
+    primary: PathBuf,
+    fallback: Option<PathBuf>,
+    failed_over: AtomicBool,
+}
+
+impl DumpTarget {
+    /// Runs `write` against the current directory, moving on to the fallback when it fails there.
+    fn write<T>(&self, mut write: impl FnMut(&Path) -> std::io::Result<T>) -> std::io::Result<T> {
+        if !self.failed_over.load(Ordering::Relaxed) {
+            match write(&self.primary) {
+                Err(e) if self.fallback.is_some() && is_failover_error(&e) => self.fail_over(&e),
+                result => return result,
+            }
+        }
+        match &self.fallback {
+            Some(dir) => {
+                std::fs::create_dir_all(dir)?;
+                write(dir)
+            }
+            None => Err(std::io::Error::other("no fallback dump directory configured")),
+        }
+    }
+
+    fn fail_over(&self, e: &dyn std::error::Error) {
+        if !self.failed_over.swap(true, Ordering::Relaxed) {
+            warn!(
+                component = "dump",
+                event = "dump_failover",
+                dir = ?self.primary,
+                fallback = ?self.fallback,
+                error = %e,
+                "dump directory cannot take the dump, writing the remaining files to the fallback directory"
+            );
+        }
+    }
+}
+
+/// Writes a file through a `.tmp` sibling renamed into place once complete; the temporary
+/// file is removed when writing fails.
+fn write_file_atomically<T>(
+    opener: &FileOpener,
+    dir: &Path,
+    name: &str,
+    write: impl FnOnce(Box<dyn Write + Send>) -> std::io::Result<T>,
+) -> std::io::Result<T> {
+    let path = dir.join(name);
+    let tmp_path = dir.join(format!("{}.tmp", name));
+    let result = opener(&tmp_path)
+        .and_then(write)
+        .and_then(|v| std::fs::rename(&tmp_path, &path).map(|_| v));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Writes the records of one shard, finishing the gzip stream when compression is on.
+/// Returns the key and uncompressed offset of every record written; stops early once `ctx`
+/// is cancelled.
+fn write_shard(
+    file: Box<dyn Write + Send>,
+    entries: &[(u64, Vec<u8>)],
+    gzip: bool,
+    crc32_control: bool,
+    ctx: &CancellationToken,
+) -> std::io::Result<Vec<(u64, u64)>> {
+    fn write_all<W: Write>(w: &mut W, entries: &[(u64, Vec<u8>)], crc32_control: bool, ctx: &CancellationToken) -> std::io::Result<Vec<(u64, u64)>> {
+        let mut written = Vec::with_capacity(entries.len());
+        let mut offset = 0u64;
+        for (key, entry_bytes) in entries {
+            if ctx.is_cancelled() {
+                break;
+            }
+            write_record(w, entry_bytes, crc32_control)?;
+            written.push((*key, offset));
+            offset += (RECORD_META_SIZE + entry_bytes.len()) as u64;
+        }
+        Ok(written)
+    }
+
+    let file = BufWriter::with_capacity(512 * 1024, file);
+    if gzip {
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        let written = write_all(&mut encoder, entries, crc32_control, ctx)?;
+        encoder.finish()?.flush()?;
+        Ok(written)
+    } else {
+        let mut writer = file;
+        let written = write_all(&mut writer, entries, crc32_control, ctx)?;
+        writer.flush()?;
+        Ok(written)
+    }
+}
+
+/// Reads the manifest of a version from whichever of its directories holds it.
+pub async fn read_manifest(dirs: &[PathBuf]) -> Option<Manifest> {
+    for dir in dirs {
+        if let Ok(raw) = fs::read(dir.join(MANIFEST_NAME)).await {
+            match serde_json::from_slice(&raw) {
+                Ok(manifest) => return Some(manifest),
+                Err(e) => warn!(component = "dump", event = "manifest_invalid", dir = ?dir, error = %e, "unreadable dump manifest"),
+            }
+        }
+    }
+    None
+}
+
+/// Free bytes for unprivileged users on the filesystem holding `dir`.
+pub fn available_space(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid out pointer.
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+/// Failure while reading a record; the rest of the file is unreadable after any of these.
+#[derive(Debug, thiserror::Error)]
+pub enum RecordError {
+    #[error("read meta: {0}")]
+    Meta(std::io::Error),
+    #[error("declared entry size {0} exceeds limit")]
+    TooLarge(usize),
+    #[error("truncated entry")]
+    Truncated,
+    #[error("read entry: {0}")]
+    Entry(std::io::Error),
+}
+
+impl RecordError {
+    /// Message used for the deduplicated error log.
+    fn log_msg(&self) -> &'static str {
+        match self {
+            RecordError::Meta(_) => "[load] read meta error",
+            RecordError::TooLarge(_) => "[load] declared entry size exceeds limit",
+            RecordError::Truncated => "[load] truncated entry",
+            RecordError::Entry(_) => "[load] read entry error",
+        }
+    }
+}
+
+/// A serialized entry together with the CRC32 stored in front of it (zero when CRC control is off).
+pub struct DumpRecord {
+    pub data: Vec<u8>,
+    pub crc: u32,
+}
+
+impl DumpRecord {
+    /// Checks the stored CRC32 against the entry bytes.
+    pub fn crc_matches(&self) -> bool {
+        crc32fast::hash(&self.data) == self.crc
+    }
+}
+
+/// Streams records from a dump file one by one, never holding more than a single entry.
+/// Stops at clean EOF and after the first error.
+pub struct DumpReader<R: Read> {
+    inner: BufReader<R>,
+    done: bool,
+}
+
+impl DumpReader<Box<dyn Read>> {
+    /// Opens a dump file, transparently decompressing `.gz` files.
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let reader: Box<dyn Read> = if is_gzip_path(path) {
+            Box::new(GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        Ok(Self::new(reader))
+    }
+}
+
+impl<R: Read> DumpReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: BufReader::with_capacity(512 * 1024, reader),
+            done: false,
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<DumpRecord>, RecordError> {
+        // Read meta buffer (8 bytes: length + CRC32)
+        let mut meta_buf = [0u8; RECORD_META_SIZE];
+        match self.inner.read_exact(&mut meta_buf) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(RecordError::Meta(e)),
+        }
 
-    /// Loads cache from disk.
-    async fn load(&self, ctx: CancellationToken) -> Result<()>;
+        let sz = u32::from_le_bytes([meta_buf[0], meta_buf[1], meta_buf[2], meta_buf[3]]) as usize;
+        let crc = u32::from_le_bytes([meta_buf[4], meta_buf[5], meta_buf[6], meta_buf[7]]);
 
-    /// Loads a specific version of cache dump.
-    #[allow(dead_code)]
-    async fn load_version(&self, ctx: CancellationToken, version: &str) -> Result<()>;
+        // The declared size is untrusted: reject absurd values up front and read through
+        // `take` so the buffer only grows as far as the bytes really present in the file.
+        if sz > MAX_ENTRY_SIZE {
+            return Err(RecordError::TooLarge(sz));
+        }
+
+        let mut data = Vec::with_capacity(sz.min(READ_CHUNK_SIZE));
+        match (&mut self.inner).take(sz as u64).read_to_end(&mut data) {
+            Ok(n) if n == sz => Ok(Some(DumpRecord { data, crc })),
+            Ok(_) => Err(RecordError::Truncated),
+            Err(e) => Err(RecordError::Entry(e)),
+        }
+    }
+}
+
+impl<R: Read> Iterator for DumpReader<R> {
+    type Item = Result<DumpRecord, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_record() {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Writes one record: length (4 bytes) + CRC32 (4 bytes, zero when disabled) + data.
+pub fn write_record<W: Write>(w: &mut W, data: &[u8], crc32_control: bool) -> std::io::Result<()> {
+    let crc = if crc32_control { crc32fast::hash(data) } else { 0 };
+
+    let mut meta_buf = [0u8; RECORD_META_SIZE];
+    meta_buf[0..4].copy_from_slice(&(data.len() as u32).to_le_bytes());
+    meta_buf[4..8].copy_from_slice(&crc.to_le_bytes());
+
+    w.write_all(&meta_buf)?;
+    w.write_all(data)
+}
+
+/// Reports whether a dump file is gzip-compressed (by extension).
+pub fn is_gzip_path(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(".gz")
+}
+
+/// Lists `v<N>` version directories in the base dump directory with their modification times.
+pub async fn list_version_dirs(base_dir: &Path) -> Result<Vec<(PathBuf, std::time::SystemTime)>> {
+    let mut dirs = Vec::new();
+    let mut entries = fs::read_dir(base_dir).await.context("Failed to read dump directory")?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
+            if file_name.starts_with("v") {
+                if let Ok(metadata) = entry.metadata().await {
+                    dirs.push((path, metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH)));
+                }
+            }
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Lists shard dump files in a version directory. With `dump_name` only files of that dump
+/// are returned, otherwise any `*-shard-*.dump[.gz]` file matches.
+pub async fn list_dump_files(dir: &Path, dump_name: Option<&str>) -> Result<Vec<(PathBuf, std::time::SystemTime)>> {
+    let pattern = match dump_name {
+        Some(name) => format!("{}-shard-", name),
+        None => "-shard-".to_string(),
+    };
+
+    let mut dump_files = Vec::new();
+    let mut entries = fs::read_dir(dir).await.context("Failed to read dump directory")?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
+            let matches_name = if dump_name.is_some() {
+                file_name.starts_with(&pattern)
+            } else {
+                file_name.contains(&pattern)
+            };
+            if matches_name && (file_name.ends_with(".dump") || file_name.ends_with(".dump.gz")) {
+                if let Ok(metadata) = entry.metadata().await {
+                    dump_files.push((path, metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH)));
+                }
+            }
+        }
+    }
+
+    Ok(dump_files)
+}
+
+/// Moves shard files and their key indexes into `v<version>` of the directory each is in.
+async fn migrate_files(files: &[PathBuf], version: u32) -> Result<()> {
+    for file in files {
+        let (Some(dir), Some(name)) = (file.parent(), file.file_name()) else {
+            continue;
+        };
+        let target = dir.join(format!("v{}", version));
+        fs::create_dir_all(&target).await.with_context(|| format!("Failed to create {:?}", target))?;
+
+        let index = key_index::index_path(file);
+        if fs::try_exists(&index).await.unwrap_or(false) {
+            let index_name = format!("{}{}", name.to_string_lossy(), key_index::INDEX_SUFFIX);
+            fs::rename(&index, target.join(index_name)).await.with_context(|| format!("Failed to move {:?}", index))?;
+        }
+        fs::rename(file, target.join(name)).await.with_context(|| format!("Failed to move {:?}", file))?;
+    }
+    Ok(())
+}
+
+/// Extracts the shard id from a `<name>-shard-<id>-<timestamp>.dump[.gz]` file name.
+pub fn dump_file_shard(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    let rest = &name[name.rfind("-shard-")? + "-shard-".len()..];
+    rest.split('-').next()?.parse().ok()
+}
+
+/// Extracts the `20060102T150405` timestamp a shard file was written with.
+pub fn dump_file_timestamp(path: &Path) -> Option<String> {
+    path.file_name()?
+        .to_str()?
+        .split('-')
+        .last()?
+        .trim_end_matches(".gz")
+        .trim_end_matches(".dump")
+        .to_string()
+        .into()
 }
 
 /// Dump implementation for cache persistence.
 pub struct DumperImpl {
     cfg: Config,
     storage: Arc<dyn Storage>,
+    opener: FileOpener,
+    /// Key indexes of the dump being restored with `load_on_demand`.
+    restore: ArcSwapOption<RestoreIndex>,
+    /// Shard count recorded in manifests and compared with the one of loaded dumps.
+    shards: usize,
+    /// Set once `load` returned, whatever it restored.
+    loaded: AtomicBool,
 }
 
 impl DumperImpl {
@@ -45,10 +481,28 @@ impl DumperImpl {
     pub fn new(cfg: Config, storage: Arc<dyn Storage>) -> Result<Self> {
         Ok(Self {
             cfg,
-            storage
+            storage,
+            opener: Arc::new(create_file),
+            restore: ArcSwapOption::empty(),
+            shards: NUM_OF_SHARDS,
+            loaded: AtomicBool::new(false),
         })
     }
 
+    /// Replaces the function opening dump files for writing.
+    #[cfg(test)]
+    pub(crate) fn with_opener(mut self, opener: FileOpener) -> Self {
+        self.opener = opener;
+        self
+    }
+
+    /// Pretends the storage has `shards` shards.
+    #[cfg(test)]
+    pub(crate) fn with_shards(mut self, shards: usize) -> Self {
+        self.shards = shards;
+        self
+    }
+
     /// Gets the dump directory path.
     fn dump_dir(&self) -> Result<PathBuf> {
         let dir = self
@@ -62,6 +516,24 @@ impl DumperImpl {
         Ok(PathBuf::from(dir))
     }
 
+    /// Gets the fallback dump directory path, if configured.
+    fn fallback_dir(&self) -> Option<PathBuf> {
+        self.cfg
+            .data()
+            .and_then(|d| d.dump.as_ref())
+            .and_then(|d| d.dir_fallback.as_ref())
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// Existing dump directories: the primary one and the fallback one.
+    fn existing_dirs(&self) -> Result<Vec<PathBuf>> {
+        let mut dirs = vec![self.dump_dir()?];
+        dirs.extend(self.fallback_dir());
+        dirs.retain(|d| d.is_dir());
+        Ok(dirs)
+    }
+
     /// Gets the dump filename.
     fn dump_name(&self) -> String {
         self.cfg
@@ -99,6 +571,34 @@ impl DumperImpl {
             .unwrap_or(true)
     }
 
+    /// Checks if entries are loaded on demand while a dump is restored.
+    fn load_on_demand(&self) -> bool {
+        self.cfg
+            .data()
+            .and_then(|d| d.dump.as_ref())
+            .map(|d| d.load_on_demand)
+            .unwrap_or(false)
+    }
+
+    /// Checks if dumps of the legacy flat layout are moved into a version dir once loaded.
+    fn migrate_legacy(&self) -> bool {
+        self.cfg
+            .data()
+            .and_then(|d| d.dump.as_ref())
+            .map(|d| d.migrate_legacy)
+            .unwrap_or(false)
+    }
+
+    /// Picks the next version number free in the primary and the fallback directory alike,
+    /// so both halves of a failed-over dump share one version name.
+    async fn next_version(&self) -> Result<u32> {
+        let mut next = 1;
+        for dir in self.existing_dirs()? {
+            next = next.max(self.next_version_dir(&dir).await?);
+        }
+        Ok(next)
+    }
+
     /// Picks the next sequential version number.
     async fn next_version_dir(&self, base_dir: &Path) -> Result<u32> {
         let mut max_v = 0u32;
@@ -122,19 +622,7 @@ impl DumperImpl {
 
     /// Rotates version directories, keeping only the newest max_of dirs.
     async fn rotate_version_dirs(&self, base_dir: &Path, max: usize) -> Result<()> {
-        let mut entries_vec = Vec::new();
-        let mut entries = fs::read_dir(base_dir).await.context("Failed to read dump directory")?;
-        
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                if file_name.starts_with("v") {
-                    if let Ok(metadata) = entry.metadata().await {
-                        entries_vec.push((path, metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH)));
-                    }
-                }
-            }
-        }
+        let mut entries_vec = list_version_dirs(base_dir).await?;
 
         if entries_vec.len() <= max {
             return Ok(());
@@ -166,29 +654,109 @@ impl DumperImpl {
         Ok(())
     }
 
-    /// Gets the latest version directory.
-    async fn get_latest_version_dir(&self, base_dir: &Path) -> Result<Option<PathBuf>> {
-        let mut entries_vec = Vec::new();
-        let mut entries = fs::read_dir(base_dir).await.context("Failed to read dump directory")?;
-        
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                if file_name.starts_with("v") {
-                    if let Ok(metadata) = entry.metadata().await {
-                        entries_vec.push((path, metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH)));
-                    }
+    /// Groups the `v<N>` directories of the primary and fallback dump directories by version.
+    async fn versions(&self) -> Result<BTreeMap<u32, Vec<PathBuf>>> {
+        let mut versions: BTreeMap<u32, Vec<PathBuf>> = BTreeMap::new();
+        for base in self.existing_dirs()? {
+            for (path, _) in list_version_dirs(&base).await? {
+                let number = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.strip_prefix('v'))
+                    .and_then(|n| n.parse::<u32>().ok());
+                if let Some(number) = number {
+                    versions.entry(number).or_default().push(path);
                 }
             }
         }
+        Ok(versions)
+    }
 
-        if entries_vec.is_empty() {
-            return Ok(None);
+    /// Shard files of the latest dump found in the directories of one version.
+    async fn version_files(&self, dirs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let dump_name = self.dump_name();
+        let mut files = Vec::new();
+        for dir in dirs {
+            files.extend(list_dump_files(dir, Some(&dump_name)).await?.into_iter().map(|(path, _)| path));
         }
 
-        // Sort by modification time (newest first)
-        entries_vec.sort_by(|a, b| b.1.cmp(&a.1));
-        Ok(Some(entries_vec[0].0.clone()))
+        // Keep the files of the latest timestamp only.
+        if let Some(ts) = files.iter().filter_map(|path| dump_file_timestamp(path)).max() {
+            files.retain(|path| dump_file_timestamp(path).as_deref() == Some(ts.as_str()));
+        }
+        Ok(files)
+    }
+
+    /// Picks the shard files of the newest complete version, with its manifest. Incomplete
+    /// versions (no manifest, or fewer files than it counts) are skipped. Dumps written before
+    /// manifests existed have none at all; then the newest version is taken as is.
+    async fn latest_complete_files(&self) -> Result<(Vec<PathBuf>, Option<Manifest>)> {
+        let versions = self.versions().await?;
+        let Some(newest) = versions.keys().next_back().copied() else {
+            anyhow::bail!("no versioned dump dirs found in {:?}", self.existing_dirs()?);
+        };
+
+        let mut any_manifest = false;
+        for (number, dirs) in versions.iter().rev() {
+            let Some(manifest) = read_manifest(dirs).await else {
+                continue;
+            };
+            any_manifest = true;
+            let files = self.version_files(dirs).await?;
+            let timestamp_matches = files.iter().all(|f| dump_file_timestamp(f).as_deref() == Some(manifest.timestamp.as_str()));
+            if files.len() == manifest.files && timestamp_matches {
+                return Ok((files, Some(manifest)));
+            }
+            warn!(
+                component = "dump",
+                event = "version_incomplete",
+                version = number,
+                found = files.len(),
+                expected = manifest.files,
+                "skipping incomplete dump version"
+            );
+        }
+
+        if any_manifest {
+            anyhow::bail!("no complete dump version found in {:?}", self.existing_dirs()?);
+        }
+        Ok((self.version_files(&versions[&newest]).await?, None))
+    }
+
+    /// Size of the newest dump on disk, from its manifest or else by adding up its shard files.
+    async fn last_dump_size(&self) -> Result<u64> {
+        let versions = self.versions().await?;
+        let Some(dirs) = versions.values().next_back() else {
+            return Ok(0);
+        };
+        if let Some(manifest) = read_manifest(dirs).await {
+            return Ok(manifest.bytes);
+        }
+        let mut bytes = 0;
+        for file in self.version_files(dirs).await? {
+            bytes += fs::metadata(&file).await.map(|m| m.len()).unwrap_or(0);
+        }
+        Ok(bytes)
+    }
+
+    /// Logs a warning up front when the dump directory has less free space than the last dump took.
+    async fn check_space(&self, dump_dir: &Path) {
+        let Ok(needed) = self.last_dump_size().await else {
+            return;
+        };
+        if let Some(available) = available_space(dump_dir) {
+            if needed > available {
+                warn!(
+                    component = "dump",
+                    event = "dump_low_space",
+                    dir = ?dump_dir,
+                    available,
+                    last_dump_bytes = needed,
+                    fallback = ?self.fallback_dir(),
+                    "dump directory has less free space than the last dump took"
+                );
+            }
+        }
     }
 
     /// Formats timestamp as "20060102T150405".
@@ -214,23 +782,42 @@ impl Dumper for DumperImpl {
         }
 
         let dump_dir = self.dump_dir()?;
+        let fallback_dir = self.fallback_dir();
         let dump_name = self.dump_name();
 
-        // Create base dump directory
-        fs::create_dir_all(&dump_dir)
-            .await
-            .context("Failed to create dump directory")?;
+        // Create base dump directory; with a fallback configured, an unusable primary one
+        // sends the whole dump there.
+        let primary_err = match fs::create_dir_all(&dump_dir).await {
+            Ok(()) => None,
+            Err(e) if fallback_dir.is_some() && is_failover_error(&e) => Some(e),
+            Err(e) => return Err(e).context("Failed to create dump directory"),
+        };
+        if primary_err.is_none() {
+            self.check_space(&dump_dir).await;
+        }
 
         // Create version directory
-        let version_num = self.next_version_dir(&dump_dir).await?;
-        let version_dir = dump_dir.join(format!("v{}", version_num));
-        fs::create_dir_all(&version_dir)
-            .await
-            .context("Failed to create version directory")?;
+        let version_name = format!("v{}", self.next_version().await?);
+        let target = Arc::new(DumpTarget {
+            primary: dump_dir.join(&version_name),
+            fallback: fallback_dir.as_ref().map(|d| d.join(&version_name)),
+            failed_over: AtomicBool::new(false),
+        });
+        let created = match primary_err {
+            Some(e) => Err(e),
+            None => fs::create_dir_all(&target.primary).await,
+        };
+        match created {
+            Ok(()) => {}
+            Err(e) if target.fallback.is_some() && is_failover_error(&e) => target.fail_over(&e),
+            Err(e) => return Err(e).context("Failed to create version directory"),
+        }
 
         let timestamp = self.format_timestamp();
         let success = Arc::new(AtomicI32::new(0));
         let failures = Arc::new(AtomicI32::new(0));
+        let files = Arc::new(AtomicI32::new(0));
+        let bytes = Arc::new(AtomicU64::new(0));
         let gzip = self.gzip_enabled();
         let crc32_control = self.crc32_enabled();
 
@@ -239,6 +826,8 @@ impl Dumper for DumperImpl {
         let shards_data = Arc::new(Mutex::new(Vec::new()));
         let shards_data_clone = shards_data.clone();
         let ctx_walk = ctx.clone();
+        let excluded = Arc::new(AtomicI64::new(0));
+        let excluded_walk = excluded.clone();
         
         self.storage.walk_shards(
             ctx_walk.clone(),
@@ -246,7 +835,15 @@ impl Dumper for DumperImpl {
                 // Collect all entries from shard synchronously
                 let mut entries = Vec::new();
                 shard.walk_r(&ctx_walk, |_key, entry| {
-                    entries.push(entry.to_bytes());
+                    // Negative entries live for seconds and a dump would not keep their TTL.
+                    if entry.is_negative() {
+                        return true;
+                    }
+                    if entry.rule().is_persisted() {
+                        entries.push((entry.key(), entry.to_bytes()));
+                    } else {
+                        excluded_walk.fetch_add(1, Ordering::Relaxed);
+                    }
                     true
                 });
                 shards_data_clone.lock().unwrap().push((shard_key, entries));
@@ -254,7 +851,7 @@ impl Dumper for DumperImpl {
         );
 
         // Extract shards data from Mutex
-        let shards_data_final: Vec<(u64, Vec<Vec<u8>>)> = {
+        let shards_data_final: Vec<(u64, Vec<(u64, Vec<u8>)>)> = {
             let guard = shards_data.lock().unwrap();
             guard.clone()
         };
@@ -263,89 +860,43 @@ impl Dumper for DumperImpl {
         let mut tasks = Vec::new();
         for (shard_key, entries) in shards_data_final.into_iter() {
             let dump_name_clone = dump_name.clone();
-            let version_dir_clone = version_dir.clone();
+            let target_clone = target.clone();
+            let opener = self.opener.clone();
             let timestamp_clone = timestamp.clone();
             let success_clone = success.clone();
             let failures_clone = failures.clone();
+            let files_clone = files.clone();
+            let bytes_clone = bytes.clone();
             let ctx_clone = ctx.clone();
-            let entries_clone = entries;
 
             let (tx, rx) = oneshot::channel();
             let handle = tokio::task::spawn_blocking(move || {
                 let ext = if gzip { ".dump.gz" } else { ".dump" };
                 let name = format!("{}-shard-{}-{}{}", dump_name_clone, shard_key, timestamp_clone, ext);
-                let file_path = version_dir_clone.join(&name);
-                let tmp_path = file_path.with_file_name(format!("{}.tmp", name));
-
-                // Create temporary file
-                let file = match std::fs::File::create(&tmp_path) {
-                    Ok(f) => f,
-                    Err(e) => {
-                        dedlog::err(Some(&e as &dyn std::error::Error), Some("file"), "[dump] create error");
-                        failures_clone.fetch_add(1, Ordering::Relaxed);
-                        let _ = tx.send(());
-                        return;
-                    }
-                };
-
-                // Setup writer (with optional gzip)
-                let writer: Box<dyn Write> = if gzip {
-                    Box::new(GzEncoder::new(file, Compression::default()))
-                } else {
-                    Box::new(BufWriter::with_capacity(512 * 1024, file))
-                };
-
-                let mut buf_writer = BufWriter::with_capacity(512 * 1024, writer);
 
-                // Write entries
-                for entry_bytes in entries_clone {
-                    if ctx_clone.is_cancelled() {
-                        break;
+                let written = target_clone.write(|dir| {
+                    let mut keys = write_file_atomically(&opener, dir, &name, |file| {
+                        write_shard(file, &entries, gzip, crc32_control, &ctx_clone)
+                    })?;
+                    let size = std::fs::metadata(dir.join(&name)).map(|m| m.len()).unwrap_or(0);
+                    // The key index is an optimisation: without it the file is still restored.
+                    let index_name = format!("{}{}", name, key_index::INDEX_SUFFIX);
+                    if let Err(e) = write_file_atomically(&opener, dir, &index_name, |mut file| key_index::write_index(&mut file, &mut keys)) {
+                        dedlog::err("dump", Some(&e as &dyn std::error::Error), Some("file"), "[dump] key index write error");
                     }
+                    Ok((keys.len(), size))
+                });
 
-                    let crc = if crc32_control {
-                        crc32fast::hash(&entry_bytes)
-                    } else {
-                        0
-                    };
-
-                    // Write length (4 bytes) + CRC32 (4 bytes) + data
-                    let len = entry_bytes.len() as u32;
-                    let len_bytes = len.to_le_bytes();
-                    let crc_bytes = crc.to_le_bytes();
-                    let mut meta_buf = [0u8; 8];
-                    meta_buf[0..4].copy_from_slice(&len_bytes);
-                    meta_buf[4..8].copy_from_slice(&crc_bytes);
-
-                    if buf_writer.write_all(&meta_buf).is_err() {
-                        failures_clone.fetch_add(1, Ordering::Relaxed);
-                        let _ = tx.send(());
-                        return;
+                match written {
+                    Ok((written, size)) => {
+                        success_clone.fetch_add(written as i32, Ordering::Relaxed);
+                        files_clone.fetch_add(1, Ordering::Relaxed);
+                        bytes_clone.fetch_add(size, Ordering::Relaxed);
                     }
-
-                    if buf_writer.write_all(&entry_bytes).is_err() {
+                    Err(e) => {
+                        dedlog::err("dump", Some(&e as &dyn std::error::Error), Some("file"), "[dump] write error");
                         failures_clone.fetch_add(1, Ordering::Relaxed);
-                        let _ = tx.send(());
-                        return;
                     }
-
-                    success_clone.fetch_add(1, Ordering::Relaxed);
-                }
-
-                // Flush and finish
-                if buf_writer.flush().is_err() {
-                    failures_clone.fetch_add(1, Ordering::Relaxed);
-                    let _ = tx.send(());
-                    return;
-                }
-
-                // Finish gzip encoder if used
-                drop(buf_writer);
-
-                // Rename tmp to final file
-                if let Err(e) = std::fs::rename(&tmp_path, &file_path) {
-                    dedlog::err(Some(&e as &dyn std::error::Error), Some("file"), "[dump] rename error");
-                    failures_clone.fetch_add(1, Ordering::Relaxed);
                 }
 
                 let _ = tx.send(());
@@ -360,9 +911,40 @@ impl Dumper for DumperImpl {
             let _ = handle.await;
         }
 
+        // The manifest goes last and only for a dump without failures, marking it complete.
+        if failures.load(Ordering::Relaxed) == 0 && !ctx.is_cancelled() {
+            let manifest = Manifest {
+                timestamp: timestamp.clone(),
+                files: files.load(Ordering::Relaxed) as usize,
+                entries: success.load(Ordering::Relaxed) as i64,
+                bytes: bytes.load(Ordering::Relaxed),
+                excluded: excluded.load(Ordering::Relaxed),
+                shards: Some(self.shards),
+                key_schema: Some(rules_key_schema(&self.cfg)),
+            };
+            let raw = serde_json::to_vec_pretty(&manifest)?;
+            let target = target.clone();
+            let opener = self.opener.clone();
+            let written = tokio::task::spawn_blocking(move || {
+                target.write(|dir| {
+                    write_file_atomically(&opener, dir, MANIFEST_NAME, |mut file| {
+                        file.write_all(&raw)?;
+                        file.flush()
+                    })
+                })
+            })
+            .await?;
+            if let Err(e) = written {
+                dedlog::err("dump", Some(&e as &dyn std::error::Error), Some("file"), "[dump] manifest write error");
+                failures.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
         let max_versions = self.max_versions();
         if max_versions > 0 {
-            self.rotate_version_dirs(&dump_dir, max_versions).await?;
+            for dir in self.existing_dirs()? {
+                self.rotate_version_dirs(&dir, max_versions).await?;
+            }
         }
 
         let duration = time::since(start);
@@ -374,6 +956,8 @@ impl Dumper for DumperImpl {
             event = "dump_complete",
             written,
             fails,
+            excluded = excluded.load(Ordering::Relaxed),
+            failed_over = target.failed_over.load(Ordering::Relaxed),
             duration_secs = duration.as_secs_f64(),
             "dumping finished"
         );
@@ -386,163 +970,202 @@ impl Dumper for DumperImpl {
     }
 
     async fn load(&self, ctx: CancellationToken) -> Result<()> {
-        let dump_dir = self.dump_dir()?;
-        let version_dir = match self.get_latest_version_dir(&dump_dir).await? {
-            Some(dir) => dir,
-            None => {
-                anyhow::bail!("no versioned dump dirs found in {:?}", dump_dir);
-            }
-        };
-        self.load_from_dir(ctx, &version_dir).await
+        let loaded = self.load_latest(ctx).await;
+        self.loaded.store(true, Ordering::Release);
+        loaded
     }
 
     async fn load_version(&self, ctx: CancellationToken, version: &str) -> Result<()> {
-        let dump_dir = self.dump_dir()?;
-        let version_dir = dump_dir.join(version);
-        if !version_dir.exists() {
+        let mut dirs = vec![self.dump_dir()?.join(version)];
+        dirs.extend(self.fallback_dir().map(|d| d.join(version)));
+        dirs.retain(|d| d.exists());
+        if dirs.is_empty() {
             anyhow::bail!("Dump version {} not found", version);
         }
-        self.load_from_dir(ctx, &version_dir).await
+        let files = self.version_files(&dirs).await?;
+        let manifest = read_manifest(&dirs).await;
+        self.load_files(ctx, files, manifest.as_ref()).await
+    }
+
+    fn load_key(&self, key: u64) -> Option<Entry> {
+        self.restore.load().as_ref()?.take(key)
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.loaded.load(Ordering::Acquire)
     }
 }
 
 impl DumperImpl {
-    /// Internal method to load dump from a specific directory.
-    async fn load_from_dir(&self, ctx: CancellationToken, dir: &Path) -> Result<()> {
+    /// Loads the latest complete dump, or the legacy flat one while no versioned dump exists.
+    async fn load_latest(&self, ctx: CancellationToken) -> Result<()> {
+        if self.versions().await?.is_empty() {
+            let legacy = self.version_files(&self.existing_dirs()?).await?;
+            if !legacy.is_empty() {
+                return self.load_legacy(ctx, legacy).await;
+            }
+        }
+        let (files, manifest) = self.latest_complete_files().await?;
+        self.load_files(ctx, files, manifest.as_ref()).await
+    }
+
+    /// Loads a dump written flat into the dump directories, before versioned dirs existed.
+    /// The files are left where they are unless `migrate_legacy` is set; then, once loaded,
+    /// they move with their key indexes into the next version dir of their directory.
+    async fn load_legacy(&self, ctx: CancellationToken, files: Vec<PathBuf>) -> Result<()> {
+        warn!(
+            component = "dump",
+            event = "load_legacy_layout",
+            files = files.len(),
+            migrate = self.migrate_legacy(),
+            "dump found outside versioned dirs, set data.dump.migrate_legacy to move it into one"
+        );
+        self.load_files(ctx, files.clone(), None).await?;
+        if !self.migrate_legacy() {
+            return Ok(());
+        }
+
+        let version = self.next_version().await?;
+        match migrate_files(&files, version).await {
+            Ok(()) => info!(
+                component = "dump",
+                event = "legacy_migrated",
+                version,
+                files = files.len(),
+                "moved the legacy dump into a version dir"
+            ),
+            // The entries are loaded already; the files are tried again on the next start.
+            Err(e) => error!(
+                component = "dump",
+                event = "legacy_migration_failed",
+                version,
+                error = %e,
+                "failed to move the legacy dump into a version dir"
+            ),
+        }
+        Ok(())
+    }
+
+    /// Checks that a dump fits the current storage and config before loading it, logging
+    /// what differs. Nothing here refuses a dump: files of another shard count all load and
+    /// their entries re-hash on set, entries of outdated key schemas are purged after loading.
+    fn check_compatibility(&self, files: &[PathBuf], manifest: Option<&Manifest>) -> ShardLayout {
+        if let Some(dumped) = manifest.and_then(|m| m.key_schema) {
+            let current = rules_key_schema(&self.cfg);
+            if dumped != current {
+                warn!(
+                    component = "dump",
+                    event = "load_key_schema_changed",
+                    dumped = format!("{:016x}", dumped),
+                    current = format!("{:016x}", current),
+                    "dump was keyed with other rules, entries of changed rules will be purged after loading"
+                );
+            }
+        }
+
+        let layout = shard_layout(files, manifest, self.shards);
+        if let ShardLayout::Resharding { from } = layout {
+            warn!(
+                component = "dump",
+                event = "load_resharding",
+                from = from.map(|n| n as i64).unwrap_or(-1),
+                to = self.shards,
+                files = files.len(),
+                "dump was written with another shard count, loading every file whatever its shard id"
+            );
+        }
+        layout
+    }
+
+    /// Internal method to load the shard files of one dump.
+    async fn load_files(&self, ctx: CancellationToken, dump_files: Vec<PathBuf>, manifest: Option<&Manifest>) -> Result<()> {
         let start = time::now();
-        let dump_name = self.dump_name();
         let cfg = self.cfg.clone();
         let storage = self.storage.clone();
         let crc32_control = self.crc32_enabled();
 
-        // Find all dump files matching the pattern
-        let pattern_prefix = format!("{}-shard-", dump_name);
-        let mut dump_files = Vec::new();
-        let mut entries = fs::read_dir(dir).await.context("Failed to read dump directory")?;
-        
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                if file_name.starts_with(&pattern_prefix) && (file_name.ends_with(".dump") || file_name.ends_with(".dump.gz")) {
-                    if let Ok(metadata) = entry.metadata().await {
-                        dump_files.push((path, metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH)));
-                    }
-                }
-            }
-        }
-
         if dump_files.is_empty() {
-            anyhow::bail!("no dump files found in {:?}", dir);
-        }
-
-        // Extract latest timestamp from filenames
-        let latest_timestamp = dump_files.iter()
-            .filter_map(|(path, _)| {
-                path.file_name()?
-                    .to_str()?
-                    .split('-')
-                    .last()?
-                    .trim_end_matches(".dump")
-                    .trim_end_matches(".gz")
-                    .to_string()
-                    .into()
-            })
-            .max();
-
-        // Filter files by latest timestamp
-        if let Some(ts) = latest_timestamp {
-            dump_files.retain(|(path, _)| {
-                path.file_name()
-                    .and_then(|n| n.to_str())
-                    .map(|s| s.contains(&ts))
-                    .unwrap_or(false)
-            });
+            anyhow::bail!("no dump files found in {:?}", self.existing_dirs()?);
         }
+        let layout = self.check_compatibility(&dump_files, manifest);
 
         let success = Arc::new(AtomicI32::new(0));
         let failures = Arc::new(AtomicI32::new(0));
+        let dropped = Arc::new(AtomicI32::new(0));
         let mut tasks = Vec::new();
 
+        // Index first: from here on a request for a pending key loads it ahead of the sequence.
+        let index = if self.load_on_demand() {
+            let (files, cfg) = (dump_files.clone(), cfg.clone());
+            tokio::task::spawn_blocking(move || RestoreIndex::open(&files, cfg, crc32_control))
+                .await?
+                .map(Arc::new)
+        } else {
+            None
+        };
+        if let Some(index) = &index {
+            info!(component = "dump", event = "load_indexed", keys = index.keys(), "restoring dump, loading requested keys on demand");
+        }
+        self.restore.store(index.clone());
+
         // Load each dump file
-        for (file_path, _) in dump_files {
+        for file_path in dump_files {
             let cfg_clone = cfg.clone();
             let storage_clone = storage.clone();
             let ctx_clone = ctx.clone();
             let success_clone = success.clone();
             let failures_clone = failures.clone();
+            let dropped_clone = dropped.clone();
             let crc32_control_clone = crc32_control;
-            let is_gzip = file_path.to_string_lossy().ends_with(".gz");
+            let index_clone = index.clone();
 
             let (tx, rx) = oneshot::channel();
             let handle = tokio::task::spawn_blocking(move || {
-                let file = match std::fs::File::open(&file_path) {
-                    Ok(f) => f,
+                let reader = match DumpReader::open(&file_path) {
+                    Ok(r) => r,
                     Err(e) => {
-                            dedlog::err(Some(&e as &dyn std::error::Error), Some("file"), "[load] open error");
+                        dedlog::err("dump", Some(&e as &dyn std::error::Error), Some("file"), "[load] open error");
                         failures_clone.fetch_add(1, Ordering::Relaxed);
                         let _ = tx.send(());
                         return;
                     }
                 };
 
-                // Setup reader (with optional gzip)
-                let reader: Box<dyn Read> = if is_gzip {
-                    Box::new(GzDecoder::new(file))
-                } else {
-                    Box::new(file)
-                };
-
-                let mut buf_reader = BufReader::with_capacity(512 * 1024, reader);
-
-                loop {
+                for record in reader {
                     if ctx_clone.is_cancelled() {
                         break;
                     }
 
-                    // Read meta buffer (8 bytes: length + CRC32)
-                    let mut meta_buf = [0u8; 8];
-                    match buf_reader.read_exact(&mut meta_buf) {
-                        Ok(_) => {},
-                        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                        Err(e) => {
-                            dedlog::err(Some(&e as &dyn std::error::Error), Some("file"), "[load] read meta error");
-                            failures_clone.fetch_add(1, Ordering::Relaxed);
-                            break;
-                        }
-                    }
-
-                    let sz = u32::from_le_bytes([meta_buf[0], meta_buf[1], meta_buf[2], meta_buf[3]]) as usize;
-                    let exp_crc = u32::from_le_bytes([meta_buf[4], meta_buf[5], meta_buf[6], meta_buf[7]]);
-
-                    // Read entry data
-                    let mut buf = vec![0u8; sz];
-                    match buf_reader.read_exact(&mut buf) {
-                        Ok(_) => {},
+                    let record = match record {
+                        Ok(record) => record,
                         Err(e) => {
-                            dedlog::err(Some(&e as &dyn std::error::Error), Some("file"), "[load] read entry error");
+                            dedlog::err("dump", Some(&e as &dyn std::error::Error), Some("file"), e.log_msg());
                             failures_clone.fetch_add(1, Ordering::Relaxed);
                             break;
                         }
-                    }
+                    };
 
                     // Verify CRC32 if enabled
-                    if crc32_control_clone {
-                        let calc_crc = crc32fast::hash(&buf);
-                        if calc_crc != exp_crc {
-                            dedlog::err(None, Some("file"), "[load] crc mismatch");
-                            failures_clone.fetch_add(1, Ordering::Relaxed);
-                            continue;
-                        }
+                    if crc32_control_clone && !record.crc_matches() {
+                        dedlog::err("dump", None, Some("file"), "[load] crc mismatch");
+                        failures_clone.fetch_add(1, Ordering::Relaxed);
+                        continue;
                     }
 
                     // Deserialize entry
-                    match crate::model::to_bytes::from_bytes(&buf, &cfg_clone) {
+                    match crate::model::to_bytes::from_bytes(&record.data, &cfg_clone) {
+                        // Dumps made before the rule opted out of persistence may still hold its entries.
+                        Ok(entry) if !entry.rule().is_persisted() => {
+                            dropped_clone.fetch_add(1, Ordering::Relaxed);
+                        }
+                        // Already loaded on demand.
+                        Ok(entry) if index_clone.as_ref().is_some_and(|ix| ix.claim(entry.key()) == Claim::Taken) => {}
                         Ok(entry) => {
                             storage_clone.set(entry);
                             success_clone.fetch_add(1, Ordering::Relaxed);
                         }
                         Err(e) => {
-                            dedlog::err(Some(&*e), Some("file"), "[load] entry decode error");
+                            dedlog::err("dump", Some(&*e), Some("file"), "[load] entry decode error");
                             failures_clone.fetch_add(1, Ordering::Relaxed);
                         }
                     }
@@ -560,15 +1183,21 @@ impl DumperImpl {
             let _ = handle.await;
         }
 
+        self.restore.store(None);
+
         let duration = time::since(start);
-        let restored = success.load(Ordering::Relaxed);
+        let on_demand = index.as_ref().map_or(0, |ix| ix.on_demand());
+        let restored = success.load(Ordering::Relaxed) as i64 + on_demand as i64;
         let fails = failures.load(Ordering::Relaxed);
 
         info!(
             component = "dump",
             event = "load_complete",
             restored,
+            on_demand,
             fails,
+            dropped = dropped.load(Ordering::Relaxed),
+            resharded = matches!(layout, ShardLayout::Resharding { .. }),
             duration_secs = duration.as_secs_f64(),
             "restoring dump"
         );
@@ -0,0 +1,537 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::config::{self, Config, ConfigTrait};
+    use crate::controller::metrics;
+    use crate::db::persistance::dumper::{
+        dump_file_shard, read_manifest, rules_key_schema, shard_layout, write_record, FileOpener, Manifest,
+        ShardLayout, MANIFEST_NAME,
+    };
+    use crate::db::persistance::key_index::{self, Claim, RestoreIndex};
+    use crate::db::persistance::{Dumper, DumperImpl};
+    use crate::db::storage::{Map, Storage, NUM_OF_SHARDS};
+    use crate::model::{Entry, Response};
+    use crate::time;
+    use crate::upstream::testing::MockUpstream;
+    use crate::upstream::Upstream;
+
+    const PATH: &str = "/api/v1/user";
+    const CLIENT_PATH: &str = "/api/v1/client";
+
+    /// File on a full disk: it can be created, but every write fails with ENOSPC.
+    struct FullDisk(#[allow(dead_code)] std::fs::File);
+
+    impl Write for FullDisk {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::from_raw_os_error(libc::ENOSPC))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Opener letting `room` files into `dir`, handing out full-disk writers there afterwards.
+    fn filling_disk(dir: PathBuf, room: usize) -> FileOpener {
+        let opened = AtomicUsize::new(0);
+        Arc::new(move |path: &Path| {
+            let file = std::fs::File::create(path)?;
+            if path.starts_with(&dir) && opened.fetch_add(1, Ordering::SeqCst) >= room {
+                return Ok(Box::new(FullDisk(file)) as Box<dyn Write + Send>);
+            }
+            Ok(Box::new(file) as Box<dyn Write + Send>)
+        })
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("advcache-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn dump_config(base: &Path, fallback: bool) -> Config {
+        let mut cfg = config::new_test_config();
+        let dump = cfg.cache.data.as_mut().unwrap().dump.as_mut().unwrap();
+        dump.enabled = true;
+        dump.dir = Some(base.join("primary").to_string_lossy().into_owned());
+        dump.dir_fallback = fallback.then(|| base.join("fallback").to_string_lossy().into_owned());
+        cfg
+    }
+
+    fn storage(cfg: &Config) -> Arc<Storage> {
+        let token = CancellationToken::new();
+        let map = Arc::new(Map::new(token.clone(), cfg.clone()));
+        let upstream = MockUpstream::new() as Arc<dyn Upstream>;
+        Storage::new(token, cfg.clone(), upstream, map).expect("storage must start")
+    }
+
+    fn fill(cfg: &Config, storage: &Storage, ids: std::ops::Range<usize>) {
+        fill_path(cfg, storage, PATH, ids);
+    }
+
+    fn fill_path(cfg: &Config, storage: &Storage, path: &str, ids: std::ops::Range<usize>) {
+        for id in ids {
+            storage.set(filled_entry(cfg, path, id));
+        }
+    }
+
+    fn filled_entry(cfg: &Config, path: &str, id: usize) -> Entry {
+        let queries = vec![(b"user[id]".to_vec(), id.to_string().into_bytes())];
+        let entry = Entry::new(cfg.rule(path).unwrap(), &queries, &[]);
+        let response = Response {
+            status: 200,
+            headers: vec![],
+            body: format!("{{\"id\":{}}}", id).into_bytes(),
+        };
+        entry.set_payload(&queries, &[], &response);
+        entry
+    }
+
+    /// Marks the rule of `path` with `cache_value.persist: false`.
+    fn exclude_from_dumps(cfg: &mut Config, path: &str) {
+        let rules = cfg.cache.rules.as_mut().unwrap();
+        let mut rule = (**rules.get(path).unwrap()).clone();
+        rule.cache_value.persist = Some(false);
+        rules.insert(path.to_string(), Arc::new(rule));
+    }
+
+    async fn restore(cfg: &Config) -> Arc<Storage> {
+        let restored = storage(cfg);
+        DumperImpl::new(cfg.clone(), restored.clone())
+            .unwrap()
+            .load(CancellationToken::new())
+            .await
+            .expect("load must succeed");
+        restored
+    }
+
+    fn shard_files(dir: &Path) -> usize {
+        std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_name().to_string_lossy().ends_with(".dump"))
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Test that shards failing with ENOSPC go to the fallback dir and load() puts both halves together.
+    #[tokio::test]
+    async fn test_dump_fails_over_to_fallback_dir() {
+        let _clock = time::start(Duration::from_millis(1));
+        let base = temp_dir("dump-failover");
+        let cfg = dump_config(&base, true);
+        let source = storage(&cfg);
+        fill(&cfg, &source, 0..200);
+
+        let dumper = DumperImpl::new(cfg.clone(), source.clone())
+            .unwrap()
+            .with_opener(filling_disk(base.join("primary"), 3));
+        dumper.dump(CancellationToken::new()).await.expect("dump must fail over, not fail");
+
+        let primary = base.join("primary/v1");
+        let fallback = base.join("fallback/v1");
+        assert!(!primary.join(MANIFEST_NAME).exists());
+        let manifest = read_manifest(std::slice::from_ref(&fallback)).await.expect("manifest goes to the fallback dir");
+        assert!(shard_files(&primary) > 0 && shard_files(&fallback) > 0);
+        assert_eq!(manifest.files, shard_files(&primary) + shard_files(&fallback));
+        assert_eq!(manifest.entries, 200);
+
+        let restored = storage(&cfg);
+        DumperImpl::new(cfg.clone(), restored.clone())
+            .unwrap()
+            .load(CancellationToken::new())
+            .await
+            .expect("load must read both dirs");
+        assert_eq!(restored.len(), 200);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    /// Test that without a fallback dir ENOSPC fails the dump and leaves the version incomplete.
+    #[tokio::test]
+    async fn test_dump_without_fallback_fails_on_full_disk() {
+        let _clock = time::start(Duration::from_millis(1));
+        let base = temp_dir("dump-full");
+        let cfg = dump_config(&base, false);
+        let source = storage(&cfg);
+        fill(&cfg, &source, 0..50);
+
+        let dumper = DumperImpl::new(cfg.clone(), source)
+            .unwrap()
+            .with_opener(filling_disk(base.join("primary"), 1));
+        assert!(dumper.dump(CancellationToken::new()).await.is_err());
+        assert!(!base.join("primary/v1").join(MANIFEST_NAME).exists());
+        assert!(!base.join("fallback").exists());
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    /// Test that load() skips a newer version without a manifest in favour of the last complete one.
+    #[tokio::test]
+    async fn test_load_picks_newest_complete_version() {
+        let _clock = time::start(Duration::from_millis(1));
+        let base = temp_dir("dump-complete");
+        let cfg = dump_config(&base, true);
+        let source = storage(&cfg);
+        let dumper = DumperImpl::new(cfg.clone(), source.clone()).unwrap();
+
+        fill(&cfg, &source, 0..40);
+        dumper.dump(CancellationToken::new()).await.unwrap();
+        fill(&cfg, &source, 40..60);
+        dumper.dump(CancellationToken::new()).await.unwrap();
+        std::fs::remove_file(base.join("primary/v2").join(MANIFEST_NAME)).unwrap();
+
+        let restored = storage(&cfg);
+        DumperImpl::new(cfg.clone(), restored.clone())
+            .unwrap()
+            .load(CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(restored.len(), 40);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    /// Test that the dumper reports loaded once load() returned, even when there was no dump.
+    #[tokio::test]
+    async fn test_load_marks_loaded_without_dump() {
+        let base = temp_dir("dump-loaded");
+        let cfg = dump_config(&base, false);
+        let dumper = DumperImpl::new(cfg.clone(), storage(&cfg)).unwrap();
+        assert!(!dumper.is_loaded());
+        let _ = dumper.load(CancellationToken::new()).await;
+        assert!(dumper.is_loaded());
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    /// Test that entries of a non-persisted rule stay out of the files and are counted in the manifest.
+    #[tokio::test]
+    async fn test_dump_skips_non_persisted_rule() {
+        let _clock = time::start(Duration::from_millis(1));
+        let base = temp_dir("dump-exclude");
+        let plain = dump_config(&base, false);
+        let mut cfg = plain.clone();
+        exclude_from_dumps(&mut cfg, CLIENT_PATH);
+        let source = storage(&cfg);
+        fill(&cfg, &source, 0..30);
+        fill_path(&cfg, &source, CLIENT_PATH, 0..20);
+        assert_eq!(source.len(), 50);
+
+        DumperImpl::new(cfg.clone(), source).unwrap().dump(CancellationToken::new()).await.unwrap();
+        let manifest = read_manifest(&[base.join("primary/v1")]).await.expect("dump must be complete");
+        assert_eq!((manifest.entries, manifest.excluded), (30, 20));
+
+        // Restoring without the flag shows the files themselves hold no entry of the rule.
+        assert_eq!(restore(&plain).await.len(), 30);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    /// Test that loading a dump made before the rule opted out drops the rule's entries.
+    #[tokio::test]
+    async fn test_load_drops_entries_of_non_persisted_rule() {
+        let _clock = time::start(Duration::from_millis(1));
+        let base = temp_dir("dump-exclude-old");
+        let plain = dump_config(&base, false);
+        let source = storage(&plain);
+        fill(&plain, &source, 0..30);
+        fill_path(&plain, &source, CLIENT_PATH, 0..20);
+        DumperImpl::new(plain.clone(), source).unwrap().dump(CancellationToken::new()).await.unwrap();
+
+        let mut cfg = plain.clone();
+        exclude_from_dumps(&mut cfg, CLIENT_PATH);
+        let restored = restore(&cfg).await;
+        assert_eq!(restored.len(), 30);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    /// Shard files of the only version of a dump.
+    fn dump_files(dir: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                let name = p.to_string_lossy();
+                name.ends_with(".dump") || name.ends_with(".dump.gz")
+            })
+            .collect()
+    }
+
+    fn user_key(cfg: &Config, id: usize) -> u64 {
+        let queries = vec![(b"user[id]".to_vec(), id.to_string().into_bytes())];
+        Entry::new(cfg.rule(PATH).unwrap(), &queries, &[]).key()
+    }
+
+    /// Test that the key index round-trips sorted and that a damaged one is rejected.
+    #[test]
+    fn test_key_index_round_trip() {
+        let dir = temp_dir("key-index");
+        let path = dir.join("cache.dump-shard-1-20260101T000000.dump.keys");
+        let mut pairs = vec![(30, 200), (10, 0), (20, 100)];
+        let mut raw = Vec::new();
+        key_index::write_index(&mut raw, &mut pairs).unwrap();
+        std::fs::write(&path, &raw).unwrap();
+        assert_eq!(key_index::read_index(&path).unwrap(), vec![(10, 0), (20, 100), (30, 200)]);
+
+        let mut flipped = raw.clone();
+        flipped[20] ^= 0x01;
+        std::fs::write(&path, &flipped).unwrap();
+        assert!(key_index::read_index(&path).is_err());
+
+        std::fs::write(&path, &raw[..raw.len() - 5]).unwrap();
+        assert!(key_index::read_index(&path).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Test that a pending key is loaded from the dump once, ahead of the sequential load,
+    /// which then skips it, from plain and gzipped files alike.
+    #[tokio::test]
+    async fn test_restore_index_takes_pending_keys_once() {
+        let _clock = time::start(Duration::from_millis(1));
+        for gzip in [false, true] {
+            let base = temp_dir(&format!("dump-on-demand-{}", gzip));
+            let mut cfg = dump_config(&base, false);
+            cfg.cache.data.as_mut().unwrap().dump.as_mut().unwrap().gzip = gzip;
+            let source = storage(&cfg);
+            fill(&cfg, &source, 0..300);
+            DumperImpl::new(cfg.clone(), source.clone()).unwrap().dump(CancellationToken::new()).await.unwrap();
+
+            let files = dump_files(&base.join("primary/v1"));
+            assert!(files.iter().all(|f| key_index::index_path(f).exists()), "every shard file must have an index");
+            let index = RestoreIndex::open(&files, cfg.clone(), true).expect("the dump is indexed");
+            assert_eq!(index.keys(), 300);
+
+            let key = user_key(&cfg, 7);
+            let entry = index.take(key).expect("a pending key must load");
+            assert_eq!(entry.key(), key);
+            assert_eq!(entry.response_payload().unwrap().body, b"{\"id\":7}");
+            assert!(index.take(key).is_none(), "a key loads once");
+            assert_eq!(index.claim(key), Claim::Taken);
+            assert_eq!(index.claim(user_key(&cfg, 8)), Claim::Claimed);
+            assert!(index.take(user_key(&cfg, 8)).is_none(), "claimed by the sequential load");
+            assert_eq!(index.claim(user_key(&cfg, 1000)), Claim::NotIndexed);
+            assert_eq!(index.on_demand(), 1);
+            let _ = std::fs::remove_dir_all(&base);
+        }
+    }
+
+    /// Test that a restore with load_on_demand restores everything, and that dumps written
+    /// without key indexes load as before.
+    #[tokio::test]
+    async fn test_load_on_demand_restores_all() {
+        let _clock = time::start(Duration::from_millis(1));
+        let base = temp_dir("dump-on-demand-load");
+        let mut cfg = dump_config(&base, false);
+        cfg.cache.data.as_mut().unwrap().dump.as_mut().unwrap().load_on_demand = true;
+        let source = storage(&cfg);
+        fill(&cfg, &source, 0..120);
+        DumperImpl::new(cfg.clone(), source).unwrap().dump(CancellationToken::new()).await.unwrap();
+
+        let restored = storage(&cfg);
+        let dumper = DumperImpl::new(cfg.clone(), restored.clone()).unwrap();
+        dumper.load(CancellationToken::new()).await.unwrap();
+        assert_eq!(restored.len(), 120);
+        assert!(dumper.load_key(user_key(&cfg, 1)).is_none(), "no restore is running");
+
+        let files = dump_files(&base.join("primary/v1"));
+        for file in &files {
+            std::fs::remove_file(key_index::index_path(file)).unwrap();
+        }
+        assert!(RestoreIndex::open(&files, cfg.clone(), true).is_none());
+        assert_eq!(restore(&cfg).await.len(), 120);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    /// Writes `entries` as the complete version `v1` of a dump made by a storage of `shards`
+    /// shards: one file per shard id, holding the keys that fall into it.
+    fn write_sharded_dump(dir: &Path, cfg: &Config, entries: &[Entry], shards: usize) -> Vec<PathBuf> {
+        const TIMESTAMP: &str = "20260101T000000";
+        let version = dir.join("v1");
+        std::fs::create_dir_all(&version).unwrap();
+
+        let mut contents = vec![Vec::new(); shards];
+        for entry in entries {
+            write_record(&mut contents[(entry.key() % shards as u64) as usize], &entry.to_bytes(), true).unwrap();
+        }
+        let mut files = Vec::new();
+        for (id, raw) in contents.iter().enumerate() {
+            let file = version.join(format!("cache.dump-shard-{}-{}.dump", id, TIMESTAMP));
+            std::fs::write(&file, raw).unwrap();
+            files.push(file);
+        }
+
+        let manifest = Manifest {
+            timestamp: TIMESTAMP.to_string(),
+            files: shards,
+            entries: entries.len() as i64,
+            bytes: contents.iter().map(|c| c.len() as u64).sum(),
+            excluded: 0,
+            shards: Some(shards),
+            key_schema: Some(rules_key_schema(cfg)),
+        };
+        std::fs::write(version.join(MANIFEST_NAME), serde_json::to_vec(&manifest).unwrap()).unwrap();
+        files
+    }
+
+    /// Test that a 64-shard dump loads fully into a 256-shard storage and the other way round,
+    /// including the files whose shard id the smaller storage does not have.
+    #[tokio::test]
+    async fn test_load_reshards_dump_of_other_shard_count() {
+        let _clock = time::start(Duration::from_millis(1));
+        for (written, current) in [(64, 256), (256, 64)] {
+            let base = temp_dir(&format!("dump-reshard-{}-{}", written, current));
+            let cfg = dump_config(&base, false);
+            let entries: Vec<_> = (0..500).map(|id| filled_entry(&cfg, PATH, id)).collect();
+            let files = write_sharded_dump(&base.join("primary"), &cfg, &entries, written);
+
+            let manifest = read_manifest(&[base.join("primary/v1")]).await.unwrap();
+            assert_eq!(
+                shard_layout(&files, Some(&manifest), current),
+                ShardLayout::Resharding { from: Some(written) }
+            );
+
+            let restored = storage(&cfg);
+            DumperImpl::new(cfg.clone(), restored.clone())
+                .unwrap()
+                .with_shards(current)
+                .load(CancellationToken::new())
+                .await
+                .expect("load must succeed");
+            assert_eq!(restored.len(), 500, "{} -> {} shards must recover every entry", written, current);
+            for id in [0, 7, 499] {
+                let entry = restored.get_by_key(user_key(&cfg, id)).expect("every user must be restored");
+                assert_eq!(entry.response_payload().unwrap().body, format!("{{\"id\":{}}}", id).into_bytes());
+            }
+            let _ = std::fs::remove_dir_all(&base);
+        }
+    }
+
+    /// Test that entries of a dump written by a node whose clock ran 3h ahead are clamped to now
+    /// on load, counted, and expire after their TTL like any other.
+    #[tokio::test]
+    async fn test_load_clamps_timestamps_ahead_of_the_clock() {
+        let _clock = time::start(Duration::from_millis(1));
+        let base = temp_dir("dump-clock-skew");
+        let cfg = dump_config(&base, false);
+        let ahead = time::unix_nano() + Duration::from_secs(3 * 3600).as_nanos() as i64;
+        let entries: Vec<_> = (0..20)
+            .map(|id| {
+                let entry = filled_entry(&cfg, PATH, id);
+                entry.set_refreshed_at_for_tests(ahead);
+                entry
+            })
+            .collect();
+        write_sharded_dump(&base.join("primary"), &cfg, &entries, NUM_OF_SHARDS);
+
+        let clamped = metrics::entry_timestamps_clamped();
+        let restored = restore(&cfg).await;
+        assert!(metrics::entry_timestamps_clamped() >= clamped + 20);
+
+        let ttl = cfg.lifetime().and_then(|l| l.ttl).unwrap().as_nanos() as i64;
+        let now = time::unix_nano();
+        for id in 0..20 {
+            let entry = restored.get_by_key(user_key(&cfg, id)).unwrap();
+            assert!(entry.fresh_at() <= now, "a restored entry must not be fresher than now");
+            assert!(!entry.is_expired(&cfg));
+            // Fresh for one TTL from now at most, then expired as usual.
+            entry.set_refreshed_at_for_tests(entry.fresh_at() - ttl - 1);
+            assert!(entry.is_expired(&cfg));
+        }
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    /// Test that the manifest records the shard count and key schema, that a dump of the same
+    /// count is matching, and that the count of a dump without a manifest is inferred.
+    #[tokio::test]
+    async fn test_manifest_records_shard_layout() {
+        let _clock = time::start(Duration::from_millis(1));
+        let base = temp_dir("dump-shard-layout");
+        let cfg = dump_config(&base, false);
+        let source = storage(&cfg);
+        fill(&cfg, &source, 0..50);
+        DumperImpl::new(cfg.clone(), source).unwrap().dump(CancellationToken::new()).await.unwrap();
+
+        let manifest = read_manifest(&[base.join("primary/v1")]).await.unwrap();
+        assert_eq!(manifest.shards, Some(NUM_OF_SHARDS));
+        assert_eq!(manifest.key_schema, Some(rules_key_schema(&cfg)));
+        let files = dump_files(&base.join("primary/v1"));
+        assert_eq!(shard_layout(&files, Some(&manifest), NUM_OF_SHARDS), ShardLayout::Matching);
+        assert_eq!(shard_layout(&files, None, NUM_OF_SHARDS), ShardLayout::Matching);
+        assert_eq!(
+            shard_layout(&files[..0], None, NUM_OF_SHARDS),
+            ShardLayout::Resharding { from: None }
+        );
+
+        let legacy: Vec<_> = (0..64).map(|id| PathBuf::from(format!("cache.dump-shard-{}-20260101T000000.dump", id))).collect();
+        assert_eq!(shard_layout(&legacy, None, NUM_OF_SHARDS), ShardLayout::Resharding { from: Some(64) });
+        assert_eq!(dump_file_shard(Path::new("my-cache.dump-shard-17-20260101T000000.dump.gz")), Some(17));
+        assert_eq!(dump_file_shard(Path::new("cache.dump-shard-x-20260101T000000.dump")), None);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    /// Copies the fixture dump written flat, before versioned dirs, into the primary dir.
+    fn legacy_dump(base: &Path) -> PathBuf {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/db/persistance/testdata/dump_legacy");
+        let primary = base.join("primary");
+        std::fs::create_dir_all(&primary).unwrap();
+        for file in dump_files(&fixture) {
+            std::fs::copy(&file, primary.join(file.file_name().unwrap())).unwrap();
+        }
+        primary
+    }
+
+    /// Config of a dump dir holding the legacy fixture, whose records were partly written
+    /// without crc control.
+    fn legacy_config(base: &Path, migrate: bool) -> Config {
+        let mut cfg = dump_config(base, false);
+        let dump = cfg.cache.data.as_mut().unwrap().dump.as_mut().unwrap();
+        dump.crc32_control = false;
+        dump.migrate_legacy = migrate;
+        cfg
+    }
+
+    /// Test that a dump of the legacy flat layout loads and is left in place.
+    #[tokio::test]
+    async fn test_legacy_layout_loads_read_only() {
+        let _clock = time::start(Duration::from_millis(1));
+        let base = temp_dir("dump-legacy-load");
+        let primary = legacy_dump(&base);
+        let cfg = legacy_config(&base, false);
+
+        let restored = restore(&cfg).await;
+        assert!(restored.len() > 0);
+        assert_eq!(dump_files(&primary).len(), 2);
+        assert!(!primary.join("v1").exists());
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    /// Test that with migrate_legacy the loaded flat dump moves into v1, which loads as a
+    /// versioned dump afterwards.
+    #[tokio::test]
+    async fn test_legacy_layout_migrates_into_v1() {
+        let _clock = time::start(Duration::from_millis(1));
+        let base = temp_dir("dump-legacy-migrate");
+        let primary = legacy_dump(&base);
+        let cfg = legacy_config(&base, true);
+
+        let restored = restore(&cfg).await;
+        assert!(dump_files(&primary).is_empty());
+        assert_eq!(dump_files(&primary.join("v1")).len(), 2);
+
+        let source = storage(&cfg);
+        let dumper = DumperImpl::new(cfg.clone(), source.clone()).unwrap();
+        dumper.load(CancellationToken::new()).await.unwrap();
+        assert_eq!(source.len(), restored.len());
+
+        // The next dump goes next to the migrated version.
+        dumper.dump(CancellationToken::new()).await.unwrap();
+        assert!(primary.join("v2").join(MANIFEST_NAME).exists());
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
@@ -0,0 +1,316 @@
+//! Offline dump inspection used by the `dump-*` CLI subcommands.
+//!
+//! Everything here works on dump files directly, without a running server or storage.
+//! Files are streamed record by record through [`DumpReader`], so memory use is bounded
+//! by the largest single entry rather than by the file size.
+
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::dumper::{
+    dump_file_timestamp, is_gzip_path, list_dump_files, list_version_dirs, write_record, DumpReader,
+    DumpRecord,
+};
+use super::key_index;
+use crate::config::Config;
+use crate::model::to_bytes::from_bytes;
+use crate::model::Entry;
+
+type DecodeError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Decodes dump records into entries.
+///
+/// With a loaded config, entries are resolved against its rules exactly like the server
+/// does on load (unknown rule paths are decode errors). Without one, a bare rule is
+/// registered for every path met in the dump, which is all decoding needs.
+pub struct EntryDecoder {
+    cfg: Config,
+    learn_rules: bool,
+}
+
+impl EntryDecoder {
+    pub fn new(cfg: Option<Config>) -> Self {
+        match cfg {
+            Some(cfg) => Self {
+                cfg,
+                learn_rules: false,
+            },
+            None => Self {
+                cfg: Config::decode_only(),
+                learn_rules: true,
+            },
+        }
+    }
+
+    pub fn decode(&mut self, data: &[u8]) -> Result<Entry, DecodeError> {
+        if self.learn_rules {
+            if let Some(path) = rule_path(data) {
+                self.cfg.ensure_rule(path);
+            }
+        }
+        from_bytes(data, &self.cfg)
+    }
+}
+
+/// Reads the rule path prefix of a serialized entry without decoding the rest.
+fn rule_path(data: &[u8]) -> Option<&str> {
+    let len = u32::from_le_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    std::str::from_utf8(data.get(4..4usize.checked_add(len)?)?).ok()
+}
+
+/// CRC state of a record. A zero CRC means the dump was written with CRC control off.
+fn crc_state(record: &DumpRecord) -> &'static str {
+    if record.crc == 0 {
+        "none"
+    } else if record.crc_matches() {
+        "ok"
+    } else {
+        "mismatch"
+    }
+}
+
+/// Lists version directories (newest first) with their shard files summary.
+pub async fn dump_ls(base_dir: &Path, out: &mut dyn Write) -> Result<()> {
+    let mut versions = list_version_dirs(base_dir).await?;
+    versions.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+
+    for (dir, modified) in versions {
+        let files = list_dump_files(&dir, None).await?;
+
+        let mut bytes = 0u64;
+        let mut gzipped = 0usize;
+        let mut timestamps = Vec::new();
+        for (path, _) in &files {
+            bytes += tokio::fs::metadata(path).await.map(|m| m.len()).unwrap_or(0);
+            if is_gzip_path(path) {
+                gzipped += 1;
+            }
+            if let Some(ts) = dump_file_timestamp(path) {
+                if !timestamps.contains(&ts) {
+                    timestamps.push(ts);
+                }
+            }
+        }
+        timestamps.sort();
+
+        let name = dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let modified = chrono::DateTime::<chrono::Utc>::from(modified).format("%Y-%m-%dT%H:%M:%SZ");
+        writeln!(
+            out,
+            "{}\tmodified={}\tfiles={}\tgzip={}\tbytes={}\twritten_at={}",
+            name,
+            modified,
+            files.len(),
+            gzipped,
+            bytes,
+            timestamps.join(",")
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Prints metadata of up to `limit` entries of a dump file as JSON lines.
+/// Returns the number of printed records.
+pub fn dump_cat(file: &Path, limit: usize, decoder: &mut EntryDecoder, out: &mut dyn Write) -> Result<usize> {
+    let reader = DumpReader::open(file).with_context(|| format!("open dump file {:?}", file))?;
+
+    let mut printed = 0;
+    for record in reader.take(limit) {
+        let line = match record {
+            Ok(record) => match decoder.decode(&record.data) {
+                Ok(entry) => serde_json::json!({
+                    "key": entry.key(),
+                    "path": entry.rule().path,
+                    "fingerprint": format!("{:016x}{:016x}", entry.fingerprint_hi(), entry.fingerprint_lo()),
+                    "key_schema": format!("{:016x}", entry.key_schema()),
+                    "updated_at": entry.fresh_at(),
+                    "payload_bytes": record.data.len(),
+                    "status": entry.response_payload().map(|p| p.code).ok(),
+                    "crc": crc_state(&record),
+                }),
+                Err(e) => serde_json::json!({ "error": e.to_string(), "crc": crc_state(&record) }),
+            },
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        };
+        writeln!(out, "{}", line)?;
+        printed += 1;
+    }
+
+    Ok(printed)
+}
+
+/// Outcome of verifying dump files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub files: usize,
+    pub ok: usize,
+    pub crc_mismatch: usize,
+    pub decode_errors: usize,
+    /// Files whose reading stopped early (unreadable meta, oversized or truncated record).
+    pub read_errors: usize,
+}
+
+impl VerifyReport {
+    pub fn is_clean(&self) -> bool {
+        self.crc_mismatch == 0 && self.decode_errors == 0 && self.read_errors == 0
+    }
+
+    fn add(&mut self, other: &VerifyReport) {
+        self.files += other.files;
+        self.ok += other.ok;
+        self.crc_mismatch += other.crc_mismatch;
+        self.decode_errors += other.decode_errors;
+        self.read_errors += other.read_errors;
+    }
+}
+
+/// Runs a CRC and decode pass over every shard file in a version directory,
+/// reporting each corrupt record and a per-file summary.
+pub async fn dump_verify(dir: &Path, decoder: &mut EntryDecoder, out: &mut dyn Write) -> Result<VerifyReport> {
+    let mut files = list_dump_files(dir, None).await?;
+    if files.is_empty() {
+        anyhow::bail!("no dump files found in {:?}", dir);
+    }
+    files.sort();
+
+    let mut total = VerifyReport::default();
+    for (path, _) in files {
+        let mut report = VerifyReport {
+            files: 1,
+            ..Default::default()
+        };
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+
+        match DumpReader::open(&path) {
+            Ok(reader) => {
+                for (idx, record) in reader.enumerate() {
+                    let record = match record {
+                        Ok(record) => record,
+                        Err(e) => {
+                            writeln!(out, "{}\trecord={}\tread error: {}", name, idx, e)?;
+                            report.read_errors += 1;
+                            break;
+                        }
+                    };
+                    if crc_state(&record) == "mismatch" {
+                        writeln!(out, "{}\trecord={}\tcrc mismatch", name, idx)?;
+                        report.crc_mismatch += 1;
+                        continue;
+                    }
+                    match decoder.decode(&record.data).map(|entry| entry.payload()) {
+                        Ok(Ok(_)) => report.ok += 1,
+                        Ok(Err(e)) => {
+                            writeln!(out, "{}\trecord={}\tpayload error: {}", name, idx, e)?;
+                            report.decode_errors += 1;
+                        }
+                        Err(e) => {
+                            writeln!(out, "{}\trecord={}\tdecode error: {}", name, idx, e)?;
+                            report.decode_errors += 1;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                writeln!(out, "{}\topen error: {}", name, e)?;
+                report.read_errors += 1;
+            }
+        }
+
+        writeln!(
+            out,
+            "{}\tok={}\tcrc_mismatch={}\tdecode_errors={}\tread_errors={}",
+            name, report.ok, report.crc_mismatch, report.decode_errors, report.read_errors
+        )?;
+        total.add(&report);
+    }
+
+    writeln!(
+        out,
+        "total\tfiles={}\tok={}\tcrc_mismatch={}\tdecode_errors={}\tread_errors={}",
+        total.files, total.ok, total.crc_mismatch, total.decode_errors, total.read_errors
+    )?;
+
+    Ok(total)
+}
+
+/// Outcome of filtering a dump file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FilterReport {
+    pub read: usize,
+    pub written: usize,
+    /// Records dropped for a crc mismatch or decode error, plus an unreadable tail if any.
+    pub skipped_corrupt: usize,
+}
+
+/// Copies entries whose rule path equals `path` from `input` into a new dump file `output`.
+/// Corrupt records are skipped and reading stops at an unreadable tail, the same way the
+/// loader treats it, so the tool can also salvage a damaged dump. Output is gzipped when its name ends with `.gz` and is
+/// written through a temporary file, so a failed run never leaves a partial dump behind.
+pub fn dump_filter(input: &Path, output: &Path, path: &str, decoder: &mut EntryDecoder) -> Result<FilterReport> {
+    let reader = DumpReader::open(input).with_context(|| format!("open dump file {:?}", input))?;
+
+    let file_name = output
+        .file_name()
+        .context("output must be a file path")?
+        .to_string_lossy()
+        .into_owned();
+    let tmp_path = output.with_file_name(format!("{}.tmp", file_name));
+    let file = std::fs::File::create(&tmp_path).with_context(|| format!("create {:?}", tmp_path))?;
+    let writer: Box<dyn Write> = if is_gzip_path(output) {
+        Box::new(GzEncoder::new(file, Compression::default()))
+    } else {
+        Box::new(file)
+    };
+    let mut buf_writer = BufWriter::with_capacity(512 * 1024, writer);
+
+    let mut report = FilterReport::default();
+    let result: Result<()> = (|| {
+        for record in reader {
+            let record = match record {
+                Ok(record) => record,
+                Err(_) => {
+                    report.skipped_corrupt += 1;
+                    break;
+                }
+            };
+            report.read += 1;
+
+            if crc_state(&record) == "mismatch" {
+                report.skipped_corrupt += 1;
+                continue;
+            }
+            let entry = match decoder.decode(&record.data) {
+                Ok(entry) => entry,
+                Err(_) => {
+                    report.skipped_corrupt += 1;
+                    continue;
+                }
+            };
+            if entry.rule().path.as_deref() != Some(path) {
+                continue;
+            }
+
+            write_record(&mut buf_writer, &entry.to_bytes(), record.crc != 0)?;
+            report.written += 1;
+        }
+        buf_writer.flush()?;
+        Ok(())
+    })();
+
+    // Finish gzip encoder if used
+    drop(buf_writer);
+
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    std::fs::rename(&tmp_path, output).with_context(|| format!("rename {:?} to {:?}", tmp_path, output))?;
+    // An index left from a file this one replaces would point into the wrong records.
+    let _ = std::fs::remove_file(key_index::index_path(output));
+
+    Ok(report)
+}
@@ -0,0 +1,189 @@
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::config::new_test_config;
+    use crate::db::persistance::inspect::{
+        dump_cat, dump_filter, dump_ls, dump_verify, EntryDecoder, FilterReport, VerifyReport,
+    };
+
+    const CLEAN_DIR: &str = "src/db/persistance/testdata/dump";
+    const CORRUPT_DIR: &str = "src/db/persistance/testdata/dump_corrupt";
+    const SHARD_0: &str = "v1/cache.dump-shard-0-20260101T000000.dump";
+    const SHARD_1_GZ: &str = "v1/cache.dump-shard-1-20260101T000000.dump.gz";
+
+    fn fixture(dir: &str, file: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(dir).join(file)
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("advcache-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn cat_lines(file: &Path, limit: usize) -> Vec<serde_json::Value> {
+        let mut out = Vec::new();
+        dump_cat(file, limit, &mut EntryDecoder::new(None), &mut out).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    /// Test that versions are listed with their shard files summary.
+    #[tokio::test]
+    async fn test_dump_ls_lists_versions() {
+        let mut out = Vec::new();
+        dump_ls(&fixture(CLEAN_DIR, ""), &mut out).await.unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert_eq!(out.lines().count(), 1, "{out}");
+        assert!(out.starts_with("v1\t"), "{out}");
+        assert!(out.contains("\tfiles=2\tgzip=1\t"), "{out}");
+        assert!(out.contains("\twritten_at=20260101T000000"), "{out}");
+    }
+
+    /// Test that entries are decoded without a config and the limit is honoured.
+    #[test]
+    fn test_dump_cat_without_config() {
+        let lines = cat_lines(&fixture(CLEAN_DIR, SHARD_0), 2);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["path"], "/api/v1/user");
+        assert_eq!(lines[1]["path"], "/api/v1/buyer");
+        assert_eq!(lines[0]["key"], lines[1]["key"]);
+        assert_eq!(lines[0]["crc"], "ok");
+        assert_eq!(lines[0]["status"], 200);
+        assert_eq!(lines[0]["fingerprint"].as_str().unwrap().len(), 32);
+
+        let lines = cat_lines(&fixture(CLEAN_DIR, SHARD_0), 10);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2]["crc"], "none");
+
+        let lines = cat_lines(&fixture(CLEAN_DIR, SHARD_1_GZ), 10);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["path"], "/api/v1/buyer");
+    }
+
+    /// Test that a clean version directory verifies without errors.
+    #[tokio::test]
+    async fn test_dump_verify_clean() {
+        let mut out = Vec::new();
+        let report = dump_verify(&fixture(CLEAN_DIR, "v1"), &mut EntryDecoder::new(None), &mut out)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            report,
+            VerifyReport {
+                files: 2,
+                ok: 5,
+                ..Default::default()
+            }
+        );
+        assert!(report.is_clean());
+    }
+
+    /// Test that crc mismatches, undecodable entries and truncated tails are reported separately.
+    #[tokio::test]
+    async fn test_dump_verify_reports_corruption() {
+        let mut out = Vec::new();
+        let report = dump_verify(&fixture(CORRUPT_DIR, "v1"), &mut EntryDecoder::new(None), &mut out)
+            .await
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert_eq!(
+            report,
+            VerifyReport {
+                files: 1,
+                ok: 1,
+                crc_mismatch: 1,
+                decode_errors: 1,
+                read_errors: 1,
+            }
+        );
+        assert!(!report.is_clean());
+        assert!(out.contains("record=1\tcrc mismatch"), "{out}");
+        assert!(out.contains("record=2\tdecode error"), "{out}");
+        assert!(out.contains("record=3\tread error"), "{out}");
+    }
+
+    /// Test that with a config, entries of paths without a rule fail to decode.
+    #[tokio::test]
+    async fn test_dump_verify_with_config_rules() {
+        let mut cfg = new_test_config();
+        cfg.cache.rules.as_mut().unwrap().remove("/api/v1/buyer");
+
+        let mut out = Vec::new();
+        let report = dump_verify(&fixture(CLEAN_DIR, "v1"), &mut EntryDecoder::new(Some(cfg)), &mut out)
+            .await
+            .unwrap();
+
+        assert_eq!(report.ok, 3);
+        assert_eq!(report.decode_errors, 2);
+    }
+
+    /// Test that filtering keeps only entries of the requested path and preserves crc control per record.
+    #[test]
+    fn test_dump_filter_by_path() {
+        let dir = temp_dir("dump-filter");
+        let output = dir.join("filtered-shard-0-20260101T000000.dump.gz");
+
+        let report = dump_filter(
+            &fixture(CLEAN_DIR, SHARD_0),
+            &output,
+            "/api/v1/user",
+            &mut EntryDecoder::new(None),
+        )
+        .unwrap();
+        assert_eq!(
+            report,
+            FilterReport {
+                read: 3,
+                written: 2,
+                skipped_corrupt: 0,
+            }
+        );
+
+        let lines = cat_lines(&output, 10);
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| l["path"] == "/api/v1/user"));
+        assert_eq!(lines[0]["crc"], "ok");
+        assert_eq!(lines[1]["crc"], "none");
+        assert!(!dir.join("filtered-shard-0-20260101T000000.dump.gz.tmp").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Test that corrupt records are skipped and the readable part of a damaged dump is salvaged.
+    #[test]
+    fn test_dump_filter_skips_corrupt() {
+        let dir = temp_dir("dump-filter-corrupt");
+        let output = dir.join("filtered.dump");
+
+        let report = dump_filter(
+            &fixture(CORRUPT_DIR, SHARD_0),
+            &output,
+            "/api/v1/user",
+            &mut EntryDecoder::new(None),
+        )
+        .unwrap();
+        assert_eq!(
+            report,
+            FilterReport {
+                read: 3,
+                written: 1,
+                skipped_corrupt: 3,
+            }
+        );
+
+        let lines = cat_lines(&output, 10);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["crc"], "ok");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
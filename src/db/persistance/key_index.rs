@@ -0,0 +1,231 @@
+//! Per-file key indexes of a dump, for loading single entries ahead of the restore.
+//
+// Each shard file `<file>` of a dump is accompanied by `<file>.keys`: the keys it holds with the
+// offset of their record in the (uncompressed) file. With `load_on_demand` a restore reads the
+// indexes first; a request missing a key that is still waiting in a file then loads that one
+// record instead of going to the origin. Every indexed key is claimed once, by the request or by
+// the sequential load, whichever comes first, so an entry is never restored twice.
+//
+// Layout (little-endian): magic, u64 count, count x (u64 key, u64 offset) sorted by key, u32 CRC32
+// of the pairs. Dumps without indexes are restored sequentially as before.
+
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use flate2::read::GzDecoder;
+
+use super::dumper::{is_gzip_path, DumpReader};
+use crate::config::Config;
+use crate::controller::metrics;
+use crate::db::storage::map::SHARD_MASK;
+use crate::dedlog;
+use crate::model::Entry;
+
+/// Suffix of the index file next to a shard file.
+pub const INDEX_SUFFIX: &str = ".keys";
+
+const MAGIC: &[u8; 8] = b"ADVKEYS1";
+const PAIR_SIZE: usize = 16;
+
+/// Path of the index of a shard file.
+pub fn index_path(dump_file: &Path) -> PathBuf {
+    let mut path = dump_file.as_os_str().to_owned();
+    path.push(INDEX_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Writes an index of `(key, offset)` pairs, sorting them first.
+pub fn write_index<W: Write>(w: &mut W, pairs: &mut [(u64, u64)]) -> std::io::Result<()> {
+    pairs.sort_unstable_by_key(|&(key, _)| key);
+    let mut buf = Vec::with_capacity(pairs.len() * PAIR_SIZE);
+    for (key, offset) in pairs.iter() {
+        buf.extend_from_slice(&key.to_le_bytes());
+        buf.extend_from_slice(&offset.to_le_bytes());
+    }
+    w.write_all(MAGIC)?;
+    w.write_all(&(pairs.len() as u64).to_le_bytes())?;
+    w.write_all(&buf)?;
+    w.write_all(&crc32fast::hash(&buf).to_le_bytes())?;
+    w.flush()
+}
+
+/// Reads an index written by [`write_index`], rejecting anything malformed.
+pub fn read_index(path: &Path) -> std::io::Result<Vec<(u64, u64)>> {
+    let invalid = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string());
+
+    let raw = std::fs::read(path)?;
+    if raw.len() < MAGIC.len() + 8 + 4 || &raw[..MAGIC.len()] != MAGIC {
+        return Err(invalid("not a dump key index"));
+    }
+    let count = u64::from_le_bytes(raw[8..16].try_into().unwrap()) as usize;
+    let pairs = &raw[16..raw.len() - 4];
+    if count.checked_mul(PAIR_SIZE) != Some(pairs.len()) {
+        return Err(invalid("key index size does not match its count"));
+    }
+    let crc = u32::from_le_bytes(raw[raw.len() - 4..].try_into().unwrap());
+    if crc32fast::hash(pairs) != crc {
+        return Err(invalid("key index crc mismatch"));
+    }
+
+    let index: Vec<(u64, u64)> = pairs
+        .chunks_exact(PAIR_SIZE)
+        .map(|c| (u64::from_le_bytes(c[..8].try_into().unwrap()), u64::from_le_bytes(c[8..].try_into().unwrap())))
+        .collect();
+    if index.windows(2).any(|w| w[0].0 >= w[1].0) {
+        return Err(invalid("key index is not sorted"));
+    }
+    Ok(index)
+}
+
+/// Outcome of claiming a key of the dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Claim {
+    /// The key is in no index: it is not in the dump, or its file has no index.
+    NotIndexed,
+    /// The key was pending and is now the caller's to load.
+    Claimed,
+    /// The key was claimed before.
+    Taken,
+}
+
+struct IndexedFile {
+    path: PathBuf,
+    pairs: Vec<(u64, u64)>,
+    /// One bit per pair, set once the key is claimed.
+    claimed: Vec<AtomicU64>,
+}
+
+impl IndexedFile {
+    /// Position of the key in the file's index.
+    fn position(&self, key: u64) -> Option<usize> {
+        self.pairs.binary_search_by_key(&key, |&(k, _)| k).ok()
+    }
+
+    fn claim(&self, pos: usize) -> bool {
+        let bit = 1u64 << (pos % 64);
+        self.claimed[pos / 64].fetch_or(bit, Ordering::AcqRel) & bit == 0
+    }
+
+    /// Hands a key that could not be loaded back to the sequential load.
+    fn release(&self, pos: usize) {
+        self.claimed[pos / 64].fetch_and(!(1u64 << (pos % 64)), Ordering::AcqRel);
+    }
+}
+
+/// Key indexes of the dump being restored.
+pub struct RestoreIndex {
+    files: Vec<IndexedFile>,
+    /// Storage shard to the file holding its keys; dump files are written per shard.
+    by_shard: HashMap<u64, usize>,
+    cfg: Config,
+    crc32_control: bool,
+    on_demand: AtomicU64,
+}
+
+impl RestoreIndex {
+    /// Reads the indexes of the given shard files. Files without a readable index are left to
+    /// the sequential load; `None` when no file has one (e.g. dumps written before indexes).
+    pub fn open(files: &[PathBuf], cfg: Config, crc32_control: bool) -> Option<Self> {
+        let mut indexed = Vec::new();
+        let mut by_shard = HashMap::new();
+        for path in files {
+            let pairs = match read_index(&index_path(path)) {
+                Ok(pairs) => pairs,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    dedlog::err("dump", Some(&e as &dyn std::error::Error), Some("file"), "[load] key index read error");
+                    continue;
+                }
+            };
+            if let Some(&(key, _)) = pairs.first() {
+                by_shard.insert(key & SHARD_MASK, indexed.len());
+            }
+            let claimed = (0..pairs.len().div_ceil(64)).map(|_| AtomicU64::new(0)).collect();
+            indexed.push(IndexedFile { path: path.clone(), pairs, claimed });
+        }
+        if indexed.is_empty() {
+            return None;
+        }
+        Some(Self {
+            files: indexed,
+            by_shard,
+            cfg,
+            crc32_control,
+            on_demand: AtomicU64::new(0),
+        })
+    }
+
+    /// Number of indexed keys.
+    pub fn keys(&self) -> usize {
+        self.files.iter().map(|f| f.pairs.len()).sum()
+    }
+
+    /// Entries loaded on demand so far.
+    pub fn on_demand(&self) -> u64 {
+        self.on_demand.load(Ordering::Relaxed)
+    }
+
+    fn locate(&self, key: u64) -> Option<(&IndexedFile, usize)> {
+        let file = &self.files[*self.by_shard.get(&(key & SHARD_MASK))?];
+        Some((file, file.position(key)?))
+    }
+
+    /// Claims the key for the sequential load.
+    pub fn claim(&self, key: u64) -> Claim {
+        match self.locate(key) {
+            None => Claim::NotIndexed,
+            Some((file, pos)) if file.claim(pos) => Claim::Claimed,
+            Some(_) => Claim::Taken,
+        }
+    }
+
+    /// Loads the entry of a key still waiting in the dump, claiming it so that the sequential
+    /// load skips it. `None` when the key is not pending or its record cannot be read.
+    pub fn take(&self, key: u64) -> Option<Entry> {
+        let (file, pos) = self.locate(key)?;
+        if !file.claim(pos) {
+            return None;
+        }
+        match self.read_entry(&file.path, file.pairs[pos].1) {
+            Ok(entry) if entry.key() == key && entry.rule().is_persisted() => {
+                self.on_demand.fetch_add(1, Ordering::Relaxed);
+                metrics::inc_dump_restored_on_demand();
+                Some(entry)
+            }
+            Ok(_) => {
+                file.release(pos);
+                None
+            }
+            Err(e) => {
+                dedlog::err("dump", Some(e.as_ref()), Some("file"), "[load] on-demand read error");
+                file.release(pos);
+                None
+            }
+        }
+    }
+
+    /// Reads the record at `offset` of a shard file. A gzip file has no random access, so it is
+    /// decompressed up to the record.
+    fn read_entry(&self, path: &Path, offset: u64) -> Result<Entry, Box<dyn std::error::Error + Send + Sync>> {
+        let mut file = std::fs::File::open(path)?;
+        let reader: Box<dyn Read> = if is_gzip_path(path) {
+            let mut decoder = GzDecoder::new(file);
+            let skipped = std::io::copy(&mut (&mut decoder).take(offset), &mut std::io::sink())?;
+            if skipped != offset {
+                return Err("record offset past the end of the file".into());
+            }
+            Box::new(decoder)
+        } else {
+            file.seek(SeekFrom::Start(offset))?;
+            Box::new(file)
+        };
+
+        let record = DumpReader::new(reader).next().ok_or("record offset past the end of the file")??;
+        if self.crc32_control && !record.crc_matches() {
+            return Err("crc mismatch".into());
+        }
+        crate::model::to_bytes::from_bytes(&record.data, &self.cfg)
+    }
+}
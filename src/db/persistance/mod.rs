@@ -1,6 +1,68 @@
 // Cache persistence (dump/load) functionality.
 
+use anyhow::Result;
+use tokio_util::sync::CancellationToken;
+
+use crate::model::Entry;
+
+#[cfg(feature = "dump")]
 pub mod dumper;
+#[cfg(feature = "dump")]
+pub mod inspect;
+#[cfg(feature = "dump")]
+pub mod key_index;
+#[cfg(all(test, feature = "dump"))]
+mod dumper_test;
+#[cfg(all(test, feature = "dump"))]
+mod inspect_test;
 
 // Re-export main types
-pub use dumper::{Dumper, DumperImpl};
+#[cfg(feature = "dump")]
+pub use dumper::DumperImpl;
+
+/// Dumper interface for cache persistence.
+#[async_trait::async_trait]
+pub trait Dumper: Send + Sync {
+    /// Dumps cache to disk.
+    async fn dump(&self, ctx: CancellationToken) -> Result<()>;
+
+    /// Loads cache from disk.
+    async fn load(&self, ctx: CancellationToken) -> Result<()>;
+
+    /// Loads a specific version of cache dump.
+    #[allow(dead_code)]
+    async fn load_version(&self, ctx: CancellationToken, version: &str) -> Result<()>;
+
+    /// Loads the entry of a key still waiting in the dump being restored, ahead of the
+    /// sequential load (`load_on_demand`). `None` when no restore is running or the key is not
+    /// pending in it.
+    fn load_key(&self, _key: u64) -> Option<Entry> {
+        None
+    }
+
+    /// Whether the dump restored on start finished loading, whether it restored anything or
+    /// failed. Readiness waits for it.
+    fn is_loaded(&self) -> bool {
+        true
+    }
+}
+
+/// Dumper of builds without the `dump` feature, whose configs cannot enable `data.dump`.
+#[cfg(not(feature = "dump"))]
+pub struct NoDumper;
+
+#[cfg(not(feature = "dump"))]
+#[async_trait::async_trait]
+impl Dumper for NoDumper {
+    async fn dump(&self, _ctx: CancellationToken) -> Result<()> {
+        anyhow::bail!("advcache was built without the `dump` feature")
+    }
+
+    async fn load(&self, _ctx: CancellationToken) -> Result<()> {
+        anyhow::bail!("advcache was built without the `dump` feature")
+    }
+
+    async fn load_version(&self, _ctx: CancellationToken, _version: &str) -> Result<()> {
+        anyhow::bail!("advcache was built without the `dump` feature")
+    }
+}
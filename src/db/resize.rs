@@ -0,0 +1,60 @@
+//! Resizing the storage at runtime.
+//
+// A resize swaps the soft, hard and admission limits at once, derived from the new size as
+// `storage.size` is at startup. Growing takes effect on the next set; shrinking below the bytes
+// stored is left to eviction, which brings them down pass by pass while requests go on. The size
+// is recorded in the runtime overrides file (`runtime.overrides`), when one is configured, and
+// outlives restarts and config reloads until it is reset to the configured one.
+
+use std::path::Path;
+
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::bytes;
+use crate::config::overrides::RuntimeOverrides;
+use crate::config::MemoryLimits;
+use crate::db::Storage;
+
+/// Sets the storage size, `None` going back to `storage.size`, and returns the limits taken.
+/// The overrides file is written first: when it cannot be, the error is returned and the
+/// limits are left as they were.
+pub fn resize(storage: &dyn Storage, size: Option<i64>, overrides: Option<&Path>) -> Result<MemoryLimits> {
+    if let Some(path) = overrides {
+        RuntimeOverrides::update(path, |o| o.storage_size = size)?;
+    }
+    let previous = storage.memory_limits();
+    let limits = storage.resize(size);
+    info!(
+        component = "storage",
+        event = "storage_resized",
+        from = %bytes::fmt_mem(previous.size),
+        to = %bytes::fmt_mem(limits.size),
+        configured = size.is_none(),
+        stored = %bytes::fmt_mem(storage.stat().0),
+        "storage resized"
+    );
+    Ok(limits)
+}
+
+/// Sets the storage size again if it was resized when the process stopped.
+pub fn restore(storage: &dyn Storage, overrides: &Path) {
+    match RuntimeOverrides::load(overrides) {
+        Ok(RuntimeOverrides { storage_size: Some(size), .. }) => {
+            let limits = storage.resize(Some(size));
+            info!(
+                component = "storage",
+                event = "storage_resize_restored",
+                size = %bytes::fmt_mem(limits.size),
+                "storage size is still set from before the restart"
+            );
+        }
+        Ok(_) => {}
+        Err(e) => warn!(
+            component = "storage",
+            event = "runtime_overrides_unreadable",
+            error = %format!("{:#}", e),
+            "runtime overrides not applied"
+        ),
+    }
+}
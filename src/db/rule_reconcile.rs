@@ -0,0 +1,89 @@
+//! Reconciliation of the rules held by stored entries.
+//!
+//! Entries keep the `Arc<Rule>` they were stored under, so after a config reload they go on
+//! refreshing with the settings of the previous rule until they are evicted. With
+//! `lifetime.reconcile_rules` on, a reload re-points every entry at the reloaded rule of its
+//! path. Entries whose key schema changed are left to the key schema purge instead.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::config::Config;
+use crate::controller::metrics;
+use crate::db::Storage;
+
+const COMP_RULE_RECONCILE: &str = "rule_reconcile";
+
+/// Outcome of a rule reconciliation.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReconcileReport {
+    pub scanned: u64,
+    pub migrated: u64,
+    /// Migrated entries per rule path.
+    pub by_rule: BTreeMap<String, u64>,
+}
+
+/// Points entries still holding a previous rule at the rule of their path in `cfg`.
+/// Entries of rules absent in `cfg` keep theirs.
+pub fn reconcile_rules(token: &CancellationToken, storage: &dyn Storage, cfg: &Config) -> ReconcileReport {
+    let report = Arc::new(Mutex::new(ReconcileReport::default()));
+    {
+        let report = report.clone();
+        let rules = cfg.cache.rules.clone().unwrap_or_default();
+        let walk_token = token.clone();
+        storage.walk_shards(
+            token.clone(),
+            Box::new(move |_shard_id, shard| {
+                let mut seen = 0u64;
+                let mut migrated: BTreeMap<String, u64> = BTreeMap::new();
+                // Swapping the rule is atomic, so it is done under the read lock of the walk.
+                shard.walk_r(&walk_token, |_key, entry| {
+                    seen += 1;
+                    let rule = entry.rule();
+                    let Some(path) = rule.path.as_deref() else {
+                        return true;
+                    };
+                    let Some(current) = rules.get(path).cloned() else {
+                        return true;
+                    };
+                    if Arc::ptr_eq(&current, &rule) || current.key_schema() != entry.key_schema() {
+                        return true;
+                    }
+                    entry.set_rule(current);
+                    *migrated.entry(path.to_string()).or_default() += 1;
+                    true
+                });
+                let mut report = report.lock().unwrap();
+                report.scanned += seen;
+                for (path, n) in migrated {
+                    report.migrated += n;
+                    *report.by_rule.entry(path).or_default() += n;
+                }
+            }),
+        );
+    }
+    let report = std::mem::take(&mut *report.lock().unwrap());
+
+    metrics::inc_rule_entries_reconciled(report.migrated);
+    for (rule, migrated) in &report.by_rule {
+        info!(
+            component = COMP_RULE_RECONCILE,
+            event = "migrated",
+            rule = %rule,
+            migrated = migrated,
+            "re-pointed entries at the reloaded rule"
+        );
+    }
+    info!(
+        component = COMP_RULE_RECONCILE,
+        event = "reconcile_done",
+        scanned = report.scanned,
+        migrated = report.migrated,
+        "rule reconciliation finished"
+    );
+
+    report
+}
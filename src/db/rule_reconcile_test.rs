@@ -0,0 +1,158 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use axum::Router;
+    use tokio_util::sync::CancellationToken;
+    use tower::ServiceExt;
+
+    use crate::config::{self, Config, ConfigTrait, LifetimeRule};
+    use crate::controller::GetController;
+    use crate::db::{Storage, DB};
+    use crate::governor::Orchestrator;
+    use crate::http::Controller;
+    use crate::model::refresh::RefreshSource;
+    use crate::model::{Entry, Response};
+    use crate::upstream::testing::MockUpstream;
+
+    const PATH: &str = "/api/v1/user";
+
+    /// Same config with the refresh TTL of `path` set on the rule itself, as a config reload
+    /// after editing the rule's `refresh` section would produce.
+    fn with_rule_ttl(cfg: &Config, path: &str, ttl: Duration) -> Config {
+        let mut cfg = cfg.clone();
+        let rules = cfg.cache.rules.as_mut().unwrap();
+        let mut rule = (*rules[path]).clone();
+        rule.refresh = Some(LifetimeRule {
+            enabled: true,
+            ttl: Some(ttl),
+            beta: None,
+            coefficient: None,
+        });
+        rule.refresh_inherited = false;
+        rules.insert(path.to_string(), Arc::new(rule));
+        cfg
+    }
+
+    fn reconciling(mut cfg: Config) -> Config {
+        cfg.cache.lifetime.as_mut().unwrap().reconcile_rules = Some(true);
+        cfg
+    }
+
+    fn stored_entry(cfg: &Config, path: &str, id: usize) -> Entry {
+        let rule = cfg.rule(path).unwrap();
+        let queries = vec![(b"user[id]".to_vec(), id.to_string().into_bytes())];
+        let entry = Entry::new(rule, &queries, &[]);
+        entry.set_payload(
+            &queries,
+            &[],
+            &Response {
+                status: 200,
+                headers: vec![],
+                body: format!("{{\"id\":{}}}", id).into_bytes(),
+            },
+        );
+        entry
+    }
+
+    fn new_db(cfg: &Config) -> (Arc<DB>, CancellationToken) {
+        let shutdown = CancellationToken::new();
+        let db = DB::new(shutdown.clone(), cfg.clone(), Arc::new(Orchestrator::new()), MockUpstream::new())
+            .expect("storage must start");
+        (db, shutdown)
+    }
+
+    /// Test that an entry reports the global lifetime while its rule inherits it, and stale once
+    /// a reload replaced its rule.
+    #[test]
+    fn test_refresh_source_follows_the_config() {
+        let cfg = config::new_test_config();
+        let entry = stored_entry(&cfg, "/api/v1/buyer", 1);
+
+        let (params, source) = entry.refresh_settings(&cfg);
+        assert_eq!(source, RefreshSource::Global);
+        assert_eq!(params.ttl, cfg.lifetime().unwrap().ttl.unwrap());
+
+        let reloaded = with_rule_ttl(&cfg, "/api/v1/buyer", Duration::from_secs(10));
+        let (params, source) = entry.refresh_settings(&reloaded);
+        assert_eq!(source, RefreshSource::Stale);
+        assert_eq!(params.ttl, cfg.lifetime().unwrap().ttl.unwrap(), "a stale rule keeps its settings");
+
+        let (params, source) = stored_entry(&reloaded, "/api/v1/buyer", 2).refresh_settings(&reloaded);
+        assert_eq!(source, RefreshSource::Rule);
+        assert_eq!(params.ttl, Duration::from_secs(10));
+    }
+
+    /// Test that with reconciliation on, a reload changing a rule's TTL is adopted by the
+    /// entries already stored, and only by those of that rule.
+    #[tokio::test]
+    async fn test_reload_reconciles_entries_to_the_new_ttl() {
+        let cfg = reconciling(config::new_test_config());
+        let (db, shutdown) = new_db(&cfg);
+        for id in 0..8 {
+            assert!(db.set(stored_entry(&cfg, PATH, id)));
+            assert!(db.set(stored_entry(&cfg, "/api/v1/buyer", id)));
+        }
+
+        let reloaded = with_rule_ttl(&cfg, PATH, Duration::from_secs(10));
+        let report = db.schedule_rule_reconciliation(reloaded.clone()).await.unwrap();
+        assert_eq!(report.scanned, 16);
+        assert_eq!(report.migrated, 8);
+        assert_eq!(report.by_rule.get(PATH), Some(&8));
+
+        for id in 0..8 {
+            let (entry, _) = db.get(&stored_entry(&cfg, PATH, id));
+            let (params, source) = entry.unwrap().refresh_settings(&reloaded);
+            assert_eq!((params.ttl, source), (Duration::from_secs(10), RefreshSource::Rule));
+
+            let (entry, _) = db.get(&stored_entry(&cfg, "/api/v1/buyer", id));
+            assert_eq!(entry.unwrap().refresh_settings(&reloaded).1, RefreshSource::Global);
+        }
+
+        // Nothing is left to migrate.
+        let report = db.schedule_rule_reconciliation(reloaded).await.unwrap();
+        assert_eq!(report.migrated, 0);
+        shutdown.cancel();
+    }
+
+    /// Test that without reconciliation entries keep the rule they were stored under.
+    #[tokio::test]
+    async fn test_reload_without_reconciliation_keeps_stale_rules() {
+        let cfg = config::new_test_config();
+        let (db, shutdown) = new_db(&cfg);
+        assert!(db.set(stored_entry(&cfg, PATH, 1)));
+
+        let reloaded = with_rule_ttl(&cfg, PATH, Duration::from_secs(60));
+        db.on_config_reload(reloaded.clone());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let (entry, _) = db.get(&stored_entry(&cfg, PATH, 1));
+        assert_eq!(entry.unwrap().refresh_settings(&reloaded).1, RefreshSource::Stale);
+        shutdown.cancel();
+    }
+
+    /// Test that the entry endpoint shows the refresh settings in effect and their source.
+    #[tokio::test]
+    async fn test_entry_endpoint_shows_refresh_settings() {
+        let cfg = config::new_test_config();
+        let (db, shutdown) = new_db(&cfg);
+        let entry = stored_entry(&cfg, "/api/v1/buyer", 1);
+        let key = entry.key();
+        assert!(db.set(entry));
+
+        let router = GetController::new(cfg, db).add_route(Router::new());
+        let resp = router
+            .oneshot(Request::get(format!("/advcache/entry?key={}", key)).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&to_bytes(resp.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(json["refresh"]["source"], "global");
+        assert_eq!(json["refresh"]["ttl"], "1day");
+        assert_eq!(json["refresh"]["beta"], 0.4);
+        assert!(json.get("hits").is_none(), "listing mode counts no hits to show");
+        shutdown.cancel();
+    }
+}
@@ -0,0 +1,118 @@
+//! Replays sampled accesses through a simple cache model for the what-if analysis.
+//
+// The model is an LRU bounded by the storage size scaled down to the sample, where an entry
+// older than the TTL is a miss and is stored again. It starts empty, so the first access of
+// every key is a miss: estimates from a window short next to the TTL lean pessimistic.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::analytics::Trace;
+
+/// z of a two-sided 95% interval.
+const Z_95: f64 = 1.96;
+
+/// Settings to estimate the cache under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scenario {
+    /// Storage size in bytes.
+    pub size: i64,
+    /// Entries older than this are misses; `None` keeps them until evicted.
+    pub ttl: Option<Duration>,
+}
+
+/// Estimated behaviour of the whole cache under a scenario.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Estimate {
+    pub size_bytes: i64,
+    #[serde(with = "humantime_serde")]
+    pub ttl: Option<Duration>,
+    /// Sampled accesses replayed.
+    pub accesses: u64,
+    pub hits: u64,
+    pub hit_rate: f64,
+    /// Half width of the 95% interval of `hit_rate`, taking sampled keys as the sample size.
+    pub hit_rate_error: f64,
+    /// Mean stored bytes over the replay, scaled to the whole cache.
+    pub memory_bytes: i64,
+    /// Most stored bytes at once, scaled to the whole cache.
+    pub peak_memory_bytes: i64,
+}
+
+struct Slot {
+    stored_at: i64,
+    size: i64,
+    tick: u64,
+}
+
+/// Replays the trace under the scenario.
+pub fn simulate(trace: &Trace, scenario: Scenario) -> Estimate {
+    let rate = if trace.rate > 0.0 { trace.rate } else { 1.0 };
+    let capacity = (scenario.size as f64 * rate) as i64;
+    let ttl = scenario.ttl.map(|ttl| ttl.as_nanos().min(i64::MAX as u128) as i64);
+
+    let mut accesses = trace.accesses.clone();
+    accesses.sort_by_key(|a| a.at);
+
+    let mut slots: HashMap<u64, Slot> = HashMap::new();
+    let mut lru: BTreeMap<u64, u64> = BTreeMap::new();
+    let mut keys = HashSet::new();
+    let (mut used, mut peak, mut used_sum) = (0i64, 0i64, 0f64);
+    let mut hits = 0u64;
+
+    for (tick, access) in accesses.iter().enumerate() {
+        let tick = tick as u64;
+        keys.insert(access.key);
+
+        if let Some(slot) = slots.get_mut(&access.key) {
+            if ttl.is_none_or(|ttl| access.at - slot.stored_at < ttl) {
+                hits += 1;
+                lru.remove(&slot.tick);
+                lru.insert(tick, access.key);
+                slot.tick = tick;
+                used_sum += used as f64;
+                continue;
+            }
+            // Expired: dropped and stored again below, as a refill would.
+            lru.remove(&slot.tick);
+            used -= slot.size;
+            slots.remove(&access.key);
+        }
+
+        if access.size <= capacity {
+            while used + access.size > capacity {
+                let Some((_, victim)) = lru.pop_first() else { break };
+                if let Some(evicted) = slots.remove(&victim) {
+                    used -= evicted.size;
+                }
+            }
+            slots.insert(access.key, Slot { stored_at: access.at, size: access.size, tick });
+            lru.insert(tick, access.key);
+            used += access.size;
+            peak = peak.max(used);
+        }
+        used_sum += used as f64;
+    }
+
+    let n = accesses.len() as u64;
+    let hit_rate = if n > 0 { hits as f64 / n as f64 } else { 0.0 };
+    let hit_rate_error = if keys.is_empty() {
+        0.0
+    } else {
+        Z_95 * (hit_rate * (1.0 - hit_rate) / keys.len() as f64).sqrt()
+    };
+    let mean = if n > 0 { used_sum / n as f64 } else { 0.0 };
+
+    Estimate {
+        size_bytes: scenario.size,
+        ttl: scenario.ttl,
+        accesses: n,
+        hits,
+        hit_rate,
+        hit_rate_error,
+        memory_bytes: (mean / rate) as i64,
+        peak_memory_bytes: (peak as f64 / rate) as i64,
+    }
+}
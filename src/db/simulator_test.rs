@@ -0,0 +1,110 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::db::analytics::{Access, AccessSampler, Trace};
+    use crate::db::simulator::{simulate, Scenario};
+
+    const SECOND: i64 = 1_000_000_000;
+
+    /// `rounds` passes over keys `0..keys` in order, one access per second, every entry `size` bytes.
+    fn cyclic(keys: u64, rounds: u64, size: i64) -> Trace {
+        let accesses = (0..rounds * keys)
+            .map(|i| Access { key: i % keys, at: i as i64 * SECOND, size })
+            .collect();
+        Trace { rate: 1.0, accesses }
+    }
+
+    fn scenario(size: i64, ttl: Option<Duration>) -> Scenario {
+        Scenario { size, ttl }
+    }
+
+    /// Test that a working set fitting the cache misses only on first access: (R-1)/R over R rounds.
+    #[test]
+    fn test_fitting_working_set_hits_after_first_round() {
+        let estimate = simulate(&cyclic(100, 10, 1024), scenario(100 * 1024, None));
+        assert_eq!(estimate.accesses, 1000);
+        assert_eq!(estimate.hits, 900);
+        assert!((estimate.hit_rate - 0.9).abs() < 1e-9);
+        assert_eq!(estimate.peak_memory_bytes, 100 * 1024);
+    }
+
+    /// Test that a cyclic scan one entry larger than an LRU never hits.
+    #[test]
+    fn test_scan_larger_than_lru_never_hits() {
+        let estimate = simulate(&cyclic(100, 10, 1024), scenario(99 * 1024, None));
+        assert_eq!(estimate.hits, 0);
+        assert_eq!(estimate.hit_rate, 0.0);
+        assert_eq!(estimate.hit_rate_error, 0.0);
+        assert_eq!(estimate.peak_memory_bytes, 99 * 1024);
+    }
+
+    /// Test that a TTL shorter than the reuse distance turns every reuse into a miss.
+    #[test]
+    fn test_ttl_shorter_than_reuse_never_hits() {
+        // Each key is reused every 100s.
+        let trace = cyclic(100, 10, 1024);
+        assert_eq!(simulate(&trace, scenario(1 << 20, Some(Duration::from_secs(1000)))).hits, 900);
+        assert_eq!(simulate(&trace, scenario(1 << 20, Some(Duration::from_secs(50)))).hits, 0);
+
+        // A TTL of 250s serves two reuses per store: 2 of every 3 accesses after the first hit.
+        let estimate = simulate(&trace, scenario(1 << 20, Some(Duration::from_secs(250))));
+        assert_eq!(estimate.hits, 100 * 6);
+    }
+
+    /// Test that sizes are scaled between the sample and the whole cache by the sample rate.
+    #[test]
+    fn test_sizes_scale_by_sample_rate() {
+        let mut trace = cyclic(100, 10, 1024);
+        trace.rate = 0.5;
+
+        // 200KB of cache holds 100KB of a half sample: the working set fits.
+        let estimate = simulate(&trace, scenario(200 * 1024, None));
+        assert_eq!(estimate.hits, 900);
+        assert_eq!(estimate.peak_memory_bytes, 200 * 1024);
+
+        assert_eq!(simulate(&trace, scenario(198 * 1024, None)).hits, 0);
+    }
+
+    /// Test that the interval narrows as more keys are sampled.
+    #[test]
+    fn test_error_narrows_with_sampled_keys() {
+        // The same hit rate, 90/190, over 100 and over 10000 keys.
+        let mut skewed = cyclic(10, 10, 1).accesses;
+        skewed.extend((0..90).map(|i| Access { key: 1_000 + i, at: 1_000 * SECOND + i as i64, size: 1 }));
+        let few = simulate(&Trace { rate: 1.0, accesses: skewed }, scenario(10, None));
+        let mut skewed = cyclic(1000, 10, 1).accesses;
+        skewed.extend((0..9000).map(|i| Access { key: 100_000 + i, at: 100_000 * SECOND + i as i64, size: 1 }));
+        let many = simulate(&Trace { rate: 1.0, accesses: skewed }, scenario(1000, None));
+        assert!((few.hit_rate - many.hit_rate).abs() < 1e-9);
+        assert!(many.hit_rate_error < few.hit_rate_error / 5.0);
+    }
+
+    /// Test that sampling keeps every access of a sampled key and about the configured share of keys.
+    #[test]
+    fn test_sampler_keeps_whole_keys() {
+        let sampler = AccessSampler::new(0.25, 1 << 20);
+        for round in 0..3 {
+            for key in 0..10_000u64 {
+                sampler.record(key.wrapping_mul(0xff51_afd7_ed55_8ccd), round);
+            }
+        }
+        let trace = sampler.trace();
+        assert_eq!(trace.rate, 0.25);
+        assert_eq!(trace.accesses.len() % 3, 0);
+        let keys = trace.accesses.len() / 3;
+        assert!((2_000..3_000).contains(&keys), "sampled {} keys of 10000", keys);
+        assert!(trace.accesses.iter().all(|a| sampler.is_sampled(a.key)));
+    }
+
+    /// Test that the window keeps the latest accesses only.
+    #[test]
+    fn test_sampler_window_is_bounded() {
+        let sampler = AccessSampler::new(1.0, 4);
+        for size in 0..10 {
+            sampler.record(7, size);
+        }
+        let sizes: Vec<_> = sampler.trace().accesses.iter().map(|a| a.size).collect();
+        assert_eq!(sizes, [6, 7, 8, 9]);
+    }
+}
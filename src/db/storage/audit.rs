@@ -0,0 +1,222 @@
+//! Eviction audit: a sampled stream of evicted entries for offline analysis.
+//
+// Eviction hands each sampled victim to a bounded channel and moves on; it never waits on the
+// consumer, which writes the events as NDJSON and counts them per rule. When the channel is
+// full the event is dropped and counted instead.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::config;
+use crate::controller::metrics;
+use crate::dedlog;
+use crate::model::Entry;
+use crate::rand;
+use crate::time;
+
+/// Events buffered between eviction and the consumer.
+const CHANNEL_CAPACITY: usize = 4096;
+
+/// File size past which the audit file is rotated when `max_file_size` is not set.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 64 << 20;
+
+/// Why an entry was evicted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EvictionReason {
+    /// Soft memory limit, by the evictor workers.
+    Soft,
+    /// Hard memory limit, inline on set.
+    Hard,
+    /// Made room for a new key that won admission, inline on set.
+    Admission,
+}
+
+impl EvictionReason {
+    pub fn label(self) -> &'static str {
+        match self {
+            EvictionReason::Soft => "soft",
+            EvictionReason::Hard => "hard",
+            EvictionReason::Admission => "admission",
+        }
+    }
+}
+
+/// One evicted entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvictionEvent {
+    /// Unix milliseconds of the eviction.
+    pub at: i64,
+    /// Key hash, hex.
+    pub key: String,
+    /// Path of the rule the entry was cached under.
+    pub rule: String,
+    /// Weight of the entry in bytes.
+    pub size: i64,
+    /// Milliseconds since the entry was stored or last refreshed.
+    pub age_ms: i64,
+    /// Milliseconds since the entry was last read.
+    pub idle_ms: i64,
+    /// Recent storage hits, as the hit counter of the entry tells them.
+    pub hits: u32,
+    pub reason: EvictionReason,
+}
+
+impl EvictionEvent {
+    fn new(entry: &Entry, reason: EvictionReason) -> Self {
+        let now = time::unix_nano();
+        let since_ms = |ts: i64| if ts > 0 { (now - ts).max(0) / 1_000_000 } else { 0 };
+        Self {
+            at: now / 1_000_000,
+            key: format!("{:016x}", entry.key()),
+            rule: entry.0.rule.load().path.clone().unwrap_or_default(),
+            size: entry.weight(),
+            age_ms: since_ms(entry.fresh_at()),
+            idle_ms: since_ms(entry.touched_at()),
+            hits: entry.hits(),
+            reason,
+        }
+    }
+}
+
+/// Sampling side of the audit, called by eviction.
+pub struct EvictionAudit {
+    sample_rate: f64,
+    tx: mpsc::Sender<EvictionEvent>,
+    dropped: AtomicU64,
+}
+
+impl EvictionAudit {
+    /// Starts the audit consumer when `eviction.audit.enabled` is set; `None` otherwise.
+    pub fn start(cfg: Option<&config::EvictionAudit>, shutdown_token: CancellationToken) -> Option<Arc<Self>> {
+        let cfg = cfg.filter(|c| c.enabled)?;
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let sink = cfg.path.as_ref().map(|path| FileSink::new(PathBuf::from(path), cfg.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE)));
+        tokio::spawn(consume(rx, sink, shutdown_token));
+        Some(Arc::new(Self {
+            sample_rate: cfg.sample_rate.unwrap_or(1.0),
+            tx,
+            dropped: AtomicU64::new(0),
+        }))
+    }
+
+    /// Records an evicted entry if it is sampled.
+    pub fn observe(&self, entry: &Entry, reason: EvictionReason) {
+        if self.sample_rate < 1.0 && rand::float64() >= self.sample_rate {
+            return;
+        }
+        if self.tx.try_send(EvictionEvent::new(entry, reason)).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Sampled events dropped because the consumer fell behind.
+    #[allow(dead_code)]
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts the events per rule and appends them to the audit file, if any.
+async fn consume(mut rx: mpsc::Receiver<EvictionEvent>, mut sink: Option<FileSink>, shutdown_token: CancellationToken) {
+    loop {
+        let event = tokio::select! {
+            _ = shutdown_token.cancelled() => break,
+            event = rx.recv() => match event {
+                Some(event) => event,
+                None => break,
+            },
+        };
+        handle(&event, sink.as_mut());
+        // Drain what piled up before flushing, so a burst costs one flush.
+        while let Ok(event) = rx.try_recv() {
+            handle(&event, sink.as_mut());
+        }
+        if let Some(sink) = sink.as_mut() {
+            sink.flush();
+        }
+    }
+}
+
+fn handle(event: &EvictionEvent, sink: Option<&mut FileSink>) {
+    metrics::inc_evictions_audited(&event.rule, event.reason);
+    if let Some(sink) = sink {
+        sink.write(event);
+    }
+}
+
+/// NDJSON file rotated to `<path>.1` once it grows past `max_size`.
+struct FileSink {
+    path: PathBuf,
+    max_size: u64,
+    file: Option<(BufWriter<File>, u64)>,
+}
+
+impl FileSink {
+    fn new(path: PathBuf, max_size: u64) -> Self {
+        Self { path, max_size, file: None }
+    }
+
+    fn write(&mut self, event: &EvictionEvent) {
+        let Ok(mut line) = serde_json::to_vec(event) else {
+            return;
+        };
+        line.push(b'\n');
+
+        if self.file.as_ref().is_some_and(|(_, size)| *size > 0 && size + line.len() as u64 > self.max_size) {
+            self.rotate();
+        }
+        if self.file.is_none() {
+            match open_append(&self.path) {
+                Ok(opened) => self.file = Some(opened),
+                Err(e) => {
+                    dedlog::err("eviction-audit", Some(&e as &dyn std::error::Error), Some(&self.path.to_string_lossy()), "open error");
+                    return;
+                }
+            }
+        }
+        if let Some((writer, size)) = self.file.as_mut() {
+            match writer.write_all(&line) {
+                Ok(()) => *size += line.len() as u64,
+                Err(e) => {
+                    dedlog::err("eviction-audit", Some(&e as &dyn std::error::Error), Some(&self.path.to_string_lossy()), "write error");
+                    self.file = None;
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some((writer, _)) = self.file.as_mut() {
+            if let Err(e) = writer.flush() {
+                dedlog::err("eviction-audit", Some(&e as &dyn std::error::Error), Some(&self.path.to_string_lossy()), "write error");
+                self.file = None;
+            }
+        }
+    }
+
+    /// Moves the current file to `<path>.1`, replacing the previous one; the next write reopens.
+    fn rotate(&mut self) {
+        self.flush();
+        self.file = None;
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+        if let Err(e) = fs::rename(&self.path, &rotated) {
+            dedlog::err("eviction-audit", Some(&e as &dyn std::error::Error), Some(&self.path.to_string_lossy()), "rotate error");
+        }
+    }
+}
+
+fn open_append(path: &Path) -> std::io::Result<(BufWriter<File>, u64)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok((BufWriter::new(file), size))
+}
@@ -0,0 +1,158 @@
+//! Sampled wait times of the shard locks (`storage.lock_profiling`).
+//
+// With profiling off, shards lock as they always did and pay one branch for it. With it on,
+// every blocking acquisition first tries the lock: an uncontended one costs nothing more, a
+// contended one is timed. One acquisition in `every` per thread is sampled into the wait
+// histogram (0 when uncontended); write locks waited on for longer than 1ms are all counted.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use serde::Serialize;
+
+use crate::controller::metrics;
+
+/// Waits for a write lock longer than this are counted as slow.
+pub const SLOW_WRITE_WAIT: Duration = Duration::from_millis(1);
+
+/// Kind of a shard lock acquisition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    Read = 0,
+    Write = 1,
+}
+
+impl LockMode {
+    pub const ALL: [LockMode; 2] = [LockMode::Read, LockMode::Write];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LockMode::Read => "read",
+            LockMode::Write => "write",
+        }
+    }
+}
+
+thread_local! {
+    /// Acquisitions left before the next sampled one on this thread.
+    static COUNTDOWN: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Decides which shard lock acquisitions are sampled.
+#[derive(Debug)]
+pub struct LockProfiler {
+    every: u64,
+}
+
+impl LockProfiler {
+    /// Profiler sampling `rate` of the acquisitions, none when the rate is 0 or unset.
+    pub fn new(rate: Option<f64>) -> Option<Self> {
+        let rate = rate.filter(|r| *r > 0.0)?;
+        Some(Self { every: (1.0 / rate).round().max(1.0) as u64 })
+    }
+
+    fn sampled(&self) -> bool {
+        COUNTDOWN.with(|left| match left.get() {
+            0 => {
+                left.set(self.every - 1);
+                true
+            }
+            n => {
+                left.set(n - 1);
+                false
+            }
+        })
+    }
+
+    /// Takes the read lock, recording the wait into `stats` when sampled.
+    pub fn read<'a, T>(&self, lock: &'a RwLock<T>, stats: &LockStats) -> RwLockReadGuard<'a, T> {
+        let sampled = self.sampled();
+        if let Some(guard) = lock.try_read() {
+            if sampled {
+                stats.record(LockMode::Read, Duration::ZERO);
+            }
+            return guard;
+        }
+        let start = Instant::now();
+        let guard = lock.read();
+        if sampled {
+            stats.record(LockMode::Read, start.elapsed());
+        }
+        guard
+    }
+
+    /// Takes the write lock, recording the wait into `stats` when sampled or slow.
+    pub fn write<'a, T>(&self, lock: &'a RwLock<T>, stats: &LockStats) -> RwLockWriteGuard<'a, T> {
+        let sampled = self.sampled();
+        if let Some(guard) = lock.try_write() {
+            if sampled {
+                stats.record(LockMode::Write, Duration::ZERO);
+            }
+            return guard;
+        }
+        let start = Instant::now();
+        let guard = lock.write();
+        let waited = start.elapsed();
+        if waited > SLOW_WRITE_WAIT {
+            stats.slow_writes.fetch_add(1, Ordering::Relaxed);
+            metrics::inc_shard_lock_slow_writes();
+        }
+        if sampled {
+            stats.record(LockMode::Write, waited);
+        }
+        guard
+    }
+}
+
+/// Lock waits of one shard, by [`LockMode`].
+#[derive(Debug, Default)]
+pub struct LockStats {
+    samples: [AtomicU64; 2],
+    wait_nanos: [AtomicU64; 2],
+    max_wait_nanos: [AtomicU64; 2],
+    slow_writes: AtomicU64,
+}
+
+impl LockStats {
+    fn record(&self, mode: LockMode, waited: Duration) {
+        let nanos = waited.as_nanos().min(u64::MAX as u128) as u64;
+        let i = mode as usize;
+        self.samples[i].fetch_add(1, Ordering::Relaxed);
+        self.wait_nanos[i].fetch_add(nanos, Ordering::Relaxed);
+        self.max_wait_nanos[i].fetch_max(nanos, Ordering::Relaxed);
+        metrics::observe_shard_lock_wait(mode, waited);
+    }
+
+    /// Current totals.
+    pub fn snapshot(&self) -> LockStatsSnapshot {
+        let mode = |i: usize| ModeStats {
+            samples: self.samples[i].load(Ordering::Relaxed),
+            wait_nanos: self.wait_nanos[i].load(Ordering::Relaxed),
+            max_wait_nanos: self.max_wait_nanos[i].load(Ordering::Relaxed),
+        };
+        LockStatsSnapshot {
+            read: mode(LockMode::Read as usize),
+            write: mode(LockMode::Write as usize),
+            slow_writes: self.slow_writes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Sampled acquisitions of one mode and the time they waited.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ModeStats {
+    pub samples: u64,
+    pub wait_nanos: u64,
+    pub max_wait_nanos: u64,
+}
+
+/// Lock waits of a shard as reported by `/advcache/shards`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LockStatsSnapshot {
+    pub read: ModeStats,
+    pub write: ModeStats,
+    /// Write acquisitions that waited longer than 1ms, sampled or not.
+    pub slow_writes: u64,
+}
@@ -0,0 +1,108 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use parking_lot::RwLock;
+    use tokio_util::sync::CancellationToken;
+
+    use crate::config::{self, Rule};
+    use crate::db::storage::contention::{LockProfiler, LockStats, LockStatsSnapshot};
+    use crate::db::storage::{Map, Shard};
+    use crate::model::Entry;
+
+    fn entries(n: usize) -> Vec<Entry> {
+        let rule = Arc::new(Rule::bare("/api/v1/user"));
+        (0..n)
+            .map(|id| Entry::new(rule.clone(), &[(b"id".to_vec(), id.to_string().into_bytes())], &[]))
+            .collect()
+    }
+
+    /// Runs walkers holding the shard read lock for 2ms at a time against writers setting
+    /// entries, until each writer has set every entry 20 times.
+    fn contend(shard: &Shard<Entry>, entries: &[Entry]) {
+        for entry in entries {
+            shard.set(entry.key(), entry.clone(), None);
+        }
+        let done = AtomicBool::new(false);
+        let token = CancellationToken::new();
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    while !done.load(Ordering::Relaxed) {
+                        shard.walk_r(&token, |_, _| {
+                            std::thread::sleep(Duration::from_millis(2));
+                            false
+                        });
+                    }
+                });
+            }
+            let writers: Vec<_> = (0..4)
+                .map(|_| {
+                    s.spawn(|| {
+                        for _ in 0..20 {
+                            for entry in entries {
+                                shard.set(entry.key(), entry.clone(), None);
+                            }
+                        }
+                    })
+                })
+                .collect();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+    }
+
+    /// Test that with profiling on, writers blocked by walkers record their waits, and waits
+    /// over 1ms are counted as slow writes.
+    #[test]
+    fn test_contention_recorded_when_profiling() {
+        let shard: Shard<Entry> = Shard::new(0, Default::default()).profiled(LockProfiler::new(Some(1.0)).map(Arc::new));
+        let entries = entries(8);
+        contend(&shard, &entries);
+
+        let stats = shard.lock_stats().snapshot();
+        assert_eq!(stats.write.samples, (4 * 20 + 1) * 8, "every write is sampled at rate 1");
+        assert!(stats.write.wait_nanos > 0, "writers must have waited for the walkers: {:?}", stats);
+        assert!(stats.write.max_wait_nanos >= Duration::from_millis(1).as_nanos() as u64);
+        assert!(stats.slow_writes > 0, "waits behind a 2ms walk are slow: {:?}", stats);
+        assert!(stats.read.samples > 0, "walks take the read lock through the profiler");
+    }
+
+    /// Test that with a rate of 0 the shards are not profiled: nothing is recorded under the
+    /// same contention.
+    #[test]
+    fn test_nothing_recorded_at_rate_zero() {
+        assert!(LockProfiler::new(Some(0.0)).is_none());
+        assert!(LockProfiler::new(None).is_none());
+
+        let mut cfg = config::new_test_config();
+        cfg.cache.storage.as_mut().unwrap().lock_profiling = Some(0.0);
+        let map: Map<Entry> = Map::new(CancellationToken::new(), cfg);
+        let shard = &map.shards[0];
+        contend(shard, &entries(8));
+
+        assert_eq!(shard.lock_stats().snapshot(), LockStatsSnapshot::default());
+    }
+
+    /// Test that a rate of 0.25 samples every fourth acquisition of a thread, uncontended ones
+    /// being recorded as no wait.
+    #[test]
+    fn test_sampling_rate() {
+        let profiler = LockProfiler::new(Some(0.25)).unwrap();
+        let lock = RwLock::new(());
+        let stats = LockStats::default();
+        for _ in 0..100 {
+            drop(profiler.read(&lock, &stats));
+            drop(profiler.write(&lock, &stats));
+        }
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.read.samples + snapshot.write.samples, 50);
+        assert_eq!(snapshot.read.wait_nanos + snapshot.write.wait_nanos, 0);
+        assert_eq!(snapshot.slow_writes, 0);
+    }
+}
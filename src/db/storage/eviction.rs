@@ -5,6 +5,7 @@ use super::lock::{try_lock, try_rlock, EVICTION_LOCK_SPINS, EVICTION_RLOCK_SPINS
 use super::map::{Map, NUM_OF_SHARDS, SHARD_MASK};
 use super::mode::LRUMode;
 use super::shard::Value;
+use crate::rand;
 use std::hint;
 use std::sync::atomic::Ordering;
 
@@ -12,17 +13,17 @@ const SHARDS_SAMPLE: i64 = 4;
 const KEYS_SAMPLE: i64 = 8;
 
 impl<V: Value> Map<V> {
-    /// Evicts entries until within the specified limit.
+    /// Evicts entries until within the specified limit, passing each victim to `on_evict`.
     /// Returns (freed_bytes, evicted_count).
-    pub fn evict_until_within_limit(&self, limit: i64, backoff: i64) -> (i64, i64) {
+    pub fn evict_until_within_limit(&self, limit: i64, backoff: i64, on_evict: Option<&dyn Fn(&V)>) -> (i64, i64) {
         match self.mode {
-            LRUMode::Listing => self.evict_until_within_limit_by_list(limit, backoff),
-            LRUMode::Sampling => self.evict_until_within_limit_by_sample(limit, backoff),
+            LRUMode::Listing => self.evict_until_within_limit_by_list(limit, backoff, on_evict),
+            LRUMode::Sampling => self.evict_until_within_limit_by_sample(limit, backoff, on_evict),
         }
     }
 
     /// Evicts using LRU list (listing mode).
-    fn evict_until_within_limit_by_list(&self, limit: i64, mut backoff: i64) -> (i64, i64) {
+    fn evict_until_within_limit_by_list(&self, limit: i64, mut backoff: i64, on_evict: Option<&dyn Fn(&V)>) -> (i64, i64) {
         if !matches!(self.mode, LRUMode::Listing) {
             return (0, 0);
         }
@@ -33,12 +34,12 @@ impl<V: Value> Map<V> {
         let mut evicted = 0i64;
 
         while backoff > 0 {
-            let cur_usage = self.mem.load(Ordering::Relaxed);
+            let (cur_usage, len) = self.stat();
             // Stop if we're within limit AND haven't freed much yet, or if storage is empty
             // The condition (cur_usage <= limit && freed <= MIN_LIMIT) means:
             // - we've reached the limit AND we haven't freed much yet (early stop optimization)
             // - if we're still above limit, we continue evicting
-            if (cur_usage <= limit && freed <= MIN_LIMIT) || self.len() == 0 {
+            if (cur_usage <= limit && freed <= MIN_LIMIT) || len == 0 {
                 return (freed, evicted);
             }
 
@@ -49,10 +50,10 @@ impl<V: Value> Map<V> {
                 continue;
             }
 
-            let (bytes_freed, did_remove) = sh.evict_one_lru_tail();
-            if did_remove {
-                self.mem.fetch_sub(bytes_freed, Ordering::Relaxed);
-                self.len.fetch_sub(1, Ordering::Relaxed);
+            if let Some((bytes_freed, victim)) = sh.pop_lru_tail() {
+                if let Some(on_evict) = on_evict {
+                    on_evict(&victim);
+                }
                 freed += bytes_freed;
                 evicted += 1;
             }
@@ -63,22 +64,24 @@ impl<V: Value> Map<V> {
     }
 
     /// Evicts using sampling (sampling mode).
-    fn evict_until_within_limit_by_sample(&self, limit: i64, mut backoff: i64) -> (i64, i64) {
-        if !matches!(self.mode, LRUMode::Sampling) || self.mem() <= limit || self.len() <= 0 {
+    fn evict_until_within_limit_by_sample(&self, limit: i64, mut backoff: i64, on_evict: Option<&dyn Fn(&V)>) -> (i64, i64) {
+        let (mem, len) = self.stat();
+        if !matches!(self.mode, LRUMode::Sampling) || mem <= limit || len <= 0 {
             return (0, 0);
         }
 
         let mut freed = 0i64;
         let mut evicted = 0i64;
 
-        while self.mem.load(Ordering::Relaxed) > limit && backoff > 0 {
+        while self.mem() > limit && backoff > 0 {
             if let Some((sh, _victim)) = self.pick_victim_by_sample(SHARDS_SAMPLE, KEYS_SAMPLE) {
                 if let Some(mut data_guard) = try_lock(&sh.data, EVICTION_LOCK_SPINS) {
                     let (bytes_freed, hit) = sh.remove_unlocked(&mut data_guard, _victim.key());
                     // Guard is automatically released when dropped
                     if bytes_freed > 0 || hit {
-                        self.mem.fetch_sub(bytes_freed, Ordering::Relaxed);
-                        self.len.fetch_sub(1, Ordering::Relaxed);
+                        if let Some(on_evict) = on_evict {
+                            on_evict(&_victim);
+                        }
                         freed += bytes_freed;
                         evicted += 1;
                     }
@@ -144,7 +147,9 @@ impl<V: Value> Map<V> {
         }
     }
 
-    /// Picks a victim using sampling.
+    /// Picks a victim using sampling: the least hit of the sampled entries, the least recently
+    /// touched of those equally hit. Each sampled entry has its hit count halved, so that the
+    /// counts tell recent popularity and a once hot entry gets evictable.
     fn pick_victim_by_sample(
         &self,
         shards_sample: i64,
@@ -157,7 +162,7 @@ impl<V: Value> Map<V> {
             return None;
         }
 
-        let mut best_at: Option<i64> = None;
+        let mut best_rank: Option<(u32, i64)> = None;
         let mut best_v: Option<V> = None;
         let mut best_sh: Option<&super::Shard<V>> = None;
 
@@ -174,25 +179,27 @@ impl<V: Value> Map<V> {
             }
 
             let data = data_guard.unwrap();
-            let shard_len = sh.len();
+            let shard_len = data.items.len();
             if shard_len == 0 {
                 continue;
             }
 
-            let mut to_scan_per_shard = keys_sample.min(shard_len);
-
-            for (_, review_entry) in data.items.iter() {
-                let at = review_entry.touched_at();
-                if best_at.is_none() || best_at.unwrap() > at {
-                    best_at = Some(at);
+            // The sample starts at a random position and wraps around, so that the scans decay
+            // every entry of the shard in turn rather than the first ones in iteration order.
+            let to_scan_per_shard = (keys_sample.max(0) as usize).min(shard_len);
+            let start = (rand::float64() * shard_len as f64) as usize;
+
+            for i in 0..to_scan_per_shard {
+                let Some((_, review_entry)) = data.items.get_index((start + i) % shard_len) else {
+                    continue;
+                };
+                let rank = (review_entry.hits(), review_entry.touched_at());
+                review_entry.decay_hits();
+                if best_rank.is_none_or(|best| best > rank) {
+                    best_rank = Some(rank);
                     best_v = Some(review_entry.clone());
                     best_sh = Some(sh);
                 }
-
-                to_scan_per_shard -= 1;
-                if to_scan_per_shard <= 0 {
-                    break;
-                }
             }
         }
 
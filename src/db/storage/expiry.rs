@@ -0,0 +1,94 @@
+//! Time-bucketed index of refresh deadlines.
+//!
+//! Every shard files its keys under the bucket in which their refresh window opens, so
+//! the refresh provider pops keys of elapsed buckets instead of sampling the whole map.
+//! A record is a bare key (8 bytes); the entry itself remembers the bucket of its live
+//! record. Records left behind when an entry is refreshed, re-scheduled or removed are
+//! not searched for: they no longer match the entry and are dropped when their bucket
+//! comes up.
+
+use std::collections::BTreeMap;
+
+/// Width of a bucket in nanoseconds.
+pub const BUCKET_NANOS: i64 = 1_000_000_000;
+
+/// Buckets an entry handed out for refresh waits before it is looked at again,
+/// in case the refresh is dropped or fails.
+pub const RETRY_BUCKETS: u32 = 5;
+
+/// Bucket value of entries without a record.
+pub const UNSCHEDULED: u32 = 0;
+
+/// Bucket in which a deadline (unix nanos) falls due: the deadline rounded up to a bucket boundary.
+pub fn bucket_of(deadline: i64) -> u32 {
+    let bucket = (deadline.max(0) + BUCKET_NANOS - 1) / BUCKET_NANOS;
+    bucket.clamp(1, u32::MAX as i64) as u32
+}
+
+/// Latest bucket that has elapsed at `now` (unix nanos).
+pub fn elapsed_bucket(now: i64) -> u32 {
+    (now.max(0) / BUCKET_NANOS).min(u32::MAX as i64) as u32
+}
+
+/// Keys of one shard grouped by the bucket they fall due in.
+#[derive(Default)]
+pub struct ExpiryIndex {
+    buckets: BTreeMap<u32, Vec<u64>>,
+    records: usize,
+}
+
+impl ExpiryIndex {
+    /// Files the key under the bucket.
+    pub fn push(&mut self, bucket: u32, key: u64) {
+        self.buckets.entry(bucket).or_default().push(key);
+        self.records += 1;
+    }
+
+    /// Takes a record of the earliest bucket if that bucket has elapsed by `now_bucket`.
+    pub fn pop_due(&mut self, now_bucket: u32) -> Option<(u32, u64)> {
+        let mut first = self.buckets.first_entry()?;
+        let bucket = *first.key();
+        if bucket > now_bucket {
+            return None;
+        }
+        let key = first.get_mut().pop();
+        if first.get().is_empty() {
+            first.remove();
+        }
+        key.map(|key| {
+            self.records -= 1;
+            (bucket, key)
+        })
+    }
+
+    /// Earliest bucket holding records.
+    pub fn next_bucket(&self) -> Option<u32> {
+        self.buckets.keys().next().copied()
+    }
+
+    /// Records of the buckets elapsed by `now_bucket`, superseded ones included.
+    pub fn due(&self, now_bucket: u32) -> impl Iterator<Item = (u32, u64)> + '_ {
+        self.buckets
+            .range(..=now_bucket)
+            .flat_map(|(&bucket, keys)| keys.iter().map(move |&key| (bucket, key)))
+    }
+
+    /// Records of the buckets after `after` up to `until`, earliest first, superseded ones included.
+    pub fn between(&self, after: u32, until: u32) -> impl Iterator<Item = (u32, u64)> + '_ {
+        self.buckets
+            .range(after.saturating_add(1)..)
+            .take_while(move |(&bucket, _)| bucket <= until)
+            .flat_map(|(&bucket, keys)| keys.iter().map(move |&key| (bucket, key)))
+    }
+
+    /// Number of records, superseded ones not yet dropped included.
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.records
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.records == 0
+    }
+}
@@ -0,0 +1,216 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::config::{self, LifetimeRule, Rule};
+    use crate::db::storage::expiry::{bucket_of, elapsed_bucket, ExpiryIndex, BUCKET_NANOS};
+    use crate::db::storage::Map;
+    use crate::model::Entry;
+    use crate::time;
+
+    const TTL: Duration = Duration::from_secs(10);
+
+    /// Rule refreshing after half of a 10s TTL; the huge beta makes a due entry always ready.
+    fn rule() -> Arc<Rule> {
+        let mut rule = Rule::bare("/api/v1/user");
+        rule.refresh = Some(LifetimeRule {
+            enabled: true,
+            ttl: Some(TTL),
+            beta: Some(1000.0),
+            coefficient: Some(0.5),
+        });
+        Arc::new(rule)
+    }
+
+    fn entry(rule: &Arc<Rule>, id: usize, updated_at: i64) -> Entry {
+        let queries = vec![(b"id".to_vec(), id.to_string().into_bytes())];
+        let entry = Entry::new(rule.clone(), &queries, &[]);
+        entry.set_refreshed_at_for_tests(updated_at);
+        entry
+    }
+
+    fn map() -> Map<Entry> {
+        Map::new(CancellationToken::new(), config::new_test_config())
+    }
+
+    fn index_len(map: &Map<Entry>) -> usize {
+        map.shards.iter().map(|sh| sh.expiry_len()).sum()
+    }
+
+    /// Test that deadlines round up to the bucket in which they have surely passed.
+    #[test]
+    fn test_bucket_of() {
+        assert_eq!(bucket_of(0), 1);
+        assert_eq!(bucket_of(BUCKET_NANOS), 1);
+        assert_eq!(bucket_of(BUCKET_NANOS + 1), 2);
+        assert!(elapsed_bucket(BUCKET_NANOS * 2 - 1) < bucket_of(BUCKET_NANOS + 1));
+        assert_eq!(elapsed_bucket(BUCKET_NANOS * 2), bucket_of(BUCKET_NANOS + 1));
+    }
+
+    /// Test that records come out of elapsed buckets only, earliest bucket first.
+    #[test]
+    fn test_index_pops_elapsed_buckets_in_order() {
+        let mut index = ExpiryIndex::default();
+        index.push(7, 70);
+        index.push(3, 30);
+        index.push(5, 50);
+        assert_eq!(index.next_bucket(), Some(3));
+
+        assert_eq!(index.pop_due(5), Some((3, 30)));
+        assert_eq!(index.pop_due(5), Some((5, 50)));
+        assert_eq!(index.pop_due(5), None);
+        assert_eq!(index.len(), 1);
+        assert_eq!(index.pop_due(7), Some((7, 70)));
+        assert!(index.is_empty());
+    }
+
+    /// Test that only the expired entry is handed out and fresh ones are not looked at.
+    #[tokio::test]
+    async fn test_next_due_returns_expired_entries_only() {
+        let _clock = time::start(Duration::from_millis(1));
+        let map = map();
+        let rule = rule();
+        let now = time::unix_nano();
+        for id in 0..100 {
+            let e = entry(&rule, id, now);
+            map.set(e.key(), e);
+        }
+        let stale = entry(&rule, 1000, now - 2 * TTL.as_nanos() as i64);
+        map.set(stale.key(), stale.clone());
+
+        let got = map.next_due(32).expect("the expired entry must be due");
+        assert_eq!(got.key(), stale.key());
+        assert!(map.next_due(32).is_none(), "a handed out entry waits before it is retried");
+        assert_eq!(index_len(&map), 101);
+    }
+
+    /// Test that an entry refreshed in place moves to its new deadline instead of being handed out.
+    #[tokio::test]
+    async fn test_refreshed_entry_moves_to_new_bucket() {
+        let _clock = time::start(Duration::from_millis(1));
+        let map = map();
+        let rule = rule();
+        let stale = entry(&rule, 1, time::unix_nano() - 2 * TTL.as_nanos() as i64);
+        map.set(stale.key(), stale.clone());
+        let filed = stale.expiry_bucket();
+
+        stale.touch_refreshed_at();
+        assert!(map.next_due(32).is_none());
+        assert!(stale.expiry_bucket() > filed);
+        assert_eq!(index_len(&map), 1);
+    }
+
+    /// Test that records of removed entries and records left behind by replacements are dropped.
+    #[tokio::test]
+    async fn test_superseded_records_are_dropped() {
+        let _clock = time::start(Duration::from_millis(1));
+        let map = map();
+        let rule = rule();
+        let past = time::unix_nano() - 2 * TTL.as_nanos() as i64;
+
+        let removed = entry(&rule, 1, past);
+        map.set(removed.key(), removed.clone());
+        map.remove(removed.key());
+
+        let replaced = entry(&rule, 2, past);
+        map.set(replaced.key(), replaced.clone());
+        let replacement = entry(&rule, 2, time::unix_nano());
+        map.set(replacement.key(), replacement.clone());
+        assert_eq!(index_len(&map), 3);
+
+        assert!(map.next_due(32).is_none());
+        assert_eq!(index_len(&map), 1, "only the replacement's record is left");
+    }
+
+    /// Test that rescheduling makes an entry marked outdated due right away.
+    #[tokio::test]
+    async fn test_reschedule_after_marking_outdated() {
+        let _clock = time::start(Duration::from_millis(1));
+        let map = map();
+        let rule = rule();
+        let e = entry(&rule, 1, time::unix_nano());
+        map.set(e.key(), e.clone());
+        assert!(map.next_due(32).is_none());
+
+        e.set_refreshed_at_for_tests(time::unix_nano() - 2 * TTL.as_nanos() as i64);
+        assert!(map.next_due(32).is_none(), "the old record is not due yet");
+        map.reschedule(&e);
+        assert_eq!(map.next_due(32).map(|v| v.key()), Some(e.key()));
+    }
+
+    /// Test that the backlog counts due entries only, neither superseded records nor handed out entries.
+    #[tokio::test]
+    async fn test_refresh_backlog() {
+        let _clock = time::start(Duration::from_millis(1));
+        let map = map();
+        let rule = rule();
+        let now = time::unix_nano();
+        let past = now - 2 * TTL.as_nanos() as i64;
+        for id in 0..3 {
+            let e = entry(&rule, id, past);
+            map.set(e.key(), e);
+        }
+        let fresh = entry(&rule, 10, now);
+        map.set(fresh.key(), fresh);
+        let removed = entry(&rule, 11, past);
+        map.set(removed.key(), removed.clone());
+        map.remove(removed.key());
+        assert_eq!(map.refresh_backlog(), 3);
+
+        assert!(map.next_due(32).is_some());
+        assert_eq!(map.refresh_backlog(), 2, "a handed out entry leaves the backlog");
+    }
+
+    /// Test that records come out of the buckets after the first bound up to the second only.
+    #[test]
+    fn test_index_between() {
+        let mut index = ExpiryIndex::default();
+        for (bucket, key) in [(3, 30), (5, 50), (7, 70), (9, 90)] {
+            index.push(bucket, key);
+        }
+        assert_eq!(index.between(3, 7).collect::<Vec<_>>(), vec![(5, 50), (7, 70)]);
+        assert_eq!(index.between(9, 9).count(), 0);
+        assert_eq!(index.between(8, 2).count(), 0);
+    }
+
+    /// Test that prewarm picks entries due within the horizon, most hit first, and leaves due,
+    /// distant, queued and recently refreshed entries alone.
+    #[tokio::test]
+    async fn test_next_prewarm_prefers_hot_entries() {
+        let _clock = time::start(Duration::from_millis(1));
+        let map = map();
+        let rule = rule();
+        let now = time::unix_nano();
+        let ahead = 8 * BUCKET_NANOS;
+
+        // Due 4s from now, within the horizon.
+        let last_second = now - BUCKET_NANOS;
+        let (cold, hot, warm) = (entry(&rule, 1, last_second), entry(&rule, 2, last_second), entry(&rule, 3, last_second));
+        for _ in 0..5 {
+            hot.count_hit(false);
+        }
+        warm.count_hit(false);
+        let due = entry(&rule, 4, now - 2 * TTL.as_nanos() as i64);
+        let distant = entry(&rule, 5, now + 60 * BUCKET_NANOS);
+        let recent = entry(&rule, 6, now);
+        for _ in 0..10 {
+            due.count_hit(false);
+            distant.count_hit(false);
+            recent.count_hit(false);
+        }
+        for e in [&cold, &hot, &warm, &due, &distant, &recent] {
+            map.set(e.key(), e.clone());
+        }
+
+        let mut picked = Vec::new();
+        while let Some(e) = map.next_prewarm(ahead, now, 64) {
+            assert!(e.try_mark_refresh_queued());
+            picked.push(e.key());
+        }
+        assert_eq!(picked, vec![hot.key(), warm.key(), cold.key()]);
+        assert_eq!(map.next_prewarm(ahead, now + BUCKET_NANOS, 64).map(|e| e.key()), Some(recent.key()));
+    }
+}
@@ -1,12 +1,16 @@
 //! High-throughput, zero-allocation sharded map for in-memory cache workloads.
 
 use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 
 use crate::config::{Config, ConfigTrait};
 
+use super::contention::LockProfiler;
+use super::expiry;
 use super::mode::LRUMode;
-use super::shard::{Shard, Value};
+use super::shard::{Detached, Shard, Value};
+use super::usage::Usage;
 
 /// Number of shards in the map.
 pub const NUM_OF_SHARDS: usize = 1024;
@@ -17,18 +21,29 @@ pub struct Map<V: Value> {
     pub(crate) mode: LRUMode,
     shutdown_token: CancellationToken,
     pub(crate) cfg: Config,
-    pub(crate) len: AtomicI64,
-    pub(crate) mem: AtomicI64,
+    /// Bytes and entries of all shards, updated by each shard under its lock.
+    usage: Arc<Usage>,
     pub(crate) iter: AtomicU64,
     pub(crate) shards: Vec<Shard<V>>,
+    pending_release: AtomicI64,
+}
+
+/// Contents removed from the map by [`Map::clear`].
+pub struct Cleared<V: Value> {
+    shards: Vec<Detached<V>>,
+    /// Weight of the cleared entries.
+    pub bytes: i64,
+    pub len: i64,
 }
 
 impl<V: Value> Map<V> {
     /// Creates a new sharded map.
     pub fn new(shutdown_token: CancellationToken, cfg: Config) -> Self {
+        let usage = Arc::new(Usage::default());
+        let profiler = LockProfiler::new(cfg.storage().lock_profiling).map(Arc::new);
         let mut shards = Vec::with_capacity(NUM_OF_SHARDS);
         for id in 0..NUM_OF_SHARDS {
-            shards.push(Shard::new(id as u64));
+            shards.push(Shard::new(id as u64, usage.clone()).profiled(profiler.clone()));
         }
 
         let mode = if cfg.storage().is_listing {
@@ -41,10 +56,10 @@ impl<V: Value> Map<V> {
             mode,
             shutdown_token,
             cfg,
-            len: AtomicI64::new(0),
-            mem: AtomicI64::new(0),
+            usage,
             iter: AtomicU64::new(0),
             shards,
+            pending_release: AtomicI64::new(0),
         };
 
         // Enable/disable LRU based on mode
@@ -57,15 +72,10 @@ impl<V: Value> Map<V> {
         map
     }
 
-    /// Sets or updates a value.
+    /// Sets or updates a value and files it in the expiry index under its refresh deadline.
     pub fn set(&self, key: u64, value: V) {
-        let (bytes_delta, len_delta) = self.shard(key).set(key, value);
-        if bytes_delta != 0 {
-            self.mem.fetch_add(bytes_delta, Ordering::Relaxed);
-        }
-        if len_delta != 0 {
-            self.len.fetch_add(len_delta, Ordering::Relaxed);
-        }
+        let due = value.refresh_due_at(&self.cfg).map(expiry::bucket_of);
+        self.shard(key).set(key, value, due);
     }
 
     /// Gets a value by key.
@@ -76,18 +86,21 @@ impl<V: Value> Map<V> {
         self.shard(key).get(key)
     }
 
+    /// Gets the values indexed under the tag, one shard lock at a time.
+    pub fn tagged(&self, tag: u64) -> Vec<V>
+    where
+        V: Clone,
+    {
+        self.shards.iter().flat_map(|shard| shard.tagged(tag)).collect()
+    }
+
     /// Removes a key.
     /// Returns (freed_bytes, hit).
     pub fn remove(&self, key: u64) -> (i64, bool)
     where
         V: Clone,
     {
-        let (freed_bytes, hit) = self.shard(key).remove(key);
-        if hit {
-            self.len.fetch_sub(1, Ordering::Relaxed);
-            self.mem.fetch_sub(freed_bytes, Ordering::Relaxed);
-        }
-        (freed_bytes, hit)
+        self.shard(key).remove(key)
     }
 
     /// Walks over all shards synchronously.
@@ -140,17 +153,41 @@ impl<V: Value> Map<V> {
             .await;
     }
 
-    /// Clears all shards.
-    pub fn clear(&self) {
+    /// Empties all shards, one shard lock at a time and only for a pointer swap.
+    /// The map is empty once this returns, the old contents are handed back to be
+    /// released with [`Map::release`], typically off the request path.
+    pub fn clear(&self) -> Cleared<V> {
+        let mut cleared = Cleared {
+            shards: Vec::new(),
+            bytes: 0,
+            len: 0,
+        };
         self.walk_shards(&self.shutdown_token, |_, shard| {
-            let (freed_bytes, items) = shard.clear();
-            if freed_bytes != 0 {
-                self.mem.fetch_sub(freed_bytes, Ordering::Relaxed);
-            }
-            if items != 0 {
-                self.len.fetch_sub(items, Ordering::Relaxed);
+            let detached = shard.detach();
+            if detached.len == 0 && detached.bytes == 0 {
+                return;
             }
+            cleared.bytes += detached.bytes;
+            cleared.len += detached.len;
+            cleared.shards.push(detached);
         });
+        self.pending_release.fetch_add(cleared.bytes, Ordering::Relaxed);
+        cleared
+    }
+
+    /// Drops contents detached by [`Map::clear`] shard by shard, counting them down
+    /// from [`Map::pending_release`] as they go.
+    pub fn release(&self, cleared: Cleared<V>) {
+        for detached in cleared.shards {
+            let bytes = detached.bytes;
+            drop(detached);
+            self.pending_release.fetch_sub(bytes, Ordering::Relaxed);
+        }
+    }
+
+    /// Bytes cleared from the map but not released yet.
+    pub fn pending_release(&self) -> i64 {
+        self.pending_release.load(Ordering::Relaxed)
     }
 
     /// Gets the shard for a given key.
@@ -166,18 +203,24 @@ impl<V: Value> Map<V> {
 
     /// Gets the number of items.
     pub fn len(&self) -> i64 {
-        self.len.load(Ordering::Relaxed)
+        self.usage.load().1
     }
 
     /// Checks if the map is empty.
     #[allow(dead_code)]
     pub fn is_empty(&self) -> bool {
-        self.len.load(Ordering::Relaxed) == 0
+        self.len() == 0
     }
 
     /// Gets the total memory usage in bytes (logical weight).
     pub fn mem(&self) -> i64 {
-        self.mem.load(Ordering::Relaxed)
+        self.usage.load().0
+    }
+
+    /// Gets (bytes, length) as of a single instant: a sum of states each shard was in,
+    /// never negative and never bytes without entries or entries without bytes.
+    pub fn stat(&self) -> (i64, i64) {
+        self.usage.load()
     }
 
     /// Gets the estimated physical memory usage including overheads.
@@ -191,8 +234,7 @@ impl<V: Value> Map<V> {
     /// - Memory alignment and allocator overhead (~15-20% for fragmented allocations)
     #[allow(dead_code)]
     pub fn mem_physical(&self) -> i64 {
-        let logical_mem = self.mem.load(Ordering::Relaxed);
-        let entry_count = self.len.load(Ordering::Relaxed);
+        let (logical_mem, entry_count) = self.stat();
         
         if entry_count == 0 {
             return logical_mem;
@@ -244,10 +286,10 @@ impl<V: Value> Map<V> {
         logical_mem + fixed_overhead + allocator_overhead
     }
 
-    /// Adds to memory counter for a specific key's shard.
-    pub fn add_mem(&self, key: u64, delta: i64) {
-        self.mem.fetch_add(delta, Ordering::Relaxed);
-        self.shard(key).add_mem(delta);
+    /// Accounts for the new weight of the entry stored under `key` once its payload was
+    /// swapped in place. Returns the bytes delta.
+    pub fn reweigh(&self, key: u64) -> i64 {
+        self.shard(key).reweigh(key)
     }
 
     /// Enables listing mode (full LRU).
@@ -1,6 +1,9 @@
 //! High-throughput, zero-allocation sharded map for in-memory cache workloads.
 
+pub mod audit;
+pub mod contention;
 pub mod eviction;
+pub mod expiry;
 pub mod lock;
 pub mod lru;
 
@@ -10,11 +13,21 @@ pub mod queue;
 pub mod refresh;
 pub mod shard;
 pub mod storage;
+pub mod tags;
+pub mod usage;
 
+#[cfg(test)]
+mod contention_test;
+#[cfg(test)]
+mod expiry_test;
 #[cfg(test)]
 mod shard_test;
 #[cfg(test)]
 mod storage_test;
+#[cfg(test)]
+mod tags_test;
+#[cfg(test)]
+mod usage_test;
 
 // Re-export main types
 pub use map::Map;
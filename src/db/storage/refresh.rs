@@ -1,11 +1,13 @@
 use std::sync::atomic::Ordering;
 
+use super::expiry;
 use super::lock::{try_rlock, REFRESH_GUARD_FACTOR, REFRESH_RLOCK_SPINS};
 use super::map::{Map, NUM_OF_SHARDS, SHARD_MASK};
 use super::shard::Value;
+use crate::time;
 
 impl<V: Value> Map<V> {
-    /// Peeks at an expired entry with TTL.
+    /// Peeks at an expired entry with TTL: queued keys first, then the expiry index.
     pub fn peek_expired_ttl(&self) -> Option<V>
     where
         V: Clone,
@@ -14,7 +16,80 @@ impl<V: Value> Map<V> {
             Some(v)
         } else {
             const DEFAULT_SAMPLE: usize = 32;
-            self.peek_expired(DEFAULT_SAMPLE)
+            self.next_due(DEFAULT_SAMPLE)
+        }
+    }
+
+    /// Takes an entry ready for refresh from the expiry indexes of the shards, looking at
+    /// no more than `budget` index records. Shards without an elapsed bucket are skipped
+    /// without locking, so a miss costs one atomic load per shard however large the map is.
+    pub fn next_due(&self, budget: usize) -> Option<V>
+    where
+        V: Clone,
+    {
+        let now_bucket = expiry::elapsed_bucket(time::unix_nano());
+        let start = (self.iter.fetch_add(1, Ordering::Relaxed) & SHARD_MASK) as usize;
+        let mut budget = budget;
+
+        for i in 0..NUM_OF_SHARDS {
+            let sh = &self.shards[(start + i) & (NUM_OF_SHARDS - 1)];
+            if sh.next_due() > now_bucket {
+                continue;
+            }
+            if let Some(v) = sh.pop_due(&self.cfg, now_bucket, &mut budget) {
+                return Some(v);
+            }
+            if budget == 0 {
+                break;
+            }
+        }
+
+        None
+    }
+
+    /// Takes the most hit entry whose refresh falls due within `ahead` nanoseconds, last refreshed
+    /// before `refreshed_before` (unix nanos) and not queued for refresh yet, looking at no more
+    /// than `budget` index records. Entries already due are left to `next_due`.
+    pub fn next_prewarm(&self, ahead: i64, refreshed_before: i64, budget: usize) -> Option<V>
+    where
+        V: Clone,
+    {
+        let now = time::unix_nano();
+        let now_bucket = expiry::elapsed_bucket(now);
+        let until_bucket = expiry::elapsed_bucket(now.saturating_add(ahead));
+        let start = (self.iter.fetch_add(1, Ordering::Relaxed) & SHARD_MASK) as usize;
+        let mut budget = budget;
+        let mut best: Option<V> = None;
+
+        for i in 0..NUM_OF_SHARDS {
+            if budget == 0 {
+                break;
+            }
+            let sh = &self.shards[(start + i) & (NUM_OF_SHARDS - 1)];
+            if sh.len() == 0 || sh.next_due() > until_bucket {
+                continue;
+            }
+            if let Some(v) = sh.most_hit_due_by(now_bucket, until_bucket, refreshed_before, &mut budget) {
+                if best.as_ref().is_none_or(|b| v.hits() > b.hits()) {
+                    best = Some(v);
+                }
+            }
+        }
+
+        best
+    }
+
+    /// Number of entries past their refresh deadline still waiting to be handed out for refresh.
+    /// Takes every shard with an elapsed bucket under a read lock, so it is meant for periodic checks.
+    pub fn refresh_backlog(&self) -> usize {
+        let now_bucket = expiry::elapsed_bucket(time::unix_nano());
+        self.shards.iter().map(|sh| sh.refresh_backlog(now_bucket)).sum()
+    }
+
+    /// Files the value under its current refresh deadline, e.g. after it was marked outdated.
+    pub fn reschedule(&self, value: &V) {
+        if let Some(due) = value.refresh_due_at(&self.cfg) {
+            self.shard(value.key()).schedule(value.key(), expiry::bucket_of(due));
         }
     }
 
@@ -51,13 +126,15 @@ impl<V: Value> Map<V> {
     }
 
     /// Peeks at expired entries by sampling.
+    /// No longer on the refresh path, which reads the expiry index; kept as the baseline for benches.
     /// Uses is_probably_expired() which implements probabilistic refresh logic:
     /// - If elapsed < coefficient * ttl: returns false (no refresh)
     /// - If elapsed >= coefficient * ttl: uses probabilistic logic with beta
     ///   probability = 1 - exp(-beta * (elapsed / ttl))
     /// This allows gradual refresh starting from coefficient * ttl with low probability,
     /// preventing thundering herd while ensuring entries are refreshed before full TTL expiration.
-    fn peek_expired(&self, sample: usize) -> Option<V>
+    #[allow(dead_code)]
+    pub fn peek_expired(&self, sample: usize) -> Option<V>
     where
         V: Clone,
     {
@@ -1,27 +1,44 @@
 //! Shard implementation.
 //
 
-use parking_lot::RwLock;
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicI64, Ordering};
+use indexmap::IndexMap;
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 
 use crate::config::Config;
 use crate::model::Entry;
 
+use super::contention::{LockProfiler, LockStats};
+use super::expiry::{self, ExpiryIndex};
+use super::lock::{try_rlock, REFRESH_RLOCK_SPINS};
 use super::lru::LRUList;
 use super::queue::Queue;
+use super::tags::TagIndex;
+use super::usage::Usage;
 
 /// Value trait for items stored in the sharded map.
 /// All methods must be O(1) and allocation-free where possible.
 pub trait Value: Send + Sync + Clone {
     fn key(&self) -> u64;
     fn weight(&self) -> i64;
+    fn accounted_weight(&self) -> i64;
+    fn set_accounted_weight(&self, weight: i64);
     fn is_expired(&self, cfg: &Config) -> bool;
     fn is_probably_expired(&self, cfg: &Config) -> bool;
     fn clear_refresh_queued(&self);
+    fn is_refresh_queued(&self) -> bool;
+    fn hits(&self) -> u32;
+    /// Halves the hit count, as eviction scans do.
+    fn decay_hits(&self);
     fn touched_at(&self) -> i64;
     fn fresh_at(&self) -> i64;
+    fn refresh_due_at(&self, cfg: &Config) -> Option<i64>;
+    fn expiry_bucket(&self) -> u32;
+    fn set_expiry_bucket(&self, bucket: u32);
+    /// Hashes of the tags the value is indexed under.
+    fn tags(&self) -> Vec<u64>;
 }
 
 
@@ -35,6 +52,14 @@ impl Value for Entry {
         self.weight()
     }
 
+    fn accounted_weight(&self) -> i64 {
+        self.accounted_weight()
+    }
+
+    fn set_accounted_weight(&self, weight: i64) {
+        self.set_accounted_weight(weight)
+    }
+
     fn is_expired(&self, cfg: &Config) -> bool {
         self.is_expired(cfg)
     }
@@ -47,6 +72,18 @@ impl Value for Entry {
         self.clear_refresh_queued();
     }
 
+    fn is_refresh_queued(&self) -> bool {
+        self.is_refresh_queued()
+    }
+
+    fn hits(&self) -> u32 {
+        self.hits()
+    }
+
+    fn decay_hits(&self) {
+        self.decay_hits()
+    }
+
     fn touched_at(&self) -> i64 {
         self.touched_at()
     }
@@ -54,13 +91,47 @@ impl Value for Entry {
     fn fresh_at(&self) -> i64 {
         self.fresh_at()
     }
+
+    fn refresh_due_at(&self, cfg: &Config) -> Option<i64> {
+        self.refresh_due_at(cfg)
+    }
+
+    fn expiry_bucket(&self) -> u32 {
+        self.expiry_bucket()
+    }
+
+    fn set_expiry_bucket(&self, bucket: u32) {
+        self.set_expiry_bucket(bucket)
+    }
+
+    fn tags(&self) -> Vec<u64> {
+        self.surrogate_keys()
+    }
 }
 
 /// Shard data protected by lock.
 pub struct ShardData<V: Value> {
-    pub(crate) items: HashMap<u64, V>,
+    /// Indexed as well as hashed, so that sampling picks entries at random positions.
+    pub(crate) items: IndexMap<u64, V>,
     lru: Option<LRUList>,
     lru_on: bool,
+    expiry: ExpiryIndex,
+    tags: TagIndex,
+}
+
+/// Former contents of a shard, no longer reachable through it.
+pub struct Detached<V: Value> {
+    #[allow(dead_code)]
+    items: IndexMap<u64, V>,
+    #[allow(dead_code)]
+    lru: Option<LRUList>,
+    #[allow(dead_code)]
+    expiry: ExpiryIndex,
+    #[allow(dead_code)]
+    tags: TagIndex,
+    /// Weight of the detached items.
+    pub bytes: i64,
+    pub len: i64,
 }
 
 /// Shard is an independent segment of the sharded map.
@@ -68,25 +139,63 @@ pub struct Shard<V: Value> {
     pub(crate) data: RwLock<ShardData<V>>,
     #[allow(dead_code)]
     id: u64,
-    mem: AtomicI64,
-    len: AtomicI64,
+    /// Bytes and entries of the shard, changed under its write lock only.
+    usage: Usage,
+    /// Bytes and entries of the whole map, changed along with `usage` so that the map
+    /// totals move in the same order as the shards.
+    total: Arc<Usage>,
     rq: Queue,
+    /// Earliest bucket of the expiry index, `u32::MAX` when it is empty.
+    next_due: AtomicU32,
+    /// Samples blocking acquisitions of `data` when `storage.lock_profiling` is on.
+    profiler: Option<Arc<LockProfiler>>,
+    lock_stats: LockStats,
 }
 
 
 impl<V: Value> Shard<V> {
-    /// Creates a new shard.
-    pub fn new(id: u64) -> Self {
+    /// Creates a new shard accounting its entries into `total` as well.
+    pub fn new(id: u64, total: Arc<Usage>) -> Self {
         Self {
             data: RwLock::new(ShardData {
-                items: HashMap::new(),
+                items: IndexMap::new(),
                 lru: None,
                 lru_on: false,
+                expiry: ExpiryIndex::default(),
+                tags: TagIndex::default(),
             }),
             id,
-            mem: AtomicI64::new(0),
-            len: AtomicI64::new(0),
+            usage: Usage::default(),
+            total,
             rq: Queue::default(),
+            next_due: AtomicU32::new(u32::MAX),
+            profiler: None,
+            lock_stats: LockStats::default(),
+        }
+    }
+
+    /// Samples the waits for the shard lock with the profiler.
+    pub fn profiled(mut self, profiler: Option<Arc<LockProfiler>>) -> Self {
+        self.profiler = profiler;
+        self
+    }
+
+    /// Sampled waits for the shard lock, all zero without profiling.
+    pub fn lock_stats(&self) -> &LockStats {
+        &self.lock_stats
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, ShardData<V>> {
+        match &self.profiler {
+            None => self.data.read(),
+            Some(profiler) => profiler.read(&self.data, &self.lock_stats),
+        }
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, ShardData<V>> {
+        match &self.profiler {
+            None => self.data.write(),
+            Some(profiler) => profiler.write(&self.data, &self.lock_stats),
         }
     }
 
@@ -99,27 +208,61 @@ impl<V: Value> Shard<V> {
     /// Gets the total weight in bytes.
     #[allow(dead_code)]
     pub fn weight(&self) -> i64 {
-        self.mem.load(Ordering::Relaxed)
+        self.usage.load().0
     }
 
     /// Gets the number of items.
     pub fn len(&self) -> i64 {
-        self.len.load(Ordering::Relaxed)
+        self.usage.load().1
+    }
+
+    /// Adds to the shard and map counters; with the write lock held.
+    fn account(&self, bytes: i64, len: i64) {
+        self.usage.add(bytes, len);
+        self.total.add(bytes, len);
     }
 
-    /// Adds to memory counter.
-    pub fn add_mem(&self, delta: i64) {
-        self.mem.fetch_add(delta, Ordering::Relaxed);
+    /// Brings the accounted weight and the tags of the value stored under `key` up to date
+    /// after its payload was swapped in place, and returns the bytes delta.
+    pub fn reweigh(&self, key: u64) -> i64 {
+        let mut data = self.write();
+        let Some(value) = data.items.get(&key) else {
+            return 0;
+        };
+        // Read under the lock, so a later swap cannot be indexed before this one.
+        let tags = value.tags();
+        let weight = value.weight();
+        let mut delta = weight - value.accounted_weight();
+        value.set_accounted_weight(weight);
+        delta += data.tags.set(key, tags);
+        if delta != 0 {
+            self.account(delta, 0);
+        }
+        delta
     }
 
-    /// Sets or updates a key-value pair.
+    /// Sets or updates a key-value pair, filing it in the expiry index under `due` if given.
     /// Returns (bytes_delta, len_delta).
-    pub fn set(&self, key: u64, new_value: V) -> (i64, i64) {
-        let mut data = self.data.write();
+    pub fn set(&self, key: u64, new_value: V, due: Option<u32>) -> (i64, i64) {
+        // Decoded before taking the lock: the value is not reachable by others yet.
+        let tags = new_value.tags();
+        let mut data = self.write();
         let new_weight = new_value.weight();
+        let tags_delta = data.tags.set(key, tags);
+
+        if let Some(due) = due {
+            // A replaced value already filed under the same bucket keeps its record.
+            let filed = data.items.get(&key).map(|old| old.expiry_bucket());
+            if filed != Some(due) {
+                self.file_unlocked(&mut data, due, key);
+            }
+            new_value.set_expiry_bucket(due);
+        }
 
-        if let Some(old_value) = data.items.get(&key) {
-            let old_weight = old_value.weight();
+        // Read before stamping the new value, which may be the stored one set again.
+        let old_weight = data.items.get(&key).map(|old| old.accounted_weight());
+        new_value.set_accounted_weight(new_weight);
+        if let Some(old_weight) = old_weight {
             data.items.insert(key, new_value);
             if data.lru_on {
                 if let Some(ref mut lru) = data.lru {
@@ -127,8 +270,8 @@ impl<V: Value> Shard<V> {
                 }
             }
 
-            let bytes_delta = new_weight - old_weight;
-            self.mem.fetch_add(bytes_delta, Ordering::Relaxed);
+            let bytes_delta = new_weight - old_weight + tags_delta;
+            self.account(bytes_delta, 0);
             (bytes_delta, 0)
         } else {
             data.items.insert(key, new_value);
@@ -138,9 +281,8 @@ impl<V: Value> Shard<V> {
                 }
             }
 
-            self.len.fetch_add(1, Ordering::Relaxed);
-            self.mem.fetch_add(new_weight, Ordering::Relaxed);
-            (new_weight, 1)
+            self.account(new_weight + tags_delta, 1);
+            (new_weight + tags_delta, 1)
         }
     }
 
@@ -149,7 +291,16 @@ impl<V: Value> Shard<V> {
     where
         V: Clone,
     {
-        self.data.read().items.get(&key).cloned()
+        self.read().items.get(&key).cloned()
+    }
+
+    /// Gets the values indexed under the tag.
+    pub fn tagged(&self, tag: u64) -> Vec<V>
+    where
+        V: Clone,
+    {
+        let data = self.read();
+        data.tags.keys(tag).into_iter().filter_map(|key| data.items.get(&key).cloned()).collect()
     }
 
     /// Removes a key and returns (freed_bytes, hit).
@@ -158,7 +309,7 @@ impl<V: Value> Shard<V> {
     where
         V: Clone,
     {
-        let mut data = self.data.write();
+        let mut data = self.write();
         self.remove_unlocked(&mut data, key)
     }
 
@@ -167,37 +318,137 @@ impl<V: Value> Shard<V> {
     where
         V: Clone,
     {
-        if let Some(old_value) = data.items.remove(&key) {
+        if let Some(old_value) = data.items.swap_remove(&key) {
             if data.lru_on {
                 if let Some(ref mut lru) = data.lru {
                     lru.remove(key);
                 }
             }
-            let freed_bytes = old_value.weight();
-            self.mem.fetch_sub(freed_bytes, Ordering::Relaxed);
-            self.len.fetch_sub(1, Ordering::Relaxed);
+            let freed_bytes = old_value.accounted_weight() - data.tags.remove(key);
+            self.account(-freed_bytes, -1);
             (freed_bytes, true)
         } else {
             (0, false)
         }
     }
 
-    /// Clears all entries.
-    /// Returns (freed_bytes, items_removed).
-    pub fn clear(&self) -> (i64, i64) {
-        let mut data = self.data.write();
-        let items_count = self.len.load(Ordering::Relaxed);
-        let freed_bytes = self.mem.load(Ordering::Relaxed);
+    /// Swaps the contents for empty ones and returns the old ones.
+    /// The write lock is held for the swap only, dropping the contents is up to the caller.
+    pub fn detach(&self) -> Detached<V> {
+        let mut data = self.write();
+        let items = std::mem::take(&mut data.items);
+        let lru = data.lru.as_mut().map(std::mem::take);
+        let expiry = std::mem::take(&mut data.expiry);
+        let tags = std::mem::take(&mut data.tags);
+        self.next_due.store(u32::MAX, Ordering::Relaxed);
+        let (bytes, len) = self.usage.take();
+        self.total.add(-bytes, -len);
+        Detached { items, lru, expiry, tags, bytes, len }
+    }
 
-        data.items.clear();
-        if let Some(ref mut lru) = data.lru {
-            lru.clear();
+    /// Files the key under `due` in the expiry index, superseding its current record.
+    pub fn schedule(&self, key: u64, due: u32) {
+        let mut data = self.write();
+        if let Some(value) = data.items.get(&key) {
+            value.set_expiry_bucket(due);
+            self.file_unlocked(&mut data, due, key);
+        }
+    }
+
+    fn file_unlocked(&self, data: &mut ShardData<V>, due: u32, key: u64) {
+        data.expiry.push(due, key);
+        self.next_due.fetch_min(due, Ordering::Relaxed);
+    }
+
+    /// Earliest bucket of the expiry index, `u32::MAX` when it is empty.
+    pub fn next_due(&self) -> u32 {
+        self.next_due.load(Ordering::Relaxed)
+    }
+
+    /// Pops keys of buckets elapsed by `now_bucket` until one is found ready for refresh,
+    /// looking at no more than `budget` records (decremented by the records looked at).
+    ///
+    /// Records of removed entries and records an entry has moved away from are dropped.
+    /// Entries refreshed since they were filed move to the bucket of their new deadline;
+    /// the others go back a little later, the returned one after [`expiry::RETRY_BUCKETS`].
+    /// Gives up without waiting when the shard is locked.
+    pub fn pop_due(&self, cfg: &Config, now_bucket: u32, budget: &mut usize) -> Option<V>
+    where
+        V: Clone,
+    {
+        let mut data = self.data.try_write()?;
+        let mut found = None;
+        while *budget > 0 && found.is_none() {
+            let Some((bucket, key)) = data.expiry.pop_due(now_bucket) else {
+                break;
+            };
+            *budget -= 1;
+
+            let value = match data.items.get(&key) {
+                Some(value) if value.expiry_bucket() == bucket => value.clone(),
+                _ => continue,
+            };
+            let next = match value.refresh_due_at(cfg).map(expiry::bucket_of) {
+                None => {
+                    value.set_expiry_bucket(expiry::UNSCHEDULED);
+                    continue;
+                }
+                Some(due) if due > now_bucket => due,
+                Some(_) if value.is_probably_expired(cfg) => {
+                    found = Some(value.clone());
+                    now_bucket.saturating_add(expiry::RETRY_BUCKETS)
+                }
+                Some(_) => now_bucket.saturating_add(1),
+            };
+            value.set_expiry_bucket(next);
+            data.expiry.push(next, key);
         }
+        self.next_due
+            .store(data.expiry.next_bucket().unwrap_or(u32::MAX), Ordering::Relaxed);
+        found
+    }
+
+    /// The most hit entry filed in a bucket after `now_bucket` up to `until_bucket`, last refreshed
+    /// before `refreshed_before` and not queued for refresh, looking at no more than `budget`
+    /// records, earliest first.
+    pub fn most_hit_due_by(&self, now_bucket: u32, until_bucket: u32, refreshed_before: i64, budget: &mut usize) -> Option<V>
+    where
+        V: Clone,
+    {
+        let data = try_rlock(&self.data, REFRESH_RLOCK_SPINS)?;
+        let mut best: Option<&V> = None;
+        for (bucket, key) in data.expiry.between(now_bucket, until_bucket) {
+            if *budget == 0 {
+                break;
+            }
+            *budget -= 1;
+            let Some(value) = data.items.get(&key).filter(|v| v.expiry_bucket() == bucket) else {
+                continue;
+            };
+            if value.fresh_at() < refreshed_before && !value.is_refresh_queued() && best.is_none_or(|b| value.hits() > b.hits()) {
+                best = Some(value);
+            }
+        }
+        best.cloned()
+    }
 
-        self.len.store(0, Ordering::Relaxed);
-        self.mem.store(0, Ordering::Relaxed);
+    /// Number of entries whose refresh deadline has elapsed by `now_bucket` and that have not
+    /// been handed out for refresh yet. Superseded records are not counted.
+    pub fn refresh_backlog(&self, now_bucket: u32) -> usize {
+        if self.next_due() > now_bucket {
+            return 0;
+        }
+        let data = self.read();
+        data.expiry
+            .due(now_bucket)
+            .filter(|(bucket, key)| data.items.get(key).is_some_and(|v| v.expiry_bucket() == *bucket))
+            .count()
+    }
 
-        (freed_bytes, items_count)
+    /// Number of records in the expiry index, superseded ones not yet dropped included.
+    #[allow(dead_code)]
+    pub fn expiry_len(&self) -> usize {
+        self.read().expiry.len()
     }
 
     /// Enqueues a key for refresh.
@@ -212,7 +463,7 @@ impl<V: Value> Shard<V> {
 
     /// Enables LRU tracking.
     pub fn enable_lru(&self) {
-        let mut data = self.data.write();
+        let mut data = self.write();
         if data.lru.is_none() {
             let mut lru = LRUList::new();
             for &key in data.items.keys() {
@@ -225,7 +476,7 @@ impl<V: Value> Shard<V> {
 
     /// Disables LRU tracking.
     pub fn disable_lru(&self) {
-        let mut data = self.data.write();
+        let mut data = self.write();
         data.lru_on = false;
         if let Some(ref mut lru) = data.lru {
             lru.clear();
@@ -244,7 +495,7 @@ impl<V: Value> Shard<V> {
 
     /// Peeks at the LRU tail.
     pub fn lru_peek_tail(&self) -> Option<u64> {
-        let data = self.data.read();
+        let data = self.read();
         if data.lru_on {
             data.lru.as_ref().and_then(|lru| lru.peek_tail())
         } else {
@@ -258,11 +509,12 @@ impl<V: Value> Shard<V> {
     where
         V: Clone,
     {
-        let mut data = self.data.write();
+        let mut data = self.write();
         if data.lru_on {
             if let Some(ref mut lru) = data.lru {
                 if let Some(key) = lru.pop_tail() {
-                    if let Some(value) = data.items.remove(&key) {
+                    if let Some(value) = data.items.swap_remove(&key) {
+                        data.tags.remove(key);
                         return Some((key, value));
                     }
                 }
@@ -272,27 +524,38 @@ impl<V: Value> Shard<V> {
     }
 
     /// Pops the LRU tail and fully removes it (for eviction).
+    #[allow(dead_code)]
     pub fn evict_one_lru_tail(&self) -> (i64, bool)
     where
         V: Clone,
     {
-        let mut data = self.data.write();
+        match self.pop_lru_tail() {
+            Some((freed_bytes, _)) => (freed_bytes, true),
+            None => (0, false),
+        }
+    }
+
+    /// Removes the LRU tail and returns its weight and value.
+    pub fn pop_lru_tail(&self) -> Option<(i64, V)>
+    where
+        V: Clone,
+    {
+        let mut data = self.write();
         if !data.lru_on {
-            return (0, false);
+            return None;
         }
 
         if let Some(ref mut lru) = data.lru {
             if let Some(key) = lru.pop_tail() {
-                if let Some(old_value) = data.items.remove(&key) {
-                    let freed_bytes = old_value.weight();
-                    self.mem.fetch_sub(freed_bytes, Ordering::Relaxed);
-                    self.len.fetch_sub(1, Ordering::Relaxed);
-                    return (freed_bytes, true);
+                if let Some(old_value) = data.items.swap_remove(&key) {
+                    let freed_bytes = old_value.accounted_weight() - data.tags.remove(key);
+                    self.account(-freed_bytes, -1);
+                    return Some((freed_bytes, old_value));
                 }
             }
         }
         
-        (0, false)
+        None
     }
 
     /// Walks over items with a read lock.
@@ -304,7 +567,7 @@ impl<V: Value> Shard<V> {
         if token.is_cancelled() {
             return;
         }
-        let data = self.data.read();
+        let data = self.read();
         for (k, v) in data.items.iter() {
             if token.is_cancelled() {
                 return;
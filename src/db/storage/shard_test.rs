@@ -14,14 +14,32 @@ mod tests {
             cache_key: crate::config::RuleKey {
                 query: None,
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: None,
                 headers_map: None,
             },
             cache_value: crate::config::RuleValue {
                 headers: None,
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         });
 
         let queries = vec![];
@@ -31,19 +49,19 @@ mod tests {
 
     #[test]
     fn test_lru_pop_tail_decrements_counters() {
-        let shard: Shard<Entry> = Shard::new(0);
+        let shard: Shard<Entry> = Shard::new(0, Default::default());
         shard.enable_lru();
 
         // Add entries
         let entry1 = make_test_entry(1);
         let weight1 = entry1.weight();
-        shard.set(1, entry1);
+        shard.set(1, entry1, None);
 
         let entry2 = make_test_entry(2);
-        shard.set(2, entry2);
+        shard.set(2, entry2, None);
 
         let entry3 = make_test_entry(3);
-        shard.set(3, entry3);
+        shard.set(3, entry3, None);
 
         // Check initial state
         let initial_mem = shard.weight();
@@ -65,13 +83,13 @@ mod tests {
 
     #[test]
     fn test_lru_pop_tail() {
-        let shard: Shard<Entry> = Shard::new(0);
+        let shard: Shard<Entry> = Shard::new(0, Default::default());
         shard.enable_lru();
 
         // Add an entry
         let entry = make_test_entry(1);
         let weight = entry.weight();
-        shard.set(1, entry);
+        shard.set(1, entry, None);
 
         // Evict it
         let (freed_bytes, did_remove) = shard.evict_one_lru_tail();
@@ -83,7 +101,7 @@ mod tests {
 
     #[test]
     fn test_lru_pop_tail_empty_shard() {
-        let shard: Shard<Entry> = Shard::new(0);
+        let shard: Shard<Entry> = Shard::new(0, Default::default());
         shard.enable_lru();
 
         // Try to evict from empty shard
@@ -95,12 +113,12 @@ mod tests {
 
     #[test]
     fn test_lru_pop_tail_lru_disabled() {
-        let shard: Shard<Entry> = Shard::new(0);
+        let shard: Shard<Entry> = Shard::new(0, Default::default());
         // LRU is disabled by default
 
         // Add an entry
         let entry = make_test_entry(1);
-        shard.set(1, entry);
+        shard.set(1, entry, None);
 
         // Try to evict (should fail because LRU is disabled)
         let (freed_bytes, did_remove) = shard.evict_one_lru_tail();
@@ -111,18 +129,18 @@ mod tests {
 
     #[test]
     fn test_lru_pop_tail_evicts_oldest() {
-        let shard: Shard<Entry> = Shard::new(0);
+        let shard: Shard<Entry> = Shard::new(0, Default::default());
         shard.enable_lru();
 
         // Add entries in order
         let entry1 = make_test_entry(1);
-        shard.set(1, entry1);
+        shard.set(1, entry1, None);
 
         let entry2 = make_test_entry(2);
-        shard.set(2, entry2);
+        shard.set(2, entry2, None);
 
         let entry3 = make_test_entry(3);
-        shard.set(3, entry3);
+        shard.set(3, entry3, None);
 
         // First eviction should remove entry1 (oldest)
         let (_, did_remove1) = shard.evict_one_lru_tail();
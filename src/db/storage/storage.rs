@@ -1,21 +1,69 @@
 //! In-memory LRU storage implementation.
 
 use anyhow::Result;
-use std::sync::atomic::Ordering;
+use arc_swap::ArcSwap;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio_util::sync::CancellationToken;
 
-use crate::config::{Config, ConfigTrait};
-use crate::model::Entry;
+use crate::config::{Config, ConfigTrait, MemoryLimits};
+use crate::controller::metrics;
+use crate::dedlog;
+use crate::model::timestamps::{DEFAULT_ACCESS_TIME_GRANULARITY, DEFAULT_MAX_CLOCK_SKEW};
+use crate::model::{checksum, Entry};
+use crate::rand;
 use crate::db::admission::Admission;
+use crate::db::capture::{self, Op};
+use super::audit::{EvictionAudit, EvictionReason};
 use super::Map;
-use crate::upstream::Upstream;
+use crate::upstream::{digest, Upstream};
 
 use crate::db::log::logger;
 
 const SHARDS_SAMPLE: i64 = 2;
 const KEYS_SAMPLE: i64 = 8;
 const SPINS_BACKOFF: i64 = 32;
+/// Expiry index records looked at per prewarm pick.
+const PREWARM_BUDGET: usize = 256;
+/// Further attempts at swapping in a refreshed payload held back by the hard memory limit.
+const REFRESH_APPLY_RETRIES: u32 = 3;
+/// Wait before the first of those attempts, doubled for each next one.
+const REFRESH_APPLY_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Why a refreshed payload was thrown away instead of swapped in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshDiscard {
+    /// The entry was evicted or removed while its refresh was in flight.
+    Gone = 0,
+    /// Swapping it in would have kept the storage over its hard memory limit, retries included.
+    Memory = 1,
+    /// The origin answered `no-store` to a rule following its `Cache-Control`: the entry is removed.
+    NoStore = 2,
+}
+
+impl RefreshDiscard {
+    pub const ALL: [RefreshDiscard; 3] = [RefreshDiscard::Gone, RefreshDiscard::Memory, RefreshDiscard::NoStore];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RefreshDiscard::Gone => "gone",
+            RefreshDiscard::Memory => "memory",
+            RefreshDiscard::NoStore => "no_store",
+        }
+    }
+}
+
+/// Outcome of one attempt at swapping in a refreshed payload.
+enum RefreshApply {
+    Applied,
+    /// Same response as the resident one: only its refresh time was touched.
+    Unchanged,
+    /// The origin answered 304: only the resident entry's refresh time was touched.
+    NotModified,
+    Discarded(RefreshDiscard),
+}
 
 /// In-memory LRU storage.
 pub struct Storage {
@@ -23,12 +71,48 @@ pub struct Storage {
     cfg: Config,
     upstream: Arc<dyn Upstream>,
     admitter: Arc<dyn Admission>,
-    soft_memory_limit: i64,
-    hard_memory_limit: i64,
-    admission_memory_limit: i64,
+    /// Memory limits, swapped whole by a config reload or a resize.
+    limits: ArcSwap<MemoryLimits>,
+    /// What the limits are derived from; held while they are swapped.
+    sizing: Mutex<Sizing>,
+    /// While a shrink is being evicted down, the hard limit is held at the bytes still stored,
+    /// so sets do not evict the whole difference at once; soft eviction passes lower it.
+    hard_limit_floor: AtomicI64,
+    strict_ttl: bool,
+    verify_sample: Option<f64>,
+    max_clock_skew: Duration,
+    eviction_audit: Option<Arc<EvictionAudit>>,
+    /// Whether hits are counted per entry, for the sampling eviction, prewarm ordering and the
+    /// eviction audit.
+    count_hits: bool,
+    /// Whether the hit counters of entries count one hit in four (`storage.sample_hits`).
+    sample_hits: bool,
+    /// Nanos the access time of an entry gets old before a hit writes it again; `None` leaves
+    /// hits without writing it (`storage.track_access_time`).
+    access_time_granularity: Option<i64>,
+    /// Bytes reserved by sets of new keys that passed admission and are not written yet.
+    in_flight_bytes: AtomicI64,
     shareded_hash_map: Arc<Map<Entry>>,
 }
 
+/// Limits of the config and the size set at runtime over them.
+struct Sizing {
+    configured: MemoryLimits,
+    resized: Option<i64>,
+}
+
+/// Bytes of a set in progress, counted against the admission budget until dropped.
+struct Reservation<'a> {
+    in_flight_bytes: &'a AtomicI64,
+    bytes: i64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.in_flight_bytes.fetch_sub(self.bytes, Ordering::AcqRel);
+    }
+}
+
 impl Storage {
     /// Creates a new in-memory storage.
     pub fn new(
@@ -40,14 +124,36 @@ impl Storage {
         let admitter_box = crate::db::admission::new_admission(cfg.admission());
         let admitter = Arc::from(admitter_box);
 
+        let verify_sample = cfg.storage().verify_sample;
+        if verify_sample.is_some() {
+            checksum::enable();
+        }
+
+        let eviction_audit = EvictionAudit::start(
+            cfg.eviction().and_then(|e| e.audit.as_ref()),
+            shutdown_token.clone(),
+        );
+
         let storage = Arc::new(Self {
             shutdown_token: shutdown_token.clone(),
             cfg: cfg.clone(),
             upstream,
             admitter,
-            soft_memory_limit: cfg.storage().soft_memory_limit,
-            hard_memory_limit: cfg.storage().hard_memory_limit,
-            admission_memory_limit: cfg.storage().admission_memory_limit,
+            limits: ArcSwap::from_pointee(cfg.storage().memory_limits()),
+            sizing: Mutex::new(Sizing { configured: cfg.storage().memory_limits(), resized: None }),
+            hard_limit_floor: AtomicI64::new(0),
+            strict_ttl: cfg.lifetime().and_then(|l| l.strict_ttl).unwrap_or(false),
+            verify_sample,
+            max_clock_skew: cfg.storage().max_clock_skew.unwrap_or(DEFAULT_MAX_CLOCK_SKEW),
+            count_hits: !cfg.storage().is_listing
+                || eviction_audit.is_some()
+                || cfg.lifetime().is_some_and(|l| l.prewarm.is_some()),
+            sample_hits: cfg.storage().sample_hits.unwrap_or(false),
+            access_time_granularity: cfg.storage().track_access_time.unwrap_or(false).then(|| {
+                cfg.storage().access_time_granularity.unwrap_or(DEFAULT_ACCESS_TIME_GRANULARITY).as_nanos() as i64
+            }),
+            eviction_audit,
+            in_flight_bytes: AtomicI64::new(0),
             shareded_hash_map: sharded_map,
         });
 
@@ -62,12 +168,15 @@ impl Storage {
             let smap = storage_clone.shareded_hash_map.clone();
             move || smap.len()
         });
+        let limits_fn: Arc<dyn Fn() -> MemoryLimits + Send + Sync> = Arc::new({
+            let storage = storage_clone.clone();
+            move || storage.memory_limits()
+        });
         tokio::task::spawn(async move {
             logger::logger(
                 shutdown_token,
                 cfg_arc,
-                storage_clone.soft_memory_limit,
-                storage_clone.hard_memory_limit,
+                limits_fn,
                 mem_fn,
                 len_fn,
             )
@@ -82,44 +191,123 @@ impl Storage {
         self.shareded_hash_map.get(key)
     }
 
-    /// Gets an entry matching the request.
+    /// Gets an entry matching the request, as for a request carrying no headers.
     pub fn get(&self, req: &Entry) -> (Option<Entry>, bool) {
-        if let Some(ptr) = self.shareded_hash_map.get(req.key()) {
-            if ptr.is_the_same_fingerprint(req) {
-                self.touch(&ptr);
-                return (Some(ptr), true);
+        self.get_variant(req, &[])
+    }
+
+    /// Gets the entry matching the request and its headers: when the entry of the key holds a
+    /// response that varies on request headers and the request's values of them differ, the
+    /// sibling entry of the request's values answers instead, if stored.
+    /// With `lifetime.strict_ttl` in remove mode, an entry past its TTL is removed and returned
+    /// as a miss, so TTL holds at read time whatever the lifetime manager's lag. Negative
+    /// entries are, whatever the mode.
+    /// With `storage.verify_sample`, a sampled entry whose payload no longer matches its checksum
+    /// is dropped and returned as a miss, so the request re-fills it from upstream.
+    pub fn get_variant(&self, req: &Entry, request_headers: &[(&str, &str)]) -> (Option<Entry>, bool) {
+        let found = self.shareded_hash_map.get(req.key()).filter(|ptr| ptr.is_the_same_fingerprint(req));
+        let found = match found.as_ref().and_then(Entry::variant) {
+            Some(variant) if !variant.matches(request_headers) => {
+                let sibling = req.variant_of_key(&variant.of_request(request_headers));
+                let found = self.shareded_hash_map.get(sibling.key()).filter(|ptr| ptr.is_the_same_fingerprint(&sibling));
+                if found.is_none() {
+                    capture::record(req.key(), Op::Get, "miss: no entry of the request's variant");
+                    return (None, false);
+                }
+                found
+            }
+            _ => found,
+        };
+        if let Some(ptr) = found {
+            if self.verify_sample.is_some_and(|s| s >= 1.0 || rand::float64() < s) && !ptr.verify_checksum() {
+                self.drop_corrupted(&ptr);
+                capture::record(req.key(), Op::Get, "miss: checksum mismatch, entry dropped");
+                return (None, false);
+            }
+            if (ptr.is_negative() || (self.strict_ttl && self.is_remove_on_ttl())) && ptr.is_past_ttl(&self.cfg) {
+                self.remove(&ptr);
+                capture::record(req.key(), Op::Get, "miss: past its TTL, entry removed");
+                return (Some(ptr), false);
+            }
+            if self.count_hits {
+                ptr.count_hit(self.sample_hits);
             }
+            if let Some(granularity) = self.access_time_granularity {
+                ptr.touch_coarse(granularity);
+            }
+            self.touch(&ptr);
+            capture::record(req.key(), Op::Get, "hit");
+            return (Some(ptr), true);
         }
+        capture::record(req.key(), Op::Get, "miss");
         (None, false)
     }
 
-    /// Sets or updates an entry.
+    /// Sets or updates an entry. A response varying on request headers is set under the
+    /// primary key unless that holds another variant, under its sibling key then.
     pub fn set(&self, new: Entry) -> bool {
+        let new = match new.variant() {
+            Some(variant) => {
+                let other_variant = self
+                    .shareded_hash_map
+                    .get(new.key())
+                    .filter(|primary| primary.is_the_same_fingerprint(&new))
+                    .and_then(|primary| primary.variant())
+                    .is_some_and(|primary| primary != variant);
+                if other_variant {
+                    new.as_sibling(&variant)
+                } else {
+                    new
+                }
+            }
+            None => new,
+        };
         let key = new.key();
+        // Stored entries get stamped with the local clock, but one arriving stamped far ahead
+        // (a dump written by a node whose clock runs ahead) is clamped first, so the skewed
+        // source is counted and logged whichever path the entry takes below.
+        new.clamp_future_refreshed_at("set", self.max_clock_skew);
         self.admitter.record(key);
 
         if let Some(old) = self.shareded_hash_map.get(key) {
             if old.is_the_same_fingerprint(&new) {
+                if new.is_no_store() {
+                    self.remove(&old);
+                    capture::record(key, Op::Set, "no-store: stored entry removed");
+                    return false;
+                }
                 if old.is_the_same_payload(&new) {
+                    old.touch();
+                    old.take_origin_ttl(&new);
                     self.touch(&old);
+                    capture::record(key, Op::Set, "same payload: entry touched");
                     return true;
                 } else {
                     self.update(&old, &new);
+                    capture::record(key, Op::Set, "payload updated");
                     return true;
                 }
             }
         }
 
-        if self.admission_memory_limit_overcome() {
-            if let Some((_sh, victim)) = self.shareded_hash_map.pick_victim(SHARDS_SAMPLE, KEYS_SAMPLE) {
-                if !self.admitter.allow(key, victim.key()) {
+        if new.is_no_store() {
+            capture::record(key, Op::Set, "no-store: not stored");
+            return false;
+        }
+
+        // Held until the entry is in the map, so concurrent sets see each other's bytes.
+        let _reservation = if self.is_admission_enabled() {
+            match self.reserve(key, new.weight()) {
+                Some(reservation) => Some(reservation),
+                None => {
                     logger::ADMISSION_NOT_ALLOWED.fetch_add(1, Ordering::Relaxed);
+                    capture::record(key, Op::Set, "refused by admission");
                     return false;
-                } else {
-                    logger::ADMISSION_ALLOWED.fetch_add(1, Ordering::Relaxed);
                 }
             }
-        }
+        } else {
+            None
+        };
 
         if self.hard_memory_limit_overcome() {
             let (freed_bytes, items) = self.hard_evict_until_within_limit();
@@ -129,17 +317,29 @@ impl Storage {
             }
         }
 
+        new.touch();
         new.touch_refreshed_at();
         self.shareded_hash_map.set(key, new);
+        capture::record(key, Op::Set, "stored");
         true
     }
 
-    /// Touches an existing entry (updates access time).
+    /// Removes an entry whose payload failed checksum verification.
+    fn drop_corrupted(&self, entry: &Entry) {
+        self.remove(entry);
+        metrics::inc_cache_entries_corrupted();
+        let key = entry.key().to_string();
+        dedlog::err("storage", None, Some(&key), "payload checksum mismatch, entry dropped");
+    }
+
+    /// Touches an existing entry: moves it up the LRU and queues it for refresh once expired.
+    /// Its access time is written by the caller.
     fn touch(&self, existing: &Entry) {
-        existing.touch();
         self.shareded_hash_map.touch(existing.key());
         if existing.is_expired(&self.cfg) && existing.try_mark_refresh_queued() {
-            if !self.shareded_hash_map.enqueue_expired(existing.key()) {
+            if self.shareded_hash_map.enqueue_expired(existing.key()) {
+                capture::record(existing.key(), Op::RefreshPick, "expired: queued for refresh");
+            } else {
                 existing.clear_refresh_queued();
             }
         }
@@ -147,52 +347,178 @@ impl Storage {
 
     /// Updates an existing entry with new payload.
     fn update(&self, existing: &Entry, in_entry: &Entry) {
-        let bytes_delta = existing.swap_payloads(in_entry);
-        self.shareded_hash_map.add_mem(existing.key(), bytes_delta);
+        existing.swap_payloads(in_entry);
+        self.shareded_hash_map.reweigh(existing.key());
         existing.touch();
         existing.touch_refreshed_at();
         existing.clear_refresh_queued();
+        self.reschedule_on_origin_ttl(existing);
         self.shareded_hash_map.touch(existing.key());
     }
 
+    /// Re-files an entry whose payload came with an origin TTL under the deadline it sets,
+    /// which the expiry index would otherwise only notice at the deadline it had before.
+    fn reschedule_on_origin_ttl(&self, entry: &Entry) {
+        if entry.origin_ttl().is_some() {
+            self.shareded_hash_map.reschedule(entry);
+        }
+    }
+
+    /// Whether expired entries are removed rather than refreshed (switchable at runtime).
+    fn is_remove_on_ttl(&self) -> bool {
+        self.cfg
+            .lifetime()
+            .map(|l| l.is_remove_on_ttl.load(Ordering::Relaxed))
+            .unwrap_or(false)
+    }
+
     /// Handles TTL expiration (internal implementation).
     async fn on_ttl_internal(
         &self,
         entry: &Entry,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        if self
-            .cfg
-            .lifetime()
-            .map(|l| l.is_remove_on_ttl.load(Ordering::Relaxed))
-            .unwrap_or(false)
-        {
+        let key = entry.key();
+        if entry.is_negative() {
+            // Negative entries are not refreshed, only removed past their TTL: a prewarm may
+            // pick one ahead of it.
+            if entry.is_past_ttl(&self.cfg) {
+                self.remove(entry);
+                capture::record(key, Op::RefreshResult, "negative entry removed on TTL");
+            } else {
+                entry.clear_refresh_queued();
+            }
+            Ok(())
+        } else if self.is_remove_on_ttl() {
             self.remove(entry);
+            capture::record(key, Op::RefreshResult, "removed on TTL");
             Ok(())
         } else {
-            // Capture weight before refresh to calculate delta
-            let old_weight = entry.weight();
-            
-            self.upstream.refresh(entry).await.map_err(
+            let refreshed = self.upstream.refresh(entry).await.map_err(
                 |e| -> Box<dyn std::error::Error + Send + Sync> {
+                    capture::record(key, Op::RefreshResult, format_args!("error: {}", e));
                     Box::new(std::io::Error::new(
                         std::io::ErrorKind::Other,
                         format!("{}", e),
                     ))
                 },
             )?;
-            
-            // Update memory counter after payload change
-            // weight() uses capacity(), which may change after set_payload()
-            let new_weight = entry.weight();
-            let bytes_delta = new_weight - old_weight;
-            if bytes_delta != 0 {
-                self.shareded_hash_map.add_mem(entry.key(), bytes_delta);
+            let Some(refreshed) = refreshed.modified() else {
+                match self.apply_not_modified(entry) {
+                    RefreshApply::NotModified => {
+                        metrics::inc_refresh_not_modified();
+                        capture::record(key, Op::RefreshResult, "not modified");
+                    }
+                    _ => {
+                        metrics::inc_refresh_discarded(RefreshDiscard::Gone);
+                        capture::record(key, Op::RefreshResult, "discarded: gone");
+                    }
+                }
+                entry.clear_refresh_queued();
+                return Ok(());
+            };
+
+            // Held back by the hard memory limit, the old payload stays and the swap is tried
+            // again with backoff, keeping this refresh worker busy meanwhile: the upstream call
+            // is not wasted and refreshes slow down while memory is short.
+            let mut backoff = REFRESH_APPLY_BACKOFF;
+            let mut outcome = self.apply_refresh(&refreshed);
+            for _ in 0..REFRESH_APPLY_RETRIES {
+                if !matches!(outcome, RefreshApply::Discarded(RefreshDiscard::Memory)) {
+                    break;
+                }
+                tokio::select! {
+                    _ = self.shutdown_token.cancelled() => break,
+                    _ = tokio::time::sleep(backoff) => {}
+                }
+                backoff *= 2;
+                outcome = self.apply_refresh(&refreshed);
+            }
+
+            match outcome {
+                RefreshApply::Applied => {
+                    metrics::inc_refresh_applied();
+                    capture::record(key, Op::RefreshResult, "applied");
+                    Ok(())
+                }
+                RefreshApply::Unchanged => {
+                    metrics::inc_refresh_unchanged();
+                    capture::record(key, Op::RefreshResult, "unchanged");
+                    Ok(())
+                }
+                RefreshApply::NotModified => {
+                    metrics::inc_refresh_not_modified();
+                    capture::record(key, Op::RefreshResult, "not modified");
+                    Ok(())
+                }
+                RefreshApply::Discarded(reason) => {
+                    metrics::inc_refresh_discarded(reason);
+                    capture::record(key, Op::RefreshResult, format_args!("discarded: {}", reason.label()));
+                    entry.clear_refresh_queued();
+                    match reason {
+                        RefreshDiscard::Gone | RefreshDiscard::NoStore => Ok(()),
+                        RefreshDiscard::Memory => Err("refreshed payload discarded: over the hard memory limit".into()),
+                    }
+                }
             }
-            
-            Ok(())
         }
     }
 
+    /// Swaps a refreshed payload into the resident entry of its key. The key is not a new one,
+    /// so admission is not consulted, as for any set of a resident key: only the hard memory
+    /// limit holds back a payload that grew. Nothing is evicted to make room, that would pick
+    /// among entries as hot as the refreshed one; the evictor does it meanwhile. A payload
+    /// with the identity hash of the resident one is not swapped in at all.
+    fn apply_refresh(&self, refreshed: &Entry) -> RefreshApply {
+        let key = refreshed.key();
+        let Some(resident) = self.shareded_hash_map.get(key).filter(|r| r.is_the_same_fingerprint(refreshed)) else {
+            return RefreshApply::Discarded(RefreshDiscard::Gone);
+        };
+        if refreshed.is_no_store() {
+            self.remove(&resident);
+            return RefreshApply::Discarded(RefreshDiscard::NoStore);
+        }
+        let unchanged = digest::stored_identity_hash(refreshed)
+            .is_some_and(|hash| digest::stored_identity_hash(&resident) == Some(hash));
+        if unchanged {
+            resident.take_origin_ttl(refreshed);
+            resident.touch_refreshed_at();
+            resident.clear_refresh_queued();
+            self.reschedule_on_origin_ttl(&resident);
+            return RefreshApply::Unchanged;
+        }
+        let growth = refreshed.weight() - resident.weight();
+        if growth > 0 && self.shareded_hash_map.mem() + growth > self.hard_limit() {
+            return RefreshApply::Discarded(RefreshDiscard::Memory);
+        }
+
+        resident.swap_payloads(refreshed);
+        // Account for the new payload under the shard lock, so a concurrent removal takes
+        // back the weight that was accounted, not the new one.
+        self.shareded_hash_map.reweigh(key);
+        resident.touch_refreshed_at();
+        resident.clear_refresh_queued();
+        self.reschedule_on_origin_ttl(&resident);
+        RefreshApply::Applied
+    }
+
+    /// Keeps the resident entry of a key whose origin answered 304 to its validators, touching
+    /// only its refresh time, as for a refresh that brought the same response.
+    fn apply_not_modified(&self, entry: &Entry) -> RefreshApply {
+        let Some(resident) = self.shareded_hash_map.get(entry.key()).filter(|r| r.is_the_same_fingerprint(entry)) else {
+            return RefreshApply::Discarded(RefreshDiscard::Gone);
+        };
+        resident.touch_refreshed_at();
+        resident.clear_refresh_queued();
+        self.reschedule_on_origin_ttl(&resident);
+        RefreshApply::NotModified
+    }
+
+    /// Moves the entry's refresh timestamp into the past and re-files it in the expiry index.
+    pub fn mark_outdated(&self, entry: &Entry) {
+        entry.untouch_refreshed_at();
+        self.shareded_hash_map.reschedule(entry);
+    }
+
     /// Gets the number of entries.
     #[allow(dead_code)]
     pub fn len(&self) -> i64 {
@@ -207,12 +533,80 @@ impl Storage {
 
     /// Gets statistics (bytes, length).
     pub fn stat(&self) -> (i64, i64) {
-        (self.shareded_hash_map.mem(), self.shareded_hash_map.len())
+        self.shareded_hash_map.stat()
+    }
+
+    /// Memory limits in effect.
+    pub fn memory_limits(&self) -> MemoryLimits {
+        **self.limits.load()
+    }
+
+    /// Whether hits are counted: by the sampling eviction, prewarm and the eviction audit.
+    pub fn counts_hits(&self) -> bool {
+        self.count_hits
+    }
+
+    /// Takes the memory limits of a reloaded config; entries above them leave with eviction.
+    /// A size set by [`Storage::resize`] stays, under the reloaded eviction thresholds.
+    pub fn set_memory_limits(&self, storage: &crate::config::Storage) {
+        let mut sizing = self.sizing.lock();
+        sizing.configured = storage.memory_limits();
+        self.limits.store(Arc::new(self.sized(&sizing)));
+    }
+
+    /// Sets the storage size, `None` going back to the configured one, and returns the limits
+    /// taken. They are swapped at once; entries above them leave with eviction.
+    pub fn resize(&self, size: Option<i64>) -> MemoryLimits {
+        let mut sizing = self.sizing.lock();
+        sizing.resized = size;
+        let limits = self.sized(&sizing);
+        let mem = self.shareded_hash_map.mem();
+        self.hard_limit_floor.store(if mem > limits.hard { mem } else { 0 }, Ordering::Relaxed);
+        self.limits.store(Arc::new(limits));
+        limits
     }
 
-    /// Clears all entries.
-    pub fn clear(&self) {
-        self.shareded_hash_map.clear();
+    /// Storage size set at runtime, if any.
+    pub fn resized(&self) -> Option<i64> {
+        self.sizing.lock().resized
+    }
+
+    fn sized(&self, sizing: &Sizing) -> MemoryLimits {
+        match sizing.resized {
+            Some(size) => MemoryLimits::derive(size, self.cfg.eviction()),
+            None => sizing.configured,
+        }
+    }
+
+    /// Clears all entries and returns the cleared (bytes, length).
+    /// Shards are swapped for empty ones, their old contents are dropped on a blocking
+    /// task so a large cache does not stall requests while it is being freed.
+    pub fn clear(&self) -> (i64, i64) {
+        let cleared = self.shareded_hash_map.clear();
+        let stat = (cleared.bytes, cleared.len);
+        let map = self.shareded_hash_map.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(rt) => {
+                rt.spawn_blocking(move || map.release(cleared));
+            }
+            Err(_) => map.release(cleared),
+        }
+        stat
+    }
+
+    /// Bytes of cleared entries that are still being released.
+    pub fn clear_pending_bytes(&self) -> i64 {
+        self.shareded_hash_map.pending_release()
+    }
+
+    /// Number of entries past their refresh deadline not picked up by the refresher yet.
+    pub fn refresh_backlog(&self) -> usize {
+        self.shareded_hash_map.refresh_backlog()
+    }
+
+    /// Entries whose response carries the tag (see `model::surrogate`).
+    pub fn tagged(&self, tag: u64) -> Vec<Entry> {
+        self.shareded_hash_map.tagged(tag)
     }
 
     /// Removes an entry.
@@ -241,40 +635,110 @@ impl Storage {
     }
 
     /// Evicts entries until within soft limit.
+    /// A pass lowers the hard limit held up by a shrink to the bytes left.
     pub fn soft_evict_until_within_limit(&self, backoff: i64) -> (i64, i64) {
-        self.shareded_hash_map
-            .evict_until_within_limit(self.soft_memory_limit, backoff)
+        let evicted = self.evict_until_within_limit(self.limits.load().soft, backoff, EvictionReason::Soft);
+        let _ = self.hard_limit_floor.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |floor| {
+            let mem = self.shareded_hash_map.mem();
+            (floor > 0).then(|| if mem > self.limits.load().hard { floor.min(mem) } else { 0 })
+        });
+        evicted
     }
 
     /// Evicts entries until within hard limit.
     fn hard_evict_until_within_limit(&self) -> (i64, i64) {
-        self.shareded_hash_map
-            .evict_until_within_limit(self.hard_memory_limit, SPINS_BACKOFF)
+        self.evict_until_within_limit(self.hard_limit(), SPINS_BACKOFF, EvictionReason::Hard)
+    }
+
+    /// Hard memory limit sets evict down to, held up while a shrink is being evicted.
+    fn hard_limit(&self) -> i64 {
+        self.limits.load().hard.max(self.hard_limit_floor.load(Ordering::Relaxed))
+    }
+
+    /// Evicts entries until within `limit`, victims passed to the audit and the debug capture
+    /// when either looks at them.
+    fn evict_until_within_limit(&self, limit: i64, backoff: i64, reason: EvictionReason) -> (i64, i64) {
+        let audit = self.eviction_audit.as_deref();
+        if audit.is_none() && capture::watchlist().is_empty() {
+            return self.shareded_hash_map.evict_until_within_limit(limit, backoff, None);
+        }
+        let on_evict = |victim: &Entry| {
+            if let Some(audit) = audit {
+                audit.observe(victim, reason);
+            }
+            capture::record(victim.key(), Op::Evict, format_args!("evicted over the {} memory limit", reason.label()));
+        };
+        self.shareded_hash_map.evict_until_within_limit(limit, backoff, Some(&on_evict))
     }
 
     /// Peeks at an expired entry with TTL.
     pub fn peek_expired_ttl(&self) -> Option<Entry> {
-        self.shareded_hash_map.peek_expired_ttl()
+        let entry = self.shareded_hash_map.peek_expired_ttl()?;
+        capture::record(entry.key(), Op::RefreshPick, "picked by the refresher");
+        Some(entry)
     }
 
     /// Checks if soft memory limit is exceeded.
     pub fn soft_memory_limit_overcome(&self) -> bool {
-        self.shareded_hash_map.len() > 0 && self.shareded_hash_map.mem() - self.soft_memory_limit > 0
+        self.shareded_hash_map.len() > 0 && self.shareded_hash_map.mem() - self.limits.load().soft > 0
     }
 
     /// Checks if hard memory limit is exceeded.
     fn hard_memory_limit_overcome(&self) -> bool {
-        self.shareded_hash_map.len() > 0 && self.shareded_hash_map.mem() - self.hard_memory_limit > 0
+        self.shareded_hash_map.len() > 0 && self.shareded_hash_map.mem() - self.hard_limit() > 0
     }
 
-    /// Checks if admission memory limit is exceeded.
-    fn admission_memory_limit_overcome(&self) -> bool {
+    fn is_admission_enabled(&self) -> bool {
         self.cfg
             .admission()
             .map(|a| a.is_enabled.load(Ordering::Relaxed))
             .unwrap_or(false)
+    }
+
+    /// Checks if admission memory limit is exceeded.
+    fn admission_memory_limit_overcome(&self) -> bool {
+        self.is_admission_enabled()
             && self.shareded_hash_map.len() > 0
-            && self.shareded_hash_map.mem() - self.admission_memory_limit > 0
+            && self.shareded_hash_map.mem() + self.in_flight_bytes.load(Ordering::Acquire) - self.limits.load().admission > 0
+    }
+
+    /// Reserves `weight` bytes of a new key against the admission budget: stored bytes, the
+    /// reservations of concurrent sets and the key's own weight must fit `admission_memory_limit`,
+    /// so a burst of fills never stores past it. A key that does not fit is stored only if it
+    /// wins TinyLFU over a sampled victim whose bytes make room for it; the victim is evicted
+    /// first, counted and audited as an admission eviction. Returns `None` when the set is refused.
+    fn reserve(&self, key: u64, weight: i64) -> Option<Reservation<'_>> {
+        let others = self.in_flight_bytes.fetch_add(weight, Ordering::AcqRel);
+        let reservation = Reservation {
+            in_flight_bytes: &self.in_flight_bytes,
+            bytes: weight,
+        };
+        let limit = self.limits.load().admission;
+        let fits = |freed: i64| self.shareded_hash_map.mem() + others + weight - freed <= limit;
+        if fits(0) {
+            return Some(reservation);
+        }
+
+        let (_sh, victim) = self.shareded_hash_map.pick_victim(SHARDS_SAMPLE, KEYS_SAMPLE)?;
+        if !fits(victim.weight()) || !self.admitter.allow(key, victim.key()) {
+            capture::record(victim.key(), Op::Evict, "kept: the new key was refused");
+            return None;
+        }
+        let (freed_bytes, removed) = self.remove(&victim);
+        // Someone else took the victim first: its bytes are already gone from mem().
+        if !removed && !fits(0) {
+            return None;
+        }
+        if removed {
+            if let Some(audit) = self.eviction_audit.as_deref() {
+                audit.observe(&victim, EvictionReason::Admission);
+            }
+            capture::record(victim.key(), Op::Evict, "evicted: lost admission to a new key");
+            logger::EVICTED_HARD_LIMIT_ITEMS.fetch_add(1, Ordering::Relaxed);
+            logger::EVICTED_HARD_LIMIT_BYTES.fetch_add(freed_bytes, Ordering::Relaxed);
+        }
+        logger::ADMISSION_ALLOWED.fetch_add(1, Ordering::Relaxed);
+        Some(reservation)
     }
 }
 
@@ -293,6 +757,14 @@ impl crate::workers::RefreshBackend for Storage {
         self.peek_expired_ttl()
     }
 
+    fn peek_prewarm(&self, ahead: Duration, refreshed_before: i64) -> Option<Entry> {
+        let entry = self
+            .shareded_hash_map
+            .next_prewarm(ahead.as_nanos() as i64, refreshed_before, PREWARM_BUDGET)?;
+        capture::record(entry.key(), Op::RefreshPick, "picked for prewarm");
+        Some(entry)
+    }
+
     async fn on_ttl(
         &self,
         entry: &Entry,
@@ -327,6 +799,10 @@ impl crate::db::Storage for Storage {
         self.get(entry)
     }
 
+    fn get_variant(&self, entry: &Entry, request_headers: &[(&str, &str)]) -> (Option<Entry>, bool) {
+        self.get_variant(entry, request_headers)
+    }
+
     fn get_by_key(&self, key: u64) -> (Option<Entry>, bool) {
         let entry = self.get_by_key(key);
         (entry.clone(), entry.is_some())
@@ -349,15 +825,61 @@ impl crate::db::Storage for Storage {
         });
     }
 
+    fn shards_count(&self) -> usize {
+        self.shareded_hash_map.shards.len()
+    }
+
+    fn walk_shard(&self, ctx: &CancellationToken, shard_id: usize, f: &mut dyn FnMut(&super::Shard<Entry>)) {
+        if let Some(shard) = self.shareded_hash_map.shards.get(shard_id).filter(|_| !ctx.is_cancelled()) {
+            f(shard);
+        }
+    }
+
     fn remove(&self, entry: &Entry) -> (i64, bool) {
         self.remove(entry)
     }
 
+    fn mark_outdated(&self, entry: &Entry) {
+        self.mark_outdated(entry)
+    }
+
     fn stat(&self) -> (i64, i64) {
         self.stat()
     }
 
-    fn clear(&self) {
-        self.shareded_hash_map.clear();
+    fn clear(&self) -> (i64, i64) {
+        Storage::clear(self)
+    }
+
+    fn clear_pending_bytes(&self) -> i64 {
+        Storage::clear_pending_bytes(self)
+    }
+
+    fn is_admission_active(&self) -> bool {
+        self.admission_memory_limit_overcome()
+    }
+
+    fn refresh_backlog(&self) -> usize {
+        Storage::refresh_backlog(self)
+    }
+
+    fn tagged(&self, tag: u64) -> Vec<Entry> {
+        Storage::tagged(self, tag)
+    }
+
+    fn memory_limits(&self) -> MemoryLimits {
+        Storage::memory_limits(self)
+    }
+
+    fn counts_hits(&self) -> bool {
+        Storage::counts_hits(self)
+    }
+
+    fn resize(&self, size: Option<i64>) -> MemoryLimits {
+        Storage::resize(self, size)
+    }
+
+    fn resized(&self) -> Option<i64> {
+        Storage::resized(self)
     }
 }
@@ -6,43 +6,14 @@ mod tests {
     use tokio_util::sync::CancellationToken;
 
     use crate::config::{self, Rule, RuleKey, RuleValue};
-    use crate::db::storage::{Map, Storage};
+    use crate::controller::metrics;
+    use crate::db::storage::storage::RefreshDiscard;
+    use crate::db::storage::{Map, Storage, NUM_OF_SHARDS};
     use crate::model::{Entry, Response};
-    use crate::upstream::Upstream;
-
-    // Mock upstream for testing
-    struct MockUpstream;
-
-    #[async_trait::async_trait]
-    impl Upstream for MockUpstream {
-        async fn request(
-            &self,
-            _rule: &crate::config::Rule,
-            _queries: &[(Vec<u8>, Vec<u8>)],
-            _headers: &[(Vec<u8>, Vec<u8>)],
-        ) -> Result<crate::upstream::Response, anyhow::Error> {
-            Err(anyhow::anyhow!("not implemented"))
-        }
-
-        async fn proxy_request(
-            &self,
-            _method: &str,
-            _path: &str,
-            _query: &str,
-            _headers: &[(String, String)],
-            _body: Option<&[u8]>,
-        ) -> Result<crate::upstream::Response, anyhow::Error> {
-            Err(anyhow::anyhow!("not implemented"))
-        }
-
-        async fn refresh(&self, _entry: &Entry) -> Result<(), anyhow::Error> {
-            Err(anyhow::anyhow!("not implemented"))
-        }
-
-        async fn is_healthy(&self) -> Result<(), anyhow::Error> {
-            Ok(())
-        }
-    }
+    use crate::time;
+    use crate::upstream::testing::MockUpstream;
+    use crate::upstream::{Response as UpstreamResponse, Upstream};
+    use crate::workers::RefreshBackend;
 
     fn make_rule(path: &str) -> Arc<Rule> {
         Arc::new(Rule {
@@ -51,14 +22,32 @@ mod tests {
             cache_key: RuleKey {
                 query: None,
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: None,
                 headers_map: None,
             },
             cache_value: RuleValue {
                 headers: None,
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         })
     }
 
@@ -79,7 +68,7 @@ mod tests {
         let token = CancellationToken::new();
         let cfg = config::new_test_config();
         let map = Arc::new(Map::new(token.clone(), cfg.clone()));
-        let upstream = Arc::new(MockUpstream) as Arc<dyn Upstream>;
+        let upstream = MockUpstream::new() as Arc<dyn Upstream>;
         let storage = Storage::new(token.clone(), cfg, upstream, map)
             .expect("Failed to create storage");
         // Give time for logger task to start
@@ -293,13 +282,96 @@ mod tests {
         }
 
         assert_eq!(storage.len(), 3);
-        assert!(storage.mem() > 0);
+        let mem = storage.mem();
+        assert!(mem > 0);
 
         // Clear all
-        storage.clear();
+        assert_eq!(storage.clear(), (mem, 3));
 
         assert_eq!(storage.len(), 0);
         assert_eq!(storage.mem(), 0);
+
+        // The detached contents are released in the background.
+        for _ in 0..100 {
+            if storage.clear_pending_bytes() == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(storage.clear_pending_bytes(), 0);
+
+        // The storage keeps working on fresh shards.
+        let entry = make_entry_with_key(rule, "after", b"body");
+        assert!(storage.set(entry.clone()));
+        assert!(storage.get(&entry).1);
+        assert_eq!(storage.len(), 1);
+    }
+
+    /// Test that reads keep a low p99 while a large storage is being cleared.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_clear_does_not_stall_reads() {
+        const ENTRIES: usize = 100_000;
+        const READERS: usize = 2;
+
+        let (storage, _token) = setup_storage().await;
+        let rule = make_rule("/api/v1/user");
+        let entries: Arc<Vec<Entry>> = Arc::new(
+            (0..ENTRIES)
+                .map(|i| make_entry_with_key(rule.clone(), &format!("k{}", i), &[b'x'; 256]))
+                .collect(),
+        );
+        for entry in entries.iter() {
+            storage.set(entry.clone());
+        }
+
+        // Readers record per-get latencies until stopped.
+        let sample = |storage: Arc<Storage>, entries: Arc<Vec<Entry>>, stop: Arc<std::sync::atomic::AtomicBool>| {
+            std::thread::spawn(move || {
+                let mut latencies = Vec::new();
+                let mut i = 0usize;
+                while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+                    let entry = &entries[i % entries.len()];
+                    let started = std::time::Instant::now();
+                    let _ = storage.get(entry);
+                    latencies.push(started.elapsed());
+                    i = i.wrapping_add(7919);
+                }
+                latencies
+            })
+        };
+        let p99 = |mut latencies: Vec<Duration>| {
+            latencies.sort();
+            latencies[latencies.len() * 99 / 100]
+        };
+
+        let run = |clear: bool| {
+            let storage = storage.clone();
+            let entries = entries.clone();
+            async move {
+                let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+                let readers: Vec<_> = (0..READERS)
+                    .map(|_| sample(storage.clone(), entries.clone(), stop.clone()))
+                    .collect();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                if clear {
+                    assert_eq!(storage.clear().1, ENTRIES as i64);
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                stop.store(true, std::sync::atomic::Ordering::Relaxed);
+                let latencies: Vec<Duration> = readers.into_iter().flat_map(|r| r.join().unwrap()).collect();
+                p99(latencies)
+            }
+        };
+
+        let before = run(false).await;
+        let during = run(true).await;
+        assert!(
+            during <= before * 20 + Duration::from_millis(5),
+            "read p99 during clear {:?} vs {:?} before",
+            during,
+            before
+        );
+        assert_eq!(storage.len(), 0);
     }
 
     /// Test that multiple gets update LRU order.
@@ -327,4 +399,524 @@ mod tests {
         assert!(hit2);
         assert!(result2.is_some());
     }
+
+    /// Storage with a 1s global TTL in remove mode, the lifetime manager not running.
+    async fn setup_ttl_storage(strict_ttl: Option<bool>) -> (Arc<Storage>, CancellationToken) {
+        let token = CancellationToken::new();
+        let mut cfg = config::new_test_config();
+        let lifetime = cfg.cache.lifetime.as_mut().unwrap();
+        lifetime.ttl = Some(Duration::from_secs(1));
+        lifetime.strict_ttl = strict_ttl;
+        lifetime.is_remove_on_ttl.store(true, std::sync::atomic::Ordering::Relaxed);
+        let map = Arc::new(Map::new(token.clone(), cfg.clone()));
+        let upstream = MockUpstream::new() as Arc<dyn Upstream>;
+        let storage = Storage::new(token.clone(), cfg, upstream, map).expect("Failed to create storage");
+        (storage, token)
+    }
+
+    /// Test that with strict_ttl an entry is a miss right after its TTL although nothing removed it.
+    #[tokio::test]
+    async fn test_strict_ttl_misses_expired_entry() {
+        let _clock = time::start(Duration::from_millis(1));
+        let (storage, _token) = setup_ttl_storage(Some(true)).await;
+        let entry = make_entry_with_key(make_rule("/api/v1/user"), "ttl", b"body");
+        assert!(storage.set(entry.clone()));
+        assert!(storage.get(&entry).1);
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let (found, hit) = storage.get(&entry);
+        assert!(!hit);
+        assert_eq!(found.map(|e| e.key()), Some(entry.key()), "the expired entry is handed back for accounting");
+        assert_eq!(storage.len(), 0, "the expired entry is removed on read");
+    }
+
+    /// Test that without strict_ttl an expired entry keeps hitting until the lifetime manager removes it.
+    #[tokio::test]
+    async fn test_lenient_ttl_serves_expired_entry() {
+        let _clock = time::start(Duration::from_millis(1));
+        let (storage, _token) = setup_ttl_storage(None).await;
+        let entry = make_entry_with_key(make_rule("/api/v1/user"), "ttl", b"body");
+        assert!(storage.set(entry.clone()));
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(storage.get(&entry).1);
+        assert_eq!(storage.len(), 1);
+    }
+
+    /// Storage stamping hits as `storage.track_access_time` and `storage.access_time_granularity` say.
+    fn setup_access_storage(track: Option<bool>, granularity: Option<Duration>) -> (Arc<Storage>, CancellationToken) {
+        let token = CancellationToken::new();
+        let mut cfg = config::new_test_config();
+        let storage_cfg = cfg.cache.storage.as_mut().unwrap();
+        storage_cfg.track_access_time = track;
+        storage_cfg.access_time_granularity = granularity;
+        let map = Arc::new(Map::new(token.clone(), cfg.clone()));
+        let upstream = MockUpstream::new() as Arc<dyn Upstream>;
+        (Storage::new(token.clone(), cfg, upstream, map).expect("Failed to create storage"), token)
+    }
+
+    /// Test that a write stamps the access time, and that hits stamp it only when tracked, once
+    /// it is older than the granularity.
+    #[tokio::test]
+    async fn test_access_time_tracking() {
+        let _clock = time::start(Duration::from_millis(1));
+        let cases = [
+            ("off", None, None, [false, false]),
+            ("default granularity", Some(true), None, [false, false]),
+            ("every hit", Some(true), Some(Duration::ZERO), [true, true]),
+            ("coarse", Some(true), Some(Duration::from_millis(40)), [false, true]),
+        ];
+        for (name, track, granularity, restamped) in cases {
+            let (storage, token) = setup_access_storage(track, granularity);
+            let entry = make_entry_with_key(make_rule("/api/v1/user"), name, b"body");
+            assert!(storage.set(entry.clone()));
+            let mut stamped = entry.touched_at();
+            assert!(stamped > 0, "{}: a write stamps the access time", name);
+
+            for (wait, restamped) in [Duration::from_millis(10), Duration::from_millis(50)].into_iter().zip(restamped) {
+                tokio::time::sleep(wait).await;
+                assert!(storage.get(&entry).1);
+                assert_eq!(entry.touched_at() > stamped, restamped, "{} after {:?}", name, wait);
+                stamped = entry.touched_at();
+            }
+            token.cancel();
+        }
+    }
+
+    /// Test that the sampling eviction has every hit counted, or one in four as four with
+    /// `storage.sample_hits`, while listing mode without readers of the count leaves it at zero.
+    #[tokio::test]
+    async fn test_hits_are_counted_for_the_sampling_eviction() {
+        let cases = [("listing", true, false, 0), ("sampling", false, false, 8), ("sampled", false, true, 8)];
+        for (name, listing, sample_hits, counted) in cases {
+            let token = CancellationToken::new();
+            let mut cfg = config::new_test_config();
+            let storage_cfg = cfg.cache.storage.as_mut().unwrap();
+            storage_cfg.mode = Some(if listing { "listing" } else { "sampling" }.to_string());
+            storage_cfg.is_listing = listing;
+            storage_cfg.sample_hits = Some(sample_hits);
+            let map = Arc::new(Map::new(token.clone(), cfg.clone()));
+            let upstream = MockUpstream::new() as Arc<dyn Upstream>;
+            let storage = Storage::new(token.clone(), cfg, upstream, map).expect("Failed to create storage");
+
+            let entry = make_entry_with_key(make_rule("/api/v1/user"), name, b"body");
+            assert!(storage.set(entry.clone()));
+            for _ in 0..8 {
+                assert!(storage.get(&entry).1);
+            }
+            assert_eq!(entry.hits(), counted, "{}", name);
+            token.cancel();
+        }
+    }
+
+    /// Test that the sampling eviction picks the least hit entry over an older but hotter one,
+    /// and halves the counts it samples until the hot entry goes as well.
+    #[tokio::test]
+    async fn test_sampling_eviction_prefers_least_hit_and_decays() {
+        let _clock = time::start(Duration::from_millis(1));
+        let token = CancellationToken::new();
+        let mut cfg = config::new_test_config();
+        let storage_cfg = cfg.cache.storage.as_mut().unwrap();
+        storage_cfg.mode = Some("sampling".to_string());
+        storage_cfg.is_listing = false;
+        let map: Map<Entry> = Map::new(token.clone(), cfg);
+
+        let rule = make_rule("/api/v1/user");
+        let (hot, cold) = (make_entry_with_key(rule.clone(), "hot", b"body"), make_entry_with_key(rule, "cold", b"body"));
+        for entry in [&hot, &cold] {
+            map.set(entry.key(), entry.clone());
+            entry.touch();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        for _ in 0..4 {
+            hot.count_hit(false);
+        }
+        assert!(hot.touched_at() < cold.touched_at(), "the hot entry is the least recently touched");
+
+        // Sample every shard, so both entries are seen.
+        let pick = || map.pick_victim(NUM_OF_SHARDS as i64, 8).map(|(_, v)| v.key());
+        assert_eq!(pick(), Some(cold.key()));
+        assert_eq!(hot.hits(), 2, "a sampled entry has its hits halved");
+        assert_eq!(pick(), Some(cold.key()));
+        assert_eq!(pick(), Some(cold.key()));
+        assert_eq!(hot.hits(), 0);
+        assert_eq!(pick(), Some(hot.key()), "once decayed, the older entry goes first");
+        token.cancel();
+    }
+
+    /// Test that concurrent sets of new keys never push stored bytes past the admission budget:
+    /// 1k simultaneous 1MB sets against 100MB.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_sets_respect_admission_budget() {
+        const BUDGET: i64 = 100 << 20;
+        let token = CancellationToken::new();
+        let mut cfg = config::new_test_config();
+        cfg.cache.storage.as_mut().unwrap().admission_memory_limit = BUDGET;
+        let map = Arc::new(Map::new(token.clone(), cfg.clone()));
+        let upstream = MockUpstream::new() as Arc<dyn Upstream>;
+        let storage = Storage::new(token.clone(), cfg, upstream, map).expect("Failed to create storage");
+
+        // Incompressible, so the stored payload stays at 1MB.
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+        let body: Arc<Vec<u8>> = Arc::new(
+            (0..1 << 20)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    seed as u8
+                })
+                .collect(),
+        );
+
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let sampler = {
+            let (storage, done) = (storage.clone(), done.clone());
+            std::thread::spawn(move || {
+                let mut peak = 0;
+                while !done.load(std::sync::atomic::Ordering::Acquire) {
+                    peak = peak.max(storage.mem());
+                }
+                peak
+            })
+        };
+
+        let rule = make_rule("/api/v1/user");
+        let sets: Vec<_> = (0..1000)
+            .map(|i| {
+                let (storage, rule, body) = (storage.clone(), rule.clone(), body.clone());
+                tokio::spawn(async move {
+                    let entry = make_entry_with_key(rule, &format!("burst-{}", i), &body);
+                    assert!(entry.weight() >= 1 << 20);
+                    storage.set(entry)
+                })
+            })
+            .collect();
+        let mut stored = 0;
+        for set in sets {
+            stored += set.await.unwrap() as i64;
+        }
+        done.store(true, std::sync::atomic::Ordering::Release);
+        let peak = sampler.join().unwrap().max(storage.mem());
+
+        assert!(peak <= BUDGET, "stored {} bytes over a {} byte budget", peak, BUDGET);
+        assert_eq!(storage.len(), stored, "refused sets must not be stored");
+        assert!((90..=100).contains(&stored), "the budget must still fill up, {} stored", stored);
+        token.cancel();
+    }
+
+    /// Test that stat() polled while sets, updates, removals and evictions hammer the same keys
+    /// never returns negative values or bytes without entries, and matches the stored entries
+    /// once writers are done.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_stat_is_consistent_under_concurrent_mutation() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        const KEYS: u64 = 64;
+        let token = CancellationToken::new();
+        let cfg = config::new_test_config();
+        let map = Arc::new(Map::new(token.clone(), cfg.clone()));
+        let upstream = MockUpstream::new() as Arc<dyn Upstream>;
+        let storage = Storage::new(token.clone(), cfg, upstream, map.clone()).expect("Failed to create storage");
+        let rule = make_rule("/api/v1/user");
+
+        let done = Arc::new(AtomicBool::new(false));
+        let poller = {
+            let (storage, done) = (storage.clone(), done.clone());
+            std::thread::spawn(move || {
+                let mut polls = 0u64;
+                while !done.load(Ordering::Acquire) {
+                    let (bytes, len) = storage.stat();
+                    assert!(bytes >= 0 && len >= 0, "stat() returned ({}, {})", bytes, len);
+                    assert_eq!(bytes == 0, len == 0, "stat() returned ({}, {})", bytes, len);
+                    polls += 1;
+                }
+                polls
+            })
+        };
+
+        let writers: Vec<_> = (0..4u64)
+            .map(|w| {
+                let (storage, map, rule) = (storage.clone(), map.clone(), rule.clone());
+                std::thread::spawn(move || {
+                    let mut seed = 0x9e37_79b9_7f4a_7c15u64 ^ (w + 1);
+                    for _ in 0..20_000 {
+                        seed ^= seed << 13;
+                        seed ^= seed >> 7;
+                        seed ^= seed << 17;
+                        // Same keys for all writers, with bodies of changing size so sets of a
+                        // stored key swap its payload in place.
+                        let entry = make_entry_with_key(rule.clone(), &format!("k{}", seed % KEYS), &vec![b'x'; (seed >> 8) as usize % 4096]);
+                        match (seed >> 20) % 8 {
+                            0..=3 => {
+                                storage.set(entry);
+                            }
+                            4..=6 => {
+                                storage.remove(&entry);
+                            }
+                            _ => {
+                                map.evict_until_within_limit(0, 4, None);
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        done.store(true, Ordering::Release);
+        assert!(poller.join().unwrap() > 0);
+
+        // At quiescence stat() is exactly the stored entries.
+        let (mut bytes, mut len) = (0, 0);
+        for i in 0..KEYS {
+            let probe = make_entry_with_key(rule.clone(), &format!("k{}", i), b"");
+            if let Some(entry) = storage.get_by_key(probe.key()) {
+                bytes += entry.weight();
+                len += 1;
+            }
+        }
+        assert_eq!(storage.stat(), (bytes, len));
+
+        storage.clear();
+        assert_eq!(storage.stat(), (0, 0));
+        token.cancel();
+    }
+
+    /// Storage refreshing from `upstream`, with the memory limits given.
+    fn setup_refresh_storage(
+        upstream: Arc<MockUpstream>,
+        admission_memory_limit: Option<i64>,
+        hard_memory_limit: Option<i64>,
+    ) -> (Arc<Storage>, config::Config, CancellationToken) {
+        let token = CancellationToken::new();
+        let mut cfg = config::new_test_config();
+        let storage_cfg = cfg.cache.storage.as_mut().unwrap();
+        if let Some(limit) = admission_memory_limit {
+            storage_cfg.admission_memory_limit = limit;
+        }
+        if let Some(limit) = hard_memory_limit {
+            storage_cfg.hard_memory_limit = limit;
+        }
+        let map = Arc::new(Map::new(token.clone(), cfg.clone()));
+        let storage = Storage::new(token.clone(), cfg.clone(), upstream, map).expect("Failed to create storage");
+        (storage, cfg, token)
+    }
+
+    fn body_of(entry: &Entry) -> Vec<u8> {
+        entry.response_payload().expect("payload must decode").body
+    }
+
+    /// Test that refreshes of resident entries land while admission refuses every new key.
+    #[tokio::test]
+    async fn test_refresh_lands_with_admission_saturated() {
+
+        let upstream = MockUpstream::builder().fallback(UpstreamResponse::ok("fresh")).build();
+        let (storage, cfg, token) = setup_refresh_storage(upstream.clone(), Some(0), None);
+        let admission = cfg.cache.admission.as_ref().unwrap().is_enabled.clone();
+        let rule = make_rule("/api/v1/user");
+
+        // Stored while admission is off; once on, everything is over its budget.
+        admission.store(false, std::sync::atomic::Ordering::Relaxed);
+        let resident: Vec<Entry> = (0..8).map(|i| make_entry_with_key(rule.clone(), &format!("resident-{}", i), b"stale")).collect();
+        for entry in &resident {
+            assert!(storage.set(entry.clone()));
+        }
+        admission.store(true, std::sync::atomic::Ordering::Relaxed);
+        let newcomer = make_entry_with_key(rule.clone(), "newcomer", &vec![b'x'; 4096]);
+        assert!(!storage.set(newcomer), "admission must refuse new keys past its budget");
+
+        let applied = metrics::refresh_applied();
+        for entry in &resident {
+            let stored = storage.get_by_key(entry.key()).expect("entry must be resident");
+            storage.on_ttl(&stored).await.expect("refresh must land");
+        }
+        for entry in &resident {
+            assert_eq!(body_of(&storage.get(entry).0.unwrap()), b"fresh");
+        }
+        assert_eq!(upstream.refreshes(), resident.len());
+        assert_eq!(storage.len(), resident.len() as i64);
+        assert!(metrics::refresh_applied() - applied >= resident.len() as u64);
+        token.cancel();
+    }
+
+    /// Test that a new key winning admission past the budget evicts its victim before it is
+    /// stored, so stored bytes stay within the budget.
+    #[tokio::test]
+    async fn test_admission_winner_evicts_victim_first() {
+        const RESIDENTS: i64 = 8192;
+        let rule = make_rule("/api/v1/user");
+        let entry = |name: &str, i: i64| make_entry_with_key(rule.clone(), &format!("{}-{:05}", name, i), &[b'x'; 64]);
+        // Enough residents for every shard a victim is sampled from to hold some.
+        let budget = RESIDENTS * entry("resident", 0).weight();
+        let (storage, _cfg, token) = setup_refresh_storage(MockUpstream::new(), Some(budget), None);
+        for i in 0..RESIDENTS {
+            assert!(storage.set(entry("resident", i)));
+        }
+        assert_eq!(storage.mem(), budget);
+
+        // Each refused set is recorded, until the newcomer outweighs its victim in TinyLFU.
+        let newcomer = entry("newcomer", 0);
+        assert!((0..16).any(|_| storage.set(newcomer.clone())), "a frequent new key must win admission");
+        assert!(storage.get_by_key(newcomer.key()).is_some());
+        assert_eq!(storage.len(), RESIDENTS, "the victim must be evicted");
+        assert!(storage.mem() <= budget, "stored {} bytes over a {} byte budget", storage.mem(), budget);
+        token.cancel();
+    }
+
+    /// Test that a refreshed payload over the hard memory limit leaves the old payload in place,
+    /// is retried without calling upstream again, and is discarded once the retries run out.
+    #[tokio::test]
+    async fn test_refresh_over_hard_limit_keeps_old_payload() {
+
+        // Incompressible, so the refreshed payload stays large.
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+        let large: Vec<u8> = (0..64 << 10)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed as u8
+            })
+            .collect();
+        let upstream = MockUpstream::builder().fallback(UpstreamResponse::ok(large)).build();
+        let (storage, cfg, token) = setup_refresh_storage(upstream.clone(), None, Some(16 << 10));
+        cfg.cache.admission.as_ref().unwrap().is_enabled.store(false, std::sync::atomic::Ordering::Relaxed);
+        let entry = make_entry_with_key(make_rule("/api/v1/user"), "grows", b"small");
+        assert!(storage.set(entry.clone()));
+        let stored = storage.get(&entry).0.unwrap();
+
+        let discarded = metrics::refresh_discarded(RefreshDiscard::Memory);
+        let started = std::time::Instant::now();
+        assert!(storage.on_ttl(&stored).await.is_err(), "a discarded refresh is reported");
+        assert!(started.elapsed() >= Duration::from_millis(350), "the swap is retried with backoff");
+
+        assert_eq!(upstream.refreshes(), 1, "retries reuse the fetched payload");
+        assert_eq!(body_of(&storage.get(&entry).0.unwrap()), b"small");
+        assert!(storage.mem() <= 16 << 10);
+        assert!(metrics::refresh_discarded(RefreshDiscard::Memory) > discarded);
+        token.cancel();
+    }
+
+    /// Test that a refresh of an entry removed meanwhile does not store it again.
+    #[tokio::test]
+    async fn test_refresh_of_removed_entry_is_discarded() {
+
+        let (storage, _cfg, token) = setup_refresh_storage(MockUpstream::new(), None, None);
+        let entry = make_entry_with_key(make_rule("/api/v1/user"), "removed", b"body");
+        assert!(storage.set(entry.clone()));
+        storage.remove(&entry);
+
+        let gone = metrics::refresh_discarded(RefreshDiscard::Gone);
+        storage.on_ttl(&entry).await.expect("a refresh of a removed entry is not an error");
+        assert_eq!(storage.len(), 0);
+        assert!(metrics::refresh_discarded(RefreshDiscard::Gone) > gone);
+        token.cancel();
+    }
+
+    fn make_entry_with_response(rule: Arc<Rule>, key_data: &str, headers: &[(&str, &str)], body: &[u8]) -> Entry {
+        let queries = vec![(b"key".to_vec(), key_data.as_bytes().to_vec())];
+        let entry = Entry::new(rule, &queries, &[]);
+        let response = Response {
+            status: 200,
+            headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            body: body.to_vec(),
+        };
+        entry.set_payload(&queries, &[], &response);
+        entry
+    }
+
+    /// Test that a refresh bringing the stored response again, even encoded differently and
+    /// with another `Date`, only touches the refresh time, while a changed header is swapped in.
+    #[tokio::test]
+    async fn test_unchanged_refresh_is_not_swapped() {
+        use std::io::Write;
+
+        let upstream = MockUpstream::builder()
+            .fallback(UpstreamResponse::new(
+                200,
+                vec![
+                    ("Content-Type".to_string(), "application/json".to_string()),
+                    ("Date".to_string(), "Tue, 17 Oct 2026 10:00:00 GMT".to_string()),
+                ],
+                b"same".to_vec(),
+            ))
+            .build();
+        let (storage, cfg, token) = setup_refresh_storage(upstream.clone(), None, None);
+        cfg.cache.admission.as_ref().unwrap().is_enabled.store(false, std::sync::atomic::Ordering::Relaxed);
+        let rule = make_rule("/api/v1/user");
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(b"same").unwrap();
+        let encoded = gzip.finish().unwrap();
+        let stored_encoded = make_entry_with_response(
+            rule.clone(),
+            "encoded",
+            &[("Content-Type", "application/json"), ("Content-Encoding", "gzip"), ("Date", "Mon, 16 Oct 2026 10:00:00 GMT")],
+            &encoded,
+        );
+        let stored_plain = make_entry_with_response(rule.clone(), "plain", &[("Content-Type", "application/json")], b"same");
+        let stored_other = make_entry_with_response(rule.clone(), "other", &[("Content-Type", "text/plain")], b"same");
+
+        for stored in [&stored_encoded, &stored_plain] {
+            assert!(storage.set(stored.clone()));
+            let payload = stored.payload_bytes();
+            stored.set_refreshed_at_for_tests(1);
+            let unchanged = metrics::refresh_unchanged();
+            storage.on_ttl(stored).await.expect("refresh must land");
+            assert!(metrics::refresh_unchanged() > unchanged);
+
+            let resident = storage.get_by_key(stored.key()).unwrap();
+            assert!(resident.fresh_at() > 1, "the refresh time is touched");
+            assert_eq!(resident.payload_bytes(), payload, "the stored payload stays");
+        }
+
+        assert!(storage.set(stored_other.clone()));
+        let applied = metrics::refresh_applied();
+        storage.on_ttl(&stored_other).await.expect("refresh must land");
+        assert!(metrics::refresh_applied() > applied);
+        let headers = storage.get_by_key(stored_other.key()).unwrap().response_payload().unwrap().headers;
+        assert!(headers.contains(&(b"Content-Type".to_vec(), b"application/json".to_vec())));
+        token.cancel();
+    }
+
+    /// Test that a rule following the origin's Cache-Control takes the lifetime of every
+    /// payload stored or refreshed, and drops the entry once the origin answers `no-store`.
+    #[tokio::test]
+    async fn test_origin_cache_control_on_set_and_refresh() {
+        let _clock = time::start(Duration::from_millis(1));
+        let upstream = MockUpstream::builder()
+            .fallback(UpstreamResponse::ok("fresh").with_header("Cache-Control", "max-age=30"))
+            .build();
+        let (storage, cfg, token) = setup_refresh_storage(upstream.clone(), None, None);
+        cfg.cache.admission.as_ref().unwrap().is_enabled.store(false, std::sync::atomic::Ordering::Relaxed);
+        let mut rule = (*make_rule("/api/v1/user")).clone();
+        rule.cache_value.respect_cache_control = Some(true);
+        let rule = Arc::new(rule);
+
+        let refused = make_entry_with_response(rule.clone(), "refused", &[("Cache-Control", "no-store")], b"body");
+        assert!(!storage.set(refused), "a no-store payload is not stored");
+        assert_eq!(storage.len(), 0);
+
+        let entry = make_entry_with_response(rule.clone(), "kept", &[("Cache-Control", "max-age=5")], b"body");
+        assert!(storage.set(entry.clone()));
+        let resident = storage.get_by_key(entry.key()).unwrap();
+        assert_eq!(resident.origin_ttl(), Some(Duration::from_secs(5)));
+
+        storage.on_ttl(&resident).await.expect("refresh must land");
+        assert_eq!(resident.origin_ttl(), Some(Duration::from_secs(30)), "a refresh brings its own lifetime");
+
+        upstream.set_response("/api/v1/user", UpstreamResponse::ok("fresh").with_header("Cache-Control", "private"));
+        let no_store = metrics::refresh_discarded(RefreshDiscard::NoStore);
+        storage.on_ttl(&resident).await.expect("a no-store refresh is not an error");
+        assert!(storage.get_by_key(entry.key()).is_none(), "the entry is dropped");
+        assert!(metrics::refresh_discarded(RefreshDiscard::NoStore) > no_store);
+
+        let plain = make_entry_with_key(make_rule("/api/v1/user"), "plain", b"body");
+        plain.set_payload(&[], &[], &Response { status: 200, headers: vec![("Cache-Control".to_string(), "no-store".to_string())], body: b"body".to_vec() });
+        assert!(storage.set(plain.clone()), "rules not following the origin store it regardless");
+        assert_eq!(plain.origin_ttl(), None);
+        token.cancel();
+    }
 }
@@ -0,0 +1,69 @@
+//! Secondary index of a shard: the keys of its entries by the tags of their responses.
+//!
+//! Kept under the shard lock along with the items, so it never holds a key the shard does
+//! not. Its memory is estimated per key and per (tag, key) pair and counted in the shard
+//! usage with the entries, so it shows in `stat()` and is weighed by eviction.
+
+use std::collections::{HashMap, HashSet};
+
+/// Estimated bytes of an indexed key: its slot in `of_key` and the boxed slice header.
+const KEY_BYTES: i64 = 48;
+/// Estimated bytes of a (tag, key) pair: its slot in the set of the tag and in the slice of
+/// the key.
+const PAIR_BYTES: i64 = 24;
+/// Estimated bytes of a tag: its slot in `by_tag` and the empty set.
+const TAG_BYTES: i64 = 80;
+
+/// Keys by tag hash, and the tag hashes of each key to unlink them on removal.
+#[derive(Default)]
+pub struct TagIndex {
+    by_tag: HashMap<u64, HashSet<u64>>,
+    of_key: HashMap<u64, Box<[u64]>>,
+}
+
+impl TagIndex {
+    /// Indexes the key under `tags` only, unlinking it from the tags it had. Returns the
+    /// bytes delta.
+    pub fn set(&mut self, key: u64, tags: Vec<u64>) -> i64 {
+        if self.of_key.get(&key).is_some_and(|had| **had == *tags) {
+            return 0;
+        }
+        let mut delta = self.remove(key);
+        if tags.is_empty() {
+            return delta;
+        }
+        for &tag in &tags {
+            let keys = self.by_tag.entry(tag).or_insert_with(|| {
+                delta += TAG_BYTES;
+                HashSet::new()
+            });
+            keys.insert(key);
+        }
+        delta += KEY_BYTES + PAIR_BYTES * tags.len() as i64;
+        self.of_key.insert(key, tags.into_boxed_slice());
+        delta
+    }
+
+    /// Unlinks the key from its tags. Returns the bytes delta, zero or negative.
+    pub fn remove(&mut self, key: u64) -> i64 {
+        let Some(tags) = self.of_key.remove(&key) else {
+            return 0;
+        };
+        let mut delta = -(KEY_BYTES + PAIR_BYTES * tags.len() as i64);
+        for tag in tags.iter() {
+            if let Some(keys) = self.by_tag.get_mut(tag) {
+                keys.remove(&key);
+                if keys.is_empty() {
+                    self.by_tag.remove(tag);
+                    delta -= TAG_BYTES;
+                }
+            }
+        }
+        delta
+    }
+
+    /// Keys indexed under the tag.
+    pub fn keys(&self, tag: u64) -> Vec<u64> {
+        self.by_tag.get(&tag).map(|keys| keys.iter().copied().collect()).unwrap_or_default()
+    }
+}
@@ -0,0 +1,44 @@
+//! Tests for the tag index of a shard.
+
+#[cfg(test)]
+mod tests {
+    use super::super::tags::TagIndex;
+
+    fn sorted(mut keys: Vec<u64>) -> Vec<u64> {
+        keys.sort_unstable();
+        keys
+    }
+
+    /// Test that keys are found by each of their tags and that re-tagging a key unlinks it
+    /// from the tags it no longer has.
+    #[test]
+    fn test_set_and_retag() {
+        let mut index = TagIndex::default();
+        assert!(index.set(1, vec![10, 20]) > 0);
+        assert!(index.set(2, vec![20]) > 0);
+        assert_eq!(index.set(3, vec![]), 0, "an untagged key costs nothing");
+
+        assert_eq!(sorted(index.keys(20)), vec![1, 2]);
+        assert_eq!(index.keys(10), vec![1]);
+        assert!(index.keys(30).is_empty());
+
+        assert_eq!(index.set(1, vec![10, 20]), 0, "same tags again change nothing");
+        index.set(1, vec![30]);
+        assert!(index.keys(10).is_empty());
+        assert_eq!(index.keys(20), vec![2]);
+        assert_eq!(index.keys(30), vec![1]);
+    }
+
+    /// Test that removing every key gives back exactly the bytes their indexing took.
+    #[test]
+    fn test_remove_gives_back_bytes() {
+        let mut index = TagIndex::default();
+        let taken = index.set(1, vec![10, 20]) + index.set(2, vec![20]) + index.set(1, vec![20, 30]);
+        assert!(taken > 0);
+
+        let freed = index.remove(1) + index.remove(2);
+        assert_eq!(freed, -taken);
+        assert_eq!(index.remove(1), 0, "a key removed twice");
+        assert!(index.keys(20).is_empty());
+    }
+}
@@ -0,0 +1,42 @@
+//! Paired byte and entry counters.
+
+use portable_atomic::AtomicU128;
+use std::sync::atomic::Ordering;
+
+/// Bytes and entries of a shard or of the whole map, packed in one atomic so that both
+/// change together and a read never sees one updated without the other.
+///
+/// The packed value is `bytes * 2^64 + len`: adding a delta of both is a single wrapping
+/// `fetch_add`, and as long as the deltas added describe real transitions the halves
+/// decode back to the state they led to.
+#[derive(Default)]
+pub struct Usage(AtomicU128);
+
+impl Usage {
+    /// Adds a delta of bytes and entries at once.
+    pub fn add(&self, bytes: i64, len: i64) {
+        if bytes != 0 || len != 0 {
+            self.0.fetch_add(pack(bytes, len), Ordering::Relaxed);
+        }
+    }
+
+    /// (bytes, len) as of a single instant.
+    pub fn load(&self) -> (i64, i64) {
+        unpack(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Resets to zero and returns the (bytes, len) it held.
+    pub fn take(&self) -> (i64, i64) {
+        unpack(self.0.swap(0, Ordering::Relaxed))
+    }
+}
+
+fn pack(bytes: i64, len: i64) -> u128 {
+    ((bytes as i128) << 64).wrapping_add(len as i128) as u128
+}
+
+fn unpack(packed: u128) -> (i64, i64) {
+    let len = packed as u64 as i64;
+    let bytes = ((packed as i128).wrapping_sub(len as i128) >> 64) as i64;
+    (bytes, len)
+}
@@ -0,0 +1,51 @@
+//! Tests for the paired byte and entry counters.
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::super::usage::Usage;
+
+    #[test]
+    fn test_deltas_of_either_sign_round_trip() {
+        let usage = Usage::default();
+        usage.add(100, 1);
+        usage.add(5 << 40, 3);
+        assert_eq!(usage.load(), (100 + (5 << 40), 4));
+
+        usage.add(-100, -1);
+        usage.add(-(5 << 40) + 7, -2);
+        assert_eq!(usage.load(), (7, 1));
+
+        // A delta of bytes alone, e.g. a payload swapped for a smaller one.
+        usage.add(-3, 0);
+        assert_eq!(usage.load(), (4, 1));
+
+        assert_eq!(usage.take(), (4, 1));
+        assert_eq!(usage.load(), (0, 0));
+    }
+
+    #[test]
+    fn test_pairs_added_concurrently_are_never_split() {
+        let usage = Arc::new(Usage::default());
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let usage = usage.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..50_000 {
+                        usage.add(64, 1);
+                        usage.add(-64, -1);
+                    }
+                })
+            })
+            .collect();
+        while writers.iter().any(|w| !w.is_finished()) {
+            let (bytes, len) = usage.load();
+            assert_eq!(bytes, len * 64, "read ({}, {})", bytes, len);
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(usage.load(), (0, 0));
+    }
+}
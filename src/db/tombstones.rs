@@ -0,0 +1,113 @@
+//! Short-lived tombstones for keys removed by invalidation.
+//!
+//! Right after a remove-invalidation the origin may still serve the old data from its own
+//! caches, and the next request would store it again. While a key is tombstoned, requests
+//! for it go to the origin and the answer is not stored. The set is bounded and entries
+//! expire on their own; lookups are lock-free while no tombstone is alive.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// Upper bound of simultaneously alive tombstones.
+pub const MAX_TOMBSTONES: usize = 64 * 1024;
+
+/// Bounded set of entry keys with an expiry each.
+pub struct Tombstones {
+    capacity: usize,
+    alive: AtomicUsize,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    keys: HashMap<u64, Instant>,
+    /// Latest expiry in `keys`; once passed every tombstone is dead.
+    horizon: Option<Instant>,
+}
+
+impl Tombstones {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            alive: AtomicUsize::new(0),
+            inner: Mutex::new(Inner {
+                keys: HashMap::new(),
+                horizon: None,
+            }),
+        }
+    }
+
+    /// Tombstones the key for `ttl`, extending an existing tombstone if it expires earlier.
+    /// When the set is full, expired tombstones are dropped first, then the one closest to expiry.
+    pub fn add(&self, key: u64, ttl: Duration) {
+        if ttl.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let until = now + ttl;
+
+        let mut inner = self.inner.lock();
+        if inner.keys.len() >= self.capacity && !inner.keys.contains_key(&key) {
+            inner.keys.retain(|_, expires| *expires > now);
+            if inner.keys.len() >= self.capacity {
+                let soonest = inner.keys.iter().min_by_key(|(_, expires)| **expires).map(|(k, _)| *k);
+                if let Some(soonest) = soonest {
+                    inner.keys.remove(&soonest);
+                }
+            }
+        }
+        let expires = inner.keys.entry(key).or_insert(until);
+        if *expires < until {
+            *expires = until;
+        }
+        if inner.horizon.is_none_or(|horizon| horizon < until) {
+            inner.horizon = Some(until);
+        }
+        self.alive.store(inner.keys.len(), Ordering::Release);
+    }
+
+    /// Whether the key has an unexpired tombstone. Expired tombstones are dropped on the way,
+    /// all at once when the last one has expired, so lookups get lock-free again.
+    pub fn contains(&self, key: u64) -> bool {
+        if self.alive.load(Ordering::Acquire) == 0 {
+            return false;
+        }
+        let now = Instant::now();
+        let mut inner = self.inner.lock();
+        if inner.horizon.is_none_or(|horizon| horizon <= now) {
+            inner.keys.clear();
+            inner.horizon = None;
+            self.alive.store(0, Ordering::Release);
+            return false;
+        }
+        let alive = match inner.keys.get(&key) {
+            Some(expires) if *expires > now => true,
+            Some(_) => {
+                inner.keys.remove(&key);
+                false
+            }
+            None => false,
+        };
+        self.alive.store(inner.keys.len(), Ordering::Release);
+        alive
+    }
+
+    /// Number of stored tombstones, expired ones not yet dropped included.
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.alive.load(Ordering::Acquire)
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for Tombstones {
+    fn default() -> Self {
+        Self::new(MAX_TOMBSTONES)
+    }
+}
@@ -0,0 +1,40 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::db::tombstones::Tombstones;
+
+    /// Test that a tombstone is alive for its TTL only and that extending keeps the later expiry.
+    #[test]
+    fn test_tombstone_expires() {
+        let tombstones = Tombstones::new(16);
+        assert!(!tombstones.contains(1));
+
+        tombstones.add(1, Duration::from_millis(50));
+        tombstones.add(1, Duration::from_millis(1));
+        tombstones.add(2, Duration::ZERO);
+        assert!(tombstones.contains(1));
+        assert!(!tombstones.contains(2));
+        assert_eq!(tombstones.len(), 1);
+
+        std::thread::sleep(Duration::from_millis(80));
+        assert!(!tombstones.contains(1));
+        assert!(tombstones.is_empty());
+    }
+
+    /// Test that a full set makes room by dropping the tombstone closest to expiry.
+    #[test]
+    fn test_tombstones_are_bounded() {
+        let tombstones = Tombstones::new(3);
+        tombstones.add(1, Duration::from_secs(10));
+        tombstones.add(2, Duration::from_secs(1));
+        tombstones.add(3, Duration::from_secs(30));
+        tombstones.add(4, Duration::from_secs(20));
+
+        assert_eq!(tombstones.len(), 3);
+        assert!(!tombstones.contains(2));
+        for key in [1, 3, 4] {
+            assert!(tombstones.contains(key));
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Coordination of admin walks over the storage shards.
+//!
+//! Invalidations, purges by prefix, clears by rule, key schema purges and rule reconciliations
+//! each walk every shard under its read lock. Run together they multiply lock pressure on the request path, so
+//! each takes one of `storage.admin_walks` permits (1 by default) first and queues otherwise;
+//! the semaphore is fair, so walks run in arrival order. Dumps are exempt: they run on their own schedule and
+//! must not be held up by admin work.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::Config;
+
+pub const DEFAULT_ADMIN_WALKS: usize = 1;
+pub const DEFAULT_ADMIN_WALK_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub const OP_INVALIDATE: &str = "invalidate";
+pub const OP_KEY_SCHEMA_PURGE: &str = "key_schema_purge";
+pub const OP_RULE_RECONCILE: &str = "rule_reconcile";
+pub const OP_CLEAR_RULE: &str = "clear_rule";
+pub const OP_PURGE: &str = "purge";
+
+/// A walk gave up waiting for a permit.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{op} gave up after waiting {waited:?} for a walk permit with {ahead} walk(s) ahead")]
+pub struct WalkQueueTimeout {
+    pub op: &'static str,
+    pub waited: Duration,
+    /// Walks running or queued ahead of it when it gave up.
+    pub ahead: usize,
+}
+
+struct Job {
+    id: u64,
+    op: &'static str,
+    since: Instant,
+}
+
+#[derive(Default)]
+struct Jobs {
+    running: Vec<Job>,
+    queued: VecDeque<Job>,
+}
+
+/// A walk running or waiting for its turn.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub id: u64,
+    pub op: &'static str,
+    /// 1 for the next walk to run; absent for running walks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+    /// Time spent running, or waiting for queued walks.
+    pub elapsed_ms: u64,
+}
+
+/// Walks running and queued, in the order they will run.
+#[derive(Debug, Clone, Serialize)]
+pub struct WalksStatus {
+    pub permits: usize,
+    pub running: Vec<JobStatus>,
+    pub queued: Vec<JobStatus>,
+}
+
+/// Hands out walk permits in arrival order.
+pub struct WalkCoordinator {
+    semaphore: Arc<Semaphore>,
+    permits: usize,
+    timeout: Duration,
+    next_id: AtomicU64,
+    jobs: Mutex<Jobs>,
+}
+
+impl WalkCoordinator {
+    /// Coordinator with the permits and queue timeout of the `storage` section.
+    pub fn new(cfg: &Config) -> Self {
+        let storage = cfg.cache.storage.as_ref();
+        let permits = storage.and_then(|s| s.admin_walks).unwrap_or(DEFAULT_ADMIN_WALKS).max(1);
+        Self {
+            semaphore: Arc::new(Semaphore::new(permits)),
+            permits,
+            timeout: storage.and_then(|s| s.admin_walk_timeout).unwrap_or(DEFAULT_ADMIN_WALK_TIMEOUT),
+            next_id: AtomicU64::new(1),
+            jobs: Mutex::new(Jobs::default()),
+        }
+    }
+
+    /// Waits for a permit, giving up after `storage.admin_walk_timeout`. Used by walks an
+    /// operator waits on.
+    pub async fn acquire(self: &Arc<Self>, op: &'static str) -> Result<WalkPermit, WalkQueueTimeout> {
+        self.wait(op, Some(self.timeout)).await
+    }
+
+    /// Waits for a permit as long as it takes. Used by walks run in the background, which
+    /// must not be skipped.
+    pub async fn acquire_background(self: &Arc<Self>, op: &'static str) -> WalkPermit {
+        match self.wait(op, None).await {
+            Ok(permit) => permit,
+            Err(_) => unreachable!("a walk without timeout cannot time out"),
+        }
+    }
+
+    async fn wait(self: &Arc<Self>, op: &'static str, timeout: Option<Duration>) -> Result<WalkPermit, WalkQueueTimeout> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let since = Instant::now();
+        self.jobs.lock().queued.push_back(Job { id, op, since });
+        // Removes the job from the queue if the caller gives up or is cancelled meanwhile.
+        let mut queued = Queued { coordinator: self, id, done: false };
+
+        let acquired = self.semaphore.clone().acquire_owned();
+        let permit = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, acquired).await.ok(),
+            None => Some(acquired.await),
+        };
+        let Some(Ok(permit)) = permit else {
+            let ahead = {
+                let jobs = self.jobs.lock();
+                jobs.running.len() + jobs.queued.iter().position(|j| j.id == id).unwrap_or(0)
+            };
+            return Err(WalkQueueTimeout { op, waited: since.elapsed(), ahead });
+        };
+
+        queued.done = true;
+        let mut jobs = self.jobs.lock();
+        jobs.queued.retain(|j| j.id != id);
+        jobs.running.push(Job { id, op, since: Instant::now() });
+        Ok(WalkPermit { coordinator: self.clone(), id, _permit: permit })
+    }
+
+    pub fn status(&self) -> WalksStatus {
+        let jobs = self.jobs.lock();
+        let now = Instant::now();
+        let status = |job: &Job, position| JobStatus {
+            id: job.id,
+            op: job.op,
+            position,
+            elapsed_ms: now.duration_since(job.since).as_millis() as u64,
+        };
+        WalksStatus {
+            permits: self.permits,
+            running: jobs.running.iter().map(|j| status(j, None)).collect(),
+            queued: jobs.queued.iter().enumerate().map(|(i, j)| status(j, Some(i + 1))).collect(),
+        }
+    }
+}
+
+struct Queued<'a> {
+    coordinator: &'a WalkCoordinator,
+    id: u64,
+    done: bool,
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.coordinator.jobs.lock().queued.retain(|j| j.id != self.id);
+        }
+    }
+}
+
+/// Permission to walk the shards, given back on drop.
+pub struct WalkPermit {
+    coordinator: Arc<WalkCoordinator>,
+    id: u64,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for WalkPermit {
+    fn drop(&mut self) {
+        self.coordinator.jobs.lock().running.retain(|j| j.id != self.id);
+    }
+}
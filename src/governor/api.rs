@@ -1,19 +1,71 @@
 //! The Governor interface.
 //
 
-use anyhow::Result;
-
 use super::service::{Config, Service};
 use std::sync::Arc;
 
+/// Result of a governor operation.
+pub type Result<T> = std::result::Result<T, GovernorError>;
+
+/// Why a governor operation failed. Messages carry the service name.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum GovernorError {
+    #[error("orchestrator: no such {0} service")]
+    ServiceNotRegistered(String),
+    /// Registered or started twice.
+    #[error("orchestrator: {0} service is already running")]
+    AlreadyRunning(String),
+    /// Nobody listens on the service transport anymore: its serve failed or it was stopped.
+    #[error("orchestrator: cannot {action} {name}, transport is closed")]
+    TransportClosed { name: String, action: &'static str },
+    /// The service listens but did not take the signal (a previous one is still pending).
+    #[error("orchestrator: cannot {action} {name}, signal was not sent")]
+    SignalNotSent { name: String, action: &'static str },
+}
+
+/// Lifecycle state of a registered service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceState {
+    /// Served, waiting for its start signal.
+    Registered,
+    Running,
+    Stopped,
+    /// Its serve panicked: no signal reaches it.
+    Failed,
+}
+
+impl ServiceState {
+    pub fn label(self) -> &'static str {
+        match self {
+            ServiceState::Registered => "registered",
+            ServiceState::Running => "running",
+            ServiceState::Stopped => "stopped",
+            ServiceState::Failed => "failed",
+        }
+    }
+}
+
+/// State of a service as seen by the governor.
+#[derive(Clone)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub state: ServiceState,
+    /// Workers up at the moment, next to the replicas of `cfg`.
+    pub active_replicas: usize,
+    pub cfg: Arc<dyn Config>,
+}
+
 /// Governor interface for orchestrating services.
 pub trait Governor: Send + Sync {
-    /// Registers a service with the orchestrator.
-    fn register(&self, name: String, s: Arc<dyn Service>);
+    /// Registers a service with the orchestrator and serves it.
+    fn register(&self, name: String, s: Arc<dyn Service>) -> Result<()>;
 
     /// Gets the configuration for a service.
     fn cfg(&self, name: &str) -> Result<Arc<dyn Config>>;
 
+    /// Gets the lifecycle state and configuration of a service.
+    fn status(&self, name: &str) -> Result<ServiceStatus>;
+
     /// Turns on a service.
     fn on(&self, name: &str) -> Result<()>;
 
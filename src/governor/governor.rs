@@ -1,17 +1,24 @@
 //! Service orchestration functionality.
 
-use anyhow::Result;
 use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
 use std::sync::{Arc, Mutex};
 use tracing::{error, info};
 
-use super::api::Governor;
+use super::api::{Governor, GovernorError, Result, ServiceState, ServiceStatus};
 use super::service::{Config, Service};
-use super::transport::ChanneledTransport;
+use super::transport::{ChanneledTransport, Transport};
+
+/// A registered service with the transport it is served on.
+struct Registered {
+    srv: Arc<dyn Service>,
+    transport: Arc<dyn Transport>,
+    state: ServiceState,
+}
 
 /// Orchestrator manages services and their lifecycle.
 pub struct Orchestrator {
-    srvs: Mutex<HashMap<String, Arc<dyn Service>>>,
+    srvs: Mutex<HashMap<String, Registered>>,
 }
 
 impl Orchestrator {
@@ -24,79 +31,115 @@ impl Orchestrator {
 
     fn with_srv<F, T>(&self, name: &str, f: F) -> Result<T>
     where
-        F: FnOnce(&Arc<dyn Service>) -> Result<T>,
+        F: FnOnce(&mut Registered) -> Result<T>,
     {
-        let srvs = self.srvs.lock().unwrap();
+        let mut srvs = self.srvs.lock().unwrap();
         let srv = srvs
-            .get(name)
-            .ok_or_else(|| anyhow::anyhow!("orchestrator: no such {} service", name))?;
+            .get_mut(name)
+            .ok_or_else(|| GovernorError::ServiceNotRegistered(name.to_string()))?;
         f(srv)
     }
 
     fn send_signal<F>(&self, name: &str, action: &'static str, send: F) -> Result<()>
     where
-        F: FnOnce(&Arc<dyn super::transport::Transport>) -> bool,
+        F: FnOnce(&Arc<dyn Transport>) -> bool,
     {
-        self.with_srv(name, |srv| {
-            let transport = srv.transport();
-            if !send(&transport) {
-                Err(anyhow::anyhow!(format!(
-                    "orchestrator: cannot {action} {}, signal was not sent",
-                    srv.name()
-                )))
-            } else {
-                info!(srv = %srv.name(), action = action, "orchestrator: action sent");
-                Ok(())
+        self.with_srv(name, |reg| {
+            if matches!(reg.state, ServiceState::Failed | ServiceState::Stopped) {
+                return Err(GovernorError::TransportClosed { name: name.to_string(), action });
             }
+            if !send(&reg.transport) {
+                return Err(GovernorError::SignalNotSent { name: name.to_string(), action });
+            }
+            info!(srv = %reg.srv.name(), action = action, "orchestrator: action sent");
+            Ok(())
         })
     }
 }
 
 impl Governor for Orchestrator {
-    fn register(&self, name: String, s: Arc<dyn Service>) {
+    fn register(&self, name: String, s: Arc<dyn Service>) -> Result<()> {
         let mut srvs = self.srvs.lock().unwrap();
         if srvs.contains_key(&name) {
-            return;
+            return Err(GovernorError::AlreadyRunning(name));
         }
 
         let transport = ChanneledTransport::new();
-        s.serve(transport.clone());
-        srvs.insert(name, s);
+        let served = std::panic::catch_unwind(AssertUnwindSafe(|| s.serve(transport.clone())));
+        let state = match served {
+            Ok(()) => ServiceState::Registered,
+            Err(panic) => {
+                let reason = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                error!(srv = %name, reason = %reason, "orchestrator: serve panicked");
+                ServiceState::Failed
+            }
+        };
+        srvs.insert(name.clone(), Registered { srv: s, transport, state });
+
+        match state {
+            ServiceState::Failed => Err(GovernorError::TransportClosed { name, action: "serve" }),
+            _ => Ok(()),
+        }
     }
 
     fn cfg(&self, name: &str) -> Result<Arc<dyn Config>> {
-        self.with_srv(name, |srv| Ok(srv.cfg()))
+        self.with_srv(name, |reg| Ok(reg.srv.cfg()))
+    }
+
+    fn status(&self, name: &str) -> Result<ServiceStatus> {
+        self.with_srv(name, |reg| {
+            Ok(ServiceStatus {
+                name: name.to_string(),
+                state: reg.state,
+                active_replicas: reg.srv.replicas(),
+                cfg: reg.srv.cfg(),
+            })
+        })
     }
 
     fn on(&self, name: &str) -> Result<()> {
-        self.send_signal(name, "turning on", |t| t.on())
+        self.send_signal(name, "turn on", |t| t.on())
     }
 
     fn off(&self, name: &str) -> Result<()> {
-        self.send_signal(name, "turning off", |t| t.off())
+        self.send_signal(name, "turn off", |t| t.off())
     }
 
     fn start(&self, name: &str) -> Result<()> {
-        self.send_signal(name, "starting", |t| t.start())
+        let running = self.with_srv(name, |reg| Ok(reg.state == ServiceState::Running))?;
+        if running {
+            return Err(GovernorError::AlreadyRunning(name.to_string()));
+        }
+        self.send_signal(name, "start", |t| t.start())?;
+        self.with_srv(name, |reg| {
+            reg.state = ServiceState::Running;
+            Ok(())
+        })
     }
 
     fn reload(&self, name: &str, cfg: Arc<dyn Config>) -> Result<()> {
-        self.send_signal(name, "reloading", move |t| t.reload(cfg.clone()))
+        self.send_signal(name, "reload", move |t| t.reload(cfg.clone()))
     }
 
     fn scale_to(&self, name: &str, n: usize) -> Result<()> {
-        self.send_signal(name, "scaling", move |t| t.scale_to(n))
+        self.send_signal(name, "scale", move |t| t.scale_to(n))
     }
 
     fn stop(&self) {
-        let srvs = self.srvs.lock().unwrap();
-        for srv in srvs.values() {
-            let transport = srv.transport();
-            let ok = transport.stop();
-            if !ok {
-                error!(srv = %srv.name(), "orchestrator: cannot stop, signal was not sent");
+        let mut srvs = self.srvs.lock().unwrap();
+        for (name, reg) in srvs.iter_mut() {
+            if matches!(reg.state, ServiceState::Failed | ServiceState::Stopped) {
+                continue;
+            }
+            if !reg.transport.stop() {
+                error!(srv = %name, "orchestrator: cannot stop, signal was not sent");
             } else {
-                info!(srv = %srv.name(), "orchestrator: stopping...");
+                reg.state = ServiceState::Stopped;
+                info!(srv = %name, "orchestrator: stopping...");
             }
         }
     }
@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, OnceLock};
+    use std::time::Duration;
+
+    use crate::governor::api::ServiceState;
+    use crate::governor::{Config, Governor, GovernorError, Orchestrator, Service, Transport};
+    use crate::workers::{CallFreq, WorkerConfig};
+
+    /// Service counting the start signals it gets; its serve panics when `panics` is set.
+    struct TestService {
+        name: String,
+        panics: bool,
+        starts: Arc<AtomicUsize>,
+        transport: OnceLock<Arc<dyn Transport>>,
+    }
+
+    impl TestService {
+        fn new(name: &str, panics: bool) -> Arc<Self> {
+            Arc::new(Self {
+                name: name.to_string(),
+                panics,
+                starts: Arc::new(AtomicUsize::new(0)),
+                transport: OnceLock::new(),
+            })
+        }
+    }
+
+    impl Service for TestService {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn cfg(&self) -> Arc<dyn Config> {
+            Arc::new(WorkerConfig::new(true, Arc::new(CallFreq::new(0, Duration::from_millis(10))), 1))
+        }
+
+        fn replicas(&self) -> usize {
+            0
+        }
+
+        fn serve(&self, t: Arc<dyn Transport>) {
+            if self.panics {
+                panic!("{} cannot serve", self.name);
+            }
+            let _ = self.transport.set(t.clone());
+            let starts = self.starts.clone();
+            tokio::spawn(async move {
+                loop {
+                    t.on_start().await;
+                    starts.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+
+        fn transport(&self) -> Arc<dyn Transport> {
+            self.transport.get().expect("transport not initialized").clone()
+        }
+    }
+
+    /// Test that a service whose serve panics is reported as failed at registration and
+    /// refuses signals instead of dropping them.
+    #[tokio::test]
+    async fn test_panicking_serve_is_surfaced() {
+        let gov = Orchestrator::new();
+        let err = gov.register("broken".to_string(), TestService::new("broken", true)).unwrap_err();
+        assert_eq!(err, GovernorError::TransportClosed { name: "broken".to_string(), action: "serve" });
+        assert!(err.to_string().contains("broken"), "{}", err);
+
+        assert_eq!(gov.status("broken").unwrap().state, ServiceState::Failed);
+        assert!(matches!(gov.start("broken"), Err(GovernorError::TransportClosed { action: "start", .. })));
+        assert!(matches!(gov.on("broken"), Err(GovernorError::TransportClosed { action: "turn on", .. })));
+    }
+
+    /// Test the lifecycle errors: unknown services, double registration or start, and signals
+    /// to stopped services.
+    #[tokio::test]
+    async fn test_lifecycle_errors() {
+        let gov = Orchestrator::new();
+        let srv = TestService::new("worker", false);
+        assert_eq!(gov.start("worker").unwrap_err(), GovernorError::ServiceNotRegistered("worker".to_string()));
+        assert!(matches!(gov.status("worker"), Err(GovernorError::ServiceNotRegistered(_))));
+
+        gov.register("worker".to_string(), srv.clone()).unwrap();
+        assert_eq!(gov.status("worker").unwrap().state, ServiceState::Registered);
+        assert_eq!(
+            gov.register("worker".to_string(), TestService::new("worker", false)).unwrap_err(),
+            GovernorError::AlreadyRunning("worker".to_string())
+        );
+
+        gov.start("worker").unwrap();
+        assert_eq!(gov.start("worker").unwrap_err(), GovernorError::AlreadyRunning("worker".to_string()));
+        let status = gov.status("worker").unwrap();
+        assert_eq!((status.name.as_str(), status.state), ("worker", ServiceState::Running));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(srv.starts.load(Ordering::Relaxed), 1);
+
+        gov.stop();
+        assert_eq!(gov.status("worker").unwrap().state, ServiceState::Stopped);
+        assert!(matches!(gov.scale_to("worker", 2), Err(GovernorError::TransportClosed { action: "scale", .. })));
+    }
+}
@@ -5,7 +5,10 @@ pub mod governor;
 pub mod service;
 pub mod transport;
 
-pub use api::Governor;
+pub use api::{Governor, GovernorError};
 pub use governor::Orchestrator;
 pub use service::{Config, Freq, Service};
 pub use transport::Transport;
+
+#[cfg(test)]
+mod governor_test;
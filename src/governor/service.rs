@@ -67,12 +67,12 @@ pub trait Service: Send + Sync {
     fn cfg(&self) -> Arc<dyn Config>;
 
     /// Gets the number of replicas.
-    #[allow(dead_code)]
     fn replicas(&self) -> usize;
 
     /// Serves the service with the given transport.
     fn serve(&self, t: Arc<dyn Transport>);
 
     /// Gets the transport for the service.
+    #[allow(dead_code)]
     fn transport(&self) -> Arc<dyn Transport>;
 }
@@ -0,0 +1,117 @@
+//! Connector timing the connect phase of upstream requests.
+//!
+//! The pooled client connects inside `request()`, so the request cannot tell how long it
+//! waited for a connection and how long for the response head. [`TimedConnector`] bounds the
+//! connect phase (TCP connect and TLS handshake) and reports it to the request that started
+//! it through [`ConnectWatch`], which the TTFB timer uses to start counting once connected.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use hyper::Uri;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tower::Service;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The connection could not be established within `connect_timeout`.
+#[derive(Debug, thiserror::Error)]
+#[error("connect timed out after {0:?}")]
+pub struct ConnectTimedOut(pub Duration);
+
+/// Connect phase of a request, as seen from the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectState {
+    /// No connect was started for the request yet: it may get a pooled connection.
+    Pending,
+    /// A connection is being established for the request.
+    Connecting,
+    /// The connect started for the request finished, or failed, at that instant.
+    Settled(Instant),
+}
+
+tokio::task_local! {
+    static WATCH: Arc<watch::Sender<ConnectState>>;
+}
+
+/// Connect state of the requests run through [`ConnectWatch::scope`].
+pub struct ConnectWatch {
+    tx: Arc<watch::Sender<ConnectState>>,
+}
+
+impl ConnectWatch {
+    pub fn new() -> Self {
+        Self { tx: Arc::new(watch::Sender::new(ConnectState::Pending)) }
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<ConnectState> {
+        self.tx.subscribe()
+    }
+
+    /// Runs `fut`, connects it starts inline being reported to this watch.
+    pub async fn scope<F: Future>(&self, fut: F) -> F::Output {
+        WATCH.scope(self.tx.clone(), fut).await
+    }
+}
+
+impl Default for ConnectWatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Connector bounding `inner` by `timeout` when set, and reporting connects to the
+/// [`ConnectWatch`] of the request that started them.
+#[derive(Debug, Clone)]
+pub struct TimedConnector<C> {
+    inner: C,
+    timeout: Option<Duration>,
+}
+
+impl<C> TimedConnector<C> {
+    pub fn new(inner: C, timeout: Option<Duration>) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+impl<C> Service<Uri> for TimedConnector<C>
+where
+    C: Service<Uri> + Send,
+    C::Future: Send + 'static,
+    C::Error: Into<BoxError>,
+{
+    type Response = C::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<C::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        // Called from the request's task when the connect is not raced by a pooled connection.
+        let watch = WATCH.try_with(|tx| tx.clone()).ok();
+        if let Some(ref tx) = watch {
+            tx.send_replace(ConnectState::Connecting);
+        }
+        let connecting = self.inner.call(uri);
+        let timeout = self.timeout;
+        Box::pin(async move {
+            let result = match timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, connecting).await {
+                    Ok(result) => result.map_err(Into::into),
+                    Err(_) => Err(ConnectTimedOut(timeout).into()),
+                },
+                None => connecting.await.map_err(Into::into),
+            };
+            if let Some(tx) = watch {
+                tx.send_replace(ConnectState::Settled(Instant::now()));
+            }
+            result
+        })
+    }
+}
@@ -2,8 +2,8 @@
 //!
 //! Implements optimized connection pool settings for highload scenarios:
 //! - Max connections per host: 2048 (idle pool)
-//! - Max idle connection duration: 30s
-//! - Connection timeout: 3s
+//! - Max idle connection duration: 30s, enforced by the pool timer
+//! - Connection timeout: 3s (TCP connect), or `backend.connect_timeout` over connect and TLS
 //! - TCP keep-alive: 30s
 //! - TCP_NODELAY: enabled
 //! - HTTP/2 optimizations: adaptive window, large initial window, keep-alive
@@ -12,15 +12,19 @@
 use std::time::Duration;
 use hyper_util::client::legacy::Client;
 use hyper_util::client::legacy::connect::HttpConnector;
-use hyper_util::rt::TokioExecutor;
+use hyper_util::rt::{TokioExecutor, TokioTimer};
 use hyper_rustls::HttpsConnector;
 use hyper_util::client::legacy::connect::dns::GaiResolver;
 use http_body_util::combinators::BoxBody;
 use hyper::body::Bytes;
 
+use super::connect::TimedConnector;
+
 /// Connection pool configuration constants.
 pub const CONNS_PER_HOST: usize = 2048;
 pub const MAX_IDLE_CONN_DURATION: Duration = Duration::from_secs(30);
+/// TCP connect timeout when `backend.connect_timeout` is unset.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
 #[allow(dead_code)]
 pub const MAX_CONN_WAIT_TIMEOUT: Duration = Duration::from_millis(500);
 #[allow(dead_code)]
@@ -43,13 +47,20 @@ pub const MAX_IDEMPOTENT_CALL_ATTEMPTS: u32 = 3;
 /// Note: `pool_max_idle_per_host` limits idle connections only. Active connections
 /// are separate and not counted toward this limit. The actual limit on total
 /// connections is determined by OS file descriptor limits and connection reuse.
-pub fn create_client() -> Client<HttpsConnector<HttpConnector<GaiResolver>>, BoxBody<Bytes, hyper::Error>> {
+pub fn create_client() -> HyperClient {
+    create_client_with(None)
+}
+
+/// Creates the client with `connect_timeout` bounding TCP connect and TLS handshake together.
+pub fn create_client_with(connect_timeout: Option<Duration>) -> HyperClient {
     let resolver = GaiResolver::new();
     
     let mut http_connector = HttpConnector::new_with_resolver(resolver);
+    // The TLS layer hands https URIs down to the TCP connector.
+    http_connector.enforce_http(false);
     http_connector.set_nodelay(true);
     http_connector.set_keepalive(Some(Duration::from_secs(30)));
-    http_connector.set_connect_timeout(Some(Duration::from_secs(3)));
+    http_connector.set_connect_timeout(Some(connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT)));
     
     // Use HTTP/1.1 only to ensure Host header is sent as HTTP/1.1 header, not :authority
     let tls = hyper_rustls::HttpsConnectorBuilder::new()
@@ -60,13 +71,16 @@ pub fn create_client() -> Client<HttpsConnector<HttpConnector<GaiResolver>>, Box
         .wrap_connector(http_connector);
     
     Client::builder(TokioExecutor::new())
+        // Without a timer the pool never reaps idle connections, it only skips expired ones
+        // on checkout: after a burst they stay open until traffic comes back.
+        .pool_timer(TokioTimer::new())
         .pool_idle_timeout(MAX_IDLE_CONN_DURATION)
         .pool_max_idle_per_host(CONNS_PER_HOST)
         .http1_title_case_headers(false)
         .http1_allow_obsolete_multiline_headers_in_responses(true)
         .retry_canceled_requests(true)
         
-        .build(tls)
+        .build(TimedConnector::new(tls, connect_timeout))
 }
 
-pub type HyperClient = Client<HttpsConnector<HttpConnector<GaiResolver>>, BoxBody<Bytes, hyper::Error>>;
+pub type HyperClient = Client<TimedConnector<HttpsConnector<HttpConnector<GaiResolver>>>, BoxBody<Bytes, hyper::Error>>;
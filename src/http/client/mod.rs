@@ -1,4 +1,5 @@
+pub mod connect;
 pub mod hyper_client;
 pub mod tls;
 
-pub use hyper_client::{create_client, HyperClient};
+pub use hyper_client::{create_client, create_client_with, HyperClient};
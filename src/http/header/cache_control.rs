@@ -0,0 +1,67 @@
+//! Freshness an origin gives its response in `Cache-Control` and `Expires`, followed by rules
+//! with `cache_value.respect_cache_control`.
+//!
+//! The cache is shared, so `private` is taken as `no-store` and `s-maxage` wins over
+//! `max-age`. `no-cache` asks for a revalidation before every reuse: the response is stored
+//! already expired, for the lifetime manager to refresh. `Expires` counts only without any
+//! of those, from the response `Date` when it has one; an unparsable date is in the past.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Freshness of an origin response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// `no-store` or `private`: the response is not stored.
+    NoStore,
+    /// Fresh for this long; zero for a response stored already expired.
+    Fresh(Duration),
+    /// Neither header says anything: the rule's TTL applies.
+    Unspecified,
+}
+
+/// Freshness of a response with the given headers, received at `now`.
+pub fn freshness<K: AsRef<str>, V: AsRef<str>>(headers: &[(K, V)], now: SystemTime) -> Freshness {
+    let values = |name: &'static str| {
+        headers
+            .iter()
+            .filter(move |(k, _)| k.as_ref().eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_ref())
+    };
+
+    let (mut no_cache, mut max_age, mut s_maxage) = (false, None, None);
+    for directive in values("cache-control").flat_map(|v| v.split(',')) {
+        let (name, arg) = match directive.split_once('=') {
+            Some((name, arg)) => (name.trim(), Some(arg.trim().trim_matches('"'))),
+            None => (directive.trim(), None),
+        };
+        let seconds = || arg.and_then(|a| a.parse::<u64>().ok()).map(Duration::from_secs);
+        if name.eq_ignore_ascii_case("no-store") || name.eq_ignore_ascii_case("private") {
+            return Freshness::NoStore;
+        } else if name.eq_ignore_ascii_case("no-cache") {
+            no_cache = true;
+        } else if name.eq_ignore_ascii_case("s-maxage") {
+            s_maxage = s_maxage.or_else(seconds);
+        } else if name.eq_ignore_ascii_case("max-age") {
+            max_age = max_age.or_else(seconds);
+        }
+    }
+    if no_cache {
+        return Freshness::Fresh(Duration::ZERO);
+    }
+    if let Some(ttl) = s_maxage.or(max_age) {
+        return Freshness::Fresh(ttl);
+    }
+
+    let Some(expires) = values("expires").next() else {
+        return Freshness::Unspecified;
+    };
+    let since = values("date").next().and_then(http_date).unwrap_or(now);
+    let ttl = http_date(expires).and_then(|expires| expires.duration_since(since).ok());
+    Freshness::Fresh(ttl.unwrap_or(Duration::ZERO))
+}
+
+/// Parses an HTTP date (`Sun, 06 Nov 1994 08:49:37 GMT`).
+pub(crate) fn http_date(value: &str) -> Option<SystemTime> {
+    let secs = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?.timestamp();
+    u64::try_from(secs).ok().map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+}
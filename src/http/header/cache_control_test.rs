@@ -0,0 +1,49 @@
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use crate::http::header::cache_control::{freshness, Freshness};
+
+    /// Sun, 06 Nov 1994 08:49:37 GMT.
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(784111777)
+    }
+
+    fn of(headers: &[(&str, &str)]) -> Freshness {
+        freshness(headers, now())
+    }
+
+    /// Test that the Cache-Control directives map to the freshness of a shared cache.
+    #[test]
+    fn test_cache_control_directives() {
+        assert_eq!(of(&[]), Freshness::Unspecified);
+        assert_eq!(of(&[("Content-Type", "application/json")]), Freshness::Unspecified);
+        assert_eq!(of(&[("Cache-Control", "public")]), Freshness::Unspecified);
+        assert_eq!(of(&[("Cache-Control", "no-store")]), Freshness::NoStore);
+        assert_eq!(of(&[("cache-control", "max-age=60, Private")]), Freshness::NoStore);
+        assert_eq!(of(&[("Cache-Control", "max-age=60")]), Freshness::Fresh(Duration::from_secs(60)));
+        assert_eq!(of(&[("Cache-Control", "public, max-age=\"30\"")]), Freshness::Fresh(Duration::from_secs(30)));
+        assert_eq!(of(&[("Cache-Control", "max-age=0")]), Freshness::Fresh(Duration::ZERO));
+        assert_eq!(of(&[("Cache-Control", "no-cache, max-age=60")]), Freshness::Fresh(Duration::ZERO));
+        assert_eq!(of(&[("Cache-Control", "max-age=60, s-maxage=600")]), Freshness::Fresh(Duration::from_secs(600)));
+        assert_eq!(
+            of(&[("Cache-Control", "public"), ("Cache-Control", "s-maxage = 5")]),
+            Freshness::Fresh(Duration::from_secs(5)),
+            "directives of repeated headers add up"
+        );
+        assert_eq!(of(&[("Cache-Control", "max-age=soon")]), Freshness::Unspecified, "an invalid max-age is ignored");
+    }
+
+    /// Test that Expires counts from the response Date or the receive time, and only without
+    /// Cache-Control lifetimes.
+    #[test]
+    fn test_expires() {
+        let expires = ("Expires", "Sun, 06 Nov 1994 08:59:37 GMT");
+        assert_eq!(of(&[expires]), Freshness::Fresh(Duration::from_secs(600)));
+        assert_eq!(of(&[expires, ("Date", "Sun, 06 Nov 1994 08:58:37 GMT")]), Freshness::Fresh(Duration::from_secs(60)));
+        assert_eq!(of(&[expires, ("Cache-Control", "max-age=5")]), Freshness::Fresh(Duration::from_secs(5)));
+        assert_eq!(of(&[expires, ("Cache-Control", "no-store")]), Freshness::NoStore);
+        assert_eq!(of(&[("Expires", "Sun, 06 Nov 1994 08:00:00 GMT")]), Freshness::Fresh(Duration::ZERO), "already past");
+        assert_eq!(of(&[("Expires", "0")]), Freshness::Fresh(Duration::ZERO), "an invalid date is in the past");
+    }
+}
@@ -0,0 +1,42 @@
+//! Conditional requests of clients revalidating a response served from the cache.
+//!
+//! `If-None-Match` is compared weakly with the entry's ETag, as RFC 9110 has it for a GET:
+//! `W/` prefixes do not count and `*` matches any stored response. `If-Modified-Since` is only
+//! looked at without `If-None-Match`, against the stored `Last-Modified`.
+
+use super::cache_control::http_date;
+
+/// Whether a request with the given headers is answered 304 for a stored response with
+/// `etag` and `last_modified`. Without a validator to compare with, never.
+pub fn is_not_modified<K: AsRef<str>, V: AsRef<str>>(
+    request_headers: &[(K, V)],
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> bool {
+    let header = |name: &str| {
+        request_headers
+            .iter()
+            .find(|(k, _)| k.as_ref().eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_ref())
+    };
+
+    if let Some(if_none_match) = header("if-none-match") {
+        let Some(etag) = etag else {
+            return false;
+        };
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || opaque(tag) == opaque(etag));
+    }
+
+    match (header("if-modified-since").and_then(http_date), last_modified.and_then(http_date)) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+/// Opaque tag of an entity tag, without the weakness indicator.
+fn opaque(tag: &str) -> &str {
+    tag.trim().strip_prefix("W/").unwrap_or(tag.trim())
+}
@@ -0,0 +1,39 @@
+#[cfg(test)]
+mod tests {
+    use crate::http::header::conditional::is_not_modified;
+
+    const ETAG: Option<&str> = Some("\"00ab\"");
+    const MODIFIED: Option<&str> = Some("Sun, 06 Nov 1994 08:49:37 GMT");
+
+    /// Test that If-None-Match matches the ETag weakly, in a list or as `*`, and never
+    /// without a stored ETag.
+    #[test]
+    fn test_if_none_match() {
+        assert!(is_not_modified(&[("If-None-Match", "\"00ab\"")], ETAG, None));
+        assert!(is_not_modified(&[("if-none-match", "W/\"00ab\"")], ETAG, None));
+        assert!(is_not_modified(&[("If-None-Match", "\"ffff\", \"00ab\"")], ETAG, None));
+        assert!(is_not_modified(&[("If-None-Match", "*")], ETAG, None));
+        assert!(!is_not_modified(&[("If-None-Match", "\"ffff\"")], ETAG, None));
+        assert!(!is_not_modified(&[("If-None-Match", "00ab")], ETAG, None), "an unquoted tag is another tag");
+        assert!(!is_not_modified(&[("If-None-Match", "*")], None, MODIFIED), "entries without an ETag are sent whole");
+        assert!(!is_not_modified::<&str, &str>(&[], ETAG, MODIFIED));
+    }
+
+    /// Test that If-Modified-Since compares with Last-Modified, only without If-None-Match.
+    #[test]
+    fn test_if_modified_since() {
+        assert!(is_not_modified(&[("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")], None, MODIFIED));
+        assert!(is_not_modified(&[("If-Modified-Since", "Mon, 07 Nov 1994 08:00:00 GMT")], None, MODIFIED));
+        assert!(!is_not_modified(&[("If-Modified-Since", "Sat, 05 Nov 1994 08:00:00 GMT")], None, MODIFIED));
+        assert!(!is_not_modified(&[("If-Modified-Since", "yesterday")], None, MODIFIED));
+        assert!(!is_not_modified(&[("If-Modified-Since", "Mon, 07 Nov 1994 08:00:00 GMT")], None, None));
+        assert!(
+            !is_not_modified(
+                &[("If-None-Match", "\"ffff\""), ("If-Modified-Since", "Mon, 07 Nov 1994 08:00:00 GMT")],
+                ETAG,
+                MODIFIED
+            ),
+            "If-None-Match takes precedence"
+        );
+    }
+}
@@ -1,13 +1,20 @@
 //! HTTP header filtering.
 
+use std::borrow::Cow;
+
 use crate::config::Rule;
 use crate::sort::key_value::kv_slice;
 
 /// Filters and sorts request headers based on rule configuration.
-pub fn filter_and_sort_request(
+/// Takes owned or borrowed pairs; names are matched case-insensitively.
+pub fn filter_and_sort_request<K, V>(
     rule: Option<&Rule>,
-    headers: &[(String, String)],
-) -> Vec<(Vec<u8>, Vec<u8>)> {
+    headers: &[(K, V)],
+) -> Vec<(Vec<u8>, Vec<u8>)>
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
     let mut out = Vec::with_capacity(32);
     
     if rule.is_none() {
@@ -25,8 +32,14 @@ pub fn filter_and_sort_request(
     }
 
     for (k, v) in headers {
-        let k_lower = k.to_lowercase();
-        if allowed_map.contains_key(&k_lower) {
+        let (k, v) = (k.as_ref(), v.as_ref());
+        // Names off the wire are lowercase already; only others are lowered into a new String.
+        let k_lower = if k.bytes().any(|b| b.is_ascii_uppercase()) {
+            Cow::Owned(k.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(k)
+        };
+        if allowed_map.contains_key(k_lower.as_ref()) {
             out.push((k.as_bytes().to_vec(), v.as_bytes().to_vec()));
         }
     }
@@ -17,14 +17,32 @@ mod tests {
             cache_key: RuleKey {
                 query: None,
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: Some(keys.into_iter().map(|s| s.to_string()).collect()),
                 headers_map: Some(headers_map),
             },
             cache_value: RuleValue {
                 headers: None,
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         }
     }
 
@@ -1,7 +1,13 @@
 //! HTTP header filtering functionality.
 
+pub mod cache_control;
 pub mod filter;
 
+#[cfg(test)]
+mod cache_control_test;
+pub mod conditional;
+#[cfg(test)]
+mod conditional_test;
 #[cfg(test)]
 mod filter_test;
 
@@ -18,4 +18,4 @@ pub use crate::middleware::recover_middleware::panics_counter;
 pub use server::{HttpServer, Server};
 
 // Common controller interface
-pub use crate::controller::controller::Controller;
+pub use crate::controller::controller::{Controller, Route};
@@ -1,12 +1,34 @@
 //! HTTP query parameter filtering.
 
-use crate::config::Rule;
+use std::borrow::Cow;
+
+use crate::config::{DuplicateQuery, Rule};
 use crate::sort::key_value::kv_slice;
 
+type KvPairs = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// A whitelisted query param was given more than once with different values under
+/// `duplicate_query: reject`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("query param {key:?} is given more than once with different values")]
+pub struct DuplicateQueryError {
+    pub key: String,
+}
+
 /// Normalizes percent encoding hex characters to lowercase (e.g., %2F -> %2f).
 /// This ensures case-insensitive percent encoding as per RFC 3986.
 /// url::form_urlencoded::parse normalizes to lowercase, so we match that behavior.
-fn normalize_percent_encoding(query_str: &str) -> String {
+/// Borrows the input when there is nothing to lower, which is the common case.
+fn normalize_percent_encoding(query_str: &str) -> Cow<'_, str> {
+    let bytes = query_str.as_bytes();
+    let has_upper_hex = bytes
+        .iter()
+        .enumerate()
+        .any(|(i, &b)| b == b'%' && bytes[i + 1..].iter().take(2).any(u8::is_ascii_uppercase));
+    if !has_upper_hex {
+        return Cow::Borrowed(query_str);
+    }
+
     let mut result = String::with_capacity(query_str.len());
     let mut chars = query_str.chars().peekable();
     
@@ -28,32 +50,90 @@ fn normalize_percent_encoding(query_str: &str) -> String {
         }
     }
     
-    result
+    Cow::Owned(result)
+}
+
+/// Matches a query param name against a glob pattern: `*` matches any run of bytes, `?` one byte.
+pub fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and the name position it currently covers up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, covered)) => {
+                    p = star + 1;
+                    n = covered + 1;
+                    backtrack = Some((star, covered + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Reports whether the query param name matches one of the `query_ignore` patterns.
+pub fn is_ignored(patterns: &[String], name: &[u8]) -> bool {
+    patterns.iter().any(|p| glob_match(p.as_bytes(), name))
+}
+
+/// Returns the request query params dropped by the rule's `query_ignore`, in request order.
+pub fn ignored_queries(rule: &Rule, query_str: &str) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let ignored = match rule.cache_key.query_ignore.as_deref() {
+        Some(patterns) if !patterns.is_empty() => patterns,
+        _ => return Vec::new(),
+    };
+
+    url::form_urlencoded::parse(query_str.trim_start_matches('?').as_bytes())
+        .filter(|(key, _)| is_ignored(ignored, key.as_bytes()))
+        .map(|(key, value)| (key.into_owned().into_bytes(), value.into_owned().into_bytes()))
+        .collect()
 }
 
 /// Filters and sorts request query parameters based on rule configuration.
-pub fn filter_and_sort_request(rule: Option<&Rule>, query_str: &str) -> Vec<(Vec<u8>, Vec<u8>)> {
+/// Params matching the rule's `query_ignore` are dropped before whitelisting, params given
+/// more than once are resolved by the rule's `duplicate_query`.
+pub fn filter_and_sort_request(
+    rule: Option<&Rule>,
+    query_str: &str,
+) -> Result<KvPairs, DuplicateQueryError> {
     let mut out = Vec::with_capacity(32);
     
     if rule.is_none() {
-        return out;
+        return Ok(out);
     }
 
     let rule = rule.unwrap();
     let allowed_keys = match &rule.cache_key.query_bytes {
         Some(keys) => keys,
-        None => return out,
+        None => return Ok(out),
     };
 
     if allowed_keys.is_empty() {
-        return out;
+        return Ok(out);
     }
 
     // Normalize percent encoding hex characters to ensure case-insensitive matching
     let normalized_query = normalize_percent_encoding(query_str.trim_start_matches('?'));
 
+    let ignored = rule.cache_key.query_ignore.as_deref().unwrap_or_default();
+
     for (key, value) in url::form_urlencoded::parse(normalized_query.as_bytes()) {
         let key_bytes = key.as_bytes();
+        if is_ignored(ignored, key_bytes) {
+            continue;
+        }
         if allowed_keys.iter().any(|k| k.as_slice() == key_bytes) {
             out.push((
                 key.into_owned().into_bytes(),
@@ -62,10 +142,53 @@ pub fn filter_and_sort_request(rule: Option<&Rule>, query_str: &str) -> Vec<(Vec
         }
     }
 
+    let mut out = resolve_duplicates(rule.cache_key.duplicate_query.unwrap_or_default(), out)?;
+
     // Sort if more than one entry using insertion sort
     if out.len() > 1 {
         kv_slice(&mut out);
     }
 
-    out
+    Ok(out)
+}
+
+/// Leaves one value per key, in request order of the keys' first appearance.
+fn resolve_duplicates(mode: DuplicateQuery, pairs: KvPairs) -> Result<KvPairs, DuplicateQueryError> {
+    // Repeated params are rare: keep the pairs as they are unless there is one.
+    if !pairs.iter().enumerate().any(|(i, (key, _))| pairs[..i].iter().any(|(k, _)| k == key)) {
+        return Ok(pairs);
+    }
+
+    // Whitelists are short: a linear scan beats hashing here.
+    let mut grouped: Vec<(Vec<u8>, Vec<Vec<u8>>)> = Vec::with_capacity(pairs.len());
+    for (key, value) in pairs {
+        match grouped.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, values)) => values.push(value),
+            None => grouped.push((key, vec![value])),
+        }
+    }
+
+    grouped
+        .into_iter()
+        .map(|(key, mut values)| {
+            if values.len() == 1 || values.iter().all(|v| *v == values[0]) {
+                return Ok((key, values.swap_remove(0)));
+            }
+            let value = match mode {
+                DuplicateQuery::First => values.swap_remove(0),
+                DuplicateQuery::Last => values.pop().unwrap_or_default(),
+                DuplicateQuery::Join => {
+                    values.sort();
+                    values.dedup();
+                    values.join(&b',')
+                }
+                DuplicateQuery::Reject => {
+                    return Err(DuplicateQueryError {
+                        key: String::from_utf8_lossy(&key).into_owned(),
+                    })
+                }
+            };
+            Ok((key, value))
+        })
+        .collect()
 }
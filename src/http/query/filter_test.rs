@@ -1,7 +1,8 @@
 #[cfg(test)]
 mod tests {
-    use crate::config::{Rule, RuleKey, RuleValue};
-    use crate::http::query::filter_and_sort_request;
+    use crate::config::{DuplicateQuery, Rule, RuleKey, RuleValue};
+    use crate::http::query::filter::glob_match;
+    use crate::http::query::{filter_and_sort_request, ignored_queries};
 
     fn make_rule_with_query_keys(keys: Vec<&str>) -> Rule {
         let query_bytes: Vec<Vec<u8>> = keys.iter().map(|k| k.as_bytes().to_vec()).collect();
@@ -11,14 +12,32 @@ mod tests {
             cache_key: RuleKey {
                 query: Some(keys.into_iter().map(|s| s.to_string()).collect()),
                 query_bytes: Some(query_bytes),
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: None,
                 headers_map: None,
             },
             cache_value: RuleValue {
                 headers: None,
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         }
     }
 
@@ -28,7 +47,7 @@ mod tests {
         let rule = make_rule_with_query_keys(vec!["user[id]", "domain"]);
         let query_str = "user[id]=123&domain=example.com&ignored=value&another=param";
 
-        let result = filter_and_sort_request(Some(&rule), query_str);
+        let result = filter_and_sort_request(Some(&rule), query_str).unwrap();
 
         assert_eq!(result.len(), 2);
         assert!(result.iter().any(|(k, v)| k == b"user[id]" && v == b"123"));
@@ -43,7 +62,7 @@ mod tests {
         let rule = make_rule_with_query_keys(vec!["zebra", "alpha", "middle"]);
         let query_str = "zebra=z&alpha=a&middle=m";
 
-        let result = filter_and_sort_request(Some(&rule), query_str);
+        let result = filter_and_sort_request(Some(&rule), query_str).unwrap();
 
         assert_eq!(result.len(), 3);
         // Should be sorted lexicographically by key
@@ -57,7 +76,7 @@ mod tests {
     fn test_filter_no_rule() {
         let query_str = "user[id]=123&domain=example.com";
 
-        let result = filter_and_sort_request(None, query_str);
+        let result = filter_and_sort_request(None, query_str).unwrap();
 
         assert_eq!(result.len(), 0);
     }
@@ -68,7 +87,7 @@ mod tests {
         let rule = make_rule_with_query_keys(vec![]);
         let query_str = "user[id]=123&domain=example.com";
 
-        let result = filter_and_sort_request(Some(&rule), query_str);
+        let result = filter_and_sort_request(Some(&rule), query_str).unwrap();
 
         assert_eq!(result.len(), 0);
     }
@@ -79,7 +98,7 @@ mod tests {
         let rule = make_rule_with_query_keys(vec!["user[id]"]);
         let query_str = "user[id]=123"; // No leading '?'
 
-        let result = filter_and_sort_request(Some(&rule), query_str);
+        let result = filter_and_sort_request(Some(&rule), query_str).unwrap();
 
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].0, b"user[id]");
@@ -92,7 +111,7 @@ mod tests {
         let rule = make_rule_with_query_keys(vec!["user[id]"]);
         let query_str = "?user[id]=123"; // With leading '?'
 
-        let result = filter_and_sort_request(Some(&rule), query_str);
+        let result = filter_and_sort_request(Some(&rule), query_str).unwrap();
 
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].0, b"user[id]");
@@ -105,7 +124,7 @@ mod tests {
         let rule = make_rule_with_query_keys(vec!["domain"]);
         let query_str = "domain=example%2Ecom%20with%20spaces";
 
-        let result = filter_and_sort_request(Some(&rule), query_str);
+        let result = filter_and_sort_request(Some(&rule), query_str).unwrap();
 
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].0, b"domain");
@@ -113,18 +132,93 @@ mod tests {
         assert_eq!(result[0].1, b"example.com with spaces");
     }
 
-    /// Test that duplicate keys are all included (not deduplicated).
+    fn with_duplicate_query(mode: Option<DuplicateQuery>) -> Rule {
+        let mut rule = make_rule_with_query_keys(vec!["lang", "user[id]", "user[name]"]);
+        rule.cache_key.duplicate_query = mode;
+        rule
+    }
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        items.iter().map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec())).collect()
+    }
+
+    /// Test that the last value wins by default, whatever the client's parameter order.
     #[test]
-    fn test_filter_duplicate_keys() {
-        let rule = make_rule_with_query_keys(vec!["user[id]"]);
-        let query_str = "user[id]=123&user[id]=456";
+    fn test_duplicate_query_last_by_default() {
+        let rule = with_duplicate_query(None);
 
-        let result = filter_and_sort_request(Some(&rule), query_str);
+        let result = filter_and_sort_request(Some(&rule), "lang=en&user[id]=1&lang=de").unwrap();
+        assert_eq!(result, pairs(&[("lang", "de"), ("user[id]", "1")]));
 
-        // url::form_urlencoded::parse returns all pairs, including duplicates
-        assert_eq!(result.len(), 2);
-        assert!(result.iter().any(|(k, v)| k == b"user[id]" && v == b"123"));
-        assert!(result.iter().any(|(k, v)| k == b"user[id]" && v == b"456"));
+        let explicit = with_duplicate_query(Some(DuplicateQuery::Last));
+        let reordered = filter_and_sort_request(Some(&explicit), "user[id]=1&lang=en&lang=de").unwrap();
+        assert_eq!(reordered, result);
+    }
+
+    /// Test that `first` keeps the first value in request order.
+    #[test]
+    fn test_duplicate_query_first() {
+        let rule = with_duplicate_query(Some(DuplicateQuery::First));
+
+        let result = filter_and_sort_request(Some(&rule), "lang=en&lang=de&lang=fr").unwrap();
+
+        assert_eq!(result, pairs(&[("lang", "en")]));
+    }
+
+    /// Test that `join` gives the same key for any order of the values and drops repeats.
+    #[test]
+    fn test_duplicate_query_join() {
+        let rule = with_duplicate_query(Some(DuplicateQuery::Join));
+
+        let a = filter_and_sort_request(Some(&rule), "lang=en&lang=de&lang=en").unwrap();
+        let b = filter_and_sort_request(Some(&rule), "lang=de&lang=en").unwrap();
+
+        assert_eq!(a, pairs(&[("lang", "de,en")]));
+        assert_eq!(a, b);
+    }
+
+    /// Test that `reject` fails on conflicting values only: repeating the same value is fine.
+    #[test]
+    fn test_duplicate_query_reject() {
+        let rule = with_duplicate_query(Some(DuplicateQuery::Reject));
+
+        let err = filter_and_sort_request(Some(&rule), "lang=en&lang=de").unwrap_err();
+        assert_eq!(err.key, "lang");
+
+        let result = filter_and_sort_request(Some(&rule), "lang=en&lang=en").unwrap();
+        assert_eq!(result, pairs(&[("lang", "en")]));
+    }
+
+    /// Test bracketed keys: an encoded `user%5Bid%5D` is the same param as `user[id]`, while
+    /// `user[id]` and `user[name]` are different params.
+    #[test]
+    fn test_duplicate_query_bracketed_keys() {
+        let query_str = "user[id]=1&user[name]=bob&user%5Bid%5D=2";
+
+        let last = filter_and_sort_request(Some(&with_duplicate_query(None)), query_str).unwrap();
+        assert_eq!(last, pairs(&[("user[id]", "2"), ("user[name]", "bob")]));
+
+        let joined = filter_and_sort_request(Some(&with_duplicate_query(Some(DuplicateQuery::Join))), query_str).unwrap();
+        assert_eq!(joined, pairs(&[("user[id]", "1,2"), ("user[name]", "bob")]));
+
+        let rejected = filter_and_sort_request(Some(&with_duplicate_query(Some(DuplicateQuery::Reject))), query_str);
+        assert_eq!(rejected.unwrap_err().key, "user[id]");
+
+        let distinct = filter_and_sort_request(
+            Some(&with_duplicate_query(Some(DuplicateQuery::Reject))),
+            "user[id]=1&user[name]=bob",
+        );
+        assert!(distinct.is_ok());
+    }
+
+    /// Test that values equal after decoding count as one value.
+    #[test]
+    fn test_duplicate_query_compares_decoded_values() {
+        let rule = with_duplicate_query(Some(DuplicateQuery::Reject));
+
+        let result = filter_and_sort_request(Some(&rule), "lang=en%2Dus&lang=en-us").unwrap();
+
+        assert_eq!(result, pairs(&[("lang", "en-us")]));
     }
 
     /// Test that empty values are included.
@@ -133,7 +227,7 @@ mod tests {
         let rule = make_rule_with_query_keys(vec!["user[id]", "domain"]);
         let query_str = "user[id]=&domain=example.com";
 
-        let result = filter_and_sort_request(Some(&rule), query_str);
+        let result = filter_and_sort_request(Some(&rule), query_str).unwrap();
 
         assert_eq!(result.len(), 2);
         let id_entry = result.iter().find(|(k, _)| k == b"user[id]").unwrap();
@@ -148,7 +242,7 @@ mod tests {
         let rule = make_rule_with_query_keys(vec!["user[id]", "Domain"]);
         let query_str = "user[id]=123&Domain=example.com&domain=ignored";
 
-        let result = filter_and_sort_request(Some(&rule), query_str);
+        let result = filter_and_sort_request(Some(&rule), query_str).unwrap();
 
         assert_eq!(result.len(), 2);
         assert!(result.iter().any(|(k, v)| k == b"user[id]" && v == b"123"));
@@ -163,10 +257,41 @@ mod tests {
         let rule = make_rule_with_query_keys(vec!["user[id]"]);
         let query_str = "user[id]=123";
 
-        let result = filter_and_sort_request(Some(&rule), query_str);
+        let result = filter_and_sort_request(Some(&rule), query_str).unwrap();
 
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].0, b"user[id]");
         assert_eq!(result[0].1, b"123");
     }
+
+    /// Test glob matching of query param names.
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"utm_*", b"utm_source"));
+        assert!(glob_match(b"utm_*", b"utm_"));
+        assert!(glob_match(b"fbclid", b"fbclid"));
+        assert!(glob_match(b"*id", b"fbclid"));
+        assert!(glob_match(b"a*b*c", b"axxbyybc"));
+        assert!(glob_match(b"ref?", b"ref1"));
+        assert!(glob_match(b"*", b""));
+        assert!(!glob_match(b"utm_*", b"xutm_source"));
+        assert!(!glob_match(b"fbclid", b"fbclid2"));
+        assert!(!glob_match(b"ref?", b"ref"));
+        assert!(!glob_match(b"a*b*c", b"axxbyyb"));
+    }
+
+    /// Test that ignored params are dropped even when whitelisted, and reported for forwarding.
+    #[test]
+    fn test_filter_drops_ignored_before_whitelist() {
+        let mut rule = make_rule_with_query_keys(vec!["user[id]", "utm_source"]);
+        rule.cache_key.query_ignore = Some(vec!["utm_*".to_string(), "fbclid".to_string()]);
+        let query_str = "utm_source=mail&user[id]=123&fbclid=abc&utm_campaign=x";
+
+        let result = filter_and_sort_request(Some(&rule), query_str).unwrap();
+        assert_eq!(result, vec![(b"user[id]".to_vec(), b"123".to_vec())]);
+
+        let ignored = ignored_queries(&rule, query_str);
+        let names: Vec<&[u8]> = ignored.iter().map(|(k, _)| k.as_slice()).collect();
+        assert_eq!(names, vec![&b"utm_source"[..], b"fbclid", b"utm_campaign"]);
+    }
 }
@@ -6,4 +6,4 @@ pub mod filter;
 mod filter_test;
 
 // Re-export
-pub use filter::filter_and_sort_request;
+pub use filter::{filter_and_sort_request, ignored_queries, DuplicateQueryError};
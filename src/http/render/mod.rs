@@ -1,2 +1,8 @@
+pub mod negotiate;
 pub mod renderer;
 pub mod templates;
+
+#[cfg(test)]
+mod negotiate_test;
+#[cfg(test)]
+mod renderer_test;
@@ -0,0 +1,144 @@
+//! Content negotiation of the admin responses.
+//!
+//! Admin endpoints answer JSON unless the `Accept` header prefers YAML (`application/yaml`,
+//! `application/x-yaml`, `text/yaml`) or, for list-like endpoints, a plain text table
+//! (`text/plain`). Media types are taken by q-value, then in the order given; types the
+//! endpoint cannot render are passed over, and when none is left the answer is JSON, never 406.
+
+use axum::{
+    http::{header::{ACCEPT, CONTENT_TYPE, VARY}, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_yaml::Value;
+
+const JSON: &str = "application/json; charset=utf-8";
+const YAML: &str = "application/yaml; charset=utf-8";
+const PLAIN: &str = "text/plain; charset=utf-8";
+
+/// Representation of an admin response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+    /// Aligned text table of the rows of a list-like response.
+    Table,
+}
+
+impl Format {
+    /// Format preferred by the `Accept` header; `Table` only when `tables` is set.
+    pub fn negotiate(headers: &HeaderMap, tables: bool) -> Self {
+        let mut ranges: Vec<(f32, Format)> = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|range| {
+                let mut parts = range.split(';').map(str::trim);
+                let format = match parts.next()?.to_ascii_lowercase().as_str() {
+                    "application/json" | "application/*" | "*/*" => Format::Json,
+                    "application/yaml" | "application/x-yaml" | "text/yaml" => Format::Yaml,
+                    "text/plain" if tables => Format::Table,
+                    _ => return None,
+                };
+                let q = parts
+                    .filter_map(|param| param.strip_prefix("q="))
+                    .find_map(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (q > 0.0).then_some((q, format))
+            })
+            .collect();
+        // Stable, so types of equal weight keep the order they were given in.
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranges.first().map_or(Format::Json, |(_, format)| *format)
+    }
+}
+
+/// Renders `value` as JSON or YAML, by the `Accept` header of the request.
+pub fn negotiated<T: Serialize>(headers: &HeaderMap, status: StatusCode, value: &T) -> Response {
+    render(Format::negotiate(headers, false), status, value, &[] as &[()])
+}
+
+/// Renders a list-like `value` as JSON, YAML or, for `text/plain`, a table of `rows`.
+pub fn negotiated_list<T: Serialize, R: Serialize>(headers: &HeaderMap, status: StatusCode, value: &T, rows: &[R]) -> Response {
+    render(Format::negotiate(headers, true), status, value, rows)
+}
+
+fn render<T: Serialize, R: Serialize>(format: Format, status: StatusCode, value: &T, rows: &[R]) -> Response {
+    let (content_type, body) = match format {
+        Format::Json => (JSON, serde_json::to_string(value).unwrap_or_default()),
+        Format::Yaml => (YAML, serde_yaml::to_string(value).unwrap_or_default()),
+        Format::Table => (PLAIN, table(rows)),
+    };
+    (status, [(CONTENT_TYPE, content_type), (VARY, "accept")], body).into_response()
+}
+
+/// Aligned text table of the rows, a column per field in declaration order; nested objects
+/// are flattened into dotted column names.
+pub fn table<R: Serialize>(rows: &[R]) -> String {
+    let rows: Vec<Vec<(String, String)>> = rows
+        .iter()
+        .map(|row| {
+            let mut cells = Vec::new();
+            flatten("", &serde_yaml::to_value(row).unwrap_or(Value::Null), &mut cells);
+            cells
+        })
+        .collect();
+
+    // Columns in the order they first appear.
+    let mut columns: Vec<String> = Vec::new();
+    for (name, _) in rows.iter().flatten() {
+        if !columns.contains(name) {
+            columns.push(name.clone());
+        }
+    }
+    let lines: Vec<Vec<&str>> = std::iter::once(columns.iter().map(String::as_str).collect())
+        .chain(rows.iter().map(|row| {
+            columns
+                .iter()
+                .map(|column| row.iter().find(|(name, _)| name == column).map_or("-", |(_, cell)| cell.as_str()))
+                .collect()
+        }))
+        .collect();
+
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| lines.iter().map(|line| line[i].chars().count()).max().unwrap_or(0))
+        .collect();
+    let mut out = String::new();
+    for line in lines {
+        let padded: Vec<String> = line.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        out.push_str(padded.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn flatten(prefix: &str, value: &Value, cells: &mut Vec<(String, String)>) {
+    let cell = match value {
+        Value::Mapping(fields) => {
+            for (name, field) in fields {
+                let name = scalar(name);
+                let name = match prefix {
+                    "" => name,
+                    _ => format!("{}.{}", prefix, name),
+                };
+                flatten(&name, field, cells);
+            }
+            return;
+        }
+        Value::Sequence(items) => items.iter().map(scalar).collect::<Vec<_>>().join(","),
+        other => scalar(other),
+    };
+    cells.push((prefix.to_string(), cell));
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        Value::Tagged(tagged) => scalar(&tagged.value),
+        other => serde_json::to_string(other).unwrap_or_default(),
+    }
+}
@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod tests {
+    use axum::http::{header::ACCEPT, HeaderMap, HeaderValue};
+    use serde::Serialize;
+
+    use crate::http::render::negotiate::{table, Format};
+
+    fn accept(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    /// Test that the format follows the media types by q-value then order, and falls back to
+    /// JSON for missing or unknown ones.
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(Format::negotiate(&HeaderMap::new(), true), Format::Json);
+        assert_eq!(Format::negotiate(&accept("application/yaml"), true), Format::Yaml);
+        assert_eq!(Format::negotiate(&accept("text/yaml"), false), Format::Yaml);
+        assert_eq!(Format::negotiate(&accept("text/plain"), true), Format::Table);
+        assert_eq!(Format::negotiate(&accept("image/png, text/csv"), true), Format::Json);
+        assert_eq!(Format::negotiate(&accept("application/json, application/yaml"), true), Format::Json);
+        assert_eq!(Format::negotiate(&accept("application/json;q=0.5, application/x-yaml"), true), Format::Yaml);
+        assert_eq!(Format::negotiate(&accept("Text/Plain; charset=utf-8, */*;q=0.1"), true), Format::Table);
+        assert_eq!(Format::negotiate(&accept("application/yaml;q=0, text/plain;q=0.2"), true), Format::Table);
+    }
+
+    /// Test that `text/plain` is passed over by endpoints without a table.
+    #[test]
+    fn test_negotiate_without_tables() {
+        assert_eq!(Format::negotiate(&accept("text/plain"), false), Format::Json);
+        assert_eq!(Format::negotiate(&accept("text/plain, application/yaml;q=0.5"), false), Format::Yaml);
+    }
+
+    #[derive(Serialize)]
+    struct Lock {
+        samples: u64,
+        max: u64,
+    }
+
+    #[derive(Serialize)]
+    struct Row {
+        id: u64,
+        name: Option<&'static str>,
+        lock: Lock,
+        tags: Vec<&'static str>,
+    }
+
+    /// Test that rows are rendered as aligned columns in field order, nested fields flattened.
+    #[test]
+    fn test_table() {
+        let rows = [
+            Row { id: 7, name: Some("seven"), lock: Lock { samples: 120, max: 3 }, tags: vec!["a", "b"] },
+            Row { id: 1024, name: None, lock: Lock { samples: 1, max: 40 }, tags: vec![] },
+        ];
+        assert_eq!(
+            table(&rows),
+            "id    name   lock.samples  lock.max  tags\n\
+             7     seven  120           3         a,b\n\
+             1024  -      1             40\n"
+        );
+        assert_eq!(table(&[] as &[Row]), "\n");
+    }
+}
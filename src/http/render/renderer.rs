@@ -1,52 +1,50 @@
+use std::io::Write;
+use std::time::Duration;
+
 use axum::{
-    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    http::{header::{AGE, CACHE_CONTROL, CONTENT_LENGTH, ETAG}, HeaderMap, HeaderName, HeaderValue, StatusCode},
     response::Response,
 };
 
+use crate::config::DownstreamTtl;
 use crate::model::Entry;
 
+use crate::http::header::conditional;
+use crate::http::utils::cache_status::{self, Diagnostics, XCache};
 use crate::http::utils::last_updated_at;
-
-/// Writes a response from raw data.
-pub fn write_from_raw_response(
-    headers: &[(Vec<u8>, Vec<u8>)],
-    body: &[u8],
-    code: u16,
-    updated_at: i64,
-) -> Response {
-    let mut header_map = HeaderMap::new();
-
-    // Set headers
-    for (k, v) in headers {
-        if let (Ok(name), Ok(value)) = (
-            HeaderName::try_from(k.as_slice()),
-            HeaderValue::from_bytes(v),
-        ) {
-            header_map.insert(name, value);
-        }
-    }
-
-    if let Some(last_updated) = last_updated_at::set_last_updated_at_value(updated_at) {
-        if let (Ok(name), Ok(value)) = (
-            HeaderName::try_from(last_updated_at::LAST_UPDATED_AT_KEY.as_bytes()),
-            HeaderValue::from_str(&last_updated),
-        ) {
-            header_map.insert(name, value);
-        }
+use crate::upstream::backend_headers::is_hop_by_hop;
+
+/// Header read by surrogate caches (CDNs) in place of `Cache-Control`.
+const SURROGATE_CONTROL: &str = "surrogate-control";
+
+/// Builds the final response with framing owned by the renderer.
+///
+/// Stored and upstream headers may carry a `Content-Length` of a different representation
+/// (or framing headers from an older dump), so any of them is dropped and the length is
+/// recomputed from the body actually sent. Layers that mutate the body afterwards (the
+/// compression middleware) remove it again and let hyper choose chunked transfer. Bodies
+/// are fully buffered, so a response is either written whole or the connection is closed.
+fn build_response(code: u16, mut header_map: HeaderMap, body: Vec<u8>) -> Response {
+    let framing: Vec<HeaderName> = header_map
+        .keys()
+        .filter(|name| is_hop_by_hop(name.as_str()))
+        .cloned()
+        .collect();
+    for name in framing {
+        header_map.remove(name);
     }
 
     header_map.insert(
-        HeaderName::from_static("content-length"),
+        CONTENT_LENGTH,
         HeaderValue::from_str(&body.len().to_string())
             .unwrap_or_else(|_| HeaderValue::from_static("0")),
     );
 
-    // Build response
     let status = StatusCode::from_u16(code).unwrap_or(StatusCode::OK);
 
     Response::builder()
         .status(status)
-        .body(body.to_vec().into())
+        .body(body.into())
         .map(|mut resp| {
             *resp.headers_mut() = header_map;
             resp
@@ -59,18 +57,112 @@ pub fn write_from_raw_response(
         })
 }
 
-/// Writes a response from a Response struct.
-pub fn write_from_response(resp: &crate::model::Response, last_refreshed_at: i64) -> Response {
+/// Builds the header map of stored (or about to be stored) headers.
+///
+/// Both the MISS render (`write_from_response`) and the HIT render (`write_from_raw_response`)
+/// go through here, so they send the same headers in the same order: values are appended in
+/// the order they were stored, and a repeated name (several `Set-Cookie`, `Via`) keeps every
+/// value instead of the last one. Headers the renderer sets itself are inserted afterwards.
+fn stored_header_map<'a>(headers: impl Iterator<Item = (&'a [u8], &'a [u8])>) -> HeaderMap {
     let mut header_map = HeaderMap::new();
+    for (k, v) in headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(k), HeaderValue::from_bytes(v)) {
+            header_map.append(name, value);
+        }
+    }
+    header_map
+}
+
+/// Builds the header map of raw stored headers, with the `Last-Updated-At` of `updated_at`.
+fn raw_header_map(headers: &[(Vec<u8>, Vec<u8>)], updated_at: i64) -> HeaderMap {
+    let mut header_map = stored_header_map(headers.iter().map(|(k, v)| (k.as_slice(), v.as_slice())));
 
-    // Set headers
-    for (k, v) in &resp.headers {
-        if let (Ok(name), Ok(value)) =
-            (HeaderName::try_from(k.as_bytes()), HeaderValue::from_str(v))
-        {
+    if let Some(last_updated) = last_updated_at::set_last_updated_at_value(updated_at) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::try_from(last_updated_at::LAST_UPDATED_AT_KEY.as_bytes()),
+            HeaderValue::from_str(&last_updated),
+        ) {
             header_map.insert(name, value);
         }
     }
+    header_map
+}
+
+/// Writes a response from raw data.
+pub fn write_from_raw_response(
+    headers: &[(Vec<u8>, Vec<u8>)],
+    body: &[u8],
+    code: u16,
+    updated_at: i64,
+) -> Response {
+    build_response(code, raw_header_map(headers, updated_at), body.to_vec())
+}
+
+/// Sets the ETag of the payload stored in `entry` on a response rendered from it, unless the
+/// origin's own `ETag` was stored with it.
+pub fn set_etag(response: &mut Response, entry: &Entry) {
+    let headers = response.headers_mut();
+    if headers.contains_key(ETAG) {
+        return;
+    }
+    if let Some(etag) = entry.etag() {
+        let mut buf = [0u8; ETAG_LEN];
+        if let Ok(value) = HeaderValue::from_bytes(format_etag(etag, &mut buf)) {
+            headers.insert(ETAG, value);
+        }
+    }
+}
+
+/// Length of a generated ETag: 16 hex digits, quoted.
+const ETAG_LEN: usize = 18;
+
+/// Writes the generated ETag of a body hashed to `etag` into `buf`, without allocating.
+fn format_etag(etag: u64, buf: &mut [u8; ETAG_LEN]) -> &[u8] {
+    let mut cursor = &mut buf[..];
+    let _ = write!(cursor, "\"{:016x}\"", etag);
+    &buf[..]
+}
+
+/// Sets the lifetime downstream caches may keep a response rendered from an entry of `age`
+/// (`cache_value.downstream_ttl`), replacing the stored `Cache-Control` and `Surrogate-Control`.
+/// A `stale` response, served past its TTL because the origin failed, must not be kept at all.
+pub fn set_downstream_ttl(response: &mut Response, policy: DownstreamTtl, ttl: Duration, age: Duration, stale: bool) {
+    let headers = response.headers_mut();
+    if stale && policy != DownstreamTtl::Off {
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("max-age=0, must-revalidate"));
+        headers.insert(SURROGATE_CONTROL, HeaderValue::from_static("max-age=0"));
+        return;
+    }
+    if let Some(secs) = policy.max_age(ttl, age) {
+        headers.insert(CACHE_CONTROL, secs_value("s-maxage=", secs));
+        headers.insert(SURROGATE_CONTROL, secs_value("max-age=", secs));
+    }
+}
+
+fn secs_value(directive: &str, secs: u64) -> HeaderValue {
+    HeaderValue::from_str(&format!("{}{}", directive, secs)).unwrap_or_else(|_| HeaderValue::from_static("max-age=0"))
+}
+
+/// Sets the diagnostic headers: `X-Cache`, `Age` and `X-Cache-Key`. A bypassed response keeps
+/// the `Age` of the origin.
+fn set_diagnostics(header_map: &mut HeaderMap, diagnostics: Diagnostics, age: Duration) {
+    header_map.insert(cache_status::X_CACHE_KEY, HeaderValue::from_static(diagnostics.cache.as_str()));
+    if diagnostics.cache != XCache::Bypass {
+        // Misses are the common zero, spared the allocation of a formatted value.
+        let age = match age.as_secs() {
+            0 => HeaderValue::from_static("0"),
+            secs => HeaderValue::from(secs),
+        };
+        header_map.insert(AGE, age);
+    }
+    if let Some(key) = diagnostics.key {
+        header_map.insert(cache_status::X_CACHE_KEY_KEY, HeaderValue::from(key));
+    }
+}
+
+/// Writes a response from a Response struct.
+pub fn write_from_response(resp: &crate::model::Response, last_refreshed_at: i64, diagnostics: Diagnostics) -> Response {
+    let mut header_map = stored_header_map(resp.headers.iter().map(|(k, v)| (k.as_bytes(), v.as_bytes())));
 
     // Set Last-Updated-At header
     if let Some(last_updated) = last_updated_at::set_last_updated_at_value(last_refreshed_at) {
@@ -82,33 +174,20 @@ pub fn write_from_response(resp: &crate::model::Response, last_refreshed_at: i64
         }
     }
 
-    header_map.insert(
-        HeaderName::from_static("content-length"),
-        HeaderValue::from_str(&resp.body.len().to_string())
-            .unwrap_or_else(|_| HeaderValue::from_static("0")),
-    );
-
-    // Build response
-    let status = StatusCode::from_u16(resp.status).unwrap_or(StatusCode::OK);
-
-    Response::builder()
-        .status(status)
-        .body(resp.body.clone().into())
-        .map(|mut resp| {
-            *resp.headers_mut() = header_map;
-            resp
-        })
-        .unwrap_or_else(|_| {
-            Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Vec::new().into())
-                .unwrap()
-        })
+    // Just fetched: stored now, or not stored at all.
+    set_diagnostics(&mut header_map, diagnostics, Duration::ZERO);
+    build_response(resp.status, header_map, resp.body.clone())
 }
 
-/// Writes a response from a cache entry.
+/// Writes a response from a cache entry, or an empty `304 Not Modified` when the client
+/// already holds it: its `If-None-Match` matches the entry's ETag (the origin's, or the one
+/// generated for the payload), or without one, its `If-Modified-Since` is not older than the
+/// stored `Last-Modified`. Payloads without a generated ETag, restored from a dump, are always
+/// sent whole.
 pub fn write_from_entry(
     entry: &Entry,
+    request_headers: &[(&str, &str)],
+    diagnostics: Diagnostics,
 ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
     let resp_payload = entry.response_payload()?;
 
@@ -117,5 +196,36 @@ pub fn write_from_entry(
     let code = resp_payload.code;
     let fresh_at = entry.fresh_at();
 
-    Ok(write_from_raw_response(&headers, &body, code, fresh_at))
+    let mut response = if code == 200 && is_not_modified(entry, &headers, request_headers) {
+        build_response(304, raw_header_map(&headers, fresh_at), Vec::new())
+    } else {
+        write_from_raw_response(&headers, &body, code, fresh_at)
+    };
+    // Read off the cached clock, as every entry timestamp.
+    set_diagnostics(response.headers_mut(), diagnostics, entry.age());
+    // Negative entries (404 and 5xx answers) are not revalidated.
+    if code == 200 {
+        set_etag(&mut response, entry);
+    }
+    Ok(response)
+}
+
+/// Whether the request revalidates the payload of `entry`, stored with `headers`.
+fn is_not_modified(entry: &Entry, headers: &[(Vec<u8>, Vec<u8>)], request_headers: &[(&str, &str)]) -> bool {
+    let revalidating = request_headers
+        .iter()
+        .any(|(k, _)| k.eq_ignore_ascii_case("if-none-match") || k.eq_ignore_ascii_case("if-modified-since"));
+    let Some(generated) = entry.etag().filter(|_| revalidating) else {
+        return false;
+    };
+    let mut buf = [0u8; ETAG_LEN];
+    let generated = std::str::from_utf8(format_etag(generated, &mut buf)).unwrap_or_default();
+    let stored = |name: &str| {
+        headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name.as_bytes()))
+            .and_then(|(_, v)| std::str::from_utf8(v).ok())
+    };
+    let etag = stored("etag").unwrap_or(generated);
+    conditional::is_not_modified(request_headers, Some(etag), stored("last-modified"))
 }
@@ -0,0 +1,232 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::config::{DownstreamTtl, Rule};
+    use crate::http::render::renderer::{
+        set_downstream_ttl, set_etag, write_from_entry, write_from_raw_response, write_from_response,
+    };
+    use crate::http::utils::cache_status::{Diagnostics, XCache};
+    use crate::model::{Entry, Response as ModelResponse};
+
+    const BODY: &[u8] = b"{\"id\":1,\"name\":\"stored entry body\"}";
+
+    const HIT: Diagnostics = Diagnostics { cache: XCache::Hit, key: None };
+
+    fn stale_framing_headers() -> Vec<(String, String)> {
+        vec![
+            ("content-type".to_string(), "application/json".to_string()),
+            ("content-length".to_string(), "5".to_string()),
+            ("transfer-encoding".to_string(), "chunked".to_string()),
+            ("connection".to_string(), "keep-alive".to_string()),
+        ]
+    }
+
+    fn assert_framing(resp: &axum::response::Response) {
+        let headers = resp.headers();
+        assert_eq!(headers.get("content-length").unwrap(), &BODY.len().to_string());
+        assert!(headers.get("transfer-encoding").is_none());
+        assert!(headers.get("connection").is_none());
+        assert_eq!(headers.get("content-type").unwrap(), "application/json");
+    }
+
+    /// Test that a stored Content-Length and framing headers never reach the client.
+    #[test]
+    fn test_write_from_entry_recomputes_content_length() {
+        let entry = Entry::new(Arc::new(Rule::bare("/api/v1/user")), &[], &[]);
+        entry.set_payload(
+            &[],
+            &[],
+            &ModelResponse {
+                status: 200,
+                headers: stale_framing_headers(),
+                body: BODY.to_vec(),
+            },
+        );
+
+        assert_framing(&write_from_entry(&entry, &[], HIT).unwrap());
+    }
+
+    /// Test that upstream and raw responses get the same framing treatment.
+    #[test]
+    fn test_write_from_response_and_raw_recompute_content_length() {
+        let resp = ModelResponse {
+            status: 200,
+            headers: stale_framing_headers(),
+            body: BODY.to_vec(),
+        };
+        assert_framing(&write_from_response(&resp, 0, HIT));
+
+        let raw: Vec<(Vec<u8>, Vec<u8>)> = stale_framing_headers()
+            .into_iter()
+            .map(|(k, v)| (k.into_bytes(), v.into_bytes()))
+            .collect();
+        assert_framing(&write_from_raw_response(&raw, BODY, 200, 0));
+    }
+
+    fn header_list(resp: &axum::response::Response) -> Vec<(String, String)> {
+        resp.headers()
+            .iter()
+            .filter(|(k, _)| !matches!(k.as_str(), "content-length" | "last-updated-at" | "age"))
+            .map(|(k, v)| (k.to_string(), v.to_str().unwrap().to_string()))
+            .collect()
+    }
+
+    /// Test that a response rendered from an entry carries the same headers, repeated names
+    /// included, in the same order as the response it was stored from.
+    #[test]
+    fn test_entry_render_keeps_header_order_and_repeats() {
+        let headers: Vec<(String, String)> = [
+            ("content-type", "application/json"),
+            ("set-cookie", "a=1; Path=/"),
+            ("set-cookie", "b=2; Path=/"),
+            ("via", "1.1 edge"),
+            ("via", "1.1 origin"),
+            ("x-content-digest", "sha1=abc"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let resp = ModelResponse { status: 200, headers: headers.clone(), body: BODY.to_vec() };
+        let entry = Entry::new(Arc::new(Rule::bare("/api/v1/user")), &[], &[]);
+        entry.set_payload(&[], &[], &resp);
+
+        let mut miss = write_from_response(&resp, 0, HIT);
+        set_etag(&mut miss, &entry);
+        let miss = header_list(&miss);
+        assert_eq!(miss[..headers.len()], headers[..]);
+        assert_eq!(header_list(&write_from_entry(&entry, &[], HIT).unwrap()), miss);
+    }
+
+    fn stored_entry(headers: &[(&str, &str)]) -> Entry {
+        let headers = headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let entry = Entry::new(Arc::new(Rule::bare("/api/v1/user")), &[], &[]);
+        entry.set_payload(&[], &[], &ModelResponse { status: 200, headers, body: BODY.to_vec() });
+        entry
+    }
+
+    fn etag(resp: &axum::response::Response) -> &str {
+        resp.headers().get("etag").expect("an ETag is sent").to_str().unwrap()
+    }
+
+    /// Test that an entry is sent with the ETag of its body, and answered 304 with an empty
+    /// body and a zero Content-Length when the client already holds it.
+    #[test]
+    fn test_write_from_entry_not_modified() {
+        let entry = stored_entry(&[("content-type", "application/json")]);
+        let resp = write_from_entry(&entry, &[], HIT).unwrap();
+        assert_eq!(resp.status(), 200);
+        let generated = etag(&resp).to_string();
+        assert_eq!(generated, format!("\"{:016x}\"", entry.etag().unwrap()));
+        assert_eq!(stored_entry(&[]).etag(), entry.etag(), "the same body has the same ETag");
+
+        let resp = write_from_entry(&entry, &[("If-None-Match", generated.as_str())], HIT).unwrap();
+        assert_eq!(resp.status(), 304);
+        assert_eq!(resp.headers().get("content-length").unwrap(), "0");
+        assert_eq!(etag(&resp), generated);
+        assert_eq!(resp.headers().get("content-type").unwrap(), "application/json");
+
+        let resp = write_from_entry(&entry, &[("If-None-Match", "\"0000000000000000\"")], HIT).unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers().get("content-length").unwrap(), &BODY.len().to_string());
+    }
+
+    /// Test that the origin's stored ETag and Last-Modified are the validators compared.
+    #[test]
+    fn test_write_from_entry_origin_validators() {
+        let entry = stored_entry(&[("etag", "\"v1\""), ("last-modified", "Sat, 17 Oct 2026 10:00:00 GMT")]);
+        assert_eq!(etag(&write_from_entry(&entry, &[], HIT).unwrap()), "\"v1\"");
+
+        let status = |headers: &[(&str, &str)]| write_from_entry(&entry, headers, HIT).unwrap().status();
+        assert_eq!(status(&[("if-none-match", "W/\"v1\"")]), 304);
+        let generated = format!("\"{:016x}\"", entry.etag().unwrap());
+        assert_eq!(status(&[("if-none-match", generated.as_str())]), 200);
+        assert_eq!(status(&[("if-modified-since", "Sat, 17 Oct 2026 10:00:00 GMT")]), 304);
+        assert_eq!(status(&[("if-modified-since", "Fri, 16 Oct 2026 10:00:00 GMT")]), 200);
+    }
+
+    /// Test that an entry restored from a dump, without a generated ETag, is sent whole.
+    #[test]
+    fn test_write_from_entry_without_etag() {
+        let stored = stored_entry(&[("etag", "\"v1\"")]);
+        let restored = Entry::from_field(1, 0, 1, 0, stored.payload_bytes(), Arc::new(Rule::bare("/api/v1/user")), 0);
+        assert_eq!(restored.etag(), None);
+
+        let resp = write_from_entry(&restored, &[("If-None-Match", "\"v1\"")], HIT).unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers().get("content-length").unwrap(), &BODY.len().to_string());
+    }
+
+    fn with_cache_control() -> axum::response::Response {
+        let raw = vec![(b"cache-control".to_vec(), b"private, max-age=5".to_vec())];
+        write_from_raw_response(&raw, BODY, 200, 0)
+    }
+
+    fn lifetime_headers(resp: &axum::response::Response) -> (Option<&str>, Option<&str>) {
+        let header = |name| resp.headers().get(name).map(|v| v.to_str().unwrap());
+        (header("cache-control"), header("surrogate-control"))
+    }
+
+    /// Test that `remaining` replaces the stored Cache-Control with what is left of the TTL,
+    /// clamped to 0 past it, and that `off` leaves the stored headers as they are.
+    #[test]
+    fn test_set_downstream_ttl() {
+        let ttl = Duration::from_secs(60);
+        for (age, expected) in [(0, 60), (15, 45), (60, 0), (90, 0)] {
+            let mut resp = with_cache_control();
+            set_downstream_ttl(&mut resp, DownstreamTtl::Remaining, ttl, Duration::from_secs(age), false);
+            let (cache_control, surrogate) = lifetime_headers(&resp);
+            assert_eq!(cache_control, Some(format!("s-maxage={}", expected).as_str()), "age {}s", age);
+            assert_eq!(surrogate, Some(format!("max-age={}", expected).as_str()), "age {}s", age);
+        }
+
+        let mut resp = with_cache_control();
+        set_downstream_ttl(&mut resp, DownstreamTtl::Off, ttl, Duration::ZERO, false);
+        assert_eq!(lifetime_headers(&resp), (Some("private, max-age=5"), None));
+    }
+
+    /// Test that a stale-served response must be revalidated whatever the policy, unless it is off.
+    #[test]
+    fn test_set_downstream_ttl_stale() {
+        for policy in [DownstreamTtl::Remaining, DownstreamTtl::Fixed(Duration::from_secs(30))] {
+            let mut resp = with_cache_control();
+            set_downstream_ttl(&mut resp, policy, Duration::from_secs(60), Duration::from_secs(75), true);
+            assert_eq!(lifetime_headers(&resp), (Some("max-age=0, must-revalidate"), Some("max-age=0")));
+        }
+
+        let mut resp = with_cache_control();
+        set_downstream_ttl(&mut resp, DownstreamTtl::Off, Duration::from_secs(60), Duration::from_secs(75), true);
+        assert_eq!(lifetime_headers(&resp), (Some("private, max-age=5"), None));
+    }
+
+    /// Test that the diagnostic headers tell how a response was served, that a bypassed one keeps
+    /// the `Age` of the origin, and that `X-Cache-Key` is only sent when a key is given.
+    #[test]
+    fn test_diagnostic_headers() {
+        let resp = ModelResponse {
+            status: 200,
+            headers: vec![("age".to_string(), "42".to_string())],
+            body: BODY.to_vec(),
+        };
+        let header = |resp: &axum::response::Response, name: &str| {
+            resp.headers().get(name).map(|v| v.to_str().unwrap().to_string())
+        };
+
+        let miss = write_from_response(&resp, 0, Diagnostics { cache: XCache::Miss, key: Some(7) });
+        assert_eq!(header(&miss, "x-cache").as_deref(), Some("MISS"));
+        assert_eq!(header(&miss, "age").as_deref(), Some("0"));
+        assert_eq!(header(&miss, "x-cache-key").as_deref(), Some("7"));
+
+        let bypass = write_from_response(&resp, 0, Diagnostics { cache: XCache::Bypass, key: None });
+        assert_eq!(header(&bypass, "x-cache").as_deref(), Some("BYPASS"));
+        assert_eq!(header(&bypass, "age").as_deref(), Some("42"));
+        assert_eq!(header(&bypass, "x-cache-key"), None);
+
+        let entry = Entry::new(Arc::new(Rule::bare("/api/v1/user")), &[], &[]);
+        entry.set_payload(&[], &[], &resp);
+        let stale = write_from_entry(&entry, &[], Diagnostics { cache: XCache::Stale, key: None }).unwrap();
+        assert_eq!(header(&stale, "x-cache").as_deref(), Some("STALE"));
+        assert!(header(&stale, "age").unwrap().parse::<u64>().is_ok(), "the age of the stored entry");
+    }
+}
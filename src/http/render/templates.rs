@@ -7,6 +7,20 @@ pub const UNAVAILABLE_RESPONSE_BODY: &[u8] = b"{
   \"message\": \"Sorry for that, please try again later or contact support.\"
 }";
 
+/// Bad gateway response body bytes.
+pub const BAD_GATEWAY_RESPONSE_BODY: &[u8] = b"{
+  \"status\": 502,
+  \"error\": \"Bad Gateway\",
+  \"message\": \"The upstream response could not be relayed.\"
+}";
+
+/// Loop detected response body bytes.
+pub const LOOP_DETECTED_RESPONSE_BODY: &[u8] = b"{
+  \"status\": 508,
+  \"error\": \"Loop Detected\",
+  \"message\": \"The request has already passed through this cache.\"
+}";
+
 /// Internal server error response body bytes.
 #[allow(dead_code)]
 pub const INTERNAL_SERVER_ERROR_RESPONSE_BODY: &[u8] = b"{
@@ -0,0 +1,143 @@
+//! Connection limits of the HTTP listeners.
+//!
+//! A listener serves up to `max_connections` connections at a time. Up to `connections_overflow`
+//! more are accepted and wait for a free slot. Once both are taken the listener stops accepting,
+//! leaving new connections in the kernel backlog, or, with `shed_retry_after` set, accepts them
+//! and answers `503` with `Retry-After` right away.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::Api;
+use crate::controller::metrics;
+use crate::http::render::templates::UNAVAILABLE_RESPONSE_BODY;
+
+/// How long a shed connection is given to send its request head and read the 503.
+const SHED_IO_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Listeners with limits of their own, labelling the connection metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Listener {
+    Api = 0,
+    Admin = 1,
+}
+
+impl Listener {
+    pub const ALL: [Listener; 2] = [Listener::Api, Listener::Admin];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Listener::Api => "api",
+            Listener::Admin => "admin",
+        }
+    }
+}
+
+/// Connection limit of one listener.
+pub struct ConnectionLimit {
+    listener: Listener,
+    /// Connections being served.
+    serving: Arc<Semaphore>,
+    /// Connections accepted, served or waiting for a serving slot.
+    accepted: Arc<Semaphore>,
+    retry_after: Option<Duration>,
+}
+
+/// A connection accepted within the limit, waiting for a serving slot.
+pub struct Reserved(OwnedSemaphorePermit);
+
+/// A connection being served; counted in the connections gauge while alive.
+pub struct Serving {
+    listener: Listener,
+    _serving: OwnedSemaphorePermit,
+    _accepted: OwnedSemaphorePermit,
+}
+
+impl Drop for Serving {
+    fn drop(&mut self) {
+        metrics::add_http_connections(self.listener, -1);
+    }
+}
+
+impl ConnectionLimit {
+    /// Limit of a listener serving `max_connections` at a time (unlimited when `None`) with
+    /// `overflow` more waiting; `retry_after` turns shedding on.
+    pub fn new(listener: Listener, max_connections: Option<usize>, overflow: usize, retry_after: Option<Duration>) -> Self {
+        let max = max_connections.unwrap_or(Semaphore::MAX_PERMITS);
+        let accepted = max.saturating_add(overflow).min(Semaphore::MAX_PERMITS);
+        Self {
+            listener,
+            serving: Arc::new(Semaphore::new(max)),
+            accepted: Arc::new(Semaphore::new(accepted)),
+            retry_after,
+        }
+    }
+
+    /// Limit of the API listener.
+    pub fn api(cfg: Option<&Api>) -> Self {
+        Self::new(
+            Listener::Api,
+            cfg.and_then(|a| a.max_connections),
+            cfg.and_then(|a| a.connections_overflow).unwrap_or(0),
+            cfg.and_then(|a| a.shed_retry_after),
+        )
+    }
+
+    /// Limit of the admin listener: no overflow and no shedding, it is only ever waited on.
+    pub fn admin(cfg: Option<&Api>) -> Self {
+        Self::new(Listener::Admin, cfg.and_then(|a| a.admin_max_connections), 0, None)
+    }
+
+    /// Whether connections beyond the limit are accepted and shed rather than left unaccepted.
+    pub fn sheds(&self) -> bool {
+        self.retry_after.is_some()
+    }
+
+    /// Waits until a connection may be accepted.
+    pub async fn reserve(&self) -> Reserved {
+        let permit = self.accepted.clone().acquire_owned().await.expect("connection limit is never closed");
+        Reserved(permit)
+    }
+
+    /// Reserves room for an accepted connection, `None` when the limit and the overflow are full.
+    pub fn try_reserve(&self) -> Option<Reserved> {
+        self.accepted.clone().try_acquire_owned().ok().map(Reserved)
+    }
+
+    /// Waits for a serving slot of a reserved connection.
+    pub async fn serve(&self, reserved: Reserved) -> Serving {
+        let permit = self.serving.clone().acquire_owned().await.expect("connection limit is never closed");
+        metrics::add_http_connections(self.listener, 1);
+        Serving {
+            listener: self.listener,
+            _serving: permit,
+            _accepted: reserved.0,
+        }
+    }
+
+    /// Answers a connection over the limit with 503 and closes it.
+    pub async fn shed(&self, mut stream: TcpStream) {
+        metrics::inc_http_connections_shed(self.listener);
+        let retry_after = self.retry_after.unwrap_or_default().as_secs().max(1);
+
+        // Read the request head first, so closing with unread data does not reset the connection
+        // before the client sees the answer.
+        let mut head = [0u8; 1024];
+        let _ = tokio::time::timeout(SHED_IO_TIMEOUT, stream.read(&mut head)).await;
+        let response = format!(
+            "HTTP/1.1 503 Service Unavailable\r\nRetry-After: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            retry_after,
+            UNAVAILABLE_RESPONSE_BODY.len()
+        );
+        let _ = tokio::time::timeout(SHED_IO_TIMEOUT, async {
+            stream.write_all(response.as_bytes()).await?;
+            stream.write_all(UNAVAILABLE_RESPONSE_BODY).await?;
+            stream.shutdown().await
+        })
+        .await;
+    }
+}
@@ -1,3 +1,4 @@
+pub mod limit;
 pub mod server;
 
 pub use server::{HttpServer, Server};
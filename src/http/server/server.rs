@@ -2,24 +2,45 @@
 //
 
 use anyhow::{Context, Result};
+use axum::extract::{ConnectInfo, Request};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
 use axum::Router;
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
 use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
 use tower_http::timeout::TimeoutLayer;
 use tracing::{error, info};
 
 use crate::config::{Config, ConfigTrait};
 use crate::controller::controller::Controller;
+use crate::controller::LivenessProbeController;
+use crate::liveness;
 use crate::middleware::middleware::Middleware;
 
+use super::limit::{ConnectionLimit, Listener};
+
+/// Paths served by the admin listener.
+const ADMIN_PATH_PREFIXES: &[&str] = &["/advcache/", "/cache/", "/k8s/", "/healthz", "/metrics"];
+
 /// Server trait for HTTP server operations.
 #[async_trait::async_trait]
 pub trait Server: Send + Sync {
     /// Starts the server (blocking).
     async fn listen_and_serve(&self) -> Result<()>;
+
+    /// Resolves once the listeners stopped accepting and are closed, while open connections
+    /// may still be answering their requests.
+    async fn closed(&self);
 }
 
 /// HTTP server implementation.
@@ -27,6 +48,8 @@ pub struct HttpServer {
     shutdown_token: CancellationToken,
     config: Config,
     router: Router,
+    /// Cancelled once the listeners are closed.
+    closed: CancellationToken,
 }
 
 impl HttpServer {
@@ -44,32 +67,28 @@ impl HttpServer {
             shutdown_token,
             config,
             router,
+            closed: CancellationToken::new(),
         }))
     }
 
     /// Starts the HTTP server (async version).
+    /// With `api.admin_port` set, the admin, probe and metrics endpoints are served on that
+    /// port as well, under a connection limit of their own.
     pub async fn listen_and_serve(&self) -> Result<()> {
+        // Listeners that failed to start are closed as well.
+        let _closed = self.closed.clone().drop_guard();
         let api_cfg = self.config.api().context("API configuration is required")?;
 
         let name = api_cfg.name.as_deref().unwrap_or("advcache");
-        let port = api_cfg.port.as_deref().unwrap_or("8020");
-
-        // Ensure port starts with ':'
-        let port = if port.starts_with(':') {
-            port.to_string()
-        } else {
-            format!(":{}", port)
-        };
-
-        let addr: SocketAddr = format!("0.0.0.0{}", port)
-            .parse()
-            .context("Failed to parse server address")?;
+        let port = normalize_port(api_cfg.port.as_deref().unwrap_or("8020"));
+        let addr = socket_addr(&port)?;
 
         info!(
             component = "server",
             event = "started",
             name = name,
             port = port,
+            max_connections = ?api_cfg.max_connections,
             "server started"
         );
 
@@ -78,26 +97,53 @@ impl HttpServer {
             .await
             .context("Failed to bind TCP listener")?;
 
-        // Create shutdown signal
-        let shutdown_token = self.shutdown_token.clone();
-
-        // Start server with graceful shutdown
-        let serve_future =
-            axum::serve(listener, self.router.clone()).with_graceful_shutdown(async move {
-                shutdown_token.cancelled().await;
-            });
-
-        // Run server
-        if let Err(e) = serve_future.await {
-            error!(
-                component = "server",
-                event = "listen_and_serve_failed",
-                name = name,
-                port = port,
-                error = %e,
-                "server failed to listen and serve"
-            );
-            return Err(e.into());
+        let api = accept(
+            listener,
+            self.router.clone(),
+            Arc::new(ConnectionLimit::api(Some(api_cfg))),
+            self.shutdown_token.clone(),
+        );
+
+        let admin = match api_cfg.admin_port.as_deref() {
+            Some(admin_port) => {
+                let admin_port = normalize_port(admin_port);
+                let admin_listener = TcpListener::bind(&socket_addr(&admin_port)?)
+                    .await
+                    .context("Failed to bind admin TCP listener")?;
+                info!(
+                    component = "server",
+                    event = "admin_started",
+                    name = name,
+                    port = admin_port,
+                    max_connections = ?api_cfg.admin_max_connections,
+                    "admin listener started"
+                );
+                // Probes served on a port of their own are kept off the admin listener.
+                let probe_paths = match self.config.k8s().map(|k| &k.probe) {
+                    Some(probe) if probe.port.is_none() => vec![probe.liveness_path().to_string(), probe.readiness_path().to_string()],
+                    _ => Vec::new(),
+                };
+                Some(accept(
+                    admin_listener,
+                    admin_router(self.router.clone(), probe_paths),
+                    Arc::new(ConnectionLimit::admin(Some(api_cfg))),
+                    self.shutdown_token.clone(),
+                ))
+            }
+            None => None,
+        };
+
+        // Both listeners are closed before the connections they left open are waited for.
+        let (api, admin) = tokio::join!(api, async move {
+            match admin {
+                Some(admin) => Some(admin.await),
+                None => None,
+            }
+        });
+        self.closed.cancel();
+        api.closed().await;
+        if let Some(admin) = admin {
+            admin.closed().await;
         }
 
         info!(
@@ -139,12 +185,201 @@ impl HttpServer {
     }
 }
 
+/// Ensures the port starts with ':'.
+fn normalize_port(port: &str) -> String {
+    if port.starts_with(':') {
+        port.to_string()
+    } else {
+        format!(":{}", port)
+    }
+}
+
+fn socket_addr(port: &str) -> Result<SocketAddr> {
+    format!("0.0.0.0{}", port)
+        .parse()
+        .context("Failed to parse server address")
+}
+
+/// Restricts a router to the admin, probe and metrics endpoints; `probe_paths` are the
+/// configured probe paths outside the default prefixes.
+pub fn admin_router(router: Router, probe_paths: Vec<String>) -> Router {
+    let probe_paths = Arc::new(probe_paths);
+    router.layer(axum::middleware::from_fn(move |req: Request, next: Next| {
+        let probe_paths = probe_paths.clone();
+        async move {
+            let path = req.uri().path();
+            if ADMIN_PATH_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) || probe_paths.iter().any(|p| p == path) {
+                next.run(req).await
+            } else {
+                StatusCode::NOT_FOUND.into_response()
+            }
+        }
+    }))
+}
+
+/// Router answering the liveness and readiness probes only.
+pub fn probe_router(probe: Arc<dyn liveness::Prober>, cfg: &crate::config::Probe) -> Router {
+    LivenessProbeController::new(probe, Some(cfg)).add_route(Router::new())
+}
+
+/// Binds `k8s.probe.port` and serves the probes there until shutdown; does nothing without
+/// it. Returns once the listener is bound, so that, called before the rest of the instance is
+/// built, kubelet gets answers (alive, not ready yet) rather than refused connections while
+/// the instance starts.
+pub async fn start_probe_listener(
+    shutdown_token: CancellationToken,
+    cfg: &Config,
+    probe: Arc<dyn liveness::Prober>,
+) -> Result<()> {
+    let Some(probe_cfg) = cfg.k8s().map(|k| &k.probe) else {
+        return Ok(());
+    };
+    let Some(port) = probe_cfg.port.as_deref() else {
+        return Ok(());
+    };
+    let port = normalize_port(port);
+    let listener = TcpListener::bind(&socket_addr(&port)?)
+        .await
+        .context("Failed to bind probe TCP listener")?;
+    info!(
+        component = "server",
+        event = "probe_started",
+        port = port,
+        liveness_path = probe_cfg.liveness_path(),
+        readiness_path = probe_cfg.readiness_path(),
+        "probe listener started"
+    );
+
+    let router = probe_router(probe, probe_cfg);
+    let limit = Arc::new(ConnectionLimit::new(Listener::Admin, None, 0, None));
+    tokio::spawn(async move {
+        if let Err(e) = serve(listener, router, limit, shutdown_token).await {
+            error!(component = "server", event = "probe_failed", error = %e, "probe listener failed");
+        }
+    });
+    Ok(())
+}
+
+/// Serves connections of the listener under its connection limit until shutdown, then waits
+/// for the open connections to finish their requests.
+pub async fn serve(
+    listener: TcpListener,
+    router: Router,
+    limit: Arc<ConnectionLimit>,
+    shutdown_token: CancellationToken,
+) -> std::io::Result<()> {
+    accept(listener, router, limit, shutdown_token).await.closed().await;
+    Ok(())
+}
+
+/// Connections of a closed listener still answering their requests.
+pub struct Draining(watch::Sender<()>);
+
+impl Draining {
+    /// Resolves once every connection is closed.
+    pub async fn closed(self) {
+        self.0.closed().await;
+    }
+}
+
+/// Accepts connections until shutdown and closes the listener; the open connections are asked
+/// to close once their current request is answered.
+async fn accept(
+    listener: TcpListener,
+    router: Router,
+    limit: Arc<ConnectionLimit>,
+    shutdown_token: CancellationToken,
+) -> Draining {
+    let (close_tx, close_rx) = watch::channel(());
+
+    loop {
+        // Without shedding, stop accepting while the limit and the overflow are full.
+        let reserved = if limit.sheds() {
+            None
+        } else {
+            tokio::select! {
+                reserved = limit.reserve() => Some(reserved),
+                _ = shutdown_token.cancelled() => break,
+            }
+        };
+
+        let (stream, addr) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) if is_connection_error(&e) => continue,
+                Err(e) => {
+                    // Most likely out of file descriptors: give open connections time to close.
+                    error!(component = "server", event = "accept_failed", error = %e, "failed to accept connection");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+            _ = shutdown_token.cancelled() => break,
+        };
+
+        let Some(reserved) = reserved.or_else(|| limit.try_reserve()) else {
+            let limit = limit.clone();
+            tokio::spawn(async move { limit.shed(stream).await });
+            continue;
+        };
+
+        let limit = limit.clone();
+        let router = router.clone();
+        let shutdown_token = shutdown_token.clone();
+        let close_rx = close_rx.clone();
+        tokio::spawn(async move {
+            let _serving = tokio::select! {
+                serving = limit.serve(reserved) => serving,
+                _ = shutdown_token.cancelled() => return,
+            };
+            serve_connection(stream, addr, router, shutdown_token).await;
+            drop(close_rx);
+        });
+    }
+
+    drop(close_rx);
+    drop(listener);
+    Draining(close_tx)
+}
+
+async fn serve_connection(stream: TcpStream, addr: SocketAddr, router: Router, shutdown_token: CancellationToken) {
+    // Handlers telling clients apart (clear tokens) read the peer address as `ConnectInfo`.
+    let router = router.map_request(move |mut req: hyper::Request<Incoming>| {
+        req.extensions_mut().insert(ConnectInfo(addr));
+        req
+    });
+    let service = TowerToHyperService::new(router);
+    let builder = Builder::new(TokioExecutor::new());
+    let conn = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+    tokio::pin!(conn);
+
+    tokio::select! {
+        _ = conn.as_mut() => return,
+        _ = shutdown_token.cancelled() => {}
+    }
+    conn.as_mut().graceful_shutdown();
+    let _ = conn.await;
+}
+
+fn is_connection_error(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::ConnectionReset
+    )
+}
+
 #[async_trait::async_trait]
 impl Server for HttpServer {
     async fn listen_and_serve(&self) -> Result<()> {
         // Delegate to the struct's async method
         HttpServer::listen_and_serve(self).await
     }
+
+    async fn closed(&self) {
+        self.closed.cancelled().await
+    }
 }
 
 #[async_trait::async_trait]
@@ -152,4 +387,8 @@ impl Server for Arc<HttpServer> {
     async fn listen_and_serve(&self) -> Result<()> {
         HttpServer::listen_and_serve(self).await
     }
+
+    async fn closed(&self) {
+        self.closed.cancelled().await
+    }
 }
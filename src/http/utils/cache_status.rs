@@ -0,0 +1,45 @@
+//! X-Cache-Status header functionality.
+
+/// X-Cache-Status header key.
+pub const CACHE_STATUS_KEY: &str = "x-cache-status";
+
+/// Response was served from a stored entry because the upstream fill failed.
+pub const STALE_ERROR: &str = "STALE-ERROR";
+
+/// X-Cache header key.
+pub const X_CACHE_KEY: &str = "x-cache";
+
+/// X-Cache-Key header key, sent with `api.debug_headers` only.
+pub const X_CACHE_KEY_KEY: &str = "x-cache-key";
+
+/// How a response was served, as `X-Cache` tells it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XCache {
+    /// From a fresh stored entry.
+    Hit,
+    /// From the origin, through the cache.
+    Miss,
+    /// From a stored entry past its TTL.
+    Stale,
+    /// Proxied past the cache.
+    Bypass,
+}
+
+impl XCache {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            XCache::Hit => "HIT",
+            XCache::Miss => "MISS",
+            XCache::Stale => "STALE",
+            XCache::Bypass => "BYPASS",
+        }
+    }
+}
+
+/// Diagnostic headers of a response: `X-Cache`, `Age`, and `X-Cache-Key` when `key` is set.
+#[derive(Debug, Clone, Copy)]
+pub struct Diagnostics {
+    pub cache: XCache,
+    /// Key of the entry, only with `api.debug_headers`.
+    pub key: Option<u64>,
+}
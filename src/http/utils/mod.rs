@@ -1 +1,2 @@
+pub mod cache_status;
 pub mod last_updated_at;
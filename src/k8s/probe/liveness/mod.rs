@@ -21,23 +21,42 @@ pub struct Probe {
 }
 
 impl Probe {
+    /// Floor of the probe timeout unless `k8s.probe.min_timeout` says otherwise.
+    pub const DEFAULT_MIN_TIMEOUT: Duration = Duration::from_millis(10);
+
     /// Creates a new liveness probe
+    #[allow(dead_code)]
     pub fn new(timeout_duration: Duration) -> Self {
-        const MIN_TIMEOUT: Duration = Duration::from_millis(1);
-        if timeout_duration < MIN_TIMEOUT {
+        Self::with_min_timeout(timeout_duration, Self::DEFAULT_MIN_TIMEOUT)
+    }
+
+    /// Creates a new liveness probe whose timeout is at least `min_timeout`: a shorter one is
+    /// raised to it.
+    pub fn with_min_timeout(timeout_duration: Duration, min_timeout: Duration) -> Self {
+        let mut timeout = timeout_duration;
+        if timeout < min_timeout {
+            timeout = min_timeout;
             warn!(
                 error = %TimeoutIsTooShortError,
-                "min timeout duration is 1ms (timeout set up as 10ms as a more reasonable value)"
+                configured = ?timeout_duration,
+                min = ?min_timeout,
+                clamped = ?timeout,
+                "liveness probe timeout is below the minimum, raised"
             );
-            let _timeout_duration = Duration::from_millis(10);
         }
 
         Self {
             services: Arc::new(RwLock::new(Vec::new())),
-            timeout: timeout_duration,
+            timeout,
         }
     }
 
+    /// Timeout the watched services are checked within.
+    #[allow(dead_code)]
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
     /// Registers services to be checked for liveness.
     pub fn watch(&self, services: Vec<Arc<dyn Service>>) {
         let mut guard = self.services.write().expect("poisoned liveness lock");
@@ -59,21 +78,11 @@ impl Probe {
     }
 
     fn check_services(&self) -> bool {
-        let services = {
-            let guard = self.services.read().expect("poisoned liveness lock");
-            guard.clone()
-        };
-
-        if services.is_empty() {
-            return true;
-        }
+        self.services().iter().all(|service| service.is_alive(self.timeout))
+    }
 
-        for service in services {
-            if !service.is_alive(self.timeout) {
-                return false;
-            }
-        }
-        true
+    fn services(&self) -> Vec<Arc<dyn Service>> {
+        self.services.read().expect("poisoned liveness lock").clone()
     }
 }
 
@@ -101,4 +110,11 @@ impl Prober for Probe {
     fn is_alive(&self) -> bool {
         self.check_services()
     }
+
+    /// Not ready until the application registers itself: while it starts, a probe listener
+    /// that is already up answers alive but not ready.
+    fn is_ready(&self) -> bool {
+        let services = self.services();
+        !services.is_empty() && services.iter().all(|service| service.is_ready(self.timeout))
+    }
 }
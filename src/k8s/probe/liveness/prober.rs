@@ -12,4 +12,9 @@ pub trait Prober: Send + Sync {
 
     /// Checks whether the target service is alive (synchronous version).
     fn is_alive(&self) -> bool;
+
+    /// Checks whether the target service is registered and alive, i.e. takes traffic.
+    fn is_ready(&self) -> bool {
+        self.is_alive()
+    }
 }
@@ -6,4 +6,9 @@ use std::time::Duration;
 pub trait Service: Send + Sync {
     /// Checks if the service is alive
     fn is_alive(&self, timeout: Duration) -> bool;
+
+    /// Checks if the service is alive and takes traffic
+    fn is_ready(&self, timeout: Duration) -> bool {
+        self.is_alive(timeout)
+    }
 }
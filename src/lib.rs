@@ -1,3 +1,5 @@
+#[path = "shared/audit/mod.rs"]
+pub mod audit;
 #[path = "shared/bytes/mod.rs"]
 pub mod bytes;
 #[path = "shared/dedlog/mod.rs"]
@@ -28,6 +30,7 @@ pub mod metrics;
 pub mod metrics_runtime;
 pub mod middleware;
 pub mod model;
+pub mod plugin;
 pub mod shutdown;
 pub mod db;
 pub mod traces;
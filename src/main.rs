@@ -1,6 +1,8 @@
 // Main entrypoint for the AdvCache application.
 
 mod app;
+#[path = "shared/audit/mod.rs"]
+mod audit;
 #[path = "shared/bytes/mod.rs"]
 mod bytes;
 mod config;
@@ -15,6 +17,7 @@ mod metrics;
 mod metrics_runtime;
 mod middleware;
 mod model;
+mod plugin;
 #[path = "shared/rand/mod.rs"]
 mod rand;
 #[path = "shared/rate/mod.rs"]
@@ -30,7 +33,8 @@ mod upstream;
 mod workers;
 
 use crate::config::{Config, ConfigTrait};
-use crate::shutdown::GracefulShutdown;
+use crate::shutdown::signals::{self, Actions};
+use crate::shutdown::{GracefulShutdown, TimeoutError};
 
 use anyhow::{Context, Result};
 use clap::Parser;
@@ -40,6 +44,11 @@ use std::time::Duration;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
+/// Allocator of `jemalloc` builds, whose free pages idle reclamation can purge.
+#[cfg(all(feature = "jemalloc", not(target_env = "msvc")))]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
 const CONFIG_PATH: &str = "cfg/advcache.cfg.yaml";
 const CONFIG_PATH_LOCAL: &str = "cfg/advcache.cfg.local.yaml";
 
@@ -48,8 +57,51 @@ const CONFIG_PATH_LOCAL: &str = "cfg/advcache.cfg.local.yaml";
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Custom config file path
-    #[arg(short, long, value_name = "FILE")]
+    #[arg(short, long, value_name = "FILE", global = true)]
     cfg: Option<PathBuf>,
+
+    /// Load and check the config, resolve backends and check dump dirs, print the startup
+    /// summary and exit 0 or 1 without binding any port
+    #[arg(long)]
+    dry_run: bool,
+
+    #[cfg(feature = "dump")]
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Offline dump tools. They never start the server; `--cfg` is only used to decode
+/// entries against configured rules, otherwise every rule path in the dump is accepted.
+// Variant names become the `dump-*` command names.
+#[cfg(feature = "dump")]
+#[allow(clippy::enum_variant_names)]
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// List dump versions in a dump directory
+    DumpLs {
+        /// Dump base directory (the one holding v1, v2, ...)
+        dir: PathBuf,
+    },
+    /// Print metadata and keys of entries in a dump file
+    DumpCat {
+        file: PathBuf,
+        /// Maximum number of entries to print
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Check CRC and decoding of every entry in a dump version directory
+    DumpVerify {
+        /// Version directory (e.g. public/dump/v3)
+        dir: PathBuf,
+    },
+    /// Write a dump file holding only entries of the given rule path
+    DumpFilter {
+        input: PathBuf,
+        output: PathBuf,
+        /// Rule path entries must belong to (e.g. /api/v1/user)
+        #[arg(long)]
+        path: String,
+    },
 }
 
 /// Configures and logs thread parallelism settings.
@@ -117,7 +169,7 @@ fn load_cfg(path: Option<PathBuf>) -> Result<Config> {
 
 /// Configures structured logging based on configuration.
 fn configure_logger(cfg: &Config) {
-    use tracing_subscriber::fmt;
+    use tracing_subscriber::fmt::{self, MakeWriter};
     use tracing_subscriber::prelude::*;
     use tracing_subscriber::EnvFilter;
 
@@ -128,6 +180,33 @@ fn configure_logger(cfg: &Config) {
         .unwrap_or("debug");
 
     let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(log_level));
+    // Keys under debug capture are logged whatever the level
+    let filter = match format!("{}=debug", db::capture::TARGET).parse() {
+        Ok(directive) => filter.add_directive(directive),
+        Err(_) => filter,
+    };
+
+    // With logs.path, lines go to a file SIGUSR2 reopens, in the format of the env
+    if let Some(path) = cfg.logs().and_then(|logs| logs.path.as_deref()) {
+        match dedlog::configure_log_file(path) {
+            Ok(file) => {
+                let writer = move || file.make_writer();
+                if cfg.is_prod() {
+                    tracing_subscriber::registry()
+                        .with(filter)
+                        .with(fmt::layer().json().with_writer(writer))
+                        .init();
+                } else {
+                    tracing_subscriber::registry()
+                        .with(filter)
+                        .with(fmt::layer().with_ansi(false).with_writer(writer))
+                        .init();
+                }
+                return;
+            }
+            Err(e) => eprintln!("logs.path {:?} cannot be opened, logging to stdout: {}", path, e),
+        }
+    }
 
     if cfg.is_prod() {
         // Production: JSON format
@@ -146,12 +225,61 @@ fn configure_logger(cfg: &Config) {
 
 fn main() -> Result<()> {
     // Parse command-line arguments
-    let args = Args::parse();
-    
+    #[cfg_attr(not(feature = "dump"), allow(unused_mut))]
+    let mut args = Args::parse();
+
     // Now start the async runtime
-    tokio::runtime::Runtime::new()
-        .context("Failed to create tokio runtime")?
-        .block_on(async_main(args))
+    let runtime = tokio::runtime::Runtime::new().context("Failed to create tokio runtime")?;
+
+    #[cfg(feature = "dump")]
+    if let Some(command) = args.command.take() {
+        return runtime.block_on(run_command(command, args.cfg));
+    }
+
+    if args.dry_run {
+        let code = runtime.block_on(async {
+            let cfg = load_cfg(args.cfg);
+            app::dry_run::run(cfg, &mut std::io::stdout(), &mut std::io::stderr()).await
+        });
+        std::process::exit(code);
+    }
+
+    runtime.block_on(async_main(args))
+}
+
+/// Runs an offline dump subcommand and exits with a non-zero code if corruption was found.
+#[cfg(feature = "dump")]
+async fn run_command(command: Command, cfg_path: Option<PathBuf>) -> Result<()> {
+    use crate::db::persistance::inspect;
+
+    let cfg = match cfg_path {
+        Some(path) => Some(Config::load(&path).with_context(|| format!("failed to load config from {:?}", path))?),
+        None => None,
+    };
+    let mut decoder = inspect::EntryDecoder::new(cfg);
+    let mut out = std::io::stdout().lock();
+
+    match command {
+        Command::DumpLs { dir } => inspect::dump_ls(&dir, &mut out).await,
+        Command::DumpCat { file, limit } => inspect::dump_cat(&file, limit, &mut decoder, &mut out).map(|_| ()),
+        Command::DumpVerify { dir } => {
+            let report = inspect::dump_verify(&dir, &mut decoder, &mut out).await?;
+            if !report.is_clean() {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::DumpFilter { input, output, path } => {
+            let report = inspect::dump_filter(&input, &output, &path, &mut decoder)?;
+            use std::io::Write;
+            writeln!(
+                out,
+                "read={}\twritten={}\tskipped_corrupt={}",
+                report.read, report.written, report.skipped_corrupt
+            )?;
+            Ok(())
+        }
+    }
 }
 
 async fn async_main(args: Args) -> Result<()> {
@@ -167,10 +295,24 @@ async fn async_main(args: Args) -> Result<()> {
 
     // Configure logger (must be done after config is loaded)
     configure_logger(&cfg);
+
+    // Mask sensitive query parameters and headers in logs and trace attributes
+    dedlog::configure_redaction(cfg.logs().and_then(|logs| logs.redact.as_ref()));
+
+    // Size the ring of recent errors served by /advcache/errors
+    dedlog::configure_error_ring(cfg.logs().and_then(|logs| logs.error_ring));
+
+    // Codes counted under their own label in resp_status_total, the others by class
+    metrics::code::configure_status_codes(cfg.cache.metrics.as_ref().and_then(|m| m.status_codes.as_deref()));
     
     // Initialize metrics ecosystem: install ONE global Prometheus recorder and store process collector
     // Must be done after logger, before HTTP server starts
-    crate::metrics_runtime::init_metrics();
+    if let Err(e) = crate::metrics_runtime::init_metrics() {
+        error!(
+            error = %e,
+            "prometheus recorder is not installed, /metrics serves cache counters only"
+        );
+    }
 
     // Optimize thread parallelism
     set_max_num_cpus(&cfg);
@@ -183,19 +325,33 @@ async fn async_main(args: Args) -> Result<()> {
 
     // Setup graceful shutdown handler
     let graceful_shutdown = GracefulShutdown::new(shutdown_token.clone());
-    graceful_shutdown
-        .set_graceful_timeout(Duration::from_secs(60))
-        .await;
+    let shutdown_budget = cfg.cache.shutdown.clone().unwrap_or_default().total();
+    info!(
+        component = "main",
+        event = "shutdown_budget",
+        total = ?shutdown_budget,
+        "shutdown phases may take up to the sum of their timeouts, keep terminationGracePeriodSeconds above it"
+    );
 
     // Initialize liveness probe for Kubernetes/Cloud health checks
     let probe_timeout = cfg
         .k8s()
         .and_then(|k8s| k8s.probe.timeout)
         .unwrap_or(Duration::from_secs(5));
-    let probe = Arc::new(liveness::Probe::new(probe_timeout)) as Arc<dyn liveness::Prober>;
+    let probe_min_timeout = cfg
+        .k8s()
+        .and_then(|k8s| k8s.probe.min_timeout)
+        .unwrap_or(liveness::Probe::DEFAULT_MIN_TIMEOUT);
+    let probe =
+        Arc::new(liveness::Probe::with_min_timeout(probe_timeout, probe_min_timeout)) as Arc<dyn liveness::Prober>;
+
+    // With k8s.probe.port, answer probes before the rest of the instance is up
+    http::server::server::start_probe_listener(shutdown_token.clone(), &cfg, probe.clone()).await?;
 
     // Initialize and start the cache application
+    let summary = app::Summary::of(&cfg);
     let app = app::App::new(shutdown_token.clone(), cfg, probe).await?;
+    summary.log();
 
     // Register app for graceful shutdown
     graceful_shutdown.add(1);
@@ -216,16 +372,54 @@ async fn async_main(args: Args) -> Result<()> {
         graceful_done.done();
     });
 
-    // Listen for OS signals or cancellation and wait for graceful shutdown
-    if let Err(e) = graceful_shutdown.await_shutdown().await {
+    // SIGUSR1 dumps on demand, SIGUSR2 reopens the log file, SIGHUP reloads the config,
+    // until the shutdown starts
+    let user_signals = CancellationToken::new();
+    let dump_app = app.clone();
+    let reload_app = app.clone();
+    let actions = Actions {
+        dump: Arc::new(move || {
+            let app = dump_app.clone();
+            Box::pin(async move { app.dump().await })
+        }),
+        reopen: Arc::new(|| {
+            Box::pin(async {
+                if !dedlog::reopen_log_file()? {
+                    anyhow::bail!("logs.path is not set, logs go to stdout");
+                }
+                Ok(())
+            })
+        }),
+        reload: Arc::new(move || {
+            let app = reload_app.clone();
+            Box::pin(async move { app.reload_config().map(|_| ()) })
+        }),
+    };
+    if let Err(e) = signals::spawn_user_signals(user_signals.clone(), actions) {
+        warn!(
+            component = "main",
+            event = "signals_not_installed",
+            error = %e,
+            "SIGUSR1/SIGUSR2/SIGHUP handlers are not installed"
+        );
+    }
+
+    // Listen for OS signals or cancellation, then stop the app phase by phase; a second
+    // SIGINT meanwhile stops the process without waiting for the phases
+    let signals = graceful_shutdown.await_signal().await;
+    user_signals.cancel();
+    if let Some(signals) = signals {
+        signals.spawn_force_on_interrupt(|| std::process::exit(130));
+    }
+    let report = app.shutdown(&graceful_shutdown).await;
+    if report.timed_out() {
         error!(
             component = "main",
             scope = "service",
             event = "graceful_shutdown_failed",
-            error = %e,
-            "failed to gracefully shut down service"
+            "some shutdown phases exceeded their timeouts"
         );
-        return Err(e);
+        return Err(TimeoutError.into());
     }
 
     Ok(())
@@ -1,19 +1,132 @@
 // Status code metrics using atomic counters
+//
+// Codes of the allow-list (`metrics.status_codes`) are counted under their own label, the
+// others under their class (`1xx`..`5xx`, `other` outside 100..=599), so an origin answering
+// odd codes (599, 000 from a broken balancer) cannot grow the label set.
 
-use crate::controller::metrics;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use once_cell::sync::Lazy;
+
+/// Codes counted as is when `metrics.status_codes` is not set.
+pub const DEFAULT_STATUS_CODES: [u16; 14] = [200, 204, 301, 302, 304, 400, 401, 403, 404, 429, 500, 502, 503, 504];
+
+/// Codes 0..MAX_CODE have a slot; only those of 100..=599 can be allowed.
+const MAX_CODE: usize = 600;
+
+/// Labels of the classes, indexed by the first digit of the code; `other` at 0.
+const CLASS_LABELS: [&str; 6] = ["other", "1xx", "2xx", "3xx", "4xx", "5xx"];
+
+/// Label a status code is counted under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusLabel {
+    Code(u16),
+    /// Index into the class labels: 1..=5 for `1xx`..`5xx`, 0 for `other`.
+    Class(usize),
+}
+
+impl StatusLabel {
+    pub fn as_string(&self) -> String {
+        match self {
+            StatusLabel::Code(code) => code.to_string(),
+            StatusLabel::Class(class) => CLASS_LABELS[*class].to_string(),
+        }
+    }
+}
+
+/// Response counters by status code label.
+pub struct StatusCodeCounters {
+    allowed: Vec<AtomicBool>,
+    codes: Vec<AtomicU64>,
+    classes: [AtomicU64; 6],
+}
+
+impl StatusCodeCounters {
+    /// Counters counting the codes of `allowed` (the defaults when `None`) as is.
+    pub fn new(allowed: Option<&[u16]>) -> Self {
+        let counters = Self {
+            allowed: (0..MAX_CODE).map(|_| AtomicBool::new(false)).collect(),
+            codes: (0..MAX_CODE).map(|_| AtomicU64::new(0)).collect(),
+            classes: Default::default(),
+        };
+        counters.allow(allowed);
+        counters
+    }
+
+    /// Replaces the allow-list; counts taken so far are kept under their label.
+    pub fn allow(&self, allowed: Option<&[u16]>) {
+        for flag in &self.allowed {
+            flag.store(false, Ordering::Relaxed);
+        }
+        for &code in allowed.unwrap_or(&DEFAULT_STATUS_CODES) {
+            if (100..MAX_CODE as u16).contains(&code) {
+                self.allowed[code as usize].store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Label the code is counted under.
+    pub fn label(&self, code: u16) -> StatusLabel {
+        match code {
+            100..=599 if self.allowed[code as usize].load(Ordering::Relaxed) => StatusLabel::Code(code),
+            100..=599 => StatusLabel::Class(code as usize / 100),
+            _ => StatusLabel::Class(0),
+        }
+    }
+
+    pub fn inc(&self, code: u16) {
+        match self.label(code) {
+            StatusLabel::Code(code) => self.codes[code as usize].fetch_add(1, Ordering::Relaxed),
+            StatusLabel::Class(class) => self.classes[class].fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    /// Non-zero counters by label, exact codes first.
+    pub fn snapshot(&self) -> Vec<(StatusLabel, u64)> {
+        let codes = self.codes.iter().enumerate().map(|(code, c)| (StatusLabel::Code(code as u16), c));
+        let classes = self.classes.iter().enumerate().map(|(class, c)| (StatusLabel::Class(class), c));
+        codes
+            .chain(classes)
+            .map(|(label, c)| (label, c.load(Ordering::Relaxed)))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    /// Renders the counters as `resp_status_total`.
+    pub fn render(&self, output: &mut String) {
+        output.push_str("# HELP resp_status_total Total number of HTTP responses by status code, uncommon codes by class\n");
+        output.push_str("# TYPE resp_status_total counter\n");
+        for (label, count) in self.snapshot() {
+            let _ = writeln!(output, "resp_status_total{{code=\"{}\"}} {}", label.as_string(), count);
+        }
+    }
+}
+
+static STATUS_CODES: Lazy<StatusCodeCounters> = Lazy::new(|| StatusCodeCounters::new(None));
+
+/// The process-wide status code counters.
+pub fn status_codes() -> &'static StatusCodeCounters {
+    &STATUS_CODES
+}
+
+/// Installs the allow-list of exactly counted codes (`metrics.status_codes`).
+pub fn configure_status_codes(allowed: Option<&[u16]>) {
+    STATUS_CODES.allow(allowed);
+}
 
 /// Increments status code counter.
 pub fn inc_status_code(code: u16) {
-    metrics::inc_status_code(code);
+    STATUS_CODES.inc(code);
 }
 
 /// Flushes status code counters to metrics.
-/// 
+///
 /// NOTE: In the simple atomic-based metrics implementation,
-/// status codes are already tracked atomically and will be
-/// included in the /metrics endpoint automatically.
+/// status codes are already tracked atomically, bucketed on increment,
+/// and will be included in the /metrics endpoint automatically.
 /// This function is kept for API compatibility but does nothing.
 pub fn flush_status_code_counters() {
-    // Status codes are already tracked atomically in controller::metrics
+    // Status codes are already tracked atomically in STATUS_CODES
     // and will be included in the Prometheus output automatically
 }
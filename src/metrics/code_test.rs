@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use crate::metrics::code::{StatusCodeCounters, StatusLabel};
+
+    fn rendered(counters: &StatusCodeCounters) -> Vec<String> {
+        let mut output = String::new();
+        counters.render(&mut output);
+        output.lines().filter(|l| !l.starts_with('#')).map(str::to_string).collect()
+    }
+
+    /// Test that the usual codes keep their label and odd ones are counted by class.
+    #[test]
+    fn test_uncommon_codes_are_bucketed() {
+        let counters = StatusCodeCounters::new(None);
+        for code in [200, 200, 404, 503, 599, 0, 999, 418, 299, 103] {
+            counters.inc(code);
+        }
+        assert_eq!(counters.label(599), StatusLabel::Class(5));
+        assert_eq!(counters.label(0), StatusLabel::Class(0));
+
+        assert_eq!(
+            rendered(&counters),
+            vec![
+                r#"resp_status_total{code="200"} 2"#,
+                r#"resp_status_total{code="404"} 1"#,
+                r#"resp_status_total{code="503"} 1"#,
+                r#"resp_status_total{code="other"} 2"#,
+                r#"resp_status_total{code="1xx"} 1"#,
+                r#"resp_status_total{code="2xx"} 1"#,
+                r#"resp_status_total{code="4xx"} 1"#,
+                r#"resp_status_total{code="5xx"} 1"#,
+            ]
+        );
+    }
+
+    /// Test that a configured allow-list replaces the defaults.
+    #[test]
+    fn test_allow_list() {
+        let counters = StatusCodeCounters::new(Some(&[418, 599]));
+        for code in [200, 418, 599, 502] {
+            counters.inc(code);
+        }
+        assert_eq!(
+            rendered(&counters),
+            vec![
+                r#"resp_status_total{code="418"} 1"#,
+                r#"resp_status_total{code="599"} 1"#,
+                r#"resp_status_total{code="2xx"} 1"#,
+                r#"resp_status_total{code="5xx"} 1"#,
+            ]
+        );
+
+        counters.allow(None);
+        counters.inc(200);
+        assert_eq!(counters.label(418), StatusLabel::Class(4));
+        assert!(rendered(&counters).contains(&r#"resp_status_total{code="200"} 1"#.to_string()));
+    }
+
+    /// Test that codes outside 100..=599 are refused in `metrics.status_codes`.
+    #[test]
+    fn test_allow_list_rejects_non_codes() {
+        let yaml = r#"
+cache:
+  env: test
+  enabled: true
+  metrics:
+    enabled: true
+    status_codes: [200, 0]
+"#;
+        let err = config::Config::from_yaml(yaml).unwrap_err();
+        assert!(format!("{:#}", err).contains("metrics.status_codes"), "{:#}", err);
+    }
+}
@@ -1,6 +1,7 @@
 use super::policy::Policy;
 use crate::controller::metrics;
 use crate::upstream::Policy as UpstreamPolicy;
+use crate::upstream::backend_hyper_impl::TimeoutPhase;
 
 pub const AVG_TOTAL_DURATION: &str = "avg_duration_ns";
 pub const AVG_CACHE_DURATION: &str = "avg_cache_duration_ns";
@@ -81,6 +82,26 @@ pub fn add_proxied_num(value: u64) {
     metrics::inc_proxied(value);
 }
 
+/// Adds upstream responses aborted for exceeding the body size limit.
+pub fn add_upstream_response_too_large(value: u64) {
+    metrics::inc_upstream_response_too_large(value);
+}
+
+/// Adds an upstream request that timed out in `phase`.
+pub fn add_upstream_timeout(phase: TimeoutPhase) {
+    metrics::inc_upstream_timeouts(phase);
+}
+
+/// Adds an audit record written.
+pub fn add_audit_record() {
+    metrics::inc_audit_records();
+}
+
+/// Adds an audit record dropped or not written.
+pub fn add_audit_write_failure() {
+    metrics::inc_audit_write_failures();
+}
+
 /// Sets cache length.
 pub fn set_cache_length(count: u64) {
     metrics::set_cache_length(count);
@@ -141,6 +162,11 @@ pub fn add_hard_eviction_stat_counters(
     );
 }
 
+/// Adds the number of entries handed out for prewarming.
+pub fn add_prewarm_stat_counter(prewarmed: i64) {
+    metrics::add_refresh_prewarmed(prewarmed as u64);
+}
+
 /// Adds lifetime statistics.
 pub fn add_lifetime_stat_counters(updated: i64, errors: i64, scans: i64, miss: i64, hits: i64) {
     metrics::add_lifetime_stats(
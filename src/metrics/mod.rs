@@ -11,3 +11,6 @@ pub mod policy;
 // Re-export commonly used items
 pub use code::*;
 pub use meter::*;
+
+#[cfg(test)]
+mod code_test;
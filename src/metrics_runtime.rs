@@ -1,19 +1,25 @@
 use once_cell::sync::OnceCell;
-use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use metrics_exporter_prometheus::{BuildError, PrometheusBuilder, PrometheusHandle};
 use metrics_process::Collector;
 
 static PROM_HANDLE: OnceCell<PrometheusHandle> = OnceCell::new();
 static PROC_COLLECTOR: OnceCell<Collector> = OnceCell::new();
 
-pub fn init_metrics() {
-    let handle = PrometheusBuilder::new()
-        .install_recorder()
-        .expect("failed to install Prometheus recorder");
-    let _ = PROM_HANDLE.set(handle);
+/// Installs the global Prometheus recorder and the process collector.
+///
+/// The recorder has no listener of its own: its output is rendered by the `/metrics`
+/// route of the main HTTP server. Calling it again is a no-op. Fails when another
+/// recorder is already installed in the process (e.g. by an embedding application);
+/// in that case `/metrics` still serves the cache's own counters.
+pub fn init_metrics() -> Result<(), BuildError> {
+    PROM_HANDLE.get_or_try_init(|| PrometheusBuilder::new().install_recorder())?;
 
-    let collector = Collector::default();
-    collector.describe(); // регистрирует HELP/TYPE
-    let _ = PROC_COLLECTOR.set(collector);
+    PROC_COLLECTOR.get_or_init(|| {
+        let collector = Collector::default();
+        collector.describe(); // регистрирует HELP/TYPE
+        collector
+    });
+    Ok(())
 }
 
 pub fn scrape_prometheus_text() -> Option<String> {
@@ -0,0 +1,108 @@
+//! Audit middleware.
+//
+// Records every call of a route marked mutating in the audit log once it is answered, whatever
+// the outcome. The record is queued without waiting, so the call is never held up or failed
+// by the log.
+
+use std::collections::{BTreeMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request},
+    http::Method,
+    middleware::Next,
+    response::Response,
+    Router,
+};
+
+use crate::audit::{AuditLog, AuditRecord};
+use crate::controller::clear::Principal;
+use crate::dedlog::sanitizer::REDACTED;
+use crate::http::Controller;
+
+/// Params whose values are secrets whatever `logs.redact.query` says.
+const SECRET_PARAMS: &[&str] = &["token"];
+
+/// Response extension with the number of entries a call dropped or marked, recorded in its
+/// audit record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Affected(pub u64);
+
+/// AuditMiddleware records the calls of the mutating routes of the controllers.
+pub struct AuditMiddleware {
+    log: Arc<AuditLog>,
+    /// (method, route) of the mutating routes.
+    routes: Arc<HashSet<(&'static str, String)>>,
+}
+
+impl AuditMiddleware {
+    /// Creates a middleware auditing the routes of `controllers` marked mutating.
+    pub fn new(log: Arc<AuditLog>, controllers: &[Box<dyn Controller>]) -> Self {
+        let routes = controllers
+            .iter()
+            .flat_map(|controller| controller.describe())
+            .filter(|route| route.mutating)
+            .map(|route| (route.method, route.path))
+            .collect();
+        Self { log, routes: Arc::new(routes) }
+    }
+
+    async fn middleware(log: Arc<AuditLog>, routes: Arc<HashSet<(&'static str, String)>>, request: Request, next: Next) -> Response {
+        // GET routes answer HEAD too.
+        let method = if request.method() == Method::HEAD { "GET" } else { request.method().as_str() };
+        let endpoint = request.extensions().get::<MatchedPath>().map(|p| p.as_str().to_string());
+        let Some(endpoint) = endpoint.filter(|endpoint| routes.iter().any(|(m, path)| *m == method && path == endpoint)) else {
+            return next.run(request).await;
+        };
+
+        let addr = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| *addr);
+        let principal = Principal::of(request.headers(), addr);
+        let mut record = AuditRecord {
+            method: request.method().to_string(),
+            endpoint,
+            path: request.uri().path().to_string(),
+            params: sanitized_params(request.uri().query().unwrap_or_default()),
+            principal: principal.to_string(),
+            ..Default::default()
+        };
+
+        let response = next.run(request).await;
+
+        record.timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        record.status = response.status().as_u16();
+        record.result = AuditRecord::result_of(record.status).to_string();
+        record.affected = response.extensions().get::<Affected>().map(|Affected(n)| *n);
+        log.record(record);
+        response
+    }
+}
+
+/// Query params by name, repeated ones joined with a comma, secret values masked.
+fn sanitized_params(query: &str) -> BTreeMap<String, String> {
+    let mut params: BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        let value = if SECRET_PARAMS.contains(&name.as_ref()) || crate::dedlog::is_redacted_query(&name) {
+            REDACTED.into()
+        } else {
+            value
+        };
+        params
+            .entry(name.into_owned())
+            .and_modify(|joined| {
+                joined.push(',');
+                joined.push_str(&value);
+            })
+            .or_insert_with(|| value.into_owned());
+    }
+    params
+}
+
+impl crate::middleware::middleware::Middleware for AuditMiddleware {
+    fn apply(&self, router: Router) -> Router {
+        let (log, routes) = (self.log.clone(), self.routes.clone());
+        router.layer(axum::middleware::from_fn(move |request: Request, next: Next| {
+            Self::middleware(log.clone(), routes.clone(), request, next)
+        }))
+    }
+}
@@ -1,7 +1,13 @@
 //! Compression middleware.
 //
 
-use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use axum::body::HttpBody;
+use axum::{
+    extract::Request,
+    http::{header::{CONTENT_ENCODING, CONTENT_LENGTH}, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
 use std::sync::atomic::{AtomicBool, Ordering};
 use tower_http::compression::CompressionLayer;
 
@@ -47,6 +53,9 @@ impl CompressionMiddleware {
     pub async fn middleware(&self, request: Request, next: Next) -> Response {
         let mut response = next.run(request).await;
 
+        // Runs outside the CompressionLayer, so the body here is the one on the wire.
+        sync_content_length(&mut response);
+
         // If compression is enabled, it's handled by the CompressionLayer
         // We just need to ensure Content-Type is set if missing
         if response.headers().get("content-type").is_none() {
@@ -58,6 +67,27 @@ impl CompressionMiddleware {
     }
 }
 
+/// Makes `Content-Length` describe the final body. It is rewritten when the body size is known.
+/// An encoded body of unknown size (a gzip stream) has it dropped so hyper uses chunked transfer:
+/// a length left over from the identity representation would make keep-alive clients hang.
+/// Unencoded bodies pass through the compression layer untouched and keep the renderer's length.
+fn sync_content_length(response: &mut Response) {
+    let status = response.status();
+    if status.is_informational() || status == StatusCode::NO_CONTENT || status == StatusCode::NOT_MODIFIED {
+        return;
+    }
+
+    match response.body().size_hint().exact() {
+        Some(len) => {
+            response.headers_mut().insert(CONTENT_LENGTH, HeaderValue::from(len));
+        }
+        None if response.headers().contains_key(CONTENT_ENCODING) => {
+            response.headers_mut().remove(CONTENT_LENGTH);
+        }
+        None => {}
+    }
+}
+
 impl Default for CompressionMiddleware {
     fn default() -> Self {
         Self::new(None)
@@ -81,7 +111,8 @@ impl crate::middleware::middleware::Middleware for CompressionMiddleware {
                     _ => tower_http::compression::CompressionLevel::Default,
                 };
 
-                let layer = CompressionLayer::new().no_br().quality(compression_level);
+                // Brotli is left out of tower-http, responses are never compressed with it
+                let layer = CompressionLayer::new().quality(compression_level);
                 router.layer(layer)
             } else {
                 router
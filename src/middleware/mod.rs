@@ -1,3 +1,4 @@
+pub mod audit_middleware;
 pub mod compression_middleware;
 pub mod middleware;
 pub mod recover_middleware;
@@ -1,12 +1,40 @@
 //! Panic recovery middleware.
 //
+// A panic in a handler is caught at the middleware, logged with the request it happened on and
+// answered with a problem+json 500, so the connection stays usable and the worker keeps serving.
 
-use axum::{extract::Request, middleware::Next, response::Response};
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use futures::FutureExt;
+use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::RefCell;
+use std::panic::AssertUnwindSafe;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+use tokio::task::JoinHandle;
+use tracing::error;
+
+/// Request id header, as propagated by the cache controller.
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Handler label of requests that matched no route.
+const UNMATCHED_HANDLER: &str = "unmatched";
 
 /// Global panic counter.
 static PANICS_COUNTER: AtomicU64 = AtomicU64::new(0);
 
+static INSTALL_HOOK: Once = Once::new();
+
+thread_local! {
+    /// Backtrace of the last panic on this thread, taken by the hook where the panic happened.
+    static LAST_BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
 /// Gets the current panic counter value.
 pub fn panics_counter() -> u64 {
     PANICS_COUNTER.load(Ordering::Relaxed)
@@ -20,20 +48,142 @@ pub fn inc_panics() {
     crate::controller::metrics::inc_panics(1);
 }
 
+/// Chains a panic hook keeping the backtrace of the panic for the recovery log. It is only
+/// captured when RUST_BACKTRACE (or RUST_LIB_BACKTRACE) enables it.
+fn install_backtrace_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            LAST_BACKTRACE.with(|last| *last.borrow_mut() = Some(Backtrace::capture()));
+            previous(info);
+        }));
+    });
+}
+
+/// Takes the backtrace of the last panic on this thread, when one was captured.
+fn take_backtrace() -> Option<String> {
+    LAST_BACKTRACE
+        .with(|last| last.borrow_mut().take())
+        .filter(|bt| bt.status() == BacktraceStatus::Captured)
+        .map(|bt| bt.to_string())
+}
+
+/// Message a panic was raised with, for `panic!` with a literal or a formatted string.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload")
+}
+
+/// Counts a caught panic in the global and per-handler counters.
+fn record_panic(handler: &str) {
+    inc_panics();
+    crate::controller::metrics::inc_handler_panics(handler);
+}
+
+/// Answers a request whose handler panicked.
+fn panic_response(request_id: Option<&str>) -> Response {
+    let status = StatusCode::INTERNAL_SERVER_ERROR;
+    let mut problem = serde_json::json!({
+        "type": "about:blank",
+        "title": status.canonical_reason().unwrap_or_default(),
+        "status": status.as_u16(),
+        "detail": "the request handler panicked",
+    });
+    if let Some(id) = request_id {
+        problem["request_id"] = id.into();
+    }
+    let body = problem.to_string();
+
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/problem+json")
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(body.into())
+        .unwrap()
+}
+
+/// A task spawned for a request panicked or was cancelled before it produced its result.
+#[derive(Debug, thiserror::Error)]
+#[error("request task {handler} failed: {reason}")]
+pub struct RequestTaskError {
+    pub handler: &'static str,
+    pub reason: String,
+}
+
+/// Awaits a task spawned while serving a request (e.g. an upstream fetch run detached from
+/// the client), turning a panic in it into an error the handler answers like any other
+/// failure. The panic is logged and counted under `handler`.
+#[allow(dead_code)]
+pub async fn join_request_task<T>(handler: &'static str, task: JoinHandle<T>) -> Result<T, RequestTaskError> {
+    match task.await {
+        Ok(value) => Ok(value),
+        Err(e) if e.is_panic() => {
+            let payload = e.into_panic();
+            let reason = panic_message(payload.as_ref()).to_string();
+            error!(
+                component = "recover",
+                event = "task_panicked",
+                handler,
+                panic = %reason,
+                "request task panicked"
+            );
+            record_panic(handler);
+            Err(RequestTaskError { handler, reason })
+        }
+        Err(e) => Err(RequestTaskError {
+            handler,
+            reason: e.to_string(),
+        }),
+    }
+}
+
 /// PanicRecoverMiddleware recovers from panics in HTTP handlers.
 pub struct PanicRecoverMiddleware;
 
 impl PanicRecoverMiddleware {
     /// Creates a new panic recovery middleware.
     pub fn new() -> Self {
+        install_backtrace_hook();
         Self
     }
 
-    /// Middleware function that handles errors.
+    /// Runs the rest of the chain, answering 500 when it panics.
     pub async fn middleware(&self, request: Request, next: Next) -> Response {
-        // Execute the next middleware/handler
-        // In async Rust, we rely on proper error handling
-        next.run(request).await
+        let method = request.method().clone();
+        let path = request.uri().path().to_string();
+        let handler = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|p| p.as_str().to_string())
+            .unwrap_or_else(|| UNMATCHED_HANDLER.to_string());
+        let request_id = request
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        match AssertUnwindSafe(next.run(request)).catch_unwind().await {
+            Ok(response) => response,
+            Err(payload) => {
+                let backtrace = take_backtrace();
+                error!(
+                    component = "recover",
+                    event = "handler_panicked",
+                    method = %method,
+                    path = %path,
+                    handler = %handler,
+                    request_id = request_id.as_deref().unwrap_or_default(),
+                    panic = %panic_message(payload.as_ref()),
+                    backtrace = backtrace.as_deref().unwrap_or("disabled, set RUST_BACKTRACE=1"),
+                    "request handler panicked"
+                );
+                record_panic(&handler);
+                panic_response(request_id.as_deref())
+            }
+        }
     }
 }
 
@@ -45,9 +195,7 @@ impl Default for PanicRecoverMiddleware {
 
 // For use as axum middleware
 pub async fn panic_recover_middleware(request: Request, next: Next) -> Response {
-    PanicRecoverMiddleware::new()
-        .middleware(request, next)
-        .await
+    PanicRecoverMiddleware.middleware(request, next).await
 }
 
 // Implementation of Middleware trait
@@ -0,0 +1,150 @@
+//! Payload checksums for `storage.verify_sample`, identity hashes and ETags of stored responses.
+//
+// The checksum lives in the same allocation as the payload it covers, so a reader never sees a
+// payload paired with the checksum of the one it replaced. Nothing is hashed until a storage
+// with `verify_sample` turns checksums on. The identity hash (see `upstream::digest`) sits next
+// to it for the same reason; it is taken when a refresh first needs it. So does the ETag, an
+// xxh3 of the body taken when a fill or refresh sets the payload; payloads restored from a
+// dump have none until their next refresh.
+
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use xxhash_rust::xxh3::xxh3_64;
+
+use super::vary::Variant;
+use super::Entry;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns on checksumming of payloads stored from now on. It is never turned off: payloads
+/// stored before are simply not verified.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Encoded payload of an entry with its checksum.
+#[derive(Debug)]
+pub struct PayloadBuf {
+    bytes: Vec<u8>,
+    checksum: Option<u64>,
+    identity_hash: OnceLock<u64>,
+    etag: Option<u64>,
+    /// Variant of the response, decoded on first use (see `model::vary`).
+    pub(super) variant: OnceLock<Option<Arc<Variant>>>,
+    /// Lifetime the origin gave the payload in nanos, in place of the rule's TTL; see
+    /// `model::freshness`. Taken over from a later response with the same payload.
+    pub(super) origin_ttl: AtomicI64,
+    /// Whether the payload is a 404 or 5xx answer stored for the rule's `negative_ttl`.
+    pub(super) negative: bool,
+}
+
+impl PayloadBuf {
+    /// Wraps an encoded payload, checksummed when checksums are on.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        let checksum = ENABLED.load(Ordering::Relaxed).then(|| xxh3_64(&bytes));
+        Self {
+            bytes,
+            checksum,
+            identity_hash: OnceLock::new(),
+            etag: None,
+            variant: OnceLock::new(),
+            origin_ttl: AtomicI64::new(super::freshness::NO_ORIGIN_TTL),
+            negative: false,
+        }
+    }
+
+    /// Wraps an encoded payload whose response body is `body`, with the ETag of that body.
+    pub fn with_etag(bytes: Vec<u8>, body: &[u8]) -> Self {
+        Self { etag: Some(xxh3_64(body)), ..Self::new(bytes) }
+    }
+
+    /// The payload with the lifetime of the response it was encoded from.
+    pub fn with_freshness(self, origin_ttl: i64, negative: bool) -> Self {
+        Self { origin_ttl: AtomicI64::new(origin_ttl), negative, ..self }
+    }
+}
+
+impl Clone for PayloadBuf {
+    fn clone(&self) -> Self {
+        Self {
+            bytes: self.bytes.clone(),
+            checksum: self.checksum,
+            identity_hash: self.identity_hash.clone(),
+            etag: self.etag,
+            variant: self.variant.clone(),
+            origin_ttl: AtomicI64::new(self.origin_ttl.load(Ordering::Relaxed)),
+            negative: self.negative,
+        }
+    }
+}
+
+impl PartialEq for PayloadBuf {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes && self.checksum == other.checksum
+    }
+}
+
+impl Deref for PayloadBuf {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.bytes
+    }
+}
+
+impl Entry {
+    /// xxh3 of the encoded payload taken when it was stored, `None` if checksums were off.
+    #[allow(dead_code)]
+    pub fn checksum(&self) -> Option<u64> {
+        self.0.payload.load().as_ref().and_then(|p| p.checksum)
+    }
+
+    /// Identity hash of the stored response, taken with `f` on first use and kept with the
+    /// payload; `None` without a payload or when `f` cannot tell.
+    pub fn identity_hash_with(&self, f: impl FnOnce(&Entry) -> Option<u64>) -> Option<u64> {
+        let payload = self.0.payload.load_full()?;
+        if let Some(hash) = payload.identity_hash.get() {
+            return Some(*hash);
+        }
+        let hash = f(self)?;
+        // Only kept when it was taken from that payload, not one swapped in meanwhile.
+        match self.0.payload.load().as_ref() {
+            Some(current) if std::sync::Arc::ptr_eq(current, &payload) => Some(*payload.identity_hash.get_or_init(|| hash)),
+            _ => Some(hash),
+        }
+    }
+
+    /// xxh3 of the stored body the ETag is generated from, `None` for a payload restored from a dump.
+    pub fn etag(&self) -> Option<u64> {
+        self.0.payload.load().as_ref()?.etag
+    }
+
+    /// Records the identity hash of the stored response, known to whoever built the payload.
+    pub fn set_identity_hash(&self, hash: u64) {
+        if let Some(payload) = self.0.payload.load().as_ref() {
+            let _ = payload.identity_hash.set(hash);
+        }
+    }
+
+    /// Whether the payload still hashes to its checksum; payloads without one pass.
+    pub fn verify_checksum(&self) -> bool {
+        match self.0.payload.load().as_ref() {
+            Some(p) => p.checksum.is_none_or(|sum| xxh3_64(&p.bytes) == sum),
+            None => true,
+        }
+    }
+
+    /// Flips a bit of the stored payload behind its checksum's back, as a memory fault would.
+    #[cfg(test)]
+    #[allow(dead_code)]
+    pub fn corrupt_payload(&self, at: usize) {
+        if let Some(p) = self.0.payload.load_full() {
+            let mut corrupted = (*p).clone();
+            let at = at % corrupted.bytes.len();
+            corrupted.bytes[at] ^= 0x01;
+            self.0.payload.store(Some(std::sync::Arc::new(corrupted)));
+        }
+    }
+}
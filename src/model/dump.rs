@@ -57,13 +57,14 @@ impl Entry {
 
         serde_json::json!({
             "key": self.0.key,
-            "path": self.0.rule.path.as_deref().unwrap_or(""),
+            "path": self.0.rule.load().path.as_deref().unwrap_or(""),
             "payload": payload_map,
             "fingerprintHi": self.0.fingerprint_hi,
             "fingerprintLo": self.0.fingerprint_lo,
             "touchedAt": touched_at_secs,
             "updatedAt": updated_at_secs,
             "refreshQueued": self.0.refresh_queued.load(Ordering::Relaxed),
+            "hits": self.hits(),
         })
     }
 }
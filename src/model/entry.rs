@@ -1,9 +1,30 @@
 //! Cache entry models.
 
-use std::sync::atomic::{AtomicBool, AtomicI64};
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 
 use crate::config::Rule;
+use crate::model::checksum::PayloadBuf;
+
+/// Capacity of the key scratch buffer kept between requests; a larger one is freed.
+const KEY_SCRATCH_RETAINED: usize = 4 << 10;
+
+thread_local! {
+    /// Buffer the key bytes are laid out in before hashing.
+    static KEY_SCRATCH: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    /// Release epoch the thread's scratch buffer was last checked against.
+    static KEY_SCRATCH_EPOCH: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Bumped by [`release_key_scratch`].
+static KEY_SCRATCH_RELEASES: AtomicU64 = AtomicU64::new(0);
+
+/// Makes every thread free its key scratch buffer after its next key: the buffers belong to
+/// threads, so they cannot be freed from outside.
+pub fn release_key_scratch() {
+    KEY_SCRATCH_RELEASES.fetch_add(1, Ordering::Relaxed);
+}
 
 /// Helper struct for key building result.
 struct KeyHash {
@@ -32,6 +53,8 @@ pub struct Payload {
 pub struct RequestPayload {
     pub queries: Vec<(Vec<u8>, Vec<u8>)>,
     pub headers: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Body of a POST request keyed by it; the request is a GET without one.
+    pub body: Option<Vec<u8>>,
 }
 
 /// Response payload structure.
@@ -41,19 +64,53 @@ pub struct ResponsePayload {
     pub code: u16,
 }
 
+/// Validators of the stored response, sent back to the origin on refresh.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    /// `ETag`, sent as `If-None-Match`.
+    pub etag: Option<Vec<u8>>,
+    /// `Last-Modified`, sent as `If-Modified-Since`.
+    pub last_modified: Option<Vec<u8>>,
+}
+
+impl Validators {
+    /// Conditional request headers asking the origin for the response only if it changed.
+    pub fn conditional_headers(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut headers = Vec::with_capacity(2);
+        if let Some(etag) = &self.etag {
+            headers.push((b"If-None-Match".to_vec(), etag.clone()));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            headers.push((b"If-Modified-Since".to_vec(), last_modified.clone()));
+        }
+        headers
+    }
+}
+
 /// Internal structure for entry data.
 /// All fields are stored directly (not in Arc) since Entry itself wraps this in Arc.
 pub struct EntryInner {
     pub(crate) key: u64,
     pub(crate) fingerprint_hi: u64,
     pub(crate) fingerprint_lo: u64,
-    pub(crate) rule: Arc<Rule>,
+    /// Key schema of the rule the key was built with (see `Rule::key_schema`), 0 if unknown.
+    pub(crate) key_schema: u64,
+    /// Swapped for the reloaded rule of the same path by rule reconciliation.
+    pub(crate) rule: arc_swap::ArcSwap<Rule>,
     // Payload stored as Vec<u8> - simple, no overhead, guaranteed single copy
     // Use ArcSwapOption for atomic updates without locks, Option allows empty payload
-    pub(crate) payload: arc_swap::ArcSwapOption<Vec<u8>>,
+    pub(crate) payload: arc_swap::ArcSwapOption<PayloadBuf>,
     pub(crate) touched_at: AtomicI64,
     pub(crate) updated_at: AtomicI64,
     pub(crate) refresh_queued: AtomicBool,
+    /// Bucket of the entry's record in its shard's expiry index, 0 if it has none.
+    pub(crate) expiry_bucket: AtomicU32,
+    /// Weight the entry's shard accounts for it, set under the shard lock so that removal
+    /// takes back exactly what was added, whatever the payload was swapped for meanwhile.
+    pub(crate) accounted_weight: AtomicI64,
+    /// Recent storage hits, saturating at `HITS_CEILING` and halved by each eviction scan
+    /// looking at the entry.
+    pub(crate) hits: AtomicU32,
 }
 
 /// Entry represents a cache entry.
@@ -73,25 +130,47 @@ impl Entry {
             key: 0,
             fingerprint_hi: 0,
             fingerprint_lo: 0,
-            rule: Arc::new(Rule {
+            key_schema: 0,
+            rule: arc_swap::ArcSwap::from_pointee(Rule {
                 path: None,
                 path_bytes: None,
                 cache_key: crate::config::RuleKey {
                     query: None,
                     query_bytes: None,
+                    query_ignore: None,
+                    duplicate_query: None,
+                    transformer: None,
                     headers: None,
                     headers_map: None,
                 },
                 cache_value: crate::config::RuleValue {
                     headers: None,
                     headers_map: None,
+                    persist: None,
+                    rollout_percent: Default::default(),
+                    max_concurrent_fills: None,
+                    max_fill_wait: None,
+                    admission_hook: None,
+                    downstream_ttl: Default::default(),
+                    respect_cache_control: None,
+                    negative_ttl: None,
                 },
                 refresh: None,
+                stale_on_error: None,
+                shed_on_brownout: None,
+                methods: None,
+                key_body: None,
+                max_key_body: None,
+                max_body_size: None,
+                refresh_inherited: false,
             }),
             payload: arc_swap::ArcSwapOption::empty(),
             touched_at: AtomicI64::new(0),
             updated_at: AtomicI64::new(0),
             refresh_queued: AtomicBool::new(false),
+            expiry_bucket: AtomicU32::new(0),
+            accounted_weight: AtomicI64::new(0),
+            hits: AtomicU32::new(0),
         }
     }
 
@@ -104,39 +183,58 @@ impl Entry {
     #[cfg(test)]
     pub fn with_rule(self, rule: Arc<Rule>) -> Self {
         // Create new EntryInner with updated rule
-        use std::sync::atomic::Ordering;
         let payload_guard = self.0.payload.load();
         let payload_clone = payload_guard.as_ref().map(|arc_vec| Arc::clone(arc_vec));
         let inner = EntryInner {
             key: self.0.key,
             fingerprint_hi: self.0.fingerprint_hi,
             fingerprint_lo: self.0.fingerprint_lo,
-            rule,
+            key_schema: self.0.key_schema,
+            rule: arc_swap::ArcSwap::new(rule),
             payload: arc_swap::ArcSwapOption::from(payload_clone),
             touched_at: AtomicI64::new(self.0.touched_at.load(Ordering::Relaxed)),
             updated_at: AtomicI64::new(self.0.updated_at.load(Ordering::Relaxed)),
             refresh_queued: AtomicBool::new(self.0.refresh_queued.load(Ordering::Relaxed)),
+            expiry_bucket: AtomicU32::new(0),
+            accounted_weight: AtomicI64::new(0),
+            hits: AtomicU32::new(0),
         };
         Self(Arc::new(inner))
     }
 
     /// Creates a new entry.
+    #[cfg_attr(not(feature = "mocks"), allow(dead_code))]
     pub fn new(
         rule: Arc<Rule>,
         queries: &[(Vec<u8>, Vec<u8>)],
         headers: &[(Vec<u8>, Vec<u8>)],
     ) -> Self {
-        let key_hash = Self::build_key_hash(queries, headers, &rule);
+        Self::with_key_extra(rule, queries, headers, None)
+    }
+
+    /// Creates a new entry whose key also covers `extra`, the bytes a key transformer
+    /// derived from the request.
+    pub fn with_key_extra(
+        rule: Arc<Rule>,
+        queries: &[(Vec<u8>, Vec<u8>)],
+        headers: &[(Vec<u8>, Vec<u8>)],
+        extra: Option<&[u8]>,
+    ) -> Self {
+        let key_hash = Self::build_key_hash(queries, headers, extra, &rule);
         
         let inner = EntryInner {
             key: key_hash.key,
             fingerprint_hi: key_hash.fingerprint_hi,
             fingerprint_lo: key_hash.fingerprint_lo,
-            rule,
+            key_schema: rule.key_schema(),
+            rule: arc_swap::ArcSwap::new(rule),
             payload: arc_swap::ArcSwapOption::empty(),
             touched_at: AtomicI64::new(0),
             updated_at: AtomicI64::new(0),
             refresh_queued: AtomicBool::new(false),
+            expiry_bucket: AtomicU32::new(0),
+            accounted_weight: AtomicI64::new(0),
+            hits: AtomicU32::new(0),
         };
         Self(Arc::new(inner))
     }
@@ -145,51 +243,56 @@ impl Entry {
     fn build_key_hash(
         filtered_queries: &[(Vec<u8>, Vec<u8>)],
         filtered_headers: &[(Vec<u8>, Vec<u8>)],
+        extra: Option<&[u8]>,
         rule: &Rule,
     ) -> KeyHash {
         use xxhash_rust::xxh3::xxh3_128;
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
         
-        // Calculate buffer size
-        let mut buf_len = rule.path_bytes.as_ref().map(|p| p.len()).unwrap_or(0);
-        for (k, v) in filtered_queries {
-            buf_len += k.len() + v.len();
-        }
-        for (k, v) in filtered_headers {
-            buf_len += k.len() + v.len();
-        }
+        // Built in a per-thread scratch buffer: keys are hashed on every request.
+        KEY_SCRATCH.with(|scratch| {
+            let mut buf = scratch.borrow_mut();
+            buf.clear();
+            if let Some(ref path_bytes) = rule.path_bytes {
+                buf.extend_from_slice(path_bytes);
+            }
+            for (k, v) in filtered_queries {
+                buf.extend_from_slice(k);
+                buf.extend_from_slice(v);
+            }
+            for (k, v) in filtered_headers {
+                buf.extend_from_slice(k);
+                buf.extend_from_slice(v);
+            }
+            // Separated so that transformer bytes can never read as a header value
+            if let Some(extra) = extra {
+                buf.push(0);
+                buf.extend_from_slice(extra);
+            }
 
-        let mut buf = Vec::with_capacity(buf_len);
-        if let Some(ref path_bytes) = rule.path_bytes {
-            buf.extend_from_slice(path_bytes);
-        }
-        for (k, v) in filtered_queries {
-            buf.extend_from_slice(k);
-            buf.extend_from_slice(v);
-        }
-        for (k, v) in filtered_headers {
-            buf.extend_from_slice(k);
-            buf.extend_from_slice(v);
-        }
+            // Calculate hash using DefaultHasher
+            let mut hasher = DefaultHasher::new();
+            buf.hash(&mut hasher);
+            let key = hasher.finish();
 
-        // Calculate hash using DefaultHasher
-        let mut hasher = DefaultHasher::new();
-        buf.hash(&mut hasher);
-        let key = hasher.finish();
-        
-        // For 128-bit fingerprint, use xxh3_128 directly
-        let fingerprint = xxh3_128(&buf);
-        let fingerprint_hi = (fingerprint >> 64) as u64;
-        let fingerprint_lo = fingerprint as u64;
+            // For 128-bit fingerprint, use xxh3_128 directly
+            let fingerprint = xxh3_128(&buf);
 
-        // buf is dropped here - memory freed
+            // An unusually long key must not pin its buffer to the thread, nor any key once
+            // the buffers were released.
+            let releases = KEY_SCRATCH_RELEASES.load(Ordering::Relaxed);
+            let released = KEY_SCRATCH_EPOCH.with(|epoch| epoch.replace(releases) != releases);
+            if released || buf.capacity() > KEY_SCRATCH_RETAINED {
+                *buf = Vec::new();
+            }
 
-        KeyHash {
-            key,
-            fingerprint_hi,
-            fingerprint_lo,
-        }
+            KeyHash {
+                key,
+                fingerprint_hi: (fingerprint >> 64) as u64,
+                fingerprint_lo: fingerprint as u64,
+            }
+        })
     }
 
     /// Creates a new entry from fields.
@@ -197,6 +300,7 @@ impl Entry {
         key: u64,
         f_hi: u64,
         f_lo: u64,
+        key_schema: u64,
         payload: Vec<u8>,
         rule: Arc<Rule>,
         updated_at: i64,
@@ -204,17 +308,46 @@ impl Entry {
         let payload_opt = if payload.is_empty() {
             None
         } else {
-            Some(Arc::new(payload))
+            Some(Arc::new(PayloadBuf::new(payload)))
         };
         let inner = EntryInner {
             key,
             fingerprint_hi: f_hi,
             fingerprint_lo: f_lo,
-            rule,
+            key_schema,
+            rule: arc_swap::ArcSwap::new(rule),
             payload: arc_swap::ArcSwapOption::from(payload_opt),
             touched_at: AtomicI64::new(0),
             updated_at: AtomicI64::new(updated_at),
             refresh_queued: AtomicBool::new(false),
+            expiry_bucket: AtomicU32::new(0),
+            accounted_weight: AtomicI64::new(0),
+            hits: AtomicU32::new(0),
+        };
+        Self(Arc::new(inner))
+    }
+
+    /// Creates an entry with the key, fingerprint and rule of this one and nothing else,
+    /// to carry a refreshed payload until the storage swaps it in.
+    pub fn detached(&self) -> Self {
+        self.rekeyed(self.0.key, self.0.fingerprint_hi, self.0.fingerprint_lo)
+    }
+
+    /// Creates an entry with the given key and fingerprint and the rule of this one.
+    pub(crate) fn rekeyed(&self, key: u64, fingerprint_hi: u64, fingerprint_lo: u64) -> Self {
+        let inner = EntryInner {
+            key,
+            fingerprint_hi,
+            fingerprint_lo,
+            key_schema: self.0.key_schema,
+            rule: arc_swap::ArcSwap::new(self.0.rule.load_full()),
+            payload: arc_swap::ArcSwapOption::empty(),
+            touched_at: AtomicI64::new(0),
+            updated_at: AtomicI64::new(0),
+            refresh_queued: AtomicBool::new(false),
+            expiry_bucket: AtomicU32::new(0),
+            accounted_weight: AtomicI64::new(0),
+            hits: AtomicU32::new(0),
         };
         Self(Arc::new(inner))
     }
@@ -241,13 +374,13 @@ impl Entry {
     }
 
     /// Gets the rule.
-    pub(crate) fn rule(&self) -> &Arc<Rule> {
-        &self.0.rule
+    pub(crate) fn rule(&self) -> Arc<Rule> {
+        self.0.rule.load_full()
     }
 
-    /// Gets updated_at atomic reference (internal use).
-    pub(crate) fn updated_at_ref(&self) -> &AtomicI64 {
-        &self.0.updated_at
+    /// Points the entry at `rule`, the current rule of its path after a config reload.
+    pub(crate) fn set_rule(&self, rule: Arc<Rule>) {
+        self.0.rule.store(rule);
     }
 }
 
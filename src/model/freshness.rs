@@ -0,0 +1,82 @@
+//! Lifetime the origin gives a payload, for rules with `cache_value.respect_cache_control`,
+//! and the lifetime of negative payloads, 404 and 5xx answers stored for the rule's
+//! `cache_value.negative_ttl`.
+//!
+//! It is taken from the response the payload is set from, on fills and refreshes alike, and
+//! kept in the payload buffer, so that swapping the payload into the resident entry publishes
+//! it along with the body in one go. Dumps do not keep it: a loaded entry goes by its rule's
+//! TTL until its next refresh, and negative entries are not dumped at all. A response with
+//! `Vary: *` is not stored, whatever the rule.
+
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use super::{vary, Entry, Response};
+use crate::http::header::cache_control::{self, Freshness};
+use crate::time;
+
+/// Origin TTL of an entry going by its rule's TTL.
+pub const NO_ORIGIN_TTL: i64 = -1;
+/// Origin TTL of a payload the origin asked not to store.
+const NO_STORE: i64 = -2;
+
+impl Entry {
+    /// Origin TTL and negative flag of `resp`, the response the payload is being set from,
+    /// when the rule follows the origin's freshness or stores negative answers.
+    pub(crate) fn origin_freshness(&self, resp: &Response) -> (i64, bool) {
+        let rule = self.0.rule.load();
+        let negative_ttl = rule.cache_value.negative_ttl.filter(|_| is_negative_status(resp.status));
+        let negative = negative_ttl.is_some();
+        if vary::varies_on_anything(&resp.headers) {
+            return (NO_STORE, negative);
+        }
+        if let Some(ttl) = negative_ttl {
+            return (ttl.as_nanos().clamp(1, i64::MAX as u128) as i64, negative);
+        }
+        if rule.cache_value.respect_cache_control != Some(true) {
+            return (NO_ORIGIN_TTL, negative);
+        }
+        let ttl = match cache_control::freshness(&resp.headers, time::now()) {
+            Freshness::NoStore => NO_STORE,
+            // A zero TTL means none to the lifetime settings; 1ns is over as soon as stored.
+            Freshness::Fresh(ttl) => ttl.as_nanos().clamp(1, i64::MAX as u128) as i64,
+            Freshness::Unspecified => NO_ORIGIN_TTL,
+        };
+        (ttl, negative)
+    }
+
+    /// Raw origin TTL of the payload, `NO_ORIGIN_TTL` without one.
+    fn raw_origin_ttl(&self) -> i64 {
+        self.0.payload.load().as_ref().map_or(NO_ORIGIN_TTL, |p| p.origin_ttl.load(Ordering::Relaxed))
+    }
+
+    /// Whether the origin asked not to store the payload.
+    pub fn is_no_store(&self) -> bool {
+        self.raw_origin_ttl() == NO_STORE
+    }
+
+    /// Lifetime the origin gave the payload, in place of the rule's TTL.
+    pub fn origin_ttl(&self) -> Option<Duration> {
+        let ttl = self.raw_origin_ttl();
+        (ttl >= 0).then(|| Duration::from_nanos(ttl as u64))
+    }
+
+    /// Whether the payload is a 404 or 5xx answer stored for the rule's `negative_ttl`: such an
+    /// entry is removed once past it rather than refreshed.
+    pub fn is_negative(&self) -> bool {
+        self.0.payload.load().as_ref().is_some_and(|p| p.negative)
+    }
+
+    /// Takes the origin TTL of `other`, whose payload is the same response as the one this
+    /// entry keeps, so the negative flag, taken from its status, is the same as well.
+    pub(crate) fn take_origin_ttl(&self, other: &Entry) {
+        if let Some(payload) = self.0.payload.load().as_ref() {
+            payload.origin_ttl.store(other.raw_origin_ttl(), Ordering::Relaxed);
+        }
+    }
+}
+
+/// Statuses stored for a rule's `negative_ttl`.
+pub fn is_negative_status(status: u16) -> bool {
+    status == 404 || status >= 500
+}
@@ -12,7 +12,7 @@ impl Entry {
     ) -> Vec<(Vec<u8>, Vec<u8>)> {
         let mut out = Vec::new();
 
-        if let Some(ref headers_map) = self.0.rule.cache_key.headers_map {
+        if let Some(ref headers_map) = self.0.rule.load().cache_key.headers_map {
             for (key, key_bytes) in headers_map {
                 // Find matching header value
                 if let Some((_, value)) = request_headers
@@ -39,7 +39,7 @@ impl Entry {
     ) -> Vec<(Vec<u8>, Vec<u8>)> {
         let mut out = Vec::new();
 
-        if let Some(ref headers_map) = self.0.rule.cache_key.headers_map {
+        if let Some(ref headers_map) = self.0.rule.load().cache_key.headers_map {
             for (key, key_bytes) in headers_map {
                 // Find matching header value (case-insensitive)
                 let key_str = key.clone();
@@ -9,6 +9,11 @@ impl Entry {
         self.0.key
     }
 
+    /// Gets the key schema the key was built with (0 when unknown, e.g. restored from an old dump).
+    pub fn key_schema(&self) -> u64 {
+        self.0.key_schema
+    }
+
     /// Checks if two entries have the same fingerprint.
     pub fn is_the_same_fingerprint(&self, other: &Entry) -> bool {
         self.0.fingerprint_hi == other.0.fingerprint_hi 
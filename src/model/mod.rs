@@ -1,7 +1,9 @@
 //! Cache entry models and related functionality.
 
+pub mod checksum;
 pub mod dump;
 pub mod entry;
+pub mod freshness;
 pub mod header;
 pub mod keys;
 pub mod payload;
@@ -10,8 +12,10 @@ pub mod payload_encoder;
 pub mod query;
 pub mod refresh;
 pub mod rule;
+pub mod surrogate;
 pub mod timestamps;
 pub mod to_bytes;
+pub mod vary;
 
 #[cfg(test)]
 mod refresh_test;
@@ -23,7 +27,12 @@ mod payload_test;
 mod timestamps_test;
 #[cfg(test)]
 mod payload_encode_decode_test;
+#[cfg(test)]
+mod payload_golden_test;
+#[cfg(test)]
+mod vary_test;
 
 // Re-export main types
-pub use entry::{Entry, Payload, RequestPayload, Response, ResponsePayload};
+pub use entry::{Entry, Payload, RequestPayload, Response, ResponsePayload, Validators};
+pub use refresh::RefreshParams;
 pub use rule::{is_cache_rule_not_found_err, match_cache_rule};
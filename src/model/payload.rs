@@ -1,8 +1,6 @@
 //! Payload operations.
 //
 
-use std::sync::Arc;
-
 use super::Entry;
 
 // Re-export constants from payload_encoder
@@ -27,6 +25,17 @@ impl Entry {
         struct_size + payload_capacity
     }
 
+    /// Weight the entry's shard accounts for it: the weight when it was stored or last
+    /// reweighed, which lags [`Entry::weight`] while a swapped payload is not reweighed yet.
+    pub fn accounted_weight(&self) -> i64 {
+        self.0.accounted_weight.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Records the weight the entry's shard accounts for it; under the shard lock only.
+    pub fn set_accounted_weight(&self, weight: i64) {
+        self.0.accounted_weight.store(weight, std::sync::atomic::Ordering::Relaxed);
+    }
+
     /// Gets the estimated physical memory weight including overheads.
     /// This accounts for HashMap buckets, LRU nodes (if in listing mode), and Arc overhead.
     /// 
@@ -66,27 +75,21 @@ impl Entry {
     }
 
     /// Swaps payloads between two entries and returns weight difference.
-    /// 
-    /// Optimized to avoid unnecessary Arc clones that could cause memory leaks.
-    /// Uses ArcSwapOption::swap for atomic swap without temporary clones.
+    ///
+    /// The payload of `self`, along with the freshness and negative flag it carries, is replaced
+    /// with one atomic swap, so concurrent readers of `self` see either its old payload or
+    /// `other`'s, never anything in between. `other` is a
+    /// detached incoming entry and gets the old payload back; the weight difference is computed
+    /// from the exact buffers swapped.
     pub fn swap_payloads(&self, other: &Entry) -> i64 {
-        let new_weight = other.weight();
-        let old_weight = self.weight();
+        let incoming = other.0.payload.load_full();
+        let new_capacity = incoming.as_ref().map(|p| p.capacity()).unwrap_or(0) as i64;
 
-        // Load current payloads
-        let self_guard = self.0.payload.load();
-        let other_guard = other.0.payload.load();
-        
-        let self_payload = self_guard.as_ref().map(Arc::clone);
-        let other_payload = other_guard.as_ref().map(Arc::clone);
-        
-        drop(self_guard);
-        drop(other_guard);
-        
-        self.0.payload.store(other_payload);
-        other.0.payload.store(self_payload);
+        let previous = self.0.payload.swap(incoming);
+        let old_capacity = previous.as_ref().map(|p| p.capacity()).unwrap_or(0) as i64;
+        other.0.payload.store(previous);
 
-        new_weight - old_weight
+        new_capacity - old_capacity
     }
 
     /// Checks if two entries have the same payload.
@@ -110,10 +113,11 @@ impl Entry {
     }
 
     /// Gets the payload bytes as Vec<u8> (copy).
+    #[cfg_attr(not(feature = "dump"), allow(dead_code))]
     pub fn payload_bytes(&self) -> Vec<u8> {
         self.0.payload.load()
             .as_ref()
-            .map(|arc_vec| (**arc_vec).clone())
+            .map(|arc_vec| (***arc_vec).clone())
             .unwrap_or_default()
     }
 }
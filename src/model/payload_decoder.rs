@@ -1,12 +1,19 @@
 //! Payload decoding functionality.
+//
+// Every decode works on one snapshot of the payload: the buffer is loaded once and all sections
+// are read from it, so a refresh swapping the payload mid-decode cannot pair the request of one
+// version with the response of another.
+
+use std::sync::Arc;
 
 use byteorder::{ByteOrder, LittleEndian};
 
+use super::checksum::PayloadBuf;
 use super::payload_encoder::*;
-use super::{Entry, Payload, RequestPayload, ResponsePayload};
+use super::{Entry, Payload, RequestPayload, ResponsePayload, Validators};
 
 /// Error types for payload decoding.
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
 pub enum PayloadError {
     #[error("malformed or nil payload")]
     MalformedOrNilPayload,
@@ -25,8 +32,9 @@ pub enum PayloadError {
 impl Entry {
     /// Gets the full payload (queries, request headers, response headers, body, code).
     pub fn payload(&self) -> Result<Payload, PayloadError> {
-        let req_payload = self.request_payload()?;
-        let resp_payload = self.response_payload()?;
+        let data = self.payload_snapshot()?;
+        let req_payload = self.decode_request(&data)?;
+        let resp_payload = self.decode_response(&data)?;
 
         Ok(Payload {
             queries: req_payload.queries,
@@ -37,23 +45,47 @@ impl Entry {
         })
     }
 
-    /// Gets the request payload (queries and headers).
+    /// Gets the request payload (queries, headers and the body of a keyed POST).
     pub fn request_payload(&self) -> Result<RequestPayload, PayloadError> {
-        let data = self.get_payload_data()?;
-
-        let queries = self.unpack_queries(&data)?;
-        let headers = self.unpack_request_headers(&data)?;
-
-        Ok(RequestPayload { queries, headers })
+        let data = self.payload_snapshot()?;
+        self.decode_request(&data)
     }
 
     /// Gets the response payload (headers, body, code).
     pub fn response_payload(&self) -> Result<ResponsePayload, PayloadError> {
-        let data = self.get_payload_data()?;
+        let data = self.payload_snapshot()?;
+        self.decode_response(&data)
+    }
+
+    /// Gets the validators of the stored response, taken from its headers.
+    pub fn validators(&self) -> Result<Validators, PayloadError> {
+        let data = self.payload_snapshot()?;
+        let mut validators = Validators::default();
+        for (name, value) in self.unpack_response_headers(&data)? {
+            if name.eq_ignore_ascii_case(b"etag") {
+                validators.etag.get_or_insert(value);
+            } else if name.eq_ignore_ascii_case(b"last-modified") {
+                validators.last_modified.get_or_insert(value);
+            }
+        }
+        Ok(validators)
+    }
 
-        let code = self.unpack_status_code(&data)?;
-        let headers = self.unpack_response_headers(&data)?;
-        let body = self.unpack_response_body(&data)?;
+    fn decode_request(&self, data: &[u8]) -> Result<RequestPayload, PayloadError> {
+        let queries = self.unpack_queries(data)?;
+        let mut headers = self.unpack_request_headers(data)?;
+        let body = match headers.last() {
+            Some((name, _)) if name == REQUEST_BODY_PSEUDO_HEADER => headers.pop().map(|(_, body)| body),
+            _ => None,
+        };
+
+        Ok(RequestPayload { queries, headers, body })
+    }
+
+    fn decode_response(&self, data: &[u8]) -> Result<ResponsePayload, PayloadError> {
+        let code = self.unpack_status_code(data)?;
+        let headers = self.unpack_response_headers(data)?;
+        let body = self.unpack_response_body(data)?;
 
         Ok(ResponsePayload {
             headers,
@@ -64,74 +96,17 @@ impl Entry {
 
     /// Unpacks queries from the payload.
     fn unpack_queries(&self, data: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, PayloadError> {
-        let offset_from = LittleEndian::read_u32(&data[OFF_QUERY..OFF_QUERY + OFF_WEIGHT]) as usize;
-        let offset_to =
-            LittleEndian::read_u32(&data[OFF_REQ_HDRS..OFF_REQ_HDRS + OFF_WEIGHT]) as usize;
-
-        let mut queries = Vec::new();
-        let mut pos = offset_from;
-
-        while pos < offset_to {
-            if pos + OFF_WEIGHT > data.len() {
-                return Err(PayloadError::CorruptedQueriesSection);
-            }
-
-            let k_len = LittleEndian::read_u32(&data[pos..pos + OFF_WEIGHT]) as usize;
-            pos += OFF_WEIGHT;
-            if pos + k_len > data.len() {
-                return Err(PayloadError::CorruptedQueriesSection);
-            }
-            let k = data[pos..pos + k_len].to_vec();
-            pos += k_len;
-
-            let v_len = LittleEndian::read_u32(&data[pos..pos + OFF_WEIGHT]) as usize;
-            pos += OFF_WEIGHT;
-            if pos + v_len > data.len() {
-                return Err(PayloadError::CorruptedQueriesSection);
-            }
-            let v = data[pos..pos + v_len].to_vec();
-            pos += v_len;
-
-            queries.push((k, v));
-        }
-
-        Ok(queries)
+        unpack_kv_section(data, OFF_QUERY, OFF_REQ_HDRS, PayloadError::CorruptedQueriesSection)
     }
 
     /// Unpacks request headers from the payload.
-    fn unpack_request_headers(&self, data: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, PayloadError> {
-        let offset_from =
-            LittleEndian::read_u32(&data[OFF_REQ_HDRS..OFF_REQ_HDRS + OFF_WEIGHT]) as usize;
-        let offset_to = LittleEndian::read_u32(&data[OFF_STATUS..OFF_STATUS + OFF_WEIGHT]) as usize;
-
-        let mut headers = Vec::new();
-        let mut pos = offset_from;
-
-        while pos < offset_to {
-            if pos + OFF_WEIGHT > data.len() {
-                return Err(PayloadError::CorruptedRequestHeadersSection);
-            }
-
-            let k_len = LittleEndian::read_u32(&data[pos..pos + OFF_WEIGHT]) as usize;
-            pos += OFF_WEIGHT;
-            if pos + k_len > data.len() {
-                return Err(PayloadError::CorruptedRequestHeadersSection);
-            }
-            let k = data[pos..pos + k_len].to_vec();
-            pos += k_len;
-
-            let v_len = LittleEndian::read_u32(&data[pos..pos + OFF_WEIGHT]) as usize;
-            pos += OFF_WEIGHT;
-            if pos + v_len > data.len() {
-                return Err(PayloadError::CorruptedRequestHeadersSection);
-            }
-            let v = data[pos..pos + v_len].to_vec();
-            pos += v_len;
-
-            headers.push((k, v));
-        }
-
-        Ok(headers)
+    pub(super) fn unpack_request_headers(&self, data: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, PayloadError> {
+        unpack_kv_section(
+            data,
+            OFF_REQ_HDRS,
+            OFF_STATUS,
+            PayloadError::CorruptedRequestHeadersSection,
+        )
     }
 
     /// Unpacks status code from the payload.
@@ -153,38 +128,27 @@ impl Entry {
         &self,
         data: &[u8],
     ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, PayloadError> {
-        let offset_from =
-            LittleEndian::read_u32(&data[OFF_RESP_HDRS..OFF_RESP_HDRS + OFF_WEIGHT]) as usize;
-        let offset_to = LittleEndian::read_u32(&data[OFF_BODY..OFF_BODY + OFF_WEIGHT]) as usize;
-
-        let mut headers = Vec::new();
-        let mut pos = offset_from;
-
-        while pos < offset_to {
-            if pos + OFF_WEIGHT > data.len() {
-                return Err(PayloadError::CorruptedResponseHeadersSection);
-            }
-
-            let k_len = LittleEndian::read_u32(&data[pos..pos + OFF_WEIGHT]) as usize;
-            pos += OFF_WEIGHT;
-            if pos + k_len > data.len() {
-                return Err(PayloadError::CorruptedResponseHeadersSection);
-            }
-            let k = data[pos..pos + k_len].to_vec();
-            pos += k_len;
-
-            let v_len = LittleEndian::read_u32(&data[pos..pos + OFF_WEIGHT]) as usize;
-            pos += OFF_WEIGHT;
-            if pos + v_len > data.len() {
-                return Err(PayloadError::CorruptedResponseHeadersSection);
-            }
-            let v = data[pos..pos + v_len].to_vec();
-            pos += v_len;
-
-            headers.push((k, v));
-        }
+        unpack_kv_section(
+            data,
+            OFF_RESP_HDRS,
+            OFF_BODY,
+            PayloadError::CorruptedResponseHeadersSection,
+        )
+    }
 
-        Ok(headers)
+    /// Unpacks the response headers of the name, allocating nothing when there are none.
+    pub(super) fn unpack_response_headers_named(
+        &self,
+        data: &[u8],
+        name: &[u8],
+    ) -> Result<KvPairs, PayloadError> {
+        unpack_kv_section_filtered(
+            data,
+            OFF_RESP_HDRS,
+            OFF_BODY,
+            PayloadError::CorruptedResponseHeadersSection,
+            |k| k.eq_ignore_ascii_case(name),
+        )
     }
 
     /// Unpacks response body from the payload.
@@ -207,24 +171,76 @@ impl Entry {
         Ok(data[offset_from..offset_to].to_vec())
     }
 
-    /// Gets the payload data, checking for validity (returns Vec<u8> copy).
-    fn get_payload_data(&self) -> Result<Vec<u8>, PayloadError> {
-        let payload_guard = self.0.payload.load();
-        let arc_vec = match payload_guard.as_ref() {
-            Some(arc_vec) => arc_vec,
-            None => return Err(PayloadError::MalformedOrNilPayload),
-        };
-        
-        let data = &**arc_vec;
-        if data.is_empty() {
-            return Err(PayloadError::MalformedOrNilPayload);
+    /// Takes the current payload, checking for validity. The snapshot stays valid and
+    /// unchanged however many times the entry is refreshed while it is held.
+    fn payload_snapshot(&self) -> Result<Arc<PayloadBuf>, PayloadError> {
+        match self.0.payload.load_full() {
+            Some(buf) if buf.len() >= OFFSETS_MAP_SIZE => Ok(buf),
+            _ => Err(PayloadError::MalformedOrNilPayload),
         }
-        
-        if data.len() < OFFSETS_MAP_SIZE {
-            return Err(PayloadError::MalformedOrNilPayload);
+    }
+}
+
+/// Decoded key/value pairs of a payload section.
+type KvPairs = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Unpacks a sequence of length-prefixed key/value pairs whose bounds are stored
+/// in the offsets map at `off_from` and `off_to`.
+///
+/// Offsets and lengths come straight from the (possibly restored from disk) payload,
+/// so every one of them is validated against the buffer before slicing.
+fn unpack_kv_section(
+    data: &[u8],
+    off_from: usize,
+    off_to: usize,
+    err: PayloadError,
+) -> Result<KvPairs, PayloadError> {
+    unpack_kv_section_filtered(data, off_from, off_to, err, |_| true)
+}
+
+/// Like [`unpack_kv_section`], copying only the pairs whose key is kept. Every pair is still
+/// validated.
+fn unpack_kv_section_filtered(
+    data: &[u8],
+    off_from: usize,
+    off_to: usize,
+    err: PayloadError,
+    keep: impl Fn(&[u8]) -> bool,
+) -> Result<KvPairs, PayloadError> {
+    let offset_from = LittleEndian::read_u32(&data[off_from..off_from + OFF_WEIGHT]) as usize;
+    let offset_to = LittleEndian::read_u32(&data[off_to..off_to + OFF_WEIGHT]) as usize;
+
+    if offset_from < OFFSETS_MAP_SIZE || offset_to > data.len() {
+        return Err(err);
+    }
+
+    let mut pairs = Vec::new();
+    let mut pos = offset_from;
+
+    while pos < offset_to {
+        let (k, next) = read_chunk(data, pos, offset_to).ok_or(err)?;
+        let (v, next) = read_chunk(data, next, offset_to).ok_or(err)?;
+        pos = next;
+
+        if keep(k) {
+            pairs.push((k.to_vec(), v.to_vec()));
         }
-        
-        // Clone Vec<u8> (copy, but simple and guaranteed single source)
-        Ok(data.clone())
     }
+
+    Ok(pairs)
+}
+
+/// Reads a u32 length-prefixed chunk at `pos` which must fit entirely below `limit`.
+/// Returns the chunk and the position right after it.
+fn read_chunk(data: &[u8], pos: usize, limit: usize) -> Option<(&[u8], usize)> {
+    let len_end = pos.checked_add(OFF_WEIGHT)?;
+    if len_end > limit {
+        return None;
+    }
+    let len = LittleEndian::read_u32(&data[pos..len_end]) as usize;
+    let end = len_end.checked_add(len)?;
+    if end > limit {
+        return None;
+    }
+    Some((&data[len_end..end], end))
 }
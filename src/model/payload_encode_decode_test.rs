@@ -12,14 +12,32 @@ mod tests {
             cache_key: RuleKey {
                 query: None,
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: None,
                 headers_map: None,
             },
             cache_value: RuleValue {
                 headers: None,
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         })
     }
 
@@ -176,6 +194,28 @@ mod tests {
         assert_eq!(payload.rsp_headers.len(), 2);
     }
 
+    /// Test that a request body round-trips apart from the request headers.
+    #[test]
+    fn test_encode_decode_request_body() {
+        let queries = vec![(b"q".to_vec(), b"1".to_vec())];
+        let headers = vec![(b"accept".to_vec(), b"application/json".to_vec())];
+        let response = Response {
+            status: 200,
+            headers: vec![],
+            body: b"ok".to_vec(),
+        };
+
+        let entry = Entry::new(make_rule(), &queries, &headers);
+        entry.set_payload_with_body(&queries, &headers, Some(b"{\"q\":\"shoes\"}"), &response);
+        let req_payload = entry.request_payload().unwrap();
+        assert_eq!(req_payload.headers, headers);
+        assert_eq!(req_payload.body.as_deref(), Some(b"{\"q\":\"shoes\"}".as_slice()));
+
+        let entry = Entry::new(make_rule(), &queries, &headers);
+        entry.set_payload(&queries, &headers, &response);
+        assert!(entry.request_payload().unwrap().body.is_none());
+    }
+
     /// Test encoding with empty components.
     #[test]
     fn test_encode_decode_empty_components() {
@@ -4,6 +4,7 @@
 use std::sync::Arc;
 use byteorder::{ByteOrder, LittleEndian};
 
+use super::checksum::PayloadBuf;
 use super::{Entry, Response};
 
 /// Payload offset constants.
@@ -15,14 +16,45 @@ pub const OFF_RESP_HDRS: usize = 12;
 pub const OFF_BODY: usize = 16;
 pub const OFF_WEIGHT: usize = 4;
 
+/// Name the body of a keyed POST request (`key_body`) is stored under, last in the request
+/// headers section; no header can be named so. Decoding takes it out of the headers again.
+pub const REQUEST_BODY_PSEUDO_HEADER: &[u8] = b":body";
+
 impl Entry {
     /// Sets the payload from queries, headers, and response.
+    ///
+    /// The new payload is encoded into its own buffer and published with a single atomic
+    /// store, so readers see either the whole previous payload or the whole new one, never a
+    /// partially written buffer. Readers holding the previous payload keep it alive until they
+    /// are done. Callers update the timestamps (`touch_refreshed_at`) after this returns: a
+    /// reader that observes the new refresh time is then guaranteed to observe the new payload.
+    /// The origin's freshness of `resp` is taken along (see `model::freshness`).
+    #[cfg_attr(not(feature = "mocks"), allow(dead_code))]
     pub fn set_payload(
         &self,
         queries: &[(Vec<u8>, Vec<u8>)],
         headers: &[(Vec<u8>, Vec<u8>)],
         resp: &Response,
     ) {
+        self.set_payload_with_body(queries, headers, None, resp)
+    }
+
+    /// Sets the payload of a request that carried a body (a POST keyed by it), stored along
+    /// so that refreshes send the same request.
+    pub fn set_payload_with_body(
+        &self,
+        queries: &[(Vec<u8>, Vec<u8>)],
+        headers: &[(Vec<u8>, Vec<u8>)],
+        body: Option<&[u8]>,
+        resp: &Response,
+    ) {
+        let headers = match body {
+            Some(body) => std::borrow::Cow::Owned(
+                [headers, &[(REQUEST_BODY_PSEUDO_HEADER.to_vec(), body.to_vec())]].concat(),
+            ),
+            None => std::borrow::Cow::Borrowed(headers),
+        };
+        let headers = headers.as_ref();
         let (length, _capacity) = self.calc_payload_length(queries, headers, resp);
         // Create Vec<u8> with exact capacity - simple, no overhead
         let mut buf = Vec::with_capacity(length);
@@ -55,7 +87,8 @@ impl Entry {
 
         buf.shrink_to_fit();
         
-        self.0.payload.store(Some(Arc::new(buf)));
+        let (origin_ttl, negative) = self.origin_freshness(resp);
+        self.0.payload.store(Some(Arc::new(PayloadBuf::with_etag(buf, &resp.body).with_freshness(origin_ttl, negative))));
     }
 
     /// Packs queries into the buffer.
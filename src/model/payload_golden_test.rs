@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use crate::config::new_test_config;
+    use crate::model::payload_decoder::PayloadError;
+    use crate::model::to_bytes::from_bytes;
+
+    /// Entry encoded by the v1 wire format (see `Entry::to_bytes`), checked in as-is.
+    /// If this test starts failing, the on-disk format changed and existing dumps are no longer readable.
+    const GOLDEN_V1: &[u8] = include_bytes!("testdata/entry_v1.golden");
+
+    /// Test that the golden blob decodes field by field into the expected entry.
+    #[test]
+    fn test_golden_v1_decodes_field_by_field() {
+        let cfg = new_test_config();
+        let entry = from_bytes(GOLDEN_V1, &cfg).expect("golden blob must decode");
+
+        assert_eq!(entry.rule().path.as_deref(), Some("/api/v1/user"));
+        assert_eq!(entry.key(), 0x0123_4567_89ab_cdef);
+        assert_eq!(entry.fresh_at(), 1_700_000_000_000_000_000);
+
+        let payload = entry.payload().expect("golden payload must decode");
+        assert_eq!(
+            payload.queries,
+            vec![
+                (b"user[id]".to_vec(), b"123".to_vec()),
+                (b"domain".to_vec(), b"example.com".to_vec()),
+            ]
+        );
+        assert_eq!(
+            payload.req_headers,
+            vec![(b"accept-encoding".to_vec(), b"gzip".to_vec())]
+        );
+        assert_eq!(payload.code, 200);
+        assert_eq!(
+            payload.rsp_headers,
+            vec![(b"Content-Type".to_vec(), b"application/json".to_vec())]
+        );
+        assert_eq!(payload.body, br#"{"ok":true}"#.to_vec());
+    }
+
+    /// Test that re-encoding the decoded golden entry reproduces the exact same bytes.
+    #[test]
+    fn test_golden_v1_reencodes_byte_exact() {
+        let cfg = new_test_config();
+        let entry = from_bytes(GOLDEN_V1, &cfg).unwrap();
+
+        assert_eq!(entry.to_bytes(), GOLDEN_V1);
+    }
+
+    /// Test that every truncated prefix of the golden blob is rejected or decodes without panicking.
+    #[test]
+    fn test_golden_v1_truncated_prefixes_do_not_panic() {
+        let cfg = new_test_config();
+        for n in 0..GOLDEN_V1.len() {
+            if let Ok(entry) = from_bytes(&GOLDEN_V1[..n], &cfg) {
+                let _ = entry.payload();
+            }
+        }
+    }
+
+    /// Test that a huge declared payload length is rejected before any allocation.
+    #[test]
+    fn test_huge_declared_payload_len_is_rejected() {
+        let cfg = new_test_config();
+        let payload_len_at = 4 + b"/api/v1/user".len() + 4 * 8;
+
+        let mut blob = GOLDEN_V1.to_vec();
+        blob[payload_len_at..payload_len_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(from_bytes(&blob, &cfg).is_err());
+    }
+
+    /// Test that a corrupted key length inside the queries section is reported, not panicked on.
+    #[test]
+    fn test_corrupted_section_length_is_reported() {
+        let cfg = new_test_config();
+        let payload_at = 4 + b"/api/v1/user".len() + 4 * 8 + 4;
+        // First query value length: offsets map (20) + key len (4) + "user[id]" (8).
+        let value_len_at = payload_at + 20 + 4 + 8;
+
+        let mut blob = GOLDEN_V1.to_vec();
+        blob[value_len_at..value_len_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let entry = from_bytes(&blob, &cfg).unwrap();
+        assert_eq!(
+            entry.request_payload().err(),
+            Some(PayloadError::CorruptedQueriesSection)
+        );
+    }
+}
@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
+    use std::time::Duration;
 
     use crate::config::{Rule, RuleKey, RuleValue};
     use crate::model::{Entry, Response};
@@ -12,14 +13,32 @@ mod tests {
             cache_key: RuleKey {
                 query: None,
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: None,
                 headers_map: None,
             },
             cache_value: RuleValue {
                 headers: None,
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         })
     }
 
@@ -261,4 +280,99 @@ mod tests {
         let decoded = entry.response_payload().unwrap();
         assert_eq!(decoded.body, b"test");
     }
+
+    /// Test that readers decoding in a tight loop never observe a payload mixing two refreshes,
+    /// whether the payload is replaced in place or swapped in from an incoming entry, nor one
+    /// paired with the freshness or the negative flag of another.
+    #[test]
+    fn test_refresh_is_atomic_for_concurrent_readers() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::thread;
+
+        const REFRESHES: usize = 5_000;
+        const READERS: usize = 4;
+
+        const NEGATIVE_TTL: Duration = Duration::from_secs(30);
+
+        // Every section carries the version, and the body length varies with it, so that a
+        // payload stitched from two versions is detected. Every third version is a negative
+        // answer, the others are fresh for as many seconds as their version.
+        type Pairs = Vec<(Vec<u8>, Vec<u8>)>;
+        fn versioned(version: usize) -> (Pairs, Pairs, Response) {
+            let v = version.to_string().into_bytes();
+            let body = v.repeat(1 + version % 64);
+            let status = if version.is_multiple_of(3) { 404 } else { 200 + (version % 2) as u16 };
+            let mut response = make_response(status, &body);
+            response.headers.push(("X-Version".to_string(), version.to_string()));
+            if status != 404 {
+                response.headers.push(("Cache-Control".to_string(), format!("max-age={}", version)));
+            }
+            (vec![(b"v".to_vec(), v.clone())], vec![(b"X-Version".to_vec(), v)], response)
+        }
+
+        let mut rule = (*make_rule()).clone();
+        rule.cache_value.respect_cache_control = Some(true);
+        rule.cache_value.negative_ttl = Some(NEGATIVE_TTL);
+        let rule = Arc::new(rule);
+        let (queries, headers, response) = versioned(0);
+        let entry = Entry::new(rule.clone(), &queries, &headers);
+        entry.set_payload(&queries, &headers, &response);
+
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..READERS)
+            .map(|_| {
+                let entry = entry.clone();
+                let done = done.clone();
+                thread::spawn(move || {
+                    let mut reads = 0usize;
+                    let mut last = 0usize;
+                    while !done.load(Ordering::Relaxed) || reads == 0 {
+                        // One load of the payload, decoded and asked for its freshness.
+                        let snapshot = entry.detached();
+                        snapshot.0.payload.store(entry.0.payload.load_full());
+                        let p = snapshot.payload().expect("payload must always decode");
+                        let version: usize = std::str::from_utf8(&p.queries[0].1).unwrap().parse().unwrap();
+                        assert!(version >= last, "payload went back from {} to {}", last, version);
+                        last = version;
+
+                        let (queries, headers, response) = versioned(version);
+                        assert_eq!(p.queries, queries);
+                        assert_eq!(p.req_headers, headers);
+                        assert_eq!(p.code, response.status);
+                        assert_eq!(p.body, response.body);
+                        let rsp_headers: Vec<_> = response
+                            .headers
+                            .iter()
+                            .map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec()))
+                            .collect();
+                        assert_eq!(p.rsp_headers, rsp_headers);
+                        let negative = response.status == 404;
+                        assert_eq!(snapshot.is_negative(), negative, "negative flag of another version");
+                        let ttl = if negative { NEGATIVE_TTL } else { Duration::from_secs(version as u64) };
+                        assert_eq!(snapshot.origin_ttl(), Some(ttl), "freshness of another version");
+                        reads += 1;
+                    }
+                    reads
+                })
+            })
+            .collect();
+
+        for version in 1..=REFRESHES {
+            let (queries, headers, response) = versioned(version);
+            if version % 2 == 0 {
+                entry.set_payload(&queries, &headers, &response);
+            } else {
+                let incoming = Entry::new(rule.clone(), &queries, &headers);
+                incoming.set_payload(&queries, &headers, &response);
+                entry.swap_payloads(&incoming);
+            }
+        }
+        done.store(true, Ordering::Relaxed);
+
+        for reader in readers {
+            assert!(reader.join().expect("reader must not panic") > 0);
+        }
+        let last = entry.payload().unwrap();
+        assert_eq!(last.queries[0].1, REFRESHES.to_string().into_bytes());
+    }
 }
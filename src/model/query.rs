@@ -64,7 +64,7 @@ impl Entry {
     pub fn get_filtered_and_sorted_key_queries(&self, query_str: &str) -> Vec<(Vec<u8>, Vec<u8>)> {
         let mut out = Vec::new();
 
-        if let Some(ref query_bytes) = self.0.rule.cache_key.query_bytes {
+        if let Some(ref query_bytes) = self.0.rule.load().cache_key.query_bytes {
             // Parse query string manually
             for pair in query_str.split('&') {
                 if let Some((key, value)) = pair.split_once('=') {
@@ -90,7 +90,7 @@ impl Entry {
     /// Filters queries in place (modifies the input slice).
     #[allow(dead_code)]
     pub fn filter_and_sort_key_queries_in_place(&self, queries: &mut Vec<(Vec<u8>, Vec<u8>)>) {
-        if let Some(ref allowed) = self.0.rule.cache_key.query_bytes {
+        if let Some(ref allowed) = self.0.rule.load().cache_key.query_bytes {
             let mut n = 0;
             for i in 0..queries.len() {
                 let key = &queries[i].0;
@@ -150,6 +150,9 @@ impl Entry {
             let k = &data[pos..pos + k_len];
             pos += k_len;
 
+            if pos + OFF_WEIGHT > data.len() {
+                return Err(QueryError::CorruptedQueriesSection);
+            }
             let v_len = LittleEndian::read_u32(&data[pos..pos + OFF_WEIGHT]) as usize;
             pos += OFF_WEIGHT;
             if pos + v_len > data.len() {
@@ -182,7 +185,7 @@ impl Entry {
     /// Filters queries by allowed keys.
     /// Helper function for parse_filter_and_sort_query.
     fn filter_queries(&self, queries: &[(Vec<u8>, Vec<u8>)]) -> Vec<(Vec<u8>, Vec<u8>)> {
-        if let Some(ref allowed) = self.0.rule.cache_key.query_bytes {
+        if let Some(ref allowed) = self.0.rule.load().cache_key.query_bytes {
             queries
                 .iter()
                 .filter(|(key, _)| allowed.iter().any(|ak| key.starts_with(ak)))
@@ -1,16 +1,107 @@
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
 
 use super::Entry;
-use crate::config::{Config, ConfigTrait};
+use crate::config::{Config, ConfigTrait, Rule};
 use crate::rand;
 use crate::time;
 
+/// Refresh parameters in effect for a rule: the global lifetime settings
+/// with the rule's own `refresh` section applied on top.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RefreshParams {
+    /// False when the rule explicitly disables background refresh.
+    pub enabled: bool,
+    pub ttl: Duration,
+    pub beta: f64,
+    pub coefficient: f64,
+}
+
+impl RefreshParams {
+    /// Resolves effective parameters. Rule TTL and beta override the global ones only when
+    /// positive, the coefficient is taken from the rule as is.
+    pub fn resolve(cfg: &Config, rule: &Rule) -> Self {
+        let lifetime = cfg.lifetime();
+        let mut params = Self {
+            enabled: true,
+            ttl: lifetime.and_then(|l| l.ttl).unwrap_or_default(),
+            beta: lifetime.and_then(|l| l.beta).unwrap_or(1.0),
+            coefficient: lifetime.and_then(|l| l.coefficient).unwrap_or(0.0),
+        };
+
+        if let Some(rule_lifetime) = &rule.refresh {
+            params.enabled = rule_lifetime.enabled;
+            if let Some(ttl) = rule_lifetime.ttl.filter(|d| !d.is_zero()) {
+                params.ttl = ttl;
+            }
+            if let Some(beta) = rule_lifetime.beta.filter(|b| *b > 0.0) {
+                params.beta = beta;
+            }
+            if let Some(coefficient) = rule_lifetime.coefficient {
+                params.coefficient = coefficient;
+            }
+        }
+
+        params
+    }
+}
+
+/// Where the refresh settings of an entry come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshSource {
+    /// The rule's own `refresh` section.
+    Rule,
+    /// The global `lifetime` section, the rule having no `refresh` of its own.
+    Global,
+    /// A rule the config no longer has for the entry's path, from before a reload.
+    Stale,
+}
+
+impl RefreshSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            RefreshSource::Rule => "rule",
+            RefreshSource::Global => "global",
+            RefreshSource::Stale => "stale",
+        }
+    }
+}
+
 impl Entry {
+    /// Refresh parameters in effect for the entry and where they come from. The entry's own
+    /// rule is what the lifetime manager goes by, even when it is stale.
+    pub fn refresh_settings(&self, cfg: &Config) -> (RefreshParams, RefreshSource) {
+        let rule = self.0.rule.load();
+        let current = rule.path.as_deref().and_then(|path| cfg.rule(path));
+        let source = match current {
+            Some(current) if Arc::ptr_eq(&current, &rule) => {
+                if rule.refresh.is_none() || rule.refresh_inherited {
+                    RefreshSource::Global
+                } else {
+                    RefreshSource::Rule
+                }
+            }
+            _ => RefreshSource::Stale,
+        };
+        (self.refresh_params(cfg), source)
+    }
+
+    /// Refresh parameters of the entry's rule, with the TTL the origin gave its payload when
+    /// there is one.
+    pub fn refresh_params(&self, cfg: &Config) -> RefreshParams {
+        let mut params = RefreshParams::resolve(cfg, &self.0.rule.load());
+        if let Some(ttl) = self.origin_ttl() {
+            params.ttl = ttl;
+        }
+        params
+    }
+
     /// Checks that elapsed time is greater than TTL (used in hotpath: GET).
     pub fn is_expired(&self, cfg: &Config) -> bool {
-        let ttl = cfg
-            .lifetime()
-            .and_then(|l| l.ttl)
+        let ttl = self
+            .origin_ttl()
+            .or_else(|| cfg.lifetime().and_then(|l| l.ttl))
             .map(|d| d.as_nanos() as i64)
             .unwrap_or(0);
 
@@ -20,36 +111,40 @@ impl Entry {
         elapsed > ttl
     }
 
+    /// Returns how long ago the entry was last refreshed.
+    pub fn age(&self) -> Duration {
+        Duration::from_nanos((time::unix_nano() - self.fresh_at()).max(0) as u64)
+    }
+
+    /// Returns how long the entry has been past its TTL (zero while it is still fresh).
+    /// The rule's refresh TTL takes precedence over the global lifetime TTL.
+    pub fn stale_for(&self, cfg: &Config) -> Duration {
+        let ttl = self.refresh_params(cfg).ttl.as_nanos() as i64;
+
+        let updated_at = self.0.updated_at.load(Ordering::Relaxed);
+        let stale = time::unix_nano() - updated_at - ttl;
+
+        Duration::from_nanos(stale.max(0) as u64)
+    }
+
+    /// Checks that the entry has outlived its TTL, the rule's refresh TTL taking precedence
+    /// over the global one. Entries without any TTL never expire.
+    pub fn is_past_ttl(&self, cfg: &Config) -> bool {
+        let ttl = self.refresh_params(cfg).ttl.as_nanos() as i64;
+        let updated_at = self.0.updated_at.load(Ordering::Relaxed);
+        ttl > 0 && time::unix_nano() - updated_at > ttl
+    }
+
     /// Implements probabilistic refresh logic (beta algorithm) for background refresh.
     /// Returns true if the entry is stale and, with a probability proportional to its staleness, should be refreshed now.
     pub fn is_probably_expired(&self, cfg: &Config) -> bool {
-        let lifetime = cfg.lifetime();
-        let mut ttl = lifetime
-            .and_then(|l| l.ttl)
-            .map(|d| d.as_nanos() as i64)
-            .unwrap_or(0);
-        let mut beta = lifetime.and_then(|l| l.beta).unwrap_or(1.0);
-        let mut coefficient = lifetime.and_then(|l| l.coefficient).unwrap_or(0.0);
-
-        // Per-entry overrides (if present).
-        if let Some(rule_lifetime) = &self.0.rule.refresh {
-            if !rule_lifetime.enabled {
-                return false;
-            }
-            if let Some(ref ttl_duration) = rule_lifetime.ttl {
-                if ttl_duration.as_nanos() > 0 {
-                    ttl = ttl_duration.as_nanos() as i64;
-                }
-            }
-            if let Some(beta_val) = rule_lifetime.beta {
-                if beta_val > 0.0 {
-                    beta = beta_val;
-                }
-            }
-            if let Some(coeff_val) = rule_lifetime.coefficient {
-                coefficient = coeff_val;
-            }
+        let params = self.refresh_params(cfg);
+        if !params.enabled {
+            return false;
         }
+        let ttl = params.ttl.as_nanos() as i64;
+        let beta = params.beta;
+        let coefficient = params.coefficient;
 
         let updated_at = self.0.updated_at.load(Ordering::Relaxed);
         let elapsed = time::unix_nano() - updated_at;
@@ -71,6 +166,31 @@ impl Entry {
         rand::float64() < probability
     }
 
+    /// Unix nanos at which the entry's refresh window opens: `coefficient * ttl` after its last
+    /// update, or the full TTL when no coefficient is set. None when the entry is never refreshed.
+    pub fn refresh_due_at(&self, cfg: &Config) -> Option<i64> {
+        let params = self.refresh_params(cfg);
+        let ttl = params.ttl.as_nanos() as i64;
+        if !params.enabled || ttl <= 0 {
+            return None;
+        }
+        let window = if params.coefficient > 0.0 {
+            ((ttl as f64) * params.coefficient).round() as i64
+        } else {
+            ttl
+        };
+        Some(self.0.updated_at.load(Ordering::Relaxed).saturating_add(window))
+    }
+
+    /// Bucket of the entry's record in the expiry index (see `db::storage::expiry`).
+    pub fn expiry_bucket(&self) -> u32 {
+        self.0.expiry_bucket.load(Ordering::Relaxed)
+    }
+
+    pub fn set_expiry_bucket(&self, bucket: u32) {
+        self.0.expiry_bucket.store(bucket, Ordering::Relaxed);
+    }
+
     /// Tries to mark the entry as refresh queued.
     pub fn try_mark_refresh_queued(&self) -> bool {
         self.0.refresh_queued
@@ -78,6 +198,11 @@ impl Entry {
             .is_ok()
     }
 
+    /// Whether the entry is queued for refresh.
+    pub fn is_refresh_queued(&self) -> bool {
+        self.0.refresh_queued.load(Ordering::Relaxed)
+    }
+
     /// Clears the refresh queued flag.
     pub fn clear_refresh_queued(&self) {
         self.0.refresh_queued.store(false, Ordering::Relaxed);
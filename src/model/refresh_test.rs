@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
     use crate::config;
+    use crate::config::ConfigTrait;
     use crate::time;
     use std::sync::Arc;
     use std::time::Duration;
@@ -22,12 +23,23 @@ mod tests {
             cache_key: config::RuleKey {
                 query: None,
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: None,
                 headers_map: None,
             },
             cache_value: config::RuleValue {
                 headers: None,
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: Some(config::LifetimeRule {
                 enabled: true,
@@ -35,6 +47,13 @@ mod tests {
                 beta: Some(0.5),
                 coefficient: Some(0.5),
             }),
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         });
 
         let e = crate::model::Entry::init().with_rule(rule.clone());
@@ -79,12 +98,23 @@ mod tests {
             cache_key: config::RuleKey {
                 query: None,
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: None,
                 headers_map: None,
             },
             cache_value: config::RuleValue {
                 headers: None,
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: Some(config::LifetimeRule {
                 enabled: true,
@@ -92,6 +122,13 @@ mod tests {
                 beta: Some(8.0),
                 coefficient: Some(0.0),
             }),
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         });
 
         let e = crate::model::Entry::init().with_rule(rule.clone());
@@ -113,4 +150,37 @@ mod tests {
             panic!("too few refresh events: {}/{}", hits, TRIALS);
         }
     }
+
+    /// TestRefreshParams_RuleOverrides checks that rule ttl/beta override the lifetime ones only when positive.
+    #[test]
+    fn test_refresh_params_rule_overrides() {
+        let cfg = config::new_test_config();
+
+        let user = cfg.rule("/api/v1/user").unwrap();
+        let params = crate::model::RefreshParams::resolve(&cfg, &user);
+        assert!(params.enabled);
+        assert_eq!(params.ttl, Duration::from_secs(60));
+        assert_eq!(params.beta, 0.4);
+        assert_eq!(params.coefficient, 0.5);
+
+        // No refresh section: the rule inherits the global lifetime settings (disabled in test config).
+        let client = cfg.rule("/api/v1/client").unwrap();
+        let params = crate::model::RefreshParams::resolve(&cfg, &client);
+        assert!(!params.enabled);
+        assert_eq!(params.ttl, Duration::from_secs(24 * 3600));
+
+        // Zero ttl and beta fall back to the lifetime ones, a disabled section disables refresh.
+        let mut rule = (*user).clone();
+        rule.refresh = Some(config::LifetimeRule {
+            enabled: false,
+            ttl: Some(Duration::ZERO),
+            beta: Some(0.0),
+            coefficient: Some(0.1),
+        });
+        let params = crate::model::RefreshParams::resolve(&cfg, &rule);
+        assert!(!params.enabled);
+        assert_eq!(params.ttl, Duration::from_secs(24 * 3600));
+        assert_eq!(params.beta, 0.4);
+        assert_eq!(params.coefficient, 0.1);
+    }
 }
@@ -0,0 +1,39 @@
+//! Tags of responses the origin answered with `Surrogate-Key`.
+//!
+//! The header holds tags separated by spaces (`Surrogate-Key: product-123 category-9`). Stored
+//! entries are indexed by the hashes of their tags, so everything carrying a tag can be
+//! invalidated at once (`/advcache/invalidate/tag`).
+
+use xxhash_rust::xxh3::xxh3_64;
+
+use super::Entry;
+
+/// Name of the header, lowercase as stored.
+pub const HEADER: &str = "surrogate-key";
+
+/// Hash of a tag, as the index keeps it.
+pub fn tag_hash(tag: &str) -> u64 {
+    xxh3_64(tag.as_bytes())
+}
+
+impl Entry {
+    /// Hashes of the tags of the stored response, sorted and deduplicated; allocates nothing
+    /// when it has none.
+    pub fn surrogate_keys(&self) -> Vec<u64> {
+        let Some(payload) = self.0.payload.load_full() else {
+            return Vec::new();
+        };
+        let Ok(headers) = self.unpack_response_headers_named(&payload, HEADER.as_bytes()) else {
+            return Vec::new();
+        };
+        let mut tags: Vec<u64> = headers
+            .iter()
+            .filter_map(|(_, value)| std::str::from_utf8(value).ok())
+            .flat_map(str::split_ascii_whitespace)
+            .map(tag_hash)
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
+}
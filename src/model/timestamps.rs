@@ -1,15 +1,54 @@
 //! Timestamp management for entries.
 //
 
+use std::cell::Cell;
 use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use super::Entry;
+use crate::controller::metrics;
+use crate::dedlog;
 use crate::time;
 
+/// How far ahead of the local clock an entry timestamp may be before it is clamped
+/// (`storage.max_clock_skew`).
+pub const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+
+/// How old the access time of an entry gets before a hit writes it again
+/// (`storage.access_time_granularity`).
+pub const DEFAULT_ACCESS_TIME_GRANULARITY: Duration = Duration::from_secs(10);
+
+/// Hits of a thread of which `storage.sample_hits` counts one.
+pub const HIT_SAMPLE: u32 = 4;
+
+/// Value the hit counter of an entry stops growing at.
+pub const HITS_CEILING: u32 = u32::MAX / 2;
+
+thread_local! {
+    /// Hits seen by the thread, picking the sampled ones.
+    static HIT_TICK: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Whether `ts` (unix nanos) lies more than `max_skew` ahead of `now`, as left by a node whose
+/// clock runs ahead. Such a timestamp would keep an entry fresh long past its TTL.
+pub fn is_past_clock_skew(ts: i64, now: i64, max_skew: Duration) -> bool {
+    ts.saturating_sub(now) > max_skew.as_nanos() as i64
+}
+
+/// Counts and logs a timestamp of `source` clamped to `now` for being too far ahead.
+pub fn report_clamped(source: &'static str, ts: i64, now: i64) {
+    metrics::inc_entry_timestamps_clamped();
+    let extra = format!("source={} ahead={:?}", source, Duration::from_nanos((ts - now) as u64));
+    dedlog::err("clock_skew", None, Some(&extra), "timestamp ahead of the local clock clamped to now");
+}
+
 impl Entry {
     /// Gets the fresh timestamp (when entry was last updated).
+    ///
+    /// Pairs with the release store in `touch_refreshed_at`: a payload read after this load is
+    /// at least as new as the refresh the timestamp records.
     pub fn fresh_at(&self) -> i64 {
-        self.0.updated_at.load(Ordering::Relaxed)
+        self.0.updated_at.load(Ordering::Acquire)
     }
 
     /// Updates the touched timestamp.
@@ -17,24 +56,82 @@ impl Entry {
         self.0.touched_at.store(time::unix_nano(), Ordering::Relaxed);
     }
 
+    /// Updates the touched timestamp once it is at least `granularity` nanos old, so a hit
+    /// within it reads the stamp and the cached clock without writing the shared cache line.
+    pub fn touch_coarse(&self, granularity: i64) {
+        let now = time::unix_nano();
+        if now - self.0.touched_at.load(Ordering::Relaxed) >= granularity {
+            self.0.touched_at.store(now, Ordering::Relaxed);
+        }
+    }
+
     /// Gets the touched timestamp.
     pub fn touched_at(&self) -> i64 {
         self.0.touched_at.load(Ordering::Relaxed)
     }
 
-    /// Updates the refreshed timestamp.
+    /// Counts a storage hit. With `sampled`, only one in `HIT_SAMPLE` of the thread's hits is
+    /// written, counting for `HIT_SAMPLE`.
+    pub fn count_hit(&self, sampled: bool) {
+        let step = if sampled {
+            let tick = HIT_TICK.with(|tick| {
+                let next = tick.get().wrapping_add(1);
+                tick.set(next);
+                next
+            });
+            if !tick.is_multiple_of(HIT_SAMPLE) {
+                return;
+            }
+            HIT_SAMPLE
+        } else {
+            1
+        };
+        // A plain add: the ceiling leaves room for the adds racing past the check.
+        if self.0.hits.load(Ordering::Relaxed) < HITS_CEILING {
+            self.0.hits.fetch_add(step, Ordering::Relaxed);
+        }
+    }
+
+    /// Recent storage hits.
+    pub fn hits(&self) -> u32 {
+        self.0.hits.load(Ordering::Relaxed)
+    }
+
+    /// Halves the hit counter, so that it tells recent popularity. A hit counted meanwhile
+    /// may be lost.
+    pub fn decay_hits(&self) {
+        let hits = self.0.hits.load(Ordering::Relaxed);
+        if hits > 0 {
+            self.0.hits.store(hits / 2, Ordering::Relaxed);
+        }
+    }
+
+    /// Clamps the refreshed timestamp to now when it lies more than `max_skew` ahead, counting
+    /// and logging it. Returns whether it did.
+    pub fn clamp_future_refreshed_at(&self, source: &'static str, max_skew: Duration) -> bool {
+        let now = time::unix_nano();
+        let ts = self.fresh_at();
+        if !is_past_clock_skew(ts, now, max_skew) {
+            return false;
+        }
+        self.0.updated_at.store(now, Ordering::Release);
+        report_clamped(source, ts, now);
+        true
+    }
+
+    /// Updates the refreshed timestamp. Called after the new payload is stored, so the
+    /// timestamp may lag the payload but never leads it.
     pub fn touch_refreshed_at(&self) {
-        self.0.updated_at.store(time::unix_nano(), Ordering::Relaxed);
+        self.0.updated_at.store(time::unix_nano(), Ordering::Release);
     }
 
     /// Untouches the refreshed timestamp (sets it to past).
     pub fn untouch_refreshed_at(&self) {
         // Calculate TTL in nanoseconds
-        // Use refresh lifetime rule for TTL
-        let ttl_nanos = self.0.rule
-            .refresh
-            .as_ref()
-            .and_then(|r| r.ttl)
+        // Use the origin TTL, else the refresh lifetime rule for TTL
+        let ttl_nanos = self
+            .origin_ttl()
+            .or_else(|| self.0.rule.load().refresh.as_ref().and_then(|r| r.ttl))
             .map(|d| d.as_nanos() as i64)
             .unwrap_or(0);
         self.0.updated_at
@@ -14,12 +14,23 @@ mod tests {
             cache_key: RuleKey {
                 query: None,
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: None,
                 headers_map: None,
             },
             cache_value: RuleValue {
                 headers: None,
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: Some(LifetimeRule {
                 enabled: true,
@@ -27,6 +38,13 @@ mod tests {
                 beta: None,
                 coefficient: None,
             }),
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         })
     }
 
@@ -37,14 +55,32 @@ mod tests {
             cache_key: RuleKey {
                 query: None,
                 query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
                 headers: None,
                 headers_map: None,
             },
             cache_value: RuleValue {
                 headers: None,
                 headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
             },
             refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
         })
     }
 
@@ -118,7 +154,7 @@ mod tests {
 
         entry.touch_refreshed_at();
         let fresh = entry.fresh_at();
-        let updated = entry.updated_at_ref().load(std::sync::atomic::Ordering::Relaxed);
+        let updated = entry.0.updated_at.load(std::sync::atomic::Ordering::Relaxed);
         
         assert_eq!(fresh, updated);
     }
@@ -260,4 +296,66 @@ mod tests {
         assert!(touched > 0);
         assert!(fresh > 0);
     }
+
+    #[tokio::test]
+    async fn test_clamp_future_refreshed_at() {
+        let _token = time::start(Duration::from_millis(1));
+        let skew = Duration::from_secs(300);
+
+        let entry = Entry::new(make_rule_with_ttl(10), &[], &[]);
+        let within = time::unix_nano() + Duration::from_secs(60).as_nanos() as i64;
+        entry.set_refreshed_at_for_tests(within);
+        assert!(!entry.clamp_future_refreshed_at("test", skew), "skew within the bound is kept");
+        assert_eq!(entry.fresh_at(), within);
+
+        let ahead = time::unix_nano() + Duration::from_secs(3 * 3600).as_nanos() as i64;
+        entry.set_refreshed_at_for_tests(ahead);
+        assert!(entry.clamp_future_refreshed_at("test", skew));
+        assert!(entry.fresh_at() <= time::unix_nano());
+        assert!(!crate::model::timestamps::is_past_clock_skew(entry.fresh_at(), time::unix_nano(), skew));
+    }
+
+    /// Test that the hit counter counts every hit, stops at its ceiling, and is halved by decay
+    /// down to zero.
+    #[test]
+    fn test_hit_counter_saturates_and_decays() {
+        use crate::model::timestamps::HITS_CEILING;
+        use std::sync::atomic::Ordering;
+
+        let entry = Entry::new(make_rule_without_ttl(), &[], &[]);
+        for _ in 0..10 {
+            entry.count_hit(false);
+        }
+        assert_eq!(entry.hits(), 10);
+
+        for expected in [5, 2, 1, 0, 0] {
+            entry.decay_hits();
+            assert_eq!(entry.hits(), expected);
+        }
+
+        entry.0.hits.store(HITS_CEILING, Ordering::Relaxed);
+        entry.count_hit(false);
+        assert_eq!(entry.hits(), HITS_CEILING, "a saturated counter does not grow");
+        entry.decay_hits();
+        assert_eq!(entry.hits(), HITS_CEILING / 2);
+    }
+
+    /// Test that sampled hits write one hit in four of the thread, each counting for four.
+    #[test]
+    fn test_sampled_hits_count_one_in_four() {
+        use crate::model::timestamps::HIT_SAMPLE;
+
+        let entry = Entry::new(make_rule_without_ttl(), &[], &[]);
+        let mut writes = 0;
+        for _ in 0..(8 * HIT_SAMPLE) {
+            let before = entry.hits();
+            entry.count_hit(true);
+            if entry.hits() != before {
+                assert_eq!(entry.hits() - before, HIT_SAMPLE);
+                writes += 1;
+            }
+        }
+        assert_eq!(writes, 8);
+        assert_eq!(entry.hits(), 8 * HIT_SAMPLE, "the estimate matches the hits over whole rounds");
+    }
 }
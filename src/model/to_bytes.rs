@@ -21,9 +21,12 @@ impl Entry {
     /// - uint64  updatedAtUnix
     /// - uint32  payloadLen
     /// - []byte  payload
+    /// - uint64  keySchema (optional, omitted when unknown; older readers ignore it)
     ///
+    #[cfg_attr(not(feature = "dump"), allow(dead_code))]
     pub fn to_bytes(&self) -> Vec<u8> {
-        let rule_path = self.0.rule.path_bytes.as_deref().unwrap_or(&[]);
+        let rule = self.0.rule.load();
+        let rule_path = rule.path_bytes.as_deref().unwrap_or(&[]);
         let payload = self.payload_bytes();
 
         // Pre-calculate size
@@ -34,6 +37,7 @@ impl Entry {
         total += 8; // fingerprintLo
         total += 8; // updatedAt
         total += 4 + payload.len(); // payloadLen + payload
+        total += 8; // keySchema
 
         let mut buf = Vec::with_capacity(total);
 
@@ -60,11 +64,17 @@ impl Entry {
             buf.extend_from_slice(&payload);
         }
 
+        // keySchema
+        if self.0.key_schema != 0 {
+            buf.extend_from_slice(&self.0.key_schema.to_le_bytes());
+        }
+
         buf
     }
 }
 
 /// Decodes Entry from the wire format described in ToBytes.
+#[cfg_attr(not(feature = "dump"), allow(dead_code))]
 pub fn from_bytes(
     data: &[u8],
     cfg: &Config,
@@ -128,10 +138,18 @@ pub fn from_bytes(
     let mut payload = vec![0u8; payload_len];
     cursor.read_exact(&mut payload)?;
 
+    // keySchema (absent in entries written before it was introduced)
+    let key_schema = if cursor.position() as usize + U64 <= data.len() {
+        cursor.read_u64::<LittleEndian>()?
+    } else {
+        0
+    };
+
     Ok(Entry::from_field(
         key,
         f_hi,
         f_lo,
+        key_schema,
         payload,
         rule.clone(), // Already Arc<Rule>, just clone the Arc
         updated_at,
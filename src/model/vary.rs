@@ -0,0 +1,174 @@
+//! Variants of responses the origin answered with `Vary`.
+//!
+//! The request headers a response varies on are stored with its payload, among the request
+//! headers fills and refreshes send, so the variant an entry holds is known from its payload
+//! alone, after a fill, a refresh or a dump load alike. A request whose values of them differ
+//! is answered by a sibling entry, keyed by the primary key and those values. `Vary: *` makes
+//! a response not stored at all (see `model::freshness`). `Accept-Encoding` is left out: the
+//! cache negotiates encodings itself.
+
+use std::sync::Arc;
+
+use xxhash_rust::xxh3::{xxh3_128, xxh3_64};
+
+use super::checksum::PayloadBuf;
+use super::Entry;
+
+/// What a response varies on, from its `Vary` headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Varies {
+    /// `Vary: *`: on something no request header tells.
+    Anything,
+    /// On these request headers, lowercase, sorted and deduplicated.
+    Headers(Vec<String>),
+}
+
+/// Parses the `Vary` headers of a response; `None` when it does not vary on request headers.
+pub fn parse(headers: &[(String, String)]) -> Option<Varies> {
+    let mut names = Vec::new();
+    for (name, value) in headers {
+        if !name.eq_ignore_ascii_case("vary") {
+            continue;
+        }
+        for token in value.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            if token == "*" {
+                return Some(Varies::Anything);
+            }
+            if !token.eq_ignore_ascii_case("accept-encoding") {
+                names.push(token.to_ascii_lowercase());
+            }
+        }
+    }
+    names.sort_unstable();
+    names.dedup();
+    (!names.is_empty()).then_some(Varies::Headers(names))
+}
+
+/// Whether a response says `Vary: *`.
+pub fn varies_on_anything(headers: &[(String, String)]) -> bool {
+    matches!(parse(headers), Some(Varies::Anything))
+}
+
+/// Value of the request header, its repeated lines joined with `, `.
+fn header_value(request_headers: &[(&str, &str)], name: &str) -> Option<String> {
+    let mut values = request_headers.iter().filter(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| *v);
+    let first = values.next()?;
+    Some(values.fold(first.to_string(), |joined, v| joined + ", " + v))
+}
+
+/// The headers of `names` the request carries, as they are sent to the origin and stored.
+pub fn request_headers<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    request_headers: &[(&str, &str)],
+) -> Vec<(Vec<u8>, Vec<u8>)> {
+    names
+        .into_iter()
+        .filter_map(|name| header_value(request_headers, name).map(|v| (name.as_bytes().to_vec(), v.into_bytes())))
+        .collect()
+}
+
+/// Request headers a stored response varies on, with the values of the request it answers,
+/// `None` for the headers that request did not carry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    headers: Vec<(String, Option<Vec<u8>>)>,
+}
+
+impl Variant {
+    /// The variant of a payload: the headers its response varies on, with their values among
+    /// its request headers, only decoded when it varies.
+    fn of(rsp_headers: &[(Vec<u8>, Vec<u8>)], req_headers: impl FnOnce() -> Vec<(Vec<u8>, Vec<u8>)>) -> Option<Self> {
+        let rsp_headers: Vec<(String, String)> = rsp_headers
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(b"vary"))
+            .map(|(k, v)| (String::from_utf8_lossy(k).into_owned(), String::from_utf8_lossy(v).into_owned()))
+            .collect();
+        let Some(Varies::Headers(names)) = parse(&rsp_headers) else {
+            return None;
+        };
+        let req_headers = req_headers();
+        let headers = names
+            .into_iter()
+            .map(|name| {
+                let value = req_headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name.as_bytes())).map(|(_, v)| v.clone());
+                (name, value)
+            })
+            .collect();
+        Some(Self { headers })
+    }
+
+    /// Names of the headers, lowercase and sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.headers.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Whether the request carries the values of this variant.
+    pub fn matches(&self, request_headers: &[(&str, &str)]) -> bool {
+        self.headers
+            .iter()
+            .all(|(name, value)| header_value(request_headers, name).map(String::into_bytes).as_ref() == value.as_ref())
+    }
+
+    /// The variant of the same headers the request asks for.
+    pub fn of_request(&self, request_headers: &[(&str, &str)]) -> Self {
+        let headers = self
+            .names()
+            .map(|name| (name.to_string(), header_value(request_headers, name).map(String::into_bytes)))
+            .collect();
+        Self { headers }
+    }
+
+    /// Lays out the headers and values for hashing, an absent value apart from an empty one.
+    fn key_bytes(&self, buf: &mut Vec<u8>) {
+        for (name, value) in &self.headers {
+            buf.extend_from_slice(name.as_bytes());
+            match value {
+                Some(value) => {
+                    buf.push(1);
+                    buf.extend_from_slice(value);
+                }
+                None => buf.push(0),
+            }
+            buf.push(0);
+        }
+    }
+}
+
+impl PayloadBuf {
+    /// The variant of the payload, decoded once.
+    fn variant(&self, entry: &Entry) -> Option<Arc<Variant>> {
+        self.variant
+            .get_or_init(|| {
+                let rsp_headers = entry.unpack_response_headers_named(self, b"vary").ok()?;
+                let req_headers = || entry.unpack_request_headers(self).unwrap_or_default();
+                Variant::of(&rsp_headers, req_headers).map(Arc::new)
+            })
+            .clone()
+    }
+}
+
+impl Entry {
+    /// The variant the stored response answers, `None` when it does not vary.
+    pub fn variant(&self) -> Option<Arc<Variant>> {
+        self.0.payload.load_full()?.variant(self)
+    }
+
+    /// An entry for the variant of this entry's key, without a payload: the key of a sibling
+    /// to look up.
+    pub fn variant_of_key(&self, variant: &Variant) -> Entry {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(&self.0.key.to_le_bytes());
+        buf.extend_from_slice(&self.0.fingerprint_hi.to_le_bytes());
+        buf.extend_from_slice(&self.0.fingerprint_lo.to_le_bytes());
+        variant.key_bytes(&mut buf);
+        let fingerprint = xxh3_128(&buf);
+        self.rekeyed(xxh3_64(&buf), (fingerprint >> 64) as u64, fingerprint as u64)
+    }
+
+    /// This entry, payload and lifetime included, under the key of its variant.
+    pub(crate) fn as_sibling(&self, variant: &Variant) -> Entry {
+        let sibling = self.variant_of_key(variant);
+        sibling.0.payload.store(self.0.payload.load_full());
+        sibling
+    }
+}
@@ -0,0 +1,61 @@
+#[cfg(test)]
+mod tests {
+    use crate::config;
+    use crate::model::vary::{self, Varies};
+    use crate::model::{Entry, Response};
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn stored(vary: &str, request_headers: &[(&str, &str)]) -> Entry {
+        let rule = config::new_test_config().cache.rules.unwrap()["/api/v1/user"].clone();
+        let queries = vec![(b"user[id]".to_vec(), b"1".to_vec())];
+        let entry = Entry::new(rule, &queries, &[]);
+        let request_headers: Vec<(Vec<u8>, Vec<u8>)> = request_headers
+            .iter()
+            .map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec()))
+            .collect();
+        let response = Response { status: 200, headers: headers(&[("Vary", vary)]), body: b"ok".to_vec() };
+        entry.set_payload(&queries, &request_headers, &response);
+        entry
+    }
+
+    /// Test that Vary headers are merged, lowercased, sorted and deduplicated, that
+    /// Accept-Encoding is left out and that `*` wins over named headers.
+    #[test]
+    fn test_parse() {
+        assert_eq!(vary::parse(&headers(&[("Content-Type", "text/plain")])), None);
+        assert_eq!(vary::parse(&headers(&[("Vary", "Accept-Encoding")])), None);
+        assert_eq!(
+            vary::parse(&headers(&[("Vary", "Accept-Language, Accept-Encoding"), ("vary", "X-Tenant, accept-language")])),
+            Some(Varies::Headers(vec!["accept-language".to_string(), "x-tenant".to_string()]))
+        );
+        assert_eq!(vary::parse(&headers(&[("Vary", "Accept-Language"), ("Vary", "*")])), Some(Varies::Anything));
+        assert!(vary::varies_on_anything(&headers(&[("Vary", " * ")])));
+    }
+
+    /// Test that the variant of a stored response matches requests with the same values of the
+    /// headers it varies on, telling an absent header apart, and that other variants get
+    /// other keys.
+    #[test]
+    fn test_variant_matches_and_keys() {
+        let entry = stored("Accept-Language", &[("Accept-Language", "en")]);
+        let variant = entry.variant().expect("the response varies");
+        assert_eq!(variant.names().collect::<Vec<_>>(), vec!["accept-language"]);
+        assert!(variant.matches(&[("accept-language", "en"), ("x-other", "1")]));
+        assert!(!variant.matches(&[("Accept-Language", "de")]));
+        assert!(!variant.matches(&[]));
+
+        let de = variant.of_request(&[("Accept-Language", "de")]);
+        let absent = variant.of_request(&[]);
+        let empty = variant.of_request(&[("Accept-Language", "")]);
+        let key = |v: &vary::Variant| entry.variant_of_key(v).key();
+        assert_eq!(key(&variant), key(&variant.of_request(&[("Accept-Language", "en")])));
+        assert_ne!(key(&variant), key(&de));
+        assert_ne!(key(&absent), key(&empty));
+        assert_ne!(key(&variant), entry.key());
+
+        assert!(stored("Accept-Encoding", &[]).variant().is_none());
+    }
+}
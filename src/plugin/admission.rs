@@ -0,0 +1,17 @@
+//! Admission hooks: whether a response fetched on a miss is stored.
+
+use super::KeyInput;
+
+/// Response fetched from the origin on a miss.
+#[allow(dead_code)] // read by plugins outside the binary
+pub struct ResponseView<'a> {
+    pub status: u16,
+    pub headers: &'a [(String, String)],
+    pub body: &'a [u8],
+}
+
+/// Decides whether a response is stored. A refused response is still answered to the client;
+/// the next request for the key is a miss again. See the module contract in [`crate::plugin`].
+pub trait AdmissionHook: Send + Sync {
+    fn admit(&self, request: &KeyInput<'_>, response: &ResponseView<'_>) -> bool;
+}
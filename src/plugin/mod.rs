@@ -0,0 +1,107 @@
+//! Extension points for keying and admission logic that YAML cannot express.
+//!
+//! A rule names a [`KeyTransformer`] with `cache_key.transformer` and an [`AdmissionHook`] with
+//! `cache_value.admission_hook`. Deployments embedding the crate register their own under a
+//! name with [`register_key_transformer`] / [`register_admission_hook`] before the config is
+//! loaded; loading fails on a name nothing is registered under. The binary ships these
+//! transformers:
+//!
+//! - `jwt_sub`: the `sub` claim of the bearer JWT in `Authorization`;
+//! - `jwt_claim:<claim>`: any top-level claim of that JWT;
+//! - `header_regex:<header>:<pattern>`: the first capture group (or the whole match) of
+//!   `pattern` in the header value.
+//!
+//! # Contract
+//!
+//! Both run synchronously on the request path of every request of the rule, so they must not
+//! block, do I/O or take locks held elsewhere; a few microseconds and an allocation or two is
+//! the budget. They must not panic: a transformer that cannot derive its bytes returns `None`
+//! and the request is keyed as if the rule had no transformer (a panic is answered 500 by the
+//! recover middleware). Their output must be a pure function of the input, otherwise equal
+//! requests get different keys.
+//!
+//! Transformers only key: nothing is verified. `jwt_*` read the claims without checking the
+//! signature, so a client forging a token gets the entry of the claim it forged. Use them only
+//! behind a gateway that rejects invalid tokens. Headers a transformer reads reach the origin
+//! only when they are also whitelisted in `cache_key.headers`, and refreshes replay the
+//! stored request, which holds whitelisted headers only.
+
+pub mod admission;
+pub mod transformer;
+
+#[cfg(test)]
+mod plugin_test;
+
+pub use admission::{AdmissionHook, ResponseView};
+pub use transformer::{HeaderRegexTransformer, JwtClaimTransformer, KeyInput, KeyTransformer};
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{bail, Result};
+use parking_lot::RwLock;
+
+type Registry<T> = OnceLock<RwLock<HashMap<String, Arc<T>>>>;
+
+static TRANSFORMERS: Registry<dyn KeyTransformer> = OnceLock::new();
+static ADMISSION_HOOKS: Registry<dyn AdmissionHook> = OnceLock::new();
+
+/// Registers a key transformer under `name`, replacing the one registered before.
+#[allow(dead_code)]
+pub fn register_key_transformer(name: &str, transformer: Arc<dyn KeyTransformer>) {
+    TRANSFORMERS
+        .get_or_init(Default::default)
+        .write()
+        .insert(name.to_string(), transformer);
+}
+
+/// Registers an admission hook under `name`, replacing the one registered before.
+#[allow(dead_code)]
+pub fn register_admission_hook(name: &str, hook: Arc<dyn AdmissionHook>) {
+    ADMISSION_HOOKS
+        .get_or_init(Default::default)
+        .write()
+        .insert(name.to_string(), hook);
+}
+
+/// Returns the transformer registered under `name`, building a built-in on first use.
+pub fn key_transformer(name: &str) -> Result<Arc<dyn KeyTransformer>> {
+    let registry = TRANSFORMERS.get_or_init(Default::default);
+    if let Some(transformer) = registry.read().get(name) {
+        return Ok(transformer.clone());
+    }
+
+    let transformer = builtin_transformer(name)?;
+    Ok(registry
+        .write()
+        .entry(name.to_string())
+        .or_insert(transformer)
+        .clone())
+}
+
+/// Returns the admission hook registered under `name`.
+pub fn admission_hook(name: &str) -> Result<Arc<dyn AdmissionHook>> {
+    match ADMISSION_HOOKS.get().and_then(|hooks| hooks.read().get(name).cloned()) {
+        Some(hook) => Ok(hook),
+        None => bail!("no admission hook is registered as {:?}", name),
+    }
+}
+
+fn builtin_transformer(name: &str) -> Result<Arc<dyn KeyTransformer>> {
+    if name == "jwt_sub" {
+        return Ok(Arc::new(JwtClaimTransformer::new("sub")));
+    }
+    if let Some(claim) = name.strip_prefix("jwt_claim:") {
+        if claim.is_empty() {
+            bail!("jwt_claim transformer needs a claim name: jwt_claim:<claim>");
+        }
+        return Ok(Arc::new(JwtClaimTransformer::new(claim)));
+    }
+    if let Some(spec) = name.strip_prefix("header_regex:") {
+        let Some((header, pattern)) = spec.split_once(':').filter(|(h, _)| !h.is_empty()) else {
+            bail!("header_regex transformer needs a header and a pattern: header_regex:<header>:<pattern>");
+        };
+        return Ok(Arc::new(HeaderRegexTransformer::new(header, pattern)?));
+    }
+    bail!("no key transformer is registered as {:?}", name)
+}
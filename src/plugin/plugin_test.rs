@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+
+    use crate::config::Rule;
+    use crate::plugin::{self, KeyInput, KeyTransformer};
+
+    fn jwt(claims: &str) -> String {
+        format!(
+            "Bearer {}.{}.signature",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#),
+            URL_SAFE_NO_PAD.encode(claims)
+        )
+    }
+
+    fn key_bytes(name: &str, headers: &[(&str, &str)]) -> Option<Vec<u8>> {
+        let input = KeyInput {
+            path: b"/api/v1/me",
+            queries: &[],
+            headers: &[],
+            request_headers: headers,
+        };
+        plugin::key_transformer(name).unwrap().key_bytes(&input)
+    }
+
+    /// Test that the JWT built-ins read the claim of a bearer token and skip anything else.
+    #[test]
+    fn test_jwt_claim_transformers() {
+        let token = jwt(r#"{"sub":"user-42","tenant":7,"nothing":null}"#);
+        assert_eq!(key_bytes("jwt_sub", &[("Authorization", &token)]), Some(b"user-42".to_vec()));
+        assert_eq!(key_bytes("jwt_claim:tenant", &[("authorization", &token)]), Some(b"7".to_vec()));
+        assert_eq!(key_bytes("jwt_claim:nothing", &[("authorization", &token)]), None);
+        assert_eq!(key_bytes("jwt_claim:missing", &[("authorization", &token)]), None);
+
+        assert_eq!(key_bytes("jwt_sub", &[]), None);
+        assert_eq!(key_bytes("jwt_sub", &[("authorization", "Basic dXNlcjpwYXNz")]), None);
+        assert_eq!(key_bytes("jwt_sub", &[("authorization", "Bearer not-a-jwt")]), None);
+        assert_eq!(key_bytes("jwt_sub", &[("authorization", "Bearer a.%%%.c")]), None);
+    }
+
+    /// Test that the header pattern built-in keys by its first group, or the whole match.
+    #[test]
+    fn test_header_regex_transformer() {
+        let headers = [("X-Client", "ios/17.2 build 311")];
+        assert_eq!(key_bytes(r"header_regex:x-client:^(\w+)/", &headers), Some(b"ios".to_vec()));
+        assert_eq!(key_bytes(r"header_regex:X-Client:\d+\.\d+", &headers), Some(b"17.2".to_vec()));
+        assert_eq!(key_bytes(r"header_regex:X-Client:^android", &headers), None);
+        assert_eq!(key_bytes(r"header_regex:X-Other:.*", &headers), None);
+    }
+
+    /// Test that unknown names and malformed built-in specs are refused.
+    #[test]
+    fn test_unknown_or_invalid_transformer() {
+        for name in ["jwt_nope", "jwt_claim:", "header_regex:x-client", "header_regex::.*", "header_regex:x:("] {
+            assert!(plugin::key_transformer(name).is_err(), "{} must be refused", name);
+        }
+        assert!(plugin::admission_hook("plugin-test-unregistered").is_err());
+    }
+
+    struct Constant(&'static [u8]);
+
+    impl KeyTransformer for Constant {
+        fn key_bytes(&self, _input: &KeyInput<'_>) -> Option<Vec<u8>> {
+            Some(self.0.to_vec())
+        }
+    }
+
+    /// Test that a registered transformer is found by name and replaces the previous one.
+    #[test]
+    fn test_register_transformer() {
+        plugin::register_key_transformer("plugin-test-constant", Arc::new(Constant(b"a")));
+        assert_eq!(key_bytes("plugin-test-constant", &[]), Some(b"a".to_vec()));
+        plugin::register_key_transformer("plugin-test-constant", Arc::new(Constant(b"b")));
+        assert_eq!(key_bytes("plugin-test-constant", &[]), Some(b"b".to_vec()));
+    }
+
+    /// Test that setting or changing the transformer changes the key schema.
+    #[test]
+    fn test_transformer_is_part_of_key_schema() {
+        let mut rule = Rule::bare("/api/v1/me");
+        let bare = rule.key_schema();
+        rule.cache_key.transformer = Some("jwt_sub".to_string());
+        let sub = rule.key_schema();
+        rule.cache_key.transformer = Some("jwt_claim:tenant".to_string());
+        let tenant = rule.key_schema();
+        assert!(bare != sub && sub != tenant && bare != tenant);
+    }
+}
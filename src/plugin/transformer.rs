@@ -0,0 +1,89 @@
+//! Key transformers: extra key bytes derived from the request.
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use regex::Regex;
+
+/// Request as seen by the plugins: the whitelisted queries and headers the key is built from,
+/// sorted, and every header the client sent.
+#[allow(dead_code)] // read by plugins outside the binary
+pub struct KeyInput<'a> {
+    pub path: &'a [u8],
+    pub queries: &'a [(Vec<u8>, Vec<u8>)],
+    pub headers: &'a [(Vec<u8>, Vec<u8>)],
+    pub request_headers: &'a [(&'a str, &'a str)],
+}
+
+impl KeyInput<'_> {
+    /// Value of the request header, matched case-insensitively.
+    pub fn request_header(&self, name: &str) -> Option<&str> {
+        self.request_headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| *v)
+    }
+}
+
+/// Derives bytes added to the cache key of a request, after the whitelisted queries and
+/// headers. See the module contract in [`crate::plugin`].
+pub trait KeyTransformer: Send + Sync {
+    /// Bytes to key the request by, or `None` to key it without them.
+    fn key_bytes(&self, input: &KeyInput<'_>) -> Option<Vec<u8>>;
+}
+
+/// Keys by a top-level claim of the bearer JWT in `Authorization`. The signature is not
+/// checked.
+pub struct JwtClaimTransformer {
+    claim: String,
+}
+
+impl JwtClaimTransformer {
+    pub fn new(claim: &str) -> Self {
+        Self { claim: claim.to_string() }
+    }
+}
+
+impl KeyTransformer for JwtClaimTransformer {
+    fn key_bytes(&self, input: &KeyInput<'_>) -> Option<Vec<u8>> {
+        let auth = input.request_header("authorization")?;
+        let (scheme, token) = auth.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            return None;
+        }
+
+        let claims = token.trim().split('.').nth(1)?;
+        let claims = URL_SAFE_NO_PAD.decode(claims.trim_end_matches('=')).ok()?;
+        let claims: serde_json::Value = serde_json::from_slice(&claims).ok()?;
+        match claims.get(&self.claim)? {
+            serde_json::Value::String(s) => Some(s.as_bytes().to_vec()),
+            serde_json::Value::Null => None,
+            other => Some(other.to_string().into_bytes()),
+        }
+    }
+}
+
+/// Keys by the first capture group of a pattern in a request header, or by the whole match
+/// when the pattern has no group.
+pub struct HeaderRegexTransformer {
+    header: String,
+    pattern: Regex,
+}
+
+impl HeaderRegexTransformer {
+    pub fn new(header: &str, pattern: &str) -> Result<Self> {
+        Ok(Self {
+            header: header.to_string(),
+            pattern: Regex::new(pattern).with_context(|| format!("invalid header_regex pattern {:?}", pattern))?,
+        })
+    }
+}
+
+impl KeyTransformer for HeaderRegexTransformer {
+    fn key_bytes(&self, input: &KeyInput<'_>) -> Option<Vec<u8>> {
+        let value = input.request_header(&self.header)?;
+        let captures = self.pattern.captures(value)?;
+        let matched = captures.get(1).or_else(|| captures.get(0))?;
+        Some(matched.as_str().as_bytes().to_vec())
+    }
+}
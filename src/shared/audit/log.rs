@@ -0,0 +1,304 @@
+//! Append-only audit log, as JSON lines rotated by size.
+//
+// Records are queued to a writer thread and appended there, so an admin call never waits on
+// the disk: a record that does not fit in the queue, or that fails to be written, is logged
+// and counted in `audit_write_failures` and the call goes on. Every line carries `seq`, which
+// goes on across rotations and restarts, and `chain`, a hash of the line seeded with the chain
+// of the line before it. A line edited, dropped or moved breaks the chain from there on. The
+// hash is not keyed, so it shows accidental and careless edits, not a tail rewritten as a
+// whole; ship the file off the host when that matters.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::xxh3_64_with_seed;
+
+use crate::config::Config;
+use crate::metrics::meter;
+
+/// Size past which the file is rotated when `audit.max_size` is not set (64 MiB).
+pub const DEFAULT_MAX_SIZE: u64 = 64 << 20;
+
+/// Rotated files kept when `audit.max_files` is not set.
+pub const DEFAULT_MAX_FILES: usize = 5;
+
+/// Records waiting for the writer before new ones are dropped.
+const QUEUE_SIZE: usize = 4096;
+
+/// Bytes read at a time while looking for the last lines of a file.
+const TAIL_CHUNK: u64 = 64 << 10;
+
+/// One admin call.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Position in the log from 1, set by the writer.
+    #[serde(default)]
+    pub seq: u64,
+    /// When the call was answered, RFC 3339 in UTC.
+    pub timestamp: String,
+    pub method: String,
+    /// Route called, e.g. `/advcache/upstream/:backend_id/drain`.
+    pub endpoint: String,
+    /// Path requested, path params filled in.
+    pub path: String,
+    /// Query params, with secrets and `logs.redact.query` values masked.
+    pub params: BTreeMap<String, String>,
+    /// Who called: `credentials:<hash of the Authorization header>`, `addr:<ip>` or `anonymous`.
+    pub principal: String,
+    pub status: u16,
+    /// `ok` for a 2xx answer, `refused` for a 4xx one, `failed` otherwise.
+    pub result: String,
+    /// Entries the call dropped or marked, for clears and invalidations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub affected: Option<u64>,
+    /// Hex hash of the line without this field, seeded with the chain of the previous line
+    /// (0 for the first line of the log). Set by the writer, always last.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub chain: String,
+}
+
+impl AuditRecord {
+    /// `result` of a call answered with `status`.
+    pub fn result_of(status: u16) -> &'static str {
+        match status {
+            200..=299 => "ok",
+            400..=499 => "refused",
+            _ => "failed",
+        }
+    }
+
+    /// Chain of the record following a record of chain `prev`.
+    fn chain_after(&self, prev: u64) -> u64 {
+        let unchained = AuditRecord { chain: String::new(), ..self.clone() };
+        xxh3_64_with_seed(serde_json::to_string(&unchained).unwrap_or_default().as_bytes(), prev)
+    }
+
+    fn chain_value(&self) -> u64 {
+        u64::from_str_radix(&self.chain, 16).unwrap_or_default()
+    }
+}
+
+/// Checks that every record follows the one before it: consecutive `seq` and a matching
+/// `chain`. The first record is taken as is.
+pub fn is_chained(records: &[AuditRecord]) -> bool {
+    records.windows(2).all(|pair| {
+        pair[1].seq == pair[0].seq + 1 && pair[1].chain == format!("{:016x}", pair[1].chain_after(pair[0].chain_value()))
+    })
+}
+
+enum Message {
+    Record(Box<AuditRecord>),
+    /// Answered once the records queued before it are written.
+    Flush(tokio::sync::oneshot::Sender<()>),
+}
+
+/// AuditLog appends records to `audit.path` from a writer thread.
+pub struct AuditLog {
+    path: PathBuf,
+    max_files: usize,
+    tx: SyncSender<Message>,
+}
+
+impl AuditLog {
+    /// Opens the log of `audit.path`; `None` when it is not set.
+    pub fn from_config(cfg: &Config) -> io::Result<Option<Arc<Self>>> {
+        let Some(audit) = cfg.cache.audit.as_ref() else {
+            return Ok(None);
+        };
+        let Some(path) = audit.path.as_deref() else {
+            return Ok(None);
+        };
+        let log = Self::open(
+            path,
+            audit.max_size.unwrap_or(DEFAULT_MAX_SIZE),
+            audit.max_files.unwrap_or(DEFAULT_MAX_FILES),
+        )?;
+        Ok(Some(Arc::new(log)))
+    }
+
+    /// Opens the file for appending, going on from its last record, and starts the writer.
+    pub fn open(path: impl AsRef<Path>, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+        let last = rotated_paths(&path, max_files)
+            .find_map(|p| last_lines(&p, 1).ok().and_then(|lines| lines.last().and_then(|l| serde_json::from_str::<AuditRecord>(l).ok())));
+
+        let mut writer = Writer {
+            path: path.clone(),
+            max_size,
+            max_files,
+            file: Some(file),
+            size,
+            seq: last.as_ref().map_or(0, |r| r.seq),
+            chain: last.as_ref().map_or(0, |r| r.chain_value()),
+        };
+        let (tx, rx) = mpsc::sync_channel(QUEUE_SIZE);
+        std::thread::Builder::new().name("audit-writer".to_string()).spawn(move || {
+            for message in rx {
+                match message {
+                    Message::Record(record) => writer.write(*record),
+                    Message::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        })?;
+
+        Ok(Self { path, max_files, tx })
+    }
+
+    /// Queues the record without waiting; it is dropped, logged and counted when the queue is full.
+    pub fn record(&self, record: AuditRecord) {
+        if let Err(e) = self.tx.try_send(Message::Record(Box::new(record))) {
+            let reason = match e {
+                TrySendError::Full(_) => "audit queue full, record dropped",
+                TrySendError::Disconnected(_) => "audit writer stopped, record dropped",
+            };
+            meter::add_audit_write_failure();
+            crate::dedlog::err("audit", None, None, reason);
+        }
+    }
+
+    /// Resolves once the records queued so far are written.
+    pub async fn flush(&self) {
+        let (done, written) = tokio::sync::oneshot::channel();
+        let tx = self.tx.clone();
+        let queued = tokio::task::spawn_blocking(move || tx.send(Message::Flush(done)).is_ok()).await;
+        if queued.unwrap_or(false) {
+            let _ = written.await;
+        }
+    }
+
+    /// Up to the `limit` most recent records written, oldest first, rotated files included.
+    pub fn tail(&self, limit: usize) -> io::Result<Vec<AuditRecord>> {
+        let mut records: Vec<AuditRecord> = Vec::new();
+        for path in rotated_paths(&self.path, self.max_files) {
+            if records.len() >= limit {
+                break;
+            }
+            let mut older: Vec<AuditRecord> = last_lines(&path, limit - records.len())?
+                .iter()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect();
+            older.append(&mut records);
+            records = older;
+        }
+        Ok(records)
+    }
+}
+
+struct Writer {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    /// None after a failure, opened again for the next record.
+    file: Option<File>,
+    size: u64,
+    seq: u64,
+    chain: u64,
+}
+
+impl Writer {
+    fn write(&mut self, mut record: AuditRecord) {
+        record.seq = self.seq + 1;
+        record.chain.clear();
+        let chain = record.chain_after(self.chain);
+        let unchained = serde_json::to_string(&record).unwrap_or_default();
+        // `chain` is the last field: appended to the object it hashes.
+        let line = format!("{},\"chain\":\"{:016x}\"}}\n", &unchained[..unchained.len() - 1], chain);
+
+        match self.append(line.as_bytes()) {
+            Ok(()) => {
+                self.seq = record.seq;
+                self.chain = chain;
+                meter::add_audit_record();
+            }
+            Err(e) => {
+                self.file = None;
+                meter::add_audit_write_failure();
+                crate::dedlog::err("audit", Some(&e), Some(&self.path.display().to_string()), "audit record not written");
+            }
+        }
+    }
+
+    fn append(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.file = None;
+            self.rotate()?;
+        }
+        if self.file.is_none() {
+            let file = open_append(&self.path)?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(line)?;
+            self.size += line.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// Moves `<path>.N` to `<path>.N+1`, dropping the oldest, and the file to `<path>.1`.
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..self.max_files).rev() {
+            match fs::rename(numbered(&self.path, n), numbered(&self.path, n + 1)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        fs::rename(&self.path, numbered(&self.path, 1))?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let mut numbered = path.as_os_str().to_owned();
+    numbered.push(format!(".{}", n));
+    PathBuf::from(numbered)
+}
+
+/// The file, then its rotated files from the newest.
+fn rotated_paths(path: &Path, max_files: usize) -> impl Iterator<Item = PathBuf> + '_ {
+    std::iter::once(path.to_path_buf()).chain((1..=max_files).map(move |n| numbered(path, n)))
+}
+
+/// Up to `limit` last non-empty lines of the file, oldest first; none when it is missing.
+fn last_lines(path: &Path, limit: usize) -> io::Result<Vec<String>> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut pos = file.metadata()?.len();
+    let mut tail: Vec<u8> = Vec::new();
+    // The last line ends with a newline, so `limit` lines are read once there are more newlines.
+    while pos > 0 && tail.iter().filter(|&&b| b == b'\n').count() <= limit {
+        let read = TAIL_CHUNK.min(pos);
+        pos -= read;
+        let mut chunk = vec![0; read as usize];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&tail);
+        tail = chunk;
+    }
+
+    let text = String::from_utf8_lossy(&tail);
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    if pos > 0 {
+        // Started mid-line.
+        lines.remove(0);
+    }
+    let lines: Vec<String> = lines.into_iter().filter(|l| !l.trim().is_empty()).map(str::to_string).collect();
+    Ok(lines[lines.len().saturating_sub(limit)..].to_vec())
+}
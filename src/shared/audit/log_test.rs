@@ -0,0 +1,125 @@
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::audit::log::is_chained;
+    use crate::audit::{AuditLog, AuditRecord};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("advcache-audit-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn record(i: usize) -> AuditRecord {
+        AuditRecord {
+            timestamp: "2026-01-01T00:00:00.000Z".to_string(),
+            method: "GET".to_string(),
+            endpoint: "/advcache/eviction/scale".to_string(),
+            path: "/advcache/eviction/scale".to_string(),
+            params: [("to".to_string(), i.to_string())].into_iter().collect(),
+            principal: "anonymous".to_string(),
+            status: 200,
+            result: "ok".to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn write(log: &AuditLog, range: std::ops::Range<usize>) {
+        for i in range {
+            log.record(record(i));
+        }
+        log.flush().await;
+    }
+
+    fn read_lines(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path).unwrap_or_default().lines().map(str::to_string).collect()
+    }
+
+    /// Test that records are appended as JSON lines numbered from 1 and chained.
+    #[tokio::test]
+    async fn test_appends_chained_lines() {
+        let dir = temp_dir("append");
+        let path = dir.join("audit.log");
+        let log = AuditLog::open(&path, 1 << 20, 3).unwrap();
+        write(&log, 0..5).await;
+
+        let lines = read_lines(&path);
+        assert_eq!(lines.len(), 5);
+        let records: Vec<AuditRecord> = lines.iter().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(records.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(records[2].params["to"], "2");
+        assert!(lines[0].ends_with(&format!(",\"chain\":\"{}\"}}", records[0].chain)));
+        assert!(is_chained(&records));
+        assert_eq!(log.tail(100).unwrap(), records);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Test that the file is rotated past its max size, the oldest rotated file dropped, and
+    /// that the tail reads across rotated files.
+    #[tokio::test]
+    async fn test_rotates_by_size() {
+        let dir = temp_dir("rotation");
+        let path = dir.join("audit.log");
+        let line_len = serde_json::to_string(&record(10)).unwrap().len() as u64 + 60;
+        let log = AuditLog::open(&path, line_len * 4, 2).unwrap();
+        write(&log, 10..40).await;
+
+        for file in [path.clone(), dir.join("audit.log.1"), dir.join("audit.log.2")] {
+            let size = std::fs::metadata(&file).unwrap().len();
+            assert!(size > 0 && size <= line_len * 4, "{} has {} bytes", file.display(), size);
+        }
+        assert!(!dir.join("audit.log.3").exists(), "only max_files rotated files are kept");
+
+        let tail = log.tail(100).unwrap();
+        assert!(tail.len() > 4 && tail.len() < 30, "{} records kept", tail.len());
+        assert_eq!(tail.last().unwrap().seq, 30);
+        assert!(is_chained(&tail), "the chain goes on across rotations");
+
+        let last = log.tail(6).unwrap();
+        assert_eq!(last.iter().map(|r| r.seq).collect::<Vec<_>>(), (25..=30).collect::<Vec<_>>());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Test that a log opened again goes on with the sequence and chain of its last record.
+    #[tokio::test]
+    async fn test_reopen_goes_on() {
+        let dir = temp_dir("reopen");
+        let path = dir.join("audit.log");
+        write(&AuditLog::open(&path, 1 << 20, 3).unwrap(), 0..3).await;
+        let log = AuditLog::open(&path, 1 << 20, 3).unwrap();
+        write(&log, 3..5).await;
+
+        let tail = log.tail(10).unwrap();
+        assert_eq!(tail.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert!(is_chained(&tail));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Test that an edited, dropped or reordered line breaks the chain.
+    #[tokio::test]
+    async fn test_tampering_breaks_the_chain() {
+        let dir = temp_dir("tamper");
+        let path = dir.join("audit.log");
+        let log = AuditLog::open(&path, 1 << 20, 3).unwrap();
+        write(&log, 0..5).await;
+        let lines = read_lines(&path);
+        let parse = |lines: &[String]| lines.iter().map(|l| serde_json::from_str(l).unwrap()).collect::<Vec<AuditRecord>>();
+
+        let mut edited = lines.clone();
+        edited[2] = edited[2].replace("\"status\":200", "\"status\":403");
+        assert!(!is_chained(&parse(&edited)));
+
+        let mut dropped = lines.clone();
+        dropped.remove(2);
+        assert!(!is_chained(&parse(&dropped)));
+
+        let mut reordered = lines.clone();
+        reordered.swap(1, 2);
+        assert!(!is_chained(&parse(&reordered)));
+
+        assert!(is_chained(&parse(&lines)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
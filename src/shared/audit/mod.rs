@@ -0,0 +1,8 @@
+//! Audit log of the admin calls changing the state of the instance (`audit.path`).
+
+pub mod log;
+
+#[cfg(test)]
+mod log_test;
+
+pub use log::{AuditLog, AuditRecord};
@@ -36,43 +36,47 @@ pub fn fmt_mem(bytes: i64) -> String {
     }
 }
 
-/// Compares two byte slices for equality with optimized performance.
-/// 
-/// For small slices (< 32 bytes), uses direct byte-by-byte comparison.
-/// For larger slices, uses fast hash-based comparison with xxh3:
-/// hashes first 8, middle 8, and last 8 bytes for O(1) comparison
-/// with negligible collision probability for cache use cases.
+/// Parses a memory size such as "512", "64KB", "1.5GB" or "2TB" into bytes.
+///
+/// Units are binary (1KB = 1024B) and case-insensitive; a bare number is bytes.
+/// Returns `None` for anything else, negative sizes and sizes past `i64::MAX`.
+pub fn parse_mem(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num: f64 = num.parse().ok()?;
+    let scale = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "KB" => 1024.0,
+        "MB" => 1024.0 * 1024.0,
+        "GB" => 1024.0 * 1024.0 * 1024.0,
+        "TB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    let bytes = num * scale;
+    (bytes.is_finite() && bytes < i64::MAX as f64).then_some(bytes as i64)
+}
+
+/// Compares two byte slices for equality, every byte of them: keys, header values and
+/// invalidation filters must never match on a part of their bytes. Slice `==` compiles to a
+/// `memcmp`, vectorized already.
 pub fn is_bytes_equal(a: &[u8], b: &[u8]) -> bool {
+    a == b
+}
+
+/// Tells whether two byte slices are probably equal, as `is_bytes_equal` used to: slices of 32
+/// bytes or more are compared on their first, middle and last 8 bytes, so slices differing
+/// elsewhere are taken as equal. Kept for the tests to show what it missed.
+#[cfg(test)]
+fn is_probably_equal(a: &[u8], b: &[u8]) -> bool {
     if a.len() != b.len() {
         return false;
     }
     if a.len() < 32 {
         return a == b;
     }
-
-    use xxhash_rust::xxh3::Xxh3;
-    let mut hasher_a = Xxh3::new();
-    let mut hasher_b = Xxh3::new();
-
-    // Sample three 8-byte chunks: start, middle, end
-    hasher_a.update(&a[..8]);
-    hasher_b.update(&b[..8]);
-
     let mid = a.len() / 2;
-    hasher_a.update(&a[mid..mid + 8]);
-    hasher_b.update(&b[mid..mid + 8]);
-
-    hasher_a.update(&a[a.len() - 8..]);
-    hasher_b.update(&b[b.len() - 8..]);
-
-    hasher_a.digest() == hasher_b.digest()
-}
-
-/// Compares two byte slices for equality using fast hash-based comparison.
-/// 
-/// This is a convenience function with alternative naming for compatibility.
-pub fn is_bytes_are_equals(a: &[u8], b: &[u8]) -> bool {
-    is_bytes_equal(a, b)
+    a[..8] == b[..8] && a[mid..mid + 8] == b[mid..mid + 8] && a[a.len() - 8..] == b[b.len() - 8..]
 }
 
 #[cfg(test)]
@@ -86,6 +90,20 @@ mod tests {
         assert_eq!(fmt_mem(1024 * 1024 * 1024), "1GB 0MB");
     }
 
+    #[test]
+    fn test_parse_mem() {
+        assert_eq!(parse_mem("512"), Some(512));
+        assert_eq!(parse_mem("64KB"), Some(64 * 1024));
+        assert_eq!(parse_mem("20gb"), Some(20 << 30));
+        assert_eq!(parse_mem("1.5 GB"), Some(3 << 29));
+        assert_eq!(parse_mem("2TB"), Some(2 << 40));
+        assert_eq!(parse_mem(""), None);
+        assert_eq!(parse_mem("GB"), None);
+        assert_eq!(parse_mem("-1GB"), None);
+        assert_eq!(parse_mem("10PB"), None);
+        assert_eq!(parse_mem("1e30"), None);
+    }
+
     #[test]
     fn test_is_bytes_equal() {
         let a = b"hello world";
@@ -94,4 +112,25 @@ mod tests {
         assert!(is_bytes_equal(a, b));
         assert!(!is_bytes_equal(a, c));
     }
+
+    /// Pairs of slices of many lengths differing in one byte between the sampled chunks, at
+    /// 8..mid: the sampled comparison takes them as equal, the exact one does not.
+    #[test]
+    fn test_is_bytes_equal_sees_unsampled_bytes() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x5eed);
+        for _ in 0..2000 {
+            let len = rng.gen_range(32..4096);
+            let a: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            let mid = len / 2;
+            let mut b = a.clone();
+            let at = rng.gen_range(8..mid);
+            b[at] ^= rng.gen_range(1..=255u8);
+
+            assert!(is_probably_equal(&a, &b), "len {}, byte {}: unsampled by the old comparison", len, at);
+            assert!(!is_bytes_equal(&a, &b), "len {}, byte {} differs", len, at);
+            assert!(is_bytes_equal(&a, &a.clone()));
+        }
+    }
 }
@@ -1 +0,0 @@
-pub const COMPONENT: &str = "component";
@@ -0,0 +1,79 @@
+//! Log file sink (`logs.path`), reopened on SIGUSR2.
+//
+// logrotate moves the file aside and signals the process; until the file is reopened, lines
+// keep going to the moved one. Writers take the lock for one formatted line at a time, so a
+// reopen never splits a line between the two files.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use parking_lot::{Mutex, MutexGuard};
+use tracing_subscriber::fmt::MakeWriter;
+
+static LOG_FILE: OnceLock<LogFile> = OnceLock::new();
+
+/// Append-only log file that can be reopened at the same path.
+pub struct LogFile {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl LogFile {
+    /// Opens the file for appending, creating it if missing.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = open_append(&path)?;
+        Ok(Self { path, file: Mutex::new(file) })
+    }
+
+    /// Opens the path again: lines written from now on go to the file found there.
+    pub fn reopen(&self) -> io::Result<()> {
+        let file = open_append(&self.path)?;
+        *self.file.lock() = file;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Writer holding the file for one log line.
+pub struct LogFileWriter<'a>(MutexGuard<'a, File>);
+
+impl Write for LogFileWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogFile {
+    type Writer = LogFileWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogFileWriter(self.file.lock())
+    }
+}
+
+/// Opens `logs.path` and keeps it for [`reopen_log_file`]. Called once, when the logger is set up.
+pub fn configure_log_file(path: &str) -> io::Result<&'static LogFile> {
+    if let Some(file) = LOG_FILE.get() {
+        return Ok(file);
+    }
+    let file = LogFile::open(path)?;
+    Ok(LOG_FILE.get_or_init(|| file))
+}
+
+/// Reopens the log file; returns false when logs are not written to a file.
+pub fn reopen_log_file() -> io::Result<bool> {
+    match LOG_FILE.get() {
+        Some(file) => file.reopen().map(|_| true),
+        None => Ok(false),
+    }
+}
@@ -0,0 +1,52 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use crate::dedlog::file::LogFile;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("advcache-logs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Test that lines go to the moved file until the log file is reopened, and to a new file
+    /// at the configured path after.
+    #[test]
+    fn test_reopen_follows_rotation() {
+        let dir = temp_dir("rotation");
+        let path = dir.join("advcache.log");
+        let rotated = dir.join("advcache.log.1");
+        let file = LogFile::open(&path).unwrap();
+
+        file.make_writer().write_all(b"before\n").unwrap();
+        std::fs::rename(&path, &rotated).unwrap();
+        file.make_writer().write_all(b"moved\n").unwrap();
+        file.reopen().unwrap();
+        file.make_writer().write_all(b"after\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&rotated).unwrap(), "before\nmoved\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "after\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Test that opening appends to a file already there.
+    #[test]
+    fn test_open_appends() {
+        let dir = temp_dir("append");
+        let path = dir.join("advcache.log");
+        std::fs::write(&path, "old\n").unwrap();
+
+        let file = LogFile::open(&path).unwrap();
+        file.make_writer().write_all(b"new\n").unwrap();
+        file.reopen().unwrap();
+        file.make_writer().write_all(b"newer\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\nnew\nnewer\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,34 +1,55 @@
 use dashmap::DashMap;
+use std::future::Future;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 use tracing::error;
 
-use crate::dedlog::sanitizer::{Sanitizer, WithCollapseSpaces};
-use crate::dedlog::consts;
-
+use crate::dedlog::ring::{error_ring, ErrorRecord, ErrorRing};
+use crate::dedlog::sanitizer::{redacted, Sanitizer, WithCollapseSpaces};
+use crate::time;
 
 /// Log entry for deduplication
 pub struct LogEntry {
+    component: &'static str,
     err: Option<String>,
     reason: String,
     extra: Option<String>,
+    request_id: Option<String>,
+    /// Unix milliseconds the error occurred at.
+    timestamp: i64,
     count: usize,
 }
 
 impl LogEntry {
     #[allow(dead_code)] // Used internally in err() function
-    fn new(err: Option<String>, extra: Option<String>, reason: String) -> Self {
+    pub(crate) fn new(component: &'static str, err: Option<String>, extra: Option<String>, reason: String) -> Self {
         Self {
+            component,
             err,
             reason,
             extra,
+            request_id: current_request_id(),
+            timestamp: time::unix_nano() / 1_000_000,
             count: 1,
         }
     }
 }
 
+tokio::task_local! {
+    static REQUEST_ID: Option<String>;
+}
+
+/// Runs a request handler with its request id attached to the errors it reports.
+pub async fn with_request_id<F: Future>(request_id: Option<String>, f: F) -> F::Output {
+    REQUEST_ID.scope(request_id, f).await
+}
+
+fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok().flatten()
+}
+
 // Global channel for sending log entries
 // We'll initialize this when start_dedup_logger is called
 // Use std::sync::Mutex for synchronous access on hotpath
@@ -52,9 +73,10 @@ fn set_err_ch(tx: mpsc::Sender<LogEntry>) {
 /// Hot path method for logging errors without affecting performance.
 /// This is a synchronous function that uses non-blocking try_lock and try_send
 /// to avoid blocking the hotpath.
-pub fn err(err: Option<&dyn std::error::Error>, extra: Option<&str>, msg: &str) {
+pub fn err(component: &'static str, err: Option<&dyn std::error::Error>, extra: Option<&str>, msg: &str) {
     if let Some(tx) = get_err_ch() {
         let entry = LogEntry::new(
+            component,
             err.map(|e| e.to_string()),
             extra.map(|s| s.to_string()),
             msg.to_string(),
@@ -83,6 +105,7 @@ pub async fn start_dedup_logger(ctx: CancellationToken) {
             }
             entry = rx.recv() => {
                 if let Some(entry) = entry {
+                    record_entry(&error_ring(), &entry, &sanitizer);
                     if let Some(mut existing) = cur_map.get_mut(&entry.reason) {
                         existing.count += 1;
                     } else {
@@ -99,38 +122,7 @@ pub async fn start_dedup_logger(ctx: CancellationToken) {
 
                 // Log all entries from previous period
                 for entry in prev.iter() {
-                    if let Some(err) = &entry.err {
-                        let sanitized_err = sanitizer.sanitize(err);
-                        if let Some(extra) = &entry.extra {
-                            error!(
-                                component = consts::COMPONENT,
-                                count = entry.count,
-                                err = %sanitized_err,
-                                extra = %extra,
-                                "{}", entry.reason
-                            );
-                        } else {
-                            error!(
-                                component = consts::COMPONENT,
-                                count = entry.count,
-                                err = %sanitized_err,
-                                "{}", entry.reason
-                            );
-                        }
-                    } else if let Some(extra) = &entry.extra {
-                        error!(
-                            component = consts::COMPONENT,
-                            count = entry.count,
-                            extra = %extra,
-                            "{}", entry.reason
-                        );
-                    } else {
-                        error!(
-                            component = consts::COMPONENT,
-                            count = entry.count,
-                            "{}", entry.reason
-                        );
-                    }
+                    write_entry(&entry, &sanitizer);
                 }
                 // Explicitly drop prev to ensure DashMap is freed immediately after logging
                 // This prevents holding onto the previous period's data longer than necessary
@@ -139,3 +131,56 @@ pub async fn start_dedup_logger(ctx: CancellationToken) {
         }
     }
 }
+
+/// Adds an entry to the error ring, sanitized and redacted as [`write_entry`] logs it.
+pub(crate) fn record_entry(ring: &ErrorRing, entry: &LogEntry, sanitizer: &Sanitizer) {
+    if ring.capacity() == 0 {
+        return;
+    }
+    ring.push(ErrorRecord {
+        timestamp: entry.timestamp,
+        component: entry.component.to_string(),
+        class: entry.reason.clone(),
+        message: entry.err.as_ref().map(|err| redacted(&sanitizer.sanitize(err)).to_string()),
+        request: entry.extra.as_ref().map(|extra| redacted(extra).to_string()),
+        request_id: entry.request_id.clone(),
+        count: entry.count,
+    });
+}
+
+/// Writes a single aggregated entry. Request representations in `err` and `extra`
+/// go through redaction since they may carry tokens or emails in query strings.
+pub(crate) fn write_entry(entry: &LogEntry, sanitizer: &Sanitizer) {
+    if let Some(err) = &entry.err {
+        let sanitized_err = sanitizer.sanitize(err);
+        if let Some(extra) = &entry.extra {
+            error!(
+                component = entry.component,
+                count = entry.count,
+                err = %redacted(&sanitized_err),
+                extra = %redacted(extra),
+                "{}", entry.reason
+            );
+        } else {
+            error!(
+                component = entry.component,
+                count = entry.count,
+                err = %redacted(&sanitized_err),
+                "{}", entry.reason
+            );
+        }
+    } else if let Some(extra) = &entry.extra {
+        error!(
+            component = entry.component,
+            count = entry.count,
+            extra = %redacted(extra),
+            "{}", entry.reason
+        );
+    } else {
+        error!(
+            component = entry.component,
+            count = entry.count,
+            "{}", entry.reason
+        );
+    }
+}
@@ -1,7 +1,18 @@
 //! Deduplicated logging functionality to prevent log spam.
 
-pub mod consts;
+pub mod file;
 pub mod sanitizer;
 pub mod log_entry;
+pub mod ring;
 
-pub use log_entry::{err, start_dedup_logger};
\ No newline at end of file
+#[cfg(test)]
+mod file_test;
+#[cfg(test)]
+mod ring_test;
+#[cfg(test)]
+mod sanitizer_test;
+
+pub use file::{configure_log_file, reopen_log_file};
+pub use log_entry::{err, start_dedup_logger, with_request_id};
+pub use ring::{configure_error_ring, error_ring};
+pub use sanitizer::{configure_redaction, is_redacted_query, redacted, redacted_headers};
\ No newline at end of file
@@ -0,0 +1,116 @@
+//! Ring of the most recent errors, served by `/advcache/errors`.
+//
+// Fed by the dedup logger with every error it receives, sanitized and redacted the way it is
+// logged. An error repeating one recorded within the dedup window bumps that record's count
+// instead of taking a slot, so a burst of the same failure does not push everything else out.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// Records kept when `logs.error_ring` is not set.
+pub const DEFAULT_CAPACITY: usize = 512;
+
+/// Window in milliseconds within which a repeated error is counted on the previous record.
+pub const DEDUP_WINDOW_MS: i64 = 5_000;
+
+/// One error, or a run of identical ones.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorRecord {
+    /// Unix milliseconds of the latest occurrence.
+    pub timestamp: i64,
+    pub component: String,
+    /// What failed: the reason the error is logged with.
+    pub class: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Request or resource the error occurred on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub count: usize,
+}
+
+impl ErrorRecord {
+    fn same_error(&self, other: &ErrorRecord) -> bool {
+        self.component == other.component && self.class == other.class && self.message == other.message
+    }
+}
+
+/// Bounded ring of error records, oldest first.
+pub struct ErrorRing {
+    capacity: usize,
+    records: Mutex<VecDeque<ErrorRecord>>,
+}
+
+impl ErrorRing {
+    /// Creates a ring keeping up to `capacity` records; 0 keeps none.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Mutex::new(VecDeque::with_capacity(capacity.min(DEFAULT_CAPACITY))),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Records an error. A record of the same error seen within the dedup window takes its
+    /// count, moves to the newest position and reports the latest timestamp and request.
+    pub fn push(&self, mut record: ErrorRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut records = self.records.lock();
+        let window_start = record.timestamp - DEDUP_WINDOW_MS;
+        let repeated = records
+            .iter()
+            .rev()
+            .take_while(|r| r.timestamp >= window_start)
+            .position(|r| r.same_error(&record))
+            .map(|pos| records.len() - 1 - pos);
+        if let Some(idx) = repeated {
+            if let Some(prev) = records.remove(idx) {
+                record.count += prev.count;
+                record.timestamp = record.timestamp.max(prev.timestamp);
+            }
+        }
+        records.push_back(record);
+        while records.len() > self.capacity {
+            records.pop_front();
+        }
+    }
+
+    /// Up to `limit` records, newest first, of `component` only when given and last seen at or
+    /// after `since` (unix milliseconds) when given.
+    pub fn recent(&self, limit: usize, component: Option<&str>, since: Option<i64>) -> Vec<ErrorRecord> {
+        let records = self.records.lock();
+        records
+            .iter()
+            .rev()
+            .filter(|r| component.is_none_or(|c| r.component == c))
+            .filter(|r| since.is_none_or(|s| r.timestamp >= s))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+static RING: Lazy<ArcSwap<ErrorRing>> = Lazy::new(|| ArcSwap::from_pointee(ErrorRing::new(DEFAULT_CAPACITY)));
+
+/// Sets the size of the global error ring (`logs.error_ring`), dropping what it holds.
+pub fn configure_error_ring(capacity: Option<usize>) {
+    RING.store(Arc::new(ErrorRing::new(capacity.unwrap_or(DEFAULT_CAPACITY))));
+}
+
+/// The global error ring fed by the dedup logger.
+pub fn error_ring() -> Arc<ErrorRing> {
+    RING.load_full()
+}
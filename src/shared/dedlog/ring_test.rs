@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod tests {
+    use crate::config::{new_test_config, ConfigTrait};
+    use crate::dedlog::log_entry::{record_entry, with_request_id, LogEntry};
+    use crate::dedlog::ring::{ErrorRecord, ErrorRing, DEDUP_WINDOW_MS};
+    use crate::dedlog::sanitizer::{configure_redaction, Sanitizer, WithCollapseSpaces};
+
+    fn record(timestamp: i64, component: &str, class: &str, message: &str) -> ErrorRecord {
+        ErrorRecord {
+            timestamp,
+            component: component.to_string(),
+            class: class.to_string(),
+            message: Some(message.to_string()),
+            request: None,
+            request_id: None,
+            count: 1,
+        }
+    }
+
+    fn classes(records: &[ErrorRecord]) -> Vec<&str> {
+        records.iter().map(|r| r.class.as_str()).collect()
+    }
+
+    /// Test that records are listed newest first and `limit` keeps the newest.
+    #[test]
+    fn test_newest_first() {
+        let ring = ErrorRing::new(8);
+        ring.push(record(1_000, "upstream", "a", "x"));
+        ring.push(record(2_000, "dump", "b", "x"));
+        ring.push(record(3_000, "upstream", "c", "x"));
+
+        assert_eq!(classes(&ring.recent(10, None, None)), vec!["c", "b", "a"]);
+        assert_eq!(classes(&ring.recent(2, None, None)), vec!["c", "b"]);
+    }
+
+    /// Test that an error repeating within the dedup window is counted on its record, which
+    /// moves to the newest position, while a repeat after the window takes a new record.
+    #[test]
+    fn test_dedup_counts() {
+        let ring = ErrorRing::new(8);
+        ring.push(record(1_000, "upstream", "timeout", "connect timed out"));
+        ring.push(record(1_500, "dump", "write", "disk full"));
+        ring.push(record(2_000, "upstream", "timeout", "connect timed out"));
+        ring.push(record(2_500, "upstream", "timeout", "connect timed out"));
+        ring.push(record(2_600, "upstream", "timeout", "connection refused"));
+
+        let recent = ring.recent(10, None, None);
+        assert_eq!(classes(&recent), vec!["timeout", "timeout", "write"]);
+        assert_eq!(recent[0].message.as_deref(), Some("connection refused"));
+        assert_eq!(recent[0].count, 1);
+        assert_eq!(recent[1].count, 3);
+        assert_eq!(recent[1].timestamp, 2_500);
+        assert_eq!(recent[2].count, 1);
+
+        ring.push(record(2_500 + DEDUP_WINDOW_MS + 1, "upstream", "timeout", "connect timed out"));
+        let recent = ring.recent(10, None, None);
+        assert_eq!(recent.len(), 4);
+        assert_eq!(recent[0].count, 1);
+    }
+
+    /// Test that the ring never holds more than its capacity, dropping the oldest records.
+    #[test]
+    fn test_size_bound() {
+        let ring = ErrorRing::new(3);
+        for i in 0..10 {
+            ring.push(record(i * 10_000, "upstream", &i.to_string(), "x"));
+        }
+        let recent = ring.recent(usize::MAX, None, None);
+        assert_eq!(classes(&recent), vec!["9", "8", "7"]);
+
+        let disabled = ErrorRing::new(0);
+        disabled.push(record(0, "upstream", "a", "x"));
+        assert!(disabled.recent(10, None, None).is_empty());
+    }
+
+    /// Test filtering by component and by the time a record was last seen.
+    #[test]
+    fn test_filters() {
+        let ring = ErrorRing::new(8);
+        ring.push(record(1_000, "upstream", "a", "x"));
+        ring.push(record(2_000, "dump", "b", "x"));
+        ring.push(record(3_000, "upstream", "c", "x"));
+
+        assert_eq!(classes(&ring.recent(10, Some("upstream"), None)), vec!["c", "a"]);
+        assert_eq!(classes(&ring.recent(10, None, Some(2_000))), vec!["c", "b"]);
+        assert_eq!(classes(&ring.recent(10, Some("dump"), Some(2_001))), Vec::<&str>::new());
+    }
+
+    /// Test that entries reach the ring sanitized and redacted as they are logged, tagged with
+    /// the request id of the handler that reported them.
+    #[tokio::test]
+    async fn test_entry_is_sanitized_and_tagged() {
+        let cfg = new_test_config();
+        configure_redaction(cfg.logs().and_then(|l| l.redact.as_ref()));
+        let ring = ErrorRing::new(8);
+        let sanitizer = Sanitizer::new(WithCollapseSpaces(true));
+
+        let entry = with_request_id(Some("req-42".to_string()), async {
+            LogEntry::new(
+                "cache-controller",
+                Some("connect 10.0.0.7:80 failed   for /api/v1/user?token=t0k3n".to_string()),
+                Some("GET /api/v1/user?token=t0k3n&id=7 HTTP/1.1".to_string()),
+                "fetch upstream error while proxying".to_string(),
+            )
+        })
+        .await;
+        record_entry(&ring, &entry, &sanitizer);
+
+        let recent = ring.recent(10, None, None);
+        assert_eq!(recent.len(), 1);
+        let rec = &recent[0];
+        assert_eq!(rec.component, "cache-controller");
+        assert_eq!(rec.class, "fetch upstream error while proxying");
+        assert_eq!(rec.request_id.as_deref(), Some("req-42"));
+        let message = rec.message.as_deref().unwrap();
+        assert!(message.contains("<ip4>") && !message.contains("t0k3n"), "{message}");
+        assert!(!message.contains("  "), "{message}");
+        assert_eq!(rec.request.as_deref(), Some("GET /api/v1/user?token=<redacted>&id=7 HTTP/1.1"));
+    }
+}
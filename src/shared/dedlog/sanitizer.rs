@@ -1,7 +1,13 @@
 //! Log message sanitization for deduplicated logging.
 //
 
+use arc_swap::{ArcSwap, Guard};
+use once_cell::sync::Lazy;
 use regex::Regex;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::Redact;
 
 /// Sanitization rule with regex pattern and placeholder
 struct Rule {
@@ -109,3 +115,148 @@ impl Sanitizer {
         result
     }
 }
+
+/// Placeholder written instead of a redacted value.
+pub const REDACTED: &str = "<redacted>";
+
+/// Query parameters and headers whose values must never reach logs or span attributes.
+/// Names are matched case-insensitively.
+#[derive(Debug, Default, Clone)]
+pub struct Redaction {
+    query: Vec<String>,
+    headers: Vec<String>,
+}
+
+static REDACTION: Lazy<ArcSwap<Redaction>> = Lazy::new(|| ArcSwap::from_pointee(Redaction::default()));
+
+/// Installs the process-wide redaction lists (`logs.redact` section of the config).
+pub fn configure_redaction(cfg: Option<&Redact>) {
+    REDACTION.store(Arc::new(Redaction::from_cfg(cfg)));
+}
+
+/// Wraps a request representation (or any message that may embed one) so that
+/// values of sensitive query parameters are masked while it is being formatted.
+pub fn redacted(raw: &str) -> Redacted<'_> {
+    Redacted {
+        redaction: REDACTION.load(),
+        raw,
+    }
+}
+
+/// Checks whether values of the query parameter are masked (`logs.redact.query`).
+pub fn is_redacted_query(name: &str) -> bool {
+    REDACTION.load().is_sensitive_query(name)
+}
+
+/// Wraps a header list so that values of sensitive headers are masked while it is being formatted.
+pub fn redacted_headers<K: AsRef<str>, V: AsRef<str>>(headers: &[(K, V)]) -> RedactedHeaders<'_, K, V> {
+    RedactedHeaders {
+        redaction: REDACTION.load(),
+        headers,
+    }
+}
+
+impl Redaction {
+    /// Creates redaction lists from query parameter and header names.
+    pub fn new(query: &[String], headers: &[String]) -> Self {
+        Self {
+            query: query.iter().map(|s| s.trim().to_ascii_lowercase()).filter(|s| !s.is_empty()).collect(),
+            headers: headers.iter().map(|s| s.trim().to_ascii_lowercase()).filter(|s| !s.is_empty()).collect(),
+        }
+    }
+
+    /// Creates redaction lists from the `logs.redact` config section.
+    pub fn from_cfg(cfg: Option<&Redact>) -> Self {
+        match cfg {
+            Some(r) => Self::new(
+                r.query.as_deref().unwrap_or(&[]),
+                r.headers.as_deref().unwrap_or(&[]),
+            ),
+            None => Self::default(),
+        }
+    }
+
+    /// Checks whether the query parameter value must be masked.
+    pub fn is_sensitive_query(&self, name: &str) -> bool {
+        self.query.iter().any(|q| q.eq_ignore_ascii_case(name))
+    }
+
+    /// Checks whether the header value must be masked; the signature of a signed upstream
+    /// request always is.
+    pub fn is_sensitive_header(&self, name: &str) -> bool {
+        name.eq_ignore_ascii_case(crate::upstream::signing::SIGNATURE_HEADER)
+            || self.headers.iter().any(|h| h.eq_ignore_ascii_case(name))
+    }
+
+    /// Writes `raw` masking values of sensitive query parameters.
+    /// Every `?...` query found in the text is processed, so full error messages
+    /// with embedded URIs are handled the same way as bare request lines.
+    pub fn write_request(&self, f: &mut fmt::Formatter<'_>, raw: &str) -> fmt::Result {
+        if self.query.is_empty() {
+            return f.write_str(raw);
+        }
+
+        let mut rest = raw;
+        while let Some(q) = rest.find('?') {
+            f.write_str(&rest[..=q])?;
+            rest = &rest[q + 1..];
+
+            let end = rest
+                .find(|c: char| c.is_whitespace() || matches!(c, '#' | ')' | '"' | '\''))
+                .unwrap_or(rest.len());
+            let (query, tail) = rest.split_at(end);
+
+            for (i, pair) in query.split('&').enumerate() {
+                if i > 0 {
+                    f.write_str("&")?;
+                }
+                match pair.split_once('=') {
+                    Some((name, _)) if self.is_sensitive_query(name) => {
+                        f.write_str(name)?;
+                        f.write_str("=")?;
+                        f.write_str(REDACTED)?;
+                    }
+                    _ => f.write_str(pair)?,
+                }
+            }
+            rest = tail;
+        }
+        f.write_str(rest)
+    }
+}
+
+/// Display adapter returned by [`redacted`].
+pub struct Redacted<'a> {
+    redaction: Guard<Arc<Redaction>>,
+    raw: &'a str,
+}
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.redaction.write_request(f, self.raw)
+    }
+}
+
+/// Display adapter returned by [`redacted_headers`], renders `name: value` pairs separated by `, `.
+pub struct RedactedHeaders<'a, K, V> {
+    redaction: Guard<Arc<Redaction>>,
+    headers: &'a [(K, V)],
+}
+
+impl<K: AsRef<str>, V: AsRef<str>> fmt::Display for RedactedHeaders<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, value)) in self.headers.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(name.as_ref())?;
+            f.write_str(": ")?;
+            if self.redaction.is_sensitive_header(name.as_ref()) {
+                f.write_str(REDACTED)?;
+            } else {
+                f.write_str(value.as_ref())?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+#[cfg(test)]
+mod tests {
+    use std::fmt::{self, Write as _};
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::Attributes;
+    use tracing::{Event, Id, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer};
+    use tracing_subscriber::prelude::*;
+
+    use crate::config::{new_test_config, ConfigTrait, Rule, RuleKey, RuleValue};
+    use crate::dedlog::log_entry::{write_entry, LogEntry};
+    use crate::dedlog::sanitizer::{
+        configure_redaction, redacted, redacted_headers, Redaction, Sanitizer, WithCollapseSpaces,
+    };
+    use crate::upstream::trace;
+
+    /// Collects every span attribute and event field as `name=value` lines.
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<String>>>);
+
+    impl Capture {
+        fn joined(&self) -> String {
+            self.0.lock().unwrap().join("\n")
+        }
+    }
+
+    struct Collector<'a>(&'a mut Vec<String>);
+
+    impl Visit for Collector<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push(format!("{}={}", field.name(), value));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for Capture {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            attrs.record(&mut Collector(&mut self.0.lock().unwrap()));
+        }
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            event.record(&mut Collector(&mut self.0.lock().unwrap()));
+        }
+    }
+
+    fn install_test_redaction() {
+        let cfg = new_test_config();
+        configure_redaction(cfg.logs().and_then(|l| l.redact.as_ref()));
+    }
+
+    fn make_redaction() -> Redaction {
+        Redaction::new(
+            &["token".to_string(), "Email".to_string()],
+            &["authorization".to_string(), "cookie".to_string()],
+        )
+    }
+
+    /// Formats through the given redaction without touching the global one.
+    fn fmt_request(r: &Redaction, raw: &str) -> String {
+        struct W<'a>(&'a Redaction, &'a str);
+        impl fmt::Display for W<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.write_request(f, self.1)
+            }
+        }
+        let mut out = String::new();
+        write!(out, "{}", W(r, raw)).unwrap();
+        out
+    }
+
+    fn make_rule() -> Rule {
+        Rule {
+            path: Some("/api/v1/user".to_string()),
+            path_bytes: Some(b"/api/v1/user".to_vec()),
+            cache_key: RuleKey {
+                query: None,
+                query_bytes: None,
+                query_ignore: None,
+                duplicate_query: None,
+                transformer: None,
+                headers: None,
+                headers_map: None,
+            },
+            cache_value: RuleValue {
+                headers: None,
+                headers_map: None,
+                persist: None,
+                rollout_percent: Default::default(),
+                max_concurrent_fills: None,
+                max_fill_wait: None,
+                admission_hook: None,
+                downstream_ttl: Default::default(),
+                respect_cache_control: None,
+                negative_ttl: None,
+            },
+            refresh: None,
+            stale_on_error: None,
+            shed_on_brownout: None,
+            methods: None,
+            key_body: None,
+            max_key_body: None,
+            max_body_size: None,
+            refresh_inherited: false,
+        }
+    }
+
+    /// Test that sensitive query values in a request line are masked and the rest is kept intact.
+    #[test]
+    fn test_redacts_request_line() {
+        let r = make_redaction();
+        assert_eq!(
+            fmt_request(&r, "GET /api/v1/user?id=1&token=secret&EMAIL=a@b.io HTTP/1.1"),
+            "GET /api/v1/user?id=1&token=<redacted>&EMAIL=<redacted> HTTP/1.1"
+        );
+    }
+
+    /// Test that every URI embedded in an error message is processed.
+    #[test]
+    fn test_redacts_uris_embedded_in_messages() {
+        let r = make_redaction();
+        assert_eq!(
+            fmt_request(
+                &r,
+                "Hyper client error: refused (URI: http://up/a?token=x) retry (URI: http://up/b?email=y&p=1)"
+            ),
+            "Hyper client error: refused (URI: http://up/a?token=<redacted>) retry (URI: http://up/b?email=<redacted>&p=1)"
+        );
+    }
+
+    /// Test that strings without sensitive parameters are written unchanged.
+    #[test]
+    fn test_leaves_non_sensitive_input_untouched() {
+        let r = make_redaction();
+        for raw in ["", "GET /a HTTP/1.1", "GET /a? HTTP/1.1", "GET /a?tokenish=1&=2&token HTTP/1.1"] {
+            assert_eq!(fmt_request(&r, raw), raw);
+        }
+        assert_eq!(fmt_request(&Redaction::default(), "GET /a?token=1"), "GET /a?token=1");
+    }
+
+    /// Test that sensitive header values are masked case-insensitively.
+    #[test]
+    fn test_redacts_headers() {
+        install_test_redaction();
+        let headers = [("Authorization", "Bearer abc"), ("Cookie", "sid=1"), ("Accept", "*/*")];
+        assert_eq!(
+            redacted_headers(&headers).to_string(),
+            "Authorization: <redacted>, Cookie: <redacted>, Accept: */*"
+        );
+    }
+
+    /// Test that dedlog output has redacted request representations in both `err` and `extra`.
+    #[test]
+    fn test_dedlog_sink_is_redacted() {
+        install_test_redaction();
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(capture.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let sanitizer = Sanitizer::new(WithCollapseSpaces(true));
+            let entry = LogEntry::new(
+                "cache-controller",
+                Some("upstream failed for /api/v1/user?token=t0k3n".to_string()),
+                Some("GET /api/v1/user?token=t0k3n&id=7 HTTP/1.1".to_string()),
+                "fetch upstream error while proxying".to_string(),
+            );
+            write_entry(&entry, &sanitizer);
+        });
+
+        let out = capture.joined();
+        assert!(!out.contains("t0k3n"), "leaked token: {out}");
+        assert!(out.contains("extra=GET /api/v1/user?token=<redacted>&id=7 HTTP/1.1"), "{out}");
+        assert!(out.contains("component=cache-controller"), "{out}");
+    }
+
+    /// Test that upstream span attributes carry redacted requests.
+    #[test]
+    fn test_upstream_span_attributes_are_redacted() {
+        install_test_redaction();
+        crate::traces::enable_tracing();
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(capture.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let rule = make_rule();
+            let _a = trace::start_request_span(&rule, "GET http://up/api/v1/user?email=a@b.io&id=1");
+            let _b = trace::start_proxy_request_span("/api/v1/user", "POST http://up/api/v1/user?token=zzz");
+        });
+        // The flag is process-wide: left on, every later request would walk its headers for a context.
+        crate::traces::disable_tracing();
+
+        let out = capture.joined();
+        assert!(!out.contains("a@b.io") && !out.contains("zzz"), "leaked: {out}");
+        assert!(out.contains("http.request=GET http://up/api/v1/user?email=<redacted>&id=1"), "{out}");
+        assert!(out.contains("http.request=POST http://up/api/v1/user?token=<redacted>"), "{out}");
+    }
+
+    /// Test that the global adapter follows the installed configuration.
+    #[test]
+    fn test_global_redaction_uses_config() {
+        install_test_redaction();
+        assert_eq!(
+            redacted("GET /x?Token=1&q=2").to_string(),
+            "GET /x?Token=<redacted>&q=2"
+        );
+    }
+}
@@ -57,6 +57,7 @@ pub fn unix_nano() -> i64 {
 }
 
 /// Returns the duration elapsed since the given time.
+#[cfg_attr(not(feature = "dump"), allow(dead_code))]
 pub fn since(t: SystemTime) -> Duration {
     now().duration_since(t).unwrap_or(Duration::ZERO)
 }
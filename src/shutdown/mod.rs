@@ -1,24 +1,86 @@
 //! Graceful shutdown functionality.
+//!
+//! Once a signal arrives, the application stops in ordered phases, each run through
+//! [`GracefulShutdown::run_phase`] under a time budget of its own. A phase over its budget is
+//! abandoned with a warning and the next one starts, so a slow dump does not keep the
+//! remaining phases from running. Every phase is logged with its duration and outcome and
+//! recorded in a [`ShutdownReport`]. The signals handled are described in [`signals`].
 
 use anyhow::Result;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::signal;
+use std::time::{Duration, Instant};
 use tokio::time::timeout;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
+pub mod signals;
+
+use signals::ShutdownSignals;
+
+/// Closing the listeners.
+pub const PHASE_STOP_ACCEPTING: &str = "stop_accepting";
+/// Answering the requests of open connections.
+pub const PHASE_DRAIN_HTTP: &str = "drain_http";
+/// Stopping the background workers through the governor.
+pub const PHASE_STOP_WORKERS: &str = "stop_workers";
+/// Writing the dump.
+pub const PHASE_DUMP: &str = "dump";
+/// Cancelling the root token and closing what is left.
+pub const PHASE_FINAL_CANCEL: &str = "final_cancel";
+
 #[derive(Debug, thiserror::Error)]
 #[error("graceful shutdown timeout exceeded")]
 pub struct TimeoutError;
 
-/// Graceful shutdown handler
+/// How a shutdown phase ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhaseOutcome {
+    Completed,
+    Failed,
+    /// The phase exceeded its budget and was abandoned.
+    TimedOut,
+}
+
+/// One phase of a shutdown.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseReport {
+    pub phase: String,
+    pub outcome: PhaseOutcome,
+    pub duration_ms: u64,
+    pub timeout_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Phases of a shutdown in the order they ran.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShutdownReport {
+    /// When the shutdown started, RFC 3339.
+    #[serde(default)]
+    pub started_at: String,
+    /// Signal the shutdown started on, unset when it was started from within.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+    pub phases: Vec<PhaseReport>,
+}
+
+impl ShutdownReport {
+    /// Whether any phase was abandoned over its budget.
+    pub fn timed_out(&self) -> bool {
+        self.phases.iter().any(|p| p.outcome == PhaseOutcome::TimedOut)
+    }
+}
+
 /// Graceful shutdown handler using Rust async primitives
 #[derive(Clone)]
 pub struct GracefulShutdown {
     shutdown_token: CancellationToken,
-    timeout: Arc<tokio::sync::RwLock<Duration>>,
     counter: Arc<tokio::sync::Semaphore>,
+    report: Arc<Mutex<ShutdownReport>>,
 }
 
 impl GracefulShutdown {
@@ -26,16 +88,11 @@ impl GracefulShutdown {
     pub fn new(shutdown_token: CancellationToken) -> Self {
         Self {
             shutdown_token,
-            timeout: Arc::new(tokio::sync::RwLock::new(Duration::from_secs(10))),
             counter: Arc::new(tokio::sync::Semaphore::new(0)),
+            report: Arc::new(Mutex::new(ShutdownReport::default())),
         }
     }
 
-    /// Sets the graceful shutdown timeout
-    pub async fn set_graceful_timeout(&self, timeout: Duration) {
-        *self.timeout.write().await = timeout;
-    }
-
     /// Adds to the wait counter
     pub fn add(&self, n: usize) {
         // Add permits to the semaphore
@@ -48,17 +105,35 @@ impl GracefulShutdown {
         let _ = self.counter.try_acquire();
     }
 
-    /// Waits for shutdown signal and then waits for all tasks to complete
-    pub async fn await_shutdown(&self) -> Result<()> {
-        // Wait for either OS signal or cancellation
-        tokio::select! {
-            _ = signal::ctrl_c() => {
+    /// Waits for SIGTERM, SIGINT or the cancellation of the root token. Returns the signal
+    /// listener when a signal started the shutdown, to watch for a second one meanwhile.
+    pub async fn await_signal(&self) -> Option<ShutdownSignals> {
+        let mut signals = ShutdownSignals::new()
+            .inspect_err(|e| {
+                error!(
+                    component = "graceful-shutdown",
+                    event = "signals_not_installed",
+                    error = %e,
+                    "signal handlers are not installed, only the root token stops the instance"
+                )
+            })
+            .ok();
+        let recv = async {
+            match signals.as_mut() {
+                Some(signals) => signals.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        let signal = tokio::select! {
+            signal = recv => {
                 info!(
                     component = "graceful-shutdown",
                     event = "os_signal",
-                    signal = "SIGINT",
+                    signal = signal.label(),
                     "cancellation started"
                 );
+                Some(signal)
             }
             _ = self.shutdown_token.cancelled() => {
                 info!(
@@ -66,40 +141,73 @@ impl GracefulShutdown {
                     event = "ctx_done",
                     "cancellation started"
                 );
+                None
             }
+        };
+        let mut report = self.report.lock();
+        report.started_at = chrono::Utc::now().to_rfc3339();
+        report.signal = signal.map(|s| s.label().to_string());
+        signal.and(signals)
+    }
+
+    /// Runs one shutdown phase within its budget, logs and records how it ended. A phase
+    /// over budget is dropped, which cancels whatever it was awaiting.
+    pub async fn run_phase<F>(&self, phase: &str, budget: Duration, fut: F) -> PhaseOutcome
+    where
+        F: Future<Output = Result<()>>,
+    {
+        let started = Instant::now();
+        let (outcome, err) = match timeout(budget, fut).await {
+            Ok(Ok(())) => (PhaseOutcome::Completed, None),
+            Ok(Err(e)) => (PhaseOutcome::Failed, Some(format!("{:#}", e))),
+            Err(_) => (PhaseOutcome::TimedOut, None),
+        };
+        let elapsed = started.elapsed();
+
+        match outcome {
+            PhaseOutcome::Completed => info!(
+                component = "graceful-shutdown",
+                event = "phase_completed",
+                phase = phase,
+                duration = ?elapsed,
+                "shutdown phase completed"
+            ),
+            PhaseOutcome::Failed => error!(
+                component = "graceful-shutdown",
+                event = "phase_failed",
+                phase = phase,
+                duration = ?elapsed,
+                error = err.as_deref().unwrap_or_default(),
+                "shutdown phase failed"
+            ),
+            PhaseOutcome::TimedOut => warn!(
+                component = "graceful-shutdown",
+                event = "phase_timeout",
+                phase = phase,
+                timeout = ?budget,
+                "shutdown phase exceeded its timeout, moving on to the next one"
+            ),
         }
 
-        self.cancel_and_await_with_timeout().await
+        self.report.lock().phases.push(PhaseReport {
+            phase: phase.to_string(),
+            outcome,
+            duration_ms: elapsed.as_millis() as u64,
+            timeout_ms: budget.as_millis() as u64,
+            error: err,
+        });
+        outcome
     }
 
-    async fn cancel_and_await_with_timeout(&self) -> Result<()> {
-        // Cancel the shutdown token
+    /// Cancels the root token and waits for the registered tasks to complete.
+    pub async fn cancel_and_wait(&self) {
         self.shutdown_token.cancel();
+        self.wait_for_completion().await;
+    }
 
-        let timeout_duration = *self.timeout.read().await;
-
-        // Wait for all tasks to complete, with timeout
-        let result = match timeout(timeout_duration, self.wait_for_completion()).await {
-            Ok(_) => {
-                info!(
-                    component = "graceful-shutdown",
-                    event = "shutdown_success",
-                    "service was gracefully shut down"
-                );
-                Ok(())
-            }
-            Err(_) => {
-                warn!(
-                    component = "graceful-shutdown",
-                    event = "shutdown_timeout",
-                    timeout_secs = timeout_duration.as_secs(),
-                    "not all tasks were closed within timeout"
-                );
-                Err(TimeoutError.into())
-            }
-        };
-
-        result
+    /// Phases run so far.
+    pub fn report(&self) -> ShutdownReport {
+        self.report.lock().clone()
     }
 
     async fn wait_for_completion(&self) {
@@ -113,3 +221,8 @@ impl GracefulShutdown {
         }
     }
 }
+
+#[cfg(test)]
+mod shutdown_test;
+#[cfg(test)]
+mod signals_test;
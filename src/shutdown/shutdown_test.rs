@@ -0,0 +1,82 @@
+//! Tests for the shutdown phases.
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::shutdown::*;
+
+    /// Test that a dump over its budget is abandoned and the phases after it still run.
+    #[tokio::test]
+    async fn test_slow_dump_does_not_block_later_phases() {
+        let token = CancellationToken::new();
+        let gsh = GracefulShutdown::new(token.clone());
+        let budget = Duration::from_millis(50);
+
+        let workers = gsh.run_phase(PHASE_STOP_WORKERS, budget, async { Ok(()) }).await;
+        let dump = gsh
+            .run_phase(PHASE_DUMP, budget, async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(())
+            })
+            .await;
+        let cancel = gsh
+            .run_phase(PHASE_FINAL_CANCEL, budget, async {
+                gsh.cancel_and_wait().await;
+                Ok(())
+            })
+            .await;
+
+        assert_eq!(workers, PhaseOutcome::Completed);
+        assert_eq!(dump, PhaseOutcome::TimedOut);
+        assert_eq!(cancel, PhaseOutcome::Completed);
+        assert!(token.is_cancelled(), "the final phase must run after a slow dump");
+
+        let report = gsh.report();
+        assert!(report.timed_out());
+        let phases: Vec<_> = report.phases.iter().map(|p| p.phase.as_str()).collect();
+        assert_eq!(phases, [PHASE_STOP_WORKERS, PHASE_DUMP, PHASE_FINAL_CANCEL]);
+        let dump = &report.phases[1];
+        assert_eq!(dump.timeout_ms, 50);
+        assert!(dump.duration_ms >= 50 && dump.duration_ms < 5_000, "{:?}", dump);
+    }
+
+    /// Test that a failed phase is reported with its error and does not count as a timeout.
+    #[tokio::test]
+    async fn test_failed_phase_is_reported() {
+        let gsh = GracefulShutdown::new(CancellationToken::new());
+
+        let outcome = gsh
+            .run_phase(PHASE_DUMP, Duration::from_secs(1), async {
+                Err(anyhow::anyhow!("disk is full"))
+            })
+            .await;
+
+        assert_eq!(outcome, PhaseOutcome::Failed);
+        let report = gsh.report();
+        assert!(!report.timed_out());
+        assert_eq!(report.phases[0].error.as_deref(), Some("disk is full"));
+    }
+
+    /// Test that the report keeps its shape through YAML, as it is stored in the overrides file.
+    #[test]
+    fn test_report_round_trip() {
+        let report = ShutdownReport {
+            started_at: "2026-01-02T03:04:05+00:00".to_string(),
+            signal: Some("SIGTERM".to_string()),
+            phases: vec![PhaseReport {
+                phase: PHASE_DUMP.to_string(),
+                outcome: PhaseOutcome::TimedOut,
+                duration_ms: 60_000,
+                timeout_ms: 60_000,
+                error: None,
+            }],
+        };
+
+        let yaml = serde_yaml::to_string(&report).unwrap();
+        assert!(yaml.contains("outcome: timed_out"), "{}", yaml);
+        assert_eq!(serde_yaml::from_str::<ShutdownReport>(&yaml).unwrap(), report);
+    }
+}
@@ -0,0 +1,167 @@
+//! OS signals the process acts on.
+//!
+//! - SIGTERM (what Kubernetes sends) and SIGINT start the graceful shutdown. A second SIGINT
+//!   while it runs stops the process at once, for an operator at the terminal; a second
+//!   SIGTERM is logged and ignored, the orchestrator follows up with SIGKILL on its own.
+//! - SIGUSR1 writes a dump on demand (`data.dump`).
+//! - SIGUSR2 reopens the log file (`logs.path`), e.g. after logrotate moved it.
+//! - SIGHUP reloads the rules, lifetime and eviction sections of the config file.
+//!
+//! Every signal received is logged and counted in `process_signals{signal}`. On other
+//! platforms only Ctrl-C is handled.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::controller::metrics;
+
+/// A signal the process handles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Terminate = 0,
+    Interrupt = 1,
+    Dump = 2,
+    Reopen = 3,
+    Reload = 4,
+}
+
+impl Signal {
+    pub const ALL: [Signal; 5] = [Signal::Terminate, Signal::Interrupt, Signal::Dump, Signal::Reopen, Signal::Reload];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Signal::Terminate => "SIGTERM",
+            Signal::Interrupt => "SIGINT",
+            Signal::Dump => "SIGUSR1",
+            Signal::Reopen => "SIGUSR2",
+            Signal::Reload => "SIGHUP",
+        }
+    }
+}
+
+/// Listener of the signals stopping the process: SIGTERM and SIGINT.
+pub struct ShutdownSignals {
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+}
+
+impl ShutdownSignals {
+    /// Installs the handlers; from here on the signals no longer stop the process by default.
+    pub fn new() -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Ok(Self { terminate: signal(SignalKind::terminate())?, interrupt: signal(SignalKind::interrupt())? })
+        }
+        #[cfg(not(unix))]
+        Ok(Self {})
+    }
+
+    /// Waits for the next SIGTERM or SIGINT, counts it and returns which one it was.
+    pub async fn recv(&mut self) -> Signal {
+        #[cfg(unix)]
+        let signal = tokio::select! {
+            _ = self.terminate.recv() => Signal::Terminate,
+            _ = self.interrupt.recv() => Signal::Interrupt,
+        };
+        #[cfg(not(unix))]
+        let signal = {
+            let _ = tokio::signal::ctrl_c().await;
+            Signal::Interrupt
+        };
+        metrics::inc_process_signals(signal);
+        signal
+    }
+
+    /// Once the shutdown started: calls `force` on a second SIGINT, ignores further SIGTERMs.
+    pub fn spawn_force_on_interrupt<F>(mut self, force: F) -> JoinHandle<()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        tokio::spawn(async move {
+            loop {
+                let signal = self.recv().await;
+                if signal == Signal::Interrupt {
+                    warn!(
+                        component = "graceful-shutdown",
+                        event = "os_signal",
+                        signal = signal.label(),
+                        "second interrupt, stopping without finishing the shutdown"
+                    );
+                    force();
+                    return;
+                }
+                info!(
+                    component = "graceful-shutdown",
+                    event = "os_signal",
+                    signal = signal.label(),
+                    "shutdown already in progress, signal ignored"
+                );
+            }
+        })
+    }
+}
+
+/// Action run on a signal.
+pub type Action = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync>;
+
+/// Actions of the SIGUSR1, SIGUSR2 and SIGHUP handlers.
+#[derive(Clone)]
+pub struct Actions {
+    pub dump: Action,
+    pub reopen: Action,
+    pub reload: Action,
+}
+
+/// Runs the actions on SIGUSR1, SIGUSR2 and SIGHUP until `stop` is cancelled. Signals arriving while an
+/// action runs are coalesced into one run after it; cancelling `stop` drops a running action.
+#[cfg(unix)]
+pub fn spawn_user_signals(stop: CancellationToken, actions: Actions) -> io::Result<JoinHandle<()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut dump = signal(SignalKind::user_defined1())?;
+    let mut reopen = signal(SignalKind::user_defined2())?;
+    let mut reload = signal(SignalKind::hangup())?;
+    Ok(tokio::spawn(async move {
+        loop {
+            let (signal, action) = tokio::select! {
+                _ = stop.cancelled() => return,
+                _ = dump.recv() => (Signal::Dump, actions.dump.clone()),
+                _ = reopen.recv() => (Signal::Reopen, actions.reopen.clone()),
+                _ = reload.recv() => (Signal::Reload, actions.reload.clone()),
+            };
+            metrics::inc_process_signals(signal);
+            info!(component = "signals", event = "os_signal", signal = signal.label(), "signal received");
+
+            let result = tokio::select! {
+                _ = stop.cancelled() => return,
+                result = action() => result,
+            };
+            match result {
+                Ok(()) => info!(component = "signals", event = "signal_handled", signal = signal.label(), "signal handled"),
+                Err(e) => error!(
+                    component = "signals",
+                    event = "signal_failed",
+                    signal = signal.label(),
+                    error = %format!("{:#}", e),
+                    "signal handler failed"
+                ),
+            }
+        }
+    }))
+}
+
+/// SIGUSR1, SIGUSR2 and SIGHUP do not exist on this platform.
+#[cfg(not(unix))]
+pub fn spawn_user_signals(stop: CancellationToken, _actions: Actions) -> io::Result<JoinHandle<()>> {
+    Ok(tokio::spawn(async move { stop.cancelled().await }))
+}
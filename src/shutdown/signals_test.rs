@@ -0,0 +1,132 @@
+//! Tests for the OS signal handlers, raising the signals in the test process.
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::controller::metrics;
+    use crate::shutdown::signals::{spawn_user_signals, Actions, ShutdownSignals, Signal};
+    use crate::shutdown::GracefulShutdown;
+
+    fn raise(signal: libc::c_int) {
+        assert_eq!(unsafe { libc::raise(signal) }, 0);
+    }
+
+    async fn eventually(what: &str, cond: impl Fn() -> bool) {
+        for _ in 0..200 {
+            if cond() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("{} did not happen within 2s", what);
+    }
+
+    /// Test that SIGTERM starts the shutdown and is recorded, that a second SIGTERM is ignored
+    /// and that a second SIGINT forces the stop.
+    #[tokio::test]
+    async fn test_shutdown_signals() {
+        // Keeps the default action, stopping the test process, from running in any case.
+        let _installed = ShutdownSignals::new().unwrap();
+        let terms = metrics::process_signals(Signal::Terminate);
+
+        let gsh = Arc::new(GracefulShutdown::new(CancellationToken::new()));
+        let waiting = tokio::spawn({
+            let gsh = gsh.clone();
+            async move { gsh.await_signal().await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        raise(libc::SIGTERM);
+        let signals = tokio::time::timeout(Duration::from_secs(2), waiting)
+            .await
+            .expect("SIGTERM must start the shutdown")
+            .unwrap()
+            .expect("a signal started the shutdown");
+        assert_eq!(gsh.report().signal.as_deref(), Some("SIGTERM"));
+        assert!(metrics::process_signals(Signal::Terminate) > terms);
+
+        let forced = Arc::new(AtomicUsize::new(0));
+        let watcher = signals.spawn_force_on_interrupt({
+            let forced = forced.clone();
+            move || {
+                forced.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        raise(libc::SIGTERM);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(forced.load(Ordering::SeqCst), 0, "a second SIGTERM is ignored");
+
+        raise(libc::SIGINT);
+        tokio::time::timeout(Duration::from_secs(2), watcher).await.expect("SIGINT must force the stop").unwrap();
+        assert_eq!(forced.load(Ordering::SeqCst), 1);
+    }
+
+    /// Test that the shutdown started from within is recorded without a signal.
+    #[tokio::test]
+    async fn test_token_starts_shutdown_without_signal() {
+        let token = CancellationToken::new();
+        let gsh = GracefulShutdown::new(token.clone());
+        token.cancel();
+        assert!(gsh.await_signal().await.is_none());
+        assert_eq!(gsh.report().signal, None);
+        assert!(!gsh.report().started_at.is_empty());
+    }
+
+    /// Test that SIGUSR1 runs the dump, SIGUSR2 the reopen and SIGHUP the reload, each once per
+    /// signal, a failing action does not stop the handler, and nothing runs once it is stopped.
+    #[tokio::test]
+    async fn test_user_signals_run_actions() {
+        let dumps = Arc::new(AtomicUsize::new(0));
+        let reopens = Arc::new(AtomicUsize::new(0));
+        let reloads = Arc::new(AtomicUsize::new(0));
+        let actions = Actions {
+            dump: Arc::new({
+                let dumps = dumps.clone();
+                move || {
+                    dumps.fetch_add(1, Ordering::SeqCst);
+                    Box::pin(async { Ok(()) })
+                }
+            }),
+            reopen: Arc::new({
+                let reopens = reopens.clone();
+                move || {
+                    reopens.fetch_add(1, Ordering::SeqCst);
+                    Box::pin(async { Err(anyhow::anyhow!("logs.path is not set")) })
+                }
+            }),
+            reload: Arc::new({
+                let reloads = reloads.clone();
+                move || {
+                    reloads.fetch_add(1, Ordering::SeqCst);
+                    Box::pin(async { Ok(()) })
+                }
+            }),
+        };
+        let counted = metrics::process_signals(Signal::Dump);
+        let stop = CancellationToken::new();
+        let handler = spawn_user_signals(stop.clone(), actions).unwrap();
+
+        raise(libc::SIGUSR2);
+        eventually("the reopen", || reopens.load(Ordering::SeqCst) == 1).await;
+        raise(libc::SIGUSR1);
+        eventually("the dump", || dumps.load(Ordering::SeqCst) == 1).await;
+        raise(libc::SIGUSR2);
+        eventually("the second reopen", || reopens.load(Ordering::SeqCst) == 2).await;
+        raise(libc::SIGHUP);
+        eventually("the reload", || reloads.load(Ordering::SeqCst) == 1).await;
+        assert_eq!(dumps.load(Ordering::SeqCst), 1);
+        assert!(metrics::process_signals(Signal::Dump) > counted);
+        assert!(metrics::process_signals(Signal::Reload) > 0);
+
+        stop.cancel();
+        tokio::time::timeout(Duration::from_secs(2), handler).await.expect("the handler must stop").unwrap();
+        raise(libc::SIGUSR1);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(dumps.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,159 @@
+// Integration tests for `backend.accept_encoding`.
+//
+// A real backend points at a local origin that records the Accept-Encoding it receives and
+// either gzips its answer when asked to or ignores the header and answers identity.
+
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use axum::http::{HeaderMap, Request, StatusCode, Uri};
+use axum::response::IntoResponse;
+use axum::Router;
+
+use crate::db::Storage;
+use crate::tests::support::router::serve_origin;
+use crate::tests::support::RouterCache;
+use crate::upstream::{BackendImpl, Upstream};
+
+const PATH: &str = "/api/v1/user";
+const BODY: &[u8] = b"{\"user\":\"compressed\"}";
+const REQUESTED: &str = "gzip, br";
+
+type Seen = Arc<Mutex<Vec<String>>>;
+
+/// Origin recording the Accept-Encoding of every non-healthcheck request; it gzips the answer when asked to,
+/// unless `honours` is false, in which case it always answers identity.
+async fn start_origin(honours: bool) -> (String, Seen) {
+    let seen: Seen = Arc::default();
+    let recorded = seen.clone();
+    let app = Router::new().fallback(move |uri: Uri, headers: HeaderMap| {
+        let accept = headers
+            .get("accept-encoding")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if uri.path() != "/healthz" {
+            recorded.lock().unwrap().push(accept.clone());
+        }
+        async move {
+            if honours && accept.contains("gzip") {
+                let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                enc.write_all(BODY).unwrap();
+                (StatusCode::OK, [("content-encoding", "gzip")], enc.finish().unwrap()).into_response()
+            } else {
+                (StatusCode::OK, BODY.to_vec()).into_response()
+            }
+        }
+    });
+    (serve_origin(app).await, seen)
+}
+
+fn start(origin: &str) -> RouterCache<BackendImpl> {
+    RouterCache::builder()
+        .origin(origin)
+        .config(|cfg| {
+            let backend = cfg.cache.upstream.as_mut().unwrap().backend.as_mut().unwrap();
+            backend.accept_encoding = Some(REQUESTED.to_string());
+        })
+        .start_backend()
+}
+
+/// Sends a GET and returns the status, Content-Encoding and raw body.
+async fn get(
+    cache: &RouterCache<BackendImpl>,
+    uri: &str,
+    accept_encoding: Option<&str>,
+) -> (StatusCode, Option<String>, Vec<u8>) {
+    let mut req = Request::get(uri);
+    if let Some(ae) = accept_encoding {
+        req = req.header("accept-encoding", ae);
+    }
+    let resp = cache.send(req.body(Body::empty()).unwrap()).await;
+    let status = resp.status();
+    let coding = resp
+        .headers()
+        .get("content-encoding")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, coding, body.to_vec())
+}
+
+fn gunzip(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    flate2::read::GzDecoder::new(data).read_to_end(&mut out).unwrap();
+    out
+}
+
+/// Test that a client accepting gzip gets the origin's gzip answer stored and served encoded.
+#[tokio::test]
+async fn test_fill_stores_encoded_for_accepting_client() {
+    let (origin, seen) = start_origin(true).await;
+    let cache = start(&origin);
+
+    let uri = format!("{}?user[id]=1", PATH);
+    for _ in 0..2 {
+        let (status, coding, body) = get(&cache, &uri, Some("gzip")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(coding.as_deref(), Some("gzip"));
+        assert_eq!(gunzip(&body), BODY);
+    }
+    cache.shutdown.cancel();
+    assert_eq!(*seen.lock().unwrap(), vec![REQUESTED.to_string()], "the second request is a hit");
+}
+
+/// Test that a client without Accept-Encoding gets an identity body although the origin gzipped,
+/// and that refreshes ask the origin for the configured codings too.
+#[tokio::test]
+async fn test_fill_stores_decoded_for_client_without_accept_encoding() {
+    let (origin, seen) = start_origin(true).await;
+    let cache = start(&origin);
+
+    let (status, coding, body) = get(&cache, &format!("{}?user[id]=2", PATH), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(coding, None);
+    assert_eq!(body, BODY);
+
+    let stored = cache.stored(PATH, &[("user[id]", "2")]).expect("entry must be cached after the fill");
+    let refreshed = cache.upstream.refresh(&stored).await.expect("refresh must succeed");
+    let refreshed = refreshed.modified().expect("a new response");
+    assert!(cache.db.set(refreshed));
+    let (_, coding, body) = get(&cache, &format!("{}?user[id]=2", PATH), None).await;
+    cache.shutdown.cancel();
+
+    assert_eq!(coding, None);
+    assert_eq!(body, BODY);
+    assert_eq!(*seen.lock().unwrap(), vec![REQUESTED.to_string(); 2]);
+}
+
+/// Test that an origin ignoring Accept-Encoding is cached as identity whatever the client accepts.
+#[tokio::test]
+async fn test_origin_ignoring_accept_encoding() {
+    let (origin, seen) = start_origin(false).await;
+    let cache = start(&origin);
+
+    let (status, coding, body) = get(&cache, &format!("{}?user[id]=3", PATH), Some("gzip")).await;
+    cache.shutdown.cancel();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(coding, None);
+    assert_eq!(body, BODY);
+    assert_eq!(*seen.lock().unwrap(), vec![REQUESTED.to_string()]);
+}
+
+/// Test that proxied requests keep the client's Accept-Encoding.
+#[tokio::test]
+async fn test_proxy_request_keeps_client_accept_encoding() {
+    let (origin, seen) = start_origin(false).await;
+    let cache = start(&origin);
+
+    let headers = vec![("Accept-Encoding".to_string(), "identity".to_string())];
+    let resp = cache
+        .upstream
+        .proxy_request("GET", "/api/v1/uncached", "", &headers, None)
+        .await
+        .expect("proxy request must succeed");
+    cache.shutdown.cancel();
+    assert_eq!(resp.status, 200);
+    assert_eq!(*seen.lock().unwrap(), vec!["identity".to_string()]);
+}
@@ -1,7 +1,7 @@
 // Integration tests for administrative API endpoints.
 
 use std::collections::HashMap;
-use crate::tests::support::{assert_equal, assert_ok, cache_addr, do_json, init_test_harness, H};
+use crate::tests::support::{assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, H};
 
 #[derive(serde::Deserialize)]
 struct AdmissionResponse {
@@ -30,7 +30,7 @@ struct ClearStatusResponse {
 /// Test that admission control endpoints work correctly.
 #[tokio::test]
 async fn test_admission_control_endpoints() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
 
@@ -61,7 +61,7 @@ async fn test_admission_control_endpoints() {
 /// Test that compression endpoints work correctly.
 #[tokio::test]
 async fn test_compression_endpoints() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
 
@@ -92,7 +92,7 @@ async fn test_compression_endpoints() {
 /// Test that config endpoint returns valid JSON.
 #[tokio::test]
 async fn test_config_endpoint() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
 
@@ -113,7 +113,7 @@ async fn test_config_endpoint() {
 /// Test that clear endpoint token mechanism works.
 #[tokio::test]
 async fn test_clear_endpoint_token_mechanism() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
 
@@ -139,12 +139,50 @@ async fn test_clear_endpoint_token_mechanism() {
     let clear_resp: ClearStatusResponse = serde_json::from_slice(&body).unwrap();
     assert!(clear_resp.cleared.unwrap_or(false), "cache should be cleared");
     assert!(clear_resp.error.is_none(), "should not have error");
+
+    // A token is single use: a retry of the same clear is refused
+    let (status, _, body, _) = assert_ok(
+        do_json::<ClearStatusResponse>("GET", &url, &H::new()).await,
+    );
+    assert_equal(403, status);
+    let clear_resp: ClearStatusResponse = serde_json::from_slice(&body).unwrap();
+    assert!(clear_resp.cleared.is_none(), "a used token must not clear again");
+    assert!(clear_resp.error.is_some(), "should report why the token was refused");
+}
+
+/// Test that a clear token is valid for the default 60s and is only accepted from the
+/// credentials it was issued to.
+#[tokio::test]
+async fn test_clear_token_bound_to_credentials() {
+    init_test_harness(Capability::Http).await.unwrap();
+
+    let base = cache_addr().await;
+    let mut admin = H::new();
+    admin.insert("Authorization".to_string(), "Bearer admin".to_string());
+    let mut other = H::new();
+    other.insert("Authorization".to_string(), "Bearer other".to_string());
+
+    let requested_at = chrono::Utc::now().timestamp_millis();
+    let (status, _, body, _) = assert_ok(
+        do_json::<TokenResponse>("GET", &format!("{}/advcache/clear", base), &admin).await,
+    );
+    assert_equal(200, status);
+    let token_resp: TokenResponse = serde_json::from_slice(&body).unwrap();
+    let ttl = token_resp.expires_at - requested_at;
+    assert!((55_000..=65_000).contains(&ttl), "token should be valid for about 60s, got {}ms", ttl);
+
+    // Used with other credentials, the token is refused and used up
+    let url = format!("{}/advcache/clear?token={}", base, urlencoding::encode(&token_resp.token));
+    let (status, _, _, _) = assert_ok(do_json::<ClearStatusResponse>("GET", &url, &other).await);
+    assert_equal(403, status);
+    let (status, _, _, _) = assert_ok(do_json::<ClearStatusResponse>("GET", &url, &admin).await);
+    assert_equal(403, status);
 }
 
 /// Test that upstream policy endpoints work correctly.
 #[tokio::test]
 async fn test_upstream_policy_endpoints() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
 
@@ -173,9 +211,10 @@ async fn test_upstream_policy_endpoints() {
 }
 
 /// Test that traces endpoints work correctly.
+#[cfg(feature = "tracing-otel")]
 #[tokio::test]
 async fn test_traces_endpoints() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
 
@@ -201,32 +240,35 @@ async fn test_traces_endpoints() {
     let _resp: serde_json::Value = serde_json::from_slice(&body).unwrap();
 }
 
-/// Test that metrics endpoint returns valid response.
+/// Test that metrics are served by the main listener, including recorder and cache counters.
 #[tokio::test]
 async fn test_metrics_endpoint() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
 
-    // Metrics endpoint may not be fully initialized in test environment
-    // Just verify the endpoint exists and returns a response
-    let result = crate::support::do_request("GET", &format!("{}/metrics", base), &H::new(), None).await;
-    
-    // Should either return 200 or some valid status code (not connection error)
-    if let Ok(resp) = result {
-        let status = resp.status().as_u16();
-        // Accept any 2xx or 5xx status (metrics may not be initialized)
-        assert!(status >= 200 && status < 600, "metrics endpoint should return valid status, got: {}", status);
-    } else {
-        // If connection fails, that's also acceptable for metrics in test environment
-        // Metrics endpoint initialization may require special setup
+    let resp = assert_ok(crate::support::do_request("GET", &format!("{}/metrics", base), &H::new(), None).await);
+    assert_equal(200, resp.status().as_u16());
+
+    let body = resp.text().await.unwrap();
+    assert!(body.contains("# TYPE cache_hits counter"), "missing cache counters: {body}");
+}
+
+/// Test that installing the recorder again is a no-op and never binds or panics.
+#[tokio::test]
+async fn test_metrics_recorder_init_is_idempotent() {
+    if !init_test_harness(Capability::InProcess).await.unwrap() {
+        return;
     }
+
+    assert!(crate::metrics_runtime::init_metrics().is_ok());
+    assert!(crate::metrics_runtime::scrape_prometheus_text().is_some());
 }
 
 /// Test that get entry endpoint works correctly.
 #[tokio::test]
 async fn test_get_entry_endpoint() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
 
@@ -248,3 +290,29 @@ async fn test_get_entry_endpoint() {
     );
     assert_equal(404, status);
 }
+
+/// Test that the shards endpoint lists the shards waiting longest for their lock, with the
+/// totals of all shards; nothing is sampled without `storage.lock_profiling`.
+#[tokio::test]
+async fn test_shards_endpoint() {
+    init_test_harness(Capability::Http).await.unwrap();
+
+    let base = cache_addr().await;
+
+    let (status, _, body, _) = assert_ok(
+        do_json::<serde_json::Value>("GET", &format!("{}/advcache/shards?top=4", base), &H::new()).await,
+    );
+    assert_equal(200, status);
+    let shards: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(shards["lock_profiling"].is_null());
+    assert_eq!(shards["shards"], 1024);
+    assert_eq!(shards["top"].as_array().unwrap().len(), 4);
+    assert_eq!(shards["lock"]["write"]["samples"], 0);
+    assert_eq!(shards["lock"]["slow_writes"], 0);
+
+    let (_, _, body, _) = assert_ok(
+        do_json::<serde_json::Value>("GET", &format!("{}/advcache/shards?top=0", base), &H::new()).await,
+    );
+    let shards: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(shards["top"].as_array().unwrap().len(), 1024);
+}
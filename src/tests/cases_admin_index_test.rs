@@ -0,0 +1,167 @@
+// Integration tests for the admin index (`GET /advcache/`).
+//
+// The controllers are those the server registers, on an in-process router whose routes
+// answer with the path they matched instead of running their handler, so that the index
+// is checked against what axum routes without side effects.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::{MatchedPath, Request};
+use axum::http::{Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::IntoResponse;
+use axum::Router;
+use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
+
+use crate::app::server::HttpServer;
+use crate::config::reload::Reloader;
+use crate::config::{self, Config, MetricsAuth};
+use crate::db::DB;
+use crate::governor::Orchestrator;
+use crate::http::Controller;
+use crate::liveness;
+use crate::upstream::testing::MockUpstream;
+
+struct Admin {
+    /// Every controller the server registers, the index included.
+    controllers: Vec<Box<dyn Controller>>,
+    /// Routes of the controllers answering with the path they matched.
+    router: Router,
+    shutdown: CancellationToken,
+}
+
+impl Admin {
+    fn start(cfg: Config) -> Self {
+        let shutdown = CancellationToken::new();
+        let upstream = MockUpstream::new();
+        let governor = Arc::new(Orchestrator::new());
+        let db = DB::new(shutdown.clone(), cfg.clone(), governor.clone(), upstream.clone()).expect("storage must start");
+        let probe = Arc::new(liveness::Probe::new(Duration::from_secs(1))) as Arc<dyn liveness::Prober>;
+        let reloader = Arc::new(Reloader::new(cfg.clone()));
+        let controllers = HttpServer::controllers(shutdown.clone(), &cfg, db, upstream, governor, probe, None, reloader);
+
+        let router = controllers
+            .iter()
+            .fold(Router::new(), |router, controller| controller.add_route(router))
+            .layer(middleware::from_fn(|req: Request, _next: Next| async move {
+                match req.extensions().get::<MatchedPath>() {
+                    Some(matched) => matched.as_str().to_string().into_response(),
+                    None => StatusCode::NOT_FOUND.into_response(),
+                }
+            }));
+        Self { controllers, router, shutdown }
+    }
+
+    /// (method, path) of every route the controllers register.
+    fn registered(&self, hidden: bool) -> BTreeSet<(String, String)> {
+        self.controllers
+            .iter()
+            .flat_map(|controller| controller.describe())
+            .filter(|route| hidden || !route.hidden)
+            .map(|route| (route.method.to_string(), route.path))
+            .collect()
+    }
+
+    /// Endpoints listed by the index.
+    async fn index(&self) -> Vec<serde_json::Value> {
+        let index = self.controllers.last().expect("the index is registered last");
+        let router = index.add_route(Router::new());
+        let resp = router.oneshot(Request::get("/advcache/").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        json["endpoints"].as_array().expect("endpoints must be a list").clone()
+    }
+
+    /// Path the router matched for the request.
+    async fn matched(&self, method: &str, path: &str) -> String {
+        // Path params are filled in, as a client would.
+        let uri = path.replace(":backend_id", "origin");
+        let req = Request::builder().method(Method::from_bytes(method.as_bytes()).unwrap()).uri(uri).body(Body::empty()).unwrap();
+        let resp = self.router.clone().oneshot(req).await.unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+}
+
+impl Drop for Admin {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+fn listed(endpoints: &[serde_json::Value]) -> BTreeSet<(String, String)> {
+    endpoints
+        .iter()
+        .map(|e| (e["method"].as_str().unwrap().to_string(), e["path"].as_str().unwrap().to_string()))
+        .collect()
+}
+
+fn endpoint<'a>(endpoints: &'a [serde_json::Value], path: &str) -> &'a serde_json::Value {
+    endpoints.iter().find(|e| e["path"] == path).unwrap_or_else(|| panic!("{} must be indexed", path))
+}
+
+#[tokio::test]
+async fn test_index_matches_the_registered_routes() {
+    let admin = Admin::start(config::new_test_config());
+    let endpoints = admin.index().await;
+
+    // Every registered route but the hidden ones is indexed, and nothing else.
+    assert_eq!(listed(&endpoints), admin.registered(false));
+
+    // Every indexed endpoint is routed to itself, not to the cache catch-all.
+    for (method, path) in listed(&endpoints) {
+        assert_eq!(admin.matched(&method, &path).await, path, "{} {} must be registered", method, path);
+    }
+}
+
+#[tokio::test]
+async fn test_index_describes_endpoints() {
+    let admin = Admin::start(config::new_test_config());
+    let endpoints = admin.index().await;
+
+    let clear = endpoint(&endpoints, "/advcache/clear");
+    assert_eq!(clear["method"], "GET");
+    assert_eq!(clear["destructive"], true);
+    assert_eq!(clear["auth"], false);
+    assert!(!clear["description"].as_str().unwrap().is_empty());
+    assert_eq!(endpoint(&endpoints, "/advcache/invalidate")["destructive"], true);
+    assert_eq!(endpoint(&endpoints, "/advcache/walks")["destructive"], false);
+    assert_eq!(endpoint(&endpoints, "/advcache/shards")["destructive"], false);
+    assert_eq!(endpoint(&endpoints, "/advcache/")["method"], "GET");
+
+    let rollout: Vec<_> = endpoints.iter().filter(|e| e["path"] == "/advcache/rollout").map(|e| &e["method"]).collect();
+    assert_eq!(rollout, ["GET", "POST"]);
+}
+
+#[tokio::test]
+async fn test_hidden_routes_are_indexed_in_debug_only() {
+    let endpoints = Admin::start(config::new_test_config()).index().await;
+    assert!(endpoints.iter().all(|e| e["path"] != "/cache/bypass" && e["path"] != "/*path"));
+
+    let mut cfg = config::new_test_config();
+    cfg.cache.env = config::DEBUG.to_string();
+    let admin = Admin::start(cfg);
+    let endpoints = admin.index().await;
+    assert_eq!(listed(&endpoints), admin.registered(true));
+    endpoint(&endpoints, "/cache/bypass");
+    endpoint(&endpoints, "/*path");
+}
+
+#[tokio::test]
+async fn test_metrics_auth_is_indexed() {
+    let endpoints = Admin::start(config::new_test_config()).index().await;
+    assert_eq!(endpoint(&endpoints, "/metrics")["auth"], false);
+
+    let mut cfg = config::new_test_config();
+    cfg.cache.metrics.as_mut().unwrap().auth = Some(MetricsAuth {
+        username: "prometheus".to_string(),
+        password_env: "ADVCACHE_TEST_INDEX_METRICS_PASSWORD".to_string(),
+    });
+    let endpoints = Admin::start(cfg).index().await;
+    assert_eq!(endpoint(&endpoints, "/metrics")["auth"], true);
+}
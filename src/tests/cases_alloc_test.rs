@@ -0,0 +1,80 @@
+// Allocation budget of the request hot path.
+//
+// Requests go through the cache over a mock upstream on a current-thread runtime, so
+// everything a request allocates happens on the counting thread. Each figure is the minimum
+// over a few rounds, which filters out one-off allocations of lazily initialized state.
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+
+use crate::support::alloc::count_allocs;
+use crate::tests::support::RouterCache;
+
+const ROUNDS: usize = 16;
+/// Allocations per request. Run alone, a hit made 56 and a miss 75 before header vectors were
+/// borrowed, against 35 and 58 now, the ETag and diagnostic headers included; the budgets leave
+/// room for the handful the global metrics and tracing state installed by other tests adds.
+const HIT_BUDGET: usize = 45;
+const MISS_BUDGET: usize = 68;
+
+fn start(rt: &Runtime) -> RouterCache {
+    let _rt = rt.enter();
+    RouterCache::start()
+}
+
+/// A request carrying `extra` headers outside the key whitelist.
+fn request(query: &str, extra: usize) -> Request<Body> {
+    let mut request = Request::get(format!("/api/v1/user?{}", query))
+        .header("host", "cache.local")
+        .header("accept-encoding", "gzip");
+    for i in 0..extra {
+        request = request.header(format!("x-extra-{}", i), format!("value-{}", i));
+    }
+    request.body(Body::empty()).unwrap()
+}
+
+/// Fewest allocations of serving `request(query, extra)` over the rounds.
+fn min_allocs(rt: &Runtime, router: &Router, query: impl Fn(usize) -> String, extra: usize) -> usize {
+    (0..ROUNDS)
+        .map(|round| {
+            let (router, request) = (router.clone(), request(&query(round), extra));
+            let (status, allocs) = count_allocs(|| rt.block_on(async { router.oneshot(request).await.unwrap().status() }));
+            assert_eq!(status, StatusCode::OK);
+            allocs
+        })
+        .min()
+        .unwrap()
+}
+
+/// Test that a hit allocates within budget and that headers outside the key whitelist do not
+/// cost allocations.
+#[test]
+fn test_hit_path_allocation_budget() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let cache = start(&rt);
+
+    let query = |_| "user[id]=1&domain=example.com&language=en".to_string();
+    rt.block_on(cache.send(request(&query(0), 0)));
+
+    let few = min_allocs(&rt, &cache.router, query, 0);
+    let many = min_allocs(&rt, &cache.router, query, 32);
+    println!("hit allocations: {} (+32 headers: {})", few, many);
+    assert_eq!(few, many, "a hit must not allocate per request header");
+    assert!(few <= HIT_BUDGET, "a hit made {} allocations", few);
+}
+
+/// Test that a miss allocates within budget and not per request header either.
+#[test]
+fn test_miss_path_allocation_budget() {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let cache = start(&rt);
+
+    let few = min_allocs(&rt, &cache.router, |round| format!("user[id]=plain-{}", round), 0);
+    let many = min_allocs(&rt, &cache.router, |round| format!("user[id]=extra-{}", round), 32);
+    println!("miss allocations: {} (+32 headers: {})", few, many);
+    assert_eq!(few, many, "a miss must not allocate per request header");
+    assert!(few <= MISS_BUDGET, "a miss made {} allocations", few);
+}
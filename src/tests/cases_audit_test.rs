@@ -0,0 +1,191 @@
+// Integration tests for the audit log of admin mutations (`audit`, `GET /advcache/audit`).
+//
+// Admin controllers run on an in-process router behind the audit middleware, writing to a
+// log of their own in a temp dir, so the tests read back exactly the calls they made.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
+
+use crate::audit::{AuditLog, AuditRecord};
+use crate::config::{self, MockSpec};
+use crate::controller::{AdmissionController, AuditController, ClearController, InvalidateController};
+use crate::db::mock::mock_entry;
+use crate::db::{Storage, DB};
+use crate::governor::Orchestrator;
+use crate::http::{Controller, Middleware};
+use crate::middleware::audit_middleware::AuditMiddleware;
+use crate::model::match_cache_rule;
+use crate::upstream::testing::MockUpstream;
+
+const PATH: &str = "/api/v1/user";
+const AUTHORIZATION: &str = "Bearer admin-token";
+
+struct Admin {
+    router: Router,
+    path: PathBuf,
+    dir: PathBuf,
+    shutdown: CancellationToken,
+}
+
+impl Admin {
+    /// Admin controllers behind the audit middleware, over a storage holding `entries` of PATH.
+    fn start(name: &str, entries: usize) -> Self {
+        let dir = std::env::temp_dir().join(format!("advcache-audit-cases-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.log");
+
+        let cfg = config::new_test_config();
+        let shutdown = CancellationToken::new();
+        let db = DB::new(shutdown.clone(), cfg.clone(), Arc::new(Orchestrator::new()), MockUpstream::new())
+            .expect("storage must start");
+        let rule = match_cache_rule(&cfg, PATH.as_bytes()).unwrap();
+        let spec = MockSpec { path: PATH.to_string(), count: entries, body_size: None, compressible: true, query_cardinality: None };
+        for i in 0..entries {
+            db.set(mock_entry(rule.clone(), &spec, i));
+        }
+
+        let log = Arc::new(AuditLog::open(&path, 1 << 20, 3).unwrap());
+        let controllers: Vec<Box<dyn Controller>> = vec![
+            Box::new(AdmissionController::new(cfg.clone())),
+            Box::new(ClearController::new(cfg.clone(), db.clone())),
+            Box::new(InvalidateController::new(cfg, db)),
+            Box::new(AuditController::new(Some(log.clone()))),
+        ];
+        let router = controllers.iter().fold(Router::new(), |router, controller| controller.add_route(router));
+        let router = AuditMiddleware::new(log, &controllers).apply(router);
+        Self { router, path, dir, shutdown }
+    }
+
+    /// Calls `uri` from `addr`, with the admin credentials when `auth` is set.
+    async fn call(&self, uri: &str, addr: &str, auth: bool) -> (StatusCode, serde_json::Value) {
+        let mut req = Request::get(uri);
+        if auth {
+            req = req.header("authorization", AUTHORIZATION);
+        }
+        let mut req = req.body(Body::empty()).unwrap();
+        req.extensions_mut().insert(ConnectInfo(addr.parse::<SocketAddr>().unwrap()));
+        let resp = self.router.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    /// Records in the log file.
+    fn file(&self) -> Vec<AuditRecord> {
+        std::fs::read_to_string(&self.path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn raw_file(&self) -> String {
+        std::fs::read_to_string(&self.path).unwrap()
+    }
+}
+
+impl Drop for Admin {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Test that mutations are recorded with their endpoint, sanitized params, principal, result
+/// and affected entries, that reads are not, and that the tail endpoint serves them.
+#[tokio::test]
+async fn test_mutations_are_audited() {
+    let admin = Admin::start("mutations", 20);
+
+    // A read and a toggle from an address.
+    assert_eq!(admin.call("/advcache/admission", "10.0.0.7:4000", false).await.0, StatusCode::OK);
+    assert_eq!(admin.call("/advcache/admission/on", "10.0.0.7:4000", false).await.0, StatusCode::OK);
+
+    // An invalidation marking every entry, with credentials.
+    let (status, body) = admin.call(&format!("/advcache/invalidate?_path={}", PATH), "10.0.0.8:4000", true).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["affected"], 20);
+
+    // A refused clear, then a clear with a token of its own.
+    let (status, _) = admin.call("/advcache/clear?token=forged", "10.0.0.8:4000", true).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (_, body) = admin.call("/advcache/clear", "10.0.0.8:4000", true).await;
+    let token = body["token"].as_str().unwrap().to_string();
+    let (status, body) = admin.call(&format!("/advcache/clear?token={}", token), "10.0.0.8:4000", true).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["items"], 20);
+
+    // The tail endpoint waits for the records queued before it.
+    let (status, tail) = admin.call("/advcache/audit?limit=100", "10.0.0.7:4000", false).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(tail["count"], 5);
+    assert_eq!(tail["chained"], true);
+    let records: Vec<AuditRecord> = serde_json::from_value(tail["records"].clone()).unwrap();
+    assert_eq!(records, admin.file(), "the endpoint serves the file");
+
+    let endpoints: Vec<&str> = records.iter().map(|r| r.endpoint.as_str()).collect();
+    assert_eq!(
+        endpoints,
+        vec!["/advcache/admission/on", "/advcache/invalidate", "/advcache/clear", "/advcache/clear", "/advcache/clear"]
+    );
+    assert_eq!(records.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+
+    let toggle = &records[0];
+    assert_eq!(toggle.principal, "addr:10.0.0.7");
+    assert_eq!((toggle.status, toggle.result.as_str(), toggle.affected), (200, "ok", None));
+    assert!(toggle.timestamp.ends_with('Z'), "{}", toggle.timestamp);
+
+    let invalidation = &records[1];
+    assert!(invalidation.principal.starts_with("credentials:"), "{}", invalidation.principal);
+    assert_eq!(invalidation.params["_path"], PATH);
+    assert_eq!(invalidation.affected, Some(20));
+
+    let refused = &records[2];
+    assert_eq!((refused.status, refused.result.as_str()), (403, "refused"));
+    assert_eq!(refused.params["token"], "<redacted>");
+
+    let issued = &records[3];
+    assert!(issued.params.is_empty());
+    assert_eq!(issued.affected, None);
+
+    let cleared = &records[4];
+    assert_eq!((cleared.status, cleared.affected), (200, Some(20)));
+    assert_eq!(cleared.params["token"], "<redacted>");
+    assert!(!admin.raw_file().contains(&token), "clear tokens must not reach the audit log");
+}
+
+/// Test that `limit` takes the most recent records and that a wrong one is refused.
+#[tokio::test]
+async fn test_tail_limit() {
+    let admin = Admin::start("limit", 0);
+    for _ in 0..3 {
+        admin.call("/advcache/admission/on", "10.0.0.7:4000", false).await;
+        admin.call("/advcache/admission/off", "10.0.0.7:4000", false).await;
+    }
+
+    let (status, tail) = admin.call("/advcache/audit?limit=2", "10.0.0.7:4000", false).await;
+    assert_eq!(status, StatusCode::OK);
+    let records: Vec<AuditRecord> = serde_json::from_value(tail["records"].clone()).unwrap();
+    assert_eq!(records.iter().map(|r| r.seq).collect::<Vec<_>>(), vec![5, 6]);
+    assert_eq!(records[1].endpoint, "/advcache/admission/off");
+    assert_eq!(admin.file().len(), 6);
+
+    assert_eq!(admin.call("/advcache/audit?limit=many", "10.0.0.7:4000", false).await.0, StatusCode::BAD_REQUEST);
+}
+
+/// Test that the endpoint answers 404 while the audit log is off.
+#[tokio::test]
+async fn test_disabled() {
+    let router = AuditController::new(None).add_route(Router::new());
+    let resp = router.oneshot(Request::get("/advcache/audit").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
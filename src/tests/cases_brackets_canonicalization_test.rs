@@ -1,7 +1,7 @@
 // Integration tests for bracket canonicalization.
 
 use crate::support::{
-    assert_equal, assert_ok, cache_addr, do_json, init_test_harness, new_namespace, H,
+    assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, new_namespace, H,
 };
 use sha1::{Digest, Sha1};
 
@@ -13,7 +13,7 @@ fn qhash(b: &[u8]) -> String {
 
 #[tokio::test]
 async fn test_brackets_literal_vs_encoded_same_body() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Brackets_LiteralVsEncoded_SameBody");
     let base = cache_addr().await;
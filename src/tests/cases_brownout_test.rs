@@ -0,0 +1,193 @@
+// Integration tests for the brownout (`cache.brownout`), which sheds cache misses with 429
+// while the upstream path is saturated and keeps serving hits.
+//
+// The mock upstream is slow and reports itself saturated once two calls are in flight. Ticks of
+// the metrics writer are driven by hand, so the tests choose the instants the signals are
+// evaluated at.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::response::Response;
+use tower::ServiceExt;
+
+use crate::config::{self, Config};
+use crate::controller::brownout::Signals;
+use crate::controller::cache_metrics::Snapshot;
+use crate::controller::BrownoutController;
+use crate::db::Storage;
+use crate::support::RouterCache;
+use crate::upstream::testing::MockUpstream;
+use crate::upstream::Upstream;
+
+const PATH: &str = "/api/v1/user";
+const CRITICAL_PATH: &str = "/api/v1/buyer";
+
+fn brownout_config(cfg: &mut Config) {
+    cfg.cache.brownout = Some(config::Brownout {
+        enabled: true,
+        upstream_saturated_for: Some(Duration::from_secs(2)),
+        max_refresh_backlog: None,
+        max_error_rate: None,
+        recover_after: Some(Duration::from_secs(10)),
+        retry_after: Some(Duration::from_secs(7)),
+    });
+    let rules = cfg.cache.rules.as_mut().unwrap();
+    let mut critical = (*rules[CRITICAL_PATH]).clone();
+    critical.shed_on_brownout = Some(false);
+    rules.insert(CRITICAL_PATH.to_string(), Arc::new(critical));
+}
+
+/// Cache over a slow mock upstream reporting itself saturated at two calls in flight.
+fn start(change: impl FnOnce(&mut Config)) -> RouterCache {
+    RouterCache::builder()
+        .config(change)
+        .upstream(MockUpstream::builder().latency(Duration::from_millis(50)).concurrency(2).build())
+        .start()
+        .mount(|cache| BrownoutController::new(cache.brownout.clone()))
+}
+
+/// One tick of the metrics writer at `now`.
+fn tick(cache: &RouterCache, now: Instant) -> bool {
+    let signals = Signals::collect(
+        cache.brownout.config(),
+        &Snapshot::default(),
+        cache.db.as_ref() as &dyn Storage,
+        cache.upstream.as_ref(),
+    );
+    cache.brownout.observe(&signals, now)
+}
+
+/// Keeps two slow misses in flight, saturating the upstream until the returned handles complete.
+async fn saturate(cache: &RouterCache) -> Vec<tokio::task::JoinHandle<Response>> {
+    cache.upstream.set_latency(Duration::from_millis(500));
+    let handles: Vec<_> = (0..2)
+        .map(|i| {
+            let router = cache.router.clone();
+            let uri = format!("{}?user[id]=slow-{}", PATH, i);
+            tokio::spawn(async move { router.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap() })
+        })
+        .collect();
+    while cache.upstream.inflight() < 2 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    handles
+}
+
+fn assert_shed(resp: &Response) {
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resp.headers()[header::RETRY_AFTER], "7");
+    assert_eq!(resp.headers()["x-error-reason"], "brownout");
+    assert_eq!(resp.headers()["x-ratelimit-reason"], "brownout");
+}
+
+/// Test that a saturated upstream engages the brownout once it lasts `upstream_saturated_for`:
+/// misses are shed, hits and misses of exempt rules are served, and the brownout disengages
+/// after `recover_after` without saturation.
+#[tokio::test]
+async fn test_saturation_sheds_misses_and_serves_hits() {
+    let cache = start(brownout_config);
+    let hit_uri = format!("{}?user[id]=warm", PATH);
+    assert_eq!(cache.get(&hit_uri).await.status(), StatusCode::OK);
+
+    let in_flight = saturate(&cache).await;
+    let t0 = Instant::now();
+    assert!(!tick(&cache, t0), "saturation must last upstream_saturated_for first");
+    assert!(tick(&cache, t0 + Duration::from_secs(2)));
+    let status = cache.brownout.status();
+    assert_eq!(status.breached[0].condition, "upstream_saturated");
+
+    let calls = cache.upstream.fills();
+    assert_eq!(cache.get(&hit_uri).await.status(), StatusCode::OK, "hits keep being served");
+    assert_shed(&cache.get(&format!("{}?user[id]=cold", PATH)).await);
+    assert_eq!(cache.upstream.fills(), calls, "a shed miss must not reach the origin");
+    let resp = cache.get(&format!("{}?user[id]=cold", CRITICAL_PATH)).await;
+    assert_eq!(resp.status(), StatusCode::OK, "shed_on_brownout: false rules are exempt");
+
+    for handle in in_flight {
+        assert_eq!(handle.await.unwrap().status(), StatusCode::OK);
+    }
+    assert!(!cache.upstream.is_saturated());
+
+    // Hysteresis: the brownout holds until no signal was breached for recover_after.
+    assert!(tick(&cache, t0 + Duration::from_secs(3)));
+    assert!(tick(&cache, t0 + Duration::from_secs(12)));
+    assert!(!tick(&cache, t0 + Duration::from_secs(13)));
+    assert_eq!(cache.get(&format!("{}?user[id]=cold", PATH)).await.status(), StatusCode::OK);
+}
+
+/// Test that a saturation run shorter than `upstream_saturated_for` does not engage.
+#[tokio::test]
+async fn test_short_saturation_does_not_engage() {
+    let cache = start(brownout_config);
+
+    let in_flight = saturate(&cache).await;
+    let t0 = Instant::now();
+    assert!(!tick(&cache, t0));
+    for handle in in_flight {
+        handle.await.unwrap();
+    }
+    assert!(!tick(&cache, t0 + Duration::from_secs(1)));
+    assert!(!tick(&cache, t0 + Duration::from_secs(5)), "the run ended before it lasted long enough");
+}
+
+/// Test that the admin endpoint forces the brownout on and off for drills, without config.
+#[tokio::test]
+async fn test_admin_endpoint_forces_brownout() {
+    let cache = start(|_| {});
+    let miss = |id: &str| format!("{}?user[id]={}", PATH, id);
+
+    let status: serde_json::Value = json(cache.get("/advcache/brownout/on").await).await;
+    assert_eq!(status["mode"], "on");
+    assert_eq!(status["active"], true);
+    let resp = cache.get(&miss("drill-1")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resp.headers()[header::RETRY_AFTER], "5");
+
+    // Ticks do not undo a forced mode.
+    assert!(tick(&cache, Instant::now()));
+
+    let status = json(cache.get("/advcache/brownout/auto").await).await;
+    assert_eq!((status["mode"].as_str(), status["active"].as_bool()), (Some("auto"), Some(false)));
+    assert_eq!(cache.get(&miss("drill-2")).await.status(), StatusCode::OK);
+
+    let status = json(cache.get("/advcache/brownout").await).await;
+    assert_eq!(status["engaged"], false);
+}
+
+/// Test that forcing the brownout off keeps misses flowing while the signals call for shedding.
+#[tokio::test]
+async fn test_forced_off_overrides_signals() {
+    let cache = start(brownout_config);
+    cache.get("/advcache/brownout/off").await;
+
+    let in_flight = saturate(&cache).await;
+    let t0 = Instant::now();
+    tick(&cache, t0);
+    assert!(!tick(&cache, t0 + Duration::from_secs(2)));
+    assert!(cache.brownout.status().engaged);
+    for handle in in_flight {
+        handle.await.unwrap();
+    }
+    assert_eq!(cache.get(&format!("{}?user[id]=cold", PATH)).await.status(), StatusCode::OK);
+}
+
+/// Test that an enabled brownout without any signal fails to load.
+#[test]
+fn test_brownout_requires_a_signal() {
+    let yaml = r#"
+cache:
+  env: test
+  enabled: true
+  brownout:
+    enabled: true
+"#;
+    let err = config::Config::from_yaml(yaml).unwrap_err();
+    assert!(format!("{:#}", err).contains("brownout.enabled requires"), "{:#}", err);
+}
+
+async fn json(resp: Response) -> serde_json::Value {
+    serde_json::from_slice(&axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap()).unwrap()
+}
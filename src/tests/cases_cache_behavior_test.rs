@@ -2,12 +2,12 @@
 
 
 use std::collections::HashMap;
-use crate::support::{assert_equal, assert_ok, cache_addr, do_json, init_test_harness, new_namespace, with_ns, H};
+use crate::support::{assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, new_namespace, with_ns, H};
 
 /// Test that cache stores entries with different query parameter orders as same key.
 #[tokio::test]
 async fn test_cache_query_order_independence() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Cache_QueryOrder");
     let base = cache_addr().await;
@@ -38,7 +38,7 @@ async fn test_cache_query_order_independence() {
 /// Test that cache respects Accept-Encoding in cache key.
 #[tokio::test]
 async fn test_cache_respects_accept_encoding_in_key() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Cache_AcceptEncoding");
     let base = cache_addr().await;
@@ -88,7 +88,7 @@ async fn test_cache_respects_accept_encoding_in_key() {
 /// Test that cache handles large response bodies correctly.
 #[tokio::test]
 async fn test_cache_large_response_body() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Cache_LargeBody");
     let base = cache_addr().await;
@@ -125,7 +125,7 @@ async fn test_cache_large_response_body() {
 /// Verifies cache persistence and that entries don't disappear unexpectedly.
 #[tokio::test]
 async fn test_cache_persistence_over_time() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Cache_Persistence");
     let base = cache_addr().await;
@@ -162,7 +162,7 @@ async fn test_cache_persistence_over_time() {
 /// Test that cache handles requests with no query parameters.
 #[tokio::test]
 async fn test_cache_no_query_parameters() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Cache_NoQuery");
     let base = cache_addr().await;
@@ -190,7 +190,7 @@ async fn test_cache_no_query_parameters() {
 /// Test that cache handles multiple cache hits correctly.
 #[tokio::test]
 async fn test_cache_multiple_hits() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Cache_MultipleHits");
     let base = cache_addr().await;
@@ -224,7 +224,7 @@ async fn test_cache_multiple_hits() {
 /// Test that cache handles requests with special characters in query values.
 #[tokio::test]
 async fn test_cache_special_characters_in_query() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Cache_SpecialChars");
     let base = cache_addr().await;
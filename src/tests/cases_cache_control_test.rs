@@ -0,0 +1,80 @@
+// Integration tests for rules following the origin's `Cache-Control` and `Expires`
+// (`cache_value.respect_cache_control`).
+//
+// The following rule is a copy of the user rule under a path of its own, next to the user rule
+// left as configured.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::http::StatusCode;
+
+use crate::tests::support::RouterCache;
+use crate::upstream::Response;
+
+const IGNORING_PATH: &str = "/api/v1/user";
+const FOLLOWING_PATH: &str = "/api/v1/origin-ttl";
+
+fn start() -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| {
+            let rules = cfg.cache.rules.as_mut().unwrap();
+            let mut rule = (*rules[IGNORING_PATH]).clone();
+            rule.path = Some(FOLLOWING_PATH.to_string());
+            rule.path_bytes = Some(FOLLOWING_PATH.as_bytes().to_vec());
+            rule.cache_value.respect_cache_control = Some(true);
+            rules.insert(FOLLOWING_PATH.to_string(), Arc::new(rule));
+        })
+        .start()
+}
+
+/// Requests user `id` under `path`, answered by the origin with `headers`, twice.
+async fn get_twice(cache: &RouterCache, path: &str, id: u32, headers: &[(&str, &str)]) {
+    let mut resp = Response::ok("{}");
+    for (name, value) in headers {
+        resp = resp.with_header(*name, *value);
+    }
+    cache.upstream.set_response(path, resp);
+    for _ in 0..2 {
+        let resp = cache.get(&format!("{}?user[id]={}", path, id)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let _ = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    }
+}
+
+/// Test that a `no-store` or `private` answer is not stored by a following rule, every request
+/// reaching the origin, while the rule ignoring them stores it as usual.
+#[tokio::test]
+async fn test_no_store_is_not_stored() {
+    let cache = start();
+
+    get_twice(&cache, FOLLOWING_PATH, 1, &[("Cache-Control", "no-store")]).await;
+    get_twice(&cache, FOLLOWING_PATH, 2, &[("Cache-Control", "private, max-age=60")]).await;
+    assert_eq!(cache.upstream.fills(), 4);
+    assert!(cache.stored(FOLLOWING_PATH, &[("user[id]", "1")]).is_none());
+    assert!(cache.stored(FOLLOWING_PATH, &[("user[id]", "2")]).is_none());
+
+    get_twice(&cache, IGNORING_PATH, 1, &[("Cache-Control", "no-store")]).await;
+    assert_eq!(cache.upstream.fills(), 5);
+    assert!(cache.stored(IGNORING_PATH, &[("user[id]", "1")]).is_some());
+}
+
+/// Test that a following rule stores answers for the lifetime the origin gives them, from
+/// `max-age` or from `Expires` and `Date`, and for the rule's TTL without either.
+#[tokio::test]
+async fn test_origin_lifetime_replaces_rule_ttl() {
+    let cache = start();
+
+    get_twice(&cache, FOLLOWING_PATH, 1, &[("Cache-Control", "public, max-age=5")]).await;
+    let expires = [("Date", "Sat, 17 Oct 2026 10:00:00 GMT"), ("Expires", "Sat, 17 Oct 2026 10:02:00 GMT")];
+    get_twice(&cache, FOLLOWING_PATH, 2, &expires).await;
+    get_twice(&cache, FOLLOWING_PATH, 3, &[]).await;
+    get_twice(&cache, IGNORING_PATH, 1, &[("Cache-Control", "max-age=5")]).await;
+    assert_eq!(cache.upstream.fills(), 4, "stored answers are served from the cache");
+
+    let ttl = |path, id| cache.stored(path, &[("user[id]", id)]).expect("entry must be stored").origin_ttl();
+    assert_eq!(ttl(FOLLOWING_PATH, "1"), Some(Duration::from_secs(5)));
+    assert_eq!(ttl(FOLLOWING_PATH, "2"), Some(Duration::from_secs(120)));
+    assert_eq!(ttl(FOLLOWING_PATH, "3"), None);
+    assert_eq!(ttl(IGNORING_PATH, "1"), None);
+}
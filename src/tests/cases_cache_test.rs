@@ -2,12 +2,12 @@
 
 use std::collections::HashMap;
 use crate::support::{
-    assert_equal, assert_ok, cache_addr, do_json, init_test_harness, new_namespace, with_ns, H,
+    assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, new_namespace, with_ns, H,
 };
 
 #[tokio::test]
 async fn test_cache_hit_on_warm_identity() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Cache_HitOnWarmIdentity");
     let base = cache_addr().await;
@@ -37,7 +37,7 @@ async fn test_cache_hit_on_warm_identity() {
 
 #[tokio::test]
 async fn test_cache_vary_by_accept_encoding() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Cache_VaryByAcceptEncoding");
     let base = cache_addr().await;
@@ -70,7 +70,7 @@ async fn test_cache_vary_by_accept_encoding() {
 
 #[tokio::test]
 async fn test_proxy_propagates_5xx_no_pollution() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Proxy_Propagates_5xx_NoPollution");
     let base = cache_addr().await;
@@ -103,7 +103,7 @@ async fn test_proxy_propagates_5xx_no_pollution() {
 
 #[tokio::test]
 async fn test_concurrency_cold_key_storm() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Concurrency_ColdKeyStorm");
     let base = cache_addr().await;
@@ -142,7 +142,7 @@ async fn test_concurrency_cold_key_storm() {
 
 #[tokio::test]
 async fn test_cache_serve_stale_during_refresh_soft() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Cache_ServeStaleDuringRefresh_Soft");
     let base = cache_addr().await;
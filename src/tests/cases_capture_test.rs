@@ -0,0 +1,108 @@
+// Integration tests for the debug capture of a single key (`/advcache/debug/capture`).
+//
+// The lifetime manager runs, so invalidated entries are refreshed in the background.
+
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+
+use crate::config::TTLMode;
+use crate::controller::{DebugCaptureController, InvalidateController};
+use crate::tests::support::RouterCache;
+use crate::time;
+use crate::upstream::Response;
+
+const PATH: &str = "/api/v1/user";
+
+fn start() -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| {
+            if let Some(lifetime) = cfg.cache.lifetime.as_mut() {
+                lifetime.enabled = true;
+                lifetime.on_ttl = Some(TTLMode::Refresh);
+                lifetime.rate = Some(100);
+            }
+        })
+        .start()
+        .mount(|cache| InvalidateController::new(cache.cfg.clone(), cache.db.clone()))
+        .mount(|cache| DebugCaptureController::new(cache.cfg.clone()))
+}
+
+async fn start_capture(cache: &RouterCache, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+    let request = Request::post("/advcache/debug/capture").body(Body::from(body.to_string())).unwrap();
+    cache.send_json(request).await
+}
+
+/// `op: detail` of the events captured so far.
+async fn events(cache: &RouterCache, id: u64) -> Vec<String> {
+    let (status, body) = cache.get_json(&format!("/advcache/debug/capture/{}", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    body["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| format!("{}: {}", e["op"].as_str().unwrap(), e["detail"].as_str().unwrap()))
+        .collect()
+}
+
+/// Test that a key captured by its request is traced through a miss, a hit, an invalidation
+/// and the background refresh it triggers, while other keys are not.
+#[tokio::test]
+async fn test_captures_miss_hit_invalidate_refresh() {
+    let _clock = time::start(Duration::from_millis(1));
+    let cache = start();
+    let body = serde_json::json!({ "path": PATH, "params": { "user[id]": "1" }, "duration": "30s" });
+    let (status, capture) = start_capture(&cache, body).await;
+    assert_eq!(status, StatusCode::OK, "{}", capture);
+    let id = capture["id"].as_u64().unwrap();
+    assert_eq!(capture["active"], true);
+
+    cache.upstream.set_response(PATH, Response::ok("{\"version\":1}"));
+    for _ in 0..2 {
+        assert_eq!(cache.get_json(&format!("{}?user[id]=1", PATH)).await.0, StatusCode::OK);
+    }
+    assert_eq!(cache.get_json(&format!("{}?user[id]=2", PATH)).await.0, StatusCode::OK);
+
+    cache.upstream.set_response(PATH, Response::ok("{\"version\":2}"));
+    let (status, _) = cache.get_json(&format!("/advcache/invalidate?_path={}&user[id]=1", PATH)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let events = loop {
+        let events = events(&cache, id).await;
+        if events.iter().any(|e| e.starts_with("refresh_result")) || Instant::now() > deadline {
+            break events;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    assert_eq!(
+        events,
+        vec![
+            "get: miss",
+            "set: stored",
+            "get: hit",
+            "invalidate: marked outdated",
+            "refresh_pick: picked by the refresher",
+            "refresh_result: applied",
+        ]
+    );
+}
+
+/// Test that a capture is started by key too, that a key is watched once, and that bad
+/// requests and unknown captures are refused.
+#[tokio::test]
+async fn test_capture_requests() {
+    let cache = start();
+    let key = u64::MAX - 7;
+    let (status, capture) = start_capture(&cache, serde_json::json!({ "key": key })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(capture["key"], key);
+    assert!(capture["remaining_ms"].as_u64().unwrap() > 55_000, "60s by default");
+
+    assert_eq!(start_capture(&cache, serde_json::json!({ "key": key })).await.0, StatusCode::CONFLICT);
+    assert_eq!(start_capture(&cache, serde_json::json!({})).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(start_capture(&cache, serde_json::json!({ "key": 1, "duration": "1h" })).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(start_capture(&cache, serde_json::json!({ "path": "/no/rule" })).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(cache.get_json("/advcache/debug/capture/999999").await.0, StatusCode::NOT_FOUND);
+}
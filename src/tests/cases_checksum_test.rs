@@ -0,0 +1,77 @@
+// Integration tests for payload checksum verification (`storage.verify_sample`).
+//
+// Re-fills are counted on the mock upstream; corruption is simulated by flipping a bit of a
+// stored payload behind its checksum.
+
+use axum::http::StatusCode;
+
+use crate::config::Config;
+use crate::controller::metrics;
+use crate::tests::support::RouterCache;
+use crate::upstream::testing::MockUpstream;
+use crate::upstream::Response;
+
+const PATH: &str = "/api/v1/user";
+const BODY: &str = r#"{"user":{"id":7,"name":"a rather long cached body to flip a bit in"}}"#;
+
+fn start(verify_sample: Option<f64>) -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| cfg.cache.storage.as_mut().unwrap().verify_sample = verify_sample)
+        .upstream(MockUpstream::builder().respond(PATH, Response::ok(BODY)).build())
+        .start()
+}
+
+/// Test that a corrupted payload is detected on read, dropped, counted and re-filled.
+#[tokio::test]
+async fn test_corruption_is_detected_and_healed() {
+    let cache = start(Some(1.0));
+    let uri = format!("{}?user[id]=7", PATH);
+
+    assert_eq!(cache.get_text(&uri).await, (StatusCode::OK, BODY.to_string()));
+    let entry = cache.stored(PATH, &[("user[id]", "7")]).expect("filled entry must be stored");
+    assert!(entry.checksum().is_some());
+    assert!(entry.verify_checksum());
+
+    let corrupted_before = metrics::cache_entries_corrupted();
+    entry.corrupt_payload(entry.payload_bytes().len() - 3);
+    assert!(!entry.verify_checksum());
+
+    assert_eq!(cache.get_text(&uri).await, (StatusCode::OK, BODY.to_string()), "the corrupted body must not be served");
+    assert!(metrics::cache_entries_corrupted() > corrupted_before);
+    assert_eq!(cache.upstream.fills(), 2, "the dropped entry is re-filled from the origin");
+
+    let healed = cache.stored(PATH, &[("user[id]", "7")]).expect("re-filled entry must be stored");
+    assert!(healed.verify_checksum());
+    assert_eq!(cache.get_text(&uri).await, (StatusCode::OK, BODY.to_string()));
+    assert_eq!(cache.upstream.fills(), 2, "the healed entry is served from the cache");
+}
+
+/// Test that without `verify_sample` reads do not verify, even if payloads carry checksums.
+#[tokio::test]
+async fn test_disabled_verification_serves_as_stored() {
+    let cache = start(None);
+    let uri = format!("{}?user[id]=8", PATH);
+
+    assert_eq!(cache.get_text(&uri).await.0, StatusCode::OK);
+    let entry = cache.stored(PATH, &[("user[id]", "8")]).expect("filled entry must be stored");
+    entry.corrupt_payload(entry.payload_bytes().len() - 3);
+
+    let (status, body) = cache.get_text(&uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(body, BODY, "unverified reads serve the payload as stored");
+    assert_eq!(cache.upstream.fills(), 1);
+}
+
+/// Test that `verify_sample` outside (0, 1] is rejected.
+#[test]
+fn test_verify_sample_validation() {
+    let yaml = |sample: &str| {
+        format!(
+            "cache:\n  env: test\n  enabled: true\n  storage:\n    size: 1024\n    verify_sample: {}\n  upstream:\n    backend:\n      id: main\n      enabled: true\n      scheme: http\n      host: main.local:8080\n      timeout: 10s\n      max_timeout: 1m\n",
+            sample
+        )
+    };
+    assert!(Config::from_yaml(&yaml("0.01")).is_ok());
+    assert!(Config::from_yaml(&yaml("0")).is_err());
+    assert!(Config::from_yaml(&yaml("1.5")).is_err());
+}
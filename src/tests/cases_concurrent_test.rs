@@ -2,13 +2,23 @@
 
 
 use std::collections::HashMap;
-use crate::support::{assert_equal, assert_ok, cache_addr, do_json, init_test_harness, new_namespace, with_ns, H};
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use tower::ServiceExt;
+
+use crate::controller::metrics;
+use crate::tests::support::RouterCache;
+use crate::upstream::testing::MockUpstream;
+use crate::support::{assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, new_namespace, with_ns, H};
 
 /// Test that concurrent requests to the same cache key return consistent results.
 /// This verifies that cache handles concurrent access correctly without data races.
 #[tokio::test]
 async fn test_concurrent_requests_same_key() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Concurrent_SameKey");
     let base = cache_addr().await;
@@ -53,7 +63,7 @@ async fn test_concurrent_requests_same_key() {
 /// Verifies key isolation under concurrent load.
 #[tokio::test]
 async fn test_concurrent_requests_different_keys() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Concurrent_DifferentKeys");
     let base = cache_addr().await;
@@ -90,7 +100,7 @@ async fn test_concurrent_requests_different_keys() {
 /// Verifies that multiple concurrent cold requests don't cause issues.
 #[tokio::test]
 async fn test_concurrent_first_requests_misses() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Concurrent_CacheMisses");
     let base = cache_addr().await;
@@ -132,7 +142,7 @@ async fn test_concurrent_first_requests_misses() {
 /// Test that concurrent writes and reads don't cause data corruption.
 #[tokio::test]
 async fn test_concurrent_read_write() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Concurrent_ReadWrite");
     let base = cache_addr().await;
@@ -178,3 +188,74 @@ async fn test_concurrent_read_write() {
         assert_equal(200, status);
     }
 }
+
+/// In-process cache over a slow mock upstream, for misses that must overlap.
+fn collapsing_cache(latency: Duration) -> RouterCache {
+    RouterCache::builder().upstream(MockUpstream::builder().latency(latency).build()).start()
+}
+
+async fn get_status(router: Router, uri: &'static str) -> (StatusCode, Vec<u8>) {
+    let resp = router.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+    let status = resp.status();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, body.to_vec())
+}
+
+/// Test that concurrent misses of one key are answered by a single upstream fill, the
+/// requests that waited for it being counted as collapsed.
+#[tokio::test]
+async fn test_concurrent_misses_collapse_into_one_fill() {
+    let cache = collapsing_cache(Duration::from_millis(200));
+    let collapsed = metrics::collapsed_requests();
+
+    let handles: Vec<_> = (0..10)
+        .map(|_| tokio::spawn(get_status(cache.router.clone(), "/api/v1/user?user[id]=1001")))
+        .collect();
+    let mut bodies = Vec::new();
+    for handle in handles {
+        let (status, body) = handle.await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        bodies.push(body);
+    }
+
+    assert_eq!(cache.upstream.fills(), 1, "calls: {:?}", cache.upstream.calls());
+    assert!(bodies.windows(2).all(|w| w[0] == w[1]), "all requests get the filled answer");
+    assert!(metrics::collapsed_requests() >= collapsed + 9);
+}
+
+/// Test that a failed fill is shared with the requests waiting for it instead of each of them
+/// retrying the origin.
+#[tokio::test]
+async fn test_failed_fill_is_shared() {
+    let cache = collapsing_cache(Duration::from_millis(200));
+    cache.upstream.fail_next(1);
+
+    let handles: Vec<_> = (0..5)
+        .map(|_| tokio::spawn(get_status(cache.router.clone(), "/api/v1/user?user[id]=1002")))
+        .collect();
+    for handle in handles {
+        let (status, _) = handle.await.unwrap();
+        assert!(status.is_server_error(), "got {}", status);
+    }
+    assert_eq!(cache.upstream.fills(), 1);
+}
+
+/// Test that requests waiting for a fill whose client went away are woken and one of them
+/// fills the key instead.
+#[tokio::test]
+async fn test_cancelled_leader_wakes_followers() {
+    let cache = collapsing_cache(Duration::from_millis(300));
+    let uri = "/api/v1/user?user[id]=1003";
+
+    let leader = tokio::spawn(get_status(cache.router.clone(), uri));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let followers: Vec<_> = (0..5).map(|_| tokio::spawn(get_status(cache.router.clone(), uri))).collect();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    leader.abort();
+
+    for handle in followers {
+        let (status, _) = handle.await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+    }
+    assert_eq!(cache.upstream.fills(), 2, "the cancelled fill and the one retried by a follower");
+}
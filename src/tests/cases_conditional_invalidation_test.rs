@@ -0,0 +1,114 @@
+// Integration tests for `_if_refreshed_before` on `/advcache/invalidate`.
+//
+// A refresh is landed by storing a new payload for the key, as the lifetime manager does.
+
+use std::time::Duration;
+
+use axum::http::StatusCode;
+
+use crate::config::ConfigTrait;
+use crate::controller::InvalidateController;
+use crate::db::Storage;
+use crate::model::{Entry, Response};
+use crate::tests::support::RouterCache;
+use crate::time;
+
+const PATH: &str = "/api/v1/user";
+
+fn start() -> RouterCache {
+    RouterCache::start().mount(|cache| InvalidateController::new(cache.cfg.clone(), cache.db.clone()))
+}
+
+/// Lands a refresh of the key: a new payload stored over the entry.
+fn refresh(cache: &RouterCache, user_id: &str) {
+    let queries = vec![(b"user[id]".to_vec(), user_id.as_bytes().to_vec())];
+    let entry = Entry::new(cache.cfg.rule(PATH).unwrap(), &queries, &[]);
+    let resp = Response { status: 200, headers: vec![], body: b"refreshed".to_vec() };
+    entry.set_payload(&queries, &[], &resp);
+    assert!(cache.db.set(entry));
+}
+
+fn now_ms() -> i64 {
+    time::unix_nano() / 1_000_000
+}
+
+/// Test that an invalidation older than a refresh that raced ahead of it skips the entry,
+/// while one issued after the refresh invalidates it.
+#[tokio::test]
+async fn test_skips_entries_refreshed_after_the_change() {
+    let _clock = time::start(Duration::from_millis(1));
+    let cache = start();
+    let uri = format!("{}?user[id]=1", PATH);
+    assert_eq!(cache.get_json(&uri).await.0, StatusCode::OK);
+
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    let source_change = now_ms();
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    refresh(&cache, "1");
+    let refreshed_at = cache.stored(PATH, &[("user[id]", "1")]).unwrap().fresh_at();
+
+    let stale = format!("/advcache/invalidate?_path={}&user[id]=1&_if_refreshed_before={}", PATH, source_change);
+    let (status, body) = cache.get_json(&stale).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["affected"], 0);
+    assert_eq!(body["skipped_newer"], 1);
+    assert_eq!(cache.stored(PATH, &[("user[id]", "1")]).unwrap().fresh_at(), refreshed_at, "the refreshed entry is left as is");
+
+    // Retrying the same event is just as harmless.
+    assert_eq!(cache.get_json(&stale).await.1["skipped_newer"], 1);
+
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    let later = format!("/advcache/invalidate?_path={}&user[id]=1&_if_refreshed_before={}", PATH, now_ms());
+    let (_, body) = cache.get_json(&later).await;
+    assert_eq!(body["affected"], 1);
+    assert_eq!(body["skipped_newer"], 0);
+    assert!(cache.stored(PATH, &[("user[id]", "1")]).unwrap().fresh_at() < refreshed_at, "the entry is marked outdated");
+}
+
+/// Test that `_remove` honours the predicate per entry: only entries refreshed before it go.
+#[tokio::test]
+async fn test_remove_only_entries_refreshed_before() {
+    let _clock = time::start(Duration::from_millis(1));
+    let cache = start();
+    for id in ["1", "2"] {
+        assert_eq!(cache.get_json(&format!("{}?user[id]={}", PATH, id)).await.0, StatusCode::OK);
+    }
+
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    let source_change = now_ms();
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    refresh(&cache, "2");
+
+    let uri = format!("/advcache/invalidate?_path={}&_remove=1&_if_refreshed_before={}", PATH, source_change);
+    let (_, body) = cache.get_json(&uri).await;
+    assert_eq!(body["affected"], 1);
+    assert_eq!(body["skipped_newer"], 1);
+    assert!(cache.stored(PATH, &[("user[id]", "1")]).is_none());
+    assert!(cache.stored(PATH, &[("user[id]", "2")]).is_some());
+}
+
+/// Test that a malformed `_if_refreshed_before` is rejected.
+#[tokio::test]
+async fn test_rejects_malformed_timestamp() {
+    let cache = start();
+    let uri = format!("/advcache/invalidate?_path={}&_if_refreshed_before=yesterday", PATH);
+    assert_eq!(cache.get_json(&uri).await.0, StatusCode::BAD_REQUEST);
+}
+
+/// Test that an `_if_refreshed_before` from a client whose clock runs hours ahead is clamped to
+/// now and counted, and still invalidates the entries refreshed before it.
+#[tokio::test]
+async fn test_clamps_timestamp_ahead_of_the_clock() {
+    let _clock = time::start(Duration::from_millis(1));
+    let cache = start();
+    assert_eq!(cache.get_json(&format!("{}?user[id]=1", PATH)).await.0, StatusCode::OK);
+    tokio::time::sleep(Duration::from_millis(5)).await;
+
+    let clamped = crate::controller::metrics::entry_timestamps_clamped();
+    let ahead = now_ms() + 3 * 3600 * 1000;
+    let uri = format!("/advcache/invalidate?_path={}&user[id]=1&_if_refreshed_before={}", PATH, ahead);
+    let (status, body) = cache.get_json(&uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["affected"], 1);
+    assert!(crate::controller::metrics::entry_timestamps_clamped() > clamped);
+}
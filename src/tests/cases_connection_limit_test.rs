@@ -0,0 +1,156 @@
+// Integration tests for the connection limits of the HTTP listeners.
+//
+// A listener serves a router whose handler holds every request at a gate, so the number of
+// connections in flight is under the test's control; clients speak raw HTTP/1.1.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::routing::get;
+use axum::Router;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+use crate::controller::metrics;
+use crate::http::server::limit::{ConnectionLimit, Listener};
+use crate::http::server::server::{admin_router, serve};
+
+#[derive(Default)]
+struct Load {
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+/// Router whose `/slow` handler waits for the gate, recording how many requests are in flight.
+fn gated_router(gate: Arc<Semaphore>, load: Arc<Load>) -> Router {
+    Router::new().route(
+        "/slow",
+        get(move || {
+            let (gate, load) = (gate.clone(), load.clone());
+            async move {
+                let now = load.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                load.max_in_flight.fetch_max(now, Ordering::SeqCst);
+                let _pass = gate.acquire().await.unwrap();
+                load.in_flight.fetch_sub(1, Ordering::SeqCst);
+                "done"
+            }
+        }),
+    )
+}
+
+async fn start(router: Router, limit: ConnectionLimit) -> (String, CancellationToken) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let shutdown = CancellationToken::new();
+    tokio::spawn(serve(listener, router, Arc::new(limit), shutdown.clone()));
+    (addr, shutdown)
+}
+
+/// Sends a GET on a fresh connection and returns the whole raw answer.
+async fn raw_get(addr: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let req = format!("GET {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n", path);
+    stream.write_all(req.as_bytes()).await.unwrap();
+    let mut resp = Vec::new();
+    let _ = stream.read_to_end(&mut resp).await;
+    String::from_utf8_lossy(&resp).into_owned()
+}
+
+async fn wait_for(what: &str, cond: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if cond() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out waiting for {}", what);
+}
+
+/// Test that no more than `max_connections` are served at a time, the overflow waits for a
+/// slot and the rest is shed with 503 and Retry-After.
+#[tokio::test]
+async fn test_connection_cap_holds_and_sheds() {
+    let gate = Arc::new(Semaphore::new(0));
+    let load = Arc::new(Load::default());
+    let limit = ConnectionLimit::new(Listener::Api, Some(2), 1, Some(Duration::from_secs(3)));
+    let (addr, shutdown) = start(gated_router(gate.clone(), load.clone()), limit).await;
+
+    let mut clients = Vec::new();
+    for _ in 0..3 {
+        let addr = addr.clone();
+        clients.push(tokio::spawn(async move { raw_get(&addr, "/slow").await }));
+    }
+    wait_for("the served connections", || load.in_flight.load(Ordering::SeqCst) == 2).await;
+    assert_eq!(metrics::http_connections(Listener::Api), 2);
+
+    let mut shed = Vec::new();
+    for _ in 0..7 {
+        let addr = addr.clone();
+        shed.push(tokio::spawn(async move { raw_get(&addr, "/slow").await }));
+    }
+    for client in shed {
+        let resp = tokio::time::timeout(Duration::from_secs(5), client).await.unwrap().unwrap();
+        assert!(resp.starts_with("HTTP/1.1 503"), "unexpected answer: {}", resp);
+        assert!(resp.contains("Retry-After: 3\r\n"));
+    }
+    assert_eq!(load.in_flight.load(Ordering::SeqCst), 2, "shed connections never reach the router");
+
+    gate.add_permits(100);
+    for client in clients {
+        let resp = client.await.unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"), "unexpected answer: {}", resp);
+    }
+    shutdown.cancel();
+    assert_eq!(load.max_in_flight.load(Ordering::SeqCst), 2);
+    wait_for("the gauge to drop", || metrics::http_connections(Listener::Api) == 0).await;
+}
+
+/// Test that without shedding connections beyond the limit wait in the accept backlog and are
+/// served once a slot frees up.
+#[tokio::test]
+async fn test_backpressure_without_shedding() {
+    let gate = Arc::new(Semaphore::new(0));
+    let load = Arc::new(Load::default());
+    let limit = ConnectionLimit::new(Listener::Admin, Some(1), 0, None);
+    let (addr, shutdown) = start(gated_router(gate.clone(), load.clone()), limit).await;
+
+    let mut clients = Vec::new();
+    for _ in 0..4 {
+        let addr = addr.clone();
+        clients.push(tokio::spawn(async move { raw_get(&addr, "/slow").await }));
+    }
+    wait_for("the first connection", || load.in_flight.load(Ordering::SeqCst) == 1).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(load.in_flight.load(Ordering::SeqCst), 1);
+
+    gate.add_permits(100);
+    for client in clients {
+        let resp = tokio::time::timeout(Duration::from_secs(5), client).await.unwrap().unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"), "unexpected answer: {}", resp);
+    }
+    shutdown.cancel();
+    assert_eq!(load.max_in_flight.load(Ordering::SeqCst), 1);
+}
+
+/// Test that the admin listener only serves the admin, probe and metrics endpoints, custom
+/// probe paths included.
+#[tokio::test]
+async fn test_admin_router_serves_admin_paths_only() {
+    let router = Router::new()
+        .route("/advcache/bypass", get(|| async { "bypass" }))
+        .route("/k8s/probe", get(|| async { "probe" }))
+        .route("/live", get(|| async { "live" }))
+        .route("/*path", get(|| async { "cached" }));
+    let limit = ConnectionLimit::new(Listener::Admin, None, 0, None);
+    let (addr, shutdown) = start(admin_router(router, vec!["/live".to_string()]), limit).await;
+
+    assert!(raw_get(&addr, "/advcache/bypass").await.starts_with("HTTP/1.1 200"));
+    assert!(raw_get(&addr, "/k8s/probe").await.starts_with("HTTP/1.1 200"));
+    assert!(raw_get(&addr, "/live").await.starts_with("HTTP/1.1 200"));
+    assert!(raw_get(&addr, "/lively").await.starts_with("HTTP/1.1 404"));
+    assert!(raw_get(&addr, "/api/v1/user").await.starts_with("HTTP/1.1 404"));
+    shutdown.cancel();
+}
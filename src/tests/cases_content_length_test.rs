@@ -0,0 +1,193 @@
+// Integration tests for response framing through the middleware stack.
+//
+// A stored identity entry is served through the same middlewares as the real server and read
+// back with a raw HTTP/1.1 client over one keep-alive connection, so a Content-Length that
+// does not match the bytes on the wire shows up as a short read or a hang.
+
+use std::io::Read;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::routing::get;
+use axum::Router;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::config::{Compression, Rule};
+use crate::http::render::renderer;
+use crate::http::utils::cache_status::{Diagnostics, XCache};
+use crate::middleware::compression_middleware::CompressionMiddleware;
+use crate::middleware::middleware::Middleware;
+use crate::middleware::recover_middleware::PanicRecoverMiddleware;
+use crate::model::{Entry, Response as ModelResponse};
+
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn stored_body() -> Vec<u8> {
+    let items: Vec<String> = (0..64).map(|i| format!("{{\"id\":{i},\"name\":\"user-{i}\"}}")).collect();
+    format!("[{}]", items.join(",")).into_bytes()
+}
+
+/// Entry stored with the identity representation and its Content-Length, as the origin sent it.
+fn stored_entry() -> Entry {
+    let body = stored_body();
+    let entry = Entry::new(Arc::new(Rule::bare("/api/v1/user")), &[], &[]);
+    entry.set_payload(
+        &[],
+        &[],
+        &ModelResponse {
+            status: 200,
+            headers: vec![
+                ("content-type".to_string(), "application/json".to_string()),
+                ("content-length".to_string(), body.len().to_string()),
+            ],
+            body,
+        },
+    );
+    entry
+}
+
+/// Serves the entry behind the server middlewares, applied in the same order as the app does.
+async fn serve_entry() -> String {
+    let entry = Arc::new(stored_entry());
+    let router = Router::new().route(
+        "/entry",
+        get(move || {
+            let entry = entry.clone();
+            async move { renderer::write_from_entry(&entry, &[], Diagnostics { cache: XCache::Hit, key: None }).unwrap() }
+        }),
+    );
+
+    let middlewares: Vec<Box<dyn Middleware>> = vec![
+        Box::new(PanicRecoverMiddleware::new()),
+        Box::new(CompressionMiddleware::new(Some(Compression {
+            enabled: true,
+            level: Some(1),
+        }))),
+    ];
+    let router = middlewares.iter().rev().fold(router, |router, m| m.apply(router));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    addr
+}
+
+struct RawResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// Body as framed on the wire (chunked transfer already decoded).
+    body: Vec<u8>,
+}
+
+impl RawResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Sends one request on the connection and reads exactly one response using its framing.
+async fn round_trip(conn: &mut BufReader<TcpStream>, accept_encoding: &str) -> RawResponse {
+    let request = format!(
+        "GET /entry HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: {}\r\n\r\n",
+        accept_encoding
+    );
+    conn.get_mut().write_all(request.as_bytes()).await.unwrap();
+
+    tokio::time::timeout(IO_TIMEOUT, read_response(conn))
+        .await
+        .expect("response was not delimited correctly (client would hang)")
+}
+
+async fn read_line(conn: &mut BufReader<TcpStream>) -> String {
+    let mut line = String::new();
+    conn.read_line(&mut line).await.unwrap();
+    line.trim_end_matches("\r\n").to_string()
+}
+
+async fn read_response(conn: &mut BufReader<TcpStream>) -> RawResponse {
+    let status_line = read_line(conn).await;
+    let status = status_line.split(' ').nth(1).unwrap().parse().unwrap();
+
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(conn).await;
+        if line.is_empty() {
+            break;
+        }
+        let (k, v) = line.split_once(':').unwrap();
+        headers.push((k.trim().to_string(), v.trim().to_string()));
+    }
+    let mut resp = RawResponse {
+        status,
+        headers,
+        body: Vec::new(),
+    };
+
+    if let Some(len) = resp.header("content-length") {
+        assert!(resp.header("transfer-encoding").is_none(), "both framings present");
+        let mut body = vec![0u8; len.parse().unwrap()];
+        conn.read_exact(&mut body).await.unwrap();
+        resp.body = body;
+    } else {
+        assert_eq!(resp.header("transfer-encoding"), Some("chunked"));
+        loop {
+            let size = usize::from_str_radix(&read_line(conn).await, 16).unwrap();
+            let mut chunk = vec![0u8; size + 2];
+            conn.read_exact(&mut chunk).await.unwrap();
+            if size == 0 {
+                break;
+            }
+            resp.body.extend_from_slice(&chunk[..size]);
+        }
+    }
+
+    resp
+}
+
+fn gunzip(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    flate2::read::GzDecoder::new(data).read_to_end(&mut out).unwrap();
+    out
+}
+
+/// Test that a gzip-compressed stored entry carries no stale identity Content-Length.
+#[tokio::test]
+async fn test_gzip_entry_framing_matches_wire() {
+    let addr = serve_entry().await;
+    let mut conn = BufReader::new(TcpStream::connect(&addr).await.unwrap());
+
+    let resp = round_trip(&mut conn, "gzip").await;
+    assert_eq!(resp.status, 200);
+    assert_eq!(resp.header("content-encoding"), Some("gzip"));
+    if let Some(len) = resp.header("content-length") {
+        assert_eq!(len, resp.body.len().to_string());
+    }
+    assert_ne!(resp.header("content-length"), Some(stored_body().len().to_string().as_str()));
+    assert_eq!(gunzip(&resp.body), stored_body());
+}
+
+/// Test that compressed and identity responses can follow each other on one keep-alive connection.
+#[tokio::test]
+async fn test_keep_alive_mixed_encodings() {
+    let addr = serve_entry().await;
+    let mut conn = BufReader::new(TcpStream::connect(&addr).await.unwrap());
+
+    for accept_encoding in ["gzip", "identity", "gzip", "identity"] {
+        let resp = round_trip(&mut conn, accept_encoding).await;
+        assert_eq!(resp.status, 200);
+
+        if accept_encoding == "gzip" {
+            assert_eq!(gunzip(&resp.body), stored_body());
+        } else {
+            assert!(resp.header("content-encoding").is_none());
+            assert_eq!(resp.header("content-length"), Some(stored_body().len().to_string().as_str()));
+            assert_eq!(resp.body, stored_body());
+        }
+    }
+}
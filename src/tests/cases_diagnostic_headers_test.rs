@@ -0,0 +1,81 @@
+// Integration tests for the diagnostic response headers (`X-Cache`, `Age`, `X-Cache-Key`).
+//
+// `Age` is read off the cached clock, so the tests keep it ticking.
+
+use std::time::Duration;
+
+use axum::http::{HeaderMap, StatusCode};
+
+use crate::tests::support::RouterCache;
+use crate::time;
+use crate::upstream::testing::{Call, MockUpstream};
+use crate::upstream::Response;
+
+const USER_URI: &str = "/api/v1/user?user[id]=1";
+
+fn start(debug_headers: bool) -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| cfg.cache.api.as_mut().unwrap().debug_headers = Some(debug_headers))
+        .upstream(MockUpstream::builder().handle("/api/v1/user", |_: &Call| Response::ok("user")).build())
+        .start()
+}
+
+async fn get(cache: &RouterCache, uri: &str) -> (StatusCode, HeaderMap) {
+    let resp = cache.get(uri).await;
+    (resp.status(), resp.headers().clone())
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).map(|v| v.to_str().unwrap())
+}
+
+fn age(headers: &HeaderMap) -> u64 {
+    header(headers, "age").expect("age header").parse().expect("age in seconds")
+}
+
+/// Test that the first request is a MISS and the second a HIT, and that the `Age` of the hits
+/// grows with the time since the entry was stored.
+#[tokio::test]
+async fn test_miss_then_hit_with_growing_age() {
+    let _clock = time::start(Duration::from_millis(1));
+    let cache = start(false);
+
+    let (status, miss) = get(&cache, USER_URI).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(header(&miss, "x-cache"), Some("MISS"));
+    assert_eq!(age(&miss), 0);
+
+    let (_, first) = get(&cache, USER_URI).await;
+    assert_eq!(header(&first, "x-cache"), Some("HIT"));
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let (_, second) = get(&cache, USER_URI).await;
+    assert_eq!(header(&second, "x-cache"), Some("HIT"));
+    assert!(age(&second) > age(&first), "age {} then {}", age(&first), age(&second));
+    assert!(header(&second, "x-cache-key").is_none(), "the key is only sent with debug_headers");
+}
+
+/// Test that `api.debug_headers` adds the key of the entry, the same on the MISS and the HIT.
+#[tokio::test]
+async fn test_debug_headers_send_cache_key() {
+    let cache = start(true);
+
+    let (_, miss) = get(&cache, USER_URI).await;
+    let (_, hit) = get(&cache, USER_URI).await;
+    let key = header(&miss, "x-cache-key").expect("x-cache-key on the miss");
+    assert!(key.parse::<u64>().is_ok());
+    assert_eq!(header(&hit, "x-cache-key"), Some(key));
+
+    let (_, other) = get(&cache, "/api/v1/user?user[id]=2").await;
+    assert_ne!(header(&other, "x-cache-key"), Some(key), "another query is another entry");
+}
+
+/// Test that a request no rule matches is a BYPASS, without a key nor an `Age` of the cache.
+#[tokio::test]
+async fn test_unmatched_path_is_bypass() {
+    let cache = start(true);
+
+    let (_, headers) = get(&cache, "/not/ruled").await;
+    assert_eq!(header(&headers, "x-cache"), Some("BYPASS"));
+    assert!(header(&headers, "age").is_none());
+    assert!(header(&headers, "x-cache-key").is_none());
+}
@@ -0,0 +1,156 @@
+// Integration tests for draining the upstream backend (`/advcache/upstream/{id}/drain`).
+//
+// The backend points at a local origin that counts fills and health checks separately, so the
+// tests see what reaches the origin.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode, Uri};
+use axum::Router;
+
+use crate::config::overrides::RuntimeOverrides;
+use crate::controller::{metrics, BackendDrainController, ChangeBackendPolicyController};
+use crate::tests::support::router::{into_text, serve_origin};
+use crate::tests::support::RouterCache;
+use crate::upstream::{drain, BackendImpl, Upstream};
+
+const PATH: &str = "/api/v1/user";
+
+#[derive(Default)]
+struct OriginCalls {
+    fills: AtomicUsize,
+    health_checks: AtomicUsize,
+}
+
+async fn start_origin() -> (String, Arc<OriginCalls>) {
+    let calls = Arc::new(OriginCalls::default());
+    let counter = calls.clone();
+    let app = Router::new().fallback(move |uri: Uri| {
+        let counter = counter.clone();
+        async move {
+            if uri.path() == "/healthz" {
+                counter.health_checks.fetch_add(1, Ordering::Relaxed);
+            } else {
+                counter.fills.fetch_add(1, Ordering::Relaxed);
+            }
+            (StatusCode::OK, [("content-type", "application/json")], "{\"ok\":true}")
+        }
+    });
+    (serve_origin(app).await, calls)
+}
+
+fn overrides_file(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("advcache-overrides-{}-{}.yaml", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().into_owned()
+}
+
+/// Cache, drain and policy endpoints over a backend of its own id, as drain metrics are global;
+/// the drain routes share their prefix with the policy ones.
+fn start(id: &str, origin: &str, overrides: Option<&str>) -> RouterCache<BackendImpl> {
+    RouterCache::builder()
+        .origin(origin)
+        .config(|cfg| {
+            cfg.cache.upstream.as_mut().unwrap().backend.as_mut().unwrap().id = Some(id.to_string());
+            cfg.cache.runtime.as_mut().unwrap().overrides = overrides.map(str::to_string);
+        })
+        .start_backend()
+        .mount(|_| ChangeBackendPolicyController::new())
+        .mount(|cache| {
+            let overrides = cache.cfg.cache.runtime.as_ref().unwrap().overrides.clone();
+            BackendDrainController::new(cache.upstream.clone(), overrides)
+        })
+}
+
+async fn get(cache: &RouterCache<BackendImpl>, uri: &str) -> StatusCode {
+    cache.get(uri).await.status()
+}
+
+async fn post(cache: &RouterCache<BackendImpl>, uri: &str) -> (StatusCode, String) {
+    into_text(cache.send(Request::post(uri).body(Body::empty()).unwrap()).await).await
+}
+
+fn user(id: u32) -> String {
+    format!("{}?user[id]={}", PATH, id)
+}
+
+/// Test that a drained single backend gets no fills or refreshes, which fail fast, while cached
+/// entries are still served and health probes go on; undraining resumes fills.
+#[tokio::test]
+async fn test_drained_backend_fails_fills_fast_and_serves_hits() {
+    let (origin, calls) = start_origin().await;
+    let cache = start("drain-fills", &origin, None);
+
+    assert_eq!(get(&cache, &user(1)).await, StatusCode::OK);
+    assert_eq!(calls.fills.load(Ordering::Relaxed), 1);
+
+    let (status, body) = post(&cache, "/advcache/upstream/drain-fills/drain").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("\"drained\":true"), "{}", body);
+    assert_eq!(metrics::backend_drained("drain-fills"), Some(true));
+    let (_, status_body) = cache.get_text("/advcache/upstream/backends").await;
+    assert!(status_body.contains("\"drained\":true"), "{}", status_body);
+
+    assert_eq!(get(&cache, &user(1)).await, StatusCode::OK, "cached entries keep being served");
+    let started = Instant::now();
+    assert_eq!(get(&cache, &user(2)).await, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(started.elapsed() < Duration::from_secs(1), "a fill must fail fast");
+    let err = cache
+        .upstream
+        .request("GET", &cache.cfg.cache.rules.as_ref().unwrap()[PATH], &[], &[], None)
+        .await
+        .expect_err("fills must be refused");
+    assert!(format!("{:#}", err).contains("backend drain-fills is drained"), "{:#}", err);
+    assert_eq!(calls.fills.load(Ordering::Relaxed), 1, "nothing may reach a drained origin");
+
+    let health_checks = calls.health_checks.load(Ordering::Relaxed);
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    assert!(calls.health_checks.load(Ordering::Relaxed) > health_checks, "health probes must go on");
+    assert!(cache.upstream.is_alive());
+
+    let (status, body) = post(&cache, "/advcache/upstream/drain-fills/undrain").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("\"drained\":false"), "{}", body);
+    assert_eq!(get(&cache, &user(2)).await, StatusCode::OK);
+    assert_eq!(calls.fills.load(Ordering::Relaxed), 2);
+}
+
+/// Test that an unknown backend id is answered 404 and drains nothing.
+#[tokio::test]
+async fn test_drain_unknown_backend() {
+    let (origin, _) = start_origin().await;
+    let cache = start("drain-unknown", &origin, None);
+
+    let (status, _) = post(&cache, "/advcache/upstream/other/drain").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(get(&cache, "/advcache/upstream/policy").await, StatusCode::OK);
+    assert!(!cache.upstream.is_drained());
+}
+
+/// Test that the drain state is written to the runtime overrides file and restored from it by a
+/// backend started afterwards, as after a restart.
+#[tokio::test]
+async fn test_drain_survives_restart() {
+    let (origin, _) = start_origin().await;
+    let path = overrides_file("drain");
+    let cache = start("drain-restart", &origin, Some(&path));
+
+    let (status, _) = post(&cache, "/advcache/upstream/drain-restart/drain").await;
+    assert_eq!(status, StatusCode::OK);
+    let persisted = RuntimeOverrides::load(path.as_ref()).unwrap();
+    assert!(persisted.drained_backends.contains("drain-restart"));
+
+    let restarted = start("drain-restart", &origin, Some(&path));
+    assert!(!restarted.upstream.is_drained());
+    drain::restore(restarted.upstream.as_ref(), path.as_ref());
+    assert!(restarted.upstream.is_drained());
+
+    let (status, _) = post(&cache, "/advcache/upstream/drain-restart/undrain").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(RuntimeOverrides::load(path.as_ref()).unwrap().drained_backends.is_empty());
+    drop((cache, restarted));
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,95 @@
+// Integration tests for `--dry-run` (config check without starting the instance).
+//
+// Configs are written to temp files and loaded like the binary does; the tests assert the
+// exit code and what is printed.
+
+use std::path::PathBuf;
+
+use crate::app::dry_run;
+use crate::config::Config;
+
+/// Writes a config with the backend host and dump settings given; returns its path.
+fn write_config(name: &str, host: &str, dump: &str) -> PathBuf {
+    let yaml = format!(
+        "cache:\n  env: test\n  enabled: true\n  api:\n    port: \"8020\"\n  upstream:\n    backend:\n      id: main\n      enabled: true\n      scheme: http\n      host: \"{host}\"\n      timeout: 10s\n      max_timeout: 1m\n{dump}  storage:\n    size: 1073741824\n  eviction:\n    enabled: true\n    replicas: 2\n    soft_limit: 0.8\n    hard_limit: 0.99\n    check_interval: 100ms\n  rules:\n    /api/v1/user:\n      cache_key:\n        query: []\n        headers: []\n      cache_value:\n        headers: []\n"
+    );
+    let path = std::env::temp_dir().join(format!("advcache-dry-run-{}-{}.yaml", name, std::process::id()));
+    std::fs::write(&path, yaml).unwrap();
+    path
+}
+
+fn dump_section(dir: &str) -> String {
+    format!("  data:\n    dump:\n      enabled: true\n      dump_dir: \"{dir}\"\n      gzip: false\n      crc32_control_sum: true\n")
+}
+
+/// Runs the dry run on the config file; returns the exit code and what went to stdout and stderr.
+async fn dry_run(path: &PathBuf) -> (i32, String, String) {
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let code = dry_run::run(Config::load(path), &mut out, &mut err).await;
+    let _ = std::fs::remove_file(path);
+    (code, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap())
+}
+
+/// Test that a good config exits 0 and prints the summary of its setup.
+#[cfg(feature = "dump")]
+#[tokio::test]
+async fn test_good_config_exits_zero_with_summary() {
+    let dir = std::env::temp_dir().join(format!("advcache-dry-run-dump-{}", std::process::id()));
+    let path = write_config("good", "127.0.0.1:8080", &dump_section(&dir.join("new").to_string_lossy()));
+
+    let (code, out, err) = dry_run(&path).await;
+    assert_eq!(code, 0, "stderr: {}", err);
+    let summary: serde_json::Value = serde_json::from_str(&out).expect("the summary is JSON");
+    assert_eq!(summary["env"], "test");
+    assert_eq!(summary["listen"]["api"], "0.0.0.0:8020");
+    assert_eq!(summary["storage"]["size"], 1073741824);
+    assert_eq!(summary["rules"], serde_json::json!(["/api/v1/user"]));
+    assert_eq!(summary["backends"][0]["url"], "http://127.0.0.1:8080");
+    assert_eq!(summary["workers"]["evictor"], 2);
+    assert_eq!(summary["dump"], true);
+    assert!(summary["features"].as_array().unwrap().contains(&serde_json::json!("dump")));
+    assert!(!dir.exists(), "the dump dir is checked, not created");
+}
+
+/// Test that an invalid config, an unresolvable backend host and an unwritable dump dir each
+/// exit 1 and say why.
+#[tokio::test]
+async fn test_bad_configs_exit_one() {
+    let invalid = write_config("invalid", "127.0.0.1:8080", "");
+    let yaml = std::fs::read_to_string(&invalid).unwrap().replace("      cache_key:", "      methods: [PUT]\n      cache_key:");
+    std::fs::write(&invalid, yaml).unwrap();
+    let (code, out, err) = dry_run(&invalid).await;
+    assert_eq!(code, 1);
+    assert!(out.is_empty(), "no summary of an invalid config");
+    assert!(err.contains("config is invalid"), "stderr: {}", err);
+
+    let unresolvable = write_config("unresolvable", "no-such-backend.invalid:8080", "");
+    let (code, _, err) = dry_run(&unresolvable).await;
+    assert_eq!(code, 1);
+    assert!(err.contains("no-such-backend.invalid"), "stderr: {}", err);
+
+    #[cfg(feature = "dump")]
+    {
+        let file = std::env::temp_dir().join(format!("advcache-dry-run-file-{}", std::process::id()));
+        std::fs::write(&file, b"").unwrap();
+        let unwritable = write_config("unwritable", "127.0.0.1:8080", &dump_section(&file.join("dump").to_string_lossy()));
+        let (code, out, err) = dry_run(&unwritable).await;
+        let _ = std::fs::remove_file(&file);
+        assert_eq!(code, 1);
+        assert!(!out.is_empty(), "the summary is printed along with the problems");
+        assert!(err.contains("dump dir"), "stderr: {}", err);
+    }
+}
+
+/// Test that a build without the `dump` feature refuses a config enabling `data.dump`,
+/// naming the feature.
+#[cfg(not(feature = "dump"))]
+#[tokio::test]
+async fn test_dump_without_feature_exits_one() {
+    let dir = std::env::temp_dir().join(format!("advcache-dry-run-nodump-{}", std::process::id()));
+    let path = write_config("nodump", "127.0.0.1:8080", &dump_section(&dir.to_string_lossy()));
+    let (code, out, err) = dry_run(&path).await;
+    assert_eq!(code, 1);
+    assert!(out.is_empty(), "no summary of an invalid config");
+    assert!(err.contains("`dump` feature"), "stderr: {}", err);
+}
@@ -0,0 +1,121 @@
+// Integration tests for `cache_key.duplicate_query`.
+//
+// The backend points at a local origin that counts API calls, so keying is checked through hits
+// and misses.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use axum::http::{StatusCode, Uri};
+use axum::Router;
+
+use crate::config::DuplicateQuery;
+use crate::controller::InvalidateController;
+use crate::tests::support::router::serve_origin;
+use crate::tests::support::RouterCache;
+use crate::upstream::BackendImpl;
+
+const PATH: &str = "/api/v1/user";
+
+async fn start_origin() -> (String, Arc<AtomicUsize>) {
+    let fills = Arc::new(AtomicUsize::new(0));
+    let counter = fills.clone();
+    let app = Router::new().fallback(move |uri: Uri| {
+        let counter = counter.clone();
+        async move {
+            if uri.path().starts_with("/api/") {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+            (StatusCode::OK, [("content-type", "application/json")], "{\"ok\":true}")
+        }
+    });
+
+    (serve_origin(app).await, fills)
+}
+
+fn start(origin: &str, mode: Option<DuplicateQuery>) -> RouterCache<BackendImpl> {
+    RouterCache::builder()
+        .origin(origin)
+        .config(|cfg| {
+            let rules = cfg.cache.rules.as_mut().unwrap();
+            let mut rule = (*rules[PATH]).clone();
+            rule.cache_key.duplicate_query = mode;
+            rules.insert(PATH.to_string(), Arc::new(rule));
+        })
+        .start_backend()
+        .mount(|cache| InvalidateController::new(cache.cfg.clone(), cache.db.clone()))
+}
+
+async fn get(cache: &RouterCache<BackendImpl>, uri: &str) -> (StatusCode, String, String) {
+    let resp = cache.get(uri).await;
+    let status = resp.status();
+    let content_type = resp
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, content_type, String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Test that by default the last value wins: a request repeating `user[id]` shares its entry
+/// with the request carrying only the last value, and invalidating either form hits it.
+#[tokio::test]
+async fn test_duplicate_query_last_shares_entry() {
+    let (origin, fills) = start_origin().await;
+    let cache = start(&origin, None);
+
+    let (status, _, _) = get(&cache, &format!("{}?user[id]=1&user[id]=2", PATH)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, _) = get(&cache, &format!("{}?user[id]=2", PATH)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(fills.load(Ordering::Relaxed), 1, "both requests must hit the same entry");
+
+    let (status, _, body) = get(
+        &cache,
+        &format!("/advcache/invalidate?_path={}&user%5Bid%5D=1&user[id]=2&_remove=1", PATH),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("\"affected\":1"), "{}", body);
+}
+
+/// Test that `reject` answers 400 with a problem document without reaching the origin, lets
+/// a repeated identical value through, and is mirrored by the invalidator.
+#[tokio::test]
+async fn test_duplicate_query_reject() {
+    let (origin, fills) = start_origin().await;
+    let cache = start(&origin, Some(DuplicateQuery::Reject));
+
+    let (status, content_type, body) = get(&cache, &format!("{}?user[id]=1&user[id]=2", PATH)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(content_type, "application/problem+json");
+    let problem: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(problem["status"], 400);
+    assert!(problem["detail"].as_str().unwrap().contains("user[id]"), "{}", body);
+    assert_eq!(fills.load(Ordering::Relaxed), 0, "a rejected request must not reach the origin");
+
+    let (status, _, _) = get(&cache, &format!("{}?user[id]=1&user[id]=1", PATH)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, _) = get(&cache, &format!("/advcache/invalidate?_path={}&user[id]=1&user[id]=2", PATH)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+/// Test that `join` keys the request by all its values whatever their order.
+#[tokio::test]
+async fn test_duplicate_query_join() {
+    let (origin, fills) = start_origin().await;
+    let cache = start(&origin, Some(DuplicateQuery::Join));
+
+    for query in ["user[id]=2&user[id]=1", "user[id]=1&user[id]=2"] {
+        let (status, _, _) = get(&cache, &format!("{}?{}", PATH, query)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    assert_eq!(fills.load(Ordering::Relaxed), 1, "value order must not change the key");
+
+    let (status, _, _) = get(&cache, &format!("{}?user[id]=2", PATH)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(fills.load(Ordering::Relaxed), 2, "a single value is a different key");
+}
@@ -2,12 +2,12 @@
 
 
 use std::collections::HashMap;
-use crate::support::{assert_equal, assert_ok, cache_addr, do_json, init_test_harness, new_namespace, with_ns, H};
+use crate::support::{assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, new_namespace, with_ns, H};
 
 /// Test that non-200 responses from upstream are not cached.
 #[tokio::test]
 async fn test_non_200_responses_not_cached() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Non200_NotCached");
     let base = cache_addr().await;
@@ -41,7 +41,7 @@ async fn test_non_200_responses_not_cached() {
 /// Test that cache hit returns correct response even after entry is stored.
 #[tokio::test]
 async fn test_cache_hit_after_store() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_CacheHit_AfterStore");
     let base = cache_addr().await;
@@ -75,7 +75,7 @@ async fn test_cache_hit_after_store() {
 /// Test that empty response bodies are handled correctly.
 #[tokio::test]
 async fn test_empty_response_body() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_EmptyResponseBody");
     let base = cache_addr().await;
@@ -106,7 +106,7 @@ async fn test_empty_response_body() {
 /// This tests the admission control rejection scenario.
 #[tokio::test]
 async fn test_cache_miss_admission_rejection_still_serves() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_CacheMiss_AdmissionRejection");
     let base = cache_addr().await;
@@ -133,7 +133,7 @@ async fn test_cache_miss_admission_rejection_still_serves() {
 /// This tests error handling when reading corrupted cache entries.
 #[tokio::test]
 async fn test_cache_handles_corrupted_entries() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     // This test is difficult to implement without direct cache manipulation
     // The cache should handle corrupted entries by treating them as misses
@@ -167,7 +167,7 @@ async fn test_cache_handles_corrupted_entries() {
 /// Test that POST requests are not cached and pass through to upstream.
 #[tokio::test]
 async fn test_post_request_not_cached() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Post_NotCached");
     let base = cache_addr().await;
@@ -207,7 +207,7 @@ async fn test_post_request_not_cached() {
 /// Non-200 responses should be proxied but not stored in cache.
 #[tokio::test]
 async fn test_only_200_responses_cached() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Only200_Cached");
     let base = cache_addr().await;
@@ -0,0 +1,206 @@
+// Integration tests for the eviction audit (`eviction.audit`).
+//
+// Memory limits are set so that eviction is forced: a soft limit of zero empties the storage on
+// the next soft pass, a hard limit of zero makes every set evict inline.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{self, EvictionAudit, Rule, RuleKey, RuleValue};
+use crate::controller::metrics;
+use crate::db::storage::audit::{EvictionEvent, EvictionReason};
+use crate::db::storage::{Map, Storage};
+use crate::model::{Entry, Response};
+use crate::time;
+use crate::upstream::testing::MockUpstream;
+use crate::upstream::Upstream;
+
+fn rule(path: &str) -> Arc<Rule> {
+    Arc::new(Rule {
+        path: Some(path.to_string()),
+        path_bytes: Some(path.as_bytes().to_vec()),
+        cache_key: RuleKey {
+            query: None,
+            query_bytes: None,
+            query_ignore: None,
+            duplicate_query: None,
+            transformer: None,
+            headers: None,
+            headers_map: None,
+        },
+        cache_value: RuleValue {
+            headers: None,
+            headers_map: None,
+            persist: None,
+            rollout_percent: Default::default(),
+            max_concurrent_fills: None,
+            max_fill_wait: None,
+            admission_hook: None,
+            downstream_ttl: Default::default(),
+            respect_cache_control: None,
+            negative_ttl: None,
+        },
+        refresh: None,
+        stale_on_error: None,
+        shed_on_brownout: None,
+        methods: None,
+        key_body: None,
+        max_key_body: None,
+        max_body_size: None,
+        refresh_inherited: false,
+    })
+}
+
+fn entry(rule: &Arc<Rule>, id: usize) -> Entry {
+    let queries = vec![(b"id".to_vec(), id.to_string().into_bytes())];
+    let entry = Entry::new(rule.clone(), &queries, &[]);
+    let response = Response {
+        status: 200,
+        headers: vec![],
+        body: format!(r#"{{"id":{}}}"#, id).into_bytes(),
+    };
+    entry.set_payload(&queries, &[], &response);
+    entry
+}
+
+fn audit_file(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("advcache-audit-{}-{}.ndjson", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().into_owned()
+}
+
+/// Storage with the audit on and the given soft and hard limits; admission never rejects.
+fn storage(audit: Option<EvictionAudit>, soft: i64, hard: i64) -> (Arc<Storage>, CancellationToken) {
+    let mut cfg = config::new_test_config();
+    cfg.cache.eviction.as_mut().unwrap().audit = audit;
+    let st = cfg.cache.storage.as_mut().unwrap();
+    st.soft_memory_limit = soft;
+    st.hard_memory_limit = hard;
+    st.admission_memory_limit = i64::MAX;
+    let token = CancellationToken::new();
+    let map = Arc::new(Map::new(token.clone(), cfg.clone()));
+    let upstream = MockUpstream::new() as Arc<dyn Upstream>;
+    let storage = Storage::new(token.clone(), cfg, upstream, map).expect("storage must start");
+    (storage, token)
+}
+
+fn read_events(path: &str) -> Vec<EvictionEvent> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("every line must be an event"))
+        .collect()
+}
+
+async fn wait_for(what: &str, cond: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if cond() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out waiting for {}", what);
+}
+
+/// Test that every victim of a forced soft eviction is written to the audit file with its
+/// rule, size, age, hits and reason, and counted per rule.
+#[tokio::test]
+async fn test_soft_eviction_is_audited() {
+    let _clock = time::start(Duration::from_millis(1));
+    let path = audit_file("soft");
+    let audit = EvictionAudit { enabled: true, sample_rate: Some(1.0), path: Some(path.clone()), max_file_size: None };
+    let (storage, token) = storage(Some(audit), 0, i64::MAX);
+    let rule = rule("/audit/soft");
+
+    for id in 0..10 {
+        assert!(storage.set(entry(&rule, id)));
+    }
+    let hot = entry(&rule, 3);
+    for _ in 0..4 {
+        assert!(storage.get(&hot).1);
+    }
+
+    let (_, evicted) = storage.soft_evict_until_within_limit(1 << 16);
+    assert_eq!(evicted, 10);
+    wait_for("the audited evictions", || metrics::evictions_audited("/audit/soft", EvictionReason::Soft) == 10).await;
+    wait_for("the audit file", || read_events(&path).len() == 10).await;
+
+    let events = read_events(&path);
+    for event in &events {
+        assert_eq!(event.rule, "/audit/soft");
+        assert_eq!(event.reason, EvictionReason::Soft);
+        assert!(event.size > 0);
+        assert!(event.age_ms >= 0 && event.idle_ms >= 0);
+        assert_eq!(event.key.len(), 16);
+    }
+    let hot_event = events.iter().find(|e| e.key == format!("{:016x}", hot.key())).expect("hot entry must be audited");
+    assert_eq!(hot_event.hits, 4);
+    assert_eq!(events.iter().map(|e| e.hits).sum::<u32>(), 4);
+
+    token.cancel();
+    let _ = std::fs::remove_file(&path);
+}
+
+/// Test that inline hard-limit evictions are reported with the hard reason, and that without a
+/// path the events still reach the per-rule counters.
+#[tokio::test]
+async fn test_hard_eviction_is_counted_without_file() {
+    let audit = EvictionAudit { enabled: true, sample_rate: None, path: None, max_file_size: None };
+    let (storage, token) = storage(Some(audit), i64::MAX, 0);
+    let rule = rule("/audit/hard");
+
+    // Inline eviction gives up after a few empty shards, so not every set evicts.
+    for id in 0..500 {
+        assert!(storage.set(entry(&rule, id)));
+    }
+    let evicted = 500 - storage.len() as u64;
+    assert!(evicted > 0);
+    wait_for("the audited evictions", || metrics::evictions_audited("/audit/hard", EvictionReason::Hard) == evicted).await;
+    assert_eq!(metrics::evictions_audited("/audit/hard", EvictionReason::Soft), 0);
+    token.cancel();
+}
+
+/// Test that a partial sample rate records only part of the victims and that the file rotates
+/// to `<path>.1` once it outgrows `max_file_size`.
+#[tokio::test]
+async fn test_sampling_and_rotation() {
+    let path = audit_file("sampled");
+    let rotated = format!("{}.1", path);
+    let _ = std::fs::remove_file(&rotated);
+    let audit = EvictionAudit { enabled: true, sample_rate: Some(0.5), path: Some(path.clone()), max_file_size: Some(2048) };
+    let (storage, token) = storage(Some(audit), 0, i64::MAX);
+    let rule = rule("/audit/sampled");
+
+    for id in 0..400 {
+        assert!(storage.set(entry(&rule, id)));
+    }
+    let (_, evicted) = storage.soft_evict_until_within_limit(1 << 16);
+    assert_eq!(evicted, 400);
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let sampled = metrics::evictions_audited("/audit/sampled", EvictionReason::Soft);
+    assert!(sampled > 100 && sampled < 300, "about half must be sampled, got {}", sampled);
+
+    let current = std::fs::metadata(&path).expect("audit file must exist").len();
+    assert!(current <= 2048);
+    assert!(std::fs::metadata(&rotated).is_ok(), "audit file must have rotated");
+    assert!(read_events(&path).iter().all(|e| e.rule == "/audit/sampled"));
+
+    token.cancel();
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&rotated);
+}
+
+/// Test that hits are not counted while the audit is off.
+#[tokio::test]
+async fn test_disabled_audit_counts_no_hits() {
+    let (storage, token) = storage(None, i64::MAX, i64::MAX);
+    let rule = rule("/audit/off");
+    let e = entry(&rule, 1);
+    assert!(storage.set(e.clone()));
+    assert!(storage.get(&e).1);
+    assert_eq!(e.hits(), 0);
+    token.cancel();
+}
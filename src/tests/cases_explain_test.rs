@@ -0,0 +1,145 @@
+// Integration tests for the rule resolution explain endpoint.
+
+use std::collections::HashMap;
+
+use crate::support::{assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, new_namespace, with_ns, H};
+
+async fn explain(base: &str, query: &str, headers: &H) -> serde_json::Value {
+    let url = format!("{}/advcache/explain?{}", base, query);
+    let (status, _, body, _) = assert_ok(do_json::<serde_json::Value>("GET", &url, headers).await);
+    assert_equal(200, status);
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn gzip() -> H {
+    let mut headers = H::new();
+    headers.insert("Accept-Encoding".to_string(), "gzip".to_string());
+    headers.insert("X-Not-In-Key".to_string(), "1".to_string());
+    headers
+}
+
+/// Test that the matched rule and normalized key components are reported.
+#[tokio::test]
+async fn test_explain_rule_and_key_components() {
+    init_test_harness(Capability::Http).await.unwrap();
+    let base = cache_addr().await;
+
+    let resp = explain(
+        &base,
+        "method=GET&path=/api/v1/user&user[id]=1&utm_source=x&domain=example.com",
+        &gzip(),
+    )
+    .await;
+
+    assert_equal(&serde_json::json!("/api/v1/user"), &resp["request"]["path"]);
+    assert_equal(&serde_json::json!(true), &resp["rule"]["matched"]);
+    assert_equal(&serde_json::json!("/api/v1/user"), &resp["rule"]["path"]);
+    assert_equal(&serde_json::json!("exact match on rule path"), &resp["rule"]["reason"]);
+
+    // Non-whitelisted query params and headers are dropped, the rest is sorted.
+    assert_equal(
+        &serde_json::json!([["domain", "example.com"], ["user[id]", "1"]]),
+        &resp["key"]["queries"],
+    );
+    assert_equal(&serde_json::json!([["accept-encoding", "gzip"]]), &resp["key"]["headers"]);
+    assert!(resp["key"]["key"].is_u64(), "{resp}");
+    assert_equal(32, resp["key"]["fingerprint"].as_str().unwrap().len());
+}
+
+/// Test that the reported key is the one the cache stores the entry under, and that explain itself stores nothing.
+#[tokio::test]
+async fn test_explain_key_matches_stored_entry() {
+    init_test_harness(Capability::Http).await.unwrap();
+    let base = cache_addr().await;
+
+    let ns = new_namespace("Test_Explain_KeyMatchesStored");
+    let mut params = HashMap::new();
+    params.insert("user[id]".to_string(), "77".to_string());
+    let path = with_ns("/api/v1/user", &ns, &params);
+    let (_, query) = path.split_once('?').unwrap();
+
+    let resp = explain(&base, &format!("path=/api/v1/user&{}", query), &gzip()).await;
+    let key = resp["key"]["key"].as_u64().unwrap();
+
+    let entry_url = format!("{}/advcache/entry?key={}", base, key);
+    let (status, _, _, _) = assert_ok(do_json::<serde_json::Value>("GET", &entry_url, &H::new()).await);
+    assert_equal(404, status);
+
+    let (status, _, _, _) =
+        assert_ok(do_json::<serde_json::Value>("GET", &format!("{}{}", base, path), &gzip()).await);
+    assert_equal(200, status);
+
+    let (status, _, _, _) = assert_ok(do_json::<serde_json::Value>("GET", &entry_url, &H::new()).await);
+    assert_equal(200, status);
+}
+
+/// Test that effective refresh settings come from the rule when it overrides them, otherwise from lifetime.
+#[tokio::test]
+async fn test_explain_effective_refresh() {
+    init_test_harness(Capability::Http).await.unwrap();
+    let base = cache_addr().await;
+
+    let resp = explain(&base, "path=/api/v1/user", &H::new()).await;
+    assert_equal(&serde_json::json!({
+        "enabled": true,
+        "ttl": "1m",
+        "beta": 0.4,
+        "coefficient": 0.5,
+        "max_stale_on_error": "10m",
+    }), &resp["refresh"]);
+
+    let resp = explain(&base, "path=/api/v1/client", &H::new()).await;
+    assert_equal(&serde_json::json!("1day"), &resp["refresh"]["ttl"]);
+
+    // Rule opted out of stale-on-error.
+    let resp = explain(&base, "path=/api/v1/flaky_strict", &H::new()).await;
+    assert!(resp["refresh"].get("max_stale_on_error").is_none(), "{resp}");
+    assert_equal(&serde_json::json!(true), &resp["bypass"]["stale_on_error_opt_out"]);
+}
+
+/// Test that admission, bypass and backend sections are present and consistent.
+#[tokio::test]
+async fn test_explain_admission_bypass_and_backend() {
+    init_test_harness(Capability::Http).await.unwrap();
+    let base = cache_addr().await;
+
+    let resp = explain(&base, "path=/api/v1/user", &H::new()).await;
+
+    let admission = &resp["admission"];
+    assert!(admission["enabled"].is_boolean(), "{resp}");
+    assert_equal(&admission["would_apply"], &admission["doorkeeper"]);
+
+    assert!(resp["bypass"]["global"].is_boolean(), "{resp}");
+    assert_equal(&serde_json::json!(false), &resp["bypass"]["stale_on_error_opt_out"]);
+
+    assert_equal(&serde_json::json!("test-up"), &resp["backend"]["id"]);
+    assert!(resp["backend"]["url"].as_str().unwrap().starts_with("http://"), "{resp}");
+    let policy = resp["backend"]["policy"].as_str().unwrap();
+    assert!(policy == "deny" || policy == "await", "{resp}");
+}
+
+/// Test that unmatched paths, non-GET methods and missing path are reported without a key.
+#[tokio::test]
+async fn test_explain_not_cacheable() {
+    init_test_harness(Capability::Http).await.unwrap();
+    let base = cache_addr().await;
+
+    let resp = explain(&base, "path=/api/v1/unknown&user[id]=1", &H::new()).await;
+    assert_equal(&serde_json::json!(false), &resp["rule"]["matched"]);
+    assert_equal(
+        &serde_json::json!("no rule configured for path, request is proxied"),
+        &resp["rule"]["reason"],
+    );
+    assert!(resp.get("key").is_none() && resp.get("refresh").is_none(), "{resp}");
+
+    let resp = explain(&base, "method=POST&path=/api/v1/user", &H::new()).await;
+    assert_equal(&serde_json::json!(false), &resp["rule"]["matched"]);
+    assert_equal(
+        &serde_json::json!("only GET requests are served through the cache"),
+        &resp["rule"]["reason"],
+    );
+
+    let url = format!("{}/advcache/explain?method=GET", base);
+    let (status, _, _, _) = assert_ok(do_json::<serde_json::Value>("GET", &url, &H::new()).await);
+    assert_equal(400, status);
+}
@@ -0,0 +1,16 @@
+// Support for running the integration cases against an external instance (`external-e2e`).
+
+use crate::support::harness::external_urls;
+use crate::support::upstream::UpstreamServer;
+
+/// Serves the test upstream on the address of ADVCACHE_TEST_UPSTREAM_URL until killed, for the
+/// instance under test to fill from (see `cfg/advcache.e2e.cfg.yaml`).
+#[tokio::test]
+#[ignore = "serves the test upstream until killed"]
+async fn test_serve_upstream() {
+    let (_, upstream_url) = external_urls();
+    let addr = upstream_url.trim_start_matches("http://");
+    let upstream = UpstreamServer::start_on(addr).await;
+    println!("[e2e] test upstream at http://{}/healthz", upstream.addr());
+    futures::future::pending::<()>().await;
+}
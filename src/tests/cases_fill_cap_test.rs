@@ -0,0 +1,139 @@
+// Integration tests for the per-rule cap on concurrent upstream fills
+// (`cache_value.max_concurrent_fills`).
+//
+// The upstream is slow. Capped rules are copies of the user rule under paths of their own, so
+// the per-rule gauges of parallel tests stay apart.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::response::Response;
+use tower::ServiceExt;
+
+use crate::config;
+use crate::controller::metrics;
+use crate::tests::support::RouterCache;
+use crate::upstream::testing::MockUpstream;
+
+const UNCAPPED_PATH: &str = "/api/v1/user";
+
+/// Cache over an upstream answering after `latency`, with a copy of the user rule under `path`
+/// capped at `cap` fills.
+fn start(path: &str, cap: usize, wait: Duration, latency: Duration) -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| {
+            let rules = cfg.cache.rules.as_mut().unwrap();
+            let mut rule = (*rules[UNCAPPED_PATH]).clone();
+            rule.path = Some(path.to_string());
+            rule.path_bytes = Some(path.as_bytes().to_vec());
+            rule.cache_value.max_concurrent_fills = Some(cap);
+            rule.cache_value.max_fill_wait = Some(wait);
+            rules.insert(path.to_string(), Arc::new(rule));
+        })
+        .upstream(MockUpstream::builder().latency(latency).build())
+        .start()
+}
+
+/// Spawns a miss for each id at once.
+fn burst(cache: &RouterCache, path: &str, ids: std::ops::Range<usize>) -> Vec<tokio::task::JoinHandle<Response>> {
+    ids.map(|id| {
+        let router = cache.router.clone();
+        let uri = format!("{}?user[id]={}", path, id);
+        tokio::spawn(async move { router.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap() })
+    })
+    .collect()
+}
+
+/// Test that 200 distinct misses at a rule capped at 10 never have more than 10 fills in
+/// flight, and are all filled as slots free up.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_fills_stay_under_the_cap() {
+    const PATH: &str = "/api/v1/search";
+    let cache = start(PATH, 10, Duration::from_secs(30), Duration::from_millis(20));
+
+    let capped = burst(&cache, PATH, 0..200);
+    while cache.upstream.inflight() < 10 {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    let gauge = metrics::rule_fills_inflight(PATH);
+    assert!((1..=10).contains(&gauge), "the gauge shows the rule's fills in flight, got {}", gauge);
+
+    for handle in capped {
+        assert_eq!(handle.await.unwrap().status(), StatusCode::OK);
+    }
+    assert_eq!(cache.upstream.fills(), 200);
+    let peak = cache.upstream.peak_inflight();
+    assert!(peak <= 10, "fills in flight must never exceed the cap, peaked at {}", peak);
+    assert_eq!(metrics::rule_fills_inflight(PATH), 0);
+    assert_eq!(metrics::rule_fills_rejected(PATH), 0);
+}
+
+/// Test that a saturated capped rule does not hold up the misses of other rules.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_cap_does_not_affect_other_rules() {
+    const PATH: &str = "/api/v1/search-isolated";
+    let cache = start(PATH, 2, Duration::from_secs(30), Duration::from_millis(200));
+
+    let capped = burst(&cache, PATH, 0..20);
+    while cache.upstream.inflight() < 2 {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    let started = Instant::now();
+    let others = burst(&cache, UNCAPPED_PATH, 0..10);
+    for handle in others {
+        assert_eq!(handle.await.unwrap().status(), StatusCode::OK);
+    }
+    assert!(
+        started.elapsed() < Duration::from_secs(1),
+        "uncapped misses must not queue behind the capped rule, took {:?}",
+        started.elapsed()
+    );
+    for handle in capped {
+        assert_eq!(handle.await.unwrap().status(), StatusCode::OK);
+    }
+}
+
+/// Test that a miss waiting longer than `max_fill_wait` for a slot is refused with 429 and
+/// `Retry-After` without reaching the origin.
+#[tokio::test]
+async fn test_miss_fails_fast_after_max_fill_wait() {
+    const PATH: &str = "/api/v1/search-bounded";
+    let cache = start(PATH, 1, Duration::from_millis(50), Duration::from_millis(500));
+
+    let slow = burst(&cache, PATH, 0..1);
+    while cache.upstream.inflight() < 1 {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    let request = Request::get(format!("{}?user[id]=1", PATH)).body(Body::empty()).unwrap();
+    let resp = cache.send(request).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resp.headers()[header::RETRY_AFTER], "1");
+    assert_eq!(resp.headers()["x-error-reason"], "fill_cap");
+    assert_eq!(resp.headers()["x-ratelimit-reason"], "fill-cap");
+    assert_eq!(metrics::rule_fills_rejected(PATH), 1);
+    assert_eq!(cache.upstream.fills(), 1, "the refused miss must not reach the origin");
+
+    for handle in slow {
+        assert_eq!(handle.await.unwrap().status(), StatusCode::OK);
+    }
+}
+
+/// Test that a cap of 0 is rejected.
+#[test]
+fn test_zero_cap_is_rejected() {
+    let yaml = r#"
+cache:
+  env: test
+  enabled: true
+  rules:
+    /api/v1/search:
+      cache_key:
+        query: [q]
+      cache_value:
+        max_concurrent_fills: 0
+"#;
+    let err = config::Config::from_yaml(yaml).unwrap_err();
+    assert!(format!("{:#}", err).contains("max_concurrent_fills must be at least 1"), "{:#}", err);
+}
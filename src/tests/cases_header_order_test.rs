@@ -0,0 +1,64 @@
+// Integration tests for the headers of responses served from entries.
+//
+// A HIT must carry the headers of the MISS it was stored from, repeated names included and in
+// the same order: clients sign responses over their header list.
+
+use std::collections::HashMap;
+
+use crate::support::{
+    assert_equal, assert_ok, assert_same_headers, cache_addr, do_json, do_request, header_list, init_test_harness,
+    new_namespace, with_global_lock, with_ns, Capability, H,
+};
+
+fn values<'a>(headers: &'a [(String, Vec<u8>)], name: &str) -> Vec<&'a [u8]> {
+    headers.iter().filter(|(k, _)| k == name).map(|(_, v)| v.as_slice()).collect()
+}
+
+/// Test that a HIT repeats the MISS headers byte for byte, repeated Vary and X-Content-Digest
+/// values kept apart and in order.
+#[tokio::test]
+async fn test_hit_headers_match_miss() {
+    with_global_lock(|| async {
+        init_test_harness(Capability::Http).await.unwrap();
+
+        let ns = new_namespace("Test_HeaderOrder_MissHit");
+        let base = cache_addr().await;
+        // Admission off so the MISS is stored whatever the cache holds already.
+        let admission = |state: &'static str| {
+            let url = format!("{}/advcache/admission/{}", base, state);
+            async move { assert_ok(do_json::<serde_json::Value>("GET", &url, &H::new()).await) }
+        };
+        admission("off").await;
+
+        let mut params = HashMap::new();
+        params.insert("user[id]".to_string(), "4242".to_string());
+        params.insert("picked".to_string(), "multi_headers".to_string());
+        let url = format!("{}{}", base, with_ns("/api/v1/user", &ns, &params));
+
+        let mut headers = H::new();
+        headers.insert("Accept-Encoding".to_string(), "identity".to_string());
+
+        let miss = assert_ok(do_request("GET", &url, &headers, None).await);
+        assert_equal(200, miss.status().as_u16());
+        let miss_headers = header_list(&miss);
+        // The compression layer may append its own `Vary: accept-encoding` after the stored ones.
+        assert!(
+            values(&miss_headers, "vary").starts_with(&[b"Accept-Encoding".as_slice(), b"Origin".as_slice()]),
+            "{:?}",
+            miss_headers
+        );
+        assert_eq!(values(&miss_headers, "x-content-digest").len(), 2, "{:?}", miss_headers);
+
+        let hit = assert_ok(do_request("GET", &url, &headers, None).await);
+        assert_equal(200, hit.status().as_u16());
+        let hit_headers = header_list(&hit);
+        let updated_at = values(&miss_headers, "last-updated-at");
+        assert!(!updated_at.is_empty(), "MISS must store the entry");
+        assert_eq!(updated_at, values(&hit_headers, "last-updated-at"), "second request must be a HIT");
+
+        assert_same_headers(&miss_headers, &hit_headers);
+
+        admission("on").await;
+    })
+    .await;
+}
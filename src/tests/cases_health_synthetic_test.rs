@@ -0,0 +1,135 @@
+// Integration tests for the deep health check of a backend (`health_synthetic`).
+//
+// A real backend points at a local origin whose `/healthz` always passes while its API path
+// can be switched to fail, as when its database is down behind a trivial health handler.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::http::{HeaderMap, Request, StatusCode, Uri};
+use axum::Router;
+use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
+
+use crate::config::{self, HealthSynthetic};
+use crate::controller::BackendDrainController;
+use crate::http::Controller;
+use crate::upstream::health::SYNTHETIC_HEADER;
+use crate::upstream::{BackendImpl, Upstream};
+
+const PATH: &str = "/api/v1/user";
+
+#[derive(Default)]
+struct Origin {
+    api_down: AtomicBool,
+    synthetic_calls: AtomicUsize,
+}
+
+async fn start_origin() -> (String, Arc<Origin>) {
+    let origin = Arc::new(Origin::default());
+    let state = origin.clone();
+    let router = Router::new().fallback(move |uri: Uri, headers: HeaderMap| {
+        let state = state.clone();
+        async move {
+            if uri.path() == "/healthz" {
+                return StatusCode::OK;
+            }
+            if headers.contains_key(SYNTHETIC_HEADER) && uri.query() == Some("user[id]=1") {
+                state.synthetic_calls.fetch_add(1, Ordering::Relaxed);
+            }
+            if state.api_down.load(Ordering::Relaxed) {
+                StatusCode::INTERNAL_SERVER_ERROR
+            } else {
+                StatusCode::OK
+            }
+        }
+    });
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    (addr, origin)
+}
+
+/// Backend with a deep check of the user path every 100ms, and the status endpoint over it.
+fn start(origin: &str, shutdown: &CancellationToken) -> (Router, Arc<BackendImpl>) {
+    let mut backend = config::new_test_config().cache.upstream.unwrap().backend.unwrap();
+    backend.id = Some("synthetic".to_string());
+    backend.host = Some(origin.to_string());
+    backend.health_synthetic = Some(HealthSynthetic {
+        path: PATH.to_string(),
+        query: Some("user[id]=1".to_string()),
+        expected_status: None,
+        interval: Some(Duration::from_millis(100)),
+        fail_threshold: Some(2),
+        ok_threshold: Some(2),
+    });
+    let backend = BackendImpl::new(shutdown.clone(), Some(backend)).expect("backend must start");
+    let router = BackendDrainController::new(backend.clone(), None).add_route(Router::new());
+    (router, backend)
+}
+
+async fn wait_alive(backend: &BackendImpl, alive: bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while backend.is_alive() != alive {
+        assert!(Instant::now() < deadline, "backend never became alive={}", alive);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+async fn health(router: &Router) -> serde_json::Value {
+    let req = Request::get("/advcache/upstream/backends").body(Body::empty()).unwrap();
+    let resp = router.clone().oneshot(req).await.unwrap();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    status["backends"][0]["health"].clone()
+}
+
+/// Test that a backend whose `/healthz` passes while its API path fails is marked down by the
+/// deep check, that the status endpoint shows both results, and that it comes back up once
+/// the API answers again.
+#[tokio::test]
+async fn test_failing_api_marks_backend_down_despite_healthz() {
+    let (addr, origin) = start_origin().await;
+    let shutdown = CancellationToken::new();
+    let (router, backend) = start(&addr, &shutdown);
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(backend.is_alive());
+    assert!(origin.synthetic_calls.load(Ordering::Relaxed) > 0, "synthetic requests carry the header and query");
+    assert_eq!(health(&router).await["deep"]["last_ok"], true);
+
+    origin.api_down.store(true, Ordering::Relaxed);
+    wait_alive(&backend, false).await;
+    let checks = health(&router).await;
+    assert_eq!(checks["shallow"]["up"], true, "{}", checks);
+    assert_eq!(checks["deep"]["up"], false, "{}", checks);
+    assert!(checks["deep"]["last_error"].as_str().unwrap().contains("500"), "{}", checks);
+
+    origin.api_down.store(false, Ordering::Relaxed);
+    wait_alive(&backend, true).await;
+    let checks = health(&router).await;
+    assert_eq!((&checks["shallow"]["up"], &checks["deep"]["up"]), (&true.into(), &true.into()), "{}", checks);
+    shutdown.cancel();
+}
+
+/// Test that a backend without `health_synthetic` shows its shallow check only.
+#[tokio::test]
+async fn test_status_without_deep_check() {
+    let (addr, _origin) = start_origin().await;
+    let shutdown = CancellationToken::new();
+    let mut backend = config::new_test_config().cache.upstream.unwrap().backend.unwrap();
+    backend.host = Some(addr);
+    let backend = BackendImpl::new(shutdown.clone(), Some(backend)).expect("backend must start");
+    let router = BackendDrainController::new(backend, None).add_route(Router::new());
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let checks = health(&router).await;
+    assert_eq!(checks["shallow"]["up"], true, "{}", checks);
+    assert!(checks.get("deep").is_none(), "{}", checks);
+    shutdown.cancel();
+}
@@ -2,7 +2,7 @@
 
 
 use std::collections::HashMap;
-use crate::support::{assert_equal, assert_ok, cache_addr, do_json, init_test_harness, new_namespace, with_ns, H};
+use crate::support::{assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, new_namespace, with_ns, H};
 
 #[derive(serde::Deserialize)]
 struct AdmissionResponse {
@@ -17,7 +17,7 @@ async fn test_admission_control_affects_storage() {
     use crate::support::with_global_lock;
     
     with_global_lock(|| async {
-        init_test_harness().await.unwrap();
+        init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
 
@@ -66,7 +66,7 @@ async fn test_invalidation_causes_cache_miss() {
     use crate::support::{get_updated_at, set_global_defaults, with_global_lock};
     
     with_global_lock(|| async {
-        init_test_harness().await.unwrap();
+        init_test_harness(Capability::Http).await.unwrap();
 
         let ns = new_namespace("Test_Invalidation_CausesMiss");
         let base = cache_addr().await;
@@ -170,7 +170,7 @@ async fn test_cache_clear_actually_clears() {
     use crate::support::with_global_lock;
     
     with_global_lock(|| async {
-        init_test_harness().await.unwrap();
+        init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Clear_ActuallyClears");
     let base = cache_addr().await;
@@ -255,7 +255,7 @@ async fn test_compression_middleware_affects_headers() {
     use crate::support::with_global_lock;
     
     with_global_lock(|| async {
-        init_test_harness().await.unwrap();
+        init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
 
@@ -303,7 +303,7 @@ async fn test_multiple_invalidations_independent() {
     use crate::support::with_global_lock;
     
     with_global_lock(|| async {
-        init_test_harness().await.unwrap();
+        init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_MultipleInvalidations");
     let base = cache_addr().await;
@@ -384,7 +384,7 @@ async fn test_cache_proxy_mode_switching_consistency() {
     use crate::support::with_global_lock;
     
     with_global_lock(|| async {
-        init_test_harness().await.unwrap();
+        init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_CacheProxy_Switching");
     let base = cache_addr().await;
@@ -2,7 +2,7 @@
 
 
 use std::collections::HashMap;
-use crate::support::{assert_equal, assert_ok, cache_addr, do_json, init_test_harness, new_namespace, with_ns, H};
+use crate::support::{assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, new_namespace, with_ns, H};
 
 #[derive(serde::Deserialize)]
 struct InvalidateResponse {
@@ -13,7 +13,7 @@ struct InvalidateResponse {
 /// Test that invalidation by path only marks all entries for the path as outdated.
 #[tokio::test]
 async fn test_invalidate_by_path_only() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Invalidate_ByPathOnly");
     let base = cache_addr().await;
@@ -83,7 +83,7 @@ async fn test_invalidate_by_path_only() {
 /// Test that invalidation with query parameters marks only matching entries.
 #[tokio::test]
 async fn test_invalidate_with_query_params() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Invalidate_WithQuery");
     let base = cache_addr().await;
@@ -155,7 +155,7 @@ async fn test_invalidate_with_query_params() {
 /// Test that invalidation with _remove flag removes entries instead of marking outdated.
 #[tokio::test]
 async fn test_invalidate_with_remove_flag() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Invalidate_WithRemove");
     let base = cache_addr().await;
@@ -207,7 +207,7 @@ async fn test_invalidate_with_remove_flag() {
 /// Test that invalidation without _path parameter returns error.
 #[tokio::test]
 async fn test_invalidate_missing_path_parameter() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
 
@@ -225,7 +225,7 @@ async fn test_invalidate_missing_path_parameter() {
 /// Test that invalidation with non-existent path returns NOT_FOUND.
 #[tokio::test]
 async fn test_invalidate_nonexistent_path() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
 
@@ -243,7 +243,7 @@ async fn test_invalidate_nonexistent_path() {
 /// Test that invalidation affects only entries matching all query parameters.
 #[tokio::test]
 async fn test_invalidate_exact_query_match() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Invalidate_ExactMatch");
     let base = cache_addr().await;
@@ -0,0 +1,136 @@
+// Integration tests for the admin jobs over the shards (`/advcache/jobs`).
+//
+// Clears by rule and invalidations are submitted as jobs and followed through the jobs
+// endpoints.
+
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use tower::ServiceExt;
+
+use crate::controller::{ClearController, InvalidateController, JobsController};
+use crate::db::Storage;
+use crate::tests::support::router::into_json;
+use crate::tests::support::RouterCache;
+
+const USER: &str = "/api/v1/user";
+const BUYER: &str = "/api/v1/buyer";
+
+fn start() -> RouterCache {
+    RouterCache::start()
+        .mount(|cache| InvalidateController::new(cache.cfg.clone(), cache.db.clone()))
+        .mount(|cache| ClearController::new(cache.cfg.clone(), cache.db.clone()))
+        .mount(|cache| JobsController::new(cache.db.clone()))
+}
+
+/// Requests the entry and returns whether it was served from the cache.
+async fn is_hit(cache: &RouterCache, path: &str, id: u32) -> bool {
+    let fills = cache.upstream.fills();
+    assert_eq!(cache.get_json(&format!("{}?user[id]={}", path, id)).await.0, StatusCode::OK);
+    cache.upstream.fills() == fills
+}
+
+/// Polls the job until it is finished and returns its record.
+async fn wait_job(cache: &RouterCache, id: u64) -> serde_json::Value {
+    loop {
+        let (status, job) = cache.get_json(&format!("/advcache/jobs/{}", id)).await;
+        assert_eq!(status, StatusCode::OK, "{}", job);
+        if job.get("finished_at").is_some() {
+            return job;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+/// Test that a clear by rule is answered 202 with a job that removes the entries of that rule
+/// alone, and whose progress the jobs endpoints report.
+#[tokio::test]
+async fn test_clear_rule_runs_as_job() {
+    let cache = start();
+    for id in 0..5 {
+        assert!(!is_hit(&cache, USER, id).await);
+        assert!(!is_hit(&cache, BUYER, id).await);
+    }
+
+    let (_, token) = cache.get_json("/advcache/clear").await;
+    let token = token["token"].as_str().unwrap().to_string();
+    let (status, body) = cache.get_json(&format!("/advcache/clear?token={}&rule=/api/v1/nope", token)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{}", body);
+
+    // The token is still usable after an unknown rule.
+    let (status, body) = cache.get_json(&format!("/advcache/clear?token={}&rule={}", token, USER)).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+    let job = wait_job(&cache, body["job"].as_u64().unwrap()).await;
+    assert_eq!(job["op"], "clear_rule");
+    assert_eq!(job["target"], USER);
+    assert_eq!(job["state"], "done");
+    assert_eq!((job["scanned"].as_u64(), job["matched"].as_u64(), job["acted"].as_u64()), (Some(10), Some(5), Some(5)));
+    assert_eq!(job["shards"], job["shards_done"]);
+
+    for id in 0..5 {
+        assert!(!is_hit(&cache, USER, id).await, "entries of the cleared rule are gone");
+        assert!(is_hit(&cache, BUYER, id).await, "entries of other rules are kept");
+    }
+
+    let (status, body) = cache.get_json(&format!("/advcache/clear?token={}&rule={}", token, USER)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "a token is used once: {}", body);
+}
+
+/// Test that an invalidation reports the job it ran, listed by `/advcache/jobs`.
+#[tokio::test]
+async fn test_invalidation_is_listed() {
+    let cache = start();
+    assert!(!is_hit(&cache, USER, 1).await);
+
+    let (status, body) = cache.get_json(&format!("/advcache/invalidate?_path={}&user[id]=1&_remove=1", USER)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["affected"], 1);
+    let id = body["job"].as_u64().unwrap();
+
+    let (status, list) = cache.get_json("/advcache/jobs").await;
+    assert_eq!(status, StatusCode::OK);
+    let jobs = list["jobs"].as_array().unwrap();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!((jobs[0]["id"].as_u64(), jobs[0]["op"].as_str()), (Some(id), Some("invalidate")));
+    assert_eq!(jobs[0]["acted"], 1);
+    assert!(!is_hit(&cache, USER, 1).await);
+}
+
+/// Test that `DELETE /advcache/jobs/{id}` cancels a job still waiting for its walk, which then
+/// answers its invalidation as not done and removes nothing.
+#[tokio::test]
+async fn test_cancel_job() {
+    let cache = start();
+    assert!(!is_hit(&cache, USER, 2).await);
+    let (status, _) = cache.call_json(Method::DELETE, "/advcache/jobs/42").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let walks = cache.db.walks().expect("the db coordinates walks");
+    let walking = walks.acquire("test").await.unwrap();
+    let invalidation = {
+        let router = cache.router.clone();
+        let uri = format!("/advcache/invalidate?_path={}&user[id]=2&_remove=1", USER);
+        tokio::spawn(async move {
+            into_json(router.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap()).await
+        })
+    };
+    let id = loop {
+        let (_, list) = cache.get_json("/advcache/jobs").await;
+        if let Some(job) = list["jobs"].as_array().unwrap().first() {
+            assert_eq!(job["state"], "queued");
+            break job["id"].as_u64().unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    };
+
+    let (status, _) = cache.call_json(Method::DELETE, &format!("/advcache/jobs/{}", id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = invalidation.await.unwrap();
+    assert_eq!(body["success"], false, "{}", body);
+    assert_eq!(body["error"], "cancelled");
+    assert_eq!(wait_job(&cache, id).await["state"], "cancelled");
+    drop(walking);
+
+    assert!(is_hit(&cache, USER, 2).await, "a cancelled invalidation removes nothing");
+}
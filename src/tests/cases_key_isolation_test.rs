@@ -1,7 +1,7 @@
 // Integration tests for cache key isolation.
 
 use crate::support::{
-    assert_equal, assert_ok, cache_addr, do_json, hash, init_test_harness, new_namespace, with_ns,
+    assert_equal, assert_ok, cache_addr, do_json, hash, Capability, init_test_harness, new_namespace, with_ns,
     H,
 };
 use std::collections::HashMap;
@@ -65,7 +65,7 @@ fn encode_query(m: &HashMap<String, String>) -> String {
 
 #[tokio::test]
 async fn test_key_isolation_path_variants() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_key_isolation_path_variants");
     let base = cache_addr().await;
@@ -112,7 +112,7 @@ async fn test_key_isolation_path_variants() {
 
 #[tokio::test]
 async fn test_key_isolation_query_variants() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_key_isolation_query_variants");
     let base = cache_addr().await;
@@ -161,7 +161,7 @@ async fn test_key_isolation_query_variants() {
 
 #[tokio::test]
 async fn test_key_isolation_header_accept_encoding() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_key_isolation_header_accept_encoding");
     let base = cache_addr().await;
@@ -215,7 +215,7 @@ async fn test_key_isolation_header_accept_encoding() {
 
 #[tokio::test]
 async fn test_key_isolation_mixed_matrix_no_leakage() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_key_isolation_mixed_matrix_no_leakage");
     let base = cache_addr().await;
@@ -312,7 +312,7 @@ async fn test_key_isolation_mixed_matrix_no_leakage() {
 
 #[tokio::test]
 async fn test_accept_encoding_normalization() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_accept_encoding_normalization");
     let base = cache_addr().await;
@@ -358,7 +358,7 @@ async fn test_accept_encoding_normalization() {
 
 #[tokio::test]
 async fn test_header_case_insensitivity() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_header_case_insensitivity");
     let base = cache_addr().await;
@@ -389,7 +389,7 @@ async fn test_header_case_insensitivity() {
 
 #[tokio::test]
 async fn test_namespace_isolation_changes_body() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let base = cache_addr().await;
     let ns_a = format!(
@@ -0,0 +1,148 @@
+// Integration tests for `cache_key.transformer` and `cache_value.admission_hook`.
+//
+// The backend points at a local origin that counts API calls, so keying and storing are checked
+// through hits and misses.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode, Uri};
+use axum::Router;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+
+use crate::config::Config;
+use crate::plugin::{self, AdmissionHook, KeyInput, ResponseView};
+use crate::tests::support::router::serve_origin;
+use crate::tests::support::RouterCache;
+use crate::upstream::BackendImpl;
+
+const PATH: &str = "/api/v1/user";
+
+async fn start_origin() -> (String, Arc<AtomicUsize>) {
+    let fills = Arc::new(AtomicUsize::new(0));
+    let counter = fills.clone();
+    let app = Router::new().fallback(move |uri: Uri| {
+        let counter = counter.clone();
+        async move {
+            if uri.path().starts_with("/api/") {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+            (StatusCode::OK, [("content-type", "application/json")], "{\"ok\":true}")
+        }
+    });
+
+    (serve_origin(app).await, fills)
+}
+
+fn start(origin: &str, transformer: Option<&str>, admission_hook: Option<&str>) -> RouterCache<BackendImpl> {
+    RouterCache::builder()
+        .origin(origin)
+        .config(|cfg| {
+            let rules = cfg.cache.rules.as_mut().unwrap();
+            let mut rule = (*rules[PATH]).clone();
+            rule.cache_key.transformer = transformer.map(str::to_string);
+            rule.cache_value.admission_hook = admission_hook.map(str::to_string);
+            rules.insert(PATH.to_string(), Arc::new(rule));
+        })
+        .start_backend()
+}
+
+async fn get(cache: &RouterCache<BackendImpl>, auth: Option<&str>) -> StatusCode {
+    let mut request = Request::get(format!("{}?user[id]=1", PATH));
+    if let Some(auth) = auth {
+        request = request.header("authorization", auth);
+    }
+    let resp = cache.send(request.body(Body::empty()).unwrap()).await;
+    resp.status()
+}
+
+fn jwt(claims: &str) -> String {
+    format!(
+        "Bearer {}.{}.sig",
+        URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256"}"#),
+        URL_SAFE_NO_PAD.encode(claims)
+    )
+}
+
+/// Test that `jwt_sub` keys requests by the token subject: different tokens of one subject
+/// share an entry, another subject and an anonymous request get entries of their own.
+#[tokio::test]
+async fn test_jwt_sub_transformer_keys_by_subject() {
+    let (origin, fills) = start_origin().await;
+    let cache = start(&origin, Some("jwt_sub"), None);
+
+    let alice = [jwt(r#"{"sub":"alice","iat":1}"#), jwt(r#"{"sub":"alice","iat":2}"#)];
+    for token in &alice {
+        assert_eq!(get(&cache, Some(token)).await, StatusCode::OK);
+    }
+    assert_eq!(fills.load(Ordering::Relaxed), 1, "tokens of one subject must share the entry");
+
+    assert_eq!(get(&cache, Some(&jwt(r#"{"sub":"bob"}"#))).await, StatusCode::OK);
+    assert_eq!(fills.load(Ordering::Relaxed), 2, "another subject must not read alice's entry");
+
+    for auth in [None, Some("Bearer garbage")] {
+        assert_eq!(get(&cache, auth).await, StatusCode::OK);
+    }
+    assert_eq!(
+        fills.load(Ordering::Relaxed),
+        3,
+        "requests without a readable token share the untransformed entry"
+    );
+
+    assert_eq!(get(&cache, Some(&alice[0])).await, StatusCode::OK);
+    assert_eq!(fills.load(Ordering::Relaxed), 3);
+}
+
+/// Refuses to store the responses of requests flagged with `x-no-store`.
+struct RefuseFlagged;
+
+impl AdmissionHook for RefuseFlagged {
+    fn admit(&self, request: &KeyInput<'_>, response: &ResponseView<'_>) -> bool {
+        response.status == 200 && request.request_header("x-no-store").is_none()
+    }
+}
+
+/// Test that a registered admission hook keeps refused responses out of the cache while they
+/// are still answered.
+#[tokio::test]
+async fn test_admission_hook_refuses_store() {
+    plugin::register_admission_hook("cases-refuse-flagged", Arc::new(RefuseFlagged));
+    let (origin, fills) = start_origin().await;
+    let cache = start(&origin, None, Some("cases-refuse-flagged"));
+
+    let flagged = || {
+        Request::get(format!("{}?user[id]=2", PATH))
+            .header("x-no-store", "1")
+            .body(Body::empty())
+            .unwrap()
+    };
+    for _ in 0..2 {
+        let resp = cache.send(flagged()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    assert_eq!(fills.load(Ordering::Relaxed), 2, "a refused response must not be stored");
+
+    for _ in 0..2 {
+        assert_eq!(get(&cache, None).await, StatusCode::OK);
+    }
+    assert_eq!(fills.load(Ordering::Relaxed), 3, "admitted responses are stored");
+}
+
+/// Test that a config naming an unregistered plugin or a malformed built-in fails to load.
+#[test]
+fn test_plugin_names_are_validated() {
+    let yaml = |key: &str, value: &str| {
+        format!(
+            "cache:\n  env: test\n  enabled: true\n  upstream:\n    backend:\n      id: main\n      enabled: true\n      scheme: http\n      host: main.local:8080\n      timeout: 10s\n      max_timeout: 1m\n  rules:\n    /api/v1/me:\n      cache_key:\n        query: []\n        headers: []\n        transformer: {}\n      cache_value:\n        headers: []\n        admission_hook: {}\n",
+            if key == "transformer" { value } else { "jwt_sub" },
+            if key == "admission_hook" { value } else { "~" },
+        )
+    };
+    assert!(Config::from_yaml(&yaml("transformer", "jwt_sub")).is_ok());
+    assert!(Config::from_yaml(&yaml("transformer", "'header_regex:x-client:^(\\w+)/'")).is_ok());
+    assert!(Config::from_yaml(&yaml("transformer", "no-such-transformer")).is_err());
+    assert!(Config::from_yaml(&yaml("transformer", "'header_regex:x-client:('")).is_err());
+    assert!(Config::from_yaml(&yaml("admission_hook", "no-such-hook")).is_err());
+}
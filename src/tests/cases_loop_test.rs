@@ -0,0 +1,82 @@
+// Integration tests for self-request loop protection.
+//
+// The cache is served on a local port with its backend pointing at that very port, the
+// misconfiguration the loop guard exists for. The first hop back must be refused with 508.
+
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+
+use crate::config::{self, Config};
+use crate::tests::support::RouterCache;
+use crate::upstream::loop_guard::{instance_id, LOOP_HEADER, MAX_HOPS};
+
+const PATH: &str = "/api/v1/user";
+
+fn point_at_self(cfg: &mut Config, addr: &str) {
+    let backend = cfg.cache.upstream.as_mut().unwrap().backend.as_mut().unwrap();
+    backend.host = Some(addr.to_string());
+    backend.timeout = Some(Duration::from_secs(10));
+    cfg.cache.api.as_mut().unwrap().port = addr.rsplit_once(':').map(|(_, port)| port.to_string());
+}
+
+async fn send<U>(cache: &RouterCache<U>, request: Request<Body>) -> StatusCode {
+    let resp = cache.send(request).await;
+    let status = resp.status();
+    let _ = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    status
+}
+
+/// Test that a cache whose backend is the cache itself stops the loop at the first hop with 508.
+#[tokio::test]
+async fn test_backend_pointing_to_self_is_refused_fast() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let cache = RouterCache::builder().config(|cfg| point_at_self(cfg, &addr)).start_backend();
+    assert_eq!(cache.cfg.backend_host_pointing_to_self(), Some(addr.clone()));
+    let served = cache.router.clone();
+    tokio::spawn(async move {
+        axum::serve(listener, served).await.unwrap();
+    });
+
+    let started = Instant::now();
+    let uri = format!("{}?user[id]=1", PATH);
+    let status = send(&cache, Request::get(uri.as_str()).body(Body::empty()).unwrap()).await;
+    drop(cache);
+
+    assert_eq!(status, StatusCode::LOOP_DETECTED);
+    assert!(started.elapsed() < Duration::from_secs(2), "the loop must be cut at the first hop");
+}
+
+/// Test that inbound requests carrying this instance or too many hops never reach the upstream.
+#[tokio::test]
+async fn test_looping_request_is_refused_before_upstream() {
+    let cache = RouterCache::start();
+    let uri = format!("{}?user[id]=1", PATH);
+
+    let own = format!("edge-1, {}", instance_id());
+    let request = Request::get(uri.as_str()).header(LOOP_HEADER, own).body(Body::empty()).unwrap();
+    assert_eq!(send(&cache, request).await, StatusCode::LOOP_DETECTED);
+
+    let chain: Vec<String> = (0..MAX_HOPS).map(|i| format!("edge-{}", i)).collect();
+    let request = Request::get(uri.as_str()).header(LOOP_HEADER, chain.join(", ")).body(Body::empty()).unwrap();
+    assert_eq!(send(&cache, request).await, StatusCode::LOOP_DETECTED);
+    assert_eq!(cache.upstream.fills(), 0);
+
+    let request = Request::get(uri.as_str()).header(LOOP_HEADER, "edge-1").body(Body::empty()).unwrap();
+    assert_eq!(send(&cache, request).await, StatusCode::OK);
+    assert_eq!(cache.upstream.fills(), 1);
+}
+
+/// Test that a backend on another port is not reported as pointing to the cache.
+#[test]
+fn test_backend_elsewhere_is_not_self() {
+    let mut cfg = config::new_test_config();
+    point_at_self(&mut cfg, "127.0.0.1:1");
+    assert!(cfg.backend_host_pointing_to_self().is_some());
+
+    let mut cfg = config::new_test_config();
+    cfg.cache.upstream.as_mut().unwrap().backend.as_mut().unwrap().host = Some("127.0.0.1:9".to_string());
+    assert_eq!(cfg.backend_host_pointing_to_self(), None);
+}
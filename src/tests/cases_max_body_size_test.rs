@@ -0,0 +1,86 @@
+// Integration tests for the largest response body stored (`max_body_size`).
+//
+// The mock upstream answers bodies of a set size, so the tests count the fills that reach the
+// origin: an answer that is stored is filled once.
+
+use std::io::Write;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+
+use crate::controller::metrics;
+use crate::tests::support::RouterCache;
+use crate::upstream::Response;
+
+const USER_PATH: &str = "/api/v1/user";
+const CLIENT_PATH: &str = "/api/v1/client";
+
+/// Cache storing bodies of up to 1 KiB for the user rule and 2 KiB for the others.
+fn start() -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| {
+            cfg.cache.storage.as_mut().unwrap().max_body_size = Some(2048);
+            let rules = cfg.cache.rules.as_mut().unwrap();
+            let mut rule = (*rules[USER_PATH]).clone();
+            rule.max_body_size = Some(1024);
+            rules.insert(USER_PATH.to_string(), Arc::new(rule));
+        })
+        .start()
+}
+
+/// Gets the path twice, checking both answers carry the whole body; returns the origin fills.
+async fn get_twice(cache: &RouterCache, uri: &str, body_len: usize) -> usize {
+    cache.upstream.reset_calls();
+    for _ in 0..2 {
+        let request = Request::get(uri).header("accept-encoding", "identity").body(Body::empty()).unwrap();
+        let resp = cache.send(request).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.len(), body_len, "{} is answered whole", uri);
+    }
+    cache.upstream.fills()
+}
+
+/// Test that answers over the rule's limit are served but not stored and counted, while
+/// answers under it and under the storage default of other rules are stored.
+#[tokio::test]
+async fn test_oversized_answers_are_served_but_not_stored() {
+    let cache = start();
+
+    cache.upstream.set_response(USER_PATH, Response::ok(vec![b'a'; 1500]));
+    cache.upstream.set_response(CLIENT_PATH, Response::ok(vec![b'a'; 1500]));
+    let rejected = metrics::rejected_oversize();
+    assert_eq!(get_twice(&cache, "/api/v1/user?user[id]=1", 1500).await, 2, "over the rule limit");
+    assert!(metrics::rejected_oversize() >= rejected + 2);
+    assert_eq!(get_twice(&cache, "/api/v1/client?user[id]=1", 1500).await, 1, "under the storage default");
+
+    cache.upstream.set_response(USER_PATH, Response::ok(vec![b'a'; 1000]));
+    cache.upstream.set_response(CLIENT_PATH, Response::ok(vec![b'a'; 3000]));
+    assert_eq!(get_twice(&cache, "/api/v1/user?user[id]=2", 1000).await, 1, "under the rule limit");
+    assert_eq!(get_twice(&cache, "/api/v1/client?user[id]=2", 3000).await, 2, "over the storage default");
+}
+
+/// Test that an encoded answer is held to the limit by its decoded length.
+#[tokio::test]
+async fn test_encoded_answers_are_limited_by_decoded_length() {
+    let cache = start();
+
+    let gzip = |len: usize| {
+        let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        enc.write_all(&vec![b'a'; len]).unwrap();
+        enc.finish().unwrap()
+    };
+    let encoded = |body: Vec<u8>| {
+        Response::new(200, vec![("Content-Encoding".to_string(), "gzip".to_string())], body)
+    };
+
+    let big = gzip(8000);
+    assert!(big.len() < 1024, "the encoded body fits the limit");
+    cache.upstream.set_response(USER_PATH, encoded(big.clone()));
+    assert_eq!(get_twice(&cache, "/api/v1/user?user[id]=3", big.len()).await, 2);
+
+    let small = gzip(900);
+    cache.upstream.set_response(USER_PATH, encoded(small.clone()));
+    assert_eq!(get_twice(&cache, "/api/v1/user?user[id]=4", small.len()).await, 1);
+}
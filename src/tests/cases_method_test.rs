@@ -0,0 +1,93 @@
+// Integration tests for the methods a rule caches (`methods`).
+//
+// The upstream is a mock, so the tests see which calls reach the origin as proxies or fills
+// and how many entries are stored.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+
+use crate::config::Config;
+use crate::controller::metrics;
+use crate::db::Storage;
+use crate::tests::support::RouterCache;
+
+const USER_PATH: &str = "/api/v1/user";
+
+/// Cache with the user rule caching the `methods` given.
+fn start(methods: Option<Vec<String>>) -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| {
+            let rules = cfg.cache.rules.as_mut().unwrap();
+            let mut rule = (*rules[USER_PATH]).clone();
+            rule.methods = methods;
+            rules.insert(USER_PATH.to_string(), Arc::new(rule));
+        })
+        .start()
+}
+
+async fn call(cache: &RouterCache, method: &str, uri: &str, body: &'static str) -> StatusCode {
+    let resp = cache.send(Request::builder().method(method).uri(uri).body(Body::from(body)).unwrap()).await;
+    let status = resp.status();
+    let _ = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    status
+}
+
+/// Test that POST, PUT, PATCH and DELETE on a ruled path reach the origin with their body
+/// every time, are counted, and neither look up nor populate the storage.
+#[tokio::test]
+async fn test_unlisted_methods_on_ruled_paths_are_proxied() {
+    let cache = start(None);
+
+    for method in ["POST", "PUT", "PATCH", "DELETE"] {
+        let counted = metrics::ruled_non_get_requests(method);
+        for _ in 0..2 {
+            assert_eq!(call(&cache, method, "/api/v1/user?user[id]=1", r#"{"name":"a"}"#).await, StatusCode::OK);
+            let last = cache.upstream.last_call().unwrap();
+            assert_eq!((last.method.as_str(), last.body.as_deref()), (method, Some(br#"{"name":"a"}"#.as_slice())));
+        }
+        assert!(metrics::ruled_non_get_requests(method) >= counted + 2, "{} is counted", method);
+    }
+    assert_eq!(cache.upstream.proxied(), 8);
+    assert_eq!(cache.upstream.fills(), 0);
+    assert_eq!(cache.db.stat().1, 0, "nothing is stored");
+}
+
+/// Test that HEAD is cached by default and proxied by a rule listing only GET, being counted
+/// only when it is proxied.
+#[tokio::test]
+async fn test_head_is_cached_unless_the_rule_lists_only_get() {
+    let cache = start(None);
+    let counted = metrics::ruled_non_get_requests("HEAD");
+    for _ in 0..2 {
+        assert_eq!(call(&cache, "HEAD", "/api/v1/user?user[id]=2", "").await, StatusCode::OK);
+    }
+    assert_eq!((cache.upstream.fills(), cache.upstream.proxied()), (1, 0));
+    assert_eq!(cache.db.stat().1, 1);
+    assert_eq!(metrics::ruled_non_get_requests("HEAD"), counted, "a cached HEAD is not counted");
+
+    let cache = start(Some(vec!["GET".to_string()]));
+    for _ in 0..2 {
+        assert_eq!(call(&cache, "HEAD", "/api/v1/user?user[id]=2", "").await, StatusCode::OK);
+    }
+    assert_eq!((cache.upstream.fills(), cache.upstream.proxied()), (0, 2));
+    assert_eq!(cache.db.stat().1, 0);
+    assert_eq!(metrics::ruled_non_get_requests("HEAD"), counted + 2);
+    assert_eq!(call(&cache, "GET", "/api/v1/user?user[id]=2", "").await, StatusCode::OK);
+    assert_eq!((cache.upstream.fills(), cache.db.stat().1), (1, 1), "GET is still cached");
+}
+
+/// Test that `methods` must list GET, and may add HEAD.
+#[test]
+fn test_methods_must_list_get() {
+    let yaml = |methods: &str| {
+        format!(
+            "cache:\n  env: test\n  enabled: true\n  upstream:\n    backend:\n      id: main\n      enabled: true\n      scheme: http\n      host: main.local:8080\n      timeout: 10s\n      max_timeout: 1m\n  rules:\n    /api/v1/user:\n      methods: {methods}\n      cache_key:\n        query: []\n        headers: []\n      cache_value:\n        headers: []\n"
+        )
+    };
+    assert!(Config::from_yaml(&yaml("[GET]")).is_ok());
+    assert!(Config::from_yaml(&yaml("[GET, HEAD]")).is_ok());
+    assert!(Config::from_yaml(&yaml("[HEAD]")).is_err());
+    assert!(Config::from_yaml(&yaml("[GET, DELETE]")).is_err());
+}
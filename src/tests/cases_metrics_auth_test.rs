@@ -0,0 +1,127 @@
+// Integration tests for basic auth on `/metrics` (`metrics.auth`).
+//
+// Each test reads its password from an environment variable of its own, so tests running in
+// parallel do not see each other's credentials.
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Method, Request, StatusCode};
+use axum::response::Response;
+use base64::prelude::*;
+
+use crate::config::{self, MetricsAuth};
+use crate::controller::PrometheusMetricsController;
+use crate::tests::support::RouterCache;
+
+const USERNAME: &str = "prometheus";
+
+fn start(password_env: Option<&str>) -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| {
+            cfg.cache.metrics.as_mut().unwrap().auth = password_env.map(|env| MetricsAuth {
+                username: USERNAME.to_string(),
+                password_env: env.to_string(),
+            });
+        })
+        .start()
+        .mount(|cache| PrometheusMetricsController::new(cache.cfg.clone()))
+}
+
+async fn scrape(cache: &RouterCache, method: Method, credentials: Option<(&str, &str)>) -> Response {
+    let mut request = Request::builder().method(method).uri("/metrics");
+    if let Some((user, password)) = credentials {
+        let encoded = BASE64_STANDARD.encode(format!("{}:{}", user, password));
+        request = request.header(header::AUTHORIZATION, format!("Basic {}", encoded));
+    }
+    cache.send(request.body(Body::empty()).unwrap()).await
+}
+
+async fn body(resp: Response) -> String {
+    String::from_utf8(to_bytes(resp.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap()
+}
+
+/// Test that without `metrics.auth` the endpoint answers anyone, on GET and HEAD.
+#[tokio::test]
+async fn test_metrics_without_auth_are_open() {
+    let cache = start(None);
+
+    let resp = scrape(&cache, Method::GET, None).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(body(resp).await.contains("metrics_auth_failures"));
+
+    let resp = scrape(&cache, Method::HEAD, None).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(body(resp).await.is_empty(), "HEAD must not carry the body");
+}
+
+/// Test that the configured credentials are let in, on GET and HEAD.
+#[tokio::test]
+async fn test_metrics_with_good_credentials() {
+    std::env::set_var("ADVCACHE_TEST_METRICS_PASSWORD_GOOD", "s3cret");
+    let cache = start(Some("ADVCACHE_TEST_METRICS_PASSWORD_GOOD"));
+
+    let resp = scrape(&cache, Method::GET, Some((USERNAME, "s3cret"))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(body(resp).await.contains("cache_hits"));
+
+    let resp = scrape(&cache, Method::HEAD, Some((USERNAME, "s3cret"))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+/// Test that missing or wrong credentials are challenged, HEAD included, and counted.
+#[tokio::test]
+async fn test_metrics_with_bad_credentials() {
+    std::env::set_var("ADVCACHE_TEST_METRICS_PASSWORD_BAD", "s3cret");
+    let cache = start(Some("ADVCACHE_TEST_METRICS_PASSWORD_BAD"));
+    let failures_before = count_of(&crate::controller::metrics::metrics_text(), "metrics_auth_failures");
+
+    for credentials in [None, Some((USERNAME, "wrong")), Some(("admin", "s3cret")), Some((USERNAME, "s3cret!"))] {
+        let resp = scrape(&cache, Method::GET, credentials).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{:?}", credentials);
+        assert_eq!(resp.headers()[header::WWW_AUTHENTICATE], r#"Basic realm="metrics", charset="UTF-8""#);
+        assert!(!body(resp).await.contains("cache_hits"));
+    }
+    let resp = scrape(&cache, Method::HEAD, Some((USERNAME, "wrong"))).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let failures = count_of(&crate::controller::metrics::metrics_text(), "metrics_auth_failures");
+    assert!(failures >= failures_before + 5, "{} -> {}", failures_before, failures);
+}
+
+/// Test that repeated failures lock the endpoint out, good credentials included.
+#[tokio::test]
+async fn test_metrics_lock_out_after_repeated_failures() {
+    std::env::set_var("ADVCACHE_TEST_METRICS_PASSWORD_LOCKOUT", "s3cret");
+    let cache = start(Some("ADVCACHE_TEST_METRICS_PASSWORD_LOCKOUT"));
+
+    for _ in 0..10 {
+        let resp = scrape(&cache, Method::GET, Some((USERNAME, "guess"))).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+    let resp = scrape(&cache, Method::GET, Some((USERNAME, "s3cret"))).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = resp.headers()[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+    assert!((1..=60).contains(&retry_after));
+}
+
+/// Test that a config naming an unset password variable fails to load.
+#[test]
+fn test_metrics_auth_requires_the_password_env() {
+    let yaml = r#"
+cache:
+  env: test
+  enabled: true
+  metrics:
+    enabled: true
+    auth:
+      username: prometheus
+      password_env: ADVCACHE_TEST_METRICS_PASSWORD_UNSET
+"#;
+    let err = config::Config::from_yaml(yaml).unwrap_err();
+    assert!(format!("{:#}", err).contains("ADVCACHE_TEST_METRICS_PASSWORD_UNSET"), "{:#}", err);
+}
+
+fn count_of(text: &str, metric: &str) -> u64 {
+    text.lines()
+        .find_map(|line| line.strip_prefix(metric)?.strip_prefix(' ')?.parse().ok())
+        .unwrap_or(0)
+}
@@ -0,0 +1,119 @@
+// Integration tests for a backend broken by its config (`upstream::misconfig`).
+//
+// The cache runs over a real backend whose host does not resolve, next to one pointing at a
+// closed port for the transient failure to compare with. Entries stored
+// beforehand stand for what the cache held when the bad config rolled out.
+
+use std::time::{Duration, Instant};
+
+use axum::http::{HeaderMap, StatusCode};
+
+use crate::config::ConfigTrait;
+use crate::controller::cache::ERROR_CODE_UPSTREAM_MISCONFIGURED;
+use crate::controller::{metrics, BackendDrainController};
+use crate::db::Storage;
+use crate::model::{Entry, Response as ModelResponse};
+use crate::tests::support::RouterCache;
+use crate::upstream::misconfig::MisconfigKind;
+use crate::upstream::{BackendImpl, Upstream};
+
+const PATH: &str = "/api/v1/user";
+const CACHED_BODY: &str = "{\"id\":1}";
+
+/// Cache and backends endpoints over a backend of its own id at `host`, as config error metrics
+/// are global, with user 1 already stored.
+fn start(id: &str, host: &str) -> RouterCache<BackendImpl> {
+    let cache = RouterCache::builder()
+        .config(|cfg| {
+            let backend = cfg.cache.upstream.as_mut().unwrap().backend.as_mut().unwrap();
+            backend.id = Some(id.to_string());
+            backend.host = Some(host.to_string());
+        })
+        .start_backend()
+        .mount(|cache| BackendDrainController::new(cache.upstream.clone(), None));
+
+    let queries = vec![(b"user[id]".to_vec(), b"1".to_vec())];
+    let entry = Entry::new(cache.cfg.rule(PATH).unwrap(), &queries, &[]);
+    let resp = ModelResponse { status: 200, headers: Vec::new(), body: CACHED_BODY.as_bytes().to_vec() };
+    entry.set_payload(&queries, &[], &resp);
+    assert!(cache.db.set(entry));
+    cache
+}
+
+async fn user(cache: &RouterCache<BackendImpl>, id: u32) -> (StatusCode, HeaderMap, String) {
+    let (parts, body) = cache.get(&format!("{}?user[id]={}", PATH, id)).await.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    (parts.status, parts.headers, String::from_utf8_lossy(&body).into_owned())
+}
+
+async fn config_error(cache: &RouterCache<BackendImpl>) -> serde_json::Value {
+    let (_, backends) = cache.get_json("/advcache/upstream/backends").await;
+    backends["backends"][0]["config_error"].clone()
+}
+
+/// Test that with an unresolvable backend host, misses fail with a problem naming the
+/// misconfiguration, surfaced by the gauge and the backends endpoint, while hits are served;
+/// it stays so once the health observer marks the backend down.
+#[tokio::test]
+async fn test_unresolvable_host_is_reported_and_hits_served() {
+    let cache = start("misconfig-dns", "advcache-misconfigured.invalid");
+    assert_eq!(metrics::upstream_config_error("misconfig-dns"), Some(false));
+
+    let (status, headers, body) = user(&cache, 2).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(headers["content-type"], "application/problem+json");
+    let problem: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(problem["error_code"], ERROR_CODE_UPSTREAM_MISCONFIGURED);
+    assert_eq!(problem["status"], 503);
+
+    assert_eq!(cache.upstream.config_error().map(|m| m.kind), Some(MisconfigKind::Dns));
+    assert_eq!(metrics::upstream_config_error("misconfig-dns"), Some(true));
+    let config_error = config_error(&cache).await;
+    assert!(config_error.as_str().is_some_and(|e| e.starts_with("dns: ")), "{}", config_error);
+    assert!(metrics::metrics_text().contains("upstream_config_error{backend=\"misconfig-dns\"} 1"));
+
+    let (status, _, body) = user(&cache, 1).await;
+    assert_eq!(status, StatusCode::OK, "hits are served");
+    assert_eq!(body, CACHED_BODY);
+
+    // Health probes fail the same way and mark the backend down; misses still say why.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while cache.upstream.is_alive() {
+        assert!(Instant::now() < deadline, "the health observer must mark the backend down");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let (status, _, body) = user(&cache, 3).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(body.contains(ERROR_CODE_UPSTREAM_MISCONFIGURED), "{}", body);
+    assert_eq!(user(&cache, 1).await.0, StatusCode::OK);
+}
+
+/// Test that a backend URL that cannot be requested is reported from the start.
+#[tokio::test]
+async fn test_invalid_url_is_reported_at_build_time() {
+    let cache = start("misconfig-uri", "bad host");
+
+    assert_eq!(cache.upstream.config_error().map(|m| m.kind), Some(MisconfigKind::InvalidUri));
+    assert_eq!(metrics::upstream_config_error("misconfig-uri"), Some(true));
+    let (status, _, body) = user(&cache, 2).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(body.contains(ERROR_CODE_UPSTREAM_MISCONFIGURED), "{}", body);
+    assert_eq!(user(&cache, 1).await.0, StatusCode::OK);
+}
+
+/// Test that a refused connection is a transient failure: no error code, no config error.
+#[tokio::test]
+async fn test_refused_connection_is_transient() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let closed = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    let cache = start("misconfig-refused", &closed);
+
+    let (status, headers, body) = user(&cache, 2).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(headers["content-type"], "application/json");
+    assert!(!body.contains(ERROR_CODE_UPSTREAM_MISCONFIGURED), "{}", body);
+    assert!(cache.upstream.config_error().is_none());
+    assert_eq!(metrics::upstream_config_error("misconfig-refused"), Some(false));
+    assert!(config_error(&cache).await.is_null());
+}
@@ -0,0 +1,158 @@
+// Integration tests for negative caching of 404 and 5xx answers (`cache_value.negative_ttl`).
+//
+// The negative rule is a copy of the user rule under a path of its own, next to the user rule
+// left as configured.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{self, Config, ConfigTrait};
+use crate::controller::{metrics, InvalidateController};
+use crate::db::storage::{Map, Storage as MapStorage};
+use crate::model::{Entry, Response as ModelResponse};
+use crate::tests::support::RouterCache;
+use crate::time;
+use crate::upstream::testing::MockUpstream;
+use crate::upstream::Response;
+use crate::workers::RefreshBackend;
+
+const PLAIN_PATH: &str = "/api/v1/user";
+const NEGATIVE_PATH: &str = "/api/v1/negative";
+
+/// Adds a copy of the user rule under `NEGATIVE_PATH` storing error answers for `negative_ttl`.
+fn add_negative_rule(cfg: &mut Config, negative_ttl: Duration) {
+    let rules = cfg.cache.rules.as_mut().unwrap();
+    let mut rule = (*rules[PLAIN_PATH]).clone();
+    rule.path = Some(NEGATIVE_PATH.to_string());
+    rule.path_bytes = Some(NEGATIVE_PATH.as_bytes().to_vec());
+    rule.cache_value.negative_ttl = Some(negative_ttl);
+    rules.insert(NEGATIVE_PATH.to_string(), Arc::new(rule));
+}
+
+fn start(negative_ttl: Duration) -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| add_negative_rule(cfg, negative_ttl))
+        .start()
+        .mount(|cache| InvalidateController::new(cache.cfg.clone(), cache.db.clone()))
+}
+
+async fn get(cache: &RouterCache, uri: &str) -> StatusCode {
+    let resp = cache.get(uri).await;
+    let status = resp.status();
+    let _ = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    status
+}
+
+async fn get_user(cache: &RouterCache, path: &str, id: u32) -> StatusCode {
+    get(cache, &format!("{}?user[id]={}", path, id)).await
+}
+
+/// Test that 404 and 5xx answers of a rule with `negative_ttl` are answered from memory and
+/// counted as negative hits, while a rule without it sends every request to the origin.
+#[tokio::test]
+async fn test_error_answers_are_stored() {
+    let cache = start(Duration::from_secs(60));
+    cache.upstream.set_response(NEGATIVE_PATH, Response::empty(404));
+    cache.upstream.set_response(PLAIN_PATH, Response::empty(404));
+
+    let negative_hits = metrics::cache_negative_hits();
+    for _ in 0..3 {
+        assert_eq!(get_user(&cache, NEGATIVE_PATH, 1).await, StatusCode::NOT_FOUND);
+    }
+    assert_eq!(cache.upstream.fills(), 1);
+    assert!(metrics::cache_negative_hits() >= negative_hits + 2);
+    let stored = cache.stored(NEGATIVE_PATH, &[("user[id]", "1")]).expect("the 404 is stored");
+    assert!(stored.is_negative());
+    assert_eq!(stored.origin_ttl(), Some(Duration::from_secs(60)));
+
+    cache.upstream.set_response(NEGATIVE_PATH, Response::empty(503));
+    for _ in 0..2 {
+        assert_eq!(get_user(&cache, NEGATIVE_PATH, 2).await, StatusCode::SERVICE_UNAVAILABLE);
+    }
+    assert_eq!(cache.upstream.fills(), 2);
+
+    cache.upstream.set_response(NEGATIVE_PATH, Response::empty(403));
+    for _ in 0..2 {
+        assert_eq!(get_user(&cache, NEGATIVE_PATH, 3).await, StatusCode::FORBIDDEN);
+    }
+    assert_eq!(cache.upstream.fills(), 4, "other statuses are not stored");
+
+    for _ in 0..2 {
+        assert_eq!(get_user(&cache, PLAIN_PATH, 1).await, StatusCode::NOT_FOUND);
+    }
+    assert_eq!(cache.upstream.fills(), 6);
+    assert!(cache.stored(PLAIN_PATH, &[("user[id]", "1")]).is_none());
+}
+
+/// Test that a negative entry past its TTL is a miss, its key filled again from the origin
+/// and stored as a positive entry once the origin answers 200.
+#[tokio::test]
+async fn test_negative_entries_expire() {
+    let _clock = time::start(Duration::from_millis(1));
+    let cache = start(Duration::from_millis(50));
+    cache.upstream.set_response(NEGATIVE_PATH, Response::empty(404));
+    assert_eq!(get_user(&cache, NEGATIVE_PATH, 1).await, StatusCode::NOT_FOUND);
+    assert_eq!(get_user(&cache, NEGATIVE_PATH, 1).await, StatusCode::NOT_FOUND);
+    assert_eq!(cache.upstream.fills(), 1);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    cache.upstream.set_response(NEGATIVE_PATH, Response::ok("{}"));
+    assert_eq!(get_user(&cache, NEGATIVE_PATH, 1).await, StatusCode::OK);
+    assert_eq!(cache.upstream.fills(), 2);
+    let stored = cache.stored(NEGATIVE_PATH, &[("user[id]", "1")]).expect("the 200 is stored");
+    assert!(!stored.is_negative());
+    assert_eq!(stored.origin_ttl(), None);
+}
+
+/// Test that invalidation purges negative entries, whether it removes or marks outdated.
+#[tokio::test]
+async fn test_invalidation_purges_negative_entries() {
+    let _clock = time::start(Duration::from_millis(1));
+    let cache = start(Duration::from_secs(60));
+    cache.upstream.set_response(NEGATIVE_PATH, Response::empty(404));
+    for id in [1, 2] {
+        assert_eq!(get_user(&cache, NEGATIVE_PATH, id).await, StatusCode::NOT_FOUND);
+    }
+
+    let remove = format!("/advcache/invalidate?_path={}&user[id]=1&_remove=1", NEGATIVE_PATH);
+    assert_eq!(get(&cache, &remove).await, StatusCode::OK);
+    assert!(cache.stored(NEGATIVE_PATH, &[("user[id]", "1")]).is_none());
+
+    let mark = format!("/advcache/invalidate?_path={}&user[id]=2", NEGATIVE_PATH);
+    assert_eq!(get(&cache, &mark).await, StatusCode::OK);
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    cache.upstream.set_response(NEGATIVE_PATH, Response::ok("{}"));
+    assert_eq!(get_user(&cache, NEGATIVE_PATH, 2).await, StatusCode::OK, "the outdated 404 is not served");
+    assert_eq!(cache.upstream.fills(), 3);
+}
+
+/// Test that the lifetime manager removes a negative entry past its TTL instead of refreshing
+/// it, and leaves one picked ahead of it alone.
+#[tokio::test]
+async fn test_lifetime_manager_removes_negative_entries() {
+    let _clock = time::start(Duration::from_millis(1));
+    let mut cfg = config::new_test_config();
+    add_negative_rule(&mut cfg, Duration::from_secs(60));
+    let shutdown = CancellationToken::new();
+    let upstream = MockUpstream::new();
+    let map = Arc::new(Map::new(shutdown.clone(), cfg.clone()));
+    let storage = MapStorage::new(shutdown.clone(), cfg.clone(), upstream.clone(), map).expect("storage must start");
+
+    let queries = vec![(b"user[id]".to_vec(), b"1".to_vec())];
+    let entry = Entry::new(cfg.rule(NEGATIVE_PATH).unwrap(), &queries, &[]);
+    entry.set_payload(&queries, &[], &ModelResponse { status: 404, headers: vec![], body: vec![] });
+    assert!(entry.is_negative());
+    assert!(storage.set(entry.clone()));
+
+    storage.on_ttl(&entry).await.expect("an early pick is fine");
+    assert!(storage.get_by_key(entry.key()).is_some(), "kept until its TTL is over");
+
+    entry.set_refreshed_at_for_tests(1);
+    storage.on_ttl(&entry).await.expect("removal must succeed");
+    assert!(storage.get_by_key(entry.key()).is_none());
+    assert_eq!(upstream.refreshes(), 0, "negative entries are not refreshed");
+    shutdown.cancel();
+}
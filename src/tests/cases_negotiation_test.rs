@@ -0,0 +1,115 @@
+// Integration tests for the `Accept` negotiation of the admin endpoints.
+//
+// `/advcache/config`, `/advcache/shards` and `/advcache/jobs` are requested as JSON, YAML and
+// plain text, and with media types they do not render.
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+
+use crate::controller::{InvalidateController, JobsController, ShardsController, ShowConfigController};
+use crate::tests::support::RouterCache;
+
+fn start() -> RouterCache {
+    RouterCache::start()
+        .mount(|cache| ShowConfigController::new(cache.cfg.clone()))
+        .mount(|cache| ShardsController::new(cache.cfg.clone(), cache.db.clone()))
+        .mount(|cache| JobsController::new(cache.db.clone()))
+        .mount(|cache| InvalidateController::new(cache.cfg.clone(), cache.db.clone()))
+}
+
+/// Requests `uri` with the `Accept` header, if any; returns the status, content type and body.
+async fn get(cache: &RouterCache, uri: &str, accept: Option<&str>) -> (StatusCode, String, String) {
+    let mut req = Request::get(uri);
+    if let Some(accept) = accept {
+        req = req.header("accept", accept);
+    }
+    let resp = cache.send(req.body(Body::empty()).unwrap()).await;
+    let status = resp.status();
+    let content_type = resp.headers().get("content-type").map(|v| v.to_str().unwrap().to_string()).unwrap_or_default();
+    assert_eq!(resp.headers().get("vary").map(|v| v.to_str().unwrap()), Some("accept"));
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, content_type, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// Test that the config is rendered as JSON by default and for unknown media types, and as
+/// YAML when asked; having no table, `text/plain` is answered with JSON.
+#[tokio::test]
+async fn test_config_formats() {
+    let cache = start();
+
+    let (status, content_type, json) = get(&cache, "/advcache/config", None).await;
+    assert_eq!((status, content_type.as_str()), (StatusCode::OK, "application/json; charset=utf-8"));
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert!(json["cache"]["rules"]["/api/v1/user"].is_object(), "{}", json);
+
+    let (status, content_type, yaml) = get(&cache, "/advcache/config", Some("application/yaml")).await;
+    assert_eq!((status, content_type.as_str()), (StatusCode::OK, "application/yaml; charset=utf-8"));
+    let yaml: serde_json::Value = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(yaml, json, "YAML renders the same document");
+
+    for accept in ["text/plain", "image/png", "application/json"] {
+        let (status, content_type, body) = get(&cache, "/advcache/config", Some(accept)).await;
+        assert_eq!((status, content_type.as_str()), (StatusCode::OK, "application/json; charset=utf-8"), "{}", accept);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap(), json);
+    }
+}
+
+/// Test that the shards are rendered as JSON, YAML, and as a table of the top shards.
+#[tokio::test]
+async fn test_shards_formats() {
+    let cache = start();
+
+    let (status, content_type, json) = get(&cache, "/advcache/shards?top=3", Some("text/csv")).await;
+    assert_eq!((status, content_type.as_str()), (StatusCode::OK, "application/json; charset=utf-8"));
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["top"].as_array().unwrap().len(), 3);
+
+    let (_, content_type, yaml) = get(&cache, "/advcache/shards?top=3", Some("application/x-yaml")).await;
+    assert_eq!(content_type, "application/yaml; charset=utf-8");
+    let yaml: serde_json::Value = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(yaml["shards"], json["shards"]);
+    assert_eq!(yaml["top"].as_array().unwrap().len(), 3);
+
+    let (status, content_type, text) = get(&cache, "/advcache/shards?top=3", Some("text/plain, application/json;q=0.9")).await;
+    assert_eq!((status, content_type.as_str()), (StatusCode::OK, "text/plain; charset=utf-8"));
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 4, "a header and a line per shard:\n{}", text);
+    let header: Vec<&str> = lines[0].split_whitespace().collect();
+    assert_eq!(&header[..4], ["id", "len", "bytes", "lock.read.samples"]);
+    for line in &lines[1..] {
+        assert_eq!(line.split_whitespace().count(), header.len(), "{}", text);
+    }
+}
+
+/// Test that the jobs are rendered as JSON, YAML, and as a table of the jobs.
+#[tokio::test]
+async fn test_jobs_formats() {
+    let cache = start();
+    let (_, table, _) = get(&cache, "/advcache/jobs", Some("text/plain")).await;
+    assert_eq!(table, "text/plain; charset=utf-8");
+    for id in [1, 2] {
+        let uri = format!("/advcache/invalidate?_path=/api/v1/user&user[id]={}&_remove=1", id);
+        assert_eq!(cache.get(&uri).await.status(), StatusCode::OK);
+    }
+
+    let (status, content_type, json) = get(&cache, "/advcache/jobs", None).await;
+    assert_eq!((status, content_type.as_str()), (StatusCode::OK, "application/json; charset=utf-8"));
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["jobs"].as_array().unwrap().len(), 2);
+
+    let (_, content_type, yaml) = get(&cache, "/advcache/jobs", Some("application/yaml")).await;
+    assert_eq!(content_type, "application/yaml; charset=utf-8");
+    assert_eq!(serde_yaml::from_str::<serde_json::Value>(&yaml).unwrap(), json);
+
+    let (_, _, text) = get(&cache, "/advcache/jobs", Some("text/plain")).await;
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", text);
+    assert!(lines[0].starts_with("id  op          target        state"), "{}", text);
+    assert!(lines[1].starts_with("1   invalidate  /api/v1/user  done"), "{}", text);
+
+    let (_, content_type, one) = get(&cache, "/advcache/jobs/2", Some("application/yaml")).await;
+    assert_eq!(content_type, "application/yaml; charset=utf-8");
+    assert_eq!(serde_yaml::from_str::<serde_json::Value>(&one).unwrap(), json["jobs"][1]);
+    let (_, content_type, _) = get(&cache, "/advcache/jobs/2", Some("text/plain")).await;
+    assert_eq!(content_type, "application/json; charset=utf-8", "a single job has no table");
+}
@@ -0,0 +1,76 @@
+// Integration tests for clients revalidating cached responses with `If-None-Match` and
+// `If-Modified-Since`.
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+
+use crate::controller::metrics;
+use crate::tests::support::RouterCache;
+use crate::upstream::testing::MockUpstream;
+use crate::upstream::Response;
+
+const PATH: &str = "/api/v1/user";
+const BODY: &str = "{\"id\":1}";
+
+fn start(origin: Response) -> RouterCache {
+    let upstream = MockUpstream::new();
+    upstream.set_response(PATH, origin);
+    RouterCache::builder().upstream(upstream).start()
+}
+
+/// Requests user 1 with `headers`; answers the status, headers and body.
+async fn get(cache: &RouterCache, headers: &[(&str, &str)]) -> (StatusCode, axum::http::HeaderMap, Vec<u8>) {
+    let mut req = Request::get(format!("{}?user[id]=1", PATH));
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    let (parts, body) = cache.send(req.body(Body::empty()).unwrap()).await.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX).await.unwrap().to_vec();
+    (parts.status, parts.headers, body)
+}
+
+/// Test that the miss and the hit carry the same generated ETag, and that a hit revalidated
+/// with it is answered 304 with an empty body and a zero Content-Length, still as a hit.
+#[tokio::test]
+async fn test_if_none_match_hit_is_not_modified() {
+    let cache = start(Response::ok(BODY).with_header("Content-Type", "application/json"));
+
+    let (status, miss, body) = get(&cache, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, BODY.as_bytes());
+    let etag = miss.get("etag").expect("the miss carries the ETag").to_str().unwrap().to_string();
+
+    let (status, hit, _) = get(&cache, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(hit.get("etag").unwrap(), etag.as_str());
+
+    let hits = metrics::cache_hits();
+    let (status, headers, body) = get(&cache, &[("If-None-Match", etag.as_str())]).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert!(body.is_empty());
+    assert_eq!(headers.get("content-length").unwrap(), "0");
+    assert_eq!(headers.get("etag").unwrap(), etag.as_str());
+    assert!(metrics::cache_hits() > hits, "a 304 is counted as a hit");
+
+    let (status, _, body) = get(&cache, &[("If-None-Match", "\"0000000000000000\"")]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, BODY.as_bytes());
+    assert_eq!(cache.upstream.fills(), 1);
+}
+
+/// Test that the origin's ETag and Last-Modified, when stored, are the validators the client
+/// revalidates with.
+#[tokio::test]
+async fn test_origin_validators_are_compared() {
+    let origin = Response::ok(BODY)
+        .with_header("ETag", "\"v1\"")
+        .with_header("Last-Modified", "Sat, 17 Oct 2026 10:00:00 GMT");
+    let cache = start(origin);
+
+    let (_, miss, _) = get(&cache, &[]).await;
+    assert_eq!(miss.get("etag").unwrap(), "\"v1\"");
+
+    assert_eq!(get(&cache, &[("If-None-Match", "\"v1\"")]).await.0, StatusCode::NOT_MODIFIED);
+    assert_eq!(get(&cache, &[("If-Modified-Since", "Sat, 17 Oct 2026 12:00:00 GMT")]).await.0, StatusCode::NOT_MODIFIED);
+    assert_eq!(get(&cache, &[("If-Modified-Since", "Fri, 16 Oct 2026 12:00:00 GMT")]).await.0, StatusCode::OK);
+}
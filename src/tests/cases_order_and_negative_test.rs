@@ -1,7 +1,7 @@
 // Integration tests for query order insensitivity and negative whitelist changes.
 
 use crate::support::{
-    assert_equal, assert_ok, cache_addr, do_json, hash, init_test_harness, new_namespace, H,
+    assert_equal, assert_ok, cache_addr, do_json, hash, Capability, init_test_harness, new_namespace, H,
 };
 use std::collections::HashMap;
 
@@ -22,7 +22,7 @@ fn build_raw_query(order: &[&str], vals: &HashMap<String, String>) -> String {
 
 #[tokio::test]
 async fn test_query_order_insensitive() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_query_order_insensitive");
     let base = cache_addr().await;
@@ -71,7 +71,7 @@ async fn test_query_order_insensitive() {
 
 #[tokio::test]
 async fn test_whitelist_negative_change_one_key_changes_body() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_whitelist_negative_change_one_key_changes_body");
     let base = cache_addr().await;
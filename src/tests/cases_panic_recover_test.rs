@@ -0,0 +1,198 @@
+// Integration tests for the panic recovery middleware.
+//
+// A test controller panics on purpose behind the server middlewares; the tests check the
+// problem+json answer, the log event and that the connection keeps serving afterwards.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tower::ServiceExt;
+use tracing::field::{Field, Visit};
+use tracing::instrument::WithSubscriber;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::prelude::*;
+
+use crate::controller::metrics;
+use crate::middleware::compression_middleware::CompressionMiddleware;
+use crate::middleware::middleware::Middleware;
+use crate::middleware::recover_middleware::{join_request_task, PanicRecoverMiddleware};
+
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Collects the fields of every event as `name=value` lines.
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<String>>>);
+
+struct Collector<'a>(&'a mut Vec<String>);
+
+impl Visit for Collector<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.push(format!("{}={:?}", field.name(), value));
+    }
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push(format!("{}={}", field.name(), value));
+    }
+}
+
+impl<S: Subscriber> Layer<S> for Capture {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        event.record(&mut Collector(&mut self.0.lock().unwrap()));
+    }
+}
+
+/// Test controller: `/panic/{id}` panics in the handler, `/task` in a task it spawns.
+fn router() -> Router {
+    let router = Router::new()
+        .route("/ok", get(|| async { "ok" }))
+        .route(
+            "/panic/:id",
+            get(|| async {
+                if true {
+                    panic!("deliberate handler panic");
+                }
+                "unreachable"
+            }),
+        )
+        .route(
+            "/task",
+            get(|| async {
+                let task = tokio::spawn(async {
+                    if true {
+                        panic!("deliberate task panic");
+                    }
+                    "unreachable"
+                });
+                match join_request_task("test_task", task).await {
+                    Ok(body) => (StatusCode::OK, body.to_string()).into_response(),
+                    Err(e) => (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response(),
+                }
+            }),
+        );
+
+    let middlewares: Vec<Box<dyn Middleware>> = vec![
+        Box::new(PanicRecoverMiddleware::new()),
+        Box::new(CompressionMiddleware::new(None)),
+    ];
+    middlewares.iter().rev().fold(router, |router, m| m.apply(router))
+}
+
+/// Test that a handler panic is answered with a problem+json 500 carrying the request id,
+/// logged with the request context and counted under the matched route.
+#[tokio::test]
+async fn test_handler_panic_answers_500() {
+    let capture = Capture::default();
+    let subscriber = tracing_subscriber::registry().with(capture.clone());
+    let before = metrics::handler_panics("/panic/:id");
+
+    let request = Request::get("/panic/7")
+        .header("x-request-id", "req-panic-1")
+        .body(Body::empty())
+        .unwrap();
+    let resp = router().oneshot(request).with_subscriber(subscriber).await.unwrap();
+
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(resp.headers()["content-type"], "application/problem+json");
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(problem["status"], 500);
+    assert_eq!(problem["request_id"], "req-panic-1");
+    assert!(
+        !String::from_utf8_lossy(&body).contains("deliberate"),
+        "the panic message must not reach the client"
+    );
+
+    let log = capture.0.lock().unwrap().join("\n");
+    for field in [
+        "event=handler_panicked",
+        "method=GET",
+        "path=/panic/7",
+        "handler=/panic/:id",
+        "request_id=req-panic-1",
+        "panic=deliberate handler panic",
+        "backtrace=",
+    ] {
+        assert!(log.contains(field), "missing {:?} in {}", field, log);
+    }
+    assert_eq!(metrics::handler_panics("/panic/:id"), before + 1);
+}
+
+/// Sends a GET on the connection and reads exactly one response using its framing.
+async fn round_trip(conn: &mut BufReader<TcpStream>, path: &str) -> (u16, String) {
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+    conn.get_mut().write_all(request.as_bytes()).await.unwrap();
+
+    tokio::time::timeout(IO_TIMEOUT, async {
+        let mut line = String::new();
+        conn.read_line(&mut line).await.unwrap();
+        let status = line.split(' ').nth(1).unwrap().parse().unwrap();
+
+        let (mut len, mut chunked) = (0, false);
+        loop {
+            line.clear();
+            conn.read_line(&mut line).await.unwrap();
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((k, v)) = header.split_once(':') {
+                assert!(!k.eq_ignore_ascii_case("connection") || v.trim() != "close", "connection closed");
+                if k.eq_ignore_ascii_case("content-length") {
+                    len = v.trim().parse().unwrap();
+                }
+                chunked |= k.eq_ignore_ascii_case("transfer-encoding") && v.trim() == "chunked";
+            }
+        }
+        let mut body = vec![0u8; len];
+        conn.read_exact(&mut body).await.unwrap();
+        if chunked {
+            loop {
+                line.clear();
+                conn.read_line(&mut line).await.unwrap();
+                let size = usize::from_str_radix(line.trim_end(), 16).unwrap();
+                let mut chunk = vec![0u8; size + 2];
+                conn.read_exact(&mut chunk).await.unwrap();
+                if size == 0 {
+                    break;
+                }
+                body.extend_from_slice(&chunk[..size]);
+            }
+        }
+        (status, String::from_utf8_lossy(&body).into_owned())
+    })
+    .await
+    .expect("connection was dropped instead of answered")
+}
+
+/// Test that requests keep being served on the same connection after handler and task panics,
+/// and that a panicking spawned task is turned into an error response by its handler.
+#[tokio::test]
+async fn test_connection_survives_panics() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router()).await.unwrap();
+    });
+    let before = metrics::handler_panics("test_task");
+
+    let mut conn = BufReader::new(TcpStream::connect(addr).await.unwrap());
+    assert_eq!(round_trip(&mut conn, "/ok").await, (200, "ok".to_string()));
+
+    let (status, body) = round_trip(&mut conn, "/panic/1").await;
+    assert_eq!(status, 500, "{}", body);
+    assert_eq!(round_trip(&mut conn, "/ok").await, (200, "ok".to_string()));
+
+    let (status, body) = round_trip(&mut conn, "/task").await;
+    assert_eq!(status, 503);
+    assert!(body.contains("deliberate task panic"), "{}", body);
+    assert_eq!(metrics::handler_panics("test_task"), before + 1);
+    assert_eq!(round_trip(&mut conn, "/ok").await, (200, "ok".to_string()));
+}
@@ -0,0 +1,211 @@
+// Integration tests for propagating admin calls to peer instances (`upstream.peers`).
+//
+// Each test runs two cache instances in process, each on a listener of its own over a mock
+// upstream, listing each other as peers. Fills reaching each mock tell hits from misses.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::Router;
+use tokio::net::TcpListener;
+
+use crate::config;
+use crate::controller::{BypassOnOffController, ClearController, InvalidateController};
+use crate::tests::support::RouterCache;
+use crate::upstream::peers::PROPAGATED_HEADER;
+
+const PATH: &str = "/api/v1/user";
+
+/// Serves an instance with the given peers on the listener.
+fn start(listener: TcpListener, peers: Vec<String>) -> RouterCache {
+    let cache = RouterCache::builder()
+        .config(|cfg| {
+            let upstream_cfg = cfg.cache.upstream.as_mut().unwrap();
+            upstream_cfg.peers = Some(peers);
+            upstream_cfg.peer_timeout = Some(Duration::from_millis(500));
+        })
+        .start()
+        .mount(|cache| InvalidateController::new(cache.cfg.clone(), cache.db.clone()))
+        .mount(|cache| ClearController::new(cache.cfg.clone(), cache.db.clone()))
+        .mount(|cache| BypassOnOffController::new(cache.cfg.clone()));
+    let serving = cache.router.clone();
+    tokio::spawn(async move { axum::serve(listener, serving).await.unwrap() });
+    cache
+}
+
+async fn get_with(cache: &RouterCache, uri: &str, headers: HeaderMap) -> (StatusCode, serde_json::Value) {
+    let mut request = Request::get(uri).body(Body::empty()).unwrap();
+    *request.headers_mut() = headers;
+    cache.send_json(request).await
+}
+
+/// Requests the user and returns whether it was served from the cache.
+async fn is_hit(cache: &RouterCache, id: u32) -> bool {
+    let fills = cache.upstream.fills();
+    let (status, _) = cache.get_json(&format!("{}?user[id]={}", PATH, id)).await;
+    assert_eq!(status, StatusCode::OK);
+    cache.upstream.fills() == fills
+}
+
+async fn listener() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    (listener, addr)
+}
+
+/// Two instances listing each other as peers.
+async fn pair() -> (RouterCache, RouterCache) {
+    let (listener_a, addr_a) = listener().await;
+    let (listener_b, addr_b) = listener().await;
+    (start(listener_a, vec![addr_b]), start(listener_b, vec![addr_a]))
+}
+
+/// Test that an invalidation on one instance removes the entry on its peer, which does not
+/// forward it back.
+#[tokio::test]
+async fn test_invalidation_propagates_to_peer() {
+    let (a, b) = pair().await;
+    for instance in [&a, &b] {
+        assert!(!is_hit(instance, 1).await);
+        assert!(is_hit(instance, 1).await);
+    }
+
+    let (status, body) = a.get_json(&format!("/advcache/invalidate?_path={}&user[id]=1&_remove=1", PATH)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["affected"], 1);
+    assert_eq!(body["peers"].as_array().unwrap().len(), 1, "{}", body);
+    assert_eq!(body["peers"][0]["ok"], true, "{}", body);
+    assert_eq!(body["peers"][0]["status"], 200);
+
+    assert!(!is_hit(&b, 1).await, "the peer must miss after the invalidation");
+    assert!(!is_hit(&a, 1).await);
+}
+
+/// Test that `_propagate=0` keeps an invalidation local.
+#[tokio::test]
+async fn test_propagation_can_be_turned_off_per_call() {
+    let (a, b) = pair().await;
+    assert!(!is_hit(&a, 2).await);
+    assert!(!is_hit(&b, 2).await);
+
+    let (status, body) = a.get_json(&format!("/advcache/invalidate?_path={}&user[id]=2&_remove=1&_propagate=0", PATH)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("peers").is_none(), "{}", body);
+
+    assert!(is_hit(&b, 2).await, "the peer must keep its entry");
+    assert!(!is_hit(&a, 2).await);
+}
+
+/// Test that a call forwarded by a peer is not forwarded again.
+#[tokio::test]
+async fn test_forwarded_call_is_not_forwarded_again() {
+    let (a, b) = pair().await;
+    assert!(!is_hit(&b, 3).await);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(PROPAGATED_HEADER, "some-peer".parse().unwrap());
+    let (_, body) = get_with(&a, &format!("/advcache/invalidate?_path={}&user[id]=3&_remove=1", PATH), headers).await;
+    assert!(body.get("peers").is_none(), "{}", body);
+    assert!(is_hit(&b, 3).await);
+}
+
+/// Test that a clear propagates, peers being cleared with tokens of their own.
+#[tokio::test]
+async fn test_clear_propagates_to_peer() {
+    let (a, b) = pair().await;
+    assert!(!is_hit(&b, 4).await);
+
+    let (_, body) = a.get_json("/advcache/clear").await;
+    let token = body["token"].as_str().unwrap().to_string();
+    let (status, body) = a.get_json(&format!("/advcache/clear?token={}", token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cleared"], true);
+    assert_eq!(body["peers"][0]["ok"], true, "{}", body);
+
+    assert!(!is_hit(&b, 4).await, "the peer must be cleared");
+}
+
+/// Test that a bypass toggle propagates.
+#[tokio::test]
+async fn test_bypass_toggle_propagates_to_peer() {
+    let (a, b) = pair().await;
+
+    let (_, body) = a.get_json("/advcache/bypass/on").await;
+    assert_eq!(body["enabled"], true);
+    assert_eq!(body["peers"][0]["ok"], true, "{}", body);
+    assert_eq!(b.get_json("/advcache/bypass").await.1["enabled"], true);
+
+    a.get_json("/advcache/bypass/off").await;
+    assert_eq!(b.get_json("/advcache/bypass").await.1["enabled"], false);
+}
+
+/// Test that an unreachable peer is reported without failing the local invalidation, and that
+/// credentials are forwarded to the peers that are reached.
+#[tokio::test]
+async fn test_peer_failures_are_reported_and_auth_is_forwarded() {
+    let seen = Arc::new(Mutex::new(Vec::<HeaderMap>::new()));
+    let recorder = {
+        let seen = seen.clone();
+        Router::new().fallback(move |headers: HeaderMap| {
+            let seen = seen.clone();
+            async move {
+                seen.lock().unwrap().push(headers);
+                "{}"
+            }
+        })
+    };
+    let (recorder_listener, recorder_addr) = listener().await;
+    tokio::spawn(async move { axum::serve(recorder_listener, recorder).await.unwrap() });
+    // Bound and dropped: nothing listens there anymore.
+    let (closed, closed_addr) = listener().await;
+    drop(closed);
+
+    let (listener_a, _) = listener().await;
+    let a = start(listener_a, vec![recorder_addr.clone(), closed_addr.clone()]);
+    assert!(!is_hit(&a, 5).await);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::AUTHORIZATION, "Bearer admin-token".parse().unwrap());
+    let uri = format!("/advcache/invalidate?_path={}&user[id]=5&_remove=1", PATH);
+    let (status, body) = get_with(&a, &uri, headers).await;
+    assert_eq!(status, StatusCode::OK, "peer failures must not fail the local call");
+    assert_eq!(body["affected"], 1);
+
+    let peers = body["peers"].as_array().unwrap();
+    assert_eq!(peers[0]["peer"], recorder_addr.as_str());
+    assert_eq!(peers[0]["ok"], true);
+    assert_eq!(peers[1]["peer"], closed_addr.as_str());
+    assert_eq!(peers[1]["ok"], false);
+    assert!(peers[1]["status"].is_null());
+    assert!(peers[1]["error"].is_string());
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 1);
+    assert_eq!(seen[0][header::AUTHORIZATION], "Bearer admin-token");
+    assert!(seen[0].contains_key(PROPAGATED_HEADER));
+}
+
+/// Test that a peer that is not a host:port fails the config load.
+#[test]
+fn test_invalid_peer_fails_config_load() {
+    let yaml = r#"
+cache:
+  env: test
+  enabled: true
+  upstream:
+    backend:
+      id: main
+      enabled: true
+      scheme: http
+      host: localhost:8090
+      timeout: 10s
+      max_timeout: 1m
+      rate: 1000
+    peers:
+      - "http://cache-1:8020/path"
+"#;
+    let err = config::Config::from_yaml(yaml).unwrap_err();
+    assert!(format!("{:#}", err).contains("invalid upstream.peers entry"), "{:#}", err);
+}
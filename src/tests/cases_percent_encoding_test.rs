@@ -1,12 +1,12 @@
 // Integration tests for percent encoding equivalence.
 
 use crate::support::{
-    assert_equal, assert_ok, cache_addr, do_json, init_test_harness, new_namespace, phash, H,
+    assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, new_namespace, phash, H,
 };
 
 #[tokio::test]
 async fn test_query_percent_encoding_space_plus_vs_20_equivalent() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_query_percent_encoding_space_plus_vs_20_equivalent");
     let base = cache_addr().await;
@@ -41,7 +41,7 @@ async fn test_query_percent_encoding_space_plus_vs_20_equivalent() {
 
 #[tokio::test]
 async fn test_query_percent_encoding_literal_plus_different_from_space() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_query_percent_encoding_literal_plus_different_from_space");
     let base = cache_addr().await;
@@ -76,7 +76,7 @@ async fn test_query_percent_encoding_literal_plus_different_from_space() {
 
 #[tokio::test]
 async fn test_query_percent_encoding_hex_case_insensitive_equivalent() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_query_percent_encoding_hex_case_insensitive_equivalent");
     let base = cache_addr().await;
@@ -133,7 +133,7 @@ async fn test_query_percent_encoding_hex_case_insensitive_equivalent() {
 
 #[tokio::test]
 async fn test_query_percent_encoding_utf8_equivalent() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_query_percent_encoding_utf8_equivalent");
     let base = cache_addr().await;
@@ -173,7 +173,7 @@ async fn test_query_percent_encoding_utf8_equivalent() {
 
 #[tokio::test]
 async fn test_percent_encoding_double_encoding_not_equivalent() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_percent_encoding_double_encoding_not_equivalent");
     let base = cache_addr().await;
@@ -208,7 +208,7 @@ async fn test_percent_encoding_double_encoding_not_equivalent() {
 
 #[tokio::test]
 async fn test_query_percent_encoding_slash_in_value_equivalent() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("test_query_percent_encoding_slash_in_value_equivalent");
     let base = cache_addr().await;
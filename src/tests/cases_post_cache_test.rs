@@ -0,0 +1,133 @@
+// Integration tests for caching POST requests keyed by their body (`methods`, `key_body`).
+//
+// The e2e cases POST to /api/v1/search, whose upstream echoes the body with the number of the
+// call; a cached answer keeps the number of the call that filled it. Cases that need to see
+// what reached the origin run in-process over a mock upstream.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+
+use crate::config::Config;
+use crate::controller::cache::resolve_cache_request;
+use crate::db::Storage;
+use crate::tests::support::RouterCache;
+use crate::upstream::testing::CallKind;
+use crate::upstream::Upstream;
+use crate::support::{assert_ok, cache_addr, do_request, Capability, init_test_harness, new_namespace, with_ns, H};
+
+const SEARCH_PATH: &str = "/api/v1/search";
+
+async fn post_search(url: &str, body: &str) -> serde_json::Value {
+    let mut headers = H::new();
+    headers.insert("Accept-Encoding".to_string(), "identity".to_string());
+    headers.insert("Content-Type".to_string(), "application/json".to_string());
+    let resp = assert_ok(do_request("POST", url, &headers, Some(body.as_bytes())).await);
+    assert_eq!(resp.status().as_u16(), 200);
+    resp.json().await.expect("json answer")
+}
+
+/// Test that POSTs with different bodies are cached apart, each answered from its own entry
+/// when repeated, and that a GET of the same params is not answered by either.
+#[tokio::test]
+async fn test_posts_with_different_bodies_are_cached_apart() {
+    init_test_harness(Capability::Http).await.unwrap();
+
+    let ns = new_namespace("Test_PostCache_BodiesApart");
+    let mut params = HashMap::new();
+    params.insert("user[id]".to_string(), "7".to_string());
+    let url = format!("{}{}", cache_addr().await, with_ns(SEARCH_PATH, &ns, &params));
+
+    let first = post_search(&url, r#"{"q":"shoes"}"#).await;
+    let second = post_search(&url, r#"{"q":"hats"}"#).await;
+    assert_eq!(first["body"], r#"{"q":"shoes"}"#);
+    assert_eq!(second["body"], r#"{"q":"hats"}"#);
+    assert_eq!(first["method"], "POST", "the fill is sent as a POST");
+    assert_ne!(first["call"], second["call"], "each body is filled on its own");
+
+    assert_eq!(post_search(&url, r#"{"q":"shoes"}"#).await, first);
+    assert_eq!(post_search(&url, r#"{"q":"hats"}"#).await, second);
+
+    let resp = assert_ok(do_request("GET", &url, &H::new(), None).await);
+    let get: serde_json::Value = resp.json().await.expect("json answer");
+    assert_eq!(get["method"], "GET");
+    assert_ne!(get["call"], first["call"]);
+    assert_ne!(get["call"], second["call"]);
+}
+
+/// Cache with the search rule keying bodies of up to 16 bytes.
+fn start() -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| {
+            let rules = cfg.cache.rules.as_mut().unwrap();
+            let mut rule = (*rules[SEARCH_PATH]).clone();
+            rule.max_key_body = Some(16);
+            rules.insert(SEARCH_PATH.to_string(), Arc::new(rule));
+        })
+        .start()
+}
+
+async fn post(cache: &RouterCache, uri: &str, body: &'static str) -> StatusCode {
+    let resp = cache.send(Request::post(uri).body(Body::from(body)).unwrap()).await;
+    let status = resp.status();
+    let _ = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    status
+}
+
+/// Test that POSTs bypass the cache on a rule not listing POST and with bodies over the rule's
+/// `max_key_body`, reaching the origin with their body every time.
+#[tokio::test]
+async fn test_post_bypasses_cache_unless_keyed() {
+    let cache = start();
+
+    for _ in 0..2 {
+        assert_eq!(post(&cache, "/api/v1/user?user[id]=1", "{}").await, StatusCode::OK);
+        assert_eq!(post(&cache, "/api/v1/search?user[id]=1", r#"{"q":"over sixteen bytes"}"#).await, StatusCode::OK);
+    }
+    assert_eq!(cache.upstream.proxied(), 4);
+    assert_eq!(cache.upstream.fills(), 0);
+    let last = cache.upstream.last_call().unwrap();
+    assert_eq!((last.method.as_str(), last.body.as_deref()), ("POST", Some(br#"{"q":"over sixteen bytes"}"#.as_slice())));
+    assert_eq!(cache.db.stat().1, 0, "nothing is stored");
+}
+
+/// Test that a keyed POST is filled once with its body, and refreshed as the same POST.
+#[tokio::test]
+async fn test_keyed_post_is_filled_and_refreshed_with_its_body() {
+    let cache = start();
+
+    for _ in 0..3 {
+        assert_eq!(post(&cache, "/api/v1/search?user[id]=2", r#"{"q":"a"}"#).await, StatusCode::OK);
+    }
+    cache.upstream.assert_calls(CallKind::Fill, 1);
+    let fill = cache.upstream.last_call().unwrap();
+    assert_eq!((fill.method.as_str(), fill.body.as_deref()), ("POST", Some(br#"{"q":"a"}"#.as_slice())));
+
+    let resolved = resolve_cache_request(&cache.cfg, SEARCH_PATH.as_bytes(), "user[id]=2", &[], "POST", Some(br#"{"q":"a"}"#))
+        .unwrap_or_else(|_| panic!("the search rule keys POSTs"));
+    let entry = cache.db.get_by_key(resolved.entry.key()).0.expect("the POST is stored");
+    assert!(cache.upstream.refresh(&entry).await.is_ok());
+    let refresh = cache.upstream.last_call().unwrap();
+    assert_eq!(refresh.kind, CallKind::Refresh);
+    assert_eq!((refresh.method.as_str(), refresh.body.as_deref()), ("POST", Some(br#"{"q":"a"}"#.as_slice())));
+}
+
+/// Test that `methods` only accepts GET and POST, that POST and `key_body` must be set together,
+/// and that a zero `max_key_body` is refused.
+#[test]
+fn test_post_rule_settings_are_validated() {
+    let yaml = |settings: &str| {
+        format!(
+            "cache:\n  env: test\n  enabled: true\n  upstream:\n    backend:\n      id: main\n      enabled: true\n      scheme: http\n      host: main.local:8080\n      timeout: 10s\n      max_timeout: 1m\n  rules:\n    /api/v1/search:\n{settings}      cache_key:\n        query: []\n        headers: []\n      cache_value:\n        headers: []\n"
+        )
+    };
+    assert!(Config::from_yaml(&yaml("")).is_ok());
+    assert!(Config::from_yaml(&yaml("      methods: [get, post]\n      key_body: true\n")).is_ok());
+    assert!(Config::from_yaml(&yaml("      methods: [GET, POST]\n      key_body: true\n      max_key_body: 1024\n")).is_ok());
+    assert!(Config::from_yaml(&yaml("      methods: [GET, POST]\n")).is_err());
+    assert!(Config::from_yaml(&yaml("      key_body: true\n")).is_err());
+    assert!(Config::from_yaml(&yaml("      methods: [GET, PUT]\n")).is_err());
+    assert!(Config::from_yaml(&yaml("      methods: [GET, POST]\n      key_body: true\n      max_key_body: 0\n")).is_err());
+}
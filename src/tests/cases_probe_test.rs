@@ -0,0 +1,233 @@
+// Integration tests for the Kubernetes probe endpoints (`k8s.probe`).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{self, Config};
+use crate::controller::probe::{Failing, ReadinessProbeController, CHECK_APP, CHECK_DUMP, CHECK_UPSTREAM, CHECK_WORKER};
+use crate::db::{Storage, DB, SVC_EVICTOR, SVC_LIFETIME_MANAGER};
+use crate::governor::{Governor, Orchestrator};
+use crate::http::server::limit::{ConnectionLimit, Listener};
+use crate::http::server::server::{probe_router, serve, start_probe_listener};
+use crate::liveness::{self, Prober, Service};
+use crate::upstream::testing::MockUpstream;
+
+/// Service whose liveness the test switches.
+struct Switch(AtomicBool);
+
+impl Service for Switch {
+    fn is_alive(&self, _timeout: Duration) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Service alive but out of readiness, like an instance a non-critical worker failed to start on.
+struct Degraded;
+
+impl Service for Degraded {
+    fn is_alive(&self, _timeout: Duration) -> bool {
+        true
+    }
+
+    fn is_ready(&self, _timeout: Duration) -> bool {
+        false
+    }
+}
+
+fn probe() -> Arc<liveness::Probe> {
+    Arc::new(liveness::Probe::new(Duration::from_secs(1)))
+}
+
+/// Port nobody listens on right now.
+async fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port()
+}
+
+/// Sends a GET on a fresh connection and returns the status line.
+async fn status_of(addr: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.expect("the probe listener must accept connections");
+    let req = format!("GET {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n", path);
+    stream.write_all(req.as_bytes()).await.unwrap();
+    let mut resp = Vec::new();
+    let _ = stream.read_to_end(&mut resp).await;
+    String::from_utf8_lossy(&resp).lines().next().unwrap_or_default().to_string()
+}
+
+fn probe_config(port: u16) -> Config {
+    let mut cfg = config::new_test_config();
+    let probe = &mut cfg.cache.k8s.as_mut().unwrap().probe;
+    probe.liveness_path = Some("/healthz/live".to_string());
+    probe.readiness_path = Some("/healthz/ready".to_string());
+    probe.port = Some(port.to_string());
+    cfg
+}
+
+/// Test that the probe listener answers on its custom paths as soon as it is started, before
+/// any application registers: alive but not ready, then ready once the application is up.
+#[tokio::test]
+async fn test_probe_listener_answers_before_the_app_is_up() {
+    let port = free_port().await;
+    let cfg = probe_config(port);
+    let probe = probe();
+    let shutdown = CancellationToken::new();
+    start_probe_listener(shutdown.clone(), &cfg, probe.clone() as Arc<dyn Prober>).await.unwrap();
+
+    // No sleep or retry: the listener is bound once start_probe_listener returns.
+    let addr = format!("127.0.0.1:{}", port);
+    assert_eq!(status_of(&addr, "/healthz/live").await, "HTTP/1.1 200 OK");
+    assert_eq!(status_of(&addr, "/healthz/ready").await, "HTTP/1.1 503 Service Unavailable");
+    assert_eq!(status_of(&addr, "/k8s/probe").await, "HTTP/1.1 404 Not Found");
+
+    let app = Arc::new(Switch(AtomicBool::new(true)));
+    probe.watch(vec![app.clone() as Arc<dyn Service>]);
+    assert_eq!(status_of(&addr, "/healthz/ready").await, "HTTP/1.1 200 OK");
+
+    app.0.store(false, Ordering::Relaxed);
+    assert_eq!(status_of(&addr, "/healthz/live").await, "HTTP/1.1 503 Service Unavailable");
+    assert_eq!(status_of(&addr, "/healthz/ready").await, "HTTP/1.1 503 Service Unavailable");
+    shutdown.cancel();
+}
+
+/// Service answering its liveness check after a delay.
+struct Slow(Duration);
+
+impl Service for Slow {
+    fn is_alive(&self, _timeout: Duration) -> bool {
+        std::thread::sleep(self.0);
+        true
+    }
+}
+
+/// Test that a zero timeout is raised to a working one rather than stored as is.
+#[tokio::test]
+async fn test_zero_timeout_is_clamped() {
+    let probe = liveness::Probe::new(Duration::ZERO);
+    assert_eq!(probe.timeout(), liveness::Probe::DEFAULT_MIN_TIMEOUT);
+    probe.watch(vec![Arc::new(Switch(AtomicBool::new(true))) as Arc<dyn Service>]);
+    assert!(probe.is_alive_async().await);
+
+    let probe = liveness::Probe::with_min_timeout(Duration::from_millis(5), Duration::from_millis(50));
+    assert_eq!(probe.timeout(), Duration::from_millis(50), "a timeout below the minimum is raised to it");
+    let probe = liveness::Probe::with_min_timeout(Duration::from_millis(50), Duration::from_millis(5));
+    assert_eq!(probe.timeout(), Duration::from_millis(50), "a timeout above the minimum is kept");
+}
+
+/// Test that a service answering in 2ms is alive under a probe configured with a zero timeout.
+#[tokio::test]
+async fn test_slow_service_alive_under_clamped_timeout() {
+    let probe = liveness::Probe::new(Duration::ZERO);
+    probe.watch(vec![Arc::new(Slow(Duration::from_millis(2))) as Arc<dyn Service>]);
+    assert!(probe.is_alive_async().await);
+    assert!(Prober::is_alive(&probe));
+}
+
+/// Test that a service may be alive without being ready.
+#[tokio::test]
+async fn test_alive_but_not_ready() {
+    let probe = probe();
+    probe.watch(vec![Arc::new(Degraded) as Arc<dyn Service>]);
+    assert!(Prober::is_alive(probe.as_ref()));
+    assert!(!Prober::is_ready(probe.as_ref()));
+}
+
+/// Test that without custom paths the probes keep answering on `/k8s/probe` and `/healthz`.
+#[tokio::test]
+async fn test_default_probe_paths() {
+    let cfg = config::new_test_config();
+    let probe = probe();
+    probe.watch(vec![Arc::new(Switch(AtomicBool::new(true))) as Arc<dyn Service>]);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let shutdown = CancellationToken::new();
+    let router = probe_router(probe as Arc<dyn Prober>, &cfg.cache.k8s.as_ref().unwrap().probe);
+    let limit = Arc::new(ConnectionLimit::new(Listener::Admin, None, 0, None));
+    tokio::spawn(serve(listener, router, limit, shutdown.clone()));
+
+    assert_eq!(status_of(&addr, "/k8s/probe").await, "HTTP/1.1 200 OK");
+    assert_eq!(status_of(&addr, "/healthz").await, "HTTP/1.1 200 OK");
+    shutdown.cancel();
+}
+
+/// Test that a probe config clashing with itself or the API ports is rejected.
+#[test]
+fn test_probe_config_validation() {
+    let yaml = |probe: &str| {
+        format!(
+            "cache:\n  env: test\n  enabled: true\n  api:\n    port: \"8020\"\n  k8s:\n    probe:\n      timeout: 5s\n{}",
+            probe
+        )
+    };
+    assert!(Config::from_yaml(&yaml("      liveness_path: /healthz/live\n      readiness_path: /healthz/ready\n      port: \"9091\"\n")).is_ok());
+    assert!(Config::from_yaml(&yaml("      liveness_path: /healthz\n")).is_err(), "liveness and readiness paths must differ");
+    assert!(Config::from_yaml(&yaml("      readiness_path: ready\n")).is_err(), "paths must be absolute");
+    assert!(Config::from_yaml(&yaml("      port: \"8020\"\n")).is_err(), "the probe port must not be the API port");
+}
+
+/// Readiness controller over a fresh DB, its governor and a mock upstream, with the app registered.
+fn readiness(cfg: &Config, shutdown: &CancellationToken) -> (ReadinessProbeController, Arc<Orchestrator>, Arc<MockUpstream>) {
+    let governor = Arc::new(Orchestrator::new());
+    let upstream = MockUpstream::new();
+    let db = DB::new(shutdown.clone(), cfg.clone(), governor.clone(), upstream.clone()).expect("storage must start");
+    let probe = probe();
+    probe.watch(vec![Arc::new(Switch(AtomicBool::new(true))) as Arc<dyn Service>]);
+    let controller = ReadinessProbeController::new(
+        probe,
+        db as Arc<dyn Storage>,
+        upstream.clone(),
+        governor.clone(),
+        cfg.cache.k8s.as_ref().map(|k| &k.probe),
+    );
+    (controller, governor, upstream)
+}
+
+/// Test that readiness fails with the dump alone until the dump restored on start is loaded.
+#[tokio::test]
+async fn test_readiness_waits_for_the_dump() {
+    let dir = std::env::temp_dir().join(format!("advcache-ready-dump-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut cfg = config::new_test_config();
+    let dump = cfg.cache.data.as_mut().unwrap().dump.as_mut().unwrap();
+    dump.enabled = true;
+    dump.dir = Some(dir.to_string_lossy().into_owned());
+    let shutdown = CancellationToken::new();
+    let (controller, _governor, upstream) = readiness(&cfg, &shutdown);
+    upstream.set_healthy(false);
+
+    // The load is spawned on this current-thread runtime: it has not run yet.
+    assert_eq!(controller.failing(), vec![Failing { check: CHECK_DUMP, worker: None }]);
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while controller.failing().iter().any(|f| f.check == CHECK_DUMP) {
+        assert!(tokio::time::Instant::now() < deadline, "the dump must finish loading");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(controller.failing(), vec![Failing { check: CHECK_UPSTREAM, worker: None }]);
+    shutdown.cancel();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Test that readiness follows the upstream health and goes once the workers are stopped.
+#[tokio::test]
+async fn test_readiness_tracks_upstream_and_workers() {
+    let cfg = config::new_test_config();
+    let shutdown = CancellationToken::new();
+    let (controller, governor, upstream) = readiness(&cfg, &shutdown);
+    assert!(controller.failing().is_empty(), "no dump to load: ready at once");
+
+    upstream.set_healthy(false);
+    assert_eq!(controller.failing(), vec![Failing { check: CHECK_UPSTREAM, worker: None }]);
+    upstream.set_healthy(true);
+    assert!(controller.failing().is_empty());
+
+    governor.stop();
+    let workers: Vec<_> = controller.failing().into_iter().filter(|f| f.check == CHECK_WORKER).filter_map(|f| f.worker).collect();
+    assert_eq!(workers, vec![SVC_EVICTOR.to_string(), SVC_LIFETIME_MANAGER.to_string()]);
+    assert!(controller.failing().iter().all(|f| f.check != CHECK_APP));
+    shutdown.cancel();
+}
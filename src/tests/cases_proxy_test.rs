@@ -2,7 +2,7 @@
 
 use std::collections::HashMap;
 use crate::support::{
-    assert_equal, assert_ok, cache_addr, do_json, init_test_harness, new_namespace, with_ns, H,
+    assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, new_namespace, with_ns, H,
 };
 use once_cell::sync::Lazy;
 use tokio::sync::Mutex as AsyncMutex;
@@ -11,7 +11,7 @@ static CACHE_MODE_LOCK: Lazy<AsyncMutex<()>> = Lazy::new(|| AsyncMutex::new(()))
 
 #[tokio::test]
 async fn test_toggle_cache_on_off_roundtrip() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let _guard = CACHE_MODE_LOCK.lock().await;
 
@@ -84,7 +84,7 @@ async fn test_toggle_cache_on_off_roundtrip() {
 
 #[tokio::test]
 async fn test_proxy_mode_whitelist_noise_ignored_semantics() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let _guard = CACHE_MODE_LOCK.lock().await;
     toggle_cache(false).await;
@@ -132,7 +132,7 @@ async fn test_proxy_mode_whitelist_noise_ignored_semantics() {
 
 #[tokio::test]
 async fn test_proxy_forwarded_host_passed_to_upstream() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let _guard = CACHE_MODE_LOCK.lock().await;
     toggle_cache(false).await; // proxy mode
@@ -244,7 +244,7 @@ async fn test_proxy_forwarded_host_passed_to_upstream() {
 
 #[tokio::test]
 async fn test_hop_by_hop_headers_not_sent_to_upstream() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let _guard = CACHE_MODE_LOCK.lock().await;
     toggle_cache(false).await; // proxy mode
@@ -0,0 +1,98 @@
+// Integration tests for pure-cache mode (`upstream.proxy_enabled: false`).
+
+use axum::http::StatusCode;
+
+use crate::config::{self, Config, ConfigTrait};
+use crate::controller::{BypassOnOffController, ExplainController};
+use crate::tests::support::RouterCache;
+use crate::upstream::testing::CallKind;
+
+/// Disables proxying, refusing unmatched requests with `status`.
+fn pure_cache(cfg: &mut Config, status: Option<u16>) {
+    let upstream = cfg.cache.upstream.as_mut().unwrap();
+    upstream.proxy_enabled = Some(false);
+    upstream.proxy_disabled_status = status;
+}
+
+fn start(change: impl FnOnce(&mut Config)) -> RouterCache {
+    RouterCache::builder()
+        .config(change)
+        .start()
+        .mount(|cache| BypassOnOffController::new(cache.cfg.clone()))
+        .mount(|cache| ExplainController::new(cache.cfg.clone(), cache.db.clone()))
+}
+
+/// Test that unmatched paths get the configured status and never reach the origin, while rule matches still fill.
+#[tokio::test]
+async fn test_pure_cache_refuses_unmatched_paths() {
+    let cache = start(|cfg| pure_cache(cfg, Some(403)));
+
+    let (status, body) = cache.get_json("/api/v1/unknown?user[id]=1").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["status"], 403);
+
+    let (status, _) = cache.get_json("/api/v1/user?user[id]=1&domain=pure.example").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = cache.get_json("/api/v1/user?user[id]=1&domain=pure.example").await;
+    assert_eq!(status, StatusCode::OK);
+
+    cache.upstream.assert_calls(CallKind::Proxy, 0);
+    cache.upstream.assert_calls(CallKind::Fill, 1);
+}
+
+/// Test that with the cache disabled nothing is proxied and the default status is returned.
+#[tokio::test]
+async fn test_pure_cache_with_cache_disabled() {
+    let cache = start(|cfg| {
+        pure_cache(cfg, None);
+        cfg.set_enabled(false);
+    });
+
+    for uri in ["/api/v1/user?user[id]=2", "/api/v1/unknown"] {
+        let (status, _) = cache.get_json(uri).await;
+        assert_eq!(status.as_u16(), config::DEFAULT_PROXY_DISABLED_STATUS);
+    }
+
+    cache.upstream.assert_not_called();
+}
+
+/// Test that enabling bypass is refused while proxying is disabled.
+#[tokio::test]
+async fn test_pure_cache_refuses_bypass() {
+    let cache = start(|cfg| pure_cache(cfg, None));
+
+    let (status, body) = cache.get_json("/advcache/bypass/on").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["enabled"], false);
+
+    let (status, body) = cache.get_json("/advcache/bypass").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["enabled"], false);
+}
+
+/// Test that explain reports refused requests instead of proxied ones.
+#[tokio::test]
+async fn test_pure_cache_explain() {
+    let cache = start(|cfg| pure_cache(cfg, None));
+
+    let (_, body) = cache.get_json("/advcache/explain?path=/api/v1/unknown").await;
+    assert_eq!(body["bypass"]["proxy_enabled"], false);
+    assert_eq!(
+        body["rule"]["reason"],
+        "no rule configured for path, proxying is disabled so request is refused"
+    );
+}
+
+/// Test that with proxying enabled the same unmatched request reaches the origin unchanged.
+#[tokio::test]
+async fn test_proxy_enabled_forwards_unmatched_paths() {
+    let cache = start(|_| {});
+
+    let (status, _) = cache.get_json("/api/v1/unknown?user[id]=1").await;
+    assert_eq!(status, StatusCode::OK);
+
+    cache.upstream.assert_calls(CallKind::Proxy, 1);
+    cache.upstream.assert_called(CallKind::Proxy, "/api/v1/unknown");
+    let call = cache.upstream.last_call().unwrap();
+    assert_eq!((call.method.as_str(), call.query.as_str()), ("GET", "user[id]=1"));
+}
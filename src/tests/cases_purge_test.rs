@@ -0,0 +1,95 @@
+// Integration tests for purges: `PURGE` on the main handler and `/advcache/purge?prefix=`.
+//
+// Entries are filled with GETs, then purged, and the storage stats are checked against what the
+// purges report.
+
+use axum::http::{Method, StatusCode};
+
+use crate::controller::PurgeController;
+use crate::db::Storage;
+use crate::tests::support::RouterCache;
+
+fn start() -> RouterCache {
+    RouterCache::start().mount(|cache| PurgeController::new(cache.db.clone()))
+}
+
+/// Fills the entries of the ids under the path.
+async fn fill(cache: &RouterCache, path: &str, ids: std::ops::Range<u32>) {
+    for id in ids {
+        assert_eq!(cache.get_json(&format!("{}?user[id]={}", path, id)).await.0, StatusCode::OK);
+    }
+}
+
+async fn purge_prefix(cache: &RouterCache, prefix: &str) -> (StatusCode, serde_json::Value) {
+    cache.call_json(Method::POST, &format!("/advcache/purge?prefix={}", prefix)).await
+}
+
+fn purge_method() -> Method {
+    Method::from_bytes(b"PURGE").unwrap()
+}
+
+/// Test that PURGE removes the one entry a GET of the request is served, reports what it
+/// freed, answers 404 once it is gone, and that the next GET fills it again.
+#[tokio::test]
+async fn test_purge_method_removes_the_entry() {
+    let cache = start();
+    fill(&cache, "/api/v1/user", 1..3).await;
+    let (bytes, len) = cache.db.stat();
+    assert_eq!(len, 2);
+
+    let (status, body) = cache.call_json(purge_method(), "/api/v1/user?user[id]=1").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["removed"], 1);
+    assert_eq!(body["freed_bytes"], bytes - cache.db.stat().0);
+    assert_eq!(cache.db.stat().1, 1);
+
+    let (status, body) = cache.call_json(purge_method(), "/api/v1/user?user[id]=1").await;
+    assert_eq!((status, &body["removed"]), (StatusCode::NOT_FOUND, &serde_json::json!(0)));
+    assert_eq!(cache.upstream.proxied(), 0, "PURGE on a ruled path does not reach the origin");
+
+    let fills = cache.upstream.fills();
+    fill(&cache, "/api/v1/user", 1..3).await;
+    assert_eq!(cache.upstream.fills(), fills + 1, "only the purged entry is filled again");
+}
+
+/// Test that a prefix purge removes the entries of every path under it and no other, and
+/// keeps the storage stats in line with the removed entries and freed bytes.
+#[tokio::test]
+async fn test_prefix_purge_removes_entries_under_the_prefix() {
+    let cache = start();
+    fill(&cache, "/api/v1/user", 0..5).await;
+    fill(&cache, "/api/v1/client", 0..3).await;
+    let (bytes, _) = cache.db.stat();
+
+    let (status, body) = purge_prefix(&cache, "/api/v1/us").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!((body["success"].as_bool(), body["removed"].as_i64()), (Some(true), Some(5)));
+    let (left_bytes, left) = cache.db.stat();
+    assert_eq!(left, 3);
+    assert_eq!(body["freed_bytes"].as_i64(), Some(bytes - left_bytes));
+
+    let fills = cache.upstream.fills();
+    fill(&cache, "/api/v1/client", 0..3).await;
+    assert_eq!(cache.upstream.fills(), fills, "entries of other paths are kept");
+
+    assert_eq!(purge_prefix(&cache, "api").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(cache.call_json(Method::POST, "/advcache/purge").await.0, StatusCode::BAD_REQUEST);
+}
+
+/// Test that concurrent purges of overlapping prefixes count every entry once between them.
+#[tokio::test]
+async fn test_overlapping_purges_do_not_double_count() {
+    let cache = start();
+    fill(&cache, "/api/v1/user", 0..20).await;
+    fill(&cache, "/api/v1/client", 0..10).await;
+    let (bytes, len) = cache.db.stat();
+    assert_eq!(len, 30);
+
+    let (wide, narrow) = tokio::join!(purge_prefix(&cache, "/api/v1"), purge_prefix(&cache, "/api/v1/user"));
+    assert_eq!((wide.0, narrow.0), (StatusCode::OK, StatusCode::OK));
+    let removed = wide.1["removed"].as_i64().unwrap() + narrow.1["removed"].as_i64().unwrap();
+    let freed = wide.1["freed_bytes"].as_i64().unwrap() + narrow.1["freed_bytes"].as_i64().unwrap();
+    assert_eq!(removed, 30);
+    assert_eq!(freed, bytes);
+    assert_eq!(cache.db.stat(), (0, 0));
+}
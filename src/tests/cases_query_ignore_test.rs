@@ -0,0 +1,91 @@
+// Integration tests for `cache_key.query_ignore`.
+//
+// The backend points at a local origin recording every request URI, so both the keying and the
+// fill URL are checked.
+
+use std::sync::{Arc, Mutex};
+
+use axum::http::{StatusCode, Uri};
+use axum::Router;
+
+use crate::tests::support::router::serve_origin;
+use crate::tests::support::RouterCache;
+use crate::upstream::BackendImpl;
+
+const PATH: &str = "/api/v1/user";
+
+/// Origin answering every request with 200 and recording the URIs of API calls.
+async fn start_origin() -> (String, Arc<Mutex<Vec<String>>>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let recorder = seen.clone();
+    let app = Router::new().fallback(move |uri: Uri| {
+        let recorder = recorder.clone();
+        async move {
+            if uri.path().starts_with("/api/") {
+                recorder.lock().unwrap().push(uri.to_string());
+            }
+            (StatusCode::OK, [("content-type", "application/json")], "{\"ok\":true}")
+        }
+    });
+
+    (serve_origin(app).await, seen)
+}
+
+fn start(origin: &str, forward: bool) -> RouterCache<BackendImpl> {
+    RouterCache::builder()
+        .origin(origin)
+        .config(|cfg| {
+            cfg.cache.api.as_mut().unwrap().forward_ignored_query = Some(forward);
+            let rules = cfg.cache.rules.as_mut().unwrap();
+            let mut rule = (*rules[PATH]).clone();
+            rule.cache_key.query_ignore = Some(vec!["utm_*".to_string(), "fbclid".to_string()]);
+            rules.insert(PATH.to_string(), Arc::new(rule));
+        })
+        .start_backend()
+}
+
+async fn get(cache: &RouterCache<BackendImpl>, uri: &str) -> StatusCode {
+    let resp = cache.get(uri).await;
+    let status = resp.status();
+    let _ = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    status
+}
+
+fn query_names(uri: &str) -> Vec<String> {
+    let query = uri.split_once('?').map(|(_, q)| q).unwrap_or_default();
+    url::form_urlencoded::parse(query.as_bytes())
+        .map(|(k, _)| k.into_owned())
+        .collect()
+}
+
+/// Test that URLs differing only in ignored params share one entry and the origin gets the stripped URL.
+#[tokio::test]
+async fn test_ignored_params_share_entry_and_are_stripped_upstream() {
+    let (origin, seen) = start_origin().await;
+    let cache = start(&origin, false);
+
+    let first = format!("{}?user[id]=7&utm_source=news&fbclid=abc", PATH);
+    let second = format!("{}?utm_source=ads&utm_medium=cpc&user[id]=7", PATH);
+    assert_eq!(get(&cache, &first).await, StatusCode::OK);
+    assert_eq!(get(&cache, &second).await, StatusCode::OK);
+
+    let seen = seen.lock().unwrap().clone();
+    assert_eq!(seen.len(), 1, "second URL must be served from the same entry: {:?}", seen);
+    assert_eq!(query_names(&seen[0]), vec!["user[id]"]);
+}
+
+/// Test that with forwarding enabled the fill URL keeps ignored params while keying still ignores them.
+#[tokio::test]
+async fn test_ignored_params_forwarded_when_configured() {
+    let (origin, seen) = start_origin().await;
+    let cache = start(&origin, true);
+
+    let first = format!("{}?user[id]=8&utm_source=news&fbclid=abc", PATH);
+    let second = format!("{}?user[id]=8&utm_source=ads", PATH);
+    assert_eq!(get(&cache, &first).await, StatusCode::OK);
+    assert_eq!(get(&cache, &second).await, StatusCode::OK);
+
+    let seen = seen.lock().unwrap().clone();
+    assert_eq!(seen.len(), 1, "{:?}", seen);
+    assert_eq!(query_names(&seen[0]), vec!["user[id]", "utm_source", "fbclid"]);
+}
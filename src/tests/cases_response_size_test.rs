@@ -0,0 +1,105 @@
+// Integration tests for `backend.max_response_size`.
+//
+// A real backend points at a local origin that can switch an endpoint from a small JSON
+// answer to an endless chunked body, so the limit is exercised on fills and refreshes.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, Bytes};
+use axum::http::StatusCode;
+use axum::Router;
+
+use crate::controller::metrics;
+use crate::tests::support::router::serve_origin;
+use crate::tests::support::RouterCache;
+use crate::upstream::backend_hyper_impl::is_response_too_large;
+use crate::upstream::{BackendImpl, Upstream};
+
+const PATH: &str = "/api/v1/user";
+const LIMIT: usize = 16 * 1024;
+
+/// Origin answering small JSON until `endless` is set, then streaming chunks forever.
+async fn start_origin(endless: Arc<AtomicBool>) -> String {
+    let app = Router::new().fallback(move || {
+        let endless = endless.load(Ordering::SeqCst);
+        async move {
+            if endless {
+                let chunk = Bytes::from(vec![b'x'; 4096]);
+                let stream = futures::stream::repeat_with(move || Ok::<_, std::io::Error>(chunk.clone()));
+                (StatusCode::OK, Body::from_stream(stream))
+            } else {
+                (StatusCode::OK, Body::from("{\"small\":true}"))
+            }
+        }
+    });
+
+    serve_origin(app).await
+}
+
+fn start(origin: &str) -> RouterCache<BackendImpl> {
+    RouterCache::builder()
+        .origin(origin)
+        .config(|cfg| {
+            cfg.cache.upstream.as_mut().unwrap().backend.as_mut().unwrap().max_response_size = Some(LIMIT);
+        })
+        .start_backend()
+}
+
+async fn get(cache: &RouterCache<BackendImpl>, uri: &str) -> (StatusCode, Vec<u8>) {
+    let resp = cache.get(uri).await;
+    let status = resp.status();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, body.to_vec())
+}
+
+fn too_large_counter() -> u64 {
+    metrics::metrics_text()
+        .lines()
+        .find_map(|line| line.strip_prefix("upstream_response_too_large "))
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Test that an endless origin body is cut off at the limit and answered with 502.
+#[tokio::test]
+async fn test_endless_upstream_body_is_aborted_with_bad_gateway() {
+    let endless = Arc::new(AtomicBool::new(true));
+    let origin = start_origin(endless).await;
+    let cache = start(&origin);
+
+    let before = too_large_counter();
+    let started = Instant::now();
+    let (status, _) = get(&cache, &format!("{}?user[id]=1", PATH)).await;
+    cache.shutdown.cancel();
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(started.elapsed() < Duration::from_secs(5), "the read must stop at the limit");
+    assert!(too_large_counter() > before);
+}
+
+/// Test that a refresh hitting the limit fails but keeps the stored entry and its payload.
+#[tokio::test]
+async fn test_refresh_over_limit_keeps_entry() {
+    let endless = Arc::new(AtomicBool::new(false));
+    let origin = start_origin(endless.clone()).await;
+    let cache = start(&origin);
+
+    let uri = format!("{}?user[id]=2", PATH);
+    let (status, body) = get(&cache, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"{\"small\":true}");
+
+    let stored = cache.stored(PATH, &[("user[id]", "2")]).expect("entry must be cached after the fill");
+
+    endless.store(true, Ordering::SeqCst);
+    let err = cache.upstream.refresh(&stored).await.err().expect("an endless body must fail the refresh");
+    assert!(is_response_too_large(&err), "unexpected error: {:#}", err);
+    assert!(cache.stored(PATH, &[("user[id]", "2")]).is_some(), "a failed refresh must not remove the entry");
+
+    let (status, body) = get(&cache, &uri).await;
+    cache.shutdown.cancel();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"{\"small\":true}");
+}
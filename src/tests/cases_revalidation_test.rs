@@ -0,0 +1,148 @@
+// Integration tests for conditional refreshes with the stored `ETag` / `Last-Modified`.
+//
+// A real backend points at a local origin that records the validators it receives, answers
+// 304 when they match its current version and a body naming the version otherwise. Entries
+// are refreshed straight through a storage over that backend.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::http::{HeaderMap, StatusCode, Uri};
+use axum::response::IntoResponse;
+use axum::Router;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{self, Config, ConfigTrait};
+use crate::controller::metrics;
+use crate::db::storage::{Map, Storage};
+use crate::model::{Entry, Response as ModelResponse};
+use crate::time;
+use crate::upstream::{BackendImpl, Upstream};
+use crate::workers::RefreshBackend;
+
+const PATH: &str = "/api/v1/user";
+const LAST_MODIFIED: &str = "Sat, 17 Oct 2026 10:00:00 GMT";
+
+/// Validators of every non-healthcheck request: `If-None-Match` and `If-Modified-Since`.
+type Seen = Arc<Mutex<Vec<(Option<String>, Option<String>)>>>;
+
+/// Origin serving version `version` of its answer, tagged `"v<version>"`.
+async fn start_origin(version: Arc<AtomicUsize>) -> (String, Seen) {
+    let seen: Seen = Arc::default();
+    let recorded = seen.clone();
+    let router = Router::new().fallback(move |uri: Uri, headers: HeaderMap| {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        let if_none_match = header("if-none-match");
+        if uri.path() != "/healthz" {
+            recorded.lock().unwrap().push((if_none_match.clone(), header("if-modified-since")));
+        }
+        let version = version.load(Ordering::SeqCst);
+        let etag = format!("\"v{}\"", version);
+        async move {
+            if if_none_match.as_deref() == Some(etag.as_str()) {
+                return (StatusCode::NOT_MODIFIED, [("etag", etag)]).into_response();
+            }
+            let headers = [("etag", etag), ("last-modified", LAST_MODIFIED.to_string())];
+            (StatusCode::OK, headers, format!("{{\"version\":{}}}", version)).into_response()
+        }
+    });
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    (addr, seen)
+}
+
+struct Harness {
+    storage: Arc<Storage>,
+    backend: Arc<BackendImpl>,
+    cfg: Config,
+    shutdown: CancellationToken,
+}
+
+async fn harness(origin: &str) -> Harness {
+    let mut cfg = config::new_test_config();
+    cfg.cache.upstream.as_mut().unwrap().backend.as_mut().unwrap().host = Some(origin.to_string());
+    let shutdown = CancellationToken::new();
+    let backend = BackendImpl::new(shutdown.clone(), cfg.cache.upstream.as_ref().unwrap().backend.clone())
+        .expect("backend must start");
+    let map = Arc::new(Map::new(shutdown.clone(), cfg.clone()));
+    let storage = Storage::new(shutdown.clone(), cfg.clone(), backend.clone(), map).expect("storage must start");
+    Harness { storage, backend, cfg, shutdown }
+}
+
+impl Harness {
+    /// Fills the entry of user 1 from the origin and stores it.
+    async fn fill(&self) -> Entry {
+        let rule = self.cfg.rule(PATH).unwrap();
+        let queries = vec![(b"user[id]".to_vec(), b"1".to_vec())];
+        let resp = self.backend.request("GET", &rule, &queries, &[], None).await.expect("fill must succeed");
+        let entry = Entry::new(rule, &queries, &[]);
+        entry.set_payload(&queries, &[], &ModelResponse { status: resp.status, headers: resp.headers, body: resp.body });
+        assert!(self.storage.set(entry.clone()));
+        entry
+    }
+
+    fn body(&self, entry: &Entry) -> Vec<u8> {
+        self.storage.get_by_key(entry.key()).expect("entry must stay stored").response_payload().unwrap().body
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
+
+/// Test that a refresh sends the stored validators, kept though the rule does not list them,
+/// and that a 304 leaves the stored payload in place while touching its refresh time.
+#[tokio::test]
+async fn test_not_modified_refresh_keeps_payload() {
+    let _clock = time::start(Duration::from_millis(1));
+    let version = Arc::new(AtomicUsize::new(1));
+    let (origin, seen) = start_origin(version.clone()).await;
+    let h = harness(&origin).await;
+
+    let entry = h.fill().await;
+    let validators = entry.validators().unwrap();
+    assert_eq!(validators.etag.as_deref(), Some(&b"\"v1\""[..]));
+    assert_eq!(validators.last_modified.as_deref(), Some(LAST_MODIFIED.as_bytes()));
+
+    let payload = entry.payload_bytes();
+    entry.set_refreshed_at_for_tests(1);
+    let not_modified = metrics::refresh_not_modified();
+    h.storage.on_ttl(&entry).await.expect("a 304 refresh must land");
+
+    assert_eq!(
+        seen.lock().unwrap().last().cloned(),
+        Some((Some("\"v1\"".to_string()), Some(LAST_MODIFIED.to_string())))
+    );
+    assert!(metrics::refresh_not_modified() > not_modified);
+    let resident = h.storage.get_by_key(entry.key()).unwrap();
+    assert_eq!(resident.payload_bytes(), payload, "the stored payload is not rewritten");
+    assert!(resident.fresh_at() > 1, "the refresh time is touched");
+    assert_eq!(h.body(&entry), b"{\"version\":1}");
+}
+
+/// Test that once the origin has a new version, the refresh brings and stores it with its
+/// new validators.
+#[tokio::test]
+async fn test_modified_refresh_replaces_payload() {
+    let version = Arc::new(AtomicUsize::new(1));
+    let (origin, seen) = start_origin(version.clone()).await;
+    let h = harness(&origin).await;
+
+    let entry = h.fill().await;
+    version.store(2, Ordering::SeqCst);
+    let applied = metrics::refresh_applied();
+    h.storage.on_ttl(&entry).await.expect("refresh must land");
+
+    assert_eq!(seen.lock().unwrap().last().unwrap().0.as_deref(), Some("\"v1\""));
+    assert!(metrics::refresh_applied() > applied);
+    assert_eq!(h.body(&entry), b"{\"version\":2}");
+    let resident = h.storage.get_by_key(entry.key()).unwrap();
+    assert_eq!(resident.validators().unwrap().etag.as_deref(), Some(&b"\"v2\""[..]));
+}
@@ -0,0 +1,142 @@
+// Integration tests for `cache_value.rollout_percent` and `/advcache/rollout`.
+//
+// The backend points at a local origin that counts API calls per user id, so the tests see which
+// keys were stored.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode, Uri};
+use axum::Router;
+
+use crate::config::RolloutPercent;
+use crate::controller::metrics::{self, RolloutResult};
+use crate::controller::{RolloutController, ShowConfigController};
+use crate::tests::support::router::{into_text, serve_origin};
+use crate::tests::support::RouterCache;
+use crate::upstream::BackendImpl;
+
+const PATH: &str = "/api/v1/user";
+
+/// Origin counting API calls by query string.
+async fn start_origin() -> (String, Arc<Mutex<HashMap<String, usize>>>) {
+    let calls = Arc::new(Mutex::new(HashMap::new()));
+    let counter = calls.clone();
+    let app = Router::new().fallback(move |uri: Uri| {
+        let counter = counter.clone();
+        async move {
+            if uri.path().starts_with("/api/") {
+                *counter.lock().unwrap().entry(uri.query().unwrap_or_default().to_string()).or_insert(0) += 1;
+            }
+            (StatusCode::OK, [("content-type", "application/json")], "{\"ok\":true}")
+        }
+    });
+
+    (serve_origin(app).await, calls)
+}
+
+/// Cache whose user rule is ramped to `percent`, under a path of its own as rollout metrics are
+/// global.
+fn start(origin: &str, path: &str, percent: u8) -> RouterCache<BackendImpl> {
+    RouterCache::builder()
+        .origin(origin)
+        .config(|cfg| {
+            let rules = cfg.cache.rules.as_mut().unwrap();
+            let mut rule = (*rules[PATH]).clone();
+            rule.path = Some(path.to_string());
+            rule.path_bytes = Some(path.as_bytes().to_vec());
+            rule.cache_value.rollout_percent = RolloutPercent::new(percent);
+            rules.insert(path.to_string(), Arc::new(rule));
+        })
+        .start_backend()
+        .mount(|cache| ShowConfigController::new(cache.cfg.clone()))
+        .mount(|cache| RolloutController::new(cache.cfg.clone()))
+}
+
+async fn get(cache: &RouterCache<BackendImpl>, uri: &str) -> StatusCode {
+    cache.get(uri).await.status()
+}
+
+fn origin_calls(calls: &Mutex<HashMap<String, usize>>, id: u32) -> usize {
+    let calls = calls.lock().unwrap();
+    let id = id.to_string();
+    calls
+        .iter()
+        .filter(|(query, _)| url::form_urlencoded::parse(query.as_bytes()).any(|(k, v)| k == "user[id]" && v == id))
+        .map(|(_, n)| n)
+        .sum()
+}
+
+/// Test that with a partial rollout each key is consistently either stored or proxied on every
+/// request, that about the configured share is stored, and that both sides are counted.
+#[tokio::test]
+async fn test_rollout_is_key_stable() {
+    let (origin, calls) = start_origin().await;
+    let path = "/api/v1/rollout-stable";
+    let cache = start(&origin, path, 50);
+
+    const USERS: u32 = 40;
+    for _ in 0..3 {
+        for id in 0..USERS {
+            assert_eq!(get(&cache, &format!("{}?user[id]={}", path, id)).await, StatusCode::OK);
+        }
+    }
+
+    let mut cached = 0;
+    for id in 0..USERS {
+        match origin_calls(&calls, id) {
+            1 => cached += 1,
+            3 => {}
+            n => panic!("user {} reached the origin {} times: neither always cached nor always proxied", id, n),
+        }
+    }
+    assert!(cached > 0 && cached < USERS, "a 50% rollout must split the keys, {} of {} cached", cached, USERS);
+
+    let (hits, misses) = (
+        metrics::rollout_requests(path, true, RolloutResult::Hit),
+        metrics::rollout_requests(path, true, RolloutResult::Miss),
+    );
+    assert_eq!((hits, misses), (2 * cached as u64, cached as u64));
+    assert_eq!(
+        metrics::rollout_requests(path, false, RolloutResult::Proxied),
+        3 * (USERS - cached) as u64
+    );
+}
+
+/// Test that the percent is changed at runtime through the admin endpoint, takes effect for the
+/// next request and is shown in the effective config.
+#[tokio::test]
+async fn test_rollout_adjusted_at_runtime() {
+    let (origin, calls) = start_origin().await;
+    let path = "/api/v1/rollout-runtime";
+    let cache = start(&origin, path, 0);
+
+    for _ in 0..2 {
+        assert_eq!(get(&cache, &format!("{}?user[id]=7", path)).await, StatusCode::OK);
+    }
+    assert_eq!(origin_calls(&calls, 7), 2, "nothing is stored at 0%");
+
+    let set = |query: &str| Request::post(format!("/advcache/rollout?{}", query)).body(Body::empty()).unwrap();
+    let (status, body) = into_text(cache.send(set(&format!("path={}&percent=100", path))).await).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body.contains("\"percent\":100"), "{}", body);
+
+    for _ in 0..2 {
+        assert_eq!(get(&cache, &format!("{}?user[id]=7", path)).await, StatusCode::OK);
+    }
+    assert_eq!(origin_calls(&calls, 7), 3, "the second request at 100% must be a hit");
+
+    let (status, _) = into_text(cache.send(set(&format!("path={}&percent=30", path))).await).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, config) = cache.get_text("/advcache/config").await;
+    let config: serde_json::Value = serde_json::from_str(&config).unwrap();
+    assert_eq!(config["cache"]["rules"][path]["cache_value"]["rollout_percent"], 30);
+
+    let (status, _) = into_text(cache.send(set(&format!("path={}&percent=101", path))).await).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = into_text(cache.send(set("path=/api/v1/unknown&percent=10")).await).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, list) = cache.get_text("/advcache/rollout").await;
+    assert!(list.contains(&format!("{{\"path\":\"{}\",\"percent\":30}}", path)), "{}", list);
+}
@@ -0,0 +1,52 @@
+// Integration tests for the rule settings gauges of `/metrics` (`cache_rule_*`).
+//
+// Rules are changed the way a running cache changes them, by a reload and through
+// `/advcache/rollout`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+
+use crate::config;
+use crate::controller::{PrometheusMetricsController, RolloutController};
+use crate::tests::support::RouterCache;
+
+const PATH: &str = "/api/v1/user";
+
+/// Value of the series `name{rule="PATH"}` in a scrape, if present.
+async fn gauge(cache: &RouterCache, name: &str) -> Option<String> {
+    let (status, text) = cache.get_text("/metrics").await;
+    assert_eq!(status, StatusCode::OK);
+    let series = format!("{}{{rule=\"{}\"}} ", name, PATH);
+    text.lines().find_map(|line| line.strip_prefix(series.as_str()).map(str::to_string))
+}
+
+/// Test that the rule gauges report the configured settings and follow a reload changing the
+/// TTL and refresh, and a rollout adjusted at runtime.
+#[tokio::test]
+async fn test_rule_gauges_follow_runtime_changes() {
+    let cache = RouterCache::start()
+        .mount(|cache| PrometheusMetricsController::new(cache.cfg.clone()))
+        .mount(|cache| RolloutController::new(cache.cfg.clone()));
+    assert_eq!(gauge(&cache, "cache_rule_ttl_seconds").await.as_deref(), Some("60"));
+    assert_eq!(gauge(&cache, "cache_rule_refresh_enabled").await.as_deref(), Some("1"));
+    assert_eq!(gauge(&cache, "cache_rule_beta").await.as_deref(), Some("0.4"));
+    assert_eq!(gauge(&cache, "cache_rule_rollout_percent").await.as_deref(), Some("100"));
+
+    let mut next = config::new_test_config();
+    let rules = next.cache.rules.as_mut().unwrap();
+    let mut rule = (*rules[PATH]).clone();
+    let refresh = rule.refresh.as_mut().unwrap();
+    refresh.ttl = Some(Duration::from_millis(90_500));
+    refresh.enabled = false;
+    rules.insert(PATH.to_string(), Arc::new(rule));
+    cache.cfg.apply_reload(&next);
+    assert_eq!(gauge(&cache, "cache_rule_ttl_seconds").await.as_deref(), Some("90.5"));
+    assert_eq!(gauge(&cache, "cache_rule_refresh_enabled").await.as_deref(), Some("0"));
+
+    let set = Request::post(format!("/advcache/rollout?path={}&percent=25", PATH)).body(Body::empty()).unwrap();
+    assert_eq!(cache.send(set).await.status(), StatusCode::OK);
+    assert_eq!(gauge(&cache, "cache_rule_rollout_percent").await.as_deref(), Some("25"));
+}
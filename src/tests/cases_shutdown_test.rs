@@ -0,0 +1,64 @@
+// Integration tests for the last shutdown report (`/advcache/shutdown/last`).
+
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
+
+use crate::config::overrides::RuntimeOverrides;
+use crate::controller::ShutdownReportController;
+use crate::http::Controller;
+use crate::shutdown::{self, GracefulShutdown};
+
+async fn get(router: &Router) -> (StatusCode, String) {
+    let request = Request::get("/advcache/shutdown/last").body(Body::empty()).unwrap();
+    let resp = router.clone().oneshot(request).await.unwrap();
+    let status = resp.status();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Test that a report written to the overrides file on shutdown is served by the next process,
+/// including a dump that ran over its budget and the phase that still ran after it.
+#[tokio::test]
+async fn test_last_shutdown_report_is_served() {
+    let path = std::env::temp_dir().join(format!("advcache-overrides-shutdown-{}.yaml", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let router = ShutdownReportController::new(Some(path.to_string_lossy().into_owned())).add_route(Router::new());
+
+    let (status, _) = get(&router).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "nothing is recorded before the first shutdown");
+
+    let gsh = GracefulShutdown::new(CancellationToken::new());
+    gsh.run_phase(shutdown::PHASE_DUMP, Duration::from_millis(20), async {
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        Ok(())
+    })
+    .await;
+    gsh.run_phase(shutdown::PHASE_FINAL_CANCEL, Duration::from_millis(20), async {
+        gsh.cancel_and_wait().await;
+        Ok(())
+    })
+    .await;
+    RuntimeOverrides::update(&path, |o| o.last_shutdown = Some(gsh.report())).unwrap();
+
+    let (status, body) = get(&router).await;
+    assert_eq!(status, StatusCode::OK);
+    let report: shutdown::ShutdownReport = serde_json::from_str(&body).unwrap();
+    assert_eq!(report, gsh.report());
+    assert_eq!(report.phases[0].outcome, shutdown::PhaseOutcome::TimedOut);
+    assert_eq!(report.phases[1].outcome, shutdown::PhaseOutcome::Completed);
+    let _ = std::fs::remove_file(&path);
+}
+
+/// Test that the endpoint answers 404 without an overrides file configured.
+#[tokio::test]
+async fn test_last_shutdown_without_overrides() {
+    let router = ShutdownReportController::new(None).add_route(Router::new());
+    let (status, body) = get(&router).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body.contains("runtime.overrides"), "{}", body);
+}
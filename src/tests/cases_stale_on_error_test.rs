@@ -0,0 +1,114 @@
+// Integration tests for serving stored entries when a miss fill fails with 5xx.
+//
+// Concurrent misses of one key are collapsed into one fill, so an entry is stored while a fill
+// is in flight only by a miss that gave up waiting for it (after the backend timeout) and
+// filled on its own. Those cases run on an in-process cache over a mock upstream whose backend
+// timeout is short; the upstream behind /api/v1/flaky* of the e2e harness alternates per
+// request: odd calls answer 503 after 600ms, even calls answer 200 after 50ms.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::Router;
+use tower::ServiceExt;
+
+use crate::tests::support::RouterCache;
+use crate::upstream::testing::MockUpstream;
+use crate::upstream::Response;
+use crate::support::{assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, new_namespace, with_ns, H};
+
+const PATH: &str = "/api/v1/user";
+const URI: &str = "/api/v1/user?user[id]=4242";
+
+const CACHE_STATUS: &str = "x-cache-status";
+
+fn flaky_params() -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    params.insert("user[id]".to_string(), "4242".to_string());
+    params.insert("domain".to_string(), "flaky.example".to_string());
+    params
+}
+
+fn identity() -> H {
+    let mut headers = H::new();
+    headers.insert("Accept-Encoding".to_string(), "identity".to_string());
+    headers
+}
+
+/// Cache whose misses wait for a concurrent fill of their key for 200ms at most.
+fn short_wait_cache(stale_on_error: bool, upstream: Arc<MockUpstream>) -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| {
+            let backend = cfg.cache.upstream.as_mut().unwrap().backend.as_mut().unwrap();
+            backend.timeout = Some(Duration::from_millis(200));
+            let rules = cfg.cache.rules.as_mut().unwrap();
+            let mut rule = (*rules[PATH]).clone();
+            rule.stale_on_error = Some(stale_on_error);
+            rules.insert(PATH.to_string(), Arc::new(rule));
+        })
+        .upstream(upstream)
+        .start()
+}
+
+async fn get(router: Router) -> (StatusCode, HeaderMap) {
+    let resp = router.oneshot(Request::get(URI).body(Body::empty()).unwrap()).await.unwrap();
+    let (parts, body) = resp.into_parts();
+    let _ = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    (parts.status, parts.headers)
+}
+
+/// Fires a miss whose fill answers 503 after 600ms and, while it is in flight, one that gives
+/// up waiting for it and fills the cache. Returns (status, headers) of the first (failing) one.
+async fn race_failing_fill_with_successful_one(stale_on_error: bool) -> (StatusCode, HeaderMap) {
+    let upstream = MockUpstream::builder().latency(Duration::from_millis(600)).build();
+    let cache = short_wait_cache(stale_on_error, upstream.clone());
+
+    let failing = tokio::spawn(get(cache.router.clone()));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    upstream.set_latency(Duration::from_millis(50));
+
+    let (status, _) = get(cache.router.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(upstream.fills(), 2, "the second miss filled on its own");
+    // Resolved once its latency is over, the first fill gets the 503.
+    upstream.set_response(PATH, Response::empty(503));
+
+    failing.await.unwrap()
+}
+
+/// Test that a failed fill is answered from the entry stored meanwhile, marked as STALE-ERROR.
+#[tokio::test]
+async fn test_serves_stored_entry_on_5xx_fill() {
+    let (status, headers) = race_failing_fill_with_successful_one(true).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers.get(CACHE_STATUS).and_then(|v| v.to_str().ok()), Some("STALE-ERROR"));
+}
+
+/// Test that the 5xx is propagated when nothing is stored for the key.
+#[tokio::test]
+async fn test_propagates_5xx_without_stored_entry() {
+    init_test_harness(Capability::Http).await.unwrap();
+
+    let ns = new_namespace("Test_StaleOnError_NothingStored");
+    let base = cache_addr().await;
+    let url = format!("{}{}", base, with_ns("/api/v1/flaky", &ns, &flaky_params()));
+
+    let (status, headers, _, _) =
+        assert_ok(do_json::<serde_json::Value>("GET", &url, &identity()).await);
+
+    assert_equal(503, status);
+    assert!(!headers.contains_key(CACHE_STATUS), "unexpected cache status: {:?}", headers);
+}
+
+/// Test that a rule with `stale_on_error: false` propagates the 5xx even if an entry is stored.
+#[tokio::test]
+async fn test_rule_opt_out_propagates_5xx() {
+    let (status, headers) = race_failing_fill_with_successful_one(false).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(!headers.contains_key(CACHE_STATUS), "unexpected cache status: {:?}", headers);
+}
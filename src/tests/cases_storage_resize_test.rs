@@ -0,0 +1,130 @@
+// Integration tests for resizing the storage at runtime (`/advcache/storage/resize`).
+//
+// An 8 MiB storage is filled with entries of a known weight and resized through the admin
+// routes; the limits are checked against the bytes left stored.
+
+use std::time::{Duration, Instant};
+
+use axum::http::{Method, StatusCode};
+
+use crate::config::overrides::RuntimeOverrides;
+use crate::config::{ConfigTrait, MemoryLimits};
+use crate::controller::StorageResizeController;
+use crate::db::{resize, Storage};
+use crate::model::{Entry, Response as ModelResponse};
+use crate::tests::support::RouterCache;
+
+const MIB: i64 = 1 << 20;
+
+fn overrides_file(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("advcache-overrides-{}-{}.yaml", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().into_owned()
+}
+
+/// Cache of an 8 MiB storage evicted every 20ms, without admission so every set is stored.
+fn start(overrides: Option<&str>) -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| {
+            cfg.cache.admission = None;
+            let eviction = cfg.cache.eviction.as_mut().unwrap();
+            eviction.check_interval = Some(Duration::from_millis(20));
+            let eviction = eviction.clone();
+            cfg.cache.storage.as_mut().unwrap().set_size(8 * MIB, Some(&eviction));
+            cfg.cache.runtime.as_mut().unwrap().overrides = overrides.map(str::to_string);
+        })
+        .start()
+        .mount(|cache| StorageResizeController::new(cache.db.clone(), cache.cfg.runtime().overrides.clone()))
+}
+
+/// Stores `count` entries with 10 KiB bodies, keyed from `first` on.
+fn fill(cache: &RouterCache, first: usize, count: usize) {
+    let rule = cache.cfg.rule("/api/v1/user").unwrap();
+    for id in first..first + count {
+        let queries = vec![(b"user[id]".to_vec(), format!("{id}").into_bytes())];
+        let entry = Entry::new(rule.clone(), &queries, &[]);
+        let resp = ModelResponse { status: 200, headers: vec![], body: vec![b'a'; 10 << 10] };
+        entry.set_payload(&queries, &[], &resp);
+        entry.touch_refreshed_at();
+        assert!(cache.db.set(entry));
+    }
+}
+
+async fn wait_until(what: &str, mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out waiting until {}", what);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Test that a shrink below the bytes stored answers at once and is evicted down in the
+/// background, that sets are held to the new limits after, and that a grow stores more.
+#[tokio::test]
+async fn test_shrink_is_evicted_in_background_and_grow_stores_more() {
+    let cache = start(None);
+    let (cfg, db) = (&cache.cfg, &cache.db);
+
+    fill(&cache, 0, 400);
+    let (stored, len) = db.stat();
+    assert_eq!(len, 400);
+    assert!(stored > 3 * MIB, "about 4 MiB is stored, got {}", stored);
+    let weight = stored / len;
+
+    let (status, body) = cache.call_json(Method::POST, "/advcache/storage/resize?size=2MB").await;
+    assert_eq!(status, StatusCode::OK);
+    let shrunk = MemoryLimits::derive(2 * MIB, cfg.eviction());
+    assert_eq!((body["size"].as_i64(), body["soft"].as_i64(), body["resized"].as_bool()), (Some(2 * MIB), Some(shrunk.soft), Some(true)));
+    assert_eq!(db.memory_limits(), shrunk);
+
+    wait_until("the shrink is evicted", || db.stat().0 <= shrunk.soft).await;
+    fill(&cache, 400, 200);
+    assert!(db.stat().0 <= shrunk.hard + weight, "sets are held to the shrunk hard limit");
+
+    let (status, body) = cache.call_json(Method::POST, "/advcache/storage/resize?size=16MB").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["size"].as_i64(), Some(16 * MIB));
+    let before = db.stat().1;
+    fill(&cache, 600, 800);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(db.stat().1, before + 800, "the grown storage keeps what fits under its soft limit");
+}
+
+/// Test that a resize is persisted and restored after a restart, kept over a config reload,
+/// and dropped by going back to the configured size.
+#[tokio::test]
+async fn test_resize_outlives_restart_until_reset() {
+    let path = overrides_file("resize");
+    let cache = start(Some(&path));
+    let (cfg, db) = (&cache.cfg, &cache.db);
+    let configured = cfg.storage().memory_limits();
+
+    for bad in ["", "?size=0", "?size=lots"] {
+        let (status, _) = cache.call_json(Method::POST, &format!("/advcache/storage/resize{}", bad)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "resize{}", bad);
+    }
+    assert_eq!(db.memory_limits(), configured);
+
+    let (status, _) = cache.call_json(Method::POST, "/advcache/storage/resize?size=4MB").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(RuntimeOverrides::load(path.as_ref()).unwrap().storage_size, Some(4 * MIB));
+    db.on_config_reload(cfg.clone());
+    assert_eq!(db.memory_limits().size, 4 * MIB, "a reload keeps the size set at runtime");
+
+    let restarted = start(Some(&path));
+    assert_eq!(restarted.db.memory_limits(), configured);
+    resize::restore(restarted.db.as_ref(), path.as_ref());
+    assert_eq!(restarted.db.memory_limits(), MemoryLimits::derive(4 * MIB, cfg.eviction()));
+
+    let (status, body) = cache.call_json(Method::DELETE, "/advcache/storage/resize").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((body["size"].as_i64(), body["resized"].as_bool()), (Some(8 * MIB), Some(false)));
+    assert_eq!(db.memory_limits(), configured);
+    assert_eq!(RuntimeOverrides::load(path.as_ref()).unwrap().storage_size, None);
+
+    let (status, body) = cache.call_json(Method::GET, "/advcache/storage/size").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["hard"].as_i64(), Some(configured.hard));
+    drop((cache, restarted));
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,84 @@
+// Integration tests for invalidation by `Surrogate-Key` tag (`/advcache/invalidate/tag`).
+//
+// The invalidation controller is mounted next to the cache, over a mock upstream tagging the
+// responses of some users, so every fill is counted and the storage can be inspected directly.
+
+use axum::http::StatusCode;
+
+use crate::controller::InvalidateController;
+use crate::db::Storage;
+use crate::tests::support::RouterCache;
+use crate::upstream::testing::{Call, MockUpstream};
+use crate::upstream::Response;
+
+const PATH: &str = "/api/v1/user";
+
+/// Users 1 and 2 are answered tagged `product-123 category-9`, the others untagged.
+fn start() -> RouterCache {
+    let upstream = MockUpstream::builder()
+        .handle(PATH, |call: &Call| {
+            let response = Response::ok(format!("user {}", call.query));
+            if call.query.contains("=1") || call.query.contains("=2") {
+                response.with_header("Surrogate-Key", "product-123 category-9")
+            } else {
+                response
+            }
+        })
+        .build();
+    RouterCache::builder()
+        .upstream(upstream)
+        .start()
+        .mount(|cache| InvalidateController::new(cache.cfg.clone(), cache.db.clone()))
+}
+
+async fn fill(cache: &RouterCache, id: u32) {
+    assert_eq!(cache.get_json(&format!("{}?user[id]={}", PATH, id)).await.0, StatusCode::OK);
+}
+
+/// Test that removing by a tag removes the two entries carrying it and keeps the untagged
+/// one, giving back the memory of the tagged entries and of their index records.
+#[tokio::test]
+async fn test_remove_by_tag_purges_only_tagged_entries() {
+    let cache = start();
+    fill(&cache, 3).await;
+    let untagged = cache.db.stat();
+    fill(&cache, 1).await;
+    fill(&cache, 2).await;
+    assert_eq!(cache.db.stat().1, 3);
+
+    let (status, body) = cache.get_json("/advcache/invalidate/tag?key=product-123&_remove").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!((body["success"].as_bool(), body["affected"].as_i64()), (Some(true), Some(2)));
+    assert_eq!(cache.db.stat(), untagged, "only the untagged entry is left, the index holds nothing");
+
+    let fills = cache.upstream.fills();
+    fill(&cache, 3).await;
+    assert_eq!(cache.upstream.fills(), fills, "the untagged entry is served from the cache");
+    fill(&cache, 1).await;
+    fill(&cache, 2).await;
+    assert_eq!(cache.upstream.fills(), fills + 2, "the tagged entries are filled again");
+
+    let (_, body) = cache.get_json("/advcache/invalidate/tag?key=category-9&_remove").await;
+    assert_eq!(body["affected"].as_i64(), Some(2), "refilled entries are indexed again");
+    assert_eq!(cache.db.stat(), untagged);
+}
+
+/// Test that invalidating by a tag marks the tagged entries outdated without removing them,
+/// that a tag nothing carries affects nothing, and that the tag is required.
+#[tokio::test]
+async fn test_mark_by_tag_keeps_entries() {
+    let cache = start();
+    for id in 1..=3 {
+        fill(&cache, id).await;
+    }
+    let stat = cache.db.stat();
+
+    let (status, body) = cache.get_json("/advcache/invalidate/tag?key=category-9").await;
+    assert_eq!((status, body["affected"].as_i64()), (StatusCode::OK, Some(2)));
+    assert_eq!(cache.db.stat(), stat, "marked entries stay stored");
+
+    let (_, body) = cache.get_json("/advcache/invalidate/tag?key=product-999&_remove").await;
+    assert_eq!(body["affected"].as_i64(), Some(0));
+    assert_eq!(cache.get_json("/advcache/invalidate/tag").await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(cache.get_json("/advcache/invalidate/tag?key=+").await.0, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,80 @@
+// Integration tests for requests refused to protect the origin (`upstream.throttle_status`).
+//
+// The cache runs over a real backend whose `deny` policy limiter allows a single request per
+// second to a local origin, so that a burst of misses gets throttled.
+
+use axum::http::{header, StatusCode};
+use axum::response::Response;
+use axum::Router;
+
+use crate::config::Config;
+use crate::controller::cache::ThrottleReason;
+use crate::controller::metrics;
+use crate::tests::support::router::serve_origin;
+use crate::tests::support::RouterCache;
+use crate::upstream::BackendImpl;
+
+const PATH: &str = "/api/v1/user";
+
+/// Cache over a backend, on an origin answering 200 to everything, that takes one request per
+/// second.
+async fn start(throttle_status: Option<u16>) -> RouterCache<BackendImpl> {
+    let addr = serve_origin(Router::new().fallback(|| async { "{}" })).await;
+    RouterCache::builder()
+        .origin(&addr)
+        .config(|cfg| {
+            let upstream = cfg.cache.upstream.as_mut().unwrap();
+            upstream.throttle_status = throttle_status;
+            upstream.backend.as_mut().unwrap().rate = Some(1);
+        })
+        .start_backend()
+}
+
+/// Sends misses of distinct keys until one is throttled. The policy is process-wide and another
+/// test may switch it to `await` for a moment, so a few requests may get through first.
+async fn first_throttled(cache: &RouterCache<BackendImpl>) -> Response {
+    for id in 0..64 {
+        let resp = cache.get(&format!("{}?user[id]=throttle-{}", PATH, id)).await;
+        if resp.headers().contains_key("x-ratelimit-reason") {
+            return resp;
+        }
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    panic!("a burst over the deny limiter must be throttled");
+}
+
+/// Test that a miss refused by the deny limiter is answered 429 with `Retry-After` and the
+/// reason, counted as throttled rather than as an upstream error.
+#[tokio::test]
+async fn test_deny_limiter_answers_429_with_retry_after() {
+    let cache = start(None).await;
+    let throttled = metrics::requests_throttled(ThrottleReason::UpstreamProtection);
+
+    let resp = first_throttled(&cache).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resp.headers()[header::RETRY_AFTER], "1", "a token is due within the second, rounded up");
+    assert_eq!(resp.headers()["x-ratelimit-reason"], "upstream-protection");
+    assert_eq!(resp.headers()["x-error-reason"], "upstream_protection");
+    assert!(metrics::requests_throttled(ThrottleReason::UpstreamProtection) > throttled);
+}
+
+/// Test that `upstream.throttle_status` replaces the 429.
+#[tokio::test]
+async fn test_throttle_status_is_configurable() {
+    let cache = start(Some(503)).await;
+
+    let resp = first_throttled(&cache).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(resp.headers().contains_key(header::RETRY_AFTER));
+    drop(cache);
+
+    let yaml = |status: u16| {
+        format!(
+            "cache:\n  env: test\n  enabled: true\n  upstream:\n    throttle_status: {}\n    backend:\n      id: main\n      enabled: true\n      scheme: http\n      host: main.local:8080\n      timeout: 10s\n      max_timeout: 1m\n",
+            status
+        )
+    };
+    assert_eq!(Config::from_yaml(&yaml(503)).unwrap().throttle_status(), 503);
+    let err = Config::from_yaml(&yaml(200)).expect_err("a throttle status must be a 4xx or 5xx");
+    assert!(format!("{:#}", err).contains("upstream.throttle_status"), "{:#}", err);
+}
@@ -0,0 +1,71 @@
+// Integration tests for tombstones left by `_remove` invalidations with `_tombstone`.
+//
+// The invalidation controller is mounted next to the cache over a mock upstream, so every
+// fill is counted and the storage can be inspected directly.
+
+use std::time::Duration;
+
+use axum::http::StatusCode;
+
+use crate::controller::InvalidateController;
+use crate::tests::support::RouterCache;
+
+const PATH: &str = "/api/v1/user";
+
+fn start() -> RouterCache {
+    RouterCache::start().mount(|cache| InvalidateController::new(cache.cfg.clone(), cache.db.clone()))
+}
+
+async fn get(cache: &RouterCache, uri: &str) -> StatusCode {
+    cache.get_text(uri).await.0
+}
+
+fn is_stored(cache: &RouterCache, user_id: &str) -> bool {
+    cache.stored(PATH, &[("user[id]", user_id)]).is_some()
+}
+
+/// Test that a tombstoned key is fetched from the origin without being stored until the tombstone expires.
+#[tokio::test]
+async fn test_tombstone_blocks_refill_until_expiry() {
+    let cache = start();
+    let uri = format!("{}?user[id]=1", PATH);
+
+    assert_eq!(get(&cache, &uri).await, StatusCode::OK);
+    assert_eq!(get(&cache, &uri).await, StatusCode::OK);
+    assert_eq!(cache.upstream.fills(), 1);
+    assert!(is_stored(&cache, "1"));
+
+    let invalidate = format!("/advcache/invalidate?_path={}&user[id]=1&_remove=1&_tombstone=400ms", PATH);
+    assert_eq!(get(&cache, &invalidate).await, StatusCode::OK);
+    assert!(!is_stored(&cache, "1"));
+
+    for _ in 0..3 {
+        assert_eq!(get(&cache, &uri).await, StatusCode::OK);
+        assert!(!is_stored(&cache, "1"), "tombstoned key must not be stored");
+    }
+    assert_eq!(cache.upstream.fills(), 4, "every request in the window must reach the origin");
+
+    // Other keys of the rule are cached as usual meanwhile.
+    let other = format!("{}?user[id]=2", PATH);
+    assert_eq!(get(&cache, &other).await, StatusCode::OK);
+    assert!(is_stored(&cache, "2"));
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    assert_eq!(get(&cache, &uri).await, StatusCode::OK);
+    assert!(is_stored(&cache, "1"));
+    assert_eq!(get(&cache, &uri).await, StatusCode::OK);
+    assert_eq!(cache.upstream.fills(), 6);
+}
+
+/// Test that `_tombstone` is rejected when it does not parse or comes without `_remove`.
+#[tokio::test]
+async fn test_tombstone_param_validation() {
+    let cache = start();
+
+    let malformed = format!("/advcache/invalidate?_path={}&_remove=1&_tombstone=soon", PATH);
+    assert_eq!(get(&cache, &malformed).await, StatusCode::BAD_REQUEST);
+
+    let without_remove = format!("/advcache/invalidate?_path={}&_tombstone=5s", PATH);
+    assert_eq!(get(&cache, &without_remove).await, StatusCode::BAD_REQUEST);
+}
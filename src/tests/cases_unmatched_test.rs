@@ -0,0 +1,67 @@
+// Integration tests for the counts of requests no rule matches (`/advcache/stats`).
+//
+// The counts are process wide, so each test requests a prefix of its own.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+
+use crate::controller::{PrometheusMetricsController, StatsController};
+use crate::tests::support::router::into_text;
+use crate::tests::support::RouterCache;
+
+/// Path of a rule of the tests alone, the user rule under another prefix.
+const RULED_PATH: &str = "/unmatched-test/ruled";
+
+fn start() -> RouterCache {
+    RouterCache::builder()
+        .config(|cfg| {
+            let rules = cfg.cache.rules.as_mut().unwrap();
+            let mut rule = (*rules["/api/v1/user"]).clone();
+            rule.path = Some(RULED_PATH.to_string());
+            rule.path_bytes = Some(RULED_PATH.as_bytes().to_vec());
+            rules.insert(RULED_PATH.to_string(), Arc::new(rule));
+        })
+        .start()
+        .mount(|_| StatsController::new())
+        .mount(|cache| PrometheusMetricsController::new(cache.cfg.clone()))
+}
+
+async fn call(cache: &RouterCache, method: Method, uri: &str) -> (StatusCode, String) {
+    into_text(cache.send(Request::builder().method(method).uri(uri).body(Body::empty()).unwrap()).await).await
+}
+
+/// Test that a hammered unmatched path surfaces under its prefix in `/advcache/stats` and in
+/// `unmatched_requests_total`, whatever the method and the rest of the path.
+#[tokio::test]
+async fn test_hammered_unmatched_path_is_reported() {
+    let cache = start();
+
+    for i in 0..300 {
+        let method = if i % 3 == 0 { Method::POST } else { Method::GET };
+        assert_eq!(call(&cache, method, &format!("/unmatched-test/hammer/{}?x=1", i)).await.0, StatusCode::OK);
+    }
+    assert_eq!(cache.upstream.proxied(), 300);
+
+    let (status, body) = call(&cache, Method::GET, "/advcache/stats?top=256").await;
+    assert_eq!(status, StatusCode::OK);
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let top = stats["unmatched"]["top"].as_array().unwrap();
+    let hammered = top.iter().find(|s| s["prefix"] == "/unmatched-test/hammer").expect("the prefix is reported");
+    assert_eq!(hammered["requests"], 300);
+    assert!(stats["unmatched"]["requests"].as_u64().unwrap() >= 300);
+
+    let (_, metrics) = call(&cache, Method::GET, "/metrics").await;
+    assert!(metrics.contains("unmatched_requests_total{prefix=\"/unmatched-test/hammer\"} 300\n"), "{}", metrics);
+}
+
+/// Test that requests of ruled paths are not counted, whatever the method.
+#[tokio::test]
+async fn test_ruled_paths_are_not_counted() {
+    let cache = start();
+    for method in [Method::GET, Method::GET, Method::PUT] {
+        assert_eq!(call(&cache, method, &format!("{}?user[id]=1", RULED_PATH)).await.0, StatusCode::OK);
+    }
+    assert_eq!(crate::controller::unmatched::unmatched().requests(RULED_PATH), 0);
+}
@@ -0,0 +1,84 @@
+// Integration tests for responses varying on request headers (`Vary`).
+//
+// The mock upstream answers from the Accept-Language it was sent, so the tests see which
+// variant each request is served.
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+
+use crate::db::Storage;
+use crate::tests::support::router::into_text;
+use crate::tests::support::RouterCache;
+use crate::upstream::testing::{Call, MockUpstream};
+use crate::upstream::Response;
+
+const USER_PATH: &str = "/api/v1/user";
+
+/// Cache over a mock upstream answering the user path with the Accept-Language it was sent
+/// and the `Vary` given.
+fn start(vary: &'static str) -> RouterCache {
+    let upstream = MockUpstream::builder()
+        .handle(USER_PATH, move |call: &Call| {
+            let language = call
+                .headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case("accept-language"))
+                .map_or("none", |(_, v)| v.as_str());
+            Response::ok(format!("lang={}", language)).with_header("Vary", vary)
+        })
+        .build();
+    RouterCache::builder().upstream(upstream).start()
+}
+
+async fn get(cache: &RouterCache, language: Option<&str>) -> (StatusCode, String) {
+    let mut request = Request::builder().uri("/api/v1/user?user[id]=1");
+    if let Some(language) = language {
+        request = request.header("Accept-Language", language);
+    }
+    into_text(cache.send(request.body(Body::empty()).unwrap()).await).await
+}
+
+/// Test that two Accept-Language values of one key get their own bodies, each stored once
+/// and served from the cache afterwards, and that the origin is sent the header.
+#[tokio::test]
+async fn test_accept_language_variants_get_distinct_bodies() {
+    let cache = start("Accept-Language");
+
+    assert_eq!(get(&cache, Some("en")).await, (StatusCode::OK, "lang=en".to_string()));
+    assert_eq!(get(&cache, Some("de")).await, (StatusCode::OK, "lang=de".to_string()));
+    let last = cache.upstream.last_call().unwrap();
+    assert!(last.headers.iter().any(|(k, v)| k.eq_ignore_ascii_case("accept-language") && v == "de"));
+    let fills = cache.upstream.fills();
+
+    for _ in 0..2 {
+        assert_eq!(get(&cache, Some("en")).await.1, "lang=en");
+        assert_eq!(get(&cache, Some("de")).await.1, "lang=de");
+    }
+    assert_eq!(cache.upstream.fills(), fills, "both variants are served from the cache");
+    assert_eq!(cache.db.stat().1, 2, "one entry per variant");
+
+    assert_eq!(get(&cache, None).await.1, "lang=none");
+    assert_eq!(get(&cache, None).await.1, "lang=none");
+    assert_eq!(cache.upstream.fills(), fills + 1, "a request without the header is a variant of its own");
+}
+
+/// Test that a `Vary: *` answer is served but not stored.
+#[tokio::test]
+async fn test_vary_star_is_not_stored() {
+    let cache = start("*");
+    for _ in 0..2 {
+        assert_eq!(get(&cache, Some("en")).await, (StatusCode::OK, "lang=none".to_string()));
+    }
+    assert_eq!(cache.upstream.fills(), 2);
+    assert_eq!(cache.db.stat().1, 0);
+}
+
+/// Test that `Vary: Accept-Encoding` alone is cached as any other answer, the cache
+/// negotiating encodings itself.
+#[tokio::test]
+async fn test_vary_accept_encoding_is_cached_as_usual() {
+    let cache = start("Accept-Encoding");
+    assert_eq!(get(&cache, Some("en")).await.1, "lang=none");
+    assert_eq!(get(&cache, Some("de")).await.1, "lang=none");
+    assert_eq!((cache.upstream.fills(), cache.db.stat().1), (1, 1));
+}
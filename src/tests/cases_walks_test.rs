@@ -0,0 +1,149 @@
+// Integration tests for the coordination of admin walks over the shards (`storage.admin_walks`).
+//
+// Tests hold a walk permit of their own to keep invalidations queued while they look at
+// `/advcache/walks`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use tower::ServiceExt;
+
+use crate::config;
+use crate::controller::{InvalidateController, WalksController};
+use crate::db::walks::WalkCoordinator;
+use crate::db::Storage;
+use crate::tests::support::router::into_json;
+use crate::tests::support::RouterCache;
+
+const PATH: &str = "/api/v1/user";
+
+fn start(change: impl FnOnce(&mut config::Config)) -> (RouterCache, Arc<WalkCoordinator>) {
+    let cache = RouterCache::builder()
+        .config(change)
+        .start()
+        .mount(|cache| InvalidateController::new(cache.cfg.clone(), cache.db.clone()))
+        .mount(|cache| WalksController::new(cache.db.clone()));
+    let walks = cache.db.walks().expect("the db coordinates walks");
+    (cache, walks)
+}
+
+/// Requests the user and returns whether it was served from the cache.
+async fn is_hit(cache: &RouterCache, id: u32) -> bool {
+    let fills = cache.upstream.fills();
+    assert_eq!(cache.get_json(&format!("{}?user[id]={}", PATH, id)).await.0, StatusCode::OK);
+    cache.upstream.fills() == fills
+}
+
+/// Spawns the removal of the user, to run once it gets a walk permit.
+fn spawn_invalidation(cache: &RouterCache, id: u32) -> tokio::task::JoinHandle<(StatusCode, serde_json::Value)> {
+    let router = cache.router.clone();
+    let uri = format!("/advcache/invalidate?_path={}&user[id]={}&_remove=1", PATH, id);
+    tokio::spawn(async move {
+        into_json(router.oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap()).await
+    })
+}
+
+/// Waits for `n` walks to be queued and returns the status.
+async fn wait_queued(cache: &RouterCache, n: usize) -> serde_json::Value {
+    loop {
+        let (_, status) = cache.get_json("/advcache/walks").await;
+        if status["queued"].as_array().unwrap().len() >= n {
+            return status;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+/// Test that two invalidations queue behind a running walk and each other, reporting their
+/// positions, and both remove their entry once they get their turn.
+#[tokio::test]
+async fn test_invalidations_are_serialized() {
+    let (cache, walks) = start(|_| {});
+    for id in [1, 2, 3] {
+        assert!(!is_hit(&cache, id).await);
+    }
+
+    let running = walks.acquire("test").await.unwrap();
+    let first = spawn_invalidation(&cache, 1);
+    wait_queued(&cache, 1).await;
+    let second = spawn_invalidation(&cache, 2);
+    let status = wait_queued(&cache, 2).await;
+
+    assert_eq!(status["permits"], 1);
+    assert_eq!(status["running"].as_array().unwrap().len(), 1);
+    assert_eq!(status["running"][0]["op"], "test");
+    assert!(status["running"][0].get("position").is_none());
+    let queued = status["queued"].as_array().unwrap();
+    assert_eq!((queued[0]["op"].as_str(), queued[0]["position"].as_u64()), (Some("invalidate"), Some(1)));
+    assert_eq!((queued[1]["op"].as_str(), queued[1]["position"].as_u64()), (Some("invalidate"), Some(2)));
+    assert!(queued[0]["id"].as_u64() < queued[1]["id"].as_u64(), "walks run in arrival order");
+    assert!(!first.is_finished() && !second.is_finished());
+
+    drop(running);
+    for (handle, id) in [(first, 1), (second, 2)] {
+        let (status, body) = handle.await.unwrap();
+        assert_eq!(status, StatusCode::OK, "invalidation of {}: {}", id, body);
+        assert_eq!(body["affected"], 1, "invalidation of {}: {}", id, body);
+    }
+
+    let (_, status) = cache.get_json("/advcache/walks").await;
+    assert!(status["running"].as_array().unwrap().is_empty());
+    assert!(status["queued"].as_array().unwrap().is_empty());
+    assert!(!is_hit(&cache, 1).await);
+    assert!(!is_hit(&cache, 2).await);
+    assert!(is_hit(&cache, 3).await, "entries not invalidated are kept");
+}
+
+/// Test that an invalidation gives up with 503 after `storage.admin_walk_timeout`, reporting
+/// the walks ahead of it, and leaves the queue.
+#[tokio::test]
+async fn test_queued_invalidation_times_out() {
+    let (cache, walks) = start(|cfg| {
+        cfg.cache.storage.as_mut().unwrap().admin_walk_timeout = Some(Duration::from_millis(100));
+    });
+    assert!(!is_hit(&cache, 4).await);
+
+    let running = walks.acquire("test").await.unwrap();
+    let (status, body) = spawn_invalidation(&cache, 4).await.unwrap();
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["success"], false);
+    assert!(body["error"].as_str().unwrap().contains("1 walk(s) ahead"), "{}", body);
+    assert!(walks.status().queued.is_empty());
+    drop(running);
+
+    assert!(is_hit(&cache, 4).await, "a timed out invalidation must not remove anything");
+}
+
+/// Test that `storage.admin_walks` allows that many walks at once.
+#[tokio::test]
+async fn test_admin_walks_sets_concurrency() {
+    let mut cfg = config::new_test_config();
+    cfg.cache.storage.as_mut().unwrap().admin_walks = Some(2);
+    let walks = Arc::new(WalkCoordinator::new(&cfg));
+
+    let a = walks.acquire("a").await.unwrap();
+    let b = walks.acquire("b").await.unwrap();
+    assert_eq!(walks.status().running.len(), 2);
+    let waiting = tokio::spawn({
+        let walks = walks.clone();
+        async move { walks.acquire_background("c").await }
+    });
+    while walks.status().queued.is_empty() {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    drop(a);
+    let c = waiting.await.unwrap();
+    let ops: Vec<_> = walks.status().running.iter().map(|j| j.op).collect();
+    assert_eq!(ops, ["b", "c"]);
+    drop((b, c));
+}
+
+/// Test that `storage.admin_walks: 0` is rejected.
+#[test]
+fn test_zero_admin_walks_is_rejected() {
+    let yaml = "cache:\n  env: test\n  enabled: true\n  storage:\n    size: 1024\n    admin_walks: 0\n";
+    let err = config::Config::from_yaml(yaml).unwrap_err();
+    assert!(format!("{:#}", err).contains("storage.admin_walks must be at least 1"), "{:#}", err);
+}
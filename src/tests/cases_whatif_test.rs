@@ -0,0 +1,73 @@
+// Integration tests for the what-if analysis (`analytics`, `GET /advcache/whatif`).
+//
+// The cache samples every key, so the estimates of a known access pattern are exact.
+
+use axum::http::StatusCode;
+
+use crate::config::{Analytics, Config};
+use crate::controller::WhatIfController;
+use crate::tests::support::RouterCache;
+
+const PATH: &str = "/api/v1/user";
+
+fn start(change: impl FnOnce(&mut Config)) -> RouterCache {
+    RouterCache::builder()
+        .config(change)
+        .start()
+        .mount(|cache| WhatIfController::new(cache.cfg.clone(), cache.db.clone()))
+}
+
+fn sample_all(cfg: &mut Config) {
+    cfg.cache.analytics = Some(Analytics { sample_rate: Some(1.0), max_events: None });
+}
+
+/// Test that the endpoint answers 404 while analytics is disabled.
+#[tokio::test]
+async fn test_whatif_needs_analytics() {
+    let cache = start(|_| {});
+    let (status, body) = cache.get_json("/advcache/whatif?size=1GB").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].as_str().unwrap().contains("analytics"));
+
+    let cache = start(sample_all);
+    assert_eq!(cache.get_json("/advcache/whatif").await.0, StatusCode::NOT_FOUND, "nothing sampled yet");
+}
+
+/// Test that the replay of served requests matches what the cache did, and that a storage
+/// too small for one entry is estimated to never hit.
+#[tokio::test]
+async fn test_whatif_replays_served_requests() {
+    let cache = start(sample_all);
+    for _ in 0..4 {
+        for id in 0..10 {
+            assert_eq!(cache.get_json(&format!("{}?user[id]={}", PATH, id)).await.0, StatusCode::OK);
+        }
+    }
+
+    let (status, body) = cache.get_json("/advcache/whatif?size=1KB&ttl=off").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["sample"]["rate"], 1.0);
+    assert_eq!(body["sample"]["accesses"], 40);
+    assert_eq!(body["sample"]["keys"], 10);
+
+    // 10 fills, then 30 hits.
+    assert_eq!(body["current"]["accesses"], 40);
+    assert_eq!(body["current"]["hits"], 30);
+    assert!(body["current"]["memory_bytes"].as_i64().unwrap() > 0);
+
+    assert_eq!(body["whatif"]["size_bytes"], 1024);
+    assert_eq!(body["whatif"]["ttl"], serde_json::Value::Null);
+    assert_eq!(body["whatif"]["hits"], 0);
+}
+
+/// Test that malformed params are refused.
+#[tokio::test]
+async fn test_whatif_rejects_bad_params() {
+    let cache = start(sample_all);
+    assert_eq!(cache.get_json(&format!("{}?user[id]=1", PATH)).await.0, StatusCode::OK);
+    for query in ["size=lots", "size=0", "ttl=soon", "ttl=0s"] {
+        let (status, body) = cache.get_json(&format!("/advcache/whatif?{}", query)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+        assert!(body["error"].is_string(), "{}", query);
+    }
+}
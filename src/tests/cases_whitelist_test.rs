@@ -2,13 +2,13 @@
 
 use std::collections::HashMap;
 use crate::support::{
-    assert_equal, assert_ok, cache_addr, do_json, init_test_harness, new_namespace, phash, with_ns,
+    assert_equal, assert_ok, cache_addr, do_json, Capability, init_test_harness, new_namespace, phash, with_ns,
     H,
 };
 
 #[tokio::test]
 async fn test_whitelist_query_ignores_extras() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Whitelist_Query_Ignores_Extras");
     let base = cache_addr().await;
@@ -42,7 +42,7 @@ async fn test_whitelist_query_ignores_extras() {
 
 #[tokio::test]
 async fn test_whitelist_headers_ignores_extras() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_Whitelist_Headers_Ignores_Extras");
     let base = cache_addr().await;
@@ -73,7 +73,7 @@ async fn test_whitelist_headers_ignores_extras() {
 
 #[tokio::test]
 async fn test_response_headers_filtered_on_cache_hit() {
-    init_test_harness().await.unwrap();
+    init_test_harness(Capability::Http).await.unwrap();
 
     let ns = new_namespace("Test_ResponseHeaders_Filtered_OnCacheHit");
     let base = cache_addr().await;
@@ -1,14 +1,21 @@
 
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use tower::ServiceExt;
+
 use crate::config::{self, ConfigTrait, LifetimeRule, Rule, RuleKey, RuleValue};
-use crate::governor::Governor;
+use crate::controller::EvictionController;
+use crate::governor::api::{ServiceState, ServiceStatus};
+use crate::governor::{Config as GovernedConfig, Governor, GovernorError, Service, Transport};
 use crate::governor::Orchestrator;
+use crate::http::Controller;
 use crate::model::{Entry, Response as ModelResponse};
-use crate::db::{Storage, DB};
-use crate::upstream::{Response, Upstream};
+use crate::db::{Storage, DB, SVC_EVICTOR, SVC_LIFETIME_MANAGER};
+use crate::upstream::testing::MockUpstream;
 
 fn make_rule(path: &str, ttl: Option<Duration>) -> Arc<Rule> {
     Arc::new(Rule {
@@ -17,12 +24,23 @@ fn make_rule(path: &str, ttl: Option<Duration>) -> Arc<Rule> {
         cache_key: RuleKey {
             query: None,
             query_bytes: None,
+            query_ignore: None,
+            duplicate_query: None,
+            transformer: None,
             headers: None,
             headers_map: None,
         },
         cache_value: RuleValue {
             headers: None,
             headers_map: None,
+            persist: None,
+            rollout_percent: Default::default(),
+            max_concurrent_fills: None,
+            max_fill_wait: None,
+            admission_hook: None,
+            downstream_ttl: Default::default(),
+            respect_cache_control: None,
+            negative_ttl: None,
         },
         refresh: ttl.map(|d| LifetimeRule {
             enabled: true,
@@ -30,6 +48,13 @@ fn make_rule(path: &str, ttl: Option<Duration>) -> Arc<Rule> {
             beta: Some(1.0),
             coefficient: Some(0.0),
         }),
+        stale_on_error: None,
+        shed_on_brownout: None,
+        methods: None,
+        key_body: None,
+        max_key_body: None,
+        max_body_size: None,
+        refresh_inherited: false,
     })
 }
 
@@ -63,50 +88,6 @@ fn tune_limits(cfg: &mut config::Config, storage_size: i64, soft: f64, hard: f64
     }
 }
 
-struct CountingUpstream {
-    refresh_calls: Arc<AtomicUsize>,
-}
-
-impl CountingUpstream {
-    fn new() -> Arc<Self> {
-        Arc::new(Self {
-            refresh_calls: Arc::new(AtomicUsize::new(0)),
-        })
-    }
-}
-
-#[async_trait::async_trait]
-impl Upstream for CountingUpstream {
-    async fn request(
-        &self,
-        _rule: &Rule,
-        _queries: &[(Vec<u8>, Vec<u8>)],
-        _headers: &[(Vec<u8>, Vec<u8>)],
-    ) -> anyhow::Result<Response> {
-        Ok(Response::new(200, vec![], b"ok".to_vec()))
-    }
-
-    async fn proxy_request(
-        &self,
-        _method: &str,
-        _path: &str,
-        _query: &str,
-        _headers: &[(String, String)],
-        _body: Option<&[u8]>,
-    ) -> anyhow::Result<Response> {
-        Ok(Response::new(200, vec![], b"ok".to_vec()))
-    }
-
-    async fn refresh(&self, _entry: &Entry) -> anyhow::Result<()> {
-        self.refresh_calls.fetch_add(1, Ordering::Relaxed);
-        Ok(())
-    }
-
-    async fn is_healthy(&self) -> anyhow::Result<()> {
-        Ok(())
-    }
-}
-
 #[tokio::test]
 async fn test_evictor_respects_soft_limit() {
     let shutdown = tokio_util::sync::CancellationToken::new();
@@ -117,9 +98,12 @@ async fn test_evictor_respects_soft_limit() {
     if let Some(lifetime) = cfg.cache.lifetime.as_mut() {
         lifetime.enabled = false;
     }
+    // Admission never stores past its budget: keep it over the soft limit, so sets get there.
+    let storage = cfg.cache.storage.as_mut().unwrap();
+    storage.admission_memory_limit = storage.hard_memory_limit;
 
     let governor = Arc::new(Orchestrator::new());
-    let upstream = CountingUpstream::new();
+    let upstream = MockUpstream::new();
     let db = DB::new(
         shutdown.clone(),
         cfg.clone(),
@@ -165,6 +149,43 @@ async fn test_evictor_respects_soft_limit() {
     governor.stop();
 }
 
+/// Test that admission, with its budget under the soft limit, keeps stored bytes within it and
+/// refuses the rest, so the soft evictor finds nothing to trim.
+#[tokio::test]
+async fn test_admission_budget_leaves_evictor_idle() {
+    let shutdown = tokio_util::sync::CancellationToken::new();
+    let mut cfg = config::new_test_config();
+    tune_limits(&mut cfg, 20_000, 0.5, 0.9);
+    if let Some(lifetime) = cfg.cache.lifetime.as_mut() {
+        lifetime.enabled = false;
+    }
+
+    let governor = Arc::new(Orchestrator::new());
+    let upstream = MockUpstream::new();
+    let db = DB::new(shutdown.clone(), cfg.clone(), governor.clone(), upstream.clone()).expect("storage must start");
+
+    let rule = make_rule("/api/v1/user", None);
+    let stored = (0..32).filter(|&i| db.set(make_entry(rule.clone(), i, 2_048))).count();
+
+    let (mem_before, len_before) = db.stat();
+    let storage = cfg.storage();
+    assert!(stored < 32, "admission must refuse new keys past its budget");
+    assert_eq!(len_before, stored as i64, "refused sets must not be stored");
+    assert!(
+        mem_before <= storage.admission_memory_limit,
+        "mem_before={} must stay within the admission budget={}",
+        mem_before,
+        storage.admission_memory_limit
+    );
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(db.stat(), (mem_before, len_before), "the evictor must have nothing to trim");
+
+    shutdown.cancel();
+    drop(db);
+    governor.stop();
+}
+
 #[tokio::test]
 async fn test_lifetimer_refreshes_expired_entries() {
     let shutdown = tokio_util::sync::CancellationToken::new();
@@ -183,7 +204,7 @@ async fn test_lifetimer_refreshes_expired_entries() {
     }
 
     let governor = Arc::new(Orchestrator::new());
-    let upstream = CountingUpstream::new();
+    let upstream = MockUpstream::new();
     let db = DB::new(
         shutdown.clone(),
         cfg.clone(),
@@ -224,7 +245,7 @@ async fn test_lifetimer_refreshes_expired_entries() {
     let start = std::time::Instant::now();
     
     loop {
-        let refreshed = upstream.refresh_calls.load(Ordering::Relaxed);
+        let refreshed = upstream.refreshes();
         if refreshed >= 8 {
             // Condition met, test passes
             break;
@@ -241,7 +262,7 @@ async fn test_lifetimer_refreshes_expired_entries() {
         tokio::time::sleep(poll_interval).await;
     }
     
-    let refreshed = upstream.refresh_calls.load(Ordering::Relaxed);
+    let refreshed = upstream.refreshes();
     assert!(
         refreshed >= 8,
         "expected at least 8 refresh calls, got {}",
@@ -252,3 +273,172 @@ async fn test_lifetimer_refreshes_expired_entries() {
     drop(db);
     governor.stop();
 }
+
+#[tokio::test]
+async fn test_lifetimer_prewarms_entries_due_soon() {
+    let shutdown = tokio_util::sync::CancellationToken::new();
+    let mut cfg = config::new_test_config();
+
+    tune_limits(&mut cfg, 50_000, 0.9, 0.95);
+    if let Some(eviction) = cfg.cache.eviction.as_mut() {
+        eviction.enabled = false;
+    }
+    if let Some(lifetime) = cfg.cache.lifetime.as_mut() {
+        lifetime.enabled = true;
+        lifetime.rate = Some(100);
+        lifetime.replicas = Some(2);
+        lifetime.on_ttl = Some(config::TTLMode::Refresh);
+        // A window that opened a minute ago.
+        let minute = {
+            use chrono::Timelike;
+            let now = chrono::Local::now();
+            now.hour() * 60 + now.minute()
+        };
+        let (start, end) = ((minute + 24 * 60 - 1) % (24 * 60), (minute + 30) % (24 * 60));
+        lifetime.prewarm = Some(config::Prewarm {
+            window: format!("{:02}:{:02}-{:02}:{:02}", start / 60, start % 60, end / 60, end % 60),
+            rate: 100,
+            ahead: Some(Duration::from_secs(3600)),
+        });
+    }
+
+    let governor = Arc::new(Orchestrator::new());
+    let upstream = MockUpstream::new();
+    let db = DB::new(
+        shutdown.clone(),
+        cfg.clone(),
+        governor.clone(),
+        upstream.clone(),
+    )
+    .expect("storage must start");
+
+    // Entries refreshed before the window opened and not due for half an hour, so only prewarm
+    // refreshes them, and only those due within `ahead`.
+    let soon = make_rule("/api/v1/user", Some(Duration::from_secs(1800)));
+    let distant = make_rule("/api/v1/user", Some(Duration::from_secs(24 * 3600)));
+    let refreshed_at = crate::time::unix_nano() - 120 * 1_000_000_000;
+    for i in 0..4 {
+        for entry in [make_entry(soon.clone(), i, 256), make_entry(distant.clone(), 100 + i, 256)] {
+            db.set(entry.clone());
+            entry.set_refreshed_at_for_tests(refreshed_at);
+        }
+    }
+
+    let start = std::time::Instant::now();
+    while upstream.refreshes() < 4 {
+        if start.elapsed() >= Duration::from_secs(30) {
+            panic!("timeout waiting for prewarm refreshes, got {}", upstream.refreshes());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(upstream.refreshes(), 4, "entries due beyond `ahead` and prewarmed ones are left alone");
+
+    shutdown.cancel();
+    drop(db);
+    governor.stop();
+}
+
+/// Orchestrator serving one of the services it is given with a serve that panics.
+struct BreakingGovernor {
+    inner: Orchestrator,
+    broken: &'static str,
+}
+
+struct PanickingService(Arc<dyn Service>);
+
+impl Service for PanickingService {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn cfg(&self) -> Arc<dyn GovernedConfig> {
+        self.0.cfg()
+    }
+
+    fn replicas(&self) -> usize {
+        0
+    }
+
+    fn serve(&self, _t: Arc<dyn Transport>) {
+        panic!("{} cannot serve", self.0.name());
+    }
+
+    fn transport(&self) -> Arc<dyn Transport> {
+        unreachable!("never served")
+    }
+}
+
+impl Governor for BreakingGovernor {
+    fn register(&self, name: String, s: Arc<dyn Service>) -> Result<(), GovernorError> {
+        let s = if name == self.broken { Arc::new(PanickingService(s)) as Arc<dyn Service> } else { s };
+        self.inner.register(name, s)
+    }
+
+    fn cfg(&self, name: &str) -> Result<Arc<dyn GovernedConfig>, GovernorError> {
+        self.inner.cfg(name)
+    }
+
+    fn status(&self, name: &str) -> Result<ServiceStatus, GovernorError> {
+        self.inner.status(name)
+    }
+
+    fn on(&self, name: &str) -> Result<(), GovernorError> {
+        self.inner.on(name)
+    }
+
+    fn off(&self, name: &str) -> Result<(), GovernorError> {
+        self.inner.off(name)
+    }
+
+    fn start(&self, name: &str) -> Result<(), GovernorError> {
+        self.inner.start(name)
+    }
+
+    fn reload(&self, name: &str, cfg: Arc<dyn GovernedConfig>) -> Result<(), GovernorError> {
+        self.inner.reload(name, cfg)
+    }
+
+    fn scale_to(&self, name: &str, n: usize) -> Result<(), GovernorError> {
+        self.inner.scale_to(name, n)
+    }
+
+    fn stop(&self) {
+        self.inner.stop()
+    }
+}
+
+/// Test that a worker failing to start fails startup with the worker named, and that a
+/// worker marked not critical leaves the storage up with the failure reported.
+#[tokio::test]
+async fn test_worker_start_failure_is_surfaced() {
+    let shutdown = tokio_util::sync::CancellationToken::new();
+    let cfg = config::new_test_config();
+    let broken = || Arc::new(BreakingGovernor { inner: Orchestrator::new(), broken: SVC_EVICTOR });
+
+    let err = DB::new(shutdown.clone(), cfg.clone(), broken(), MockUpstream::new())
+        .err()
+        .expect("a critical worker failing to start fails startup");
+    assert!(err.to_string().contains(SVC_EVICTOR), "{}", err);
+
+    let mut cfg = cfg;
+    cfg.cache.eviction.as_mut().unwrap().critical = Some(false);
+    let governor = broken();
+    let db = DB::new(shutdown.clone(), cfg, governor.clone(), MockUpstream::new()).expect("storage must start");
+    assert_eq!(db.failed_workers(), vec![SVC_EVICTOR.to_string()]);
+    assert_eq!(governor.status(SVC_EVICTOR).unwrap().state, ServiceState::Failed);
+    assert_eq!(governor.status(SVC_LIFETIME_MANAGER).unwrap().state, ServiceState::Running);
+
+    // The admin endpoint shows the state of the worker.
+    let router = EvictionController::new(governor.clone()).add_route(Router::new());
+    let resp = router.oneshot(Request::get("/advcache/eviction").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value =
+        serde_json::from_slice(&axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap()).unwrap();
+    assert_eq!(body["state"], "failed");
+    assert_eq!(body["name"], SVC_EVICTOR);
+
+    shutdown.cancel();
+    drop(db);
+    governor.stop();
+}
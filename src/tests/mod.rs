@@ -3,18 +3,72 @@
 //! This module contains end-to-end tests that verify cache behavior,
 //! key isolation, whitelists, and other integration scenarios.
 
+mod cases_accept_encoding_test;
 mod cases_admin_endpoints_test;
+mod cases_admin_index_test;
+mod cases_alloc_test;
+#[cfg(feature = "mocks")]
+mod cases_audit_test;
 mod cases_brackets_canonicalization_test;
 mod cases_cache_test;
+mod cases_brownout_test;
 mod cases_cache_behavior_test;
+mod cases_cache_control_test;
+#[cfg(feature = "debug-endpoints")]
+mod cases_capture_test;
+mod cases_checksum_test;
 mod cases_concurrent_test;
+mod cases_conditional_invalidation_test;
+mod cases_connection_limit_test;
+mod cases_content_length_test;
+mod cases_diagnostic_headers_test;
+mod cases_drain_test;
+mod cases_dry_run_test;
+mod cases_duplicate_query_test;
 mod cases_error_handling_test;
+mod cases_eviction_audit_test;
+mod cases_explain_test;
+mod cases_external_test;
+mod cases_fill_cap_test;
+mod cases_health_synthetic_test;
+mod cases_header_order_test;
 mod cases_integration_test;
 mod cases_invalidation_test;
+mod cases_jobs_test;
+mod cases_negotiation_test;
+mod cases_negative_cache_test;
+mod cases_not_modified_test;
+mod cases_key_transformer_test;
 mod cases_key_isolation_test;
+mod cases_loop_test;
+mod cases_max_body_size_test;
+mod cases_method_test;
+mod cases_metrics_auth_test;
+mod cases_misconfig_test;
 mod cases_order_and_negative_test;
+mod cases_panic_recover_test;
+mod cases_peers_test;
 mod cases_percent_encoding_test;
+mod cases_post_cache_test;
 mod cases_proxy_test;
+mod cases_purge_test;
+mod cases_probe_test;
+mod cases_pure_cache_test;
+mod cases_query_ignore_test;
+mod cases_response_size_test;
+mod cases_revalidation_test;
+mod cases_rollout_test;
+mod cases_rule_metrics_test;
+mod cases_shutdown_test;
+mod cases_stale_on_error_test;
+mod cases_storage_resize_test;
+mod cases_surrogate_test;
+mod cases_throttle_test;
+mod cases_tombstone_test;
+mod cases_unmatched_test;
+mod cases_vary_test;
+mod cases_walks_test;
+mod cases_whatif_test;
 mod cases_whitelist_test;
 mod cases_workers_test;
 
@@ -6,11 +6,20 @@ use std::time::Duration;
 pub type H = HashMap<String, String>;
 
 /// Creates a new namespace for test isolation.
+/// Against an external instance, which keeps its entries and its upstream's per-URL state
+/// between runs, the namespace also differs from one run to the next.
 pub fn new_namespace(test_name: &str) -> String {
     use hex;
     use sha1::{Digest, Sha1};
+    static RUN: std::sync::OnceLock<u128> = std::sync::OnceLock::new();
     let mut hasher = Sha1::new();
     hasher.update(test_name.as_bytes());
+    if super::harness::is_external() {
+        let run = RUN.get_or_init(|| {
+            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos()
+        });
+        hasher.update(run.to_le_bytes());
+    }
     let hash = hasher.finalize();
     let hash_str = hex::encode(&hash[..4]);
     format!("{}_{}", test_name.replace("/", "_"), hash_str)
@@ -99,6 +108,45 @@ pub async fn do_json<T: serde::de::DeserializeOwned>(
     Ok((status, header_map, body, parsed))
 }
 
+/// Headers the cache sets on a response itself, left out of MISS vs HIT comparisons.
+pub const CACHE_ADDED_HEADERS: [&str; 8] = [
+    "last-updated-at",
+    "x-cache-status",
+    "x-cache",
+    "x-cache-key",
+    "age",
+    "content-length",
+    "date",
+    "x-request-id",
+];
+
+/// Response headers in the order received, one pair per value.
+pub fn header_list(resp: &reqwest::Response) -> Vec<(String, Vec<u8>)> {
+    resp.headers()
+        .iter()
+        .map(|(k, v)| (k.as_str().to_string(), v.as_bytes().to_vec()))
+        .collect()
+}
+
+/// Asserts that a HIT carries the headers of the MISS it was stored from, byte for byte and in
+/// the same order, the ones of `CACHE_ADDED_HEADERS` aside.
+pub fn assert_same_headers(miss: &[(String, Vec<u8>)], hit: &[(String, Vec<u8>)]) {
+    let stored = |headers: &[(String, Vec<u8>)]| -> Vec<(String, Vec<u8>)> {
+        headers
+            .iter()
+            .filter(|(k, _)| !CACHE_ADDED_HEADERS.contains(&k.as_str()))
+            .cloned()
+            .collect()
+    };
+    let (miss, hit) = (stored(miss), stored(hit));
+    if miss != hit {
+        let show = |headers: &[(String, Vec<u8>)]| -> Vec<String> {
+            headers.iter().map(|(k, v)| format!("{}: {}", k, String::from_utf8_lossy(v))).collect()
+        };
+        panic!("HIT headers differ from MISS\nmiss={:#?}\nhit={:#?}", show(&miss), show(&hit));
+    }
+}
+
 /// Assertions
 
 /// Asserts that an error is None.
@@ -1,33 +1,125 @@
 // Integration test harness setup.
+//
+// By default the harness starts the test upstream and a cache in-process, once per test binary.
+// Built with the `external-e2e` feature it starts nothing and points the same cases at an
+// instance running elsewhere (`ADVCACHE_TEST_BASE_URL`) in front of the test upstream
+// (`ADVCACHE_TEST_UPSTREAM_URL`), see `cfg/advcache.e2e.cfg.yaml`. Cases that need more than
+// HTTP access to it are skipped there.
 
 use super::cache::CacheServer;
 use super::upstream::UpstreamServer;
 use crate::config;
 use std::sync::OnceLock;
+use std::time::Duration;
 use tokio::sync::oneshot;
 use tokio::sync::OnceCell;
 
+/// Base URL of the external cache under test.
+pub const BASE_URL_ENV: &str = "ADVCACHE_TEST_BASE_URL";
+/// Base URL of the test upstream behind the external cache.
+pub const UPSTREAM_URL_ENV: &str = "ADVCACHE_TEST_UPSTREAM_URL";
+
+/// Defaults of the external endpoints: the ports of `cfg/advcache.e2e.cfg.yaml`.
+const DEFAULT_BASE_URL: &str = "http://127.0.0.1:8091";
+const DEFAULT_UPSTREAM_URL: &str = "http://127.0.0.1:8090";
+
+/// How long the external endpoints are waited for before the harness gives up.
+const EXTERNAL_READY_TIMEOUT: Duration = Duration::from_secs(10);
+
 // Global addresses for reuse across tests.
 static UP_ADDR: OnceCell<String> = OnceCell::const_new();
 static CACHE_ADDR: OnceCell<String> = OnceCell::const_new();
 static STARTED: OnceLock<()> = OnceLock::new();
 
-/// Initializes the test harness (upstream and cache servers).
-/// This is called once before all tests.
-pub async fn init_test_harness() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// What a test case needs from the instance under test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// HTTP requests to the cache and its admin endpoints only: runs against an external instance.
+    Http,
+    /// In-process hooks (the process-wide recorder, the test config, the local servers): skipped
+    /// against an external instance.
+    InProcess,
+}
+
+/// Whether the harness targets an external instance instead of starting its own.
+pub fn is_external() -> bool {
+    cfg!(feature = "external-e2e")
+}
+
+/// The external (cache, upstream) base URLs, from the environment or their defaults.
+pub fn external_urls() -> (String, String) {
+    let url = |env: &str, default: &str| {
+        std::env::var(env)
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| v.trim().trim_end_matches('/').to_string())
+            .unwrap_or_else(|| default.to_string())
+    };
+    (url(BASE_URL_ENV, DEFAULT_BASE_URL), url(UPSTREAM_URL_ENV, DEFAULT_UPSTREAM_URL))
+}
+
+/// Initializes the test harness (upstream and cache servers), once per test binary.
+/// Returns false when the case needs `capability` and the instance under test cannot give it:
+/// the caller returns right away, the case counting as passed.
+pub async fn init_test_harness(capability: Capability) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    if is_external() && capability == Capability::InProcess {
+        eprintln!("[e2e] skipped: needs an in-process instance");
+        return Ok(false);
+    }
     if STARTED.get().is_some() {
-        return Ok(());
+        return Ok(true);
+    }
+
+    let (up_addr, cache_addr) = if is_external() { connect_external().await? } else { start_local().await? };
+    let _ = UP_ADDR.set(up_addr);
+    let _ = CACHE_ADDR.set(cache_addr);
+    let _ = STARTED.set(());
+    Ok(true)
+}
+
+/// Waits for the external cache and upstream to answer their health checks.
+async fn connect_external() -> Result<(String, String), Box<dyn std::error::Error + Send + Sync>> {
+    let (cache_addr, up_addr) = external_urls();
+    for url in [format!("{}/healthz", up_addr), format!("{}/healthz", cache_addr)] {
+        let deadline = tokio::time::Instant::now() + EXTERNAL_READY_TIMEOUT;
+        loop {
+            match reqwest::get(&url).await {
+                Ok(resp) if resp.status().is_success() => break,
+                result if tokio::time::Instant::now() >= deadline => {
+                    let reason = match result {
+                        Ok(resp) => format!("status {}", resp.status()),
+                        Err(e) => e.to_string(),
+                    };
+                    return Err(format!(
+                        "{} is not ready ({}); set {} and {}",
+                        url, reason, BASE_URL_ENV, UPSTREAM_URL_ENV
+                    )
+                    .into());
+                }
+                _ => tokio::time::sleep(Duration::from_millis(100)).await,
+            }
+        }
     }
+    println!("[e2e] external upstream at {}, cache at {}", up_addr, cache_addr);
+    Ok((up_addr, cache_addr))
+}
 
+/// Starts the upstream and cache servers on a runtime of their own, kept for the process.
+async fn start_local() -> Result<(String, String), Box<dyn std::error::Error + Send + Sync>> {
     let (tx, rx) = oneshot::channel();
 
     std::thread::spawn(move || {
         let rt = tokio::runtime::Runtime::new().expect("init test runtime");
         rt.block_on(async move {
+            // Same cached clock as main: entries are stamped with it (Last-Updated-At).
+            let _clock = crate::time::start(Duration::from_millis(1));
             let upstream = UpstreamServer::start().await;
             let up_addr = format!("http://{}", upstream.addr());
             println!("[e2e] upstream at {}/healthz", up_addr);
 
+            // Same recorder setup as main, metrics are rendered by the cache's own /metrics route.
+            crate::metrics_runtime::init_metrics().expect("install prometheus recorder");
+
             let mut cfg = config::new_test_config();
             if let Some(ref mut upstream_cfg) = cfg.cache.upstream {
                 if let Some(ref mut backend) = upstream_cfg.backend {
@@ -57,11 +149,7 @@ pub async fn init_test_harness() -> Result<(), Box<dyn std::error::Error + Send
         });
     });
 
-    let (up_addr, cache_addr) = rx.await.map_err(|e| e.to_string())?;
-    let _ = UP_ADDR.set(up_addr);
-    let _ = CACHE_ADDR.set(cache_addr);
-    let _ = STARTED.set(());
-    Ok(())
+    Ok(rx.await.map_err(|e| e.to_string())?)
 }
 
 /// Cleans up the test harness.
@@ -0,0 +1,190 @@
+// In-process cache for integration tests.
+//
+// The cache proxy controller runs over a DB and an upstream on a router that requests are sent
+// to with `oneshot`, without a listener. Admin controllers a case needs are mounted next to it.
+// The upstream is a mock by default, so every call reaching the origin is accounted for, or a
+// real backend built from the config, for cases that need its HTTP client against a local
+// origin (`serve_origin`).
+// Workers of the cache are stopped when it is dropped.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::response::Response;
+use axum::Router;
+use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
+
+use crate::config::{self, Config, ConfigTrait};
+use crate::controller::brownout::Brownout;
+use crate::controller::CacheProxyController;
+use crate::db::{Storage, DB};
+use crate::governor::Orchestrator;
+use crate::http::Controller;
+use crate::model::Entry;
+use crate::upstream::testing::MockUpstream;
+use crate::upstream::{BackendImpl, Upstream};
+
+/// Cache served in-process; its workers are cancelled on drop.
+pub struct RouterCache<U = MockUpstream> {
+    pub router: Router,
+    pub cfg: Config,
+    pub db: Arc<DB>,
+    pub upstream: Arc<U>,
+    /// Brownout state of the cache proxy controller.
+    pub brownout: Arc<Brownout>,
+    pub shutdown: CancellationToken,
+}
+
+/// Builds a [`RouterCache`] over the test config.
+pub struct RouterCacheBuilder {
+    cfg: Config,
+    upstream: Option<Arc<MockUpstream>>,
+}
+
+impl RouterCache {
+    pub fn builder() -> RouterCacheBuilder {
+        RouterCacheBuilder {
+            cfg: config::new_test_config(),
+            upstream: None,
+        }
+    }
+
+    /// Cache over the test config and a mock upstream answering 200 to every call.
+    pub fn start() -> Self {
+        Self::builder().start()
+    }
+}
+
+impl RouterCacheBuilder {
+    /// Changes the test config before the cache starts.
+    pub fn config(mut self, change: impl FnOnce(&mut Config)) -> Self {
+        change(&mut self.cfg);
+        self
+    }
+
+    /// Points the backend at the origin on `addr`, see [`serve_origin`].
+    pub fn origin(self, addr: &str) -> Self {
+        self.config(|cfg| {
+            cfg.cache.upstream.as_mut().unwrap().backend.as_mut().unwrap().host = Some(addr.to_string());
+        })
+    }
+
+    /// Mock upstream the cache calls, instead of one answering 200 to every call.
+    pub fn upstream(mut self, upstream: Arc<MockUpstream>) -> Self {
+        self.upstream = Some(upstream);
+        self
+    }
+
+    /// Starts the cache over the mock upstream.
+    pub fn start(self) -> RouterCache {
+        let upstream = self.upstream.clone().unwrap_or_else(MockUpstream::new);
+        self.start_over(|_, _| upstream)
+    }
+
+    /// Starts the cache over a real backend built from `upstream.backend` of the config.
+    pub fn start_backend(self) -> RouterCache<BackendImpl> {
+        self.start_over(|cfg, shutdown| {
+            BackendImpl::new(shutdown.clone(), cfg.cache.upstream.as_ref().unwrap().backend.clone())
+                .expect("backend must start")
+        })
+    }
+
+    fn start_over<U: Upstream + 'static>(
+        self,
+        upstream: impl FnOnce(&Config, &CancellationToken) -> Arc<U>,
+    ) -> RouterCache<U> {
+        let shutdown = CancellationToken::new();
+        let upstream = upstream(&self.cfg, &shutdown);
+        let db = DB::new(shutdown.clone(), self.cfg.clone(), Arc::new(Orchestrator::new()), upstream.clone())
+            .expect("storage must start");
+        let proxy =
+            CacheProxyController::new(shutdown.clone(), self.cfg.clone(), db.clone(), upstream.clone());
+        RouterCache {
+            router: proxy.add_route(Router::new()),
+            brownout: proxy.brownout(),
+            cfg: self.cfg,
+            db,
+            upstream,
+            shutdown,
+        }
+    }
+}
+
+impl<U> RouterCache<U> {
+    /// Mounts the controller built from the cache next to the cache proxy controller.
+    pub fn mount<C: Controller>(mut self, controller: impl FnOnce(&Self) -> C) -> Self {
+        let controller = controller(&self);
+        self.router = controller.add_route(std::mem::replace(&mut self.router, Router::new()));
+        self
+    }
+
+    /// Sends a request through the router.
+    pub async fn send(&self, request: Request<Body>) -> Response {
+        self.router.clone().oneshot(request).await.unwrap()
+    }
+
+    /// Sends a GET without headers.
+    pub async fn get(&self, uri: &str) -> Response {
+        self.send(Request::get(uri).body(Body::empty()).unwrap()).await
+    }
+
+    /// Sends a GET without headers; returns the status and the body as text.
+    pub async fn get_text(&self, uri: &str) -> (StatusCode, String) {
+        into_text(self.get(uri).await).await
+    }
+
+    /// Sends a request; returns the status and the body as JSON, `Null` when it is not.
+    pub async fn send_json(&self, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        into_json(self.send(request).await).await
+    }
+
+    /// Sends a request without headers or body; returns the status and the body as JSON.
+    pub async fn call_json(&self, method: Method, uri: &str) -> (StatusCode, serde_json::Value) {
+        self.send_json(Request::builder().method(method).uri(uri).body(Body::empty()).unwrap()).await
+    }
+
+    /// Sends a GET without headers; returns the status and the body as JSON.
+    pub async fn get_json(&self, uri: &str) -> (StatusCode, serde_json::Value) {
+        self.call_json(Method::GET, uri).await
+    }
+
+    /// Entry stored for the query of the rule at `path`, if any.
+    pub fn stored(&self, path: &str, queries: &[(&str, &str)]) -> Option<Entry> {
+        let queries: Vec<_> =
+            queries.iter().map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec())).collect();
+        let lookup = Entry::new(self.cfg.rule(path).expect("path must have a rule"), &queries, &[]);
+        self.db.get(&lookup).0
+    }
+}
+
+/// Serves `app` on a local port as the origin of a real backend; returns its address.
+pub async fn serve_origin(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    addr
+}
+
+/// Returns the status and the body of the response as text.
+pub async fn into_text(resp: Response) -> (StatusCode, String) {
+    let status = resp.status();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Returns the status and the body of the response as JSON, `Null` when it is not.
+pub async fn into_json(resp: Response) -> (StatusCode, serde_json::Value) {
+    let status = resp.status();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+}
+
+impl<U> Drop for RouterCache<U> {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+    }
+}
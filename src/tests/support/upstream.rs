@@ -11,6 +11,7 @@ use flate2::write::GzEncoder;
 use flate2::Compression;
 use serde_json::json;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::net::TcpListener;
@@ -139,8 +140,13 @@ pub struct UpstreamServer {
 }
 
 impl UpstreamServer {
-    /// Starts the upstream server.
+    /// Starts the upstream server on a free port.
     pub async fn start() -> Self {
+        Self::start_on("127.0.0.1:0").await
+    }
+
+    /// Starts the upstream server on `addr`.
+    pub async fn start_on(addr: &str) -> Self {
         let counter = UpstreamCounters::new();
         let counter_for_handler = counter.clone();
 
@@ -206,6 +212,13 @@ impl UpstreamServer {
                 headers.insert("x-up-key", ctr_key.parse().unwrap());
                 headers.insert("x-up-ae", ae.parse().unwrap());
                 headers.insert("x-up-ae-raw", ae_raw.parse().unwrap());
+                // `picked=multi_headers` answers repeated stored headers around another one.
+                if query.split('&').any(|pair| pair == "picked=multi_headers") {
+                    headers.append("vary", "Accept-Encoding".parse().unwrap());
+                    headers.append("x-content-digest", format!("sha1={}", ctr_key).parse().unwrap());
+                    headers.append("vary", "Origin".parse().unwrap());
+                    headers.append("x-content-digest", "sha1=second".parse().unwrap());
+                }
 
                 if ae == "gzip" {
                     headers.insert("content-encoding", "gzip".parse().unwrap());
@@ -220,16 +233,65 @@ impl UpstreamServer {
             }
         };
 
+        // Flaky handler: per path+query, odd calls answer 503 slowly (600ms), even calls answer 200 quickly.
+        let flaky_calls: Arc<Mutex<HashMap<String, u64>>> = Arc::new(Mutex::new(HashMap::new()));
+        let flaky_handler = move |req: Request| {
+            let flaky_calls = flaky_calls.clone();
+            async move {
+                let key = req.uri().to_string();
+                let n = {
+                    let mut calls = flaky_calls.lock().unwrap();
+                    let n = calls.entry(key).or_insert(0);
+                    *n += 1;
+                    *n
+                };
+                if n % 2 == 1 {
+                    tokio::time::sleep(Duration::from_millis(600)).await;
+                    (StatusCode::SERVICE_UNAVAILABLE, "unavailable").into_response()
+                } else {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    let body = serde_json::to_vec(&json!({ "call": n })).unwrap();
+                    let mut headers = HeaderMap::new();
+                    headers.insert("content-type", "application/json".parse().unwrap());
+                    (StatusCode::OK, headers, body).into_response()
+                }
+            }
+        };
+
+        // Search handler: echoes the method and body with the number of the call, so a cached
+        // answer shows by its call number.
+        let search_calls = Arc::new(AtomicU64::new(0));
+        let search_handler = move |req: Request| {
+            let search_calls = search_calls.clone();
+            async move {
+                let method = req.method().to_string();
+                let body = axum::body::to_bytes(req.into_body(), usize::MAX).await.unwrap_or_default();
+                let call = search_calls.fetch_add(1, Ordering::SeqCst) + 1;
+                let body = serde_json::to_vec(&json!({
+                    "call": call,
+                    "method": method,
+                    "body": String::from_utf8_lossy(&body),
+                }))
+                .unwrap();
+                let mut headers = HeaderMap::new();
+                headers.insert("content-type", "application/json".parse().unwrap());
+                (StatusCode::OK, headers, body).into_response()
+            }
+        };
+
         let router = Router::new()
             .route("/healthz", get(|| async { "ok" }))
+            .route("/api/v1/search", axum::routing::any(search_handler))
+            .route("/api/v1/flaky", axum::routing::any(flaky_handler.clone()))
+            .route("/api/v1/flaky_strict", axum::routing::any(flaky_handler))
             .route("/api/v1/user", axum::routing::any(handler.clone()))
             .route("/api/v1/client", axum::routing::any(handler.clone()))
             .route("/api/v1/buyer", axum::routing::any(handler.clone()))
             .route("/api/v1/customer", axum::routing::any(handler));
 
-        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener = TcpListener::bind(addr).await.unwrap_or_else(|e| panic!("bind {}: {}", addr, e));
         let addr = listener.local_addr().unwrap();
-        let addr_str = format!("127.0.0.1:{}", addr.port());
+        let addr_str = format!("{}:{}", addr.ip(), addr.port());
 
         let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
 
@@ -1,8 +1,12 @@
 pub mod tracer;
 
 // Re-export commonly used functions and constants
+#[cfg(feature = "tracing-otel")]
+pub use tracer::extract;
+#[cfg(any(test, feature = "tracing-otel"))]
+pub use tracer::{disable_tracing, enable_tracing};
 pub use tracer::{
-    disable_tracing, enable_tracing, extract, is_active_tracing, ATTR_CACHE_HIT, ATTR_CACHE_IS_ERR,
+    is_active_tracing, ATTR_CACHE_HIT, ATTR_CACHE_IS_ERR,
     ATTR_CACHE_KEY, ATTR_CACHE_PROXY,
     ATTR_HTTP_RESPONSE_SIZE_KEY, ATTR_HTTP_STATUS_CODE_KEY,
 };
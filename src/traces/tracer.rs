@@ -1,6 +1,6 @@
 use anyhow::Result;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::Mutex;
 use tokio_util::sync::CancellationToken;
 
 use crate::config::Traces;
@@ -23,7 +23,8 @@ pub const ATTR_CACHE_IS_ERR: &str = "cache.is_err";
 // Global state
 static SERVICE_NAME: Mutex<Option<String>> = Mutex::new(None);
 static ENABLED: AtomicBool = AtomicBool::new(false);
-static CUR_TP: Mutex<Option<Arc<opentelemetry_sdk::trace::TracerProvider>>> = Mutex::new(None);
+#[cfg(feature = "tracing-otel")]
+static CUR_TP: Mutex<Option<std::sync::Arc<opentelemetry_sdk::trace::TracerProvider>>> = Mutex::new(None);
 static MU: Mutex<()> = Mutex::new(()); // Serialize Apply to avoid double-shutdown races
 
 // Error types
@@ -41,11 +42,13 @@ pub fn is_active_tracing() -> bool {
 }
 
 /// Enables tracing.
+#[cfg(any(test, feature = "tracing-otel"))]
 pub fn enable_tracing() {
     ENABLED.store(true, Ordering::Relaxed);
 }
 
 /// Disables tracing.
+#[cfg(any(test, feature = "tracing-otel"))]
 pub fn disable_tracing() {
     ENABLED.store(false, Ordering::Relaxed);
 }
@@ -61,7 +64,7 @@ pub fn apply(
         Some(c) => c,
         None => {
             // Switch to a minimal provider with NeverSample sampler (fast noop)
-            let _old = CUR_TP.lock().unwrap().take();
+            drop_provider();
             ENABLED.store(false, Ordering::Relaxed);
             return Box::new(move |_| Ok(()));
         }
@@ -69,7 +72,7 @@ pub fn apply(
 
     if !cfg.enabled {
         // Switch to a minimal provider with NeverSample sampler (fast noop)
-        let _old = CUR_TP.lock().unwrap().take();
+        drop_provider();
         ENABLED.store(false, Ordering::Relaxed);
         return Box::new(move |_| Ok(()));
     }
@@ -98,17 +101,21 @@ pub fn apply(
     ENABLED.store(true, Ordering::Relaxed);
     Box::new(move |_| {
         let _guard = MU.lock().unwrap();
-        if let Some(provider) = CUR_TP.lock().unwrap().take() {
-            // Shutdown the provider
-            drop(provider); // Provider will shutdown on drop
-        }
+        drop_provider();
         ENABLED.store(false, Ordering::Relaxed);
         Ok(())
     })
 }
 
+/// Shuts the current provider down, if any; it shuts down on drop.
+fn drop_provider() {
+    #[cfg(feature = "tracing-otel")]
+    drop(CUR_TP.lock().unwrap().take());
+}
+
 /// Extracts trace context from incoming request headers.
 /// Returns current context if tracing is disabled (fast no-op path).
+#[cfg(feature = "tracing-otel")]
 pub fn extract(headers: &axum::http::HeaderMap) -> opentelemetry::Context {
     // Fast path: return current context if tracing is disabled (no-op)
     if !is_active_tracing() {
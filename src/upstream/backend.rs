@@ -1,16 +1,29 @@
 use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use governor::clock::{Clock, DefaultClock};
 use governor::{Quota, RateLimiter};
 use std::num::NonZeroU32;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
+use parking_lot::Mutex;
 use tokio::sync::Semaphore;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, warn};
 
-use super::{actual_policy, change_policy, Policy, Response, Upstream};
-use crate::config::{Backend, Rule};
+use super::{actual_policy, change_policy, Policy, Refreshed, Response, Upstream};
+use crate::config::{Backend, HealthSynthetic, Rule};
+use crate::controller::metrics;
+use crate::dedlog;
+use crate::metrics::meter;
 use crate::model::Entry;
+use crate::upstream::backend_hyper_impl::{is_response_too_large, timeout_phase, Timeouts};
+use crate::upstream::digest;
+use crate::upstream::encoding;
+use crate::upstream::health::{self, Check, HealthChecks, Tracker};
+use crate::upstream::health_hook::{HealthEvent, HealthNotifier};
+use crate::upstream::loop_guard;
+use crate::upstream::misconfig::{self, Misconfig, MisconfigKind};
+use crate::upstream::signing::{self, Signer};
 use crate::upstream::trace as upstream_trace;
 use crate::upstream::proxy;
 
@@ -21,17 +34,39 @@ const BURST_PERCENT: u32 = 10;
 pub enum UpstreamError {
     #[error("backend is down")]
     BackendIsDown,
+    #[error("backend {id} is drained for maintenance")]
+    BackendIsDrained { id: String },
+    /// Refused by the `deny` policy limiter; a token is due in `retry_after`.
     #[error("backend is too busy")]
-    BackendIsTooBusy,
+    BackendIsTooBusy { retry_after: Duration },
     #[error("bad status code")]
     NotHealthyStatusCode,
+    #[error("upstream response body exceeds {limit} bytes")]
+    ResponseTooLarge { limit: usize },
+    #[error("upstream connect timed out after {after:?}")]
+    ConnectTimeout { after: Duration },
+    #[error("upstream response head not received within {after:?}")]
+    TtfbTimeout { after: Duration },
+    #[error("upstream response body not read within {after:?}")]
+    BodyTimeout { after: Duration },
+    #[error("upstream request timed out after {after:?}")]
+    Timeout { after: Duration },
+    #[error("upstream is misconfigured, {0}")]
+    Misconfigured(Misconfig),
+}
+
+/// Trackers of the health checks a backend runs.
+struct Trackers {
+    shallow: Tracker,
+    deep: Option<Tracker>,
 }
 
 /// Backend implementation for upstream requests.
 pub struct BackendImpl {
     shutdown_token: CancellationToken,
     cfg: Backend,
-    client: crate::http::client::HyperClient,
+    /// Replaced by a fresh one to drop the idle connections of the pool at once.
+    client: ArcSwap<crate::http::client::HyperClient>,
     await_rl: Arc<
         RateLimiter<
             governor::state::direct::NotKeyed,
@@ -47,7 +82,15 @@ pub struct BackendImpl {
         >,
     >,
     alive: Arc<AtomicBool>,
+    /// Threshold state of each health check; `alive` while all of them are up.
+    checks: Mutex<Trackers>,
+    drained: AtomicBool,
+    /// Misconfiguration the last failing request was told by, until a request gets through.
+    config_error: Mutex<Option<Misconfig>>,
     connection_semaphore: Arc<Semaphore>,
+    health_notifier: Option<HealthNotifier>,
+    /// Signs every request just before it is sent, when `signing` is configured.
+    signer: Option<Signer>,
 }
 
 impl BackendImpl {
@@ -68,8 +111,8 @@ impl BackendImpl {
             .allow_burst(NonZeroU32::new(burst).unwrap());
         let deny_rl = Arc::new(RateLimiter::direct(deny_quota));
 
-        use crate::http::client::create_client;
-        let client = create_client();
+        use crate::http::client::create_client_with;
+        let client = create_client_with(cfg.connect_timeout);
 
         let max_concurrent_connections = cfg.concurrency.unwrap_or(4096);
         let connection_semaphore = Arc::new(Semaphore::new(max_concurrent_connections));
@@ -78,42 +121,165 @@ impl BackendImpl {
             Policy::from_str(cfg.policy.as_deref().unwrap_or("deny")).unwrap_or(Policy::Deny);
         change_policy(policy)?;
 
+        let health_notifier = match cfg.on_health_change.as_ref() {
+            Some(hook) => HealthNotifier::spawn(shutdown_token.clone(), hook)?,
+            None => None,
+        };
+
+        let signer = cfg.signing.as_ref().map(Signer::from_config).transpose()?;
+        let checks = Trackers {
+            shallow: Tracker::new(health::SHALLOW_FAIL_THRESHOLD, health::SHALLOW_OK_THRESHOLD),
+            deep: cfg.health_synthetic.as_ref().map(|s| Tracker::new(s.fail_threshold(), s.ok_threshold())),
+        };
+
         let backend = Arc::new(Self {
             shutdown_token: shutdown_token.clone(),
             cfg,
-            client,
+            client: ArcSwap::from_pointee(client),
             await_rl,
             deny_rl,
             alive: Arc::new(AtomicBool::new(true)),
+            checks: Mutex::new(checks),
+            drained: AtomicBool::new(false),
+            config_error: Mutex::new(None),
             connection_semaphore,
+            health_notifier,
+            signer,
         });
+        metrics::set_backend_drained(backend.id(), false);
+        metrics::set_upstream_config_error(backend.id(), false);
+        // A URL that cannot be requested is reported before the first request fails on it.
+        if let Some(misconfig) = misconfig::check_base_url(&backend.base_url()) {
+            backend.set_config_error(Some(misconfig));
+        }
 
         // Start health observer
         let observer_backend = backend.clone();
         tokio::task::spawn(async move {
             observer_backend.observer().await;
         });
+        if let Some(synthetic) = backend.cfg.health_synthetic.clone() {
+            let observer_backend = backend.clone();
+            tokio::task::spawn(async move {
+                observer_backend.synthetic_observer(synthetic).await;
+            });
+        }
 
         Ok(backend)
     }
 
-    /// Sets the health status of the backend.
-    pub fn set_health(&self, up: bool) {
+    /// Sets the health status of the backend. A transition is logged as an event and
+    /// reported to the `on_health_change` webhook, if configured.
+    pub fn set_health(&self, up: bool, consecutive_failures: u32, last_error: Option<String>) {
         let prev = self.alive.swap(up, Ordering::Relaxed);
         if prev == up {
             return;
         }
-        if up {
-            warn!(
-                "clients pool is upped for upstream (to={})",
-                self.cfg.host.as_deref().unwrap_or("unknown")
-            );
-        } else {
-            error!(
-                "clients pool is down for upstream (to={})",
-                self.cfg.host.as_deref().unwrap_or("unknown")
+
+        let event = HealthEvent::new(self.id(), up, consecutive_failures, last_error);
+        event.log();
+        if let Some(notifier) = &self.health_notifier {
+            notifier.notify(event);
+        }
+    }
+
+    /// Takes the result of a probe of the check and updates the backend health once the check
+    /// flipped: it is up while all of its checks are. Returns whether the check is down.
+    fn observe(&self, check: Check, result: Result<()>) -> bool {
+        let mut checks = self.checks.lock();
+        let Trackers { shallow, deep } = &mut *checks;
+        let Some(tracker) = (match check {
+            Check::Shallow => Some(shallow),
+            Check::Deep => deep.as_mut(),
+        }) else {
+            return false;
+        };
+        let Some(check_up) = tracker.observe(result.map_err(|e| format!("{:#}", e))) else {
+            return !tracker.status().up;
+        };
+        let status = tracker.status().clone();
+        let up = checks.shallow.status().up && checks.deep.as_ref().is_none_or(|d| d.status().up);
+        drop(checks);
+
+        tracing::info!(
+            component = "upstream",
+            event = "backend_health_check_changed",
+            backend = self.id(),
+            check = check.label(),
+            up = check_up,
+            "backend health check changed"
+        );
+        let failures = if check_up { 0 } else { status.consecutive_failures };
+        self.set_health(up, failures, status.last_error);
+        !check_up
+    }
+
+    /// Sends the synthetic request of the deep check. It goes to the origin directly, past the
+    /// rate limits and the down state, and its answer is never stored.
+    async fn probe_synthetic(&self, synthetic: &HealthSynthetic) -> Result<()> {
+        let mut url = format!("{}{}", self.base_url(), synthetic.path);
+        if let Some(query) = synthetic.query.as_deref().filter(|q| !q.is_empty()) {
+            url.push('?');
+            url.push_str(query);
+        }
+        let uri: hyper::Uri = url.parse()
+            .with_context(|| format!("Invalid synthetic health check URL: {}", dedlog::redacted(&url)))?;
+
+        let mut headers = vec![(health::SYNTHETIC_HEADER.to_string(), "1".to_string())];
+        self.sign_owned("GET", &uri, None, &mut headers, &[]);
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+
+        use crate::upstream::backend_hyper_impl::make_get_request;
+        let (status, _, _) = make_get_request(&self.client.load_full(), uri, headers, self.timeouts(), None, self.cfg.max_response_size())
+            .await
+            .with_context(|| format!("Synthetic health check failed for URL: {}", dedlog::redacted(&url)))?;
+        if status != synthetic.expected_status() {
+            anyhow::bail!(
+                "synthetic health check of {} answered {}, expected {}",
+                synthetic.path,
+                status,
+                synthetic.expected_status()
             );
         }
+        Ok(())
+    }
+
+    /// Keeps the misconfiguration a request failed with as the config error; other failures
+    /// leave it as it is, and it is cleared once a request gets an answer.
+    fn track_config_error(&self, err: &anyhow::Error) {
+        if let Some(misconfig) = misconfig::misconfig_of(err) {
+            self.set_config_error(Some(misconfig.clone()));
+        }
+    }
+
+    fn set_config_error(&self, misconfig: Option<Misconfig>) {
+        let mut current = self.config_error.lock();
+        if *current == misconfig {
+            return;
+        }
+        match &misconfig {
+            Some(misconfig) => tracing::error!(
+                component = "upstream",
+                event = "backend_misconfigured",
+                backend = self.id(),
+                kind = misconfig.kind.label(),
+                error = %misconfig,
+                "backend config error, misses fail until it is fixed while hits are served from the cache"
+            ),
+            None => tracing::info!(
+                component = "upstream",
+                event = "backend_config_error_cleared",
+                backend = self.id(),
+                "backend answers again, config error cleared"
+            ),
+        }
+        metrics::set_upstream_config_error(self.id(), misconfig.is_some());
+        *current = misconfig;
+    }
+
+    /// `backend.id`, or the host when no id is set.
+    fn id(&self) -> &str {
+        self.cfg.id.as_deref().or(self.cfg.host.as_deref()).unwrap_or("unknown")
     }
 
     /// Gets the base URL for the backend.
@@ -144,8 +310,28 @@ impl BackendImpl {
         }
     }
 
+    /// Budgets of a request: `timeout` as the umbrella, plus the TTFB and body phases.
+    fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            total: self.get_timeout(false),
+            ttfb: self.cfg.ttfb_timeout,
+            body: self.cfg.body_timeout,
+        }
+    }
+
     /// Throttles requests based on policy.
     async fn throttle(&self) -> Result<()> {
+        if self.drained.load(Ordering::Relaxed) {
+            return Err(UpstreamError::BackendIsDrained { id: self.id().to_string() }.into());
+        }
+
+        // Down for its config, or with a URL that cannot be requested at all: the failure says why.
+        if let Some(misconfig) = self.config_error.lock().as_ref() {
+            if !self.alive.load(Ordering::Relaxed) || misconfig.kind == MisconfigKind::InvalidUri {
+                return Err(UpstreamError::Misconfigured(misconfig.clone()).into());
+            }
+        }
+
         if !self.alive.load(Ordering::Relaxed) {
             let host = self.cfg.host.as_deref().unwrap_or("unknown");
             tracing::warn!(
@@ -165,25 +351,63 @@ impl BackendImpl {
                 Ok(())
             }
             Policy::Deny => {
-                // Try to acquire token, fail if not available
-                if self.deny_rl.check().is_ok() {
-                    Ok(())
-                } else {
-                    Err(UpstreamError::BackendIsTooBusy.into())
+                // Try to acquire token, fail with the time until the next one if not available
+                match self.deny_rl.check() {
+                    Ok(()) => Ok(()),
+                    Err(not_until) => {
+                        let retry_after = not_until.wait_time_from(DefaultClock::default().now());
+                        Err(UpstreamError::BackendIsTooBusy { retry_after }.into())
+                    }
                 }
             }
         }
     }
-}
 
-#[async_trait::async_trait]
-impl Upstream for BackendImpl {
-    async fn request(
+    /// Signature headers of a request about to be sent, once the client's own are dropped
+    /// from `headers`; none when signing is not configured.
+    fn sign(
+        &self,
+        method: &str,
+        uri: &hyper::Uri,
+        forwarded_host: Option<&[u8]>,
+        headers: &mut Vec<(&str, &str)>,
+        body: &[u8],
+    ) -> Vec<(&'static str, String)> {
+        let Some(ref signer) = self.signer else {
+            return Vec::new();
+        };
+        headers.retain(|(k, _)| !signing::is_signing_header(k));
+        signer.sign(method, uri, forwarded_host, headers, body)
+    }
+
+    /// [`Self::sign`] over owned headers, adding the signature to them.
+    fn sign_owned(
+        &self,
+        method: &str,
+        uri: &hyper::Uri,
+        forwarded_host: Option<&[u8]>,
+        headers: &mut Vec<(String, String)>,
+        body: &[u8],
+    ) {
+        if self.signer.is_none() {
+            return;
+        }
+        let mut refs: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        let signature = self.sign(method, uri, forwarded_host, &mut refs, body);
+        headers.retain(|(k, _)| !signing::is_signing_header(k));
+        headers.extend(signature.into_iter().map(|(k, v)| (k.to_string(), v)));
+    }
+
+    /// Fetches the response of a rule's request, with the xxh3 of its body when the origin
+    /// answered it as identity: the body as received is then the body it stands for.
+    async fn fetch(
         &self,
+        method: &str,
         rule: &Rule,
         queries: &[(Vec<u8>, Vec<u8>)],
         headers: &[(Vec<u8>, Vec<u8>)],
-    ) -> Result<Response> {
+        body: Option<&[u8]>,
+    ) -> Result<(Response, Option<u64>)> {
         self.throttle().await?;
 
         let base_url = self.base_url();
@@ -211,12 +435,14 @@ impl Upstream for BackendImpl {
 
         // Parse URL to Uri
         let uri: hyper::Uri = url.parse()
-            .with_context(|| format!("Invalid URL: {}", url))?;
+            .with_context(|| format!("Invalid URL: {}", dedlog::redacted(&url)))?;
 
         // Extract forwarded host value (X-Forwarded-Host or Host) as bytes (no allocations)
         let forwarded_host = proxy::forwarded_host_value_bytes(headers);
 
-        let request_str = format!("GET {}", url);
+        let http_method = hyper::Method::from_bytes(method.as_bytes())
+            .with_context(|| format!("Invalid method: {:?}", method))?;
+        let request_str = format!("{} {}", http_method, url);
 
         let span = upstream_trace::start_request_span(rule, &request_str);
 
@@ -240,22 +466,60 @@ impl Upstream for BackendImpl {
             };
             request_headers.push((k, v));
         }
+        loop_guard::stamp(&mut request_headers);
+
+        // Fills and refreshes ask the origin for the configured codings; the client's choice is
+        // still needed to decide whether the answer may be stored encoded.
+        let client_accept_encoding = encoding::client_accept_encoding(headers);
+        if let Some(ref accept_encoding) = self.cfg.accept_encoding {
+            encoding::override_accept_encoding(&mut request_headers, accept_encoding);
+        }
+        self.sign_owned(http_method.as_str(), &uri, forwarded_host, &mut request_headers, body.unwrap_or_default());
         
         let request_headers_refs: Vec<(&str, &str)> = request_headers
             .iter()
             .map(|(k, v)| (k.as_str(), v.as_str()))
             .collect();
 
-        let timeout_duration = self.get_timeout(false);
-        
-        use crate::upstream::backend_hyper_impl::make_get_request;
+        use crate::upstream::backend_hyper_impl::make_digested_request;
         use crate::upstream::backend_headers::process_response_headers;
-        match make_get_request(&self.client, uri, request_headers_refs, timeout_duration, forwarded_host).await {
-            Ok((status, response_headers_map, body)) => {
+        let max_body = self.cfg.max_response_size();
+        let request_body = body.map(|b| hyper::body::Bytes::from(b.to_vec()));
+        match make_digested_request(
+            &self.client.load_full(),
+            http_method,
+            uri,
+            request_headers_refs,
+            request_body,
+            self.timeouts(),
+            forwarded_host,
+            max_body,
+        )
+        .await
+        {
+            Ok((status, response_headers_map, collected)) => {
+                self.set_config_error(None);
                 // Process headers directly from response (optimized)
-                let response_headers = process_response_headers(&response_headers_map, Some(rule));
+                let mut response_headers = process_response_headers(&response_headers_map, Some(rule));
                 
-                let body: Vec<u8> = body.to_vec();
+                let coding = response_headers_map
+                    .get(hyper::header::CONTENT_ENCODING)
+                    .and_then(|v| v.to_str().ok());
+                let digest = coding
+                    .is_none_or(|c| c.trim().is_empty() || c.trim().eq_ignore_ascii_case("identity"))
+                    .then_some(collected.digest);
+                let mut body: Vec<u8> = collected.bytes.to_vec();
+                // A 304 to a refresh has no body to store.
+                if self.cfg.accept_encoding.is_some() && status != 304 {
+                    body = encoding::stored_form(
+                        rule,
+                        client_accept_encoding.as_deref(),
+                        coding,
+                        &mut response_headers,
+                        body,
+                        max_body,
+                    )?;
+                }
                 let response_size: usize = body.len();
                 
                 // Record response in span
@@ -263,10 +527,17 @@ impl Upstream for BackendImpl {
                     upstream_trace::record_response_in_span(span, status, response_size);
                 }
 
-                Ok(Response::new(status, response_headers, body))
+                Ok((Response::new(status, response_headers, body), digest))
             }
             Err(e) => {
                 let e: anyhow::Error = e;
+                self.track_config_error(&e);
+                if is_response_too_large(&e) {
+                    meter::add_upstream_response_too_large(1);
+                }
+                if let Some(phase) = timeout_phase(&e) {
+                    meter::add_upstream_timeout(phase);
+                }
                 
                 // Record error in span
                 if let Some(ref span) = span {
@@ -276,6 +547,20 @@ impl Upstream for BackendImpl {
             }
         }
     }
+}
+
+#[async_trait::async_trait]
+impl Upstream for BackendImpl {
+    async fn request(
+        &self,
+        method: &str,
+        rule: &Rule,
+        queries: &[(Vec<u8>, Vec<u8>)],
+        headers: &[(Vec<u8>, Vec<u8>)],
+        body: Option<&[u8]>,
+    ) -> Result<Response> {
+        self.fetch(method, rule, queries, headers, body).await.map(|(response, _)| response)
+    }
 
     async fn proxy_request(
         &self,
@@ -295,31 +580,21 @@ impl Upstream for BackendImpl {
 
         // Parse URL to Uri
         let uri: hyper::Uri = url.parse()
-            .with_context(|| format!("Invalid URL: {}", url))?;
+            .with_context(|| format!("Invalid URL: {}", dedlog::redacted(&url)))?;
 
         // Build request string for tracing
         let request_str = format!("{} {}", method, url);
 
-        // Parse HTTP method
-        let http_method = match method {
-            "GET" => hyper::Method::GET,
-            "POST" => hyper::Method::POST,
-            "PUT" => hyper::Method::PUT,
-            "DELETE" => hyper::Method::DELETE,
-            _ => hyper::Method::GET,
-        };
+        // Parse HTTP method, passing any method along as it came
+        let http_method = hyper::Method::from_bytes(method.as_bytes())
+            .with_context(|| format!("Invalid method: {:?}", method))?;
 
-        // Convert headers to bytes format for forwarded_host_value_bytes
-        let headers_bytes: Vec<(Vec<u8>, Vec<u8>)> = headers
-            .iter()
-            .map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec()))
-            .collect();
-        
         // Extract forwarded host value (X-Forwarded-Host or Host) as bytes (no allocations)
-        let forwarded_host = proxy::forwarded_host_value_bytes(&headers_bytes);
+        let forwarded_host = proxy::forwarded_host_value_bytes(headers);
 
         // Sanitize hop-by-hop headers from request
-        let filtered_headers = proxy::filter_hop_by_hop_headers(headers);
+        let mut filtered_headers = proxy::filter_hop_by_hop_headers(headers);
+        loop_guard::stamp(&mut filtered_headers);
 
         // Start upstream span (after proxyForwardedHost)
         let span = upstream_trace::start_proxy_request_span(path, &request_str);
@@ -334,14 +609,18 @@ impl Upstream for BackendImpl {
             request_headers.push((key.as_str(), value.as_str()));
         }
 
+        // Signed last, over the headers and body as they are sent.
+        let signature = self.sign(http_method.as_str(), &uri, forwarded_host, &mut request_headers, body.unwrap_or_default());
+        request_headers.extend(signature.iter().map(|(k, v)| (*k, v.as_str())));
+
         // Convert body to Bytes if present
         let body_bytes = body.map(|b| hyper::body::Bytes::from(b.to_vec()));
 
-        let timeout_duration = self.get_timeout(false);
-        
         use crate::upstream::backend_hyper_impl::make_method_request;
-        match make_method_request(&self.client, http_method, uri, request_headers, body_bytes, timeout_duration, forwarded_host).await {
+        let max_body = self.cfg.max_response_size();
+        match make_method_request(&self.client.load_full(), http_method, uri, request_headers, body_bytes, self.timeouts(), forwarded_host, max_body).await {
             Ok((status, response_headers_map, body_bytes)) => {
+                self.set_config_error(None);
                 // Process headers directly from response (optimized)
                 use crate::upstream::backend_headers::process_response_headers;
                 let response_headers = process_response_headers(&response_headers_map, None);
@@ -357,6 +636,13 @@ impl Upstream for BackendImpl {
                 Ok(Response::new(status, response_headers, body_bytes))
             }
             Err(e) => {
+                self.track_config_error(&e);
+                if is_response_too_large(&e) {
+                    meter::add_upstream_response_too_large(1);
+                }
+                if let Some(phase) = timeout_phase(&e) {
+                    meter::add_upstream_timeout(phase);
+                }
                 // Record error in span
                 if let Some(ref span) = span {
                     upstream_trace::record_error_in_span(span, e.as_ref() as &dyn std::error::Error);
@@ -366,34 +652,52 @@ impl Upstream for BackendImpl {
         }
     }
 
-    async fn refresh(&self, entry: &Entry) -> Result<()> {
-        use crate::dedlog;
-        
+    async fn refresh(&self, entry: &Entry) -> Result<Refreshed> {
+        // Not logged per entry: refreshes are expected to fail for as long as the drain lasts.
+        if self.drained.load(Ordering::Relaxed) {
+            return Err(UpstreamError::BackendIsDrained { id: self.id().to_string() }.into());
+        }
+
         // Get request payload from entry
         let req_payload = entry.request_payload()
             .context("Failed to decode payload")?;
         
         let queries = &req_payload.queries;
         let headers = &req_payload.headers;
+        let body = req_payload.body.as_deref();
+        // Only a POST keyed by its body stores one.
+        let method = if body.is_some() { "POST" } else { "GET" };
         
         // Start refresh span
         let span = upstream_trace::start_refresh_span_context(entry);
         
+        // Validators of the stored response make the request conditional; sent on top of the
+        // request headers, the stored ones stay as they are.
+        let conditional = entry.validators().map(|v| v.conditional_headers()).unwrap_or_default();
+        let request_headers = if conditional.is_empty() {
+            std::borrow::Cow::Borrowed(headers.as_slice())
+        } else {
+            std::borrow::Cow::Owned([headers.as_slice(), conditional.as_slice()].concat())
+        };
+
         let rule = entry.rule();
-        let upstream_resp = match self.request(rule, queries, headers).await {
+        let (upstream_resp, body_digest) = match self.fetch(method, &rule, queries, &request_headers, body).await {
             Ok(r) => r,
             Err(e) => {
                 // Record error in span
                 if let Some(ref span) = span {
                     upstream_trace::record_error_in_span(span, e.as_ref());
                 }
-                let request_str = format!("GET {}", rule.path.as_deref().unwrap_or("/"));
-                dedlog::err(Some(e.as_ref()), Some(&request_str), "failed to fetch new payload while refreshing");
+                let request_str = format!("{} {}", method, rule.path.as_deref().unwrap_or("/"));
+                dedlog::err("upstream", Some(e.as_ref()), Some(&request_str), "failed to fetch new payload while refreshing");
                 return Err(e);
             }
         };
         
         // Validate response status
+        if upstream_resp.status == 304 && !conditional.is_empty() {
+            return Ok(Refreshed::NotModified);
+        }
         if upstream_resp.status != 200 {
             return Err(anyhow::anyhow!("invalid upstream status code: {}", upstream_resp.status));
         }
@@ -405,16 +709,21 @@ impl Upstream for BackendImpl {
             body: upstream_resp.body,
         };
         
-        entry.set_payload(queries, headers, &model_resp);
-        
-        // Update timestamps
-        entry.touch_refreshed_at();
-        entry.clear_refresh_queued();
-        
-        Ok(())
+        let refreshed = entry.detached();
+        refreshed.set_payload_with_body(queries, headers, body, &model_resp);
+        // Hashed as it was received; an encoded answer is hashed decoded when compared.
+        if let Some(body_digest) = body_digest {
+            let headers = model_resp.headers.iter().map(|(k, v)| (k.as_bytes(), v.as_bytes()));
+            refreshed.set_identity_hash(digest::identity_hash(model_resp.status, headers, body_digest));
+        }
+        Ok(Refreshed::Modified(refreshed))
     }
 
     async fn is_healthy(&self) -> Result<()> {
+        if let Some(misconfig) = self.config_error.lock().clone().filter(|m| m.kind == MisconfigKind::InvalidUri) {
+            return Err(UpstreamError::Misconfigured(misconfig).into());
+        }
+
         let healthcheck_path = self.cfg.healthcheck.as_deref().unwrap_or("/healthz");
         let base_url = self.base_url();
         let url = format!("{}{}", base_url, healthcheck_path);
@@ -422,13 +731,20 @@ impl Upstream for BackendImpl {
         let uri: hyper::Uri = url.parse()
             .with_context(|| format!("Invalid health check URL: {}", url))?;
 
-        let timeout_duration = self.cfg.timeout.unwrap_or(Duration::from_secs(10));
-        
-        use crate::upstream::backend_hyper_impl::make_get_request;
-        let (status, _, _) = make_get_request(&self.client, uri, Vec::new(), timeout_duration, None)
-            .await
-            .with_context(|| format!("Health check failed for URL: {}", url))?;
+        let mut headers = Vec::new();
+        self.sign_owned("GET", &uri, None, &mut headers, &[]);
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
 
+        use crate::upstream::backend_hyper_impl::make_get_request;
+        let (status, _, _) = match make_get_request(&self.client.load_full(), uri, headers, self.timeouts(), None, self.cfg.max_response_size()).await {
+            Ok(answer) => answer,
+            Err(e) => {
+                // Probes go on while the backend is down, so they are what clears the config error.
+                self.track_config_error(&e);
+                return Err(e).with_context(|| format!("Health check failed for URL: {}", url));
+            }
+        };
+        self.set_config_error(None);
 
         if status != 200 {
             return Err(UpstreamError::NotHealthyStatusCode.into());
@@ -436,22 +752,64 @@ impl Upstream for BackendImpl {
 
         Ok(())
     }
+
+    fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::Relaxed)
+    }
+
+    fn health_checks(&self) -> Option<HealthChecks> {
+        let checks = self.checks.lock();
+        Some(HealthChecks {
+            shallow: checks.shallow.status().clone(),
+            deep: checks.deep.as_ref().map(|d| d.status().clone()),
+        })
+    }
+
+    fn backend_id(&self) -> &str {
+        self.id()
+    }
+
+    fn config_error(&self) -> Option<Misconfig> {
+        self.config_error.lock().clone()
+    }
+
+    fn set_drained(&self, drained: bool) {
+        if self.drained.swap(drained, Ordering::Relaxed) == drained {
+            return;
+        }
+        metrics::set_backend_drained(self.id(), drained);
+        tracing::info!(
+            component = "upstream",
+            event = if drained { "backend_drained" } else { "backend_undrained" },
+            backend = self.id(),
+            "backend drain state changed"
+        );
+    }
+
+    fn is_drained(&self) -> bool {
+        self.drained.load(Ordering::Relaxed)
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.connection_semaphore.available_permits() == 0
+    }
+
+    fn drop_idle_connections(&self) {
+        // The old pool, and the idle connections in it, go with the last request still using it.
+        let client = crate::http::client::create_client_with(self.cfg.connect_timeout);
+        self.client.store(Arc::new(client));
+    }
 }
 
-/// Health observer that periodically checks backend health.
+/// Health observers that periodically check backend health.
 impl BackendImpl {
     async fn observer(&self) {
-        const OK_CLOSE: u32 = 3; // after 3 in a row -> UP
-        const FAIL_OPEN: u32 = 3; // after 3 in a row -> DOWN
         const BASE_PROBE: Duration = Duration::from_millis(500);
         const DOWN_PROBE: Duration = Duration::from_secs(1); // on DOWN throttling probes
 
         let mut interval = tokio::time::interval(BASE_PROBE);
         interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
-
         let mut down = false;
-        let mut fails = 0u32;
-        let mut oks = 0u32;
 
         loop {
             tokio::select! {
@@ -459,40 +817,32 @@ impl BackendImpl {
                     return;
                 }
                 _ = interval.tick() => {
-                    match self.is_healthy().await {
-                        Err(_) => {
-                            // fail
-                            oks = 0;
-                            if !down {
-                                fails += 1;
-                                if fails >= FAIL_OPEN {
-                                    down = true;
-                                    self.set_health(false);
-                                    fails = 0;
-                                    interval = tokio::time::interval(DOWN_PROBE);
-                                    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
-                                }
-                            }
-                        }
-                        Ok(_) => {
-                            // success
-                            fails = 0;
-                            if down {
-                                oks += 1;
-                                if oks >= OK_CLOSE {
-                                    down = false;
-                                    self.set_health(true);
-                                    oks = 0;
-                                    interval = tokio::time::interval(BASE_PROBE);
-                                    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
-                                }
-                            } else {
-                                oks = 0;
-                            }
-                        }
+                    let result = self.is_healthy().await;
+                    if self.observe(Check::Shallow, result) != down {
+                        down = !down;
+                        interval = tokio::time::interval(if down { DOWN_PROBE } else { BASE_PROBE });
+                        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
                     }
                 }
             }
         }
     }
+
+    /// Sends the synthetic request of `health_synthetic` every `interval`.
+    async fn synthetic_observer(&self, synthetic: HealthSynthetic) {
+        let mut interval = tokio::time::interval(synthetic.interval());
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                _ = self.shutdown_token.cancelled() => {
+                    return;
+                }
+                _ = interval.tick() => {
+                    let result = self.probe_synthetic(&synthetic).await;
+                    self.observe(Check::Deep, result);
+                }
+            }
+        }
+    }
 }
@@ -19,7 +19,7 @@ const HOP_BY_HOP: &[&str] = &[
 /// Checks if a header name is hop-by-hop (optimized with case-insensitive comparison).
 /// Uses byte-level comparison to avoid allocations.
 #[inline]
-fn is_hop_by_hop(name: &str) -> bool {
+pub(crate) fn is_hop_by_hop(name: &str) -> bool {
     let name_bytes = name.as_bytes();
     HOP_BY_HOP.iter().any(|&h| {
         let h_bytes = h.as_bytes();
@@ -28,8 +28,26 @@ fn is_hop_by_hop(name: &str) -> bool {
     })
 }
 
+/// Headers the freshness of a response is read from, kept for rules with
+/// `cache_value.respect_cache_control` whether their whitelist has them or not.
+const FRESHNESS: &[&str] = &["cache-control", "expires", "date"];
+
+/// Validators of a response, kept for every rule whether its whitelist has them or not: refreshes
+/// send them back to make the request conditional.
+const VALIDATORS: &[&str] = &["etag", "last-modified"];
+
+/// Kept for every rule as well: the variant a stored response answers is read from it.
+const VARY: &str = "vary";
+
+/// Kept for every rule too: entries are indexed by its tags for invalidation (see
+/// `model::surrogate`).
+const SURROGATE_KEY: &str = crate::model::surrogate::HEADER;
+
 /// Processes response headers directly from hyper::Response, filtering hop-by-hop
 /// and rule-based headers, returning Vec<(String, String)> efficiently.
+///
+/// The kept headers come out in the order of `response_headers` (values of a repeated name
+/// together, in the order received), one pair per value: they are stored and rendered as is.
 pub fn process_response_headers(
     response_headers: &hyper::HeaderMap,
     rule: Option<&Rule>,
@@ -37,6 +55,7 @@ pub fn process_response_headers(
     let allowed_map = rule
         .and_then(|r| r.cache_value.headers_map.as_ref())
         .filter(|m| !m.is_empty());
+    let keep_freshness = rule.is_some_and(|r| r.cache_value.respect_cache_control == Some(true));
 
     // Pre-allocate with estimated capacity (most responses have ~10-20 headers)
     let capacity = if let Some(map) = allowed_map {
@@ -54,7 +73,11 @@ pub fn process_response_headers(
 
         // Filter by rule if present (case-insensitive comparison for HTTP headers)
         if let Some(allowed) = allowed_map {
-            if !allowed.iter().any(|h| h.eq_ignore_ascii_case(name_str)) {
+            let kept = VALIDATORS.contains(&name_str)
+                || name_str == VARY
+                || name_str == SURROGATE_KEY
+                || keep_freshness && FRESHNESS.contains(&name_str);
+            if !kept && !allowed.iter().any(|h| h.eq_ignore_ascii_case(name_str)) {
                 continue;
             }
         }
@@ -6,95 +6,178 @@ use http_body_util::BodyExt;
 use hyper::{Method, Request, Uri};
 use bytes::Bytes;
 use std::time::Duration;
-use tokio::time::timeout;
+use tokio::sync::watch;
+use tokio::time::{timeout, Instant};
 use http_body_util::{Empty, Full};
 use http_body_util::combinators::BoxBody;
+use xxhash_rust::xxh3::Xxh3;
 
+use crate::dedlog::{redacted, redacted_headers};
+use crate::http::client::connect::{ConnectState, ConnectTimedOut, ConnectWatch};
 use crate::http::client::HyperClient;
+use crate::upstream::backend::UpstreamError;
+use crate::upstream::misconfig;
+
+/// Phase of an upstream request a timeout fired in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutPhase {
+    Connect = 0,
+    Ttfb = 1,
+    Body = 2,
+    /// The umbrella `backend.timeout`.
+    Total = 3,
+}
+
+impl TimeoutPhase {
+    pub const ALL: [TimeoutPhase; 4] = [TimeoutPhase::Connect, TimeoutPhase::Ttfb, TimeoutPhase::Body, TimeoutPhase::Total];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TimeoutPhase::Connect => "connect",
+            TimeoutPhase::Ttfb => "ttfb",
+            TimeoutPhase::Body => "body",
+            TimeoutPhase::Total => "total",
+        }
+    }
+}
+
+/// Time budgets of one upstream request. `total` caps everything up to the response head;
+/// the connect budget belongs to the client's connector (see [`crate::http::client::connect`]).
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    pub total: Duration,
+    /// From the connection being ready (or the request start, on a pooled one) to the head.
+    pub ttfb: Option<Duration>,
+    /// From the head to the end of the body.
+    pub body: Option<Duration>,
+}
+
+impl From<Duration> for Timeouts {
+    fn from(total: Duration) -> Self {
+        Self { total, ttfb: None, body: None }
+    }
+}
+
+/// Whether the error chain carries [`UpstreamError::ResponseTooLarge`].
+pub fn is_response_too_large(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|cause| matches!(cause.downcast_ref::<UpstreamError>(), Some(UpstreamError::ResponseTooLarge { .. })))
+}
+
+/// Time until the `deny` policy limiter has a token again, when the error chain carries
+/// [`UpstreamError::BackendIsTooBusy`].
+pub fn throttled_retry_after(err: &anyhow::Error) -> Option<Duration> {
+    err.chain().find_map(|cause| match cause.downcast_ref::<UpstreamError>() {
+        Some(UpstreamError::BackendIsTooBusy { retry_after }) => Some(*retry_after),
+        _ => None,
+    })
+}
+
+/// Phase of the timeout the error chain carries, if any.
+pub fn timeout_phase(err: &anyhow::Error) -> Option<TimeoutPhase> {
+    err.chain().find_map(|cause| match cause.downcast_ref::<UpstreamError>() {
+        Some(UpstreamError::ConnectTimeout { .. }) => Some(TimeoutPhase::Connect),
+        Some(UpstreamError::TtfbTimeout { .. }) => Some(TimeoutPhase::Ttfb),
+        Some(UpstreamError::BodyTimeout { .. }) => Some(TimeoutPhase::Body),
+        Some(UpstreamError::Timeout { .. }) => Some(TimeoutPhase::Total),
+        _ => None,
+    })
+}
+
+/// Body of an upstream answer with the xxh3 of its bytes as received.
+pub struct Collected {
+    pub bytes: Bytes,
+    pub digest: u64,
+}
+
+/// Reads the body into memory, giving up as soon as it exceeds `limit` bytes.
+/// A declared Content-Length above the limit fails before any byte is read; otherwise
+/// frames are counted as they arrive, so a chunked or endless body is cut off early.
+/// Frames are hashed as they arrive too, so a refresh can tell an unchanged body without
+/// another pass over it. Dropping the body on failure closes the connection instead of
+/// draining it.
+async fn collect_body<B>(headers: &hyper::HeaderMap, mut body: B, limit: usize) -> Result<Collected>
+where
+    B: hyper::body::Body<Data = Bytes> + Unpin,
+    B::Error: std::error::Error + Send + Sync + 'static,
+{
+    let declared = headers
+        .get(hyper::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > limit as u64) {
+        return Err(UpstreamError::ResponseTooLarge { limit }.into());
+    }
+
+    let mut buf = Vec::with_capacity(declared.map_or(0, |len| len as usize));
+    let mut hasher = Xxh3::new();
+    while let Some(frame) = body.frame().await {
+        let frame = frame.context("Failed to read response body")?;
+        if let Ok(chunk) = frame.into_data() {
+            if buf.len() + chunk.len() > limit {
+                return Err(UpstreamError::ResponseTooLarge { limit }.into());
+            }
+            hasher.update(&chunk);
+            buf.extend_from_slice(&chunk);
+        }
+    }
+    Ok(Collected { bytes: Bytes::from(buf), digest: hasher.digest() })
+}
 
 /// Makes a GET request to upstream using hyper client.
 pub async fn make_get_request(
     client: &HyperClient,
     uri: Uri,
     headers: Vec<(&str, &str)>,
-    timeout_duration: Duration,
+    timeouts: Timeouts,
     forwarded_host: Option<&[u8]>,
+    max_body: usize,
 ) -> anyhow::Result<(u16, hyper::HeaderMap, Bytes)> {
-    let uri_str = uri.to_string();
-    
-    let mut builder = Request::builder()
-        .method(Method::GET)
-        .uri(uri);
-    
-    // Set all headers except Host (Host will be set after build() to override URI-based Host)
-    for (name, value) in headers {
-        if !name.eq_ignore_ascii_case("host") {
-            builder = builder.header(name, value);
-        }
-    }
-    
-    let empty_with_error: BoxBody<Bytes, hyper::Error> = Empty::<Bytes>::new()
-        .map_err(|never: std::convert::Infallible| match never {})
-        .boxed();
-    
-    let mut req = builder.body(empty_with_error)?;
-    
-    // Set Host header after build() to ensure it's actually sent as HTTP/1.1 header.
-    // This bypasses any builder/client normalization that might ignore or modify Host.
-    if let Some(host_bytes) = forwarded_host {
-        if let Ok(host_value) = hyper::header::HeaderValue::from_bytes(host_bytes) {
-            // Explicitly remove any existing Host header (from URI) and insert the forwarded one
-            req.headers_mut().remove(hyper::header::HOST);
-            req.headers_mut().insert(hyper::header::HOST, host_value);
-        }
-    }
-    
-    let response = match timeout(timeout_duration, client.request(req)).await {
-        Ok(Ok(resp)) => resp,
-        Ok(Err(e)) => {
-            tracing::error!(
-                uri = %uri_str,
-                error = %e,
-                error_debug = ?e,
-                "Hyper client request failed"
-            );
-            return Err(anyhow::anyhow!("Hyper client error: {} (URI: {})", e, uri_str))
-                .context("Request failed");
-        }
-        Err(_) => {
-            tracing::warn!(
-                uri = %uri_str,
-                timeout = ?timeout_duration,
-                "Request timed out"
-            );
-            return Err(anyhow::anyhow!("Request timed out after {:?} (URI: {})", timeout_duration, uri_str))
-                .context("Request timeout");
-        }
-    };
-    
-    let status = response.status().as_u16();
-    let headers = response.headers().clone();
-    
-    let (_, body_stream) = response.into_parts();
-    let body_bytes = body_stream
-        .collect()
-        .await
-        .context("Failed to read response body")?
-        .to_bytes();
-    
-    Ok((status, headers, body_bytes))
+    let (status, headers, body) = make_digested_get_request(client, uri, headers, timeouts, forwarded_host, max_body).await?;
+    Ok((status, headers, body.bytes))
+}
+
+/// Makes a GET request to upstream using hyper client, keeping the digest of the body.
+pub async fn make_digested_get_request(
+    client: &HyperClient,
+    uri: Uri,
+    headers: Vec<(&str, &str)>,
+    timeouts: Timeouts,
+    forwarded_host: Option<&[u8]>,
+    max_body: usize,
+) -> anyhow::Result<(u16, hyper::HeaderMap, Collected)> {
+    make_digested_request(client, Method::GET, uri, headers, None, timeouts, forwarded_host, max_body).await
 }
 
 /// Makes a request with custom method and optional body.
+#[allow(clippy::too_many_arguments)]
 pub async fn make_method_request(
     client: &HyperClient,
     method: Method,
     uri: Uri,
     headers: Vec<(&str, &str)>,
     body: Option<Bytes>,
-    timeout_duration: Duration,
+    timeouts: Timeouts,
     forwarded_host: Option<&[u8]>,
+    max_body: usize,
 ) -> Result<(u16, hyper::HeaderMap, Vec<u8>)> {
+    let (status, headers, body) =
+        make_digested_request(client, method, uri, headers, body, timeouts, forwarded_host, max_body).await?;
+    Ok((status, headers, body.bytes.to_vec()))
+}
+
+/// Makes a request with custom method and optional body, keeping the digest of the answer's body.
+#[allow(clippy::too_many_arguments)]
+pub async fn make_digested_request(
+    client: &HyperClient,
+    method: Method,
+    uri: Uri,
+    headers: Vec<(&str, &str)>,
+    body: Option<Bytes>,
+    timeouts: Timeouts,
+    forwarded_host: Option<&[u8]>,
+    max_body: usize,
+) -> Result<(u16, hyper::HeaderMap, Collected)> {
     let uri_str = uri.to_string();
     
     let mut builder = Request::builder()
@@ -102,7 +185,7 @@ pub async fn make_method_request(
         .uri(uri);
     
     // Set all headers except Host (Host will be set after build() to override URI-based Host)
-    for (name, value) in headers {
+    for &(name, value) in &headers {
         if !name.eq_ignore_ascii_case("host") {
             builder = builder.header(name, value);
         }
@@ -130,38 +213,105 @@ pub async fn make_method_request(
         }
     }
     
-    let response = match timeout(timeout_duration, client.request(req)).await {
-        Ok(Ok(resp)) => resp,
-        Ok(Err(e)) => {
+    exchange(client, req, &uri_str, &headers, timeouts, max_body).await
+}
+
+/// Sends the request and reads the answer within `timeouts`.
+async fn exchange(
+    client: &HyperClient,
+    req: Request<BoxBody<Bytes, hyper::Error>>,
+    uri_str: &str,
+    headers: &[(&str, &str)],
+    timeouts: Timeouts,
+    max_body: usize,
+) -> Result<(u16, hyper::HeaderMap, Collected)> {
+    let connect = ConnectWatch::new();
+    let state = connect.subscribe();
+    let request = connect.scope(client.request(req));
+    let head = async {
+        match timeouts.ttfb {
+            Some(ttfb) => tokio::select! {
+                resp = request => Ok(resp),
+                _ = ttfb_elapsed(state, ttfb) => Err(UpstreamError::TtfbTimeout { after: ttfb }),
+            },
+            None => Ok(request.await),
+        }
+    };
+
+    let response = match timeout(timeouts.total, head).await {
+        Ok(Ok(Ok(resp))) => resp,
+        Ok(Ok(Err(e))) => {
+            if let Some(after) = connect_timed_out(&e) {
+                return Err(timed_out(UpstreamError::ConnectTimeout { after }, uri_str));
+            }
             tracing::error!(
-                uri = %uri_str,
+                uri = %redacted(uri_str),
+                headers = %redacted_headers(headers),
                 error = %e,
                 error_debug = ?e,
                 "Hyper client request failed"
             );
-            return Err(anyhow::anyhow!("Hyper client error: {} (URI: {})", e, uri_str))
+            if let Some(misconfig) = misconfig::classify(&e) {
+                return Err(anyhow::Error::from(UpstreamError::Misconfigured(misconfig)))
+                    .with_context(|| format!("Request failed (URI: {})", redacted(uri_str)));
+            }
+            return Err(anyhow::anyhow!("Hyper client error: {} (URI: {})", e, redacted(uri_str)))
                 .context("Request failed");
         }
-        Err(_) => {
-            tracing::warn!(
-                uri = %uri_str,
-                timeout = ?timeout_duration,
-                "Request timed out"
-            );
-            return Err(anyhow::anyhow!("Request timed out after {:?} (URI: {})", timeout_duration, uri_str))
-                .context("Request timeout");
-        }
+        Ok(Err(e)) => return Err(timed_out(e, uri_str)),
+        Err(_) => return Err(timed_out(UpstreamError::Timeout { after: timeouts.total }, uri_str)),
     };
-    
+
     let status = response.status().as_u16();
     let headers = response.headers().clone();
-    
+
     let (_, body_stream) = response.into_parts();
-    let body_bytes = body_stream
-        .collect()
-        .await
-        .context("Failed to read response body")?
-        .to_bytes();
-    
-    Ok((status, headers, body_bytes.to_vec()))
+    let body = collect_body(&headers, body_stream, max_body);
+    let body = match timeouts.body {
+        Some(after) => match timeout(after, body).await {
+            Ok(body) => body?,
+            Err(_) => return Err(timed_out(UpstreamError::BodyTimeout { after }, uri_str)),
+        },
+        None => body.await?,
+    };
+
+    Ok((status, headers, body))
+}
+
+/// Completes once the head is `ttfb` late: counted from the connect started for the request,
+/// or from the start when it runs on a pooled connection. A connect in progress is not counted.
+async fn ttfb_elapsed(mut state: watch::Receiver<ConnectState>, ttfb: Duration) {
+    let start = Instant::now();
+    loop {
+        let current = *state.borrow_and_update();
+        let since = match current {
+            ConnectState::Pending => start,
+            ConnectState::Connecting => {
+                let _ = state.changed().await;
+                continue;
+            }
+            ConnectState::Settled(at) => at,
+        };
+        tokio::select! {
+            _ = tokio::time::sleep_until(since + ttfb) => return,
+            _ = state.changed() => {}
+        }
+    }
+}
+
+/// Budget of the connect timeout in the client error's chain, if that is what failed.
+fn connect_timed_out(err: &(dyn std::error::Error + 'static)) -> Option<Duration> {
+    let mut cause = Some(err);
+    while let Some(e) = cause {
+        if let Some(ConnectTimedOut(after)) = e.downcast_ref() {
+            return Some(*after);
+        }
+        cause = e.source();
+    }
+    None
+}
+
+fn timed_out(err: UpstreamError, uri_str: &str) -> anyhow::Error {
+    tracing::warn!(uri = %redacted(uri_str), error = %err, "Request timed out");
+    anyhow::Error::from(err).context(format!("Request timeout (URI: {})", redacted(uri_str)))
 }
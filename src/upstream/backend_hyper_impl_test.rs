@@ -1,8 +1,11 @@
 //! Tests for upstream backend hyper implementation.
 //! Verifies connection handling, body consumption, and resource cleanup.
 
-use crate::upstream::backend_hyper_impl::{make_get_request, make_method_request};
-use crate::http::client::{create_client, HyperClient};
+use crate::upstream::backend_hyper_impl::{
+    is_response_too_large, make_digested_get_request, make_get_request, make_method_request, timeout_phase,
+    TimeoutPhase, Timeouts,
+};
+use crate::http::client::{create_client, create_client_with, HyperClient};
 use hyper::Uri;
 use std::time::Duration;
 
@@ -16,7 +19,7 @@ async fn test_make_get_request_handles_connection_errors() {
     // Test with invalid port to trigger connection error
     let uri: Uri = "http://127.0.0.1:99999/invalid".parse().unwrap();
     
-    let result = make_get_request(&client, uri, Vec::new(), Duration::from_secs(3), None).await;
+    let result = make_get_request(&client, uri, Vec::new(), Duration::from_secs(3).into(), None, usize::MAX).await;
     
     // Should fail with connection error, but connection should be cleaned up
     assert!(result.is_err());
@@ -36,8 +39,9 @@ async fn test_make_method_request_handles_errors() {
         uri,
         Vec::new(),
         None,
-        Duration::from_secs(3),
-        None
+        Duration::from_secs(3).into(),
+        None,
+        usize::MAX
     ).await;
     
     // Should fail but not leak
@@ -55,8 +59,9 @@ async fn test_timeout_handles_connection_cleanup() {
         &client,
         uri,
         Vec::new(),
-        Duration::from_millis(100), // Very short timeout
-        None
+        Duration::from_millis(100).into(), // Very short timeout
+        None,
+        usize::MAX
     ).await;
     
     // Should timeout, but connection should be cleaned up
@@ -64,3 +69,162 @@ async fn test_timeout_handles_connection_cleanup() {
     let err_msg = result.unwrap_err().to_string();
     assert!(err_msg.contains("timeout") || err_msg.contains("Connect"));
 }
+
+/// Starts an origin serving a body of `len` bytes with Content-Length at `/sized`
+/// and an endless chunked body at `/endless`.
+async fn start_sized_origin(len: usize) -> String {
+    use axum::body::{Body, Bytes};
+    use axum::routing::get;
+
+    let router = axum::Router::new()
+        .route("/sized", get(move || async move { vec![b'x'; len] }))
+        .route(
+            "/endless",
+            get(|| async {
+                let chunk = Bytes::from_static(&[b'x'; 1024]);
+                Body::from_stream(futures::stream::repeat_with(move || Ok::<_, std::io::Error>(chunk.clone())))
+            }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn test_body_within_limit_is_collected() {
+    let origin = start_sized_origin(4096).await;
+    let uri: Uri = format!("{}/sized", origin).parse().unwrap();
+
+    let (status, _, body) = make_get_request(&create_client(), uri, Vec::new(), Duration::from_secs(3).into(), None, 4096)
+        .await
+        .unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(body.len(), 4096);
+}
+
+/// Test that the digest taken as the frames arrive is the xxh3 of the whole body.
+#[tokio::test]
+async fn test_collected_body_digest() {
+    let origin = start_sized_origin(70_000).await;
+    let uri: Uri = format!("{}/sized", origin).parse().unwrap();
+    let (_, _, body) = make_digested_get_request(&create_client(), uri, Vec::new(), Duration::from_secs(3).into(), None, 1 << 20)
+        .await
+        .unwrap();
+    assert_eq!(body.bytes.len(), 70_000);
+    assert_eq!(body.digest, xxhash_rust::xxh3::xxh3_64(&body.bytes));
+}
+
+#[tokio::test]
+async fn test_declared_length_over_limit_is_rejected() {
+    let origin = start_sized_origin(4097).await;
+    let uri: Uri = format!("{}/sized", origin).parse().unwrap();
+
+    let err = make_method_request(&create_client(), hyper::Method::GET, uri, Vec::new(), None, Duration::from_secs(3).into(), None, 4096)
+        .await
+        .unwrap_err();
+    assert!(is_response_too_large(&err), "unexpected error: {:#}", err);
+}
+
+#[tokio::test]
+async fn test_endless_body_is_aborted_at_limit() {
+    let origin = start_sized_origin(0).await;
+    let uri: Uri = format!("{}/endless", origin).parse().unwrap();
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        make_get_request(&create_client(), uri, Vec::new(), Duration::from_secs(3).into(), None, 64 * 1024),
+    )
+    .await
+    .expect("reading must stop once the limit is exceeded");
+    let err = result.unwrap_err();
+    assert!(is_response_too_large(&err), "unexpected error: {:#}", err);
+}
+
+/// Starts an origin that reads the request and writes `reply` (nothing when empty), then
+/// holds the connection open without writing anything more.
+async fn start_stalling_origin(reply: &'static [u8]) -> std::net::SocketAddr {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                if !reply.is_empty() {
+                    let _ = stream.write_all(reply).await;
+                }
+                tokio::time::sleep(Duration::from_secs(30)).await;
+            });
+        }
+    });
+    addr
+}
+
+fn timeouts(total: u64, ttfb: Option<u64>, body: Option<u64>) -> Timeouts {
+    Timeouts {
+        total: Duration::from_millis(total),
+        ttfb: ttfb.map(Duration::from_millis),
+        body: body.map(Duration::from_millis),
+    }
+}
+
+/// Runs a GET through `client` within `budgets`, expecting it to time out in `phase` well
+/// before the other budgets could fire.
+async fn assert_times_out_in(client: &HyperClient, uri: Uri, budgets: Timeouts, phase: TimeoutPhase) {
+    let started = std::time::Instant::now();
+    let err = make_get_request(client, uri, Vec::new(), budgets, None, usize::MAX).await.unwrap_err();
+    assert_eq!(timeout_phase(&err), Some(phase), "unexpected error: {:?}", err);
+    assert!(started.elapsed() < Duration::from_secs(1), "{:?} fired after {:?}", phase, started.elapsed());
+}
+
+/// Test that a TLS handshake the origin never answers fails on the connect timeout.
+#[tokio::test]
+async fn test_connect_timeout_fires_on_stalled_handshake() {
+    let addr = start_stalling_origin(b"").await;
+    let uri: Uri = format!("https://{}/slow", addr).parse().unwrap();
+    let client = create_client_with(Some(Duration::from_millis(100)));
+
+    assert_times_out_in(&client, uri, timeouts(3000, Some(2000), Some(2000)), TimeoutPhase::Connect).await;
+}
+
+/// Test that an origin accepting the request without ever answering fails on the TTFB timeout.
+#[tokio::test]
+async fn test_ttfb_timeout_fires_on_silent_origin() {
+    let addr = start_stalling_origin(b"").await;
+    let uri: Uri = format!("http://{}/slow", addr).parse().unwrap();
+
+    assert_times_out_in(&create_client(), uri, timeouts(3000, Some(100), Some(2000)), TimeoutPhase::Ttfb).await;
+}
+
+/// Test that a head arriving in time followed by a stalled body fails on the body timeout.
+#[tokio::test]
+async fn test_body_timeout_fires_on_stalled_body() {
+    let addr = start_stalling_origin(b"HTTP/1.1 200 OK\r\ncontent-length: 100\r\n\r\npartial").await;
+    let uri: Uri = format!("http://{}/slow", addr).parse().unwrap();
+
+    assert_times_out_in(&create_client(), uri, timeouts(3000, Some(2000), Some(100)), TimeoutPhase::Body).await;
+}
+
+/// Test that without phase budgets the umbrella timeout still caps the wait for the head, and
+/// a body is read as long as it takes.
+#[tokio::test]
+async fn test_total_timeout_without_phase_budgets() {
+    let addr = start_stalling_origin(b"").await;
+    let uri: Uri = format!("http://{}/slow", addr).parse().unwrap();
+    assert_times_out_in(&create_client(), uri, timeouts(100, None, None), TimeoutPhase::Total).await;
+
+    let addr = start_stalling_origin(b"HTTP/1.1 200 OK\r\ncontent-length: 100\r\n\r\npartial").await;
+    let uri: Uri = format!("http://{}/slow", addr).parse().unwrap();
+    let stalled = tokio::time::timeout(
+        Duration::from_millis(500),
+        make_get_request(&create_client(), uri, Vec::new(), timeouts(100, None, None), None, usize::MAX),
+    )
+    .await;
+    assert!(stalled.is_err(), "the umbrella timeout must not cut the body read");
+}
@@ -0,0 +1,56 @@
+//! Identity hashes of origin responses, telling a refresh that brought nothing new.
+//!
+//! The hash covers the status, the stored headers and the body decoded to identity, so an
+//! answer stored gzip encoded and the same content answered as identity hash alike. Headers
+//! that vary per answer or with the stored coding are left out: `Date`, `Age`, `Expires`,
+//! `Content-Encoding` and `Content-Length`. A refresh whose hash matches the resident entry's
+//! only touches its refresh time instead of swapping the payload.
+
+use xxhash_rust::xxh3::{xxh3_64, Xxh3};
+
+use crate::model::Entry;
+use crate::upstream::encoding;
+
+/// Response headers not hashed: they change per answer or with the stored coding.
+const IGNORED_HEADERS: [&str; 5] = ["date", "age", "expires", "content-encoding", "content-length"];
+
+/// Identity hash of a response whose body decoded to identity has the xxh3 `body_digest`.
+pub fn identity_hash<'a>(status: u16, headers: impl IntoIterator<Item = (&'a [u8], &'a [u8])>, body_digest: u64) -> u64 {
+    let mut hasher = Xxh3::new();
+    hasher.update(&status.to_le_bytes());
+    for (name, value) in headers {
+        if IGNORED_HEADERS.iter().any(|h| name.eq_ignore_ascii_case(h.as_bytes())) {
+            continue;
+        }
+        // Lengths first, so that no two header lists hash the same bytes.
+        hasher.update(&(name.len() as u32).to_le_bytes());
+        hasher.update(&name.to_ascii_lowercase());
+        hasher.update(&(value.len() as u32).to_le_bytes());
+        hasher.update(value);
+    }
+    hasher.update(&body_digest.to_le_bytes());
+    hasher.digest()
+}
+
+/// Identity hash of the response stored in the entry, decoded first when stored encoded.
+/// Taken once per payload; `None` when the payload does not decode.
+pub fn stored_identity_hash(entry: &Entry) -> Option<u64> {
+    entry.identity_hash_with(|entry| {
+        let response = entry.response_payload().ok()?;
+        let coding = response
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(b"content-encoding"))
+            .and_then(|(_, value)| std::str::from_utf8(value).ok())
+            .map(str::trim)
+            .filter(|coding| !coding.is_empty() && !coding.eq_ignore_ascii_case("identity"));
+        let body_digest = match coding {
+            // Checked against `backend.max_response_size` when it was fetched; the bound only
+            // stands for the u32 offsets of a payload.
+            Some(coding) => xxh3_64(&encoding::decode(coding, response.body, u32::MAX as usize).ok()?),
+            None => xxh3_64(&response.body),
+        };
+        let headers = response.headers.iter().map(|(k, v)| (k.as_slice(), v.as_slice()));
+        Some(identity_hash(response.code, headers, body_digest))
+    })
+}
@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use xxhash_rust::xxh3::xxh3_64;
+
+    use crate::config::{self, ConfigTrait};
+    use crate::model::{Entry, Response};
+    use crate::upstream::digest::{identity_hash, stored_identity_hash};
+
+    fn entry(headers: &[(&str, &str)], body: &[u8]) -> Entry {
+        let rule = config::new_test_config().rule("/api/v1/user").unwrap();
+        let entry = Entry::new(rule, &[], &[]);
+        let response = Response {
+            status: 200,
+            headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            body: body.to_vec(),
+        };
+        entry.set_payload(&[], &[], &response);
+        entry
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap()
+    }
+
+    /// Test that the stored coding and the per-answer headers do not change the identity hash,
+    /// while another header or another body do, and that a body that does not decode has none.
+    #[test]
+    fn test_identity_hash_of_stored_responses() {
+        let plain = stored_identity_hash(&entry(&[("Content-Type", "text/html"), ("Date", "Mon")], b"page"));
+        let encoded = stored_identity_hash(&entry(
+            &[("content-type", "text/html"), ("Content-Encoding", "gzip"), ("Content-Length", "24"), ("Date", "Tue")],
+            &gzip(b"page"),
+        ));
+        assert!(plain.is_some());
+        assert_eq!(plain, encoded);
+
+        assert_ne!(plain, stored_identity_hash(&entry(&[("Content-Type", "text/plain")], b"page")));
+        assert_ne!(plain, stored_identity_hash(&entry(&[("Content-Type", "text/html")], b"page2")));
+        assert_eq!(stored_identity_hash(&entry(&[("Content-Encoding", "gzip")], b"not gzip")), None);
+    }
+
+    /// Test that a hash seeded from the digest of the body as received is the one the stored
+    /// payload hashes to, and is kept with the payload.
+    #[test]
+    fn test_seeded_hash_matches_stored() {
+        let headers = [("Content-Type", "text/html"), ("Cache-Control", "max-age=60")];
+        let stored = entry(&headers, b"page");
+        let seeded = entry(&headers, b"page");
+        let pairs = headers.iter().map(|(k, v)| (k.as_bytes(), v.as_bytes()));
+        seeded.set_identity_hash(identity_hash(200, pairs, xxh3_64(b"page")));
+
+        assert_eq!(stored_identity_hash(&seeded), stored_identity_hash(&stored));
+        assert_eq!(seeded.identity_hash_with(|_| None), stored_identity_hash(&stored));
+    }
+}
@@ -0,0 +1,53 @@
+//! Draining backends for origin maintenance.
+//
+// A drained backend stays in the config and keeps being health-checked, but is sent no new
+// requests: with it being the only backend, fills fail fast while cached entries keep being
+// served. The state is recorded in the runtime overrides file (`runtime.overrides`), when one is
+// configured, so a restart in the middle of an origin deploy does not undo it.
+
+use std::path::Path;
+
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::config::overrides::RuntimeOverrides;
+use crate::upstream::Upstream;
+
+/// Drains or undrains the backend. The overrides file is written first: when it cannot be, the
+/// error is returned and the backend is left as it was.
+pub fn set_drained(backend: &dyn Upstream, drained: bool, overrides: Option<&Path>) -> Result<()> {
+    if let Some(path) = overrides {
+        let id = backend.backend_id().to_string();
+        RuntimeOverrides::update(path, |o| {
+            if drained {
+                o.drained_backends.insert(id);
+            } else {
+                o.drained_backends.remove(&id);
+            }
+        })?;
+    }
+    backend.set_drained(drained);
+    Ok(())
+}
+
+/// Drains the backend again if it was drained when the process stopped.
+pub fn restore(backend: &dyn Upstream, overrides: &Path) {
+    match RuntimeOverrides::load(overrides) {
+        Ok(o) if o.drained_backends.contains(backend.backend_id()) => {
+            backend.set_drained(true);
+            info!(
+                component = "upstream",
+                event = "backend_drain_restored",
+                backend = backend.backend_id(),
+                "backend is still drained from before the restart"
+            );
+        }
+        Ok(_) => {}
+        Err(e) => warn!(
+            component = "upstream",
+            event = "runtime_overrides_unreadable",
+            error = %format!("{:#}", e),
+            "runtime overrides not applied"
+        ),
+    }
+}
@@ -0,0 +1,186 @@
+//! Content-coding negotiation with the origin on cache fills and refreshes.
+//!
+//! With `backend.accept_encoding` set, fills and refreshes ask the origin for the configured
+//! codings instead of forwarding the client's `Accept-Encoding`; proxied requests keep the
+//! client's header. The answer is then stored in a form every client sharing the entry reads:
+//!
+//! | origin answers                | key has an `Accept-Encoding` accepting the coding and the rule keeps `Content-Encoding` | otherwise              |
+//! |-------------------------------|--------------------------------------------|------------------------------|
+//! | identity (header ignored)     | stored as is                               | stored as is                 |
+//! | gzip, deflate or br           | stored encoded, `Content-Encoding` kept    | decoded, stored as identity  |
+//!
+//! The client's `Accept-Encoding` only reaches the fill when it is a key header, so an encoded
+//! entry is never shared with a client that did not ask for that coding. Decoding is bounded by
+//! `backend.max_response_size`. `br` needs the `compression-brotli` feature.
+
+use std::io::Read;
+
+use anyhow::{bail, Context, Result};
+
+use crate::config::Rule;
+use crate::upstream::backend::UpstreamError;
+
+/// Codings the cache can decode: the only ones `backend.accept_encoding` may ask for.
+#[cfg(feature = "compression-brotli")]
+pub const SUPPORTED_CODINGS: &[&str] = &["gzip", "deflate", "br"];
+/// Codings the cache can decode: the only ones `backend.accept_encoding` may ask for.
+#[cfg(not(feature = "compression-brotli"))]
+pub const SUPPORTED_CODINGS: &[&str] = &["gzip", "deflate"];
+
+const ACCEPT_ENCODING: &str = "accept-encoding";
+const CONTENT_ENCODING: &str = "content-encoding";
+const CONTENT_LENGTH: &str = "content-length";
+
+/// Codings of an `Accept-Encoding` value with their q-values, names lowercased.
+fn codings(value: &str) -> impl Iterator<Item = (String, f32)> + '_ {
+    value.split(',').filter_map(|part| {
+        let mut params = part.split(';');
+        let name = params.next()?.trim().to_ascii_lowercase();
+        if name.is_empty() {
+            return None;
+        }
+        let q = params
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        Some((name, q))
+    })
+}
+
+/// Checks that a `backend.accept_encoding` value lists decodable codings only.
+pub fn validate(value: &str) -> Result<()> {
+    if codings(value).next().is_none() {
+        bail!("no coding in backend.accept_encoding {:?}", value);
+    }
+    for (coding, _) in codings(value) {
+        if coding == "br" && !cfg!(feature = "compression-brotli") {
+            bail!("coding \"br\" in backend.accept_encoding needs advcache built with the `compression-brotli` feature");
+        }
+        if coding != "identity" && !SUPPORTED_CODINGS.contains(&coding.as_str()) {
+            bail!(
+                "unsupported coding {:?} in backend.accept_encoding (supported: {})",
+                coding,
+                SUPPORTED_CODINGS.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Whether an `Accept-Encoding` value accepts `coding`, by name or through `*`.
+pub fn accepts(accept_encoding: &str, coding: &str) -> bool {
+    let coding = if coding == "x-gzip" { "gzip" } else { coding };
+    let mut wildcard = None;
+    for (name, q) in codings(accept_encoding) {
+        let name = if name == "x-gzip" { "gzip" } else { name.as_str() };
+        if name == coding {
+            return q > 0.0;
+        }
+        if name == "*" {
+            wildcard = Some(q > 0.0);
+        }
+    }
+    wildcard.unwrap_or(false)
+}
+
+/// Replaces any `Accept-Encoding` of an outgoing request with `value`.
+pub fn override_accept_encoding(headers: &mut Vec<(String, String)>, value: &str) {
+    headers.retain(|(k, _)| !k.eq_ignore_ascii_case(ACCEPT_ENCODING));
+    headers.push(("Accept-Encoding".to_string(), value.to_string()));
+}
+
+/// The client's `Accept-Encoding` among the headers a fill is made with.
+pub fn client_accept_encoding(headers: &[(Vec<u8>, Vec<u8>)]) -> Option<String> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(ACCEPT_ENCODING.as_bytes()))
+        .and_then(|(_, v)| std::str::from_utf8(v).ok())
+        .map(str::to_string)
+}
+
+/// Whether the rule stores `Content-Encoding`: it lists it, or keeps every header.
+fn keeps_content_encoding(rule: &Rule) -> bool {
+    match rule.cache_value.headers_map.as_ref().filter(|m| !m.is_empty()) {
+        Some(allowed) => allowed.iter().any(|h| h.eq_ignore_ascii_case(CONTENT_ENCODING)),
+        None => true,
+    }
+}
+
+/// Brings an origin answer to the form it is stored in (see the module docs). `coding` is the
+/// origin's `Content-Encoding`; `headers` are the response headers already filtered by the rule.
+pub fn stored_form(
+    rule: &Rule,
+    client_accept_encoding: Option<&str>,
+    coding: Option<&str>,
+    headers: &mut Vec<(String, String)>,
+    body: Vec<u8>,
+    max_body: usize,
+) -> Result<Vec<u8>> {
+    let coding = coding.map(|c| c.trim().to_ascii_lowercase()).unwrap_or_default();
+    if coding.is_empty() || coding == "identity" {
+        return Ok(body);
+    }
+    if keeps_content_encoding(rule) && client_accept_encoding.is_some_and(|ae| accepts(ae, &coding)) {
+        return Ok(body);
+    }
+
+    let body = decode(&coding, body, max_body)?;
+    headers.retain(|(k, _)| !k.eq_ignore_ascii_case(CONTENT_ENCODING));
+    for (k, v) in headers.iter_mut() {
+        if k.eq_ignore_ascii_case(CONTENT_LENGTH) {
+            *v = body.len().to_string();
+        }
+    }
+    Ok(body)
+}
+
+/// Decoded length of a body encoded with `coding`, counted up to `limit + 1` bytes so an
+/// oversized body is told without being decoded whole. A body that fails to decode counts as
+/// long as it is.
+pub fn decoded_len(coding: Option<&str>, body: &[u8], limit: usize) -> usize {
+    let mut reader: Box<dyn Read + '_> = Box::new(body);
+    let mut encoded = false;
+    for (name, _) in codings(coding.unwrap_or_default()).collect::<Vec<_>>().into_iter().rev() {
+        reader = match name.as_str() {
+            "identity" => continue,
+            "gzip" | "x-gzip" => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            "deflate" => Box::new(flate2::read::ZlibDecoder::new(reader)),
+            #[cfg(feature = "compression-brotli")]
+            "br" => Box::new(brotli::Decompressor::new(reader, 4096)),
+            _ => return body.len(),
+        };
+        encoded = true;
+    }
+    if !encoded {
+        return body.len();
+    }
+    match std::io::copy(&mut reader.take(limit as u64 + 1), &mut std::io::sink()) {
+        Ok(decoded) => decoded as usize,
+        Err(_) => body.len(),
+    }
+}
+
+/// Decodes a body encoded with `coding` (stacked codings in the order they were applied).
+pub fn decode(coding: &str, mut body: Vec<u8>, max_body: usize) -> Result<Vec<u8>> {
+    let applied: Vec<String> = codings(coding).map(|(name, _)| name).collect();
+    for name in applied.iter().rev() {
+        let reader: Box<dyn Read + '_> = match name.as_str() {
+            "identity" => continue,
+            "gzip" | "x-gzip" => Box::new(flate2::read::MultiGzDecoder::new(body.as_slice())),
+            "deflate" => Box::new(flate2::read::ZlibDecoder::new(body.as_slice())),
+            #[cfg(feature = "compression-brotli")]
+            "br" => Box::new(brotli::Decompressor::new(body.as_slice(), 4096)),
+            other => bail!("cannot decode upstream content-encoding {:?}", other),
+        };
+        let mut decoded = Vec::with_capacity((body.len() * 4).min(max_body));
+        reader
+            .take(max_body as u64 + 1)
+            .read_to_end(&mut decoded)
+            .with_context(|| format!("decode {} upstream response", name))?;
+        if decoded.len() > max_body {
+            return Err(UpstreamError::ResponseTooLarge { limit: max_body }.into());
+        }
+        body = decoded;
+    }
+    Ok(body)
+}
@@ -0,0 +1,114 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::config::{self, ConfigTrait};
+    use crate::upstream::backend_hyper_impl::is_response_too_large;
+    use crate::upstream::encoding::{accepts, decode, decoded_len, override_accept_encoding, stored_form, validate};
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap()
+    }
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    /// Test that only decodable codings may be requested from the origin.
+    #[test]
+    fn test_validate() {
+        assert_eq!(validate("gzip, br").is_ok(), cfg!(feature = "compression-brotli"));
+        assert!(validate("gzip;q=1.0, deflate;q=0.5, identity").is_ok());
+        assert!(validate("gzip, zstd").is_err());
+        assert!(validate(" , ").is_err());
+    }
+
+    /// Test that `br` is refused without the `compression-brotli` feature, naming it.
+    #[cfg(not(feature = "compression-brotli"))]
+    #[test]
+    fn test_validate_br_needs_feature() {
+        let err = validate("br").unwrap_err().to_string();
+        assert!(err.contains("`compression-brotli`"), "{}", err);
+        assert!(decode("br", b"abc".to_vec(), 4096).is_err());
+    }
+
+    /// Test coding acceptance by name, q=0 refusals and the wildcard.
+    #[test]
+    fn test_accepts() {
+        assert!(accepts("gzip, deflate, br", "gzip"));
+        assert!(accepts("GZIP;q=0.8", "gzip"));
+        assert!(accepts("x-gzip", "gzip"));
+        assert!(!accepts("gzip;q=0", "gzip"));
+        assert!(!accepts("deflate", "gzip"));
+        assert!(accepts("*", "br"));
+        assert!(!accepts("*, br;q=0", "br"));
+    }
+
+    /// Test that the configured value replaces the client's header whatever its case.
+    #[test]
+    fn test_override_accept_encoding() {
+        let mut h = headers(&[("accept-encoding", "identity"), ("Accept", "*/*")]);
+        override_accept_encoding(&mut h, "gzip, br");
+        assert_eq!(h, headers(&[("Accept", "*/*"), ("Accept-Encoding", "gzip, br")]));
+    }
+
+    /// Test that an accepting client keyed on Accept-Encoding gets the answer stored encoded.
+    #[test]
+    fn test_stored_encoded_when_client_accepts() {
+        let rule = config::new_test_config().rule("/api/v1/user").unwrap();
+        let body = gzip(b"{\"id\":1}");
+        let mut h = headers(&[("Content-Encoding", "gzip")]);
+        let stored = stored_form(&rule, Some("gzip, br"), Some("gzip"), &mut h, body.clone(), 1 << 20).unwrap();
+        assert_eq!(stored, body);
+        assert_eq!(h, headers(&[("Content-Encoding", "gzip")]));
+    }
+
+    /// Test that an answer the client did not ask for is stored decoded, with its length fixed.
+    #[test]
+    fn test_stored_decoded_otherwise() {
+        let rule = config::new_test_config().rule("/api/v1/user").unwrap();
+        let mut h = headers(&[("Content-Encoding", "gzip"), ("Content-Length", "31")]);
+        let stored = stored_form(&rule, None, Some("gzip"), &mut h, gzip(b"{\"id\":1}"), 1 << 20).unwrap();
+        assert_eq!(stored, b"{\"id\":1}");
+        assert_eq!(h, headers(&[("Content-Length", "8")]));
+
+        let mut h = headers(&[("Content-Encoding", "gzip")]);
+        let stored = stored_form(&rule, Some("br"), Some("gzip"), &mut h, gzip(b"{}"), 1 << 20).unwrap();
+        assert_eq!(stored, b"{}");
+        assert!(h.is_empty());
+    }
+
+    /// Test that an origin ignoring Accept-Encoding has its identity answer stored untouched.
+    #[test]
+    fn test_identity_answer_is_stored_as_is() {
+        let rule = config::new_test_config().rule("/api/v1/user").unwrap();
+        let mut h = headers(&[("Content-Type", "application/json")]);
+        let stored = stored_form(&rule, None, None, &mut h, b"{}".to_vec(), 1 << 20).unwrap();
+        assert_eq!(stored, b"{}");
+        assert_eq!(h.len(), 1);
+    }
+
+    /// Test that decoding stops at the response size limit and rejects unknown codings.
+    #[test]
+    fn test_decode_limits() {
+        let bomb = gzip(&vec![b'x'; 1 << 20]);
+        let err = decode("gzip", bomb, 4096).unwrap_err();
+        assert!(is_response_too_large(&err));
+        assert!(decode("zstd", b"abc".to_vec(), 4096).is_err());
+        assert!(decode("gzip", b"not gzip".to_vec(), 4096).is_err());
+    }
+
+    /// Test that the decoded length is counted through stacked codings and stops past the limit.
+    #[test]
+    fn test_decoded_len() {
+        let body = vec![b'x'; 10_000];
+        assert_eq!(decoded_len(None, &body, 100), 10_000);
+        assert_eq!(decoded_len(Some("identity"), &body, 100), 10_000);
+        assert_eq!(decoded_len(Some("gzip"), &gzip(&body), 20_000), 10_000);
+        assert_eq!(decoded_len(Some("gzip"), &gzip(&body), 100), 101);
+        assert_eq!(decoded_len(Some("gzip, gzip"), &gzip(&gzip(&body)), 20_000), 10_000);
+        assert_eq!(decoded_len(Some("gzip"), b"not gzip", 100), 8);
+    }
+}
@@ -0,0 +1,108 @@
+//! Up/down state of the health checks of a backend.
+//!
+//! The shallow check probes `healthcheck` (`/healthz`), the deep one sends the synthetic ruled
+//! request of `health_synthetic`. Each flips its own state after enough results in a row, and
+//! the backend is up while every check it runs is.
+
+use serde::Serialize;
+
+/// Header marking the synthetic requests of the deep check, for the origin to tell them apart.
+pub const SYNTHETIC_HEADER: &str = "x-advcache-synthetic";
+
+/// Failed probes in a row marking the shallow check down.
+pub const SHALLOW_FAIL_THRESHOLD: u32 = 3;
+/// Passing probes in a row bringing the shallow check back up.
+pub const SHALLOW_OK_THRESHOLD: u32 = 3;
+
+/// Kind of health check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// `healthcheck`, e.g. `/healthz`.
+    Shallow,
+    /// The synthetic request of `health_synthetic`.
+    Deep,
+}
+
+impl Check {
+    pub fn label(self) -> &'static str {
+        match self {
+            Check::Shallow => "shallow",
+            Check::Deep => "deep",
+        }
+    }
+}
+
+/// State of one check as shown by the admin API.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckStatus {
+    /// State decided by the thresholds, not by the last probe alone.
+    pub up: bool,
+    /// Whether the last probe passed; `None` before the first one.
+    pub last_ok: Option<bool>,
+    pub consecutive_failures: u32,
+    /// Error of the last failed probe.
+    pub last_error: Option<String>,
+}
+
+/// Both checks of a backend; `deep` only with `health_synthetic`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthChecks {
+    pub shallow: CheckStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deep: Option<CheckStatus>,
+}
+
+/// Threshold state machine of one check.
+#[derive(Debug, Clone)]
+pub struct Tracker {
+    status: CheckStatus,
+    oks: u32,
+    fail_threshold: u32,
+    ok_threshold: u32,
+}
+
+impl Tracker {
+    /// A check starting up, as the backend does.
+    pub fn new(fail_threshold: u32, ok_threshold: u32) -> Self {
+        Self {
+            status: CheckStatus { up: true, last_ok: None, consecutive_failures: 0, last_error: None },
+            oks: 0,
+            fail_threshold: fail_threshold.max(1),
+            ok_threshold: ok_threshold.max(1),
+        }
+    }
+
+    /// Takes the result of a probe; returns the new state when it flipped.
+    pub fn observe(&mut self, result: Result<(), String>) -> Option<bool> {
+        let status = &mut self.status;
+        status.last_ok = Some(result.is_ok());
+        match result {
+            Err(error) => {
+                self.oks = 0;
+                status.consecutive_failures += 1;
+                status.last_error = Some(error);
+                if status.up && status.consecutive_failures >= self.fail_threshold {
+                    status.up = false;
+                    return Some(false);
+                }
+            }
+            Ok(()) => {
+                status.consecutive_failures = 0;
+                if status.up {
+                    return None;
+                }
+                self.oks += 1;
+                if self.oks >= self.ok_threshold {
+                    self.oks = 0;
+                    status.up = true;
+                    return Some(true);
+                }
+            }
+        }
+        None
+    }
+
+    pub fn status(&self) -> &CheckStatus {
+        &self.status
+    }
+}
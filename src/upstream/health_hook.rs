@@ -0,0 +1,237 @@
+//! Backend health transition notifications.
+//!
+//! Transitions are logged as a Kubernetes-style event and handed to a background sender,
+//! so the health observer never waits on the webhook. Deliveries are spaced by
+//! `min_interval`: transitions arriving in between are coalesced into one notification,
+//! which keeps a flapping origin from flooding the receiver.
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use hyper::{Method, Uri};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, warn};
+
+use crate::config::HealthHook;
+use crate::dedlog::redacted;
+use crate::http::client::{create_client, HyperClient};
+use crate::upstream::backend_hyper_impl::make_method_request;
+
+/// Stable `event` field value of the health transition log line, for log based alerting.
+pub const EVENT_BACKEND_HEALTH_CHANGED: &str = "backend_health_changed";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(10);
+const DELIVERY_ATTEMPTS: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+/// Webhook answers are only checked for their status, the body is never needed.
+const MAX_WEBHOOK_RESPONSE_SIZE: usize = 64 << 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthState {
+    Up,
+    Down,
+}
+
+/// Webhook payload describing a health transition.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthEvent {
+    pub backend_id: String,
+    pub old_state: HealthState,
+    pub new_state: HealthState,
+    /// Failed probes in a row that led to the transition (0 when going up).
+    pub consecutive_failures: u32,
+    /// Last probe error seen before the transition.
+    pub last_error: Option<String>,
+    /// RFC 3339 time of the (latest) transition.
+    pub timestamp: String,
+    /// Transitions folded into this notification by rate limiting.
+    pub coalesced: u32,
+}
+
+impl HealthEvent {
+    pub fn new(
+        backend_id: &str,
+        up: bool,
+        consecutive_failures: u32,
+        last_error: Option<String>,
+    ) -> Self {
+        let (old_state, new_state) = if up {
+            (HealthState::Down, HealthState::Up)
+        } else {
+            (HealthState::Up, HealthState::Down)
+        };
+        Self {
+            backend_id: backend_id.to_string(),
+            old_state,
+            new_state,
+            consecutive_failures,
+            last_error,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            coalesced: 0,
+        }
+    }
+
+    /// Merges a later transition: the state it started from is kept, the rest is taken from `next`.
+    fn coalesce(self, next: HealthEvent) -> HealthEvent {
+        HealthEvent {
+            old_state: self.old_state,
+            coalesced: self.coalesced + next.coalesced + 1,
+            ..next
+        }
+    }
+
+    /// Writes the transition as a Kubernetes-style event (type, reason, involved object).
+    pub fn log(&self) {
+        match self.new_state {
+            HealthState::Down => error!(
+                component = "upstream",
+                event = EVENT_BACKEND_HEALTH_CHANGED,
+                event_type = "Warning",
+                reason = "BackendDown",
+                involved_object = %self.backend_id,
+                old_state = "up",
+                new_state = "down",
+                consecutive_failures = self.consecutive_failures,
+                last_error = self.last_error.as_deref().unwrap_or_default(),
+                "clients pool is down for upstream"
+            ),
+            HealthState::Up => warn!(
+                component = "upstream",
+                event = EVENT_BACKEND_HEALTH_CHANGED,
+                event_type = "Normal",
+                reason = "BackendUp",
+                involved_object = %self.backend_id,
+                old_state = "down",
+                new_state = "up",
+                consecutive_failures = self.consecutive_failures,
+                "clients pool is upped for upstream"
+            ),
+        }
+    }
+}
+
+/// Fire-and-forget sender of health transitions to the configured webhook.
+pub struct HealthNotifier {
+    tx: mpsc::UnboundedSender<HealthEvent>,
+}
+
+impl HealthNotifier {
+    /// Starts the sender task. Returns `None` when the hook has no webhook URL.
+    pub fn spawn(shutdown_token: CancellationToken, hook: &HealthHook) -> Result<Option<Self>> {
+        let Some(url) = hook.webhook_url.as_deref().filter(|u| !u.is_empty()) else {
+            return Ok(None);
+        };
+        let uri: Uri = url
+            .parse()
+            .with_context(|| format!("invalid on_health_change.webhook_url: {}", redacted(url)))?;
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let sender = Sender {
+            client: create_client(),
+            uri,
+            timeout: hook.timeout.unwrap_or(DEFAULT_TIMEOUT),
+            min_interval: hook.min_interval.unwrap_or(DEFAULT_MIN_INTERVAL),
+        };
+        tokio::task::spawn(sender.run(shutdown_token, rx));
+
+        Ok(Some(Self { tx }))
+    }
+
+    /// Queues the transition for delivery without waiting.
+    pub fn notify(&self, event: HealthEvent) {
+        let _ = self.tx.send(event);
+    }
+}
+
+struct Sender {
+    client: HyperClient,
+    uri: Uri,
+    timeout: Duration,
+    min_interval: Duration,
+}
+
+impl Sender {
+    async fn run(self, shutdown_token: CancellationToken, mut rx: mpsc::UnboundedReceiver<HealthEvent>) {
+        let mut last_sent: Option<Instant> = None;
+
+        loop {
+            let mut event = tokio::select! {
+                _ = shutdown_token.cancelled() => return,
+                next = rx.recv() => match next {
+                    Some(event) => event,
+                    None => return,
+                },
+            };
+
+            if let Some(sent_at) = last_sent {
+                let ready_at = sent_at + self.min_interval;
+                loop {
+                    tokio::select! {
+                        _ = shutdown_token.cancelled() => return,
+                        _ = tokio::time::sleep_until(ready_at) => break,
+                        next = rx.recv() => match next {
+                            Some(next) => event = event.coalesce(next),
+                            None => break,
+                        },
+                    }
+                }
+            }
+
+            tokio::select! {
+                _ = shutdown_token.cancelled() => return,
+                _ = self.deliver(&event) => {}
+            }
+            last_sent = Some(Instant::now());
+        }
+    }
+
+    /// Posts the event, retrying with a growing backoff until a 2xx answer or attempts run out.
+    async fn deliver(&self, event: &HealthEvent) {
+        let body = match serde_json::to_vec(event) {
+            Ok(body) => Bytes::from(body),
+            Err(e) => {
+                error!(component = "upstream", event = "health_hook_failed", error = %e, "encode health hook payload");
+                return;
+            }
+        };
+
+        let mut last_err = String::new();
+        for attempt in 0..DELIVERY_ATTEMPTS {
+            if attempt > 0 {
+                tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(attempt - 1)).await;
+            }
+
+            match make_method_request(
+                &self.client,
+                Method::POST,
+                self.uri.clone(),
+                vec![("content-type", "application/json")],
+                Some(body.clone()),
+                self.timeout.into(),
+                None,
+                MAX_WEBHOOK_RESPONSE_SIZE,
+            )
+            .await
+            {
+                Ok((status, _, _)) if (200..300).contains(&status) => return,
+                Ok((status, _, _)) => last_err = format!("webhook answered {}", status),
+                Err(e) => last_err = format!("{:#}", e),
+            }
+        }
+
+        warn!(
+            component = "upstream",
+            event = "health_hook_failed",
+            backend_id = %event.backend_id,
+            url = %redacted(&self.uri.to_string()),
+            attempts = DELIVERY_ATTEMPTS,
+            error = %last_err,
+            "health change webhook was not delivered"
+        );
+    }
+}
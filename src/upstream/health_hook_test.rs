@@ -0,0 +1,180 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use axum::http::StatusCode;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use tokio_util::sync::CancellationToken;
+
+    use crate::config::{self, HealthHook};
+    use crate::upstream::health_hook::{HealthEvent, HealthNotifier, HealthState};
+    use crate::upstream::BackendImpl;
+
+    /// Local webhook receiver; it can also serve a switchable /healthz for a backend under test.
+    #[derive(Clone, Default)]
+    struct Receiver {
+        payloads: Arc<Mutex<Vec<HealthEvent>>>,
+        calls: Arc<AtomicUsize>,
+        fail_first: Arc<AtomicUsize>,
+        healthy: Arc<AtomicBool>,
+    }
+
+    impl Receiver {
+        async fn start(&self) -> String {
+            let hook = self.clone();
+            let health = self.clone();
+            let router = Router::new()
+                .route(
+                    "/hook",
+                    post(move |Json(event): Json<HealthEvent>| {
+                        let hook = hook.clone();
+                        async move {
+                            hook.calls.fetch_add(1, Ordering::SeqCst);
+                            if hook.fail_first.load(Ordering::SeqCst) > 0 {
+                                hook.fail_first.fetch_sub(1, Ordering::SeqCst);
+                                return StatusCode::INTERNAL_SERVER_ERROR;
+                            }
+                            hook.payloads.lock().unwrap().push(event);
+                            StatusCode::OK
+                        }
+                    }),
+                )
+                .route(
+                    "/healthz",
+                    get(move || {
+                        let healthy = health.healthy.load(Ordering::SeqCst);
+                        async move {
+                            if healthy {
+                                StatusCode::OK
+                            } else {
+                                StatusCode::SERVICE_UNAVAILABLE
+                            }
+                        }
+                    }),
+                );
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap().to_string();
+            tokio::spawn(async move {
+                axum::serve(listener, router).await.unwrap();
+            });
+            addr
+        }
+
+        fn payloads(&self) -> Vec<HealthEvent> {
+            self.payloads.lock().unwrap().clone()
+        }
+
+        async fn wait_for(&self, n: usize, deadline: Duration) -> Vec<HealthEvent> {
+            let until = tokio::time::Instant::now() + deadline;
+            while self.payloads().len() < n && tokio::time::Instant::now() < until {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            self.payloads()
+        }
+    }
+
+    fn hook(addr: &str, min_interval: Duration) -> HealthHook {
+        HealthHook {
+            webhook_url: Some(format!("http://{}/hook", addr)),
+            timeout: Some(Duration::from_secs(1)),
+            min_interval: Some(min_interval),
+        }
+    }
+
+    /// Test that no sender is started without a webhook URL.
+    #[tokio::test]
+    async fn test_notifier_disabled_without_url() {
+        let hook = HealthHook {
+            webhook_url: None,
+            timeout: None,
+            min_interval: None,
+        };
+        assert!(HealthNotifier::spawn(CancellationToken::new(), &hook).unwrap().is_none());
+    }
+
+    /// Test that transitions within min_interval are coalesced into one delivery with the latest state.
+    #[tokio::test]
+    async fn test_notifier_rate_limits_flaps() {
+        let receiver = Receiver::default();
+        let addr = receiver.start().await;
+        let shutdown = CancellationToken::new();
+        let notifier = HealthNotifier::spawn(shutdown.clone(), &hook(&addr, Duration::from_millis(400)))
+            .unwrap()
+            .unwrap();
+
+        notifier.notify(HealthEvent::new("b1", false, 3, Some("boom".to_string())));
+        receiver.wait_for(1, Duration::from_secs(2)).await;
+        notifier.notify(HealthEvent::new("b1", true, 0, None));
+        notifier.notify(HealthEvent::new("b1", false, 3, Some("boom again".to_string())));
+        notifier.notify(HealthEvent::new("b1", true, 0, None));
+
+        let payloads = receiver.wait_for(2, Duration::from_secs(3)).await;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        shutdown.cancel();
+
+        assert_eq!(receiver.payloads().len(), 2, "{:?}", payloads);
+        assert_eq!(payloads[0].new_state, HealthState::Down);
+        assert_eq!(payloads[0].coalesced, 0);
+        assert_eq!(payloads[1].old_state, HealthState::Down);
+        assert_eq!(payloads[1].new_state, HealthState::Up);
+        assert_eq!(payloads[1].coalesced, 2);
+    }
+
+    /// Test that a failed delivery is retried.
+    #[tokio::test]
+    async fn test_notifier_retries_failed_delivery() {
+        let receiver = Receiver::default();
+        receiver.fail_first.store(1, Ordering::SeqCst);
+        let addr = receiver.start().await;
+        let shutdown = CancellationToken::new();
+        let notifier = HealthNotifier::spawn(shutdown.clone(), &hook(&addr, Duration::from_secs(1)))
+            .unwrap()
+            .unwrap();
+
+        notifier.notify(HealthEvent::new("b1", false, 3, None));
+        let payloads = receiver.wait_for(1, Duration::from_secs(3)).await;
+        shutdown.cancel();
+
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(receiver.calls.load(Ordering::SeqCst), 2);
+    }
+
+    /// Test that the backend health observer reports both transitions with failure details.
+    #[tokio::test]
+    async fn test_backend_reports_down_and_up_transitions() {
+        let receiver = Receiver::default();
+        receiver.healthy.store(true, Ordering::SeqCst);
+        let addr = receiver.start().await;
+
+        let mut backend_cfg = config::new_test_config().cache.upstream.unwrap().backend.unwrap();
+        backend_cfg.id = Some("hooked".to_string());
+        backend_cfg.host = Some(addr.clone());
+        backend_cfg.timeout = Some(Duration::from_millis(500));
+        backend_cfg.on_health_change = Some(hook(&addr, Duration::from_millis(100)));
+
+        let shutdown = CancellationToken::new();
+        let _backend = BackendImpl::new(shutdown.clone(), Some(backend_cfg)).unwrap();
+
+        receiver.healthy.store(false, Ordering::SeqCst);
+        let payloads = receiver.wait_for(1, Duration::from_secs(5)).await;
+        assert_eq!(payloads.len(), 1, "backend was not reported down");
+        let down = &payloads[0];
+        assert_eq!(down.backend_id, "hooked");
+        assert_eq!((down.old_state, down.new_state), (HealthState::Up, HealthState::Down));
+        assert_eq!(down.consecutive_failures, 3);
+        assert!(down.last_error.is_some());
+
+        receiver.healthy.store(true, Ordering::SeqCst);
+        let payloads = receiver.wait_for(2, Duration::from_secs(6)).await;
+        shutdown.cancel();
+        assert_eq!(payloads.len(), 2, "backend was not reported up");
+        let up = &payloads[1];
+        assert_eq!((up.old_state, up.new_state), (HealthState::Down, HealthState::Up));
+        assert_eq!(up.consecutive_failures, 0);
+        assert_eq!(up.coalesced, 0);
+    }
+}
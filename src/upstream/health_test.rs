@@ -0,0 +1,41 @@
+#[cfg(test)]
+mod tests {
+    use crate::upstream::health::Tracker;
+
+    fn fail() -> Result<(), String> {
+        Err("boom".to_string())
+    }
+
+    /// Test that a check goes down after the failures in a row of its threshold only, and that
+    /// a passing probe in between starts the count over.
+    #[test]
+    fn test_goes_down_after_fail_threshold() {
+        let mut tracker = Tracker::new(3, 2);
+        assert_eq!(tracker.observe(fail()), None);
+        assert_eq!(tracker.observe(fail()), None);
+        assert_eq!(tracker.observe(Ok(())), None);
+        assert_eq!(tracker.status().consecutive_failures, 0);
+
+        assert_eq!(tracker.observe(fail()), None);
+        assert_eq!(tracker.observe(fail()), None);
+        assert_eq!(tracker.observe(fail()), Some(false));
+        let status = tracker.status();
+        assert!(!status.up);
+        assert_eq!((status.last_ok, status.consecutive_failures), (Some(false), 3));
+        assert_eq!(status.last_error.as_deref(), Some("boom"));
+        assert_eq!(tracker.observe(fail()), None, "a down check does not flip again");
+    }
+
+    /// Test that a down check comes back up after the passing probes in a row of its threshold.
+    #[test]
+    fn test_comes_back_after_ok_threshold() {
+        let mut tracker = Tracker::new(1, 2);
+        assert_eq!(tracker.observe(fail()), Some(false));
+        assert_eq!(tracker.observe(Ok(())), None);
+        assert_eq!(tracker.observe(fail()), None);
+        assert_eq!(tracker.observe(Ok(())), None);
+        assert_eq!(tracker.observe(Ok(())), Some(true));
+        assert!(tracker.status().up);
+        assert_eq!(tracker.status().last_ok, Some(true));
+    }
+}
@@ -0,0 +1,67 @@
+//! Protection against requests looping back into the cache.
+//!
+//! Every upstream request carries `X-AdvCache-Loop` with the ids of the instances it has
+//! passed through, this one appended last. A request arriving with this instance's id in
+//! the header, or with too many hops, was sent by a cache in front of its own origin
+//! (typically `backend.host` pointing at the cache itself) and is refused with 508.
+
+use std::sync::OnceLock;
+
+/// Header listing the instances a request went through, comma separated.
+pub const LOOP_HEADER: &str = "x-advcache-loop";
+
+/// Largest number of instances a request may have passed before it is treated as a loop.
+pub const MAX_HOPS: usize = 8;
+
+/// Id of this instance: the pod name or hostname, falling back to the process id.
+pub fn instance_id() -> &'static str {
+    static ID: OnceLock<String> = OnceLock::new();
+    ID.get_or_init(|| {
+        ["POD_NAME", "HOSTNAME"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .chain(system_hostname())
+            .map(|id| id.trim().to_string())
+            .find(|id| !id.is_empty() && !id.contains(','))
+            .unwrap_or_else(|| format!("advcache-{}", std::process::id()))
+    })
+}
+
+/// Hostname reported by the OS.
+pub fn system_hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for its whole length and gethostname writes at most that much.
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if rc != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..len].to_vec()).ok()
+}
+
+/// Whether an inbound `X-AdvCache-Loop` value means the request is looping: it has
+/// already passed through this instance or through more than [`MAX_HOPS`] instances.
+pub fn is_loop(value: &str) -> bool {
+    let id = instance_id();
+    let mut hops = 0;
+    for hop in value.split(',').map(str::trim).filter(|hop| !hop.is_empty()) {
+        if hop == id {
+            return true;
+        }
+        hops += 1;
+    }
+    hops >= MAX_HOPS
+}
+
+/// Appends this instance to the `X-AdvCache-Loop` header of an outgoing request, adding
+/// the header when the request does not carry one yet.
+pub fn stamp(headers: &mut Vec<(String, String)>) {
+    match headers.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(LOOP_HEADER)) {
+        Some((_, value)) if !value.trim().is_empty() => {
+            value.push_str(", ");
+            value.push_str(instance_id());
+        }
+        Some((_, value)) => *value = instance_id().to_string(),
+        None => headers.push((LOOP_HEADER.to_string(), instance_id().to_string())),
+    }
+}
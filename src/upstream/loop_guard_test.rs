@@ -0,0 +1,30 @@
+#[cfg(test)]
+mod tests {
+    use crate::upstream::loop_guard::{instance_id, is_loop, stamp, LOOP_HEADER, MAX_HOPS};
+
+    /// Test that only a chain holding this instance or too many hops counts as a loop.
+    #[test]
+    fn test_is_loop() {
+        assert!(!is_loop(""));
+        assert!(!is_loop("edge-1, edge-2"));
+        assert!(is_loop(&format!("edge-1, {}", instance_id())));
+        assert!(is_loop(instance_id()));
+
+        let hops: Vec<String> = (0..MAX_HOPS).map(|i| format!("edge-{}", i)).collect();
+        assert!(!is_loop(&hops[1..].join(",")));
+        assert!(is_loop(&hops.join(",")));
+    }
+
+    /// Test that stamping adds the header once and appends to an existing chain.
+    #[test]
+    fn test_stamp() {
+        let mut headers = vec![("Accept".to_string(), "*/*".to_string())];
+        stamp(&mut headers);
+        assert_eq!(headers[1], (LOOP_HEADER.to_string(), instance_id().to_string()));
+
+        let mut headers = vec![("X-AdvCache-Loop".to_string(), "edge-1".to_string())];
+        stamp(&mut headers);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].1, format!("edge-1, {}", instance_id()));
+    }
+}
@@ -0,0 +1,119 @@
+//! Upstream misconfiguration: failures the backend config causes rather than the origin.
+//!
+//! A backend URL with a bad scheme, or a host that does not resolve or speak the configured
+//! protocol, fails every request the same way until the config is fixed. Those failures are
+//! told apart from refusals and timeouts of a running origin: the backend keeps the last one as
+//! its config error (admin API, `upstream_config_error` gauge) and failing misses are answered
+//! with `error_code: upstream_misconfigured`. Hits are served from the cache all along.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use hyper::Uri;
+
+use crate::upstream::backend::UpstreamError;
+
+/// What is wrong with the backend config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MisconfigKind {
+    /// The backend URL does not parse, or has a scheme other than http and https.
+    InvalidUri,
+    /// The backend host does not exist (NXDOMAIN); temporary resolution failures do not count.
+    Dns,
+    /// The TLS handshake failed: a plain HTTP origin behind `scheme: https`, or a certificate
+    /// that does not verify.
+    Tls,
+}
+
+impl MisconfigKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            MisconfigKind::InvalidUri => "invalid_uri",
+            MisconfigKind::Dns => "dns",
+            MisconfigKind::Tls => "tls",
+        }
+    }
+}
+
+/// A misconfiguration with the error text it was told by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misconfig {
+    pub kind: MisconfigKind,
+    pub detail: String,
+}
+
+impl Misconfig {
+    fn new(kind: MisconfigKind, detail: impl fmt::Display) -> Self {
+        Self { kind, detail: detail.to_string() }
+    }
+}
+
+impl fmt::Display for Misconfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind.label(), self.detail)
+    }
+}
+
+/// Checks the backend base URL when the backend is built, before any request is sent.
+pub fn check_base_url(url: &str) -> Option<Misconfig> {
+    let uri = match url.parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(e) => return Some(Misconfig::new(MisconfigKind::InvalidUri, format_args!("{:?}: {}", url, e))),
+    };
+    match uri.scheme_str() {
+        Some("http" | "https") if uri.host().is_some() => None,
+        Some("http" | "https") => Some(Misconfig::new(MisconfigKind::InvalidUri, format_args!("{:?}: no host", url))),
+        _ => Some(Misconfig::new(MisconfigKind::InvalidUri, format_args!("{:?}: scheme must be http or https", url))),
+    }
+}
+
+/// Classifies the error of a client request, if the backend config is what failed it: only
+/// errors establishing the connection are looked at.
+pub fn classify(err: &hyper_util::client::legacy::Error) -> Option<Misconfig> {
+    if !err.is_connect() {
+        return None;
+    }
+    classify_cause(err.source()?)
+}
+
+fn classify_cause(err: &(dyn StdError + 'static)) -> Option<Misconfig> {
+    let mut cause = Some(err);
+    while let Some(e) = cause {
+        // The message of the connector's resolution errors; the cause is what getaddrinfo said.
+        if e.to_string() == "dns error" {
+            let detail = e.source().map(|s| s.to_string()).unwrap_or_default();
+            let temporary = detail.to_ascii_lowercase().contains("temporary");
+            return (!temporary).then(|| Misconfig::new(MisconfigKind::Dns, detail));
+        }
+        if let Some(io) = e.downcast_ref::<io::Error>() {
+            if let Some(misconfig) = classify_io(io) {
+                return Some(misconfig);
+            }
+        }
+        cause = e.source();
+    }
+    None
+}
+
+/// The TLS connector reports handshake failures and unsupported schemes as I/O errors, the
+/// former wrapping the `InvalidData` error of the TLS stream.
+fn classify_io(err: &io::Error) -> Option<Misconfig> {
+    if err.kind() == io::ErrorKind::InvalidData {
+        return Some(Misconfig::new(MisconfigKind::Tls, err));
+    }
+    let message = err.to_string();
+    if message.starts_with("unsupported scheme") || message == "missing scheme" {
+        return Some(Misconfig::new(MisconfigKind::InvalidUri, message));
+    }
+    // `io::Error::source` skips the wrapped error itself.
+    classify_cause(err.get_ref()?)
+}
+
+/// Misconfiguration an upstream error chain carries, if any.
+pub fn misconfig_of(err: &anyhow::Error) -> Option<&Misconfig> {
+    err.chain().find_map(|cause| match cause.downcast_ref::<UpstreamError>() {
+        Some(UpstreamError::Misconfigured(misconfig)) => Some(misconfig),
+        _ => None,
+    })
+}
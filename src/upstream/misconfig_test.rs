@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+    use http_body_util::{BodyExt, Empty};
+    use hyper::body::Bytes;
+    use hyper::Request;
+
+    use crate::http::client::create_client;
+    use crate::upstream::misconfig::{check_base_url, classify, Misconfig, MisconfigKind};
+
+    /// Host under the reserved `.invalid` TLD, which never resolves.
+    const UNRESOLVABLE: &str = "advcache-misconfigured.invalid";
+
+    /// Requests `uri` with the upstream client and classifies the error it fails with.
+    async fn classify_request(uri: &str) -> Option<Misconfig> {
+        let body = Empty::<Bytes>::new().map_err(|never| match never {}).boxed();
+        let req = Request::get(uri).body(body).unwrap();
+        let err = create_client().request(req).await.expect_err("the request must fail");
+        classify(&err)
+    }
+
+    /// Test that base URLs with a bad scheme or without a host are refused at build time.
+    #[test]
+    fn test_check_base_url() {
+        assert_eq!(check_base_url("http://127.0.0.1:8080"), None);
+        assert_eq!(check_base_url("https://origin.example.com"), None);
+        for url in ["ftp://origin.example.com", "htp://origin.example.com", "http://", "http://bad host"] {
+            let misconfig = check_base_url(url).unwrap_or_else(|| panic!("{} must be refused", url));
+            assert_eq!(misconfig.kind, MisconfigKind::InvalidUri, "{}", url);
+            assert!(misconfig.to_string().starts_with("invalid_uri: "), "{}", misconfig);
+        }
+    }
+
+    /// Test that an unresolvable host is classified as a DNS misconfiguration.
+    #[tokio::test]
+    async fn test_unresolvable_host_is_dns() {
+        let misconfig = classify_request(&format!("http://{}/healthz", UNRESOLVABLE)).await.expect("must be classified");
+        assert_eq!(misconfig.kind, MisconfigKind::Dns);
+        assert!(!misconfig.detail.is_empty());
+    }
+
+    /// Test that `https` against a plain HTTP origin is classified as a TLS misconfiguration.
+    #[tokio::test]
+    async fn test_plain_origin_behind_https_is_tls() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, axum::Router::new().fallback(|| async { "plain" })).await.unwrap();
+        });
+
+        let misconfig = classify_request(&format!("https://{}/", addr)).await.expect("must be classified");
+        assert_eq!(misconfig.kind, MisconfigKind::Tls);
+    }
+
+    /// Test that a refused connection is a runtime failure, not a misconfiguration.
+    #[tokio::test]
+    async fn test_refused_connection_is_not_misconfig() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        assert_eq!(classify_request(&format!("http://{}/", addr)).await, None);
+    }
+}
@@ -3,18 +3,54 @@
 pub mod backend;
 pub mod backend_headers;
 pub mod backend_hyper_impl;
+pub mod digest;
+pub mod drain;
+pub mod encoding;
+pub mod health;
+pub mod health_hook;
+pub mod loop_guard;
+pub mod misconfig;
+pub mod peers;
 pub mod probe;
 pub mod proxy;
 pub mod sanitize;
+pub mod signing;
 pub mod trace;
 pub mod upstream;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
 #[cfg(test)]
 mod proxy_test;
 
 #[cfg(test)]
 mod backend_hyper_impl_test;
 
+#[cfg(test)]
+mod digest_test;
+
+#[cfg(test)]
+mod encoding_test;
+
+#[cfg(test)]
+mod health_test;
+
+#[cfg(test)]
+mod health_hook_test;
+
+#[cfg(test)]
+mod loop_guard_test;
+
+#[cfg(test)]
+mod misconfig_test;
+
+#[cfg(test)]
+mod signing_test;
+
+#[cfg(test)]
+mod testing_test;
+
 // Re-export main types
 pub use backend::BackendImpl;
-pub use upstream::{actual_policy, change_policy, Policy, Response, Upstream};
+pub use upstream::{actual_policy, change_policy, Policy, Refreshed, Response, Upstream};
@@ -0,0 +1,150 @@
+//! Propagation of admin calls to peer instances (`upstream.peers`).
+//!
+//! An invalidation, clear or bypass toggle handled by one replica is forwarded to every peer,
+//! so the others do not keep serving what was just invalidated until TTL. Forwarded calls carry
+//! [`PROPAGATED_HEADER`], and a call carrying it is never forwarded again, so peers listing each
+//! other do not bounce calls around. A peer that cannot be reached is reported in the response
+//! of the local call, which succeeds anyway.
+
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use hyper::{Method, Uri};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::config::Config;
+use crate::http::client::{create_client, HyperClient};
+use crate::upstream::backend_hyper_impl::make_method_request;
+use crate::upstream::loop_guard;
+
+/// Header marking a call forwarded by a peer; its value is the id of the forwarding instance.
+pub const PROPAGATED_HEADER: &str = "x-advcache-propagated";
+/// Query param turning propagation off for one call (`_propagate=0`).
+pub const PROPAGATE_PARAM: &str = "_propagate";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+/// Peer answers are only reported by status, with a short body for errors.
+const MAX_PEER_RESPONSE_SIZE: usize = 64 << 10;
+const MAX_REPORTED_BODY: usize = 256;
+/// Inbound headers handed on to peers, so they authorize the call as this instance did.
+const FORWARDED_HEADERS: [&str; 2] = ["authorization", "cookie"];
+
+/// Outcome of a call forwarded to one peer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeerResult {
+    pub peer: String,
+    /// Status answered by the peer, none when it could not be reached.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Peers the admin calls handled here are forwarded to.
+pub struct Peers {
+    client: HyperClient,
+    peers: Vec<String>,
+    timeout: Duration,
+}
+
+impl Peers {
+    /// Peers of `upstream.peers`, none when the list is unset or empty.
+    pub fn from_config(cfg: &Config) -> Option<Self> {
+        let upstream = cfg.cache.upstream.as_ref()?;
+        let peers = upstream.peers.clone().filter(|p| !p.is_empty())?;
+        Some(Self {
+            client: create_client(),
+            peers,
+            timeout: upstream.peer_timeout.unwrap_or(DEFAULT_TIMEOUT),
+        })
+    }
+
+    /// Whether a call is to be forwarded: not when a peer forwarded it here, nor with
+    /// `_propagate=0` (or `false`).
+    pub fn should_propagate(headers: &HeaderMap, propagate_param: Option<&str>) -> bool {
+        !headers.contains_key(PROPAGATED_HEADER) && !matches!(propagate_param, Some("0" | "false"))
+    }
+
+    /// Forwards `GET path_and_query` to every peer at once and reports each outcome.
+    pub async fn forward(&self, path_and_query: &str, headers: &HeaderMap) -> Vec<PeerResult> {
+        let calls = self.peers.iter().map(|peer| async move {
+            match self.get(peer, path_and_query, headers).await {
+                Ok((status, body)) => result(peer, status, &body),
+                Err(e) => unreachable_peer(peer, path_and_query, format!("{:#}", e)),
+            }
+        });
+        futures::future::join_all(calls).await
+    }
+
+    /// Clears every peer. Clearing takes a token of the instance being cleared, so each peer
+    /// is asked for its own token first, then cleared with it.
+    pub async fn clear(&self, headers: &HeaderMap) -> Vec<PeerResult> {
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            token: String,
+        }
+
+        let calls = self.peers.iter().map(|peer| async move {
+            let token = match self.get(peer, "/advcache/clear", headers).await {
+                Ok((200, body)) => match serde_json::from_slice::<TokenResponse>(&body) {
+                    Ok(resp) => resp.token,
+                    Err(e) => return unreachable_peer(peer, "/advcache/clear", format!("no clear token: {}", e)),
+                },
+                Ok((status, body)) => return result(peer, status, &body),
+                Err(e) => return unreachable_peer(peer, "/advcache/clear", format!("{:#}", e)),
+            };
+            let path = format!("/advcache/clear?token={}", token);
+            match self.get(peer, &path, headers).await {
+                Ok((status, body)) => result(peer, status, &body),
+                Err(e) => unreachable_peer(peer, "/advcache/clear", format!("{:#}", e)),
+            }
+        });
+        futures::future::join_all(calls).await
+    }
+
+    async fn get(&self, peer: &str, path_and_query: &str, inbound: &HeaderMap) -> anyhow::Result<(u16, Vec<u8>)> {
+        let uri: Uri = format!("http://{}{}", peer, path_and_query).parse()?;
+        let mut headers = vec![(PROPAGATED_HEADER, loop_guard::instance_id())];
+        for name in FORWARDED_HEADERS {
+            if let Some(value) = inbound.get(name).and_then(|v| v.to_str().ok()) {
+                headers.push((name, value));
+            }
+        }
+        let (status, _, body) = make_method_request(
+            &self.client,
+            Method::GET,
+            uri,
+            headers,
+            None,
+            self.timeout.into(),
+            None,
+            MAX_PEER_RESPONSE_SIZE,
+        )
+        .await?;
+        Ok((status, body))
+    }
+}
+
+fn result(peer: &str, status: u16, body: &[u8]) -> PeerResult {
+    let ok = (200..300).contains(&status);
+    PeerResult {
+        peer: peer.to_string(),
+        status: Some(status),
+        ok,
+        error: (!ok).then(|| String::from_utf8_lossy(&body[..body.len().min(MAX_REPORTED_BODY)]).into_owned()),
+    }
+}
+
+fn unreachable_peer(peer: &str, path: &str, error: String) -> PeerResult {
+    warn!(
+        component = "peers",
+        event = "peer_propagation_failed",
+        peer = peer,
+        path = path,
+        error = %error,
+        "admin call was not propagated to peer"
+    );
+    PeerResult { peer: peer.to_string(), status: None, ok: false, error: Some(error) }
+}
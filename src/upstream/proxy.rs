@@ -93,18 +93,23 @@ pub fn extract_forwarded_host_bytes(src_headers: &[(Vec<u8>, Vec<u8>)]) -> Optio
 /// Prefers X-Forwarded-Host (case-insensitive) if value is non-empty,
 /// otherwise falls back to Host (case-insensitive) if non-empty,
 /// otherwise returns None.
-pub fn forwarded_host_value_bytes(src_headers: &[(Vec<u8>, Vec<u8>)]) -> Option<&[u8]> {
+/// Takes owned or borrowed pairs alike, so callers need not convert their headers first.
+pub fn forwarded_host_value_bytes<K, V>(src_headers: &[(K, V)]) -> Option<&[u8]>
+where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
     // Prefer X-Forwarded-Host when present and non-empty.
     for (key, value) in src_headers {
-        if key.eq_ignore_ascii_case(b"x-forwarded-host") && !value.is_empty() {
-            return Some(value.as_slice());
+        if key.as_ref().eq_ignore_ascii_case(b"x-forwarded-host") && !value.as_ref().is_empty() {
+            return Some(value.as_ref());
         }
     }
 
     // Fall back to Host header.
     for (key, value) in src_headers {
-        if key.eq_ignore_ascii_case(b"host") && !value.is_empty() {
-            return Some(value.as_slice());
+        if key.as_ref().eq_ignore_ascii_case(b"host") && !value.as_ref().is_empty() {
+            return Some(value.as_ref());
         }
     }
 
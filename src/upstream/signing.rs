@@ -0,0 +1,186 @@
+//! HMAC signing of the requests sent to a backend (`backend.signing`).
+//!
+//! Fills, refreshes, proxied requests and health checks are signed just before they are sent.
+//! The signature covers a canonical form of the request:
+//!
+//! ```text
+//! METHOD\n
+//! /path?query\n
+//! <unix seconds>\n
+//! <hex sha256 of the body>\n
+//! name:value\n              one line per signed header, in canonical order
+//! ```
+//!
+//! and is sent as the hex HMAC-SHA256 in `X-Signature`, next to `X-Signature-Timestamp` and
+//! `X-Signature-Headers` (the signed header names joined by `;`). Header names are
+//! canonicalized once, from config: lowercased, trimmed, deduplicated and sorted, so the list
+//! order in config does not change a signature. A signed header the request lacks is signed as
+//! an empty value; one sent several times as its values joined by `,`. `host` is the Host the
+//! origin receives.
+//!
+//! The timestamp comes from [`crate::time::now`], the cache's clock; verifiers should accept
+//! a skew of some seconds each way and the cache hosts should run NTP.
+//!
+//! The key is read from the environment variable `key_env` names, never from config, and is
+//! never logged: [`Signer`] prints it redacted and `X-Signature` is redacted from logged headers.
+
+use std::fmt;
+
+use anyhow::{bail, Result};
+use hyper::Uri;
+use ring::{digest, hmac};
+
+use crate::config::Signing;
+use crate::dedlog::sanitizer::REDACTED;
+use crate::time;
+
+/// The only algorithm `backend.signing.algorithm` accepts.
+pub const ALGORITHM: &str = "hmac-sha256";
+
+pub const SIGNATURE_HEADER: &str = "x-signature";
+pub const TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+pub const SIGNED_HEADERS_HEADER: &str = "x-signature-headers";
+
+/// Whether `name` is one of the headers signing sets; a client's own are dropped before
+/// a signed request is sent.
+pub fn is_signing_header(name: &str) -> bool {
+    [SIGNATURE_HEADER, TIMESTAMP_HEADER, SIGNED_HEADERS_HEADER]
+        .iter()
+        .any(|h| h.eq_ignore_ascii_case(name))
+}
+
+/// Signed header names in canonical order: lowercased, trimmed, deduplicated and sorted.
+pub fn canonical_header_names<S: AsRef<str>>(names: &[S]) -> Vec<String> {
+    let mut names: Vec<String> = names
+        .iter()
+        .map(|n| n.as_ref().trim().to_ascii_lowercase())
+        .filter(|n| !n.is_empty())
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+/// Signs requests to one backend with its key.
+pub struct Signer {
+    key: hmac::Key,
+    headers: Vec<String>,
+}
+
+impl fmt::Debug for Signer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signer")
+            .field("key", &REDACTED)
+            .field("headers", &self.headers)
+            .finish()
+    }
+}
+
+impl Signer {
+    pub fn new(key: &[u8], headers_to_sign: &[String]) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, key),
+            headers: canonical_header_names(headers_to_sign),
+        }
+    }
+
+    /// Builds the signer of `backend.signing`, reading the key from its environment variable.
+    pub fn from_config(cfg: &Signing) -> Result<Self> {
+        let algorithm = cfg.algorithm.as_deref().unwrap_or(ALGORITHM);
+        if !algorithm.eq_ignore_ascii_case(ALGORITHM) {
+            bail!("backend.signing.algorithm {:?} is not supported, only {}", algorithm, ALGORITHM);
+        }
+        if cfg.key_env.trim().is_empty() {
+            bail!("backend.signing.key_env must name the environment variable holding the key");
+        }
+        let key = match std::env::var(cfg.key_env.trim()) {
+            Ok(key) if !key.is_empty() => key,
+            _ => bail!("backend.signing.key_env: environment variable {} is not set or empty", cfg.key_env.trim()),
+        };
+        Ok(Self::new(key.as_bytes(), &cfg.headers_to_sign))
+    }
+
+    /// Signed header names in canonical order.
+    pub fn signed_headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    /// The canonical form of a request the signature is computed over.
+    pub fn canonical_request(
+        &self,
+        method: &str,
+        path_and_query: &str,
+        timestamp: u64,
+        host: Option<&str>,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> String {
+        let mut canonical = format!(
+            "{}\n{}\n{}\n{}\n",
+            method.to_ascii_uppercase(),
+            path_and_query,
+            timestamp,
+            hex::encode(digest::digest(&digest::SHA256, body)),
+        );
+        for name in &self.headers {
+            let value = if name == "host" {
+                host.unwrap_or_default().trim().to_string()
+            } else {
+                headers
+                    .iter()
+                    .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+                    .map(|(_, v)| v.trim())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            canonical.push_str(name);
+            canonical.push(':');
+            canonical.push_str(&value);
+            canonical.push('\n');
+        }
+        canonical
+    }
+
+    /// Hex HMAC-SHA256 of `message`.
+    pub fn signature(&self, message: &str) -> String {
+        hex::encode(hmac::sign(&self.key, message.as_bytes()))
+    }
+
+    /// Headers signing the request at `timestamp` (unix seconds).
+    pub fn sign_at(
+        &self,
+        method: &str,
+        uri: &Uri,
+        timestamp: u64,
+        forwarded_host: Option<&[u8]>,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Vec<(&'static str, String)> {
+        let path_and_query = uri.path_and_query().map_or("/", |pq| pq.as_str());
+        let host = match forwarded_host {
+            Some(host) => std::str::from_utf8(host).ok(),
+            None => uri.authority().map(|a| a.as_str()),
+        };
+        let canonical = self.canonical_request(method, path_and_query, timestamp, host, headers, body);
+        vec![
+            (SIGNATURE_HEADER, self.signature(&canonical)),
+            (TIMESTAMP_HEADER, timestamp.to_string()),
+            (SIGNED_HEADERS_HEADER, self.signed_headers().join(";")),
+        ]
+    }
+
+    /// Headers signing the request now, by the cache's clock.
+    pub fn sign(
+        &self,
+        method: &str,
+        uri: &Uri,
+        forwarded_host: Option<&[u8]>,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Vec<(&'static str, String)> {
+        let timestamp = time::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.sign_at(method, uri, timestamp, forwarded_host, headers, body)
+    }
+}
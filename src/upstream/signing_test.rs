@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use hyper::Uri;
+
+    use crate::config::Signing;
+    use crate::dedlog::redacted_headers;
+    use crate::upstream::signing::{
+        canonical_header_names, is_signing_header, Signer, SIGNATURE_HEADER, SIGNED_HEADERS_HEADER, TIMESTAMP_HEADER,
+    };
+
+    const TIMESTAMP: u64 = 1_700_000_000;
+
+    fn signing(key_env: &str) -> Signing {
+        Signing { algorithm: None, key_env: key_env.to_string(), headers_to_sign: vec![] }
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    /// Test that the MAC is HMAC-SHA256, by RFC 4231 test case 2.
+    #[test]
+    fn test_hmac_sha256_vector() {
+        let signer = Signer::new(b"Jefe", &[]);
+        assert_eq!(
+            signer.signature("what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    /// Test that a request is signed over its canonical form, with headers in canonical order,
+    /// the Host the origin receives and repeated headers joined.
+    #[test]
+    fn test_signs_canonical_request() {
+        let signer = Signer::new(b"secret", &names(&["X-Request-Id", "host", "Content-Type"]));
+        let uri: Uri = "http://origin.internal:8080/api/v1/user?id=1".parse().unwrap();
+        let headers = [("content-type", "application/json"), ("x-request-id", "abc"), ("X-Request-Id", " def ")];
+        let body = br#"{"id":1}"#;
+
+        assert_eq!(
+            signer.canonical_request("post", "/api/v1/user?id=1", TIMESTAMP, Some("example.com"), &headers, body),
+            "POST\n/api/v1/user?id=1\n1700000000\n\
+             037c9214eef74cc3887f3a4f085b4e17d76280dafd273b0ee160c09c4ba1cfd4\n\
+             content-type:application/json\nhost:example.com\nx-request-id:abc,def\n"
+        );
+        let signed = signer.sign_at("POST", &uri, TIMESTAMP, Some(b"example.com"), &headers, body);
+        assert_eq!(
+            signed,
+            vec![
+                (SIGNATURE_HEADER, "c21808e0bfe6f42a69f263e7b0de5b513ecc51cce66ad934babc6426b0c9aefc".to_string()),
+                (TIMESTAMP_HEADER, "1700000000".to_string()),
+                (SIGNED_HEADERS_HEADER, "content-type;host;x-request-id".to_string()),
+            ]
+        );
+
+        // Without a forwarded Host, the URI's authority is what the origin receives.
+        let canonical = signer.canonical_request("GET", "/", TIMESTAMP, uri.authority().map(|a| a.as_str()), &[], b"");
+        assert!(canonical.contains("\nhost:origin.internal:8080\n"), "{}", canonical);
+        assert!(canonical.contains("\ncontent-type:\n"), "missing headers are signed empty: {}", canonical);
+        assert!(canonical.contains("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"));
+    }
+
+    /// Test that the signed header list is canonicalized the same whatever its order, case,
+    /// padding or duplicates, and so is the signature.
+    #[test]
+    fn test_header_names_canonicalized_consistently() {
+        let canonical = names(&["content-type", "host", "x-request-id"]);
+        for list in [
+            names(&["host", "content-type", "x-request-id"]),
+            names(&["X-Request-Id", " Host ", "CONTENT-TYPE", "host", ""]),
+            names(&["x-request-id", "content-type", "content-type", "host"]),
+        ] {
+            assert_eq!(canonical_header_names(&list), canonical, "{:?}", list);
+            assert_eq!(Signer::new(b"k", &list).signed_headers(), canonical.as_slice());
+        }
+
+        let uri: Uri = "http://example.com/a?b=c".parse().unwrap();
+        let headers = [("Content-Type", "text/plain"), ("x-request-id", "1")];
+        let a = Signer::new(b"k", &names(&["x-request-id", "Content-Type"]));
+        let b = Signer::new(b"k", &names(&["content-type", "X-REQUEST-ID", "x-request-id"]));
+        assert_eq!(
+            a.sign_at("GET", &uri, TIMESTAMP, None, &headers, b""),
+            b.sign_at("GET", &uri, TIMESTAMP, None, &headers, b"")
+        );
+    }
+
+    /// Test that the key comes from its environment variable, which must be set, that only
+    /// hmac-sha256 is accepted and that neither the signer nor logged headers show secrets.
+    #[test]
+    fn test_from_config() {
+        std::env::set_var("ADVCACHE_SIGNING_TEST_KEY", "secret");
+        let signer = Signer::from_config(&signing("ADVCACHE_SIGNING_TEST_KEY")).expect("the key is set");
+        assert_eq!(signer.signature("m"), Signer::new(b"secret", &[]).signature("m"));
+        assert!(!format!("{:?}", signer).contains("secret"));
+
+        let algorithm = Signing { algorithm: Some("HMAC-SHA256".into()), ..signing("ADVCACHE_SIGNING_TEST_KEY") };
+        assert!(Signer::from_config(&algorithm).is_ok());
+        let algorithm = Signing { algorithm: Some("hmac-sha1".into()), ..signing("ADVCACHE_SIGNING_TEST_KEY") };
+        assert!(Signer::from_config(&algorithm).is_err());
+        assert!(Signer::from_config(&signing("ADVCACHE_SIGNING_TEST_UNSET")).is_err());
+        assert!(Signer::from_config(&signing(" ")).is_err());
+
+        let logged = redacted_headers(&[("X-Signature", "c218"), ("x-signature-timestamp", "1700000000")]).to_string();
+        assert_eq!(logged, "X-Signature: <redacted>, x-signature-timestamp: 1700000000");
+        assert!(is_signing_header("X-Signature-Headers") && !is_signing_header("x-request-id"));
+    }
+}
@@ -0,0 +1,420 @@
+//! Programmable [`Upstream`] for tests of code built on the upstream layer.
+//!
+//! Compiled for the crate's own tests and, for downstream crates, behind the `testing` feature.
+//! Fills, proxied requests and refreshes are all recorded as [`Call`]s with a plain
+//! method, path and query string, so assertions do not depend on which trait method
+//! (rule based or string based) the code under test went through.
+
+// Every test target uses its own subset of the helpers.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::config::Rule;
+use crate::model::{Entry, Response as ModelResponse};
+use crate::upstream::{Refreshed, Response, Upstream};
+
+/// Trait method a recorded call came through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    /// `Upstream::request`: a cache fill for a rule.
+    Fill,
+    /// `Upstream::proxy_request`.
+    Proxy,
+    /// `Upstream::refresh` of a stored entry.
+    Refresh,
+}
+
+/// A request the mock has received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    pub kind: CallKind,
+    pub method: String,
+    pub path: String,
+    /// Query string without the leading `?` (form encoded for fills).
+    pub query: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+/// Answer computed from the call.
+type Handler = Arc<dyn Fn(&Call) -> Response + Send + Sync>;
+
+#[derive(Clone)]
+enum Reply {
+    Respond(Response),
+    Fail(String),
+    Handle(Handler),
+}
+
+/// Builder of a [`MockUpstream`].
+pub struct MockUpstreamBuilder {
+    routes: HashMap<String, Reply>,
+    fallback: Reply,
+    latency: Duration,
+    healthy: bool,
+    concurrency: usize,
+}
+
+impl MockUpstreamBuilder {
+    /// Answers requests for `path` with the response.
+    pub fn respond(mut self, path: &str, response: Response) -> Self {
+        self.routes.insert(path.to_string(), Reply::Respond(response));
+        self
+    }
+
+    /// Answers requests for `path` with what `handler` makes of each call.
+    pub fn handle(mut self, path: &str, handler: impl Fn(&Call) -> Response + Send + Sync + 'static) -> Self {
+        self.routes.insert(path.to_string(), Reply::Handle(Arc::new(handler)));
+        self
+    }
+
+    /// Fails requests for `path` with the error message.
+    pub fn fail(mut self, path: &str, error: &str) -> Self {
+        self.routes.insert(path.to_string(), Reply::Fail(error.to_string()));
+        self
+    }
+
+    /// Answer for paths without a canned response (200 `ok` by default).
+    pub fn fallback(mut self, response: Response) -> Self {
+        self.fallback = Reply::Respond(response);
+        self
+    }
+
+    /// Fails every path without a canned response.
+    pub fn fail_unknown(mut self, error: &str) -> Self {
+        self.fallback = Reply::Fail(error.to_string());
+        self
+    }
+
+    /// Delay applied before every answer.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Makes `is_healthy` fail and `is_alive` false until [`MockUpstream::set_healthy`] is called.
+    pub fn unhealthy(mut self) -> Self {
+        self.healthy = false;
+        self
+    }
+
+    /// Reports the upstream saturated while `n` calls or more are in flight. Calls are not
+    /// limited, only the saturation signal is.
+    pub fn concurrency(mut self, n: usize) -> Self {
+        self.concurrency = n;
+        self
+    }
+
+    pub fn build(self) -> Arc<MockUpstream> {
+        Arc::new(MockUpstream {
+            routes: Mutex::new(self.routes),
+            fallback: Mutex::new(self.fallback),
+            latency: Mutex::new(self.latency),
+            fail_next: AtomicUsize::new(0),
+            healthy: AtomicBool::new(self.healthy),
+            concurrency: self.concurrency,
+            inflight: AtomicUsize::new(0),
+            peak_inflight: AtomicUsize::new(0),
+            calls: Mutex::new(Vec::new()),
+            idle_drops: AtomicUsize::new(0),
+        })
+    }
+}
+
+/// In-memory upstream with canned responses per path, latency, failure injection and call recording.
+pub struct MockUpstream {
+    routes: Mutex<HashMap<String, Reply>>,
+    fallback: Mutex<Reply>,
+    latency: Mutex<Duration>,
+    fail_next: AtomicUsize,
+    healthy: AtomicBool,
+    concurrency: usize,
+    inflight: AtomicUsize,
+    peak_inflight: AtomicUsize,
+    calls: Mutex<Vec<Call>>,
+    idle_drops: AtomicUsize,
+}
+
+impl MockUpstream {
+    pub fn builder() -> MockUpstreamBuilder {
+        MockUpstreamBuilder {
+            routes: HashMap::new(),
+            fallback: Reply::Respond(Response::ok("ok")),
+            latency: Duration::ZERO,
+            healthy: true,
+            concurrency: 0,
+        }
+    }
+
+    /// Mock answering every request with 200 `ok`.
+    pub fn new() -> Arc<Self> {
+        Self::builder().build()
+    }
+
+    /// Replaces the canned response for `path`.
+    pub fn set_response(&self, path: &str, response: Response) {
+        self.routes
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), Reply::Respond(response));
+    }
+
+    pub fn set_latency(&self, latency: Duration) {
+        *self.latency.lock().unwrap() = latency;
+    }
+
+    /// Fails the next `n` calls of any kind, whatever their path.
+    pub fn fail_next(&self, n: usize) {
+        self.fail_next.store(n, Ordering::SeqCst);
+    }
+
+    pub fn set_healthy(&self, healthy: bool) {
+        self.healthy.store(healthy, Ordering::SeqCst);
+    }
+
+    /// Number of calls waiting for their answer.
+    pub fn inflight(&self) -> usize {
+        self.inflight.load(Ordering::SeqCst)
+    }
+
+    /// Most calls that were waiting for their answer at once.
+    pub fn peak_inflight(&self) -> usize {
+        self.peak_inflight.load(Ordering::SeqCst)
+    }
+
+    /// All calls received so far, in arrival order.
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Number of calls of the kind received so far.
+    pub fn count(&self, kind: CallKind) -> usize {
+        self.calls.lock().unwrap().iter().filter(|c| c.kind == kind).count()
+    }
+
+    pub fn fills(&self) -> usize {
+        self.count(CallKind::Fill)
+    }
+
+    pub fn proxied(&self) -> usize {
+        self.count(CallKind::Proxy)
+    }
+
+    pub fn refreshes(&self) -> usize {
+        self.count(CallKind::Refresh)
+    }
+
+    /// Times the idle connections were dropped.
+    pub fn idle_drops(&self) -> usize {
+        self.idle_drops.load(Ordering::SeqCst)
+    }
+
+    pub fn last_call(&self) -> Option<Call> {
+        self.calls.lock().unwrap().last().cloned()
+    }
+
+    pub fn reset_calls(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+
+    /// Asserts how many calls of the kind were received.
+    #[track_caller]
+    pub fn assert_calls(&self, kind: CallKind, expected: usize) {
+        let calls = self.calls();
+        let actual = calls.iter().filter(|c| c.kind == kind).count();
+        assert_eq!(actual, expected, "unexpected number of {:?} calls, got: {:#?}", kind, calls);
+    }
+
+    /// Asserts that the origin was never reached.
+    #[track_caller]
+    pub fn assert_not_called(&self) {
+        let calls = self.calls();
+        assert!(calls.is_empty(), "upstream must not be called, got: {:#?}", calls);
+    }
+
+    /// Asserts that some call of the kind was made for the path.
+    #[track_caller]
+    pub fn assert_called(&self, kind: CallKind, path: &str) {
+        let calls = self.calls();
+        assert!(
+            calls.iter().any(|c| c.kind == kind && c.path == path),
+            "no {:?} call for {}, got: {:#?}",
+            kind,
+            path,
+            calls
+        );
+    }
+
+    /// Records the call, then waits for the latency and resolves the reply for its path.
+    async fn handle(&self, call: Call) -> Result<Response> {
+        let path = call.path.clone();
+        let index = {
+            let mut calls = self.calls.lock().unwrap();
+            calls.push(call);
+            calls.len() - 1
+        };
+
+        let latency = *self.latency.lock().unwrap();
+        if !latency.is_zero() {
+            let _inflight = Inflight::enter(&self.inflight, &self.peak_inflight);
+            tokio::time::sleep(latency).await;
+        }
+
+        let injected = self
+            .fail_next
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if injected {
+            return Err(anyhow!("mock upstream: injected failure"));
+        }
+
+        let reply = match self.routes.lock().unwrap().get(&path) {
+            Some(reply) => reply.clone(),
+            None => self.fallback.lock().unwrap().clone(),
+        };
+        match reply {
+            Reply::Respond(response) => Ok(response),
+            Reply::Fail(error) => Err(anyhow!("mock upstream: {}", error)),
+            Reply::Handle(handler) => {
+                let call = self.calls.lock().unwrap().get(index).cloned();
+                call.map(|call| handler(&call)).ok_or_else(|| anyhow!("mock upstream: calls reset before the answer"))
+            }
+        }
+    }
+}
+
+/// Counts a call in flight until dropped, so a call cancelled mid-latency is not left counted.
+struct Inflight<'a>(&'a AtomicUsize);
+
+impl<'a> Inflight<'a> {
+    fn enter(counter: &'a AtomicUsize, peak: &AtomicUsize) -> Self {
+        let inflight = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        peak.fetch_max(inflight, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for Inflight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn lossy_pairs(pairs: &[(Vec<u8>, Vec<u8>)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(k, v)| (String::from_utf8_lossy(k).into_owned(), String::from_utf8_lossy(v).into_owned()))
+        .collect()
+}
+
+fn encode_query(queries: &[(Vec<u8>, Vec<u8>)]) -> String {
+    let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+    for (k, v) in lossy_pairs(queries) {
+        serializer.append_pair(&k, &v);
+    }
+    serializer.finish()
+}
+
+#[async_trait::async_trait]
+impl Upstream for MockUpstream {
+    async fn request(
+        &self,
+        method: &str,
+        rule: &Rule,
+        queries: &[(Vec<u8>, Vec<u8>)],
+        headers: &[(Vec<u8>, Vec<u8>)],
+        body: Option<&[u8]>,
+    ) -> Result<Response> {
+        self.handle(Call {
+            kind: CallKind::Fill,
+            method: method.to_string(),
+            path: rule.path.clone().unwrap_or_default(),
+            query: encode_query(queries),
+            headers: lossy_pairs(headers),
+            body: body.map(<[u8]>::to_vec),
+        })
+        .await
+    }
+
+    async fn proxy_request(
+        &self,
+        method: &str,
+        path: &str,
+        query: &str,
+        headers: &[(String, String)],
+        body: Option<&[u8]>,
+    ) -> Result<Response> {
+        self.handle(Call {
+            kind: CallKind::Proxy,
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.trim_start_matches('?').to_string(),
+            headers: headers.to_vec(),
+            body: body.map(<[u8]>::to_vec),
+        })
+        .await
+    }
+
+    /// Resolves the reply like a fill and, as the real backend, returns it on a detached copy
+    /// of the entry. The call carries the validators of the stored response as conditional
+    /// headers; a 304 to them is not modified, anything else but 200 is an error.
+    async fn refresh(&self, entry: &Entry) -> Result<Refreshed> {
+        let request = entry.request_payload().ok();
+        let conditional = entry.validators().map(|v| v.conditional_headers()).unwrap_or_default();
+        let mut headers = request.as_ref().map(|r| lossy_pairs(&r.headers)).unwrap_or_default();
+        let body = request.as_ref().and_then(|r| r.body.clone());
+        headers.extend(lossy_pairs(&conditional));
+        let response = self
+            .handle(Call {
+                kind: CallKind::Refresh,
+                method: if body.is_some() { "POST" } else { "GET" }.to_string(),
+                path: entry.rule().path.clone().unwrap_or_default(),
+                query: request.as_ref().map(|r| encode_query(&r.queries)).unwrap_or_default(),
+                headers,
+                body: body.clone(),
+            })
+            .await?;
+
+        if response.status == 304 && !conditional.is_empty() {
+            return Ok(Refreshed::NotModified);
+        }
+        if response.status != 200 {
+            return Err(anyhow!("invalid upstream status code: {}", response.status));
+        }
+        let (queries, headers) = request.map(|r| (r.queries, r.headers)).unwrap_or_default();
+        let refreshed = entry.detached();
+        refreshed.set_payload_with_body(
+            &queries,
+            &headers,
+            body.as_deref(),
+            &ModelResponse { status: response.status, headers: response.headers, body: response.body },
+        );
+        Ok(Refreshed::Modified(refreshed))
+    }
+
+    async fn is_healthy(&self) -> Result<()> {
+        if self.healthy.load(Ordering::SeqCst) {
+            Ok(())
+        } else {
+            Err(anyhow!("mock upstream: unhealthy"))
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.concurrency > 0 && self.inflight() >= self.concurrency
+    }
+
+    fn drop_idle_connections(&self) {
+        self.idle_drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::config::Rule;
+    use crate::upstream::testing::{CallKind, MockUpstream};
+    use crate::upstream::{Response, Upstream};
+
+    /// Test that canned responses are served per path, with the fallback for other paths.
+    #[tokio::test]
+    async fn test_mock_serves_canned_responses_per_path() {
+        let upstream = MockUpstream::builder()
+            .respond("/api/v1/user", Response::ok("user").with_header("content-type", "text/plain"))
+            .fail("/api/v1/broken", "connection reset")
+            .fallback(Response::empty(404))
+            .build();
+
+        let resp = upstream
+            .request("GET", &Rule::bare("/api/v1/user"), &[(b"id".to_vec(), b"1".to_vec())], &[], None)
+            .await
+            .unwrap();
+        assert_eq!((resp.status, resp.body.as_slice()), (200, &b"user"[..]));
+        assert_eq!(resp.headers, vec![("content-type".to_string(), "text/plain".to_string())]);
+
+        let resp = upstream.proxy_request("POST", "/other", "?a=b", &[], Some(b"x")).await.unwrap();
+        assert_eq!(resp.status, 404);
+
+        let err = upstream.request("GET", &Rule::bare("/api/v1/broken"), &[], &[], None).await.unwrap_err();
+        assert!(err.to_string().contains("connection reset"));
+
+        upstream.assert_calls(CallKind::Fill, 2);
+        upstream.assert_calls(CallKind::Proxy, 1);
+        let calls = upstream.calls();
+        assert_eq!(calls[0].query, "id=1");
+        assert_eq!(calls[1].method, "POST");
+        assert_eq!(calls[1].query, "a=b");
+        assert_eq!(calls[1].body.as_deref(), Some(&b"x"[..]));
+    }
+
+    /// Test latency, one-shot failure injection and health toggling.
+    #[tokio::test]
+    async fn test_mock_latency_failures_and_health() {
+        let upstream = MockUpstream::builder()
+            .latency(Duration::from_millis(50))
+            .unhealthy()
+            .build();
+        assert!(upstream.is_healthy().await.is_err());
+        upstream.set_healthy(true);
+        assert!(upstream.is_healthy().await.is_ok());
+
+        let started = Instant::now();
+        upstream.fail_next(1);
+        assert!(upstream.request("GET", &Rule::bare("/a"), &[], &[], None).await.is_err());
+        assert!(upstream.request("GET", &Rule::bare("/a"), &[], &[], None).await.is_ok());
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        upstream.reset_calls();
+        upstream.assert_not_called();
+    }
+}
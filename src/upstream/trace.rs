@@ -6,6 +6,7 @@
 use tracing::{Level, Span};
 
 use crate::config::Rule;
+use crate::dedlog::redacted;
 use crate::model::Entry;
 use crate::traces;
 
@@ -25,7 +26,7 @@ pub fn start_proxy_request_span(
         Level::INFO,
         "upstream",
         http.path = path,
-        http.request = request_str,
+        http.request = %redacted(request_str),
     ))
 }
 
@@ -46,7 +47,7 @@ pub fn start_request_span(
         Level::INFO,
         "upstream",
         http.path = path,
-        http.request = request_str,
+        http.request = %redacted(request_str),
     ))
 }
 
@@ -5,6 +5,8 @@ use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::config::Rule;
 use crate::model::Entry;
+use crate::upstream::health::HealthChecks;
+use crate::upstream::misconfig::Misconfig;
 
 /// Policy for handling upstream requests.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -55,16 +57,36 @@ pub fn change_policy(policy: Policy) -> Result<()> {
     Ok(())
 }
 
+/// Outcome of [`Upstream::refresh`].
+pub enum Refreshed {
+    /// The new response, on a detached copy of the entry.
+    Modified(Entry),
+    /// The origin answered 304 to the validators of the stored response.
+    NotModified,
+}
+
+impl Refreshed {
+    /// The refreshed copy of the entry, if the origin sent a new response.
+    pub fn modified(self) -> Option<Entry> {
+        match self {
+            Refreshed::Modified(entry) => Some(entry),
+            Refreshed::NotModified => None,
+        }
+    }
+}
+
 /// Upstream defines the interface for external backends.
 #[async_trait::async_trait]
 pub trait Upstream: Send + Sync {
-    /// Makes a request to the upstream backend.
+    /// Makes a request to the upstream backend: a GET, or the POST of a rule keying its body.
     /// original_headers: original request headers (for proxy_forwarded_host), can be same as headers if not filtered
     async fn request(
         &self,
+        method: &str,
         rule: &Rule,
         queries: &[(Vec<u8>, Vec<u8>)],
         headers: &[(Vec<u8>, Vec<u8>)],
+        body: Option<&[u8]>,
     ) -> Result<Response>;
 
     /// Proxies a request to the upstream backend.
@@ -77,14 +99,55 @@ pub trait Upstream: Send + Sync {
         body: Option<&[u8]>,
     ) -> Result<Response>;
 
-    /// Refreshes an entry by fetching new data from upstream.
-    async fn refresh(&self, entry: &Entry) -> Result<()>;
+    /// Fetches new data for an entry from upstream, returned on a [`Entry::detached`] copy of
+    /// it. The entry itself is left untouched: the storage decides whether the copy lands.
+    /// The request carries the stored response's validators, so the origin may answer that
+    /// nothing changed instead.
+    async fn refresh(&self, entry: &Entry) -> Result<Refreshed>;
 
     /// Checks if the upstream backend is healthy.
     async fn is_healthy(&self) -> Result<()>;
+
+    /// Last known health of the backend, as seen by its health observer; does not probe it.
+    fn is_alive(&self) -> bool {
+        true
+    }
+
+    /// State of each health check of the backend, `None` when it runs none.
+    fn health_checks(&self) -> Option<HealthChecks> {
+        None
+    }
+
+    /// What is wrong with the backend config, as the last request failing for it said; `None`
+    /// once a request gets an answer. Hits keep being served meanwhile.
+    fn config_error(&self) -> Option<Misconfig> {
+        None
+    }
+
+    /// Id of the backend in admin endpoints and metrics: `backend.id`, or its host without one.
+    fn backend_id(&self) -> &str {
+        ""
+    }
+
+    /// Drains or undrains the backend. A drained backend is sent no new fills, proxied requests
+    /// or refreshes, which fail fast instead; requests in flight complete and health probes go on.
+    fn set_drained(&self, _drained: bool) {}
+
+    fn is_drained(&self) -> bool {
+        false
+    }
+
+    /// Whether every connection slot to the backend is taken, so a new request would queue.
+    fn is_saturated(&self) -> bool {
+        false
+    }
+
+    /// Closes the idle connections to the backend; requests in flight keep theirs.
+    fn drop_idle_connections(&self) {}
 }
 
 /// HTTP Response wrapper.
+#[derive(Debug, Clone)]
 pub struct Response {
     pub status: u16,
     pub headers: Vec<(String, String)>,
@@ -100,6 +163,32 @@ impl Response {
         }
     }
 
+    /// 200 response with the body and no headers.
+    #[allow(dead_code)]
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self::new(200, Vec::new(), body.into())
+    }
+
+    /// Response with the status, no headers and an empty body.
+    #[allow(dead_code)]
+    pub fn empty(status: u16) -> Self {
+        Self::new(status, Vec::new(), Vec::new())
+    }
+
+    /// Appends a header.
+    #[allow(dead_code)]
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Replaces the body.
+    #[allow(dead_code)]
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
     #[allow(dead_code)]
     pub fn is_ok(&self) -> bool {
         self.status == 200
@@ -1,6 +1,8 @@
 //! Backend interfaces for worker groups.
 //
 
+use std::time::Duration;
+
 use crate::model::Entry;
 use anyhow::Result;
 
@@ -31,6 +33,10 @@ pub trait RefreshBackend: Send + Sync {
     /// Peeks at an expired entry (without removing it).
     fn peek_expired_ttl(&self) -> Option<Entry>;
 
+    /// Peeks at the most hit entry whose refresh falls due within `ahead` and that was last
+    /// refreshed before `refreshed_before` (unix nanos).
+    fn peek_prewarm(&self, ahead: Duration, refreshed_before: i64) -> Option<Entry>;
+
     /// Handles TTL expiration for an entry.
     async fn on_ttl(
         &self,
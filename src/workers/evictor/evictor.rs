@@ -388,7 +388,14 @@ impl Service for Arc<Evictor> {
     }
 
     fn cfg(&self) -> Arc<dyn Config> {
-        self.cfg.blocking_read().clone()
+        // Called from handlers on the runtime, where blocking_read panics; writers only hold the
+        // lock to swap the config in.
+        loop {
+            if let Ok(cfg) = self.cfg.try_read() {
+                return cfg.clone();
+            }
+            std::thread::yield_now();
+        }
     }
 
     fn replicas(&self) -> usize {
@@ -15,6 +15,8 @@ pub struct Counters {
     pub scans_hit: AtomicI64,
     /// Scans that found no expired entries.
     pub scans_miss: AtomicI64,
+    /// Entries handed out for refresh ahead of their TTL.
+    pub prewarmed: AtomicI64,
 }
 
 impl Counters {
@@ -26,6 +28,7 @@ impl Counters {
             scans_total: AtomicI64::new(0),
             scans_hit: AtomicI64::new(0),
             scans_miss: AtomicI64::new(0),
+            prewarmed: AtomicI64::new(0),
         }
     }
 
@@ -38,6 +41,11 @@ impl Counters {
         let miss = self.scans_miss.swap(0, Ordering::Relaxed);
         (updated, errors, scans, miss, hits)
     }
+
+    /// Resets the prewarm counter and returns its previous value.
+    pub fn reset_prewarmed(&self) -> i64 {
+        self.prewarmed.swap(0, Ordering::Relaxed)
+    }
 }
 
 impl Default for Counters {
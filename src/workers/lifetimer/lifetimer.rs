@@ -6,15 +6,19 @@ use tokio::sync::{mpsc, Mutex, RwLock};
 use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
 
-use crate::config::Config as AppConfig;
+use crate::config::{Config as AppConfig, ConfigTrait};
 use crate::governor::{Config, Transport};
 use crate::model::Entry;
 use crate::rate;
 use crate::workers::RefreshBackend;
 
 use super::counters::Counters;
+use super::prewarm::{Prewarmer, Step, SystemClock};
 use super::telemetry;
 
+/// How often the prewarm provider checks whether its window has opened.
+const PREWARM_WINDOW_POLL: std::time::Duration = std::time::Duration::from_secs(1);
+
 /// Scalable worker group for lifetime management.
 pub struct LifetimeManager {
     shutdown_ctx: CancellationToken,
@@ -230,6 +234,7 @@ impl LifetimeManager {
         }
 
         self.run_exceed_ttl_entries_provider().await;
+        self.run_prewarm_provider().await;
 
         let need_replicas = self.cfg().await.get_replicas();
         if need_replicas > 0 {
@@ -372,6 +377,8 @@ impl LifetimeManager {
                                     }
                                     Err(_) => {
                                         counters.error_updates.fetch_add(1, Ordering::Relaxed);
+                                        // Let the entry be queued again, by a hit or by prewarm.
+                                        entry.clear_refresh_queued();
                                     }
                                 }
                             }
@@ -453,6 +460,72 @@ impl LifetimeManager {
         });
     }
 
+    /// Starts the provider of `lifetime.prewarm`, if configured. Within its window it hands the
+    /// workers hot entries due soon whenever they have nothing else to refresh.
+    async fn run_prewarm_provider(&self) {
+        let Some(prewarm) = self.g_cfg.lifetime().and_then(|l| l.prewarm.clone()) else {
+            return;
+        };
+        let prewarmer = match Prewarmer::new(&prewarm, Arc::new(SystemClock)) {
+            Ok(p) => p,
+            Err(e) => {
+                tracing::error!(name = %self.name, error = %e, "prewarm disabled");
+                return;
+            }
+        };
+
+        let shutdown_token = self.shutdown_ctx.clone();
+        let w_ctx = self.w_ctx.clone();
+        let cfg = self.cfg.clone();
+        let backend = self.backend.clone();
+        let w_tasks_tx = self.w_tasks_tx.clone();
+        let counters = self.counters.clone();
+        let name = self.name.clone();
+
+        let mut join_set = self.w_wg.lock().await;
+        join_set.spawn(async move {
+            let mut limiter = rate::Limiter::new(shutdown_token.clone(), prewarm.rate);
+            let mut was_in_window = false;
+
+            loop {
+                let in_window = prewarmer.in_window();
+                if in_window != was_in_window {
+                    tracing::info!(name = %name, window = %prewarm.window, open = in_window, "prewarm window");
+                    was_in_window = in_window;
+                }
+
+                tokio::select! {
+                    _ = shutdown_token.cancelled() => {
+                        return; // Global cancellation
+                    }
+                    _ = async {
+                        let guard = w_ctx.read().await;
+                        guard.cancelled().await
+                    } => {
+                        return; // Workers reloading
+                    }
+                    _ = tokio::time::sleep(PREWARM_WINDOW_POLL), if !in_window => {}
+                    _ = limiter.take(), if in_window => {
+                        if !cfg.read().await.is_enabled() {
+                            continue;
+                        }
+                        let step = {
+                            let tx_guard = w_tasks_tx.lock().await;
+                            prewarmer.step(backend.as_ref(), &tx_guard)
+                        };
+                        match step {
+                            Step::Prewarmed => {
+                                counters.prewarmed.fetch_add(1, Ordering::Relaxed);
+                            }
+                            Step::Closed => break,
+                            Step::OutsideWindow | Step::Busy | Step::Idle => {}
+                        }
+                    }
+                }
+            }
+        });
+    }
+
     async fn close(&self) {
         self.inited.store(false, Ordering::Relaxed);
         self.shutdown_ctx.cancel();
@@ -515,7 +588,14 @@ impl crate::governor::Service for Arc<LifetimeManager> {
     }
 
     fn cfg(&self) -> Arc<dyn Config> {
-        self.cfg.blocking_read().clone()
+        // Called from handlers on the runtime, where blocking_read panics; writers only hold the
+        // lock to swap the config in.
+        loop {
+            if let Ok(cfg) = self.cfg.try_read() {
+                return cfg.clone();
+            }
+            std::thread::yield_now();
+        }
     }
 
     fn replicas(&self) -> usize {
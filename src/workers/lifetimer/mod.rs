@@ -2,7 +2,11 @@
 
 pub mod counters;
 pub mod lifetimer;
+pub mod prewarm;
 pub mod telemetry;
 
+#[cfg(test)]
+mod prewarm_test;
+
 // Re-export main types
 pub use lifetimer::LifetimeManager;
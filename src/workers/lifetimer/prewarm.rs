@@ -0,0 +1,157 @@
+//! Prewarming: refreshing hot entries ahead of their TTL during a quiet window.
+//
+// Refresh load follows traffic: entries filled at the morning peak fall due at the next one.
+// Within `lifetime.prewarm.window` (local time) the provider takes the most hit entries whose
+// refresh falls due within `ahead` and hands them to the lifetime workers like any other refresh.
+// It only does so while the workers' queue is empty, so due refreshes go first, and the workers'
+// rate limiter keeps refreshes of both kinds within `lifetime.rate`. Entries refreshed since the
+// window opened are skipped: with a TTL shorter than `ahead` an entry would otherwise be due
+// "soon" again right after its refresh.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Timelike;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::config;
+use crate::model::Entry;
+use crate::time;
+use crate::workers::RefreshBackend;
+
+/// How far ahead entries are prewarmed when `ahead` is not set.
+pub const DEFAULT_AHEAD: Duration = Duration::from_secs(6 * 3600);
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const MINUTE_NANOS: i64 = 60_000_000_000;
+
+/// Daily window of local time, in minutes of the day; the end is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    start: u32,
+    end: u32,
+}
+
+impl Window {
+    /// Parses "HH:MM-HH:MM". An end before the start wraps past midnight; "24:00" is the end of
+    /// the day.
+    pub fn parse(s: &str) -> Result<Self> {
+        let (start, end) = s.split_once('-').ok_or_else(|| anyhow!("expected HH:MM-HH:MM"))?;
+        let (start, end) = (parse_minute(start)?, parse_minute(end)?);
+        if start == end {
+            bail!("window is empty");
+        }
+        Ok(Self {
+            start: start % MINUTES_PER_DAY,
+            end: end % MINUTES_PER_DAY,
+        })
+    }
+
+    /// Whether the minute of the day falls within the window.
+    pub fn contains(&self, minute: u32) -> bool {
+        if self.start < self.end {
+            minute >= self.start && minute < self.end
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+
+    /// Minutes since the window last opened, as of the minute of the day.
+    pub fn minutes_open(&self, minute: u32) -> u32 {
+        (minute + MINUTES_PER_DAY - self.start) % MINUTES_PER_DAY
+    }
+}
+
+fn parse_minute(s: &str) -> Result<u32> {
+    let (h, m) = s.trim().split_once(':').ok_or_else(|| anyhow!("expected HH:MM, got {:?}", s))?;
+    let (h, m): (u32, u32) = (h.parse()?, m.parse()?);
+    if m >= 60 || h > 24 || (h == 24 && m > 0) {
+        bail!("{:?} is not a time of day", s);
+    }
+    Ok(h * 60 + m)
+}
+
+/// Source of the local time of day.
+pub trait LocalClock: Send + Sync {
+    /// Minutes since local midnight.
+    fn minute_of_day(&self) -> u32;
+}
+
+/// Local time of the process (`TZ`).
+pub struct SystemClock;
+
+impl LocalClock for SystemClock {
+    fn minute_of_day(&self) -> u32 {
+        let now = chrono::Local::now();
+        now.hour() * 60 + now.minute()
+    }
+}
+
+/// Outcome of one prewarm attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    OutsideWindow,
+    /// The workers have refreshes waiting.
+    Busy,
+    /// No entry falls due within `ahead`.
+    Idle,
+    Prewarmed,
+    Closed,
+}
+
+/// Picks entries to prewarm.
+pub struct Prewarmer {
+    window: Window,
+    ahead: Duration,
+    clock: Arc<dyn LocalClock>,
+}
+
+impl Prewarmer {
+    pub fn new(cfg: &config::Prewarm, clock: Arc<dyn LocalClock>) -> Result<Self> {
+        Ok(Self {
+            window: Window::parse(&cfg.window)?,
+            ahead: cfg.ahead.unwrap_or(DEFAULT_AHEAD),
+            clock,
+        })
+    }
+
+    pub fn in_window(&self) -> bool {
+        self.window.contains(self.clock.minute_of_day())
+    }
+
+    /// Unix nanos at which the window last opened, to the minute.
+    fn opened_at(&self) -> i64 {
+        let minutes = self.window.minutes_open(self.clock.minute_of_day()) as i64;
+        time::unix_nano() - minutes * MINUTE_NANOS
+    }
+
+    /// Hands the next entry to prewarm to the workers, if the window is open and they are idle.
+    /// The entry is marked queued so that it is not picked again before it is refreshed, and is
+    /// not picked again within the window once refreshed.
+    pub fn step(&self, backend: &dyn RefreshBackend, tasks: &mpsc::Sender<Entry>) -> Step {
+        if !self.in_window() {
+            return Step::OutsideWindow;
+        }
+        if tasks.capacity() < tasks.max_capacity() {
+            return Step::Busy;
+        }
+        let Some(entry) = backend.peek_prewarm(self.ahead, self.opened_at()) else {
+            return Step::Idle;
+        };
+        if !entry.try_mark_refresh_queued() {
+            return Step::Idle;
+        }
+        match tasks.try_send(entry) {
+            Ok(()) => Step::Prewarmed,
+            Err(TrySendError::Full(entry)) => {
+                entry.clear_refresh_queued();
+                Step::Busy
+            }
+            Err(TrySendError::Closed(entry)) => {
+                entry.clear_refresh_queued();
+                Step::Closed
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::sync::mpsc;
+
+    use crate::config::{self, Rule};
+    use crate::model::Entry;
+    use crate::time;
+    use crate::workers::lifetimer::prewarm::{LocalClock, Prewarmer, Step, Window};
+    use crate::workers::RefreshBackend;
+
+    /// Clock set by the test, in minutes since midnight.
+    struct FakeClock(AtomicU32);
+
+    impl FakeClock {
+        fn at(hh: u32, mm: u32) -> Arc<Self> {
+            Arc::new(Self(AtomicU32::new(hh * 60 + mm)))
+        }
+
+        fn set(&self, hh: u32, mm: u32) {
+            self.0.store(hh * 60 + mm, Ordering::Relaxed);
+        }
+    }
+
+    impl LocalClock for FakeClock {
+        fn minute_of_day(&self) -> u32 {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    /// Backend offering its entries for prewarm in order, skipping queued and recently refreshed ones.
+    struct Candidates(Vec<Entry>);
+
+    #[async_trait::async_trait]
+    impl RefreshBackend for Candidates {
+        fn len(&self) -> i64 {
+            self.0.len() as i64
+        }
+
+        fn mem(&self) -> i64 {
+            0
+        }
+
+        fn peek_expired_ttl(&self) -> Option<Entry> {
+            None
+        }
+
+        fn peek_prewarm(&self, ahead: Duration, refreshed_before: i64) -> Option<Entry> {
+            assert_eq!(ahead, Duration::from_secs(3600));
+            self.0
+                .iter()
+                .find(|e| e.fresh_at() < refreshed_before && !e.is_refresh_queued())
+                .cloned()
+        }
+
+        async fn on_ttl(&self, _entry: &Entry) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            Ok(())
+        }
+    }
+
+    const DAY_NANOS: i64 = 24 * 3600 * 1_000_000_000;
+
+    /// Entries last refreshed a day ago.
+    fn candidates(n: usize) -> Candidates {
+        let rule = Arc::new(Rule::bare("/api/v1/user"));
+        Candidates(
+            (0..n)
+                .map(|id| {
+                    let e = Entry::new(rule.clone(), &[(b"id".to_vec(), id.to_string().into_bytes())], &[]);
+                    e.set_refreshed_at_for_tests(time::unix_nano() - DAY_NANOS);
+                    e
+                })
+                .collect(),
+        )
+    }
+
+    fn prewarmer(window: &str, clock: Arc<FakeClock>) -> Prewarmer {
+        let cfg = config::Prewarm {
+            window: window.to_string(),
+            rate: 10,
+            ahead: Some(Duration::from_secs(3600)),
+        };
+        Prewarmer::new(&cfg, clock).expect("valid prewarm config")
+    }
+
+    /// Test window parsing and membership, including windows wrapping past midnight.
+    #[test]
+    fn test_window() {
+        let night = Window::parse("03:00-05:00").unwrap();
+        assert!(!night.contains(2 * 60 + 59));
+        assert!(night.contains(3 * 60));
+        assert!(night.contains(4 * 60 + 59));
+        assert!(!night.contains(5 * 60));
+
+        let wrapping = Window::parse("22:30-01:00").unwrap();
+        assert!(wrapping.contains(23 * 60));
+        assert!(wrapping.contains(0));
+        assert!(!wrapping.contains(60));
+        assert!(!wrapping.contains(12 * 60));
+
+        let all_day = Window::parse("00:00-24:00").unwrap();
+        assert!(all_day.contains(0) && all_day.contains(24 * 60 - 1));
+
+        for bad in ["03:00", "3-5", "03:00-03:00", "25:00-01:00", "03:60-04:00", "24:01-01:00", "aa:00-01:00"] {
+            assert!(Window::parse(bad).is_err(), "{:?} must be rejected", bad);
+        }
+    }
+
+    /// Test that entries are handed out only while the window is open and the workers' queue is
+    /// empty, each entry once per window.
+    #[tokio::test]
+    async fn test_step_follows_window_and_queue() {
+        let _time = time::start(Duration::from_millis(1));
+        let clock = FakeClock::at(2, 59);
+        let prewarmer = prewarmer("03:00-05:00", clock.clone());
+        let backend = candidates(2);
+        let (tx, mut rx) = mpsc::channel(4);
+
+        assert_eq!(prewarmer.step(&backend, &tx), Step::OutsideWindow);
+        assert!(rx.try_recv().is_err());
+
+        clock.set(3, 0);
+        assert_eq!(prewarmer.step(&backend, &tx), Step::Prewarmed);
+        assert_eq!(prewarmer.step(&backend, &tx), Step::Busy, "refreshes waiting in the queue go first");
+
+        let first = rx.try_recv().unwrap();
+        assert_eq!(first.key(), backend.0[0].key());
+        assert!(first.is_refresh_queued());
+        assert_eq!(prewarmer.step(&backend, &tx), Step::Prewarmed);
+        assert_eq!(rx.try_recv().unwrap().key(), backend.0[1].key());
+        assert_eq!(prewarmer.step(&backend, &tx), Step::Idle);
+
+        // Refreshed within the window, the entry is not prewarmed again.
+        backend.0[0].touch_refreshed_at();
+        backend.0[0].clear_refresh_queued();
+        clock.set(3, 30);
+        assert_eq!(prewarmer.step(&backend, &tx), Step::Idle);
+
+        clock.set(5, 0);
+        assert_eq!(prewarmer.step(&backend, &tx), Step::OutsideWindow);
+
+        // The next night the window opens again.
+        backend.0[0].set_refreshed_at_for_tests(time::unix_nano() - DAY_NANOS);
+        clock.set(3, 30);
+        assert_eq!(prewarmer.step(&backend, &tx), Step::Prewarmed);
+    }
+
+    /// Test that an entry that cannot be handed out is not left marked as queued.
+    #[tokio::test]
+    async fn test_step_on_closed_queue() {
+        let _time = time::start(Duration::from_millis(1));
+        let prewarmer = prewarmer("00:00-24:00", FakeClock::at(12, 0));
+        let backend = candidates(1);
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+
+        assert_eq!(prewarmer.step(&backend, &tx), Step::Closed);
+        assert!(!backend.0[0].is_refresh_queued());
+    }
+}
@@ -37,7 +37,10 @@ pub async fn logger(
                     stats_snapshot(&name, &w_num_active, is_enabled);
                 let (affected, errors, scans, miss, hits) = counters.reset();
 
+                let prewarmed = counters.reset_prewarmed();
+
                 metrics::add_lifetime_stat_counters(affected, errors, scans, miss, hits);
+                metrics::add_prewarm_stat_counter(prewarmed);
 
                 let on_ttl = if g_cfg.lifetime().map(|l| l.is_remove_on_ttl.load(Ordering::Relaxed)).unwrap_or(false) {
                     "remove"
@@ -54,6 +57,7 @@ pub async fn logger(
                     scans = scans,
                     scans_hit = hits,
                     scans_miss = miss,
+                    prewarmed = prewarmed,
                     name = %name_snapshot,
                     "lifetime manager stats"
                 );